@@ -0,0 +1,84 @@
+//! Benchmarks for the generation pipeline
+//!
+//! Run with `cargo bench --features bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use tempfile::TempDir;
+
+use idp_cli::bench::{synthetic_blueprint, write_synthetic_templates};
+use idp_cli::file_writer::FileWriter;
+use idp_cli::template_discovery::TemplateDiscovery;
+use idp_cli::template_processor::TemplateProcessor;
+use idp_cli::variable_context::VariableContextBuilder;
+
+/// Number of templates and resources in the large workloads
+const SCALE: usize = 1000;
+
+fn bench_context_building(c: &mut Criterion) {
+    let mut group = c.benchmark_group("context");
+    for resources in [10, 100, SCALE] {
+        let blueprint = synthetic_blueprint(resources);
+        group.throughput(Throughput::Elements(resources as u64));
+        group.bench_function(format!("from_blueprint/{}", resources), |b| {
+            b.iter(|| VariableContextBuilder::from_blueprint(&blueprint))
+        });
+    }
+    group.finish();
+}
+
+fn bench_rendering(c: &mut Criterion) {
+    let workspace = TempDir::new().unwrap();
+    write_synthetic_templates(workspace.path(), SCALE, 100).unwrap();
+    let templates = TemplateDiscovery::new(workspace.path().to_path_buf())
+        .discover_templates()
+        .unwrap();
+    let context = VariableContextBuilder::from_blueprint(&synthetic_blueprint(100));
+    let processor = TemplateProcessor::new(&context);
+
+    let mut group = c.benchmark_group("render");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(templates.len() as u64));
+    group.bench_function(format!("files/{}", SCALE), |b| {
+        b.iter(|| {
+            templates
+                .iter()
+                .map(|t| processor.process_file(t).unwrap())
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+fn bench_file_writing(c: &mut Criterion) {
+    let workspace = TempDir::new().unwrap();
+    write_synthetic_templates(workspace.path(), SCALE, 100).unwrap();
+    let templates = TemplateDiscovery::new(workspace.path().to_path_buf())
+        .discover_templates()
+        .unwrap();
+    let context = VariableContextBuilder::from_blueprint(&synthetic_blueprint(100));
+    let processor = TemplateProcessor::new(&context);
+    let processed: Vec<_> = templates
+        .iter()
+        .map(|t| processor.process_file(t).unwrap())
+        .collect();
+
+    let mut group = c.benchmark_group("write");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(processed.len() as u64));
+    group.bench_function(format!("files/{}", SCALE), |b| {
+        b.iter_batched(
+            || TempDir::new().unwrap(),
+            |output| {
+                FileWriter::new(output.path().to_path_buf())
+                    .write_processed_files(&processed)
+                    .unwrap();
+                output
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_context_building, bench_rendering, bench_file_writing);
+criterion_main!(benches);
@@ -6,6 +6,9 @@ echo "Building IDP CLI for AWS Lambda deployment..."
 # Determine build method
 BUILD_METHOD=${1:-"container"}
 
+# Target triple for the cross build method (x86_64 or arm64 Lambda)
+LAMBDA_TARGET=${2:-"x86_64-unknown-linux-musl"}
+
 if [ "$BUILD_METHOD" = "container" ]; then
     echo "Building Lambda container image..."
     
@@ -23,7 +26,16 @@ if [ "$BUILD_METHOD" = "container" ]; then
     echo "     docker push <account-id>.dkr.ecr.<region>.amazonaws.com/idp-cli-lambda:latest"
     
 elif [ "$BUILD_METHOD" = "cross" ]; then
-    echo "Building with cross-compilation for x86_64-unknown-linux-musl..."
+    case "$LAMBDA_TARGET" in
+        x86_64-unknown-linux-musl|aarch64-unknown-linux-musl) ;;
+        *)
+            echo "Error: Unsupported Lambda target '$LAMBDA_TARGET'"
+            echo "Supported targets: x86_64-unknown-linux-musl, aarch64-unknown-linux-musl"
+            exit 1
+            ;;
+    esac
+
+    echo "Building with cross-compilation for $LAMBDA_TARGET..."
     
     # Check if cross is installed
     if ! command -v cross &> /dev/null; then
@@ -33,14 +45,14 @@ elif [ "$BUILD_METHOD" = "cross" ]; then
     fi
     
     # Build with cross for Lambda runtime
-    cross build --release --target x86_64-unknown-linux-musl --bin idp-cli-lambda --features lambda
+    cross build --profile release-lambda --target "$LAMBDA_TARGET" --bin idp-cli-lambda --features lambda
     
     # Create deployment package
     OUTPUT_DIR="target/lambda"
     mkdir -p "$OUTPUT_DIR"
     
     # Copy binary and rename to bootstrap (required by Lambda)
-    cp target/x86_64-unknown-linux-musl/release/idp-cli-lambda "$OUTPUT_DIR/bootstrap"
+    cp "target/$LAMBDA_TARGET/release-lambda/idp-cli-lambda" "$OUTPUT_DIR/bootstrap"
     
     # Create ZIP package
     cd "$OUTPUT_DIR"
@@ -49,6 +61,10 @@ elif [ "$BUILD_METHOD" = "cross" ]; then
     
     echo "✓ Lambda deployment package created: target/lambda/idp-cli-lambda.zip"
     echo ""
+    if [ "$LAMBDA_TARGET" = "aarch64-unknown-linux-musl" ]; then
+        echo "Note: add '--architectures arm64' when creating the function."
+        echo ""
+    fi
     echo "To deploy to Lambda:"
     echo "  aws lambda create-function \\"
     echo "    --function-name idp-cli \\"
@@ -85,12 +101,16 @@ elif [ "$BUILD_METHOD" = "native" ]; then
     
 else
     echo "Error: Unknown build method '$BUILD_METHOD'"
-    echo "Usage: $0 [container|cross|native]"
+    echo "Usage: $0 [container|cross|native] [lambda-target]"
     echo ""
     echo "Build methods:"
     echo "  container - Build Docker container image (recommended)"
-    echo "  cross     - Cross-compile using 'cross' tool"
+    echo "  cross     - Cross-compile using 'cross' tool (static musl binary)"
     echo "  native    - Native build (may not work on Lambda)"
+    echo ""
+    echo "Lambda targets (cross only):"
+    echo "  x86_64-unknown-linux-musl  (default)"
+    echo "  aarch64-unknown-linux-musl (Graviton / arm64)"
     exit 1
 fi
 
@@ -0,0 +1,27 @@
+//! Build script recording the compilation target so `idp-cli --target-info`
+//! can report which platform a binary was built for, and generating the gRPC
+//! service code when the `grpc` feature is enabled.
+
+fn main() {
+    let target = std::env::var("TARGET").unwrap_or_else(|_| "unknown".to_string());
+    let profile = std::env::var("PROFILE").unwrap_or_else(|_| "unknown".to_string());
+
+    println!("cargo:rustc-env=IDP_CLI_BUILD_TARGET={}", target);
+    println!("cargo:rustc-env=IDP_CLI_BUILD_PROFILE={}", profile);
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("vendored protoc is available");
+    std::env::set_var("PROTOC", protoc);
+
+    println!("cargo:rerun-if-changed=proto/idp_cli.proto");
+    tonic_build::configure()
+        .build_client(true)
+        .compile_protos(&["proto/idp_cli.proto"], &["proto"])
+        .expect("failed to compile proto/idp_cli.proto");
+}
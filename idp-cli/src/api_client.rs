@@ -1,11 +1,116 @@
+use crate::circuit_breaker::CircuitBreaker;
 use crate::error::CliError;
-use crate::models::{Blueprint, Stack};
-use reqwest::{Client, StatusCode};
+use crate::models::{Blueprint, Stack, StackEvent, StackResourceState, StackStatus};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
 use uuid::Uuid;
 
+/// Number of items requested per page when listing blueprints or stacks
+pub const LIST_PAGE_SIZE: usize = 100;
+
+/// Most pages fetched for one listing, in case the API ignores `page`
+pub const MAX_LIST_PAGES: usize = 1000;
+
+/// Largest response body read from the API unless configured otherwise (64 MiB)
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Longest part of an error response body included in an error message
+const MAX_ERROR_TEXT_CHARS: usize = 1000;
+
+/// Filter and limit for listing blueprints or stacks
+#[derive(Debug, Clone, Default)]
+pub struct ListQuery {
+    /// Case-insensitive text matched against names, descriptions and (for
+    /// stacks) stack type and cloud name
+    pub filter: Option<String>,
+    /// Stop after this many matching items
+    pub limit: Option<usize>,
+}
+
+impl ListQuery {
+    /// Check whether any of the fields contains the filter text
+    pub fn matches(&self, fields: &[&str]) -> bool {
+        match &self.filter {
+            Some(filter) => {
+                let needle = filter.to_lowercase();
+                fields.iter().any(|field| field.to_lowercase().contains(&needle))
+            }
+            None => true,
+        }
+    }
+}
+
+/// User agent sent to the API: `idp-cli/<version>`, followed by the caller if given
+pub fn user_agent(caller: Option<&str>) -> String {
+    let product = format!("idp-cli/{}", env!("CARGO_PKG_VERSION"));
+    match caller.map(str::trim).filter(|caller| !caller.is_empty()) {
+        Some(caller) => format!("{} {}", product, caller),
+        None => product,
+    }
+}
+
+/// Parse a `--header NAME=VALUE` argument
+pub fn parse_header(value: &str) -> Result<(String, String), String> {
+    let (name, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", value))?;
+    let name = name.trim();
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("'{}' is not a valid header name", name))?;
+    HeaderValue::from_str(value).map_err(|_| format!("the value of header '{}' is not valid", name))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// List response: either a plain array or a page of results
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ListPage<T> {
+    Items(Vec<T>),
+    Paged {
+        content: Vec<T>,
+        #[serde(default)]
+        last: bool,
+    },
+}
+
+/// Add a `revision` query parameter to a blueprint or stack URL
+fn with_revision(url: String, revision: Option<&str>) -> String {
+    match revision {
+        Some(revision) => {
+            let separator = if url.contains('?') { '&' } else { '?' };
+            format!("{}{}revision={}", url, separator, revision)
+        }
+        None => url,
+    }
+}
+
+/// Make sure the API honoured a pinned revision
+///
+/// Only numbered revisions can be checked; named ones such as `draft` and
+/// responses without a revision are accepted.
+fn check_revision(identifier: &str, requested: Option<&str>, returned: Option<u64>) -> Result<(), CliError> {
+    match (requested.and_then(|r| r.parse::<u64>().ok()), returned) {
+        (Some(requested), Some(returned)) if requested != returned => Err(CliError::ApiError(format!(
+            "Requested revision {} of '{}' but the API returned revision {}; the API may not support revision pinning",
+            requested, identifier, returned
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Clone)]
 pub struct ApiClient {
     base_url: String,
     api_key: String,
+    user_agent: String,
+    /// Extra headers sent with every request, e.g. for routing by an API gateway
+    headers: HeaderMap,
+    /// Responses with a larger body are rejected instead of being buffered
+    max_response_bytes: usize,
+    /// Shared by clones, so all requests from a server process count together
+    breaker: Arc<CircuitBreaker>,
     client: Client,
 }
 
@@ -20,36 +125,209 @@ impl ApiClient {
         Self {
             base_url,
             api_key,
+            user_agent: user_agent(None),
+            headers: HeaderMap::new(),
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            breaker: Arc::new(CircuitBreaker::default()),
             client,
         }
     }
 
+    /// Identify the caller in the user agent, e.g. `payments-ci/1.0`
+    pub fn with_user_agent(mut self, caller: &str) -> Self {
+        self.user_agent = user_agent(Some(caller));
+        self
+    }
+
+    /// Reject response bodies larger than this many bytes
+    ///
+    /// Stacks with thousands of resources produce multi-MB payloads; the
+    /// limit keeps a runaway response from exhausting memory.
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = max_response_bytes;
+        self
+    }
+
+    /// Refuse requests for `cool_down` after `threshold` consecutive failures; 0 never refuses
+    pub fn with_circuit_breaker(mut self, threshold: u32, cool_down: Duration) -> Self {
+        self.breaker = Arc::new(CircuitBreaker::new(threshold, cool_down));
+        self
+    }
+
+    /// Send these headers with every request, after the standard ones
+    ///
+    /// The Authorization header can't be replaced; it always carries the API key.
+    pub fn with_headers(mut self, headers: &[(String, String)]) -> Result<Self, CliError> {
+        for (name, value) in headers {
+            let header = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| CliError::ConfigurationError(format!("'{}' is not a valid header name", name)))?;
+            if header == AUTHORIZATION {
+                return Err(CliError::ConfigurationError(
+                    "The Authorization header is set from the API key and can't be overridden".to_string(),
+                ));
+            }
+            let value = HeaderValue::from_str(value).map_err(|_| {
+                CliError::ConfigurationError(format!("The value of header '{}' is not valid", name))
+            })?;
+            self.headers.insert(header, value);
+        }
+        Ok(self)
+    }
+
     pub async fn get_blueprint(&self, identifier: &str) -> Result<Blueprint, CliError> {
-        let url = self.build_blueprint_url(identifier);
+        self.get_blueprint_at(identifier, None).await
+    }
+
+    /// Fetch a specific revision of a blueprint, or the latest if `revision` is None
+    ///
+    /// `revision` is a revision number or a name the API understands, such as
+    /// `draft`. Fails if the API answers with a different numbered revision,
+    /// i.e. it does not support pinning.
+    pub async fn get_blueprint_at(&self, identifier: &str, revision: Option<&str>) -> Result<Blueprint, CliError> {
+        let url = with_revision(self.build_blueprint_url(identifier), revision);
         
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .send()
-            .await?;
+        let response = self.send(self.get(&url)).await?;
 
-        self.handle_response(response, identifier).await
+        let blueprint: Blueprint = self.handle_response(response, identifier).await?;
+        check_revision(identifier, revision, blueprint.revision)?;
+        Ok(blueprint)
+    }
+
+    /// Fetch several blueprints concurrently, each at its revision if set
+    ///
+    /// Blueprints are returned in the order requested. The first request to
+    /// fail fails the whole fetch.
+    pub async fn get_blueprints_at(&self, requests: &[(&str, Option<&str>)]) -> Result<Vec<Blueprint>, CliError> {
+        futures::future::try_join_all(
+            requests.iter().map(|(identifier, revision)| self.get_blueprint_at(identifier, *revision)),
+        )
+        .await
     }
 
     pub async fn get_stack(&self, identifier: &str) -> Result<Stack, CliError> {
-        let url = self.build_stack_url(identifier);
+        self.get_stack_at(identifier, None).await
+    }
+
+    /// Fetch a specific revision of a stack, or the latest if `revision` is None
+    ///
+    /// See [`ApiClient::get_blueprint_at`] for the accepted revisions.
+    pub async fn get_stack_at(&self, identifier: &str, revision: Option<&str>) -> Result<Stack, CliError> {
+        let url = with_revision(self.build_stack_url(identifier), revision);
         
-        let response = self
-            .client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .header("Content-Type", "application/json")
-            .send()
-            .await?;
+        let response = self.send(self.get(&url)).await?;
 
-        self.handle_response(response, identifier).await
+        let stack: Stack = self.handle_response(response, identifier).await?;
+        check_revision(identifier, revision, stack.revision)?;
+        Ok(stack)
+    }
+
+    /// Fetch a stack together with its deployment state
+    ///
+    /// Both requests run concurrently when `identifier` is an ID. A name is
+    /// resolved by fetching the stack first, so its status follows.
+    pub async fn get_stack_with_status(
+        &self,
+        identifier: &str,
+        revision: Option<&str>,
+    ) -> Result<(Stack, StackStatus), CliError> {
+        if Uuid::parse_str(identifier).is_ok() {
+            return tokio::try_join!(self.get_stack_at(identifier, revision), self.get_stack_status(identifier));
+        }
+        let stack = self.get_stack_at(identifier, revision).await?;
+        let status = self.get_stack_status(&stack.id.to_string()).await?;
+        Ok((stack, status))
+    }
+
+    /// Fetch the deployment state of a stack
+    pub async fn get_stack_status(&self, identifier: &str) -> Result<StackStatus, CliError> {
+        let url = format!("{}/status", self.stack_subresource_url(identifier).await?);
+        self.get_json(&url, identifier).await
+    }
+
+    /// Fetch the deployment state of each resource of a stack
+    pub async fn get_stack_resource_states(&self, identifier: &str) -> Result<Vec<StackResourceState>, CliError> {
+        let url = format!("{}/resources", self.stack_subresource_url(identifier).await?);
+        self.get_json(&url, identifier).await
+    }
+
+    /// Fetch a stack's event log, newest first, up to `limit` events if set
+    pub async fn get_stack_events(&self, identifier: &str, limit: Option<usize>) -> Result<Vec<StackEvent>, CliError> {
+        let mut url = format!("{}/events", self.stack_subresource_url(identifier).await?);
+        if let Some(limit) = limit {
+            url.push_str(&format!("?limit={}", limit));
+        }
+        self.get_json(&url, identifier).await
+    }
+
+    /// List blueprints visible to the API key, following pagination
+    ///
+    /// Pages are fetched until the API reports the last page, returns a page
+    /// shorter than [`LIST_PAGE_SIZE`], or `query.limit` matching blueprints
+    /// have been collected. Fails after [`MAX_LIST_PAGES`] full pages.
+    pub async fn list_blueprints(&self, query: &ListQuery) -> Result<Vec<Blueprint>, CliError> {
+        self.list_paged("blueprints", query, |blueprint: &Blueprint| {
+            query.matches(&[&blueprint.name, blueprint.description.as_deref().unwrap_or_default()])
+        })
+        .await
+    }
+
+    /// List stacks visible to the API key, following pagination
+    ///
+    /// Pages are fetched until the API reports the last page, returns a page
+    /// shorter than [`LIST_PAGE_SIZE`], or `query.limit` matching stacks have
+    /// been collected. Fails after [`MAX_LIST_PAGES`] full pages.
+    pub async fn list_stacks(&self, query: &ListQuery) -> Result<Vec<Stack>, CliError> {
+        self.list_paged("stacks", query, |stack: &Stack| {
+            query.matches(&[
+                &stack.name,
+                stack.description.as_deref().unwrap_or_default(),
+                &stack.stack_type,
+                &stack.cloud_name,
+            ])
+        })
+        .await
+    }
+
+    async fn list_paged<T: serde::de::DeserializeOwned>(
+        &self,
+        collection: &str,
+        query: &ListQuery,
+        keep: impl Fn(&T) -> bool,
+    ) -> Result<Vec<T>, CliError> {
+        let mut items = Vec::new();
+        let mut page = 0;
+        loop {
+            let url = format!("{}/{}?page={}&size={}", self.base_url, collection, page, LIST_PAGE_SIZE);
+
+            let response = self.send(self.get(&url)).await?;
+
+            let (batch, last) = match self.handle_response::<ListPage<T>>(response, collection).await? {
+                // A plain array means the API returned everything at once
+                ListPage::Items(batch) => (batch, true),
+                ListPage::Paged { content, last } => {
+                    // A short page is the last, also from APIs that ignore `page` and never set `last`
+                    let last = last || content.len() < LIST_PAGE_SIZE;
+                    (content, last)
+                }
+            };
+
+            for item in batch.into_iter().filter(|item| keep(item)) {
+                if query.limit.is_some_and(|limit| items.len() >= limit) {
+                    return Ok(items);
+                }
+                items.push(item);
+            }
+            if last || query.limit.is_some_and(|limit| items.len() >= limit) {
+                return Ok(items);
+            }
+            page += 1;
+            if page >= MAX_LIST_PAGES {
+                return Err(CliError::ApiError(format!(
+                    "Listing {} returned a full page for each of {} pages; the API may be ignoring the page parameter",
+                    collection, MAX_LIST_PAGES
+                )));
+            }
+        }
     }
 
     fn build_blueprint_url(&self, identifier: &str) -> String {
@@ -70,6 +348,105 @@ impl ApiClient {
         }
     }
 
+    /// `/stacks/{id}` for a stack UUID, or for the ID of the stack with that name
+    ///
+    /// Status, resource and event endpoints are only addressed by ID, so a
+    /// name costs an extra request.
+    async fn stack_subresource_url(&self, identifier: &str) -> Result<String, CliError> {
+        let id = match Uuid::parse_str(identifier) {
+            Ok(id) => id,
+            Err(_) => self.get_stack(identifier).await?.id,
+        };
+        Ok(format!("{}/stacks/{}", self.base_url, id))
+    }
+
+    /// GET request with the API key, user agent and extra headers
+    fn get(&self, url: &str) -> RequestBuilder {
+        self.client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Content-Type", "application/json")
+            .header("User-Agent", &self.user_agent)
+            .headers(self.headers.clone())
+    }
+
+    /// Send a request unless the circuit breaker is open
+    ///
+    /// Connection errors, timeouts and 5xx responses count as failures; any
+    /// other response closes the circuit.
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, CliError> {
+        self.breaker.check().map_err(|open| {
+            CliError::ApiUnavailableError(format!(
+                "{} consecutive requests to {} failed, the last with: {}. Requests are paused for {}s",
+                open.failures,
+                self.base_url,
+                open.last_error,
+                open.retry_after.as_secs().max(1)
+            ))
+        })?;
+        match request.send().await {
+            Ok(response) if response.status().is_server_error() => {
+                self.breaker.record_failure(&format!("HTTP {}", response.status()));
+                Ok(response)
+            }
+            Ok(response) => {
+                self.breaker.record_success();
+                Ok(response)
+            }
+            Err(e) => {
+                self.breaker.record_failure(&e.to_string());
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, url: &str, identifier: &str) -> Result<T, CliError> {
+        let response = self.send(self.get(url)).await?;
+        self.handle_response(response, identifier).await
+    }
+
+    /// Read a response body chunk by chunk, up to `max_response_bytes`
+    ///
+    /// A declared Content-Length over the limit is rejected before anything is
+    /// read; otherwise the body is read until it goes over the limit.
+    async fn read_body(&self, mut response: reqwest::Response) -> Result<Vec<u8>, CliError> {
+        let too_large = |url: &reqwest::Url| {
+            CliError::ResponseTooLargeError(format!(
+                "The response from {} is larger than {} bytes",
+                url, self.max_response_bytes
+            ))
+        };
+        let declared = response.content_length().unwrap_or(0);
+        if declared > self.max_response_bytes as u64 {
+            return Err(too_large(response.url()));
+        }
+        let mut body = Vec::with_capacity(declared as usize);
+        while let Some(chunk) = response.chunk().await? {
+            if body.len() + chunk.len() > self.max_response_bytes {
+                return Err(too_large(response.url()));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(body)
+    }
+
+    /// Read an error response body for an error message
+    ///
+    /// The body is read through [`Self::read_body`], so it is bounded the same
+    /// way, and cut to `MAX_ERROR_TEXT_CHARS`; `fallback` is used when it
+    /// can't be read or is over the limit.
+    async fn error_text(&self, response: reqwest::Response, fallback: &str) -> String {
+        let body = match self.read_body(response).await {
+            Ok(body) => body,
+            Err(_) => return fallback.to_string(),
+        };
+        let text = String::from_utf8_lossy(&body);
+        match text.char_indices().nth(MAX_ERROR_TEXT_CHARS) {
+            Some((end, _)) => format!("{}...", &text[..end]),
+            None => text.into_owned(),
+        }
+    }
+
     async fn handle_response<T: serde::de::DeserializeOwned>(
         &self,
         response: reqwest::Response,
@@ -77,10 +454,10 @@ impl ApiClient {
     ) -> Result<T, CliError> {
         match response.status() {
             StatusCode::OK => {
-                let body = response.json::<T>().await.map_err(|e| {
+                let body = self.read_body(response).await?;
+                serde_json::from_slice::<T>(&body).map_err(|e| {
                     CliError::ApiError(format!("Failed to parse response: {}", e))
-                })?;
-                Ok(body)
+                })
             }
             StatusCode::UNAUTHORIZED => {
                 Err(CliError::AuthenticationError(
@@ -91,20 +468,14 @@ impl ApiClient {
                 Err(CliError::NotFoundError(identifier.to_string()))
             }
             StatusCode::INTERNAL_SERVER_ERROR => {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown server error".to_string());
+                let error_text = self.error_text(response, "Unknown server error").await;
                 Err(CliError::ApiError(format!(
                     "Server error (500): {}",
                     error_text
                 )))
             }
             status => {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
+                let error_text = self.error_text(response, "Unknown error").await;
                 Err(CliError::ApiError(format!(
                     "Unexpected status code {}: {}",
                     status, error_text
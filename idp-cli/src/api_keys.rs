@@ -0,0 +1,274 @@
+use std::fmt;
+use std::path::Path;
+
+use lambda_http::http;
+use serde::Deserialize;
+
+use crate::debug_bundle::REDACTED;
+use crate::error::CliError;
+
+/// What an API key is allowed to do in server mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Read-only operations such as listing variables or validating templates
+    Read,
+    /// Rendering templates
+    Generate,
+    /// Uploading generated files to shared storage
+    Push,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Scope::Read => "read",
+            Scope::Generate => "generate",
+            Scope::Push => "push",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A named API key and its scopes
+#[derive(Clone, Deserialize)]
+pub struct ApiKey {
+    /// Name recorded as the caller in logs and audit records
+    pub name: String,
+    key: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("name", &self.name)
+            .field("key", &REDACTED)
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+impl ApiKey {
+    pub fn new(name: &str, key: &str, scopes: Vec<Scope>) -> Self {
+        Self {
+            name: name.to_string(),
+            key: key.to_string(),
+            scopes,
+        }
+    }
+
+    /// Check that the key has every required scope
+    pub fn require(&self, required: &[Scope]) -> Result<(), AuthRejection> {
+        match required.iter().find(|scope| !self.scopes.contains(scope)) {
+            Some(missing) => Err(AuthRejection::Forbidden {
+                name: self.name.clone(),
+                missing: *missing,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Keys file or secret contents
+#[derive(Debug, Deserialize)]
+struct KeysDocument {
+    keys: Vec<ApiKey>,
+}
+
+/// Why a request was not authorized
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthRejection {
+    /// No API key was presented
+    Missing,
+    /// The presented key is not configured
+    Invalid,
+    /// The key is valid but lacks a scope the operation needs
+    Forbidden { name: String, missing: Scope },
+}
+
+impl AuthRejection {
+    /// HTTP status code for the rejection
+    pub fn status(&self) -> u16 {
+        match self {
+            AuthRejection::Missing | AuthRejection::Invalid => 401,
+            AuthRejection::Forbidden { .. } => 403,
+        }
+    }
+
+    /// Human-readable explanation
+    pub fn message(&self) -> String {
+        match self {
+            AuthRejection::Missing => {
+                "API key required (Authorization: Bearer <key> or X-Api-Key header)".to_string()
+            }
+            AuthRejection::Invalid => "Invalid API key".to_string(),
+            AuthRejection::Forbidden { name, missing } => {
+                format!("API key '{}' lacks the '{}' scope", name, missing)
+            }
+        }
+    }
+}
+
+/// API keys accepted in server mode
+///
+/// Loaded from a JSON or YAML document of the form
+/// `{"keys": [{"name": "ci", "key": "...", "scopes": ["read", "generate"]}]}`.
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    keys: Vec<ApiKey>,
+}
+
+impl KeyStore {
+    pub fn new(keys: Vec<ApiKey>) -> Result<Self, CliError> {
+        for (i, key) in keys.iter().enumerate() {
+            if key.key.trim().is_empty() {
+                return Err(CliError::ConfigError(format!("API key '{}' is empty", key.name)));
+            }
+            if let Some(other) = keys[..i].iter().find(|other| other.key == key.key) {
+                return Err(CliError::ConfigError(format!(
+                    "API key '{}' duplicates the key of '{}'",
+                    key.name, other.name
+                )));
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    /// Parse a keys document (YAML, which includes JSON)
+    pub fn parse(content: &str) -> Result<Self, CliError> {
+        let document: KeysDocument = serde_yaml::from_str(content)?;
+        Self::new(document.keys)
+    }
+
+    /// Load keys from a JSON or YAML file
+    pub fn from_file(path: &Path) -> Result<Self, CliError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            CliError::ConfigError(format!("Failed to read API keys file {}: {}", path.display(), e))
+        })?;
+        Self::parse(&content)
+    }
+
+    /// Load keys from an AWS Secrets Manager secret string
+    pub async fn from_secret(secret_id: &str) -> Result<Self, CliError> {
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let client = aws_sdk_secretsmanager::Client::new(&config);
+        let secret = client
+            .get_secret_value()
+            .secret_id(secret_id)
+            .send()
+            .await
+            .map_err(|e| CliError::ConfigError(format!("Failed to read API keys secret {}: {}", secret_id, e)))?;
+        let content = secret.secret_string().ok_or_else(|| {
+            CliError::ConfigError(format!("API keys secret {} has no string value", secret_id))
+        })?;
+        Self::parse(content)
+    }
+
+    /// Load keys from `API_KEYS_FILE` or the `API_KEYS_SECRET` secret
+    ///
+    /// Returns `None` when neither is set, leaving the server unauthenticated.
+    pub async fn from_env() -> Result<Option<Self>, CliError> {
+        if let Ok(path) = std::env::var("API_KEYS_FILE") {
+            return Self::from_file(Path::new(&path)).map(Some);
+        }
+        if let Ok(secret_id) = std::env::var("API_KEYS_SECRET") {
+            return Self::from_secret(&secret_id).await.map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Find the key matching the presented one
+    pub fn authenticate(&self, presented: Option<&str>) -> Result<&ApiKey, AuthRejection> {
+        let presented = presented.ok_or(AuthRejection::Missing)?;
+        // Check every key so timing does not reveal which one matched
+        self.keys
+            .iter()
+            .fold(None, |found, key| {
+                if constant_time_eq(key.key.as_bytes(), presented.as_bytes()) {
+                    Some(key)
+                } else {
+                    found
+                }
+            })
+            .ok_or(AuthRejection::Invalid)
+    }
+
+    /// Authenticate the presented key and check it has every required scope
+    pub fn authorize(&self, presented: Option<&str>, required: &[Scope]) -> Result<&ApiKey, AuthRejection> {
+        let key = self.authenticate(presented)?;
+        key.require(required)?;
+        Ok(key)
+    }
+}
+
+/// Key presented in `Authorization: Bearer <key>` or `X-Api-Key`
+pub fn presented_key(headers: &http::HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    bearer
+        .or_else(|| headers.get("x-api-key").and_then(|value| value.to_str().ok()))
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYS: &str = r#"
+keys:
+  - name: ci
+    key: ci-secret
+    scopes: [read, generate]
+  - name: viewer
+    key: viewer-secret
+    scopes: [read]
+"#;
+
+    #[test]
+    fn test_authorize_checks_key_and_scopes() {
+        let store = KeyStore::parse(KEYS).unwrap();
+
+        assert_eq!(store.authorize(Some("ci-secret"), &[Scope::Generate]).unwrap().name, "ci");
+        assert_eq!(store.authorize(None, &[Scope::Read]).unwrap_err(), AuthRejection::Missing);
+        assert_eq!(store.authorize(Some("nope"), &[Scope::Read]).unwrap_err(), AuthRejection::Invalid);
+
+        let rejection = store.authorize(Some("viewer-secret"), &[Scope::Generate]).unwrap_err();
+        assert_eq!(rejection.status(), 403);
+        assert_eq!(rejection.message(), "API key 'viewer' lacks the 'generate' scope");
+        assert!(store.authorize(Some("ci-secret"), &[Scope::Generate, Scope::Push]).is_err());
+    }
+
+    #[test]
+    fn test_parse_rejects_duplicate_and_unknown_scopes() {
+        let json = r#"{"keys": [{"name": "a", "key": "k", "scopes": ["read"]},
+                                {"name": "b", "key": "k", "scopes": ["read"]}]}"#;
+        assert!(KeyStore::parse(json).unwrap_err().to_string().contains("duplicates"));
+        assert!(KeyStore::parse(r#"{"keys": [{"name": "a", "key": "k", "scopes": ["admin"]}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_debug_output_hides_keys() {
+        let store = KeyStore::parse(KEYS).unwrap();
+        let debug = format!("{:?}", store);
+        assert!(debug.contains("\"ci\"") && debug.contains(REDACTED));
+        assert!(!debug.contains("ci-secret") && !debug.contains("viewer-secret"));
+    }
+
+    #[test]
+    fn test_presented_key_headers() {
+        let mut headers = http::HeaderMap::new();
+        assert_eq!(presented_key(&headers), None);
+        headers.insert("x-api-key", "abc".parse().unwrap());
+        assert_eq!(presented_key(&headers), Some("abc"));
+        headers.insert(http::header::AUTHORIZATION, "Bearer xyz".parse().unwrap());
+        assert_eq!(presented_key(&headers), Some("xyz"));
+    }
+}
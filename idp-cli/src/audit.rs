@@ -0,0 +1,315 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use log::{error, warn};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::CliError;
+
+/// Default number of days audit records are kept
+pub const DEFAULT_RETENTION_DAYS: u64 = 365;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Outcome of an audited request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditStatus {
+    Success,
+    Failure,
+    /// Refused before running, e.g. by a rate or concurrency limit
+    Rejected,
+}
+
+/// One audited generation request
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub request_id: String,
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Who made the request (client address or API key name)
+    pub caller: String,
+    pub operation: String,
+    pub data_source: String,
+    pub identifier: String,
+    /// Where templates came from (S3 prefix or directory)
+    pub template_source: String,
+    /// SHA-256 of the template paths and contents, once discovered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_digest: Option<String>,
+    pub status: AuditStatus,
+    pub files_generated: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// Start a record for a request; the status defaults to failure until set
+    pub fn new(caller: &str, operation: &str, data_source: &str, identifier: &str) -> Self {
+        Self {
+            request_id: Uuid::new_v4().to_string(),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            caller: caller.to_string(),
+            operation: operation.to_string(),
+            data_source: data_source.to_string(),
+            identifier: identifier.to_string(),
+            template_source: String::new(),
+            template_digest: None,
+            status: AuditStatus::Failure,
+            files_generated: 0,
+            error: None,
+        }
+    }
+}
+
+/// Where audit records are written
+pub enum AuditSink {
+    /// Auditing disabled
+    Disabled,
+    /// One JSON line per record on stdout, collected by CloudWatch Logs in Lambda
+    CloudWatch,
+    /// Daily `audit-YYYY-MM-DD.jsonl` files in a directory, opened append-only
+    File { dir: PathBuf },
+    /// One item per record in a DynamoDB table, with an `expires_at` TTL attribute
+    DynamoDb {
+        client: aws_sdk_dynamodb::Client,
+        table: String,
+    },
+}
+
+/// Append-only audit log with a retention period
+///
+/// Retention is enforced per sink: old daily files are deleted, DynamoDB items
+/// carry an `expires_at` TTL, and CloudWatch uses the log group's retention.
+pub struct AuditLog {
+    sink: AuditSink,
+    retention_days: u64,
+}
+
+impl AuditLog {
+    pub fn new(sink: AuditSink, retention_days: u64) -> Self {
+        Self { sink, retention_days }
+    }
+
+    /// Audit log that records nothing
+    pub fn disabled() -> Self {
+        Self::new(AuditSink::Disabled, DEFAULT_RETENTION_DAYS)
+    }
+
+    /// Configure the audit log from environment variables
+    ///
+    /// - `AUDIT_SINK`: `cloudwatch` (default), `file`, `dynamodb` or `none`
+    /// - `AUDIT_FILE_DIR`: directory for the file sink (default: `./audit`)
+    /// - `AUDIT_TABLE`: table for the DynamoDB sink (required)
+    /// - `AUDIT_RETENTION_DAYS`: how long records are kept (default: 365)
+    pub async fn from_env() -> Result<Self, CliError> {
+        let retention_days = match std::env::var("AUDIT_RETENTION_DAYS") {
+            Ok(value) => value.trim().parse().map_err(|_| {
+                CliError::ConfigError(format!("AUDIT_RETENTION_DAYS must be a number of days, got: {}", value))
+            })?,
+            Err(_) => DEFAULT_RETENTION_DAYS,
+        };
+
+        let sink_name = std::env::var("AUDIT_SINK").unwrap_or_else(|_| "cloudwatch".to_string());
+        let sink = match sink_name.to_lowercase().as_str() {
+            "none" => AuditSink::Disabled,
+            "cloudwatch" => AuditSink::CloudWatch,
+            "file" => AuditSink::File {
+                dir: std::env::var("AUDIT_FILE_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| PathBuf::from("audit")),
+            },
+            "dynamodb" => {
+                let table = std::env::var("AUDIT_TABLE").map_err(|_| {
+                    CliError::ConfigError("AUDIT_TABLE is required when AUDIT_SINK=dynamodb".to_string())
+                })?;
+                let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                AuditSink::DynamoDb {
+                    client: aws_sdk_dynamodb::Client::new(&config),
+                    table,
+                }
+            }
+            other => {
+                return Err(CliError::ConfigError(format!(
+                    "Unknown AUDIT_SINK '{}'. Use cloudwatch, file, dynamodb or none",
+                    other
+                )))
+            }
+        };
+
+        Ok(Self::new(sink, retention_days))
+    }
+
+    /// Write a record to the sink
+    pub async fn record(&self, record: &AuditRecord) -> Result<(), CliError> {
+        match &self.sink {
+            AuditSink::Disabled => Ok(()),
+            AuditSink::CloudWatch => {
+                let line = serde_json::json!({ "audit": record });
+                println!("{}", line);
+                Ok(())
+            }
+            AuditSink::File { dir } => self.append_to_file(dir, record),
+            AuditSink::DynamoDb { client, table } => self.put_item(client, table, record).await,
+        }
+    }
+
+    /// Write a record, logging instead of failing when the sink is unavailable
+    pub async fn record_or_log(&self, record: &AuditRecord) {
+        if let Err(e) = self.record(record).await {
+            error!("Failed to write audit record {}: {}", record.request_id, e);
+        }
+    }
+
+    fn append_to_file(&self, dir: &Path, record: &AuditRecord) -> Result<(), CliError> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            CliError::IoError(format!("Failed to create audit directory {}: {}", dir.display(), e))
+        })?;
+
+        let day = record.timestamp / SECONDS_PER_DAY;
+        let path = dir.join(audit_file_name(day));
+        let is_new_file = !path.exists();
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| CliError::IoError(format!("Failed to open audit file {}: {}", path.display(), e)))?;
+        writeln!(file, "{}", serde_json::to_string(record)?)
+            .map_err(|e| CliError::IoError(format!("Failed to write audit file {}: {}", path.display(), e)))?;
+
+        // Expired files are removed once per day, when the day's file is created
+        if is_new_file {
+            prune_audit_files(dir, day.saturating_sub(self.retention_days));
+        }
+        Ok(())
+    }
+
+    async fn put_item(
+        &self,
+        client: &aws_sdk_dynamodb::Client,
+        table: &str,
+        record: &AuditRecord,
+    ) -> Result<(), CliError> {
+        let expires_at = record.timestamp + self.retention_days * SECONDS_PER_DAY;
+        let mut request = client
+            .put_item()
+            .table_name(table)
+            .item("request_id", AttributeValue::S(record.request_id.clone()))
+            .item("timestamp", AttributeValue::N(record.timestamp.to_string()))
+            .item("expires_at", AttributeValue::N(expires_at.to_string()))
+            .item("caller", AttributeValue::S(record.caller.clone()))
+            .item("operation", AttributeValue::S(record.operation.clone()))
+            .item("data_source", AttributeValue::S(record.data_source.clone()))
+            .item("identifier", AttributeValue::S(record.identifier.clone()))
+            .item("template_source", AttributeValue::S(record.template_source.clone()))
+            .item("status", AttributeValue::S(serde_json::to_value(record.status)?.as_str().unwrap_or_default().to_string()))
+            .item("files_generated", AttributeValue::N(record.files_generated.to_string()))
+            // Append-only: never overwrite an existing record
+            .condition_expression("attribute_not_exists(request_id)");
+        if let Some(digest) = &record.template_digest {
+            request = request.item("template_digest", AttributeValue::S(digest.clone()));
+        }
+        if let Some(error) = &record.error {
+            request = request.item("error", AttributeValue::S(error.clone()));
+        }
+
+        request
+            .send()
+            .await
+            .map(|_| ())
+            .map_err(|e| CliError::ApiError(format!("Failed to write audit record to {}: {}", table, e)))
+    }
+}
+
+/// Name of the audit file for a day (days since the Unix epoch)
+fn audit_file_name(day: u64) -> String {
+    let (year, month, date) = civil_from_days(day);
+    format!("audit-{:04}-{:02}-{:02}.jsonl", year, month, date)
+}
+
+/// Delete audit files for days before `oldest_kept_day`
+fn prune_audit_files(dir: &Path, oldest_kept_day: u64) {
+    let oldest_kept = audit_file_name(oldest_kept_day);
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        // File names sort chronologically
+        if name.starts_with("audit-") && name.ends_with(".jsonl") && name < oldest_kept {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                warn!("Failed to remove expired audit file {}: {}", name, e);
+            }
+        }
+    }
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) date
+pub(crate) fn civil_from_days(days: u64) -> (i64, u32, u32) {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_audit_file_names() {
+        assert_eq!(audit_file_name(0), "audit-1970-01-01.jsonl");
+        assert_eq!(audit_file_name(19_723), "audit-2024-01-01.jsonl");
+        assert_eq!(audit_file_name(19_782), "audit-2024-02-29.jsonl");
+    }
+
+    #[tokio::test]
+    async fn test_file_sink_appends_and_prunes() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = AuditLog::new(
+            AuditSink::File {
+                dir: temp_dir.path().to_path_buf(),
+            },
+            30,
+        );
+        std::fs::write(temp_dir.path().join("audit-2000-01-01.jsonl"), "{}\n").unwrap();
+
+        let mut record = AuditRecord::new("10.0.0.1", "generate", "stack", "payments-prod");
+        record.status = AuditStatus::Success;
+        record.template_digest = Some("abc".to_string());
+        log.record(&record).await.unwrap();
+        log.record(&record).await.unwrap();
+
+        let files: Vec<_> = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1, "expired file should be pruned: {:?}", files);
+
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["status"], "success");
+        assert_eq!(lines[0]["caller"], "10.0.0.1");
+        assert_eq!(lines[0]["template_digest"], "abc");
+        assert!(lines[0].get("error").is_none());
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use hcl::{Attribute, Block, Body, Expression, Identifier};
+
+use crate::error::CliError;
+use crate::template_processor::{ProcessedFile, TemplateProcessor};
+
+/// File the backend configuration is written to, at the root of the output directory
+pub const BACKEND_FILE: &str = "backend.tf";
+
+/// State key shared by all backends: the stack name, or the blueprint name for blueprints
+const STATE_NAME: &str = "{{#if stack.name}}{{stack.name}}{{else}}{{blueprint.name}}{{/if}}";
+
+/// Terraform state backend to write `backend.tf` for (`--backend`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BackendKind {
+    /// S3 bucket, optionally locked with a DynamoDB table
+    S3,
+    /// Azure Storage container
+    Azurerm,
+    /// Google Cloud Storage bucket
+    Gcs,
+}
+
+impl BackendKind {
+    /// Backend name in the `backend "<name>"` block
+    pub fn name(self) -> &'static str {
+        match self {
+            BackendKind::S3 => "s3",
+            BackendKind::Azurerm => "azurerm",
+            BackendKind::Gcs => "gcs",
+        }
+    }
+
+    /// Settings Terraform can't initialize the backend without
+    fn required(self) -> &'static [&'static str] {
+        match self {
+            BackendKind::S3 | BackendKind::Gcs => &["bucket"],
+            BackendKind::Azurerm => &["storage_account_name", "container_name"],
+        }
+    }
+
+    /// Settings used unless given with `--backend-config`
+    ///
+    /// Each stack gets its own state key, and S3 state is encrypted at rest.
+    fn defaults(self) -> Vec<(&'static str, String)> {
+        match self {
+            BackendKind::S3 => vec![
+                ("key", format!("{}/terraform.tfstate", STATE_NAME)),
+                ("encrypt", "true".to_string()),
+            ],
+            BackendKind::Azurerm => vec![("key", format!("{}.terraform.tfstate", STATE_NAME))],
+            BackendKind::Gcs => vec![("prefix", STATE_NAME.to_string())],
+        }
+    }
+}
+
+/// Parse a `--backend-config` value of the form `name=value`
+pub fn parse_setting(value: &str) -> Result<(String, String), String> {
+    let (name, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", value))?;
+    Identifier::new(name.trim()).map_err(|_| format!("'{}' is not a valid setting name", name.trim()))?;
+    Ok((name.trim().to_string(), value.to_string()))
+}
+
+/// Render `backend.tf` for a backend from the given settings and the defaults
+///
+/// Setting values may contain template expressions, e.g.
+/// `key=teams/{{stack.owner}}/{{stack.name}}.tfstate`; `true`, `false` and
+/// whole numbers are written unquoted.
+pub fn generate_backend_tf(
+    kind: BackendKind,
+    settings: &[(String, String)],
+    processor: &TemplateProcessor,
+) -> Result<ProcessedFile, CliError> {
+    let mut values: BTreeMap<String, String> =
+        kind.defaults().into_iter().map(|(name, value)| (name.to_string(), value)).collect();
+    values.extend(settings.iter().cloned());
+
+    let missing: Vec<&str> = kind.required().iter().copied().filter(|name| !values.contains_key(*name)).collect();
+    if !missing.is_empty() {
+        let flags: Vec<String> = missing.iter().map(|name| format!("--backend-config {}=...", name)).collect();
+        return Err(CliError::ConfigurationError(format!(
+            "The {} backend needs {}",
+            kind.name(),
+            flags.join(" and ")
+        )));
+    }
+
+    let mut backend = Block::builder("backend").add_label(kind.name());
+    for (name, value) in values {
+        let value = processor.process_template(&value)?;
+        let expression = match value.as_str() {
+            "true" => Expression::from(true),
+            "false" => Expression::from(false),
+            number => number.parse::<i64>().map(Expression::from).unwrap_or_else(|_| Expression::from(value)),
+        };
+        backend = backend.add_attribute(Attribute::new(name, expression));
+    }
+    let body = Body::builder()
+        .add_block(Block::builder("terraform").add_block(backend.build()).build())
+        .build();
+    let content = hcl::to_string(&body)
+        .map_err(|e| CliError::GenerationError(format!("Failed to write {}: {}", BACKEND_FILE, e)))?;
+
+    Ok(ProcessedFile::new(PathBuf::from(BACKEND_FILE), content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variable_context::VariableContext;
+    use serde_json::json;
+
+    #[test]
+    fn test_generate_backend_tf() {
+        let mut context = VariableContext::new();
+        context.insert("stack".to_string(), json!({"name": "payments", "owner": "team-a"}));
+        let processor = TemplateProcessor::new(&context);
+        let settings: Vec<_> = ["bucket=tf-state", "dynamodb_table=tf-locks", "region=eu-west-1"]
+            .iter()
+            .map(|setting| parse_setting(setting).unwrap())
+            .collect();
+
+        let backend = generate_backend_tf(BackendKind::S3, &settings, &processor).unwrap();
+        assert_eq!(backend.relative_path, PathBuf::from("backend.tf"));
+        assert_eq!(
+            backend.content,
+            "terraform {\n  backend \"s3\" {\n    bucket = \"tf-state\"\n    dynamodb_table = \"tf-locks\"\n    \
+             encrypt = true\n    key = \"payments/terraform.tfstate\"\n    region = \"eu-west-1\"\n  }\n}\n"
+        );
+        assert!(hcl::parse(&backend.content).is_ok());
+
+        let settings = vec![parse_setting("bucket=state").unwrap(), parse_setting("prefix={{stack.owner}}/x").unwrap()];
+        let backend = generate_backend_tf(BackendKind::Gcs, &settings, &processor).unwrap();
+        assert!(backend.content.contains("prefix = \"team-a/x\""), "{}", backend.content);
+
+        let error = generate_backend_tf(BackendKind::Azurerm, &[], &processor).unwrap_err();
+        assert!(error.to_string().contains("--backend-config storage_account_name=... and --backend-config container_name=..."));
+        assert!(parse_setting("bucket").is_err());
+        assert!(parse_setting("not valid=x").is_err());
+    }
+}
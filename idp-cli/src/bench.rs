@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::error::CliError;
+use crate::file_writer::FileWriter;
+use crate::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
+use crate::run_summary::RunSummary;
+use crate::template_discovery::TemplateDiscovery;
+use crate::template_processor::TemplateProcessor;
+use crate::variable_context::VariableContextBuilder;
+
+/// Resource categories cycled through by synthetic blueprints
+const CATEGORIES: &[&str] = &["Networking", "Database", "Storage", "Compute"];
+
+/// Build a blueprint with `resources` synthetic resources
+pub fn synthetic_blueprint(resources: usize) -> Blueprint {
+    let provider = CloudProvider {
+        id: Uuid::new_v4(),
+        name: "AWS".to_string(),
+        display_name: "Amazon Web Services".to_string(),
+    };
+
+    Blueprint {
+        id: Uuid::new_v4(),
+        name: "synthetic-blueprint".to_string(),
+        description: Some(format!("Synthetic blueprint with {} resources", resources)),
+        resources: (0..resources)
+            .map(|i| BlueprintResource {
+                id: Uuid::new_v4(),
+                name: format!("resource-{}", i),
+                description: None,
+                resource_type: ResourceType {
+                    id: Uuid::new_v4(),
+                    name: format!("Type{}", i % CATEGORIES.len()),
+                    category: CATEGORIES[i % CATEGORIES.len()].to_string(),
+                },
+                cloud_provider: provider.clone(),
+                configuration: json!({
+                    "size": "medium",
+                    "replicas": i % 5 + 1,
+                    "tags": { "index": i.to_string() },
+                }),
+                cloud_specific_properties: [("region".to_string(), json!("us-east-1"))]
+                    .into_iter()
+                    .collect(),
+            })
+            .collect(),
+        revision: None,
+        updated_at: None,
+        supported_cloud_providers: vec![provider],
+    }
+}
+
+/// Write `files` synthetic Terraform templates into `dir`
+///
+/// Each template renders one resource by index and loops over every resource,
+/// so rendering cost grows with both file and resource count.
+pub fn write_synthetic_templates(dir: &Path, files: usize, resources: usize) -> Result<(), CliError> {
+    for i in 0..files {
+        let subdir = dir.join(format!("module-{}", i / 100));
+        fs::create_dir_all(&subdir)?;
+        let index = if resources == 0 { 0 } else { i % resources };
+        let content = format!(
+            "# {{{{blueprint.name}}}} file {i}\n\
+             resource \"aws_instance\" \"{{{{resources.{index}.name}}}}\" {{\n  \
+               instance_type = \"{{{{default resources.{index}.configuration.size \"small\"}}}}\"\n}}\n\
+             {{{{#each resources}}}}# {{{{this.name}}}}: {{{{uppercase this.resource_type.category}}}}\n{{{{/each}}}}",
+        );
+        fs::write(subdir.join(format!("file-{}.tf", i)), content)?;
+    }
+    Ok(())
+}
+
+/// Run the full pipeline against a synthetic workload
+///
+/// Generates a blueprint with `files` resources and `files` templates in a
+/// temporary directory, then times context building, discovery, rendering
+/// and writing.
+pub fn run_synthetic(files: usize) -> Result<RunSummary, CliError> {
+    let mut summary = RunSummary::start("bench", "synthetic", &files.to_string());
+    let workspace = tempfile::TempDir::new()?;
+    let template_dir = workspace.path().join("templates");
+    let output_dir = workspace.path().join("output");
+
+    let blueprint = synthetic_blueprint(files);
+    write_synthetic_templates(&template_dir, files, files)?;
+
+    let stage = Instant::now();
+    let context = VariableContextBuilder::from_blueprint(&blueprint);
+    summary.record_stage("context", stage.elapsed());
+
+    let stage = Instant::now();
+    let templates = TemplateDiscovery::new(template_dir)
+        .discover_templates()
+        .map_err(|e| CliError::DiscoveryError(e.to_string()))?;
+    summary.record_stage("discover", stage.elapsed());
+    summary.templates_discovered = templates.len();
+
+    let stage = Instant::now();
+    let processor = TemplateProcessor::new(&context);
+    let processed = templates
+        .iter()
+        .map(|t| processor.process_file_outputs(t))
+        .collect::<Result<Vec<_>, _>>()?
+        .concat();
+    summary.record_stage("render", stage.elapsed());
+
+    let stage = Instant::now();
+    let written = FileWriter::new(output_dir).write_processed_files(&processed)?;
+    summary.record_stage("write", stage.elapsed());
+    summary.files_written = written.len();
+    summary.bytes_written = processed.iter().map(|f| f.content_len() as u64).sum();
+
+    summary.finish(&Ok(()));
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_synthetic_small_workload() {
+        let summary = run_synthetic(5).unwrap();
+        assert_eq!(summary.templates_discovered, 5);
+        assert_eq!(summary.files_written, 5);
+        assert!(summary.bytes_written > 0);
+        let stages: Vec<&str> = summary.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(stages, vec!["context", "discover", "render", "write"]);
+    }
+}
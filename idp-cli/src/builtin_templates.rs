@@ -0,0 +1,183 @@
+use std::fmt;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use crate::error::CliError;
+use crate::paths::long_path;
+
+/// Template pack compiled into the binary, selected with `--builtin-templates`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BuiltinPack {
+    /// Terraform for an AWS web service (VPC, ECS or EKS, RDS, S3, IAM) and a
+    /// GitHub Actions workflow deploying it
+    AwsWebservice,
+    /// Kubernetes manifests (Deployment, Service, HPA, PDB, NetworkPolicy,
+    /// ServiceMonitor) for each stack resource with a container image
+    #[value(name = "k8s-microservice")]
+    K8sMicroservice,
+}
+
+/// Template files of a pack as (path in the template directory, content)
+type PackFiles = &'static [(&'static str, &'static str)];
+
+macro_rules! pack_file {
+    ($pack:literal, $file:literal) => {
+        ($file, include_str!(concat!("../templates/", $pack, "/", $file)))
+    };
+    ($pack:literal, $file:literal, $source:literal) => {
+        ($file, include_str!(concat!("../templates/", $pack, "/", $source)))
+    };
+}
+
+const AWS_WEBSERVICE: PackFiles = &[
+    pack_file!("aws-webservice", "helpers/service_name.hbs"),
+    pack_file!("aws-webservice", "providers.tf"),
+    pack_file!("aws-webservice", "variables.tf"),
+    pack_file!("aws-webservice", "network.tf"),
+    pack_file!("aws-webservice", "iam.tf"),
+    pack_file!("aws-webservice", "compute.tf"),
+    pack_file!("aws-webservice", "database.tf"),
+    pack_file!("aws-webservice", "storage.tf"),
+    pack_file!("aws-webservice", "outputs.tf"),
+    // Discovery skips hidden directories, so .github is a templated name
+    pack_file!(
+        "aws-webservice",
+        "{{default github_dir '.github'}}/workflows/deploy.yml",
+        "github/workflows/deploy.yml"
+    ),
+];
+
+const K8S_MICROSERVICE: PackFiles = &[
+    pack_file!("k8s-microservice", "helpers/k8s_name.hbs"),
+    pack_file!("k8s-microservice", "namespace.yaml"),
+    pack_file!("k8s-microservice", "deployment.yaml"),
+    pack_file!("k8s-microservice", "service.yaml"),
+    pack_file!("k8s-microservice", "hpa.yaml"),
+    pack_file!("k8s-microservice", "pdb.yaml"),
+    pack_file!("k8s-microservice", "networkpolicy.yaml"),
+    pack_file!("k8s-microservice", "servicemonitor.yaml"),
+];
+
+impl BuiltinPack {
+    /// Name of the pack on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            BuiltinPack::AwsWebservice => "aws-webservice",
+            BuiltinPack::K8sMicroservice => "k8s-microservice",
+        }
+    }
+
+    /// Whether the pack only works with stack data (`stack_resources`)
+    pub fn needs_stack(self) -> bool {
+        matches!(self, BuiltinPack::K8sMicroservice)
+    }
+
+    pub fn files(self) -> PackFiles {
+        match self {
+            BuiltinPack::AwsWebservice => AWS_WEBSERVICE,
+            BuiltinPack::K8sMicroservice => K8S_MICROSERVICE,
+        }
+    }
+
+    /// Paths of the pack's templates that already exist in `dir`
+    pub fn existing_files(self, dir: &Path) -> Vec<&'static str> {
+        self.files()
+            .iter()
+            .map(|(relative_path, _)| *relative_path)
+            .filter(|relative_path| long_path(&dir.join(relative_path)).exists())
+            .collect()
+    }
+
+    /// Write the pack's templates into `dir`, which then works as a template directory
+    pub fn extract(self, dir: &Path) -> Result<(), CliError> {
+        for (relative_path, content) in self.files() {
+            let path = dir.join(relative_path);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(long_path(parent)).map_err(|e| {
+                    CliError::IoError(format!("Failed to create directory {}: {}", parent.display(), e))
+                })?;
+            }
+            std::fs::write(long_path(&path), content).map_err(|e| {
+                CliError::IoError(format!("Failed to write template {}: {}", path.display(), e))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// `builtin:<name>`, as recorded in the output manifest
+impl fmt::Display for BuiltinPack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "builtin:{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper_plugins::HelperSet;
+    use crate::template_discovery::TemplateDiscovery;
+    use crate::template_processor::{ProcessedFile, TemplateProcessor};
+    use crate::variable_context::VariableContext;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    fn render(pack: BuiltinPack, context: &VariableContext) -> Vec<ProcessedFile> {
+        let dir = tempfile::tempdir().unwrap();
+        pack.extract(dir.path()).unwrap();
+        let templates = TemplateDiscovery::new(dir.path().to_path_buf()).discover_templates().unwrap();
+        // Everything but the helper definitions is a template
+        let helpers = HelperSet::for_templates(dir.path(), None).unwrap();
+        assert_eq!(templates.len() + helpers.names().len(), pack.files().len(), "{:?}", templates);
+
+        let processor = TemplateProcessor::new(context).with_helpers(&helpers).unwrap();
+        templates.iter().flat_map(|template| processor.process_file_outputs(template).unwrap()).collect()
+    }
+
+    fn content<'a>(files: &'a [ProcessedFile], path: &str) -> &'a str {
+        files.iter().find(|f| f.relative_path == Path::new(path)).map(|f| f.content.as_str()).unwrap()
+    }
+
+    #[test]
+    fn test_aws_webservice_pack_renders() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("Payments API"));
+        let files = render(BuiltinPack::AwsWebservice, &context);
+
+        assert!(content(&files, "variables.tf").contains("default     = \"payments-api\""));
+        assert!(content(&files, "compute.tf").contains("resource \"aws_ecs_service\" \"main\""));
+        assert!(content(&files, ".github/workflows/deploy.yml").contains("role-to-assume: ${{ secrets.AWS_DEPLOY_ROLE_ARN }}"));
+    }
+
+    #[test]
+    fn test_k8s_microservice_pack_renders_services_only() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("Payments Prod"));
+        context.insert(
+            "stack_resources".to_string(),
+            json!([
+                {"name": "orders_api", "configuration": {"image": "ghcr.io/acme/orders:1.2", "replicas": 3, "allow_from": ["web"]}},
+                {"name": "orders-db", "configuration": {"engine": "postgres"}}
+            ]),
+        );
+        let files = render(BuiltinPack::K8sMicroservice, &context);
+
+        let documents = |path: &str| -> Vec<serde_yaml::Value> {
+            serde_yaml::Deserializer::from_str(content(&files, path))
+                .map(|document| serde_yaml::Value::deserialize(document).unwrap())
+                .collect()
+        };
+        for path in ["deployment.yaml", "service.yaml", "hpa.yaml", "pdb.yaml", "networkpolicy.yaml", "servicemonitor.yaml"] {
+            let documents = documents(path);
+            assert_eq!(documents.len(), 1, "{}: {:?}", path, documents);
+            assert_eq!(documents[0]["metadata"]["name"], "orders-api", "{}", path);
+            assert_eq!(documents[0]["metadata"]["namespace"], "payments-prod", "{}", path);
+        }
+        let deployment = &documents("deployment.yaml")[0];
+        assert_eq!(deployment["spec"]["replicas"], 3);
+        assert_eq!(deployment["spec"]["template"]["spec"]["containers"][0]["image"], "ghcr.io/acme/orders:1.2");
+        let policy = &documents("networkpolicy.yaml")[0];
+        assert_eq!(policy["spec"]["ingress"][0]["from"][0]["podSelector"]["matchLabels"]["app.kubernetes.io/name"], "web");
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::CliError;
+use crate::region_check::Cloud;
+use crate::resource_mapper::{kubernetes_name, map_resources, source_metadata, terraform_type};
+use crate::template_processor::ProcessedFile;
+use crate::variable_context::VariableContext;
+
+/// CDKTF project configuration written at the root of the output directory
+pub const CDKTF_FILE: &str = "cdktf.json";
+
+/// Language of the generated CDKTF program
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CdktfLanguage {
+    TypeScript,
+    Python,
+}
+
+impl CdktfLanguage {
+    fn name(self) -> &'static str {
+        match self {
+            CdktfLanguage::TypeScript => "typescript",
+            CdktfLanguage::Python => "python",
+        }
+    }
+
+    fn main_file(self) -> &'static str {
+        match self {
+            CdktfLanguage::TypeScript => "main.ts",
+            CdktfLanguage::Python => "main.py",
+        }
+    }
+
+    /// Command `cdktf synth` runs, as `cdktf init` would write it
+    fn app(self) -> &'static str {
+        match self {
+            CdktfLanguage::TypeScript => "npx ts-node main.ts",
+            CdktfLanguage::Python => "pipenv run python main.py",
+        }
+    }
+
+    /// Directory `cdktf get` writes the provider bindings to
+    fn bindings_dir(self) -> &'static str {
+        match self {
+            CdktfLanguage::TypeScript => ".gen",
+            CdktfLanguage::Python => "imports",
+        }
+    }
+
+    /// Import path of a module of a provider's bindings, e.g. `db_instance` of `aws`
+    fn module_path(self, provider: &str, module: &str) -> String {
+        match self {
+            CdktfLanguage::TypeScript => format!("./.gen/providers/{}/{}", provider, module.replace('_', "-")),
+            CdktfLanguage::Python => format!("imports.{}.{}", provider, module),
+        }
+    }
+}
+
+/// Contents of cdktf.json
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CdktfConfig {
+    language: &'static str,
+    app: &'static str,
+    terraform_providers: Vec<&'static str>,
+    terraform_modules: Vec<String>,
+    code_maker_output: &'static str,
+    context: HashMap<String, Value>,
+}
+
+/// Terraform provider of a cloud: local name, source constraint for cdktf.json
+/// and the configuration its provider construct needs
+fn provider(cloud: Cloud) -> (&'static str, &'static str, &'static [(&'static str, &'static str)]) {
+    match cloud {
+        Cloud::Aws => ("aws", "hashicorp/aws@~> 5.0", &[]),
+        Cloud::Azure => ("azurerm", "hashicorp/azurerm@~> 3.0", &[("features", "{}")]),
+        Cloud::Gcp => ("google", "hashicorp/google@~> 5.0", &[]),
+    }
+}
+
+/// A construct to instantiate in the stack
+struct Construct {
+    class: String,
+    id: String,
+    /// Configuration as (key, rendered value) pairs
+    config: Vec<(String, String)>,
+}
+
+/// CDKTF project skeleton for the resources of a blueprint or stack
+///
+/// Writes cdktf.json and a main.ts or main.py declaring one stack, named after
+/// the stack (or blueprint), with a provider construct per cloud and a
+/// resource construct per resource. Configuration keys are converted to the
+/// language's naming (camelCase or snake_case). The program imports the
+/// bindings `cdktf get` generates. Resources that can't be mapped are skipped
+/// and reported in the returned warnings.
+pub fn cdktf_files(
+    context: &VariableContext,
+    language: CdktfLanguage,
+) -> Result<(Vec<ProcessedFile>, Vec<String>), CliError> {
+    let data = context.to_template_data();
+    let (source, name, _) = source_metadata(&data);
+    let name = name.ok_or_else(|| {
+        CliError::GenerationError(format!("The {} has no name to name the CDKTF stack after", source))
+    })?;
+    let (mapped, mut warnings) = map_resources(&data);
+
+    let mut clouds: Vec<Cloud> = Vec::new();
+    let mut imports: Vec<(String, String)> = Vec::new();
+    let mut constructs = Vec::new();
+    let mut ids: HashMap<String, &str> = HashMap::new();
+    for resource in mapped {
+        let Some(resource_type) = terraform_type(resource.cloud, resource.kind) else {
+            warnings.push(format!("Resource '{}' has no Terraform mapping and was not generated", resource.name));
+            continue;
+        };
+        let id = kubernetes_name(resource.name);
+        if let Some(other) = ids.insert(id.clone(), resource.name) {
+            return Err(CliError::GenerationError(format!(
+                "Resources '{}' and '{}' both map to the construct ID '{}'; rename one of them",
+                other, resource.name, id
+            )));
+        }
+        let (provider_name, _, _) = provider(resource.cloud);
+        let module = resource_type.strip_prefix(&format!("{}_", provider_name)).unwrap_or(resource_type);
+        let class = pascal_case(module);
+        let import = (language.module_path(provider_name, module), class.clone());
+        if !imports.contains(&import) {
+            imports.push(import);
+        }
+        if !clouds.contains(&resource.cloud) {
+            clouds.push(resource.cloud);
+        }
+        let config = match resource.configuration {
+            Value::Object(map) => map
+                .iter()
+                .map(|(key, value)| (convert_key(key, language), render_value(value, language, 2)))
+                .collect(),
+            _ => Vec::new(),
+        };
+        constructs.push(Construct { class, id, config });
+    }
+
+    let providers: Vec<Construct> = clouds
+        .iter()
+        .map(|cloud| {
+            let (provider_name, _, config) = provider(*cloud);
+            Construct {
+                class: format!("{}Provider", pascal_case(provider_name)),
+                id: provider_name.to_string(),
+                config: config.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect(),
+            }
+        })
+        .collect();
+    let provider_imports = clouds.iter().map(|cloud| {
+        let (provider_name, _, _) = provider(*cloud);
+        (language.module_path(provider_name, "provider"), format!("{}Provider", pascal_case(provider_name)))
+    });
+    let imports: Vec<(String, String)> = provider_imports.chain(imports).collect();
+
+    let stack_id = kubernetes_name(name);
+    let program = match language {
+        CdktfLanguage::TypeScript => typescript_program(&stack_id, &imports, &providers, &constructs),
+        CdktfLanguage::Python => python_program(&stack_id, &imports, &providers, &constructs),
+    };
+
+    let config = CdktfConfig {
+        language: language.name(),
+        app: language.app(),
+        terraform_providers: clouds.iter().map(|cloud| provider(*cloud).1).collect(),
+        terraform_modules: Vec::new(),
+        code_maker_output: language.bindings_dir(),
+        context: HashMap::new(),
+    };
+    let config = serde_json::to_string_pretty(&config)
+        .map_err(|e| CliError::GenerationError(format!("Failed to write {}: {}", CDKTF_FILE, e)))?;
+
+    let files = vec![
+        ProcessedFile::new(PathBuf::from(CDKTF_FILE), config + "\n"),
+        ProcessedFile::new(PathBuf::from(language.main_file()), program),
+    ];
+    Ok((files, warnings))
+}
+
+fn typescript_program(
+    stack_id: &str,
+    imports: &[(String, String)],
+    providers: &[Construct],
+    constructs: &[Construct],
+) -> String {
+    let mut lines = vec![
+        "import { Construct } from \"constructs\";".to_string(),
+        "import { App, TerraformStack } from \"cdktf\";".to_string(),
+    ];
+    for (module, class) in imports {
+        lines.push(format!("import {{ {} }} from \"{}\";", class, module));
+    }
+    lines.push(String::new());
+    lines.push("class MainStack extends TerraformStack {".to_string());
+    lines.push("  constructor(scope: Construct, id: string) {".to_string());
+    lines.push("    super(scope, id);".to_string());
+    for construct in providers.iter().chain(constructs) {
+        lines.push(String::new());
+        if construct.config.is_empty() {
+            lines.push(format!("    new {}(this, {:?}, {{}});", construct.class, construct.id));
+            continue;
+        }
+        lines.push(format!("    new {}(this, {:?}, {{", construct.class, construct.id));
+        for (key, value) in &construct.config {
+            lines.push(format!("      {}: {},", key, value));
+        }
+        lines.push("    });".to_string());
+    }
+    lines.push("  }".to_string());
+    lines.push("}".to_string());
+    lines.push(String::new());
+    lines.push("const app = new App();".to_string());
+    lines.push(format!("new MainStack(app, {:?});", stack_id));
+    lines.push("app.synth();".to_string());
+    lines.join("\n") + "\n"
+}
+
+fn python_program(
+    stack_id: &str,
+    imports: &[(String, String)],
+    providers: &[Construct],
+    constructs: &[Construct],
+) -> String {
+    let mut lines = vec![
+        "#!/usr/bin/env python".to_string(),
+        "from constructs import Construct".to_string(),
+        "from cdktf import App, TerraformStack".to_string(),
+    ];
+    for (module, class) in imports {
+        lines.push(format!("from {} import {}", module, class));
+    }
+    lines.push(String::new());
+    lines.push(String::new());
+    lines.push("class MainStack(TerraformStack):".to_string());
+    lines.push("    def __init__(self, scope: Construct, id: str):".to_string());
+    lines.push("        super().__init__(scope, id)".to_string());
+    for construct in providers.iter().chain(constructs) {
+        lines.push(String::new());
+        if construct.config.is_empty() {
+            lines.push(format!("        {}(self, {:?})", construct.class, construct.id));
+            continue;
+        }
+        lines.push(format!("        {}(", construct.class));
+        lines.push("            self,".to_string());
+        lines.push(format!("            {:?},", construct.id));
+        for (key, value) in &construct.config {
+            lines.push(format!("            {}={},", key, value));
+        }
+        lines.push("        )".to_string());
+    }
+    lines.push(String::new());
+    lines.push(String::new());
+    lines.push("app = App()".to_string());
+    lines.push(format!("MainStack(app, {:?})", stack_id));
+    lines.push("app.synth()".to_string());
+    lines.join("\n") + "\n"
+}
+
+/// `db_instance` -> `DbInstance`
+fn pascal_case(name: &str) -> String {
+    name.split(['_', '-'])
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Configuration key as the bindings name it: camelCase for TypeScript, snake_case for Python
+fn convert_key(key: &str, language: CdktfLanguage) -> String {
+    match language {
+        CdktfLanguage::TypeScript => {
+            let pascal = pascal_case(key);
+            let mut chars = pascal.chars();
+            let camel = match chars.next() {
+                Some(first) => first.to_lowercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            };
+            if !camel.is_empty() && camel.chars().all(|c| c.is_ascii_alphanumeric()) {
+                camel
+            } else {
+                format!("{:?}", key)
+            }
+        }
+        CdktfLanguage::Python => {
+            let mut snake = String::new();
+            for (index, c) in key.chars().enumerate() {
+                if c.is_ascii_uppercase() {
+                    if index > 0 && !snake.ends_with('_') {
+                        snake.push('_');
+                    }
+                    snake.push(c.to_ascii_lowercase());
+                } else if c.is_ascii_alphanumeric() {
+                    snake.push(c);
+                } else {
+                    snake.push('_');
+                }
+            }
+            snake
+        }
+    }
+}
+
+/// Configuration value as a TypeScript or Python literal, nested `depth` levels deep
+fn render_value(value: &Value, language: CdktfLanguage, depth: usize) -> String {
+    let python = language == CdktfLanguage::Python;
+    // Python arguments sit one level deeper than TypeScript object entries
+    let indent = |depth: usize| if python { "    ".repeat(depth + 1) } else { "  ".repeat(depth + 1) };
+    match value {
+        Value::Null if python => "None".to_string(),
+        Value::Null => "undefined".to_string(),
+        Value::Bool(true) if python => "True".to_string(),
+        Value::Bool(false) if python => "False".to_string(),
+        Value::Array(items) if items.is_empty() => "[]".to_string(),
+        Value::Array(items) => {
+            let items: Vec<String> = items
+                .iter()
+                .map(|item| format!("{}{},", indent(depth + 1), render_value(item, language, depth + 1)))
+                .collect();
+            format!("[\n{}\n{}]", items.join("\n"), indent(depth))
+        }
+        Value::Object(map) if map.is_empty() => "{}".to_string(),
+        Value::Object(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(key, item)| {
+                    let key = convert_key(key, language);
+                    let key = if python { format!("{:?}", key) } else { key };
+                    format!("{}{}: {},", indent(depth + 1), key, render_value(item, language, depth + 1))
+                })
+                .collect();
+            format!("{{\n{}\n{}}}", entries.join("\n"), indent(depth))
+        }
+        scalar => scalar.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> VariableContext {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("Payments Prod"));
+        context.insert(
+            "stack_resources".to_string(),
+            json!([{
+                "name": "orders-db",
+                "resource_type": {"name": "Relational Database", "category": "NON_SHARED"},
+                "cloud_provider": {"name": "AWS"},
+                "configuration": {"instance_class": "db.t3.micro", "multiAz": true}
+            }]),
+        );
+        context
+    }
+
+    #[test]
+    fn test_cdktf_files_typescript() {
+        let (files, warnings) = cdktf_files(&context(), CdktfLanguage::TypeScript).unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let config: Value = serde_json::from_str(&files[0].content).unwrap();
+        assert_eq!(config["language"], "typescript");
+        assert_eq!(config["terraformProviders"], json!(["hashicorp/aws@~> 5.0"]));
+        assert_eq!(files[1].relative_path, PathBuf::from("main.ts"));
+        assert_eq!(
+            files[1].content,
+            r#"import { Construct } from "constructs";
+import { App, TerraformStack } from "cdktf";
+import { AwsProvider } from "./.gen/providers/aws/provider";
+import { DbInstance } from "./.gen/providers/aws/db-instance";
+
+class MainStack extends TerraformStack {
+  constructor(scope: Construct, id: string) {
+    super(scope, id);
+
+    new AwsProvider(this, "aws", {});
+
+    new DbInstance(this, "orders-db", {
+      instanceClass: "db.t3.micro",
+      multiAz: true,
+    });
+  }
+}
+
+const app = new App();
+new MainStack(app, "payments-prod");
+app.synth();
+"#
+        );
+    }
+
+    #[test]
+    fn test_cdktf_files_python() {
+        let (files, _) = cdktf_files(&context(), CdktfLanguage::Python).unwrap();
+        assert_eq!(files[1].relative_path, PathBuf::from("main.py"));
+        assert!(files[1].content.contains("from imports.aws.db_instance import DbInstance\n"), "{}", files[1].content);
+        assert!(
+            files[1].content.contains(
+                "        DbInstance(\n            self,\n            \"orders-db\",\n            instance_class=\"db.t3.micro\",\n            multi_az=True,\n        )\n"
+            ),
+            "{}",
+            files[1].content
+        );
+        assert_eq!(convert_key("nodePool", CdktfLanguage::Python), "node_pool");
+        assert_eq!(
+            render_value(&json!({"tags": ["a"]}), CdktfLanguage::TypeScript, 2),
+            "{\n        tags: [\n          \"a\",\n        ],\n      }"
+        );
+    }
+
+    #[test]
+    fn test_cdktf_files_with_api_resource_types() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("Payments"));
+        context.insert(
+            "stack_resources".to_string(),
+            json!([
+                {"name": "sessions", "resource_type": {"name": "Cache", "category": "NON_SHARED"}, "cloud_provider": {"name": "AWS"}},
+                {"name": "events", "resource_type": {"name": "Service Bus", "category": "SHARED"}, "cloud_provider": {"name": "Azure"}},
+                {"name": "assets", "resource_type": {"name": "Storage", "category": "BOTH"}, "cloud_provider": {"name": "Google Cloud"}},
+                {"name": "edge", "resource_type": {"name": "CDN", "category": "BOTH"}, "cloud_provider": {"name": "AWS"}}
+            ]),
+        );
+
+        let (files, warnings) = cdktf_files(&context, CdktfLanguage::TypeScript).unwrap();
+        let program = &files[1].content;
+        for import in [
+            "import { ElasticacheReplicationGroup } from \"./.gen/providers/aws/elasticache-replication-group\";\n",
+            "import { ServicebusNamespace } from \"./.gen/providers/azurerm/servicebus-namespace\";\n",
+            "import { StorageBucket } from \"./.gen/providers/google/storage-bucket\";\n",
+        ] {
+            assert!(program.contains(import), "{}", program);
+        }
+        assert!(!program.contains("\"edge\""), "{}", program);
+        assert_eq!(warnings, vec!["Resource 'edge' has a resource type 'CDN' with no known mapping and was not mapped"]);
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+
+use crate::audit::civil_from_days;
+use crate::output_manifest::OutputManifest;
+
+/// Changes between two consecutive generations of an output directory
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChangelogEntry {
+    pub files_added: Vec<String>,
+    pub files_removed: Vec<String>,
+    pub files_modified: Vec<String>,
+    pub resources_added: Vec<String>,
+    pub resources_removed: Vec<String>,
+    /// Variable path with its value before and after; None when the variable is new or gone
+    pub variables_changed: Vec<(String, Option<Value>, Option<Value>)>,
+}
+
+impl ChangelogEntry {
+    /// Compare an older generation with a newer one
+    pub fn between(older: &OutputManifest, newer: &OutputManifest) -> Self {
+        let mut entry = Self::default();
+
+        for (path, digest) in &newer.files {
+            match older.files.get(path) {
+                None => entry.files_added.push(path.clone()),
+                Some(old) if old != digest => entry.files_modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        entry.files_removed = older.files.keys().filter(|path| !newer.files.contains_key(*path)).cloned().collect();
+
+        let old_resources: BTreeSet<&String> = older.resources.iter().collect();
+        let new_resources: BTreeSet<&String> = newer.resources.iter().collect();
+        entry.resources_added = new_resources.difference(&old_resources).map(|r| r.to_string()).collect();
+        entry.resources_removed = old_resources.difference(&new_resources).map(|r| r.to_string()).collect();
+
+        let names: BTreeSet<&String> = older.variables.keys().chain(newer.variables.keys()).collect();
+        for name in names {
+            let (before, after) = (older.variables.get(name), newer.variables.get(name));
+            if before != after {
+                entry.variables_changed.push((name.clone(), before.cloned(), after.cloned()));
+            }
+        }
+
+        entry
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Markdown summary of the changes across a list of generations, newest first
+///
+/// `history` is ordered oldest first, as returned by [`OutputManifest::history`].
+pub fn render_markdown(history: &[OutputManifest]) -> String {
+    let Some(latest) = history.last() else {
+        return "No generations recorded.\n".to_string();
+    };
+
+    let mut lines = vec![format!("## Changes to {} `{}`", latest.data_source, latest.name)];
+    if history.len() < 2 {
+        lines.push(String::new());
+        lines.push(format!("Only one generation recorded ({}).", describe_generation(latest)));
+    }
+
+    for pair in history.windows(2).rev() {
+        let (older, newer) = (&pair[0], &pair[1]);
+        let entry = ChangelogEntry::between(older, newer);
+        lines.push(String::new());
+        lines.push(format!("### {} → {}", describe_generation(older), describe_generation(newer)));
+        lines.push(String::new());
+        if entry.is_empty() {
+            lines.push("No changes.".to_string());
+            continue;
+        }
+
+        if !(entry.files_added.is_empty() && entry.files_removed.is_empty() && entry.files_modified.is_empty()) {
+            lines.push(format!(
+                "**Files:** {} added, {} removed, {} modified",
+                entry.files_added.len(),
+                entry.files_removed.len(),
+                entry.files_modified.len()
+            ));
+            lines.push(String::new());
+            lines.extend(entry.files_added.iter().map(|f| format!("- Added `{}`", f)));
+            lines.extend(entry.files_removed.iter().map(|f| format!("- Removed `{}`", f)));
+            lines.extend(entry.files_modified.iter().map(|f| format!("- Modified `{}`", f)));
+            lines.push(String::new());
+        }
+
+        if !(entry.resources_added.is_empty() && entry.resources_removed.is_empty()) {
+            lines.push("**Resources:**".to_string());
+            lines.push(String::new());
+            lines.extend(entry.resources_added.iter().map(|r| format!("- Added `{}`", r)));
+            lines.extend(entry.resources_removed.iter().map(|r| format!("- Removed `{}`", r)));
+            lines.push(String::new());
+        }
+
+        if !entry.variables_changed.is_empty() {
+            lines.push("**Variables:**".to_string());
+            lines.push(String::new());
+            lines.push("| Variable | Before | After |".to_string());
+            lines.push("|----------|--------|-------|".to_string());
+            for (name, before, after) in &entry.variables_changed {
+                lines.push(format!("| `{}` | {} | {} |", name, cell(before.as_ref()), cell(after.as_ref())));
+            }
+            lines.push(String::new());
+        }
+        while lines.last().is_some_and(String::is_empty) {
+            lines.pop();
+        }
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Generation time (UTC) and revision, e.g. `2026-03-02 08:30 UTC (revision 42)`
+fn describe_generation(manifest: &OutputManifest) -> String {
+    let (year, month, day) = civil_from_days(manifest.generated_at / 86_400);
+    let minutes = manifest.generated_at % 86_400 / 60;
+    let mut description = format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, minutes / 60, minutes % 60);
+    if let Some(revision) = manifest.revision {
+        description.push_str(&format!(" (revision {})", revision));
+    }
+    description
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        None => "-".to_string(),
+        Some(Value::String(s)) => format!("`{}`", s.replace('|', "\\|")),
+        Some(other) => format!("`{}`", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn generation(generated_at: u64, files: &[(&str, &str)], resources: &[&str], size: &str) -> OutputManifest {
+        OutputManifest {
+            data_source: "blueprint".to_string(),
+            id: "b1".to_string(),
+            name: "web-app".to_string(),
+            revision: None,
+            requested_revision: None,
+            updated_at: None,
+            generated_at,
+            cli_version: "0.0.0".to_string(),
+            resources: resources.iter().map(|r| r.to_string()).collect(),
+            files: files.iter().map(|(path, digest)| (path.to_string(), digest.to_string())).collect(),
+            variables: [("resources[0].configuration.size".to_string(), json!(size))].into_iter().collect(),
+            overrides: Default::default(),
+            templates: None,
+            components: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_entry_between_generations() {
+        let older = generation(0, &[("main.tf", "a"), ("old.tf", "b")], &["db", "queue"], "small");
+        let newer = generation(60, &[("main.tf", "c"), ("cache.tf", "d")], &["db", "cache"], "large");
+
+        let entry = ChangelogEntry::between(&older, &newer);
+        assert_eq!(entry.files_added, vec!["cache.tf"]);
+        assert_eq!(entry.files_removed, vec!["old.tf"]);
+        assert_eq!(entry.files_modified, vec!["main.tf"]);
+        assert_eq!(entry.resources_added, vec!["cache"]);
+        assert_eq!(entry.resources_removed, vec!["queue"]);
+        assert_eq!(
+            entry.variables_changed,
+            vec![("resources[0].configuration.size".to_string(), Some(json!("small")), Some(json!("large")))]
+        );
+        assert!(ChangelogEntry::between(&newer, &newer).is_empty());
+    }
+
+    #[test]
+    fn test_render_markdown_newest_first() {
+        let history = vec![
+            generation(1_772_355_600, &[("main.tf", "a")], &["db"], "small"),
+            generation(1_772_359_200, &[("main.tf", "a")], &["db"], "small"),
+            generation(1_772_440_200, &[("main.tf", "b")], &["db"], "large"),
+        ];
+        let markdown = render_markdown(&history);
+        assert!(markdown.starts_with(
+            "## Changes to blueprint `web-app`\n\n\
+             ### 2026-03-01 10:00 UTC → 2026-03-02 08:30 UTC\n\n\
+             **Files:** 0 added, 0 removed, 1 modified\n\n- Modified `main.tf`\n\n\
+             **Variables:**\n"
+        ));
+        assert!(markdown.contains("| `resources[0].configuration.size` | `small` | `large` |"));
+        assert!(markdown.ends_with("### 2026-03-01 09:00 UTC → 2026-03-01 10:00 UTC\n\nNo changes.\n"));
+    }
+}
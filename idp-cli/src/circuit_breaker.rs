@@ -0,0 +1,131 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Default number of consecutive failed API requests that open the circuit
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// Default time an open circuit refuses requests
+pub const DEFAULT_COOL_DOWN: Duration = Duration::from_secs(30);
+
+/// Stops calling an API that keeps failing
+///
+/// After `threshold` consecutive failures, requests are refused without being
+/// sent until `cool_down` has passed, so callers fail at once instead of each
+/// waiting out a timeout. Requests are sent again after that: a success
+/// closes the circuit, and a single failure opens it for another `cool_down`.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cool_down: Duration,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    failures: u32,
+    open_until: Option<Instant>,
+    last_error: String,
+}
+
+/// A request refused because the circuit is open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CircuitOpen {
+    /// Consecutive failures that opened the circuit
+    pub failures: u32,
+    /// Time left until a request is let through again
+    pub retry_after: Duration,
+    /// Error of the last failed request
+    pub last_error: String,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOL_DOWN)
+    }
+}
+
+impl CircuitBreaker {
+    /// A breaker opening after `threshold` consecutive failures; 0 never opens
+    pub fn new(threshold: u32, cool_down: Duration) -> Self {
+        Self { threshold, cool_down, state: Mutex::new(CircuitState::default()) }
+    }
+
+    /// Whether a request may be sent now
+    pub fn check(&self) -> Result<(), CircuitOpen> {
+        self.check_at(Instant::now())
+    }
+
+    fn check_at(&self, now: Instant) -> Result<(), CircuitOpen> {
+        let state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(open_until) if now < open_until => Err(CircuitOpen {
+                failures: state.failures,
+                retry_after: open_until - now,
+                last_error: state.last_error.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a request that reached the API and got an answer other than a server error
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.open_until = None;
+    }
+
+    /// Record a connection error, timeout or server error
+    pub fn record_failure(&self, error: &str) {
+        self.record_failure_at(error, Instant::now());
+    }
+
+    fn record_failure_at(&self, error: &str, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.failures += 1;
+        state.last_error = error.to_string();
+        if self.threshold > 0 && state.failures >= self.threshold {
+            state.open_until = Some(now + self.cool_down);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_consecutive_failures_and_closes_on_success() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(30));
+        let start = Instant::now();
+        breaker.record_failure_at("timed out", start);
+        breaker.record_failure_at("timed out", start);
+        breaker.record_success();
+        breaker.record_failure_at("timed out", start);
+        breaker.record_failure_at("timed out", start);
+        assert!(breaker.check_at(start).is_ok(), "a success resets the count");
+
+        breaker.record_failure_at("connection refused", start);
+        let open = breaker.check_at(start + Duration::from_secs(10)).unwrap_err();
+        assert_eq!(
+            open,
+            CircuitOpen { failures: 3, retry_after: Duration::from_secs(20), last_error: "connection refused".to_string() }
+        );
+
+        // After the cool-down requests are let through; a failure opens the circuit again
+        let later = start + Duration::from_secs(30);
+        assert!(breaker.check_at(later).is_ok());
+        breaker.record_failure_at("connection refused", later);
+        assert!(breaker.check_at(later + Duration::from_secs(1)).is_err());
+        breaker.record_success();
+        assert!(breaker.check_at(later + Duration::from_secs(1)).is_ok());
+    }
+
+    #[test]
+    fn test_zero_threshold_never_opens() {
+        let breaker = CircuitBreaker::new(0, Duration::from_secs(30));
+        for _ in 0..10 {
+            breaker.record_failure("timed out");
+        }
+        assert!(breaker.check().is_ok());
+    }
+}
@@ -1,7 +1,20 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::error::ErrorKind;
+use clap::{Arg, ArgAction, Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 
+use crate::api_client::{parse_header, ListQuery};
+use crate::backend_config::{parse_setting, BackendKind};
+use crate::builtin_templates::BuiltinPack;
+use crate::config_file::{self, ConfigFile};
+use crate::debug_bundle::BundleTarget;
 use crate::error::CliError;
+use crate::post_hooks::HookConfig;
+use crate::reports::ReportTarget;
+use crate::render_sandbox::{self, SandboxPolicy};
+use crate::s3_client::S3Location;
+use crate::validators::ValidatorConfig;
+use crate::variable_context::{VariableContext, VariableContextBuilder, VariableFilter};
 
 /// IDP CLI - Generate infrastructure-as-code from IDP blueprints and stacks using templates
 #[derive(Parser, Debug)]
@@ -45,7 +58,9 @@ ENVIRONMENT VARIABLES:
 CONFIGURATION PRECEDENCE:
   1. Command-line flags (highest priority)
   2. Environment variables
-  3. Default values (lowest priority)
+  3. Project config file (.idp-cli.toml in the current directory or a parent)
+  4. Global config file (~/.idp-cli.toml)
+  5. Default values (lowest priority)
 
 TEMPLATE SYNTAX:
   Variables use {{variable_name}} syntax
@@ -66,16 +81,129 @@ pub struct CliArgs {
     pub api_key: Option<String>,
 
     /// Base URL for IDP API (can also be set via IDP_API_URL environment variable)
-    #[arg(long, env = "IDP_API_URL", default_value = "http://localhost:8082/api/v1")]
+    /// [default: http://localhost:8082/api/v1]
+    #[arg(long, env = "IDP_API_URL")]
     pub api_url: Option<String>,
 
+    /// Extra HTTP header sent with every API request, as NAME=VALUE (repeatable)
+    /// 
+    /// Headers from the [headers] table of the config files are sent too;
+    /// a flag wins over a config header with the same name.
+    /// 
+    /// Example: --header X-Org-Id=payments
+    #[arg(long = "header", value_name = "NAME=VALUE", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Caller added to the user agent sent to the API, after idp-cli/<version>
+    /// (can also be set via IDP_USER_AGENT environment variable)
+    /// 
+    /// Example: payments-ci/1.0
+    #[arg(long, env = "IDP_USER_AGENT")]
+    pub user_agent: Option<String>,
+
+    /// Largest API response body to accept, in bytes (can also be set via
+    /// IDP_MAX_RESPONSE_BYTES environment variable) [default: 67108864]
+    /// 
+    /// Raise it for stacks with many thousands of resources.
+    #[arg(long, value_name = "BYTES", env = "IDP_MAX_RESPONSE_BYTES")]
+    pub max_response_bytes: Option<usize>,
+
     /// Output directory for generated files (can also be set via IDP_OUTPUT_DIR environment variable)
-    #[arg(long, env = "IDP_OUTPUT_DIR", default_value = "./output")]
+    /// [default: ./output]
+    #[arg(long, env = "IDP_OUTPUT_DIR")]
     pub output_dir: Option<PathBuf>,
+
+    /// Show remediation steps and documentation links with errors
+    #[arg(long, global = true, env = "IDP_VERBOSE_ERRORS")]
+    pub verbose_errors: bool,
+
+    /// Line-oriented output without symbols, rules or column alignment (screen-reader friendly)
+    #[arg(long, global = true, env = "IDP_PLAIN")]
+    pub plain: bool,
+
+    /// Print results, and errors, as one JSON document on stdout
+    #[arg(long, global = true, value_enum, env = "IDP_OUTPUT", default_value_t = OutputFormat::Human)]
+    pub output: OutputFormat,
+}
+
+/// Flag printing the build target instead of running a command
+const TARGET_INFO: &str = "target-info";
+
+/// What the command line asks for
+#[derive(Debug)]
+pub enum Invocation {
+    /// Run a command
+    Run(Box<CliArgs>),
+    /// Print the build target (`--target-info`)
+    TargetInfo { output: OutputFormat },
+}
+
+/// Output format shared by all commands (`--output`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Human,
+    /// One JSON document on stdout
+    Json,
+}
+
+/// What generate does when several templates render to the same output path (`--on-conflict`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Use the template from the highest-precedence template set and warn
+    #[default]
+    Precedence,
+    /// Fail, listing every template that renders to the path
+    Error,
+}
+
+/// What generate does with generated files that already exist (`--on-existing`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExistingFilePolicy {
+    /// Replace the file with a warning
+    #[default]
+    Overwrite,
+    /// Keep the existing file and don't write the generated one
+    Skip,
+    /// Move the existing file to <file>.bak.<timestamp>, then write
+    Backup,
+    /// Fail before writing anything if any generated file exists
+    Fail,
+    /// Ask for each existing file
+    Prompt,
+}
+
+/// What generate does when the stack is not in a required state (`--on-state-mismatch`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum StateMismatchPolicy {
+    /// Fail before rendering anything
+    #[default]
+    Error,
+    /// Print a warning and generate anyway
+    Warn,
+}
+
+/// What generate produces from the rendered templates (`--target`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GenerationTarget {
+    /// The rendered files as they are
+    #[default]
+    Raw,
+    /// A Helm chart: Chart.yaml, values.yaml and the YAML manifests under templates/
+    Helm,
+    /// The rendered files plus a Crossplane managed resource per resource under crossplane/
+    Crossplane,
+    /// The rendered files plus a Pulumi.yaml program declaring the resources
+    PulumiYaml,
+    /// The rendered files plus a CDKTF TypeScript project (cdktf.json, main.ts) declaring the resources
+    CdktfTypescript,
+    /// The rendered files plus a CDKTF Python project (cdktf.json, main.py) declaring the resources
+    CdktfPython,
 }
 
 /// Data source type for template generation
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
 pub enum DataSource {
     /// Use a blueprint as the data source
     Blueprint,
@@ -92,6 +220,571 @@ impl std::fmt::Display for DataSource {
     }
 }
 
+/// Variable value type used to filter list-variables output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum VariableType {
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Null,
+}
+
+impl VariableType {
+    /// Check whether a JSON value is of this type
+    pub fn matches(&self, value: &serde_json::Value) -> bool {
+        matches!(
+            (self, value),
+            (VariableType::String, serde_json::Value::String(_))
+                | (VariableType::Number, serde_json::Value::Number(_))
+                | (VariableType::Boolean, serde_json::Value::Bool(_))
+                | (VariableType::Array, serde_json::Value::Array(_))
+                | (VariableType::Object, serde_json::Value::Object(_))
+                | (VariableType::Null, serde_json::Value::Null)
+        )
+    }
+}
+
+/// Output format for the list-variables command
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum VariableFormat {
+    /// Human-readable tree grouped by root variable
+    #[default]
+    Tree,
+    /// Markdown table for documentation
+    Markdown,
+    /// CSV for spreadsheets
+    Csv,
+    /// JSON Schema describing the variable structure
+    JsonSchema,
+}
+
+/// Additional options for the generate command
+#[derive(Args, Debug, Clone, Default)]
+pub struct GenerateOptions {
+    /// Download templates from S3 instead of reading --template-dir
+    /// 
+    /// The template tree under the prefix is downloaded to a temporary
+    /// directory for the run and takes precedence over --template-dir and
+    /// IDP_TEMPLATE_DIR. AWS credentials and region come from the standard
+    /// AWS environment.
+    /// 
+    /// Example: s3://idp-templates/terraform/web-app
+    #[arg(long, value_name = "S3_URL", value_parser = S3Location::parse)]
+    pub template_source: Option<S3Location>,
+
+    /// Render a template pack compiled into the CLI instead of --template-dir
+    /// 
+    /// Takes precedence over --template-dir and IDP_TEMPLATE_DIR. Pack
+    /// settings, such as `compute: eks` or `region`, are variables, set in the
+    /// variables file or a config file.
+    /// 
+    /// Example: aws-webservice
+    #[arg(long, value_enum, value_name = "PACK", conflicts_with = "template_source")]
+    pub builtin_templates: Option<BuiltinPack>,
+
+    /// Also upload the generated files to this S3 location
+    /// 
+    /// Each file keeps its path relative to the output directory under the
+    /// prefix and gets a content type from its extension. A manifest object
+    /// listing every uploaded key is written last, at
+    /// <prefix>/.idp-cli/upload-manifest.json. AWS credentials and region come
+    /// from the standard AWS environment.
+    /// 
+    /// Example: s3://idp-outputs/payments/dev
+    #[arg(long, value_name = "S3_URL", value_parser = S3Location::parse)]
+    pub upload_to: Option<S3Location>,
+
+    /// Encrypt uploaded files with this KMS key (ID, alias or ARN)
+    #[arg(long, value_name = "KEY", requires = "upload_to")]
+    pub upload_kms_key: Option<String>,
+
+    /// Fetch this revision of the blueprint or stack instead of the latest
+    /// 
+    /// Same as writing the identifier as <name>@<revision>. The revision is
+    /// recorded in <output-dir>/.idp-cli/manifest.json so the output can be
+    /// regenerated from the same data later. Needs an API that versions
+    /// blueprints and stacks.
+    /// 
+    /// Example: 42
+    #[arg(long, value_name = "REVISION")]
+    pub revision: Option<u64>,
+
+    /// Render templates and show what would change in the output directory without writing
+    /// 
+    /// Prints each file's path and size, whether it is new, modified or
+    /// unchanged, and a unified diff for modified files. No run summary or
+    /// idempotency record is written.
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print a unified diff of the rendered output against the output directory and fail if it differs
+    /// 
+    /// Nothing is written, as with --dry-run. New files are diffed against
+    /// /dev/null, so the output can be applied with `git apply`. Exits with
+    /// error IDP018 when any file would change, for drift checks in CI.
+    #[arg(long)]
+    pub diff: bool,
+
+    /// Write output under a subdirectory named after the value of this variable
+    /// 
+    /// Any variable path can be used. Generated files are written to
+    /// <output-dir>/<value>/..., so runs for different owners or teams can share
+    /// one output directory.
+    /// 
+    /// Example: stack.owner
+    #[arg(long, value_name = "VARIABLE")]
+    pub partition_by: Option<String>,
+
+    /// Where to write run metrics (default: <output-dir>/run-summary.json)
+    #[arg(long, value_name = "FILE")]
+    pub summary_file: Option<PathBuf>,
+
+    /// Also write output validation failures and policy violations to a report file
+    /// 
+    /// `sarif=PATH` writes SARIF for GitHub code scanning and other tools that
+    /// annotate pull requests; `junit=PATH` writes JUnit XML with a test case
+    /// per template. Reports are written whether or not generation succeeds.
+    /// 
+    /// Example: sarif=findings.sarif
+    #[arg(long, value_name = "FORMAT=PATH", value_parser = ReportTarget::parse)]
+    pub report: Vec<ReportTarget>,
+
+    /// Print newline-delimited JSON progress events to stderr
+    /// 
+    /// Each line is an object with an "event" field, e.g.
+    /// {"event":"file_rendered","path":"main.tf","bytes":120}
+    #[arg(long)]
+    pub progress_json: bool,
+
+    /// Skip generation when a run with this key already completed in the output directory
+    /// 
+    /// Retries with the same key leave the existing outputs untouched. Reusing a
+    /// key for a different request is an error.
+    /// 
+    /// Example: webhook-delivery-8f2c
+    #[arg(long, value_name = "KEY")]
+    pub idempotency_key: Option<String>,
+
+    /// On failure, write a debug bundle to this directory or s3://bucket/prefix
+    /// 
+    /// The bundle holds the variable context with sensitive values masked, the
+    /// templates and the error, and can be re-rendered with `idp-cli replay`.
+    #[arg(long, value_name = "DIR|S3_URL", value_parser = BundleTarget::parse)]
+    pub debug_bundle: Option<BundleTarget>,
+
+    /// Also write an annotated copy of each file to <output-dir>/.idp-cli/debug-render/
+    /// 
+    /// Comments in the copy mark which template expression produced each
+    /// value and what it resolved to, and where each block starts and ends.
+    #[arg(long)]
+    pub debug_render: bool,
+
+    /// Fail when a template references an undefined variable
+    /// 
+    /// Without it, undefined variables render as empty strings. Arguments of
+    /// helpers such as `default` and `if` may still be undefined.
+    #[arg(long)]
+    pub strict_variables: bool,
+
+    /// YAML file of custom template helpers, mapping each helper name to its definition
+    /// 
+    /// Values are Handlebars definitions or `rhai:` scripts. Helpers are also
+    /// loaded from <template-dir>/helpers/<name>.rhai and <name>.hbs; entries
+    /// in this file replace ones with the same name. Keep the file outside
+    /// the template directory, or it is rendered as a template too.
+    #[arg(long, value_name = "FILE", env = "IDP_HELPERS_FILE")]
+    pub helpers_file: Option<PathBuf>,
+
+    /// Number of templates to render at once (default: number of CPUs)
+    /// 
+    /// Files are written in the same order whatever the concurrency.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: Option<u16>,
+
+    /// Fail a template file that takes longer than this many seconds to render (default: 30, 0 for no limit)
+    #[arg(long, value_name = "SECONDS", env = "IDP_RENDER_TIMEOUT")]
+    pub render_timeout: Option<u64>,
+
+    /// Environment variable templates may read with {{env_var "NAME"}}; PREFIX_* allows a prefix
+    /// 
+    /// Repeat the flag or separate names with commas. Without it the env_var
+    /// helper fails, so templates and helpers can't read secrets from the
+    /// environment.
+    #[arg(long, value_name = "NAME", value_delimiter = ',')]
+    pub allow_env: Vec<String>,
+
+    /// File or directory templates may read with {{read_file "path"}}
+    /// 
+    /// Repeat the flag for several paths. Paths are resolved before they are
+    /// compared, so symlinks and `..` can't reach outside them.
+    #[arg(long, value_name = "PATH")]
+    pub allow_file: Vec<PathBuf>,
+
+    /// YAML file that customizes the next-steps guidance printed after generation
+    /// 
+    /// Sections replace built-in ones with the same id (terraform, kubernetes,
+    /// kustomize, helm, pulumi, json) or add org-specific instructions.
+    #[arg(long, value_name = "FILE", env = "IDP_GUIDANCE_FILE")]
+    pub guidance_file: Option<PathBuf>,
+
+    /// YAML file of organization quotas to check the generated resources against
+    /// 
+    /// Each quota counts a Terraform resource type or Kubernetes kind, or sums
+    /// one of its numeric attributes, and warns or fails when the generated
+    /// output goes over the limit.
+    #[arg(long, value_name = "FILE", env = "IDP_QUOTAS_FILE")]
+    pub quotas_file: Option<PathBuf>,
+
+    /// Directory of Rego policies to check the rendered files against before they are written
+    /// 
+    /// Messages of `deny` rules fail the run and nothing is written; messages
+    /// of `warn` rules are reported as warnings.
+    #[arg(long, value_name = "DIR", env = "IDP_POLICY_DIR")]
+    pub policy_dir: Option<PathBuf>,
+
+    /// Directory of JSON Schemas to validate rendered .json files against
+    /// 
+    /// The schema for `config/app.json` is `config/app.schema.json` in this
+    /// directory, or else `app.schema.json`. A template can name its own
+    /// schema in a leading `{{!-- schema: path --}}` comment instead.
+    #[arg(long, value_name = "DIR", env = "IDP_SCHEMA_DIR")]
+    pub schema_dir: Option<PathBuf>,
+
+    /// Don't compare installed terraform/tofu/kubectl/helm versions with the generated syntax
+    #[arg(long)]
+    pub skip_tool_check: bool,
+
+    /// Run these hooks in the output directory after the files are written
+    /// 
+    /// `fmt` runs `tofu fmt`, `validate` runs `tofu init -backend=false` and
+    /// `tofu validate` (terraform when tofu isn't installed), and `tflint`
+    /// runs tflint. Other names run the command defined under [hooks.<name>]
+    /// in a config file. Defaults to `post_hooks` from the config files.
+    /// 
+    /// Example: fmt,validate
+    #[arg(long, value_name = "HOOK", value_delimiter = ',')]
+    pub post_hook: Vec<String>,
+
+    /// Fail when a post-generation hook exits with a non-zero status, instead of warning
+    #[arg(long)]
+    pub strict_hooks: bool,
+
+    /// Don't check aws_region, azure_location, gcp_region and resource region values against known regions
+    /// 
+    /// Use this for regions newer than this version of the CLI.
+    #[arg(long)]
+    pub skip_region_check: bool,
+
+    /// Also write backend.tf configuring this Terraform state backend
+    /// 
+    /// The state key defaults to one per stack (the blueprint name for
+    /// blueprints), and S3 state is encrypted. Settings come from
+    /// --backend-config.
+    #[arg(long, value_enum, value_name = "BACKEND")]
+    pub backend: Option<BackendKind>,
+
+    /// Setting of the --backend block as NAME=VALUE; repeat for several
+    /// 
+    /// Values may use template variables. Required: bucket for s3 and gcs,
+    /// storage_account_name and container_name for azurerm.
+    /// 
+    /// Example: bucket=tf-state, dynamodb_table=tf-locks, key=apps/{{stack.name}}.tfstate
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_setting, requires = "backend")]
+    pub backend_config: Vec<(String, String)>,
+
+    /// Apply the template overlays for this environment
+    /// 
+    /// Files in <template-dir>/overlays/<ENV>/ replace the base templates with
+    /// the same relative path, and are added where there is none. Overlays of
+    /// other environments are never rendered.
+    /// 
+    /// Example: prod
+    #[arg(long, value_name = "ENV", env = "IDP_ENVIRONMENT")]
+    pub environment: Option<String>,
+
+    /// What to do when several templates render to the same output path
+    /// 
+    /// With `precedence`, a file in a higher-precedence template set replaces the
+    /// same file from a lower one (or the template root) with a warning; `error`
+    /// fails instead. Paths that differ only in case always fail, since they
+    /// overwrite each other on case-insensitive file systems.
+    #[arg(long, value_enum, default_value_t = ConflictPolicy::Precedence)]
+    pub on_conflict: ConflictPolicy,
+
+    /// What to do when a generated file already exists in the output directory
+    /// 
+    /// `backup` moves a changed file to <file>.bak.<timestamp> first; `fail`
+    /// checks every file before writing any; `prompt` asks on the terminal.
+    #[arg(long, value_enum, default_value_t = ExistingFilePolicy::Overwrite)]
+    pub on_existing: ExistingFilePolicy,
+
+    /// What to generate from the rendered templates
+    /// 
+    /// `raw` writes the rendered files as they are. `helm` packages them as a
+    /// Helm chart: YAML manifests go under templates/, next to a Chart.yaml
+    /// named after the stack or blueprint and a values.yaml holding each
+    /// resource's configuration. `crossplane` also writes a Crossplane managed
+    /// resource for each blueprint or stack resource to crossplane/<name>.yaml,
+    /// `pulumi-yaml` writes a Pulumi.yaml program declaring them, and
+    /// `cdktf-typescript`/`cdktf-python` a CDKTF project skeleton.
+    #[arg(long, value_enum, default_value_t = GenerationTarget::Raw)]
+    pub target: GenerationTarget,
+
+    /// Write into the output directory even if it looks unsafe
+    /// 
+    /// Without it, generation refuses to write into the file system root, the
+    /// home directory, or a git repository with uncommitted changes.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Only generate from a stack in one of these states (stacks only)
+    /// 
+    /// The stack's state is fetched from the API before rendering and compared
+    /// case-insensitively, so a stack that is still provisioning or has failed
+    /// is not turned into code.
+    /// 
+    /// Example: ACTIVE
+    #[arg(long, value_name = "STATE", value_delimiter = ',')]
+    pub require_state: Vec<String>,
+
+    /// What to do when the stack is not in a state given with --require-state
+    #[arg(long, value_enum, default_value_t = StateMismatchPolicy::Error, requires = "require_state")]
+    pub on_state_mismatch: StateMismatchPolicy,
+
+    /// Default variables from the config files, filled in by `CliArgs::apply_config`
+    #[arg(skip)]
+    pub default_variables: serde_json::Map<String, serde_json::Value>,
+
+    /// Blueprints merged by the compose command; empty for generate
+    #[arg(skip)]
+    pub composed_blueprints: Vec<String>,
+
+    /// Output validators from the config files, filled in by `CliArgs::apply_config`
+    #[arg(skip)]
+    pub validators: BTreeMap<String, ValidatorConfig>,
+
+    /// Post-generation hook definitions from the config files, filled in by `CliArgs::apply_config`
+    #[arg(skip)]
+    pub hooks: BTreeMap<String, HookConfig>,
+}
+
+impl GenerateOptions {
+    /// Whether the run only compares rendered output with the output directory (`--dry-run` or `--diff`)
+    pub fn is_preview(&self) -> bool {
+        self.dry_run || self.diff
+    }
+
+    /// Templates to render at once, from `--concurrency`
+    pub fn concurrency(&self) -> usize {
+        self.concurrency.map(usize::from).unwrap_or_else(crate::pipeline::default_concurrency)
+    }
+
+    /// Render timeout and allowlists from `--render-timeout`, `--allow-env` and `--allow-file`
+    pub fn sandbox_policy(&self) -> SandboxPolicy {
+        SandboxPolicy {
+            render_timeout: match self.render_timeout {
+                Some(secs) => render_sandbox::timeout(secs),
+                None => Some(render_sandbox::DEFAULT_RENDER_TIMEOUT),
+            },
+            allowed_env: self.allow_env.clone(),
+            allowed_files: self.allow_file.clone(),
+        }
+    }
+
+    /// Split a pinned identifier such as `my-app@42` and combine it with `--revision`
+    pub fn pinned_identifier<'a>(&self, identifier: &'a str) -> Result<(&'a str, Option<u64>), CliError> {
+        let (name, pinned) = split_revision(identifier);
+        match (pinned, self.revision) {
+            (Some(pinned), Some(revision)) if pinned != revision => Err(CliError::ConfigurationError(format!(
+                "Identifier '{}' pins revision {} but --revision is {}",
+                identifier, pinned, revision
+            ))),
+            (pinned, revision) => Ok((name, pinned.or(revision))),
+        }
+    }
+}
+
+/// Split `<identifier>@<revision>`; identifiers without a numeric suffix are returned as is
+pub fn split_revision(identifier: &str) -> (&str, Option<u64>) {
+    match identifier.rsplit_once('@') {
+        Some((name, revision)) if !name.is_empty() => match revision.parse() {
+            Ok(revision) => (name, Some(revision)),
+            Err(_) => (identifier, None),
+        },
+        _ => (identifier, None),
+    }
+}
+
+/// Listeners for the serve command
+#[derive(Args, Debug, Clone, Default)]
+pub struct ServeOptions {
+    /// Address for the gRPC service (Generate, ListVariables and Validate RPCs)
+    /// 
+    /// Example: 0.0.0.0:7070
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR", value_parser = parse_listen_addr)]
+    pub grpc: Option<std::net::SocketAddr>,
+
+    /// Address for the HTTP API, serving the same requests as the Lambda handler
+    /// 
+    /// Example: :8080 (all interfaces) or 127.0.0.1:8080
+    #[cfg(feature = "http")]
+    #[arg(long, value_name = "ADDR", value_parser = parse_listen_addr)]
+    pub http: Option<std::net::SocketAddr>,
+}
+
+/// Parse a listen address, where a bare `:PORT` means all interfaces
+pub fn parse_listen_addr(value: &str) -> Result<std::net::SocketAddr, String> {
+    let value = if value.starts_with(':') {
+        format!("0.0.0.0{}", value)
+    } else {
+        value.to_string()
+    };
+    value
+        .parse()
+        .map_err(|e| format!("invalid listen address '{}': {}", value, e))
+}
+
+/// Parse a `--var NAME=VALUE` argument; dots in NAME separate nested keys
+pub fn parse_var(value: &str) -> Result<(String, String), String> {
+    let (name, value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected NAME=VALUE, got '{}'", value))?;
+    let name = name.trim();
+    if name.split('.').any(str::is_empty) {
+        return Err(format!("'{}' is not a valid variable name", name));
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Output format for the describe and search commands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DescribeFormat {
+    /// Human-readable summary
+    #[default]
+    Human,
+    /// JSON document
+    Json,
+}
+
+/// Output format for the list-blueprints and list-stacks commands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ListFormat {
+    /// Aligned table
+    #[default]
+    Table,
+    /// JSON array
+    Json,
+}
+
+/// Filtering and output options for the list-blueprints and list-stacks commands
+#[derive(Args, Debug, Clone, Default)]
+pub struct ListOptions {
+    /// Only show entries whose name or description contains this text (case-insensitive)
+    /// 
+    /// For stacks the stack type and cloud name are matched too.
+    #[arg(long, value_name = "TEXT")]
+    pub filter: Option<String>,
+
+    /// Show at most this many entries
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+    pub format: ListFormat,
+}
+
+impl ListOptions {
+    /// Build the API list query from the options
+    pub fn to_query(&self) -> ListQuery {
+        ListQuery {
+            filter: self.filter.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+/// Filtering and output options for the list-variables command
+#[derive(Args, Debug, Clone, Default)]
+pub struct ListVariablesOptions {
+    /// Only show variables under this path; `*` matches any single segment
+    /// 
+    /// Example: resources.*.cloud_provider
+    #[arg(long)]
+    pub filter: Option<String>,
+
+    /// Only show variables whose name or string value contains this text (case-insensitive)
+    #[arg(long)]
+    pub search: Option<String>,
+
+    /// Only show variables with at most this many path segments
+    #[arg(long)]
+    pub max_depth: Option<usize>,
+
+    /// Only show variables of this type
+    #[arg(long = "type", value_enum)]
+    pub value_type: Option<VariableType>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = VariableFormat::Tree)]
+    pub format: VariableFormat,
+}
+
+impl ListVariablesOptions {
+    /// Build the variable filter described by these options
+    pub fn to_filter(&self) -> VariableFilter {
+        VariableFilter {
+            pattern: self.filter.clone(),
+            search: self.search.clone(),
+            max_depth: self.max_depth,
+            value_type: self.value_type,
+        }
+    }
+}
+
+/// Custom variables merged over the blueprint or stack data
+///
+/// Variables files are merged in the order given, then `--var` assignments,
+/// so later files override earlier ones and `--var` overrides them all.
+#[derive(Args, Debug, Clone, Default)]
+pub struct VariableInputs {
+    /// Variables file (JSON, YAML or .tfvars) to merge with the blueprint/stack data; repeat for several
+    /// 
+    /// Custom variables override blueprint/stack variables, and later files
+    /// override earlier ones. Also accepted as --variables-file.
+    /// 
+    /// Example: --var-file common.yaml --var-file prod.yaml
+    #[arg(long = "var-file", visible_alias = "variables-file", value_name = "FILE")]
+    pub var_files: Vec<PathBuf>,
+
+    /// Set a variable as NAME=VALUE, overriding the variables files; repeat for several
+    /// 
+    /// Values are strings. Dots in NAME set nested values.
+    /// 
+    /// Example: --var environment=production --var tags.team=payments
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+    pub vars: Vec<(String, String)>,
+}
+
+impl VariableInputs {
+    pub fn is_empty(&self) -> bool {
+        self.var_files.is_empty() && self.vars.is_empty()
+    }
+
+    /// Merge the variables files in order, then the `--var` assignments
+    pub fn merge_into(&self, context: &mut VariableContext) -> Result<(), CliError> {
+        for vars_file in &self.var_files {
+            VariableContextBuilder::merge_custom_variables(context, vars_file)?;
+        }
+        VariableContextBuilder::merge_assignments(context, &self.vars)
+    }
+}
+
+// Parsed once per run, so the size of the generate options doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Command {
     /// Generate infrastructure-as-code from templates using blueprint or stack data
@@ -128,16 +821,43 @@ pub enum Command {
         /// 
         /// Example: ./templates/terraform
         #[arg(long, env = "IDP_TEMPLATE_DIR")]
-        template_dir: PathBuf,
+        template_dir: Option<PathBuf>,
         
-        /// Optional variables file (JSON or YAML) to merge with blueprint/stack data
-        /// 
-        /// Custom variables will override blueprint/stack variables when conflicts occur.
-        /// Supports both JSON and YAML formats.
+        #[command(flatten)]
+        variables: VariableInputs,
+
+        #[command(flatten)]
+        options: GenerateOptions,
+    },
+    /// Generate one output project from several blueprints
+    /// 
+    /// The blueprints are merged into one variable context: `resources` holds the
+    /// resources of all of them, each blueprint's variables stay available under
+    /// `blueprints.<name>`, and `providers.<name>.version` combines the provider
+    /// version constraints of all blueprints. Accepts the same options as generate.
+    /// 
+    /// EXAMPLES:
+    ///   # Compose a platform from three blueprints
+    ///   idp-cli compose --blueprint networking --blueprint app --blueprint observability --template-dir ./templates
+    /// 
+    ///   # Pin one blueprint to a revision
+    ///   idp-cli compose --blueprint networking@12 --blueprint app --template-dir ./templates
+    Compose {
+        /// Blueprint identifier (name or UUID, optionally `@<revision>`); repeat for each blueprint
+        #[arg(long = "blueprint", value_name = "IDENTIFIER", required = true)]
+        blueprints: Vec<String>,
+
+        /// Template directory containing template files
         /// 
-        /// Example: ./custom-vars.yaml
-        #[arg(long)]
-        variables_file: Option<PathBuf>,
+        /// Can also be set via IDP_TEMPLATE_DIR environment variable.
+        #[arg(long, env = "IDP_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        variables: VariableInputs,
+
+        #[command(flatten)]
+        options: GenerateOptions,
     },
     /// List available variables from a blueprint or stack
     /// 
@@ -151,6 +871,12 @@ pub enum Command {
     /// 
     ///   # List stack variables
     ///   idp-cli list-variables stack my-prod-stack
+    /// 
+    ///   # Export a variable catalog for documentation
+    ///   idp-cli list-variables stack my-prod-stack --format markdown > VARIABLES.md
+    /// 
+    ///   # Only show cloud providers of every resource
+    ///   idp-cli list-variables blueprint web-app --filter 'resources.*.cloud_provider'
     ListVariables {
         /// Data source type (blueprint or stack)
         #[arg(value_enum)]
@@ -160,12 +886,484 @@ pub enum Command {
         /// 
         /// Can be either the human-readable name or the UUID of the blueprint/stack.
         identifier: String,
+
+        #[command(flatten)]
+        variables: VariableInputs,
+
+        #[command(flatten)]
+        options: ListVariablesOptions,
+    },
+    /// Show a structured summary of a blueprint or stack
+    /// 
+    /// Prints metadata, resources grouped by cloud provider and resource type,
+    /// configuration highlights and the linked blueprint (for stacks).
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli describe stack payments-prod
+    ///   idp-cli describe blueprint web-app --format json
+    Describe {
+        /// Data source type (blueprint or stack)
+        #[arg(value_enum)]
+        data_source: DataSource,
+
+        /// Blueprint or stack identifier (name or UUID)
+        identifier: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// Preview how a draft revision changes the generated output before publishing it
+    /// 
+    /// Renders the templates for the given revision and for the published
+    /// (latest) revision in temporary directories and prints a unified diff
+    /// between them. Nothing is written to the output directory.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli preview blueprint web-app --revision draft --template-dir ./templates
+    ///   idp-cli preview stack payments-prod --revision 43 --against 42 --template-dir ./templates
+    Preview {
+        /// Data source type (blueprint or stack)
+        #[arg(value_enum)]
+        data_source: DataSource,
+
+        /// Blueprint or stack identifier (name or UUID)
+        identifier: String,
+
+        /// Revision to preview: a revision number or a name such as draft
+        #[arg(long)]
+        revision: String,
+
+        /// Revision to compare with (default: the published revision)
+        #[arg(long, value_name = "REVISION")]
+        against: Option<String>,
+
+        /// Directory containing template files
+        #[arg(long, env = "IDP_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        variables: VariableInputs,
+    },
+    /// Summarize what changed between recent generations as markdown
+    /// 
+    /// Reads the manifest history that generate keeps in
+    /// `.idp-cli/history/` in the output directory and lists files added,
+    /// removed and modified, resources added and removed, and variable value
+    /// changes, newest first. Runs offline; the output suits PR descriptions.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli changelog --output-dir ./generated
+    ///   idp-cli changelog --output-dir ./generated --last 5 > CHANGES.md
+    Changelog {
+        /// Number of generations to compare (at least 2)
+        #[arg(long, default_value_t = 2)]
+        last: usize,
+    },
+    /// Check whether generated output is behind the blueprint or stack it came from
+    /// 
+    /// Reads the manifest that generate writes to `.idp-cli/manifest.json` in the
+    /// output directory (or its partition subdirectories), fetches the current
+    /// revision and fails when the server's `updated_at` differs from the recorded one.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli check-freshness --output-dir ./generated
+    ///   idp-cli check-freshness --output-dir ./generated --format json
+    CheckFreshness {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// Search blueprints and stacks by provider, resource type and name
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli search --provider AWS --resource-type RelationalDatabaseServer --name-contains pay
+    ///   idp-cli search --data-source stack --provider Azure
+    Search {
+        /// Only search blueprints or only search stacks (default: both)
+        #[arg(long, value_enum)]
+        data_source: Option<DataSource>,
+
+        /// Cloud provider name or display name (case-insensitive)
+        #[arg(long)]
+        provider: Option<String>,
+
+        /// Resource type name (case-insensitive)
+        #[arg(long)]
+        resource_type: Option<String>,
+
+        /// Only match blueprints/stacks whose name contains this text (case-insensitive)
+        #[arg(long)]
+        name_contains: Option<String>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// List blueprints visible to the API key
+    /// 
+    /// Use the name or ID from this list with generate, list-variables or describe.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli list-blueprints
+    ///   idp-cli list-blueprints --filter web --limit 10 --format json
+    ListBlueprints {
+        #[command(flatten)]
+        options: ListOptions,
+    },
+    /// List stacks visible to the API key
+    /// 
+    /// Use the name or ID from this list with generate, list-variables or describe.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli list-stacks
+    ///   idp-cli list-stacks --filter prod --format json
+    ListStacks {
+        #[command(flatten)]
+        options: ListOptions,
+    },
+    /// Inspect the deployment state of a stack through the IDP API
+    /// 
+    /// Shows what the web UI shows for a stack: its state, the state of each
+    /// of its resources, or its event log.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli stack status payments-prod
+    ///   idp-cli stack resources payments-prod --format json
+    ///   idp-cli stack events payments-prod --limit 20
+    Stack {
+        #[command(subcommand)]
+        action: StackAction,
+    },
+    /// Trace a single variable from the API through variable merging into templates
+    ///
+    /// Shows which part of the blueprint or stack the variable comes from, its
+    /// value before and after merging a variables file, the value templates see,
+    /// and every template line that references it (directly, through an
+    /// enclosing `{{#each}}`/`{{#with}}` block, or via a parent object).
+    ///
+    /// EXAMPLES:
+    ///   idp-cli trace-var blueprint web-app 'resources[0].configuration.engine' --template-dir ./templates
+    ///   idp-cli trace-var stack payments-prod stack.name --variables-file vars.yaml --format json
+    TraceVar {
+        /// Data source type (blueprint or stack)
+        #[arg(value_enum)]
+        data_source: DataSource,
+
+        /// Blueprint or stack identifier (name or UUID)
+        identifier: String,
+
+        /// Variable path, as shown by list-variables (e.g. resources[0].configuration.engine)
+        variable: String,
+
+        /// Template directory to search for references
+        #[arg(long, env = "IDP_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        variables: VariableInputs,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// Check templates against the variables a blueprint or stack provides
+    ///
+    /// Parses every template and reports syntax errors such as unclosed
+    /// blocks, references to variables the data source doesn't define, and
+    /// calls to unknown helpers, with file and line. References guarded by
+    /// `{{#if}}`, `{{#unless}}` or `{{default}}` are treated as optional.
+    /// Exits with an error if any problems are found.
+    ///
+    /// EXAMPLES:
+    ///   idp-cli lint-templates blueprint web-app --template-dir ./templates
+    ///   idp-cli lint-templates stack payments-prod --variables-file vars.yaml --format json
+    LintTemplates {
+        /// Data source type (blueprint or stack)
+        #[arg(value_enum)]
+        data_source: DataSource,
+
+        /// Blueprint or stack identifier (name or UUID)
+        identifier: String,
+
+        /// Template directory to lint
+        #[arg(long, env = "IDP_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        variables: VariableInputs,
+
+        /// YAML file defining custom helpers, as in generate
+        #[arg(long, value_name = "FILE", env = "IDP_HELPERS_FILE")]
+        helpers_file: Option<PathBuf>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+
+        /// Also write the problems to a report file: sarif=PATH, or junit=PATH with a test case per template
+        #[arg(long, value_name = "FORMAT=PATH", value_parser = ReportTarget::parse)]
+        report: Vec<ReportTarget>,
+    },
+    /// Render template snippets interactively against a blueprint or stack
+    ///
+    /// Fetches the blueprint or stack once, then renders each snippet typed at
+    /// the prompt against its variables. Arrow keys recall earlier input;
+    /// `:vars`, `:helpers` and `:history` list what is available, `:help` shows
+    /// all commands.
+    ///
+    /// EXAMPLES:
+    ///   idp-cli repl --blueprint web-app
+    ///   idp-cli repl --stack payments-prod --template-dir ./templates
+    Repl {
+        /// Blueprint to render against (name or UUID)
+        #[arg(long, value_name = "ID", required_unless_present = "stack", conflicts_with = "stack")]
+        blueprint: Option<String>,
+
+        /// Stack to render against (name or UUID)
+        #[arg(long, value_name = "ID")]
+        stack: Option<String>,
+
+        /// Template directory whose helpers/ are loaded, as in generate
+        #[arg(long, env = "IDP_TEMPLATE_DIR")]
+        template_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        variables: VariableInputs,
+
+        /// YAML file defining custom helpers, as in generate
+        #[arg(long, value_name = "FILE", env = "IDP_HELPERS_FILE")]
+        helpers_file: Option<PathBuf>,
+    },
+    /// List the variables, helpers and blocks a single template uses
+    ///
+    /// Parses the template without fetching any data, so it runs offline.
+    /// References inside `{{#each}}` and `{{#with}}` are resolved to absolute
+    /// variable paths, e.g. `resources[*].name`. Meant for editor extensions
+    /// that complete context variables.
+    ///
+    /// EXAMPLES:
+    ///   idp-cli inspect-template templates/main.tf
+    ///   idp-cli inspect-template templates/resources.each.tf --format json
+    InspectTemplate {
+        /// Template file to inspect
+        file: PathBuf,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// Write a built-in template pack into a directory to customize it
+    ///
+    /// The directory works with --template-dir and generates the same files as
+    /// --builtin-templates until its templates are edited. Templates that
+    /// already exist in the directory are not overwritten without --force.
+    ///
+    /// EXAMPLES:
+    ///   idp-cli eject aws-webservice --out ./templates
+    ///   idp-cli eject k8s-microservice --out ./templates/k8s --force
+    Eject {
+        /// Built-in template pack to write
+        #[arg(value_enum)]
+        pack: BuiltinPack,
+
+        /// Directory to write the templates to
+        #[arg(long, value_name = "DIR")]
+        out: PathBuf,
+
+        /// Overwrite templates that already exist in the directory
+        #[arg(long)]
+        force: bool,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// Run a long-lived generation service
+    /// 
+    /// Keeps the API client and fetched blueprint/stack data warm between requests,
+    /// so other services can generate without spawning a process per request.
+    Serve {
+        #[command(flatten)]
+        options: ServeOptions,
+    },
+    /// Re-render the templates in a debug bundle from a failed run
+    /// 
+    /// Runs offline using the bundle's (redacted) variable context, and reports
+    /// which templates fail to render.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli replay idp-debug-payments-prod-1718000000.tar.gz
+    ///   idp-cli replay bundle.tar.gz --write --output-dir ./replayed
+    Replay {
+        /// Debug bundle written by `generate --debug-bundle`
+        bundle: PathBuf,
+
+        /// Write the rendered files to the output directory
+        #[arg(long)]
+        write: bool,
+    },
+    /// View or change settings in the config files
+    /// 
+    /// Settings are read from ~/.idp-cli.toml and from the nearest
+    /// .idp-cli.toml in the current directory or a parent, which wins.
+    /// Environment variables and flags override both.
+    /// 
+    /// EXAMPLES:
+    ///   idp-cli config show
+    ///   idp-cli config set api_url https://idp.example.com/api/v1 --global
+    ///   idp-cli config set variables.environment staging
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
     },
     /// Display version information
     Version,
+    /// Run the generation pipeline against a synthetic workload and print stage timings
+    #[cfg(feature = "bench")]
+    Bench {
+        /// Number of synthetic resources and templates to generate
+        #[arg(long, default_value_t = 1000)]
+        synthetic: usize,
+    },
+}
+
+/// Actions of the config command
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum ConfigAction {
+    /// Show each setting, its value and where it came from
+    Show,
+    /// Set a value in the project config file, or the global one with --global
+    Set {
+        /// api_url, api_key, output_dir, template_dir, user_agent, headers.<name> or variables.<name>
+        key: String,
+
+        /// Value to store; variable values that parse as JSON keep their type
+        value: String,
+
+        /// Write ~/.idp-cli.toml instead of .idp-cli.toml
+        #[arg(long)]
+        global: bool,
+    },
+}
+
+/// What the stack command shows about a stack
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub enum StackAction {
+    /// Show the stack's deployment state
+    Status {
+        /// Stack identifier (name or UUID)
+        name: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// List the stack's resources with their deployment state
+    Resources {
+        /// Stack identifier (name or UUID)
+        name: String,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
+    /// Show the stack's event log, newest first
+    Events {
+        /// Stack identifier (name or UUID)
+        name: String,
+
+        /// Show at most this many events
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DescribeFormat::Human)]
+        format: DescribeFormat,
+    },
 }
 
 impl CliArgs {
+    /// Fill settings that no flag or environment variable set from the config files
+    ///
+    /// Default variables apply to generate, under the API values, as do output
+    /// validators and post-generation hooks; `--post-hook` replaces `post_hooks`.
+    pub fn apply_config(&mut self, config: &ConfigFile) {
+        if self.api_key.is_none() {
+            self.api_key = config.api_key.clone();
+        }
+        if self.api_url.is_none() {
+            self.api_url = config.api_url.clone();
+        }
+        if self.output_dir.is_none() {
+            self.output_dir = config.output_dir.clone();
+        }
+        if self.user_agent.is_none() {
+            self.user_agent = config.user_agent.clone();
+        }
+        for (name, value) in config_file::expand_headers(&config.headers) {
+            if !self.headers.iter().any(|(flag, _)| flag.eq_ignore_ascii_case(&name)) {
+                self.headers.push((name, value));
+            }
+        }
+        match &mut self.command {
+            Command::Generate { template_dir, options, .. } | Command::Compose { template_dir, options, .. } => {
+                // Templates from S3 replace the template directory
+                if template_dir.is_none() && options.template_source.is_none() && options.builtin_templates.is_none() {
+                    *template_dir = config.template_dir.clone();
+                }
+                options.default_variables = config.variables.clone();
+                options.validators = config.validators.clone();
+                if options.post_hook.is_empty() {
+                    options.post_hook = config.post_hooks.clone();
+                }
+                options.hooks = config.hooks.clone();
+            }
+            Command::Preview { template_dir, .. }
+            | Command::TraceVar { template_dir, .. }
+            | Command::LintTemplates { template_dir, .. }
+            | Command::Repl { template_dir, .. } => {
+                *template_dir = template_dir.take().or_else(|| config.template_dir.clone());
+            }
+            _ => {}
+        }
+    }
+
+    /// Parse command-line arguments, answering `--target-info` without a command
+    ///
+    /// `--target-info` prints the build target (architecture, C library, static
+    /// linking), e.g. to confirm that a binary was built for a Lambda target such
+    /// as x86_64-unknown-linux-musl or aarch64-unknown-linux-musl.
+    pub fn parse_invocation() -> Invocation {
+        Self::try_parse_invocation_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// [`CliArgs::parse_invocation`] from the given arguments
+    pub fn try_parse_invocation_from<I, T>(args: I) -> Result<Invocation, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let mut command = Self::command().subcommand_required(false).arg(
+            Arg::new(TARGET_INFO)
+                .long(TARGET_INFO)
+                .action(ArgAction::SetTrue)
+                .help("Print the build target (architecture, C library, static linking) and exit"),
+        );
+        let mut matches = command.try_get_matches_from_mut(args)?;
+        if matches.get_flag(TARGET_INFO) {
+            if let Some((name, _)) = matches.subcommand() {
+                return Err(command.error(
+                    ErrorKind::ArgumentConflict,
+                    format!("--{} can't be combined with the '{}' command", TARGET_INFO, name),
+                ));
+            }
+            let output = matches.get_one::<OutputFormat>("output").copied().unwrap_or_default();
+            return Ok(Invocation::TargetInfo { output });
+        }
+        Self::from_arg_matches_mut(&mut matches).map(|args| Invocation::Run(Box::new(args))).map_err(|e| e.format(&mut command))
+    }
+
     /// Parse command-line arguments and validate required fields
     pub fn parse_and_validate() -> Result<Self, CliError> {
         let args = Self::parse();
@@ -173,17 +1371,49 @@ impl CliArgs {
         Ok(args)
     }
 
+    /// Reject `--output json` for commands whose output is free-form text
+    pub fn check_output_format(&self) -> Result<(), CliError> {
+        if self.output != OutputFormat::Json {
+            return Ok(());
+        }
+        let command = match &self.command {
+            Command::Preview { .. } => "preview",
+            Command::Changelog { .. } => "changelog",
+            Command::Replay { .. } => "replay",
+            Command::Repl { .. } => "repl",
+            Command::Serve { .. } => "serve",
+            #[cfg(feature = "bench")]
+            Command::Bench { .. } => "bench",
+            _ => return Ok(()),
+        };
+        Err(CliError::ConfigurationError(format!("--output json is not supported by the '{}' command", command)))
+    }
+
     /// Validate that required arguments are present
     pub fn validate(&self) -> Result<(), CliError> {
-        // Version command doesn't require API key
-        if matches!(self.command, Command::Version) {
+        // Version and replay commands don't require API key
+        if matches!(
+            self.command,
+            Command::Version
+                | Command::Replay { .. }
+                | Command::Changelog { .. }
+                | Command::InspectTemplate { .. }
+                | Command::Eject { .. }
+                | Command::Config { .. }
+        ) {
+            return Ok(());
+        }
+
+        // Synthetic benchmarks run entirely offline
+        #[cfg(feature = "bench")]
+        if matches!(self.command, Command::Bench { .. }) {
             return Ok(());
         }
 
         // API key is required for Generate and ListVariables commands
         if self.api_key.is_none() {
             return Err(CliError::ConfigurationError(
-                "API key is required. Provide it via --api-key flag or IDP_API_KEY environment variable.".to_string()
+                "API key is required. Provide it via --api-key flag, IDP_API_KEY environment variable, or api_key in .idp-cli.toml.".to_string()
             ));
         }
 
@@ -205,34 +1435,83 @@ impl CliArgs {
             }
         }
 
-        // Validate template directory exists for Generate command
-        if let Command::Generate { template_dir, .. } = &self.command {
-            if !template_dir.exists() {
+        // A template directory can come from a flag, the environment or a config file
+        match &self.command {
+            Command::Generate { template_dir: None, options, .. }
+            | Command::Compose { template_dir: None, options, .. }
+                if options.template_source.is_none() && options.builtin_templates.is_none() =>
+            {
                 return Err(CliError::ConfigurationError(
-                    format!("Template directory does not exist: {}", template_dir.display())
+                    "One of --template-dir, --template-source or --builtin-templates is required".to_string(),
                 ));
             }
-            if !template_dir.is_dir() {
+            Command::Generate { data_source: DataSource::Blueprint, options, .. } | Command::Compose { options, .. }
+                if !options.require_state.is_empty() =>
+            {
                 return Err(CliError::ConfigurationError(
-                    format!("Template path is not a directory: {}", template_dir.display())
+                    "--require-state only applies when generating from a stack".to_string(),
                 ));
             }
+            Command::Generate { data_source: DataSource::Blueprint, options, .. } | Command::Compose { options, .. }
+                if options.builtin_templates.is_some_and(BuiltinPack::needs_stack) =>
+            {
+                return Err(CliError::ConfigurationError(format!(
+                    "The {} template pack renders stack resources; generate from a stack",
+                    options.builtin_templates.map(BuiltinPack::name).unwrap_or_default()
+                )));
+            }
+            Command::Preview { template_dir: None, .. } | Command::LintTemplates { template_dir: None, .. } => {
+                return Err(CliError::ConfigurationError("--template-dir is required".to_string()));
+            }
+            _ => {}
         }
 
-        // Validate variables file exists if provided
-        if let Command::Generate { variables_file: Some(vars_file), .. } = &self.command {
-            if !vars_file.exists() {
+        // Validate template directory exists for Generate command
+        let template_dir = match &self.command {
+            Command::Generate { template_dir, .. } => template_dir.as_ref(),
+            Command::Compose { template_dir, .. } => template_dir.as_ref(),
+            Command::TraceVar { template_dir, .. } => template_dir.as_ref(),
+            Command::LintTemplates { template_dir, .. } => template_dir.as_ref(),
+            Command::Preview { template_dir, .. } => template_dir.as_ref(),
+            Command::Repl { template_dir, .. } => template_dir.as_ref(),
+            _ => None,
+        };
+        if let Some(template_dir) = template_dir {
+            if !template_dir.exists() {
                 return Err(CliError::ConfigurationError(
-                    format!("Variables file does not exist: {}", vars_file.display())
+                    format!("Template directory does not exist: {}", template_dir.display())
                 ));
             }
-            if !vars_file.is_file() {
+            if !template_dir.is_dir() {
                 return Err(CliError::ConfigurationError(
-                    format!("Variables path is not a file: {}", vars_file.display())
+                    format!("Template path is not a directory: {}", template_dir.display())
                 ));
             }
         }
 
+        // Validate variables files exist if provided
+        if let Command::Generate { variables, .. }
+        | Command::Compose { variables, .. }
+        | Command::ListVariables { variables, .. }
+        | Command::TraceVar { variables, .. }
+        | Command::LintTemplates { variables, .. }
+        | Command::Preview { variables, .. }
+        | Command::Repl { variables, .. } = &self.command
+        {
+            for vars_file in &variables.var_files {
+                if !vars_file.exists() {
+                    return Err(CliError::ConfigurationError(
+                        format!("Variables file does not exist: {}", vars_file.display())
+                    ));
+                }
+                if !vars_file.is_file() {
+                    return Err(CliError::ConfigurationError(
+                        format!("Variables path is not a file: {}", vars_file.display())
+                    ));
+                }
+            }
+        }
+
         Ok(())
     }
 
@@ -241,7 +1520,7 @@ impl CliArgs {
         self.api_key
             .as_deref()
             .ok_or_else(|| CliError::ConfigurationError(
-                "API key is required. Provide it via --api-key flag or IDP_API_KEY environment variable.".to_string()
+                "API key is required. Provide it via --api-key flag, IDP_API_KEY environment variable, or api_key in .idp-cli.toml.".to_string()
             ))
     }
 
@@ -265,6 +1544,15 @@ mod tests {
     use super::*;
     use std::fs;
 
+    #[test]
+    fn test_variable_type_matches() {
+        assert!(VariableType::String.matches(&serde_json::json!("a")));
+        assert!(VariableType::Number.matches(&serde_json::json!(1)));
+        assert!(VariableType::Object.matches(&serde_json::json!({})));
+        assert!(!VariableType::Array.matches(&serde_json::json!({})));
+        assert!(VariableType::Null.matches(&serde_json::Value::Null));
+    }
+
     #[test]
     fn test_version_command_does_not_require_api_key() {
         let args = CliArgs {
@@ -272,11 +1560,30 @@ mod tests {
             api_key: None,
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert!(args.validate().is_ok());
     }
 
+    #[test]
+    fn test_target_info_flag_needs_no_command() {
+        let invocation = CliArgs::try_parse_invocation_from(["idp-cli", "--target-info", "--output", "json"]).unwrap();
+        assert!(matches!(invocation, Invocation::TargetInfo { output: OutputFormat::Json }));
+        let invocation = CliArgs::try_parse_invocation_from(["idp-cli", "version"]).unwrap();
+        assert!(matches!(invocation, Invocation::Run(args) if matches!(args.command, Command::Version)));
+
+        let error = CliArgs::try_parse_invocation_from(["idp-cli", "--target-info", "version"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+        let error = CliArgs::try_parse_invocation_from(["idp-cli", "--plain"]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::MissingSubcommand);
+    }
+
     #[test]
     fn test_generate_command_requires_api_key() {
         // Create a temporary directory for testing
@@ -287,12 +1594,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: temp_dir.clone(),
-                variables_file: None,
+                template_dir: Some(temp_dir.clone()),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: None,
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         let result = args.validate();
@@ -309,10 +1623,18 @@ mod tests {
             command: Command::ListVariables {
                 data_source: DataSource::Stack,
                 identifier: "test-stack".to_string(),
+                variables: VariableInputs::default(),
+                options: ListVariablesOptions::default(),
             },
             api_key: None,
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         let result = args.validate();
@@ -329,12 +1651,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: temp_dir.clone(),
-                variables_file: None,
+                template_dir: Some(temp_dir.clone()),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: Some("   ".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         let result = args.validate();
@@ -353,12 +1682,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: temp_dir.clone(),
-                variables_file: None,
+                template_dir: Some(temp_dir.clone()),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("invalid-url".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         let result = args.validate();
@@ -377,12 +1713,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: temp_dir.clone(),
-                variables_file: None,
+                template_dir: Some(temp_dir.clone()),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert!(args.validate().is_ok());
@@ -399,12 +1742,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: temp_dir.clone(),
-                variables_file: None,
+                template_dir: Some(temp_dir.clone()),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("https://api.example.com/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert!(args.validate().is_ok());
@@ -418,12 +1768,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: PathBuf::from("/nonexistent/path"),
-                variables_file: None,
+                template_dir: Some(PathBuf::from("/nonexistent/path")),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         let result = args.validate();
@@ -441,12 +1798,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: temp_file.clone(),
-                variables_file: None,
+                template_dir: Some(temp_file.clone()),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         let result = args.validate();
@@ -465,12 +1829,22 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
-                template_dir: temp_dir.clone(),
-                variables_file: Some(PathBuf::from("/nonexistent/vars.yaml")),
+                template_dir: Some(temp_dir.clone()),
+                variables: VariableInputs {
+                    var_files: vec![PathBuf::from("/nonexistent/vars.yaml")],
+                    vars: Vec::new(),
+                },
+                options: GenerateOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         let result = args.validate();
@@ -486,10 +1860,18 @@ mod tests {
             command: Command::ListVariables {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
+                variables: VariableInputs::default(),
+                options: ListVariablesOptions::default(),
             },
             api_key: Some("my-api-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert_eq!(args.get_api_key().unwrap(), "my-api-key");
@@ -502,6 +1884,12 @@ mod tests {
             api_key: None,
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert!(args.get_api_key().is_err());
@@ -513,10 +1901,18 @@ mod tests {
             command: Command::ListVariables {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
+                variables: VariableInputs::default(),
+                options: ListVariablesOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("https://api.example.com/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert_eq!(args.get_api_url(), "https://api.example.com/v1");
@@ -528,10 +1924,18 @@ mod tests {
             command: Command::ListVariables {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
+                variables: VariableInputs::default(),
+                options: ListVariablesOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: None,
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert_eq!(args.get_api_url(), "http://localhost:8082/api/v1");
@@ -543,10 +1947,18 @@ mod tests {
             command: Command::ListVariables {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
+                variables: VariableInputs::default(),
+                options: ListVariablesOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("/custom/path")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert_eq!(args.get_output_dir(), PathBuf::from("/custom/path"));
@@ -558,10 +1970,18 @@ mod tests {
             command: Command::ListVariables {
                 data_source: DataSource::Blueprint,
                 identifier: "test-blueprint".to_string(),
+                variables: VariableInputs::default(),
+                options: ListVariablesOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: None,
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         assert_eq!(args.get_output_dir(), PathBuf::from("./output"));
@@ -576,12 +1996,19 @@ mod tests {
             command: Command::Generate {
                 data_source: DataSource::Blueprint,
                 identifier: "my-blueprint".to_string(),
-                template_dir: temp_dir.clone(),
-                variables_file: None,
+                template_dir: Some(temp_dir.clone()),
+                variables: VariableInputs::default(),
+                options: GenerateOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
         if let Command::Generate { data_source, identifier, .. } = &args.command {
@@ -594,19 +2021,138 @@ mod tests {
         fs::remove_dir_all(&temp_dir).ok();
     }
 
+    #[test]
+    fn test_generate_accepts_template_source_instead_of_dir() {
+        let args = CliArgs::try_parse_from([
+            "idp-cli",
+            "generate",
+            "stack",
+            "payments",
+            "--template-source",
+            "s3://idp-templates/terraform/",
+        ])
+        .unwrap();
+        let Command::Generate { template_dir, options, .. } = args.command else {
+            panic!("Expected Generate command");
+        };
+        assert_eq!(template_dir, None);
+        assert_eq!(options.template_source.unwrap().key_prefix(), "terraform/");
+
+        assert!(CliArgs::try_parse_from(["idp-cli", "generate", "stack", "payments", "--template-source", "./t"]).is_err());
+    }
+
+    #[test]
+    fn test_apply_config_fills_unset_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ConfigFile {
+            api_url: Some("https://idp.example.com/api/v1".to_string()),
+            output_dir: Some(PathBuf::from("./generated")),
+            template_dir: Some(dir.path().to_path_buf()),
+            variables: serde_json::json!({"environment": "staging"}).as_object().unwrap().clone(),
+            ..Default::default()
+        };
+
+        // The template directory can come from a config file instead of a flag
+        let mut args = CliArgs::try_parse_from([
+            "idp-cli", "--api-key", "key", "--output-dir", "./out", "generate", "stack", "payments",
+        ])
+        .unwrap();
+        assert!(args.validate().unwrap_err().to_string().contains("One of --template-dir, --template-source or --builtin-templates"));
+        args.apply_config(&config);
+        assert!(args.validate().is_ok());
+        assert_eq!(args.get_api_url(), "https://idp.example.com/api/v1");
+        assert_eq!(args.get_output_dir(), PathBuf::from("./out"));
+        let Command::Generate { template_dir, options, .. } = &args.command else {
+            panic!("Expected Generate command");
+        };
+        assert_eq!(template_dir.as_deref(), Some(dir.path()));
+        assert_eq!(options.default_variables["environment"], "staging");
+    }
+
+    #[test]
+    fn test_compose_takes_repeated_blueprints() {
+        let dir = tempfile::tempdir().unwrap();
+        let template_dir = dir.path().to_str().unwrap();
+        let args = CliArgs::try_parse_from([
+            "idp-cli", "--api-key", "key", "compose", "--blueprint", "networking", "--blueprint", "app@3",
+            "--template-dir", template_dir, "--dry-run",
+        ])
+        .unwrap();
+        assert!(args.validate().is_ok());
+        let Command::Compose { blueprints, options, .. } = &args.command else {
+            panic!("Expected Compose command");
+        };
+        assert_eq!(blueprints, &["networking", "app@3"]);
+        assert!(options.dry_run);
+
+        assert!(CliArgs::try_parse_from(["idp-cli", "compose", "--template-dir", template_dir]).is_err());
+    }
+
+    #[test]
+    fn test_var_and_var_file_repeat_in_order() {
+        let args = CliArgs::try_parse_from([
+            "idp-cli", "list-variables", "stack", "payments", "--var-file", "common.yaml",
+            "--variables-file", "prod.yaml", "--var", "environment=production", "--var", "tags.team=a=b",
+        ])
+        .unwrap();
+        let Command::ListVariables { variables, .. } = &args.command else {
+            panic!("Expected ListVariables command");
+        };
+        assert_eq!(variables.var_files, [PathBuf::from("common.yaml"), PathBuf::from("prod.yaml")]);
+        assert_eq!(
+            variables.vars,
+            [("environment".to_string(), "production".to_string()), ("tags.team".to_string(), "a=b".to_string())]
+        );
+
+        assert_eq!(parse_var("replicas").unwrap_err(), "expected NAME=VALUE, got 'replicas'");
+        assert_eq!(parse_var("tags..team=x").unwrap_err(), "'tags..team' is not a valid variable name");
+    }
+
+    #[test]
+    fn test_output_format_is_global() {
+        let args = CliArgs::try_parse_from(["idp-cli", "list-variables", "stack", "payments", "--output", "json"]).unwrap();
+        assert_eq!(args.output, OutputFormat::Json);
+        assert!(args.check_output_format().is_ok());
+
+        let args = CliArgs::try_parse_from(["idp-cli", "--output", "json", "changelog"]).unwrap();
+        let error = args.check_output_format().unwrap_err();
+        assert!(error.to_string().contains("not supported by the 'changelog' command"));
+    }
+
+    #[test]
+    fn test_pinned_identifier() {
+        let options = GenerateOptions::default();
+        assert_eq!(options.pinned_identifier("web-app@42").unwrap(), ("web-app", Some(42)));
+        assert_eq!(options.pinned_identifier("team@acme").unwrap(), ("team@acme", None));
+        assert_eq!(options.pinned_identifier("@42").unwrap(), ("@42", None));
+
+        let options = GenerateOptions { revision: Some(7), ..Default::default() };
+        assert_eq!(options.pinned_identifier("web-app").unwrap(), ("web-app", Some(7)));
+        assert_eq!(options.pinned_identifier("web-app@7").unwrap(), ("web-app", Some(7)));
+        assert!(options.pinned_identifier("web-app@8").is_err());
+    }
+
     #[test]
     fn test_data_source_stack_variant() {
         let args = CliArgs {
             command: Command::ListVariables {
                 data_source: DataSource::Stack,
                 identifier: "my-stack".to_string(),
+                variables: VariableInputs::default(),
+                options: ListVariablesOptions::default(),
             },
             api_key: Some("test-key".to_string()),
             api_url: Some("http://localhost:8082/api/v1".to_string()),
             output_dir: Some(PathBuf::from("./output")),
+            headers: vec![],
+            user_agent: None,
+            max_response_bytes: None,
+            verbose_errors: false,
+            plain: false,
+            output: OutputFormat::Human,
         };
 
-        if let Command::ListVariables { data_source, identifier } = &args.command {
+        if let Command::ListVariables { data_source, identifier, .. } = &args.command {
             assert!(matches!(data_source, DataSource::Stack));
             assert_eq!(identifier, "my-stack");
         } else {
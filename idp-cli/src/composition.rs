@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::error::CliError;
+use crate::models::Blueprint;
+use crate::tool_versions::Version;
+use crate::variable_context::{VariableContext, VariableContextBuilder};
+
+/// Several blueprints merged into one variable context for a single output project
+#[derive(Debug, Clone)]
+pub struct Composition {
+    pub context: VariableContext,
+    /// Providers used by the blueprints, by lowercase name
+    pub providers: BTreeMap<String, ProviderRequirement>,
+    /// Problems worth reporting that don't stop generation, e.g. clashing resource names
+    pub warnings: Vec<String>,
+}
+
+/// A cloud provider and the version constraint every blueprint agrees on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProviderRequirement {
+    pub name: String,
+    pub display_name: String,
+    /// Constraints of all blueprints joined with `, ` (all must hold), if any declare one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// Keys of the blueprints using the provider
+    pub blueprints: Vec<String>,
+}
+
+/// Namespace key for a blueprint: `Networking Core` -> `networking_core`
+pub fn blueprint_key(name: &str) -> String {
+    let key: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    key.trim_matches('_').to_string()
+}
+
+/// Merge blueprints into one context
+///
+/// The variables of each blueprint stay available under `blueprints.<key>`
+/// (e.g. `blueprints.networking.resources[0].name`), while `resources`
+/// holds every resource so templates written for a single blueprint render
+/// the whole project. Each resource gains `blueprint` (its blueprint's key)
+/// and `qualified_name` (`<key>_<name>`), which stays unique when two
+/// blueprints use the same resource name. `providers.<name>` lists each cloud
+/// provider with the combined `provider_version` constraint of its resources;
+/// constraints that no version satisfies fail the composition.
+pub fn compose(blueprints: &[Blueprint]) -> Result<Composition, CliError> {
+    let keys: Vec<String> = blueprints.iter().map(|b| blueprint_key(&b.name)).collect();
+    for (index, key) in keys.iter().enumerate() {
+        if key.is_empty() {
+            return Err(CliError::ConfigurationError(format!(
+                "Blueprint name '{}' has no letters or digits to namespace its variables",
+                blueprints[index].name
+            )));
+        }
+        if keys[..index].contains(key) {
+            return Err(CliError::ConfigurationError(format!(
+                "Blueprints '{}' and '{}' share the namespace '{}'; each blueprint can be composed once",
+                blueprints[keys.iter().position(|k| k == key).unwrap_or(0)].name,
+                blueprints[index].name,
+                key
+            )));
+        }
+    }
+
+    let providers = resolve_providers(blueprints, &keys)?;
+    let mut context = VariableContextBuilder::from_blueprint(&merged_blueprint(blueprints)?);
+    context.variables_mut().remove("blueprint.id");
+
+    // Tag every resource with its blueprint
+    let mut owners = Vec::new();
+    for (blueprint, key) in blueprints.iter().zip(&keys) {
+        for resource in &blueprint.resources {
+            owners.push((key.clone(), format!("{}_{}", key, resource.name)));
+        }
+    }
+    if let Some(Value::Array(resources)) = context.variables_mut().get_mut("resources") {
+        for (resource, (key, qualified_name)) in resources.iter_mut().zip(&owners) {
+            if let Value::Object(resource) = resource {
+                resource.insert("blueprint".to_string(), json!(key));
+                resource.insert("qualified_name".to_string(), json!(qualified_name));
+            }
+        }
+    }
+
+    // Each blueprint's own variables, namespaced by its key
+    for (blueprint, key) in blueprints.iter().zip(&keys) {
+        for (name, value) in VariableContextBuilder::from_blueprint(blueprint).list_all() {
+            let name = name.strip_prefix("blueprint.").unwrap_or(&name);
+            context.insert(format!("blueprints.{}.{}", key, name), value.clone());
+        }
+    }
+    context.insert("composition.blueprints".to_string(), json!(keys));
+    context.insert("providers".to_string(), serde_json::to_value(&providers)?);
+
+    let mut warnings = Vec::new();
+    let mut users: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (blueprint, key) in blueprints.iter().zip(&keys) {
+        for resource in &blueprint.resources {
+            let names = users.entry(resource.name.as_str()).or_default();
+            if !names.contains(&key.as_str()) {
+                names.push(key);
+            }
+        }
+    }
+    for (name, keys) in users.into_iter().filter(|(_, keys)| keys.len() > 1) {
+        warnings.push(format!(
+            "Resource name '{}' is used by blueprints {}; use resources[N].qualified_name for unique names",
+            name,
+            keys.join(", ")
+        ));
+    }
+
+    Ok(Composition { context, providers, warnings })
+}
+
+/// One blueprint with the resources and supported providers of all of them
+fn merged_blueprint(blueprints: &[Blueprint]) -> Result<Blueprint, CliError> {
+    let mut resources = Vec::new();
+    let mut supported = Vec::new();
+    for blueprint in blueprints {
+        let value = serde_json::to_value(blueprint)?;
+        resources.extend(value["resources"].as_array().cloned().unwrap_or_default());
+        for provider in value["supported_cloud_providers"].as_array().cloned().unwrap_or_default() {
+            let name = provider["name"].as_str().map(str::to_lowercase);
+            if !supported.iter().any(|p: &Value| p["name"].as_str().map(str::to_lowercase) == name) {
+                supported.push(provider);
+            }
+        }
+    }
+    let names: Vec<&str> = blueprints.iter().map(|b| b.name.as_str()).collect();
+    Ok(serde_json::from_value(json!({
+        "id": Uuid::nil(),
+        "name": names.join(" + "),
+        "description": null,
+        "resources": resources,
+        "supported_cloud_providers": supported,
+    }))?)
+}
+
+/// Group the blueprints' cloud providers and combine their version constraints
+fn resolve_providers(
+    blueprints: &[Blueprint],
+    keys: &[String],
+) -> Result<BTreeMap<String, ProviderRequirement>, CliError> {
+    let mut providers: BTreeMap<String, ProviderRequirement> = BTreeMap::new();
+    // Constraint and the blueprint that declared it, per provider
+    let mut constraints: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for (blueprint, key) in blueprints.iter().zip(keys) {
+        for resource in &blueprint.resources {
+            let provider = &resource.cloud_provider;
+            let requirement = providers.entry(provider.name.to_lowercase()).or_insert_with(|| ProviderRequirement {
+                name: provider.name.clone(),
+                display_name: provider.display_name.clone(),
+                version: None,
+                blueprints: Vec::new(),
+            });
+            if !requirement.blueprints.contains(key) {
+                requirement.blueprints.push(key.clone());
+            }
+            if let Some(constraint) = resource.cloud_specific_properties.get("provider_version").and_then(Value::as_str) {
+                let declared = constraints.entry(provider.name.to_lowercase()).or_default();
+                if !declared.iter().any(|(c, k)| c == constraint.trim() && k == key) {
+                    declared.push((constraint.trim().to_string(), key.clone()));
+                }
+            }
+        }
+    }
+
+    for (name, declared) in constraints {
+        let mut unique: Vec<&str> = Vec::new();
+        for (constraint, _) in &declared {
+            if !unique.contains(&constraint.as_str()) {
+                unique.push(constraint);
+            }
+        }
+        let combined = unique.join(", ");
+        let bounds = Bounds::parse(&combined).map_err(|e| {
+            CliError::ConfigurationError(format!("Invalid provider_version for {}: {}", name, e))
+        })?;
+        if !bounds.is_satisfiable() {
+            let required: Vec<String> =
+                declared.iter().map(|(constraint, key)| format!("{} requires \"{}\"", key, constraint)).collect();
+            return Err(CliError::ConfigurationError(format!(
+                "Provider version conflict for {}: {}; no version satisfies all blueprints",
+                name,
+                required.join(", ")
+            )));
+        }
+        if let Some(requirement) = providers.get_mut(&name) {
+            requirement.version = Some(combined);
+        }
+    }
+    Ok(providers)
+}
+
+/// Range allowed by a Terraform version constraint such as `~> 5.0, >= 5.31`
+///
+/// Each bound is a version and whether it is inclusive.
+#[derive(Debug, Default)]
+struct Bounds {
+    lower: Option<(Version, bool)>,
+    upper: Option<(Version, bool)>,
+}
+
+impl Bounds {
+    fn parse(constraint: &str) -> Result<Self, String> {
+        let mut bounds = Bounds::default();
+        for part in constraint.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let split = part.find(|c: char| c.is_ascii_digit()).unwrap_or(part.len());
+            let (operator, version) = (part[..split].trim(), part[split..].trim());
+            let (version, parts) =
+                parse_version(version).ok_or_else(|| format!("'{}' is not a version constraint", part))?;
+            match operator {
+                "" | "=" => {
+                    bounds.raise(version, true);
+                    bounds.lower_ceiling(version, true);
+                }
+                ">=" => bounds.raise(version, true),
+                ">" => bounds.raise(version, false),
+                "<=" => bounds.lower_ceiling(version, true),
+                "<" => bounds.lower_ceiling(version, false),
+                // ~> 5.1 allows 5.x from 5.1; ~> 5.1.2 allows 5.1.x from 5.1.2
+                "~>" => {
+                    bounds.raise(version, true);
+                    let ceiling = match parts {
+                        3 => Version(version.0, version.1 + 1, 0),
+                        _ => Version(version.0 + 1, 0, 0),
+                    };
+                    bounds.lower_ceiling(ceiling, false);
+                }
+                // Excluding single versions never makes a range empty in practice
+                "!=" => {}
+                _ => return Err(format!("unknown operator '{}' in '{}'", operator, part)),
+            }
+        }
+        Ok(bounds)
+    }
+
+    fn raise(&mut self, version: Version, inclusive: bool) {
+        let tighter = match self.lower {
+            Some((current, current_inclusive)) => version > current || (version == current && current_inclusive && !inclusive),
+            None => true,
+        };
+        if tighter {
+            self.lower = Some((version, inclusive));
+        }
+    }
+
+    fn lower_ceiling(&mut self, version: Version, inclusive: bool) {
+        let tighter = match self.upper {
+            Some((current, current_inclusive)) => version < current || (version == current && current_inclusive && !inclusive),
+            None => true,
+        };
+        if tighter {
+            self.upper = Some((version, inclusive));
+        }
+    }
+
+    fn is_satisfiable(&self) -> bool {
+        match (self.lower, self.upper) {
+            (Some((lower, lower_inclusive)), Some((upper, upper_inclusive))) => {
+                lower < upper || (lower == upper && lower_inclusive && upper_inclusive)
+            }
+            _ => true,
+        }
+    }
+}
+
+/// `5`, `5.1` or `5.1.2`, with the number of parts given
+fn parse_version(text: &str) -> Option<(Version, usize)> {
+    let parts: Vec<u64> = text.split('.').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [major] => Some((Version(*major, 0, 0), 1)),
+        [major, minor] => Some((Version(*major, *minor, 0), 2)),
+        [major, minor, patch] => Some((Version(*major, *minor, *patch), 3)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BlueprintResource, CloudProvider, ResourceType};
+    use std::collections::HashMap;
+
+    fn blueprint(name: &str, resources: &[(&str, &str, Option<&str>)]) -> Blueprint {
+        Blueprint {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            resources: resources
+                .iter()
+                .map(|(resource, provider, version)| BlueprintResource {
+                    id: Uuid::new_v4(),
+                    name: resource.to_string(),
+                    description: None,
+                    resource_type: ResourceType { id: Uuid::new_v4(), name: "VM".into(), category: "compute".into() },
+                    cloud_provider: CloudProvider {
+                        id: Uuid::new_v4(),
+                        name: provider.to_string(),
+                        display_name: provider.to_string(),
+                    },
+                    configuration: json!({}),
+                    cloud_specific_properties: version
+                        .map(|v| HashMap::from([("provider_version".to_string(), json!(v))]))
+                        .unwrap_or_default(),
+                })
+                .collect(),
+            supported_cloud_providers: Vec::new(),
+            revision: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_compose_namespaces_blueprints_and_merges_resources() {
+        let blueprints = [
+            blueprint("Networking", &[("vpc", "AWS", Some("~> 5.0")), ("main", "AWS", None)]),
+            blueprint("App", &[("main", "AWS", Some(">= 5.31")), ("dns", "Cloudflare", None)]),
+        ];
+        let composition = compose(&blueprints).unwrap();
+        let context = &composition.context;
+
+        assert_eq!(context.get("blueprint.name"), Some(&json!("Networking + App")));
+        assert_eq!(context.get("blueprints.networking.name"), Some(&json!("Networking")));
+        assert_eq!(context.get("blueprints.app.resources[1].name"), Some(&json!("dns")));
+        assert_eq!(context.get("resources[2].name"), Some(&json!("main")));
+        assert_eq!(context.get("resources[2].blueprint"), Some(&json!("app")));
+        assert_eq!(context.get("resources").and_then(Value::as_array).map(Vec::len), Some(4));
+        assert_eq!(context.get("resources[1].qualified_name"), Some(&json!("networking_main")));
+        assert_eq!(context.get("composition.blueprints"), Some(&json!(["networking", "app"])));
+
+        let aws = &composition.providers["aws"];
+        assert_eq!(aws.version.as_deref(), Some("~> 5.0, >= 5.31"));
+        assert_eq!(aws.blueprints, vec!["networking", "app"]);
+        assert_eq!(composition.providers["cloudflare"].version, None);
+        assert_eq!(context.to_template_data()["providers"]["aws"]["version"], "~> 5.0, >= 5.31");
+        assert_eq!(composition.warnings.len(), 1);
+        assert!(composition.warnings[0].contains("'main' is used by blueprints networking, app"));
+    }
+
+    #[test]
+    fn test_conflicting_provider_versions_fail() {
+        let blueprints = [
+            blueprint("networking", &[("vpc", "AWS", Some("~> 4.0"))]),
+            blueprint("app", &[("web", "AWS", Some("~> 5.0"))]),
+        ];
+        let error = compose(&blueprints).unwrap_err().to_string();
+        assert!(error.contains("Provider version conflict for aws"));
+        assert!(error.contains("networking requires \"~> 4.0\", app requires \"~> 5.0\""));
+
+        assert!(Bounds::parse("~> 5.1.2, < 5.2").unwrap().is_satisfiable());
+        assert!(!Bounds::parse("~> 5.1.2, >= 5.2").unwrap().is_satisfiable());
+        assert!(Bounds::parse("= 1.2.3, >= 1.2.3").unwrap().is_satisfiable());
+        assert!(!Bounds::parse("> 1.2.3, <= 1.2.3").unwrap().is_satisfiable());
+        assert!(Bounds::parse("~> banana").is_err());
+
+        let error = compose(&[blueprint("app", &[]), blueprint("App", &[])]).unwrap_err();
+        assert!(error.to_string().contains("share the namespace 'app'"));
+    }
+}
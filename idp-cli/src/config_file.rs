@@ -0,0 +1,380 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::api_client::parse_header;
+use crate::error::CliError;
+use crate::post_hooks::HookConfig;
+use crate::validators::ValidatorConfig;
+
+/// File name of both the global (home directory) and project config files
+pub const CONFIG_FILE_NAME: &str = ".idp-cli.toml";
+
+/// Settings a config file can hold
+///
+/// ```toml
+/// api_url = "https://idp.example.com/api/v1"
+/// output_dir = "./generated"
+/// template_dir = "./templates"
+/// user_agent = "payments-ci/1.0"
+/// post_hooks = ["fmt", "checkov"]
+///
+/// [headers]
+/// X-Org-Id = "payments"
+/// X-Correlation-Id = "${CI_PIPELINE_ID}"
+///
+/// [variables]
+/// environment = "staging"
+///
+/// [validators.sh]
+/// command = ["shellcheck", "-"]
+///
+/// [hooks.checkov]
+/// command = ["checkov", "--quiet", "-d", "."]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template_dir: Option<PathBuf>,
+    /// Caller added to the user agent sent to the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    /// Extra headers sent with every API request; values may use `${VAR}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Default variables for generate; the API, `--var-file` and `--var` win over these
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub variables: Map<String, Value>,
+    /// Output validators for generate, by extension or file name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub validators: BTreeMap<String, ValidatorConfig>,
+    /// Post-generation hooks run by generate unless --post-hook is given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_hooks: Vec<String>,
+    /// Post-generation hooks that run a command, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: BTreeMap<String, HookConfig>,
+}
+
+/// Keys `config set` accepts, besides `headers.<name>` and `variables.<name>`
+pub const CONFIG_KEYS: &[&str] = &["api_url", "api_key", "output_dir", "template_dir", "user_agent"];
+
+impl ConfigFile {
+    /// Load a config file, or `None` if it doesn't exist
+    pub fn load(path: &Path) -> Result<Option<Self>, CliError> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(CliError::ConfigurationError(format!(
+                    "Failed to read config file {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let config = toml::from_str(&content).map_err(|e| {
+            CliError::ConfigurationError(format!("Invalid config file {}: {}", path.display(), e))
+        })?;
+        Ok(Some(config))
+    }
+
+    /// Write the config file, replacing its contents
+    pub fn save(&self, path: &Path) -> Result<(), CliError> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| CliError::ConfigurationError(format!("Failed to serialize config: {}", e)))?;
+        std::fs::write(path, content).map_err(|e| {
+            CliError::IoError(format!("Failed to write config file {}: {}", path.display(), e))
+        })
+    }
+
+    /// Layer `other` over this file: its settings win, headers and variables are merged by name
+    pub fn merged_with(mut self, other: &ConfigFile) -> Self {
+        self.api_url = other.api_url.clone().or(self.api_url);
+        self.api_key = other.api_key.clone().or(self.api_key);
+        self.output_dir = other.output_dir.clone().or(self.output_dir);
+        self.template_dir = other.template_dir.clone().or(self.template_dir);
+        self.user_agent = other.user_agent.clone().or(self.user_agent);
+        for (name, value) in &other.headers {
+            self.headers.insert(name.clone(), value.clone());
+        }
+        for (name, value) in &other.variables {
+            self.variables.insert(name.clone(), value.clone());
+        }
+        for (key, validator) in &other.validators {
+            self.validators.insert(key.clone(), validator.clone());
+        }
+        if !other.post_hooks.is_empty() {
+            self.post_hooks = other.post_hooks.clone();
+        }
+        for (name, hook) in &other.hooks {
+            self.hooks.insert(name.clone(), hook.clone());
+        }
+        self
+    }
+
+    /// Value of a setting as shown by `config show`, if set
+    pub fn get(&self, key: &str) -> Option<Value> {
+        match key {
+            "api_url" => self.api_url.clone().map(Value::String),
+            "api_key" => self.api_key.clone().map(Value::String),
+            "output_dir" => self.output_dir.as_ref().map(|p| Value::String(p.display().to_string())),
+            "template_dir" => self.template_dir.as_ref().map(|p| Value::String(p.display().to_string())),
+            "user_agent" => self.user_agent.clone().map(Value::String),
+            _ if key.starts_with("headers.") => self.headers.get(&key["headers.".len()..]).cloned().map(Value::String),
+            _ => {
+                let mut parts = key.strip_prefix("variables.")?.split('.');
+                let root = self.variables.get(parts.next()?)?;
+                parts.try_fold(root, |value, part| value.get(part)).cloned()
+            }
+        }
+    }
+
+    /// Set a setting from `config set`
+    ///
+    /// Variable values are read as JSON when they parse (`3`, `true`,
+    /// `["a","b"]`) and kept as strings otherwise. A dotted variable name
+    /// such as `variables.tags.team` sets a nested value.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), CliError> {
+        match key {
+            "api_url" => self.api_url = Some(value.to_string()),
+            "api_key" => self.api_key = Some(value.to_string()),
+            "output_dir" => self.output_dir = Some(PathBuf::from(value)),
+            "template_dir" => self.template_dir = Some(PathBuf::from(value)),
+            "user_agent" => self.user_agent = Some(value.to_string()),
+            _ if key.starts_with("headers.") => {
+                let (name, value) = parse_header(&format!("{}={}", &key["headers.".len()..], value))
+                    .map_err(|e| CliError::ConfigurationError(format!("Invalid header: {}", e)))?;
+                self.headers.insert(name, value);
+            }
+            _ => {
+                let name = key.strip_prefix("variables.").filter(|name| !name.is_empty()).ok_or_else(|| {
+                    CliError::ConfigurationError(format!(
+                        "Unknown config key '{}'. Use one of {}, headers.<name> or variables.<name>",
+                        key,
+                        CONFIG_KEYS.join(", ")
+                    ))
+                })?;
+                let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+                let mut parts: Vec<&str> = name.split('.').collect();
+                let last = parts.pop().unwrap_or_default();
+                let mut table = &mut self.variables;
+                for part in parts {
+                    let entry = table.entry(part).or_insert_with(|| Value::Object(Map::new()));
+                    if !entry.is_object() {
+                        *entry = Value::Object(Map::new());
+                    }
+                    table = entry.as_object_mut().expect("just made an object");
+                }
+                table.insert(last.to_string(), value);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Headers with `${VAR}` references replaced by the environment variable's value
+///
+/// A header referring to an unset variable is left out, so a correlation ID
+/// taken from a CI variable is simply not sent on a developer machine.
+pub fn expand_headers(headers: &BTreeMap<String, String>) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.clone(), expand_env(value)?)))
+        .collect()
+}
+
+fn expand_env(value: &str) -> Option<String> {
+    let mut expanded = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let end = rest[start..].find('}')? + start;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&std::env::var(&rest[start + 2..end]).ok()?);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Some(expanded)
+}
+
+/// Where a config value came from, lowest precedence first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigSource {
+    Default,
+    Global,
+    Project,
+    Environment,
+    Flag,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::Global => write!(f, "global config"),
+            ConfigSource::Project => write!(f, "project config"),
+            ConfigSource::Environment => write!(f, "environment"),
+            ConfigSource::Flag => write!(f, "command line"),
+        }
+    }
+}
+
+/// The global and project config files, as found on disk
+#[derive(Debug, Clone, Default)]
+pub struct LayeredConfig {
+    pub global: Option<(PathBuf, ConfigFile)>,
+    pub project: Option<(PathBuf, ConfigFile)>,
+}
+
+impl LayeredConfig {
+    /// Load `~/.idp-cli.toml` and the nearest `.idp-cli.toml` in the
+    /// current directory or one of its parents
+    pub fn discover() -> Result<Self, CliError> {
+        let cwd = std::env::current_dir().map_err(|e| {
+            CliError::IoError(format!("Failed to read the current directory: {}", e))
+        })?;
+        Self::load(global_path().as_deref(), &cwd)
+    }
+
+    /// Load the global file at `global` and the project file nearest to `dir`
+    pub fn load(global: Option<&Path>, dir: &Path) -> Result<Self, CliError> {
+        let global = match global {
+            Some(path) => ConfigFile::load(path)?.map(|config| (path.to_path_buf(), config)),
+            None => None,
+        };
+        let mut project = None;
+        for candidate in dir.ancestors().map(|dir| dir.join(CONFIG_FILE_NAME)) {
+            // A project under the home directory would otherwise find the global file
+            if global.as_ref().is_some_and(|(path, _)| *path == candidate) {
+                break;
+            }
+            if let Some(config) = ConfigFile::load(&candidate)? {
+                project = Some((candidate, config));
+                break;
+            }
+        }
+        Ok(Self { global, project })
+    }
+
+    /// The project file over the global one
+    pub fn effective(&self) -> ConfigFile {
+        let global = self.global.as_ref().map(|(_, config)| config.clone()).unwrap_or_default();
+        match &self.project {
+            Some((_, project)) => global.merged_with(project),
+            None => global,
+        }
+    }
+
+    /// Which file a setting comes from, if any
+    pub fn source_of(&self, key: &str) -> Option<ConfigSource> {
+        if self.project.as_ref().is_some_and(|(_, config)| config.get(key).is_some()) {
+            Some(ConfigSource::Project)
+        } else if self.global.as_ref().is_some_and(|(_, config)| config.get(key).is_some()) {
+            Some(ConfigSource::Global)
+        } else {
+            None
+        }
+    }
+}
+
+/// `~/.idp-cli.toml`, if the home directory is known
+pub fn global_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(|home| PathBuf::from(home).join(CONFIG_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_project_file_overrides_global_file() {
+        let home = tempfile::tempdir().unwrap();
+        let project = home.path().join("infra");
+        let nested = project.join("modules/network");
+        std::fs::create_dir_all(&nested).unwrap();
+        let global_path = home.path().join(CONFIG_FILE_NAME);
+        std::fs::write(
+            &global_path,
+            "api_url = \"https://idp.example.com/api/v1\"\napi_key = \"global-key\"\n\n[variables]\nowner = \"platform\"\nenvironment = \"dev\"\n",
+        )
+        .unwrap();
+        std::fs::write(
+            project.join(CONFIG_FILE_NAME),
+            "template_dir = \"./templates\"\n\n[variables]\nenvironment = \"staging\"\n",
+        )
+        .unwrap();
+
+        let layered = LayeredConfig::load(Some(&global_path), &nested).unwrap();
+        assert_eq!(layered.project.as_ref().unwrap().0, project.join(CONFIG_FILE_NAME));
+        let config = layered.effective();
+        assert_eq!(config.api_key.as_deref(), Some("global-key"));
+        assert_eq!(config.template_dir, Some(PathBuf::from("./templates")));
+        assert_eq!(config.variables["owner"], json!("platform"));
+        assert_eq!(config.variables["environment"], json!("staging"));
+        assert_eq!(layered.source_of("variables.environment"), Some(ConfigSource::Project));
+        assert_eq!(layered.source_of("api_url"), Some(ConfigSource::Global));
+        assert_eq!(layered.source_of("output_dir"), None);
+
+        // From the home directory itself, the global file is not also the project file
+        let layered = LayeredConfig::load(Some(&global_path), home.path()).unwrap();
+        assert!(layered.project.is_none());
+
+        std::fs::write(project.join(CONFIG_FILE_NAME), "api_token = \"typo\"\n").unwrap();
+        let error = LayeredConfig::load(Some(&global_path), &nested).unwrap_err();
+        assert!(error.to_string().contains("unknown field `api_token`"));
+    }
+
+    #[test]
+    fn test_expand_headers() {
+        std::env::set_var("IDP_CONFIG_TEST_PIPELINE", "4711");
+        let mut config = ConfigFile::default();
+        config.set("headers.X-Org-Id", "payments").unwrap();
+        config.set("headers.X-Correlation-Id", "ci-${IDP_CONFIG_TEST_PIPELINE}").unwrap();
+        config.set("headers.X-Job-Id", "${IDP_CONFIG_TEST_UNSET}").unwrap();
+
+        assert_eq!(
+            expand_headers(&config.headers),
+            vec![
+                ("X-Correlation-Id".to_string(), "ci-4711".to_string()),
+                ("X-Org-Id".to_string(), "payments".to_string()),
+            ]
+        );
+        assert_eq!(config.get("headers.X-Org-Id"), Some(json!("payments")));
+        assert!(config.set("headers.X Org", "x").is_err());
+    }
+
+    #[test]
+    fn test_set_round_trips_through_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        let mut config = ConfigFile::default();
+        config.set("output_dir", "./generated").unwrap();
+        config.set("variables.replicas", "3").unwrap();
+        config.set("variables.tags.team", "payments").unwrap();
+        config.set("variables.region", "eu-west-1").unwrap();
+        config.save(&path).unwrap();
+
+        let loaded = ConfigFile::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, config);
+        assert_eq!(loaded.get("variables.replicas"), Some(json!(3)));
+        assert_eq!(loaded.get("variables.tags.team"), Some(json!("payments")));
+        assert_eq!(loaded.get("output_dir"), Some(json!("./generated")));
+
+        let error = config.set("api_token", "x").unwrap_err();
+        assert!(error.to_string().contains("Unknown config key 'api_token'"));
+        assert!(ConfigFile::load(&dir.path().join("missing.toml")).unwrap().is_none());
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::error::CliError;
+use crate::resource_mapper::{crossplane_type, kubernetes_name, map_resources};
+use crate::template_processor::ProcessedFile;
+use crate::variable_context::VariableContext;
+
+/// Directory of the output directory holding the Crossplane manifests
+pub const CROSSPLANE_DIR: &str = "crossplane";
+
+/// Crossplane managed resource manifests for the resources of a blueprint or stack
+///
+/// Each resource becomes `crossplane/<name>.yaml`, a managed resource of the
+/// Upbound provider for its cloud with the resource's configuration as
+/// `spec.forProvider`. Resources that can't be mapped are skipped and
+/// reported in the returned warnings.
+pub fn crossplane_files(context: &VariableContext) -> Result<(Vec<ProcessedFile>, Vec<String>), CliError> {
+    let data = context.to_template_data();
+    let (resources, mut warnings) = map_resources(&data);
+    let mut files = Vec::new();
+    let mut names: HashMap<String, &str> = HashMap::new();
+    for resource in resources {
+        let Some((api_version, kind)) = crossplane_type(resource.cloud, resource.kind) else {
+            warnings.push(format!("Resource '{}' has no Crossplane mapping and was not generated", resource.name));
+            continue;
+        };
+        let name = kubernetes_name(resource.name);
+        if let Some(other) = names.insert(name.clone(), resource.name) {
+            return Err(CliError::GenerationError(format!(
+                "Resources '{}' and '{}' both map to the Crossplane name '{}'; rename one of them",
+                other, resource.name, name
+            )));
+        }
+        let for_provider = match resource.configuration {
+            Value::Null => json!({}),
+            configuration => configuration.clone(),
+        };
+        let manifest = json!({
+            "apiVersion": api_version,
+            "kind": kind,
+            "metadata": {"name": name},
+            "spec": {
+                "forProvider": for_provider,
+                "providerConfigRef": {"name": "default"},
+            },
+        });
+        let path = Path::new(CROSSPLANE_DIR).join(format!("{}.yaml", name));
+        let content = serde_yaml::to_string(&manifest)
+            .map_err(|e| CliError::GenerationError(format!("Failed to write {}: {}", path.display(), e)))?;
+        files.push(ProcessedFile::new(path, content));
+    }
+    Ok((files, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_crossplane_files_from_stack() {
+        let mut context = VariableContext::new();
+        context.insert(
+            "stack_resources".to_string(),
+            json!([
+                {
+                    "name": "Orders DB",
+                    "resource_type": {"name": "Cloud SQL", "category": "database"},
+                    "cloud_provider": {"name": "Google Cloud"},
+                    "configuration": {"region": "europe-west1", "databaseVersion": "POSTGRES_15"}
+                },
+                {"name": "legacy", "resource_type": {"name": "VM", "category": "compute"}, "cloud_provider": {"name": "On-prem"}}
+            ]),
+        );
+
+        let (files, warnings) = crossplane_files(&context).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].relative_path, PathBuf::from("crossplane/orders-db.yaml"));
+        let manifest: Value = serde_yaml::from_str(&files[0].content).unwrap();
+        assert_eq!(
+            manifest,
+            json!({
+                "apiVersion": "sql.gcp.upbound.io/v1beta1",
+                "kind": "DatabaseInstance",
+                "metadata": {"name": "orders-db"},
+                "spec": {
+                    "forProvider": {"region": "europe-west1", "databaseVersion": "POSTGRES_15"},
+                    "providerConfigRef": {"name": "default"}
+                }
+            })
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("'legacy'"), "{:?}", warnings);
+    }
+}
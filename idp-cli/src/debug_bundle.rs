@@ -0,0 +1,377 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tempfile::TempDir;
+
+use crate::error::CliError;
+use crate::paths;
+use crate::template_discovery::{TemplateDiscovery, TemplateFile};
+use crate::variable_context::VariableContext;
+
+/// Format version written to `manifest.json`
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Replacement for masked values
+pub const REDACTED: &str = "***REDACTED***";
+
+/// Variable names containing any of these (case-insensitive) are masked
+const SENSITIVE_NAMES: &[&str] = &[
+    "password",
+    "passwd",
+    "secret",
+    "token",
+    "api_key",
+    "apikey",
+    "private_key",
+    "access_key",
+    "credential",
+    "connection_string",
+];
+
+/// Where debug bundles of failed runs are written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BundleTarget {
+    Dir(PathBuf),
+    S3 { bucket: String, prefix: String },
+}
+
+impl BundleTarget {
+    /// Parse a directory or an `s3://bucket/prefix` URL
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.strip_prefix("s3://") {
+            Some(rest) => {
+                let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+                if bucket.is_empty() {
+                    return Err(format!("missing bucket name in '{}'", value));
+                }
+                Ok(BundleTarget::S3 {
+                    bucket: bucket.to_string(),
+                    prefix: prefix.trim_matches('/').to_string(),
+                })
+            }
+            None => Ok(BundleTarget::Dir(PathBuf::from(value))),
+        }
+    }
+}
+
+/// What a run had built when it failed
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    pub context: Option<VariableContext>,
+    pub templates: Vec<TemplateFile>,
+    pub strategy: Option<String>,
+}
+
+/// Description of a failed run, stored as `manifest.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub version: u32,
+    pub cli_version: String,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub data_source: String,
+    pub identifier: String,
+    pub template_dir: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    pub templates: Vec<String>,
+    /// Number of masked values in `context.json`
+    pub redacted_values: usize,
+    pub error: String,
+    pub error_detail: String,
+}
+
+impl BundleManifest {
+    pub fn new(data_source: &str, identifier: &str, template_dir: &Path, error: &CliError) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            cli_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            data_source: data_source.to_string(),
+            identifier: identifier.to_string(),
+            template_dir: template_dir.display().to_string(),
+            strategy: None,
+            templates: Vec::new(),
+            redacted_values: 0,
+            error: error.user_message(),
+            error_detail: format!("{:?}", error),
+        }
+    }
+
+    /// File name for the bundle, e.g. `idp-debug-payments-prod-1718000000.tar.gz`
+    pub fn file_name(&self) -> String {
+        let identifier: String = self
+            .identifier
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '-' })
+            .collect();
+        format!("idp-debug-{}-{}.tar.gz", identifier, self.created_at)
+    }
+}
+
+/// Mask values whose variable name looks sensitive, including nested object keys,
+/// and values resolved from secret references
+///
+/// Returns the redacted variables and the number of masked values.
+pub fn redact_variables(context: &VariableContext) -> (serde_json::Map<String, Value>, usize) {
+    let mut count = 0;
+    let variables = context
+        .list_all()
+        .into_iter()
+        .map(|(name, value)| {
+            // Flattened names like `resources[0].configuration.db_password`
+            let last = name.rsplit(['.', ']']).next().unwrap_or(&name);
+            let value = if is_sensitive(last) {
+                count += 1;
+                Value::String(REDACTED.to_string())
+            } else {
+                redact_value(context, value, &mut count)
+            };
+            (name, value)
+        })
+        .collect();
+    (variables, count)
+}
+
+fn redact_value(context: &VariableContext, value: &Value, count: &mut usize) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, value)| {
+                    if is_sensitive(key) {
+                        *count += 1;
+                        (key.clone(), Value::String(REDACTED.to_string()))
+                    } else {
+                        (key.clone(), redact_value(context, value, count))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(|item| redact_value(context, item, count)).collect()),
+        Value::String(_) if context.is_secret(value) => {
+            *count += 1;
+            Value::String(REDACTED.to_string())
+        }
+        other => other.clone(),
+    }
+}
+
+fn is_sensitive(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SENSITIVE_NAMES.iter().any(|sensitive| name.contains(sensitive))
+}
+
+/// Build a `.tar.gz` bundle with `manifest.json`, the redacted `context.json`
+/// and the templates under `templates/`
+pub fn build_bundle(snapshot: &Snapshot, mut manifest: BundleManifest) -> Result<Vec<u8>, CliError> {
+    let (variables, redacted) = snapshot
+        .context
+        .as_ref()
+        .map(redact_variables)
+        .unwrap_or_default();
+    manifest.redacted_values = redacted;
+    manifest.strategy = snapshot.strategy.clone();
+    manifest.templates = snapshot
+        .templates
+        .iter()
+        .map(|t| paths::to_slash(&t.relative_path))
+        .collect();
+
+    let io_error = |e: std::io::Error| CliError::IoError(format!("Failed to build debug bundle: {}", e));
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    append_bytes(&mut archive, "manifest.json", &serde_json::to_vec_pretty(&manifest)?).map_err(io_error)?;
+    append_bytes(&mut archive, "context.json", &serde_json::to_vec_pretty(&variables)?).map_err(io_error)?;
+    for template in &snapshot.templates {
+        archive
+            .append_path_with_name(&template.path, Path::new("templates").join(&template.relative_path))
+            .map_err(io_error)?;
+    }
+    archive.into_inner().and_then(|encoder| encoder.finish()).map_err(io_error)
+}
+
+fn append_bytes<W: std::io::Write>(archive: &mut tar::Builder<W>, name: &str, data: &[u8]) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    archive.append_data(&mut header, name, data)
+}
+
+/// Write a bundle for a failed run and return where it was stored
+pub async fn save_bundle(
+    snapshot: &Snapshot,
+    manifest: BundleManifest,
+    target: &BundleTarget,
+) -> Result<String, CliError> {
+    let file_name = manifest.file_name();
+    let bytes = build_bundle(snapshot, manifest)?;
+
+    match target {
+        BundleTarget::Dir(dir) => {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                CliError::IoError(format!("Failed to create directory {}: {}", dir.display(), e))
+            })?;
+            let path = dir.join(file_name);
+            std::fs::write(&path, bytes).map_err(|e| {
+                CliError::IoError(format!("Failed to write debug bundle {}: {}", path.display(), e))
+            })?;
+            Ok(path.display().to_string())
+        }
+        BundleTarget::S3 { bucket, prefix } => {
+            let key = if prefix.is_empty() {
+                file_name
+            } else {
+                format!("{}/{}", prefix, file_name)
+            };
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            aws_sdk_s3::Client::new(&config)
+                .put_object()
+                .bucket(bucket)
+                .key(&key)
+                .content_type("application/gzip")
+                .body(bytes.into())
+                .send()
+                .await
+                .map_err(|e| CliError::S3Error(format!("Failed to upload debug bundle {}: {}", key, e)))?;
+            Ok(format!("s3://{}/{}", bucket, key))
+        }
+    }
+}
+
+/// Contents of a bundle, extracted to a temporary directory
+pub struct LoadedBundle {
+    pub manifest: BundleManifest,
+    pub context: VariableContext,
+    pub templates: Vec<TemplateFile>,
+    _dir: TempDir,
+}
+
+/// Extract a bundle so its templates can be rendered again
+pub fn load_bundle(path: &Path) -> Result<LoadedBundle, CliError> {
+    let io_error = |e: std::io::Error| {
+        CliError::IoError(format!("Failed to read debug bundle {}: {}", path.display(), e))
+    };
+    let file = std::fs::File::open(path).map_err(io_error)?;
+    let dir = TempDir::new().map_err(io_error)?;
+    // `unpack` refuses entries that would land outside the directory
+    tar::Archive::new(GzDecoder::new(file)).unpack(dir.path()).map_err(io_error)?;
+
+    let read = |name: &str| -> Result<String, CliError> {
+        let mut content = String::new();
+        std::fs::File::open(dir.path().join(name))
+            .and_then(|mut f| f.read_to_string(&mut content))
+            .map_err(|e| CliError::IoError(format!("Debug bundle is missing {}: {}", name, e)))?;
+        Ok(content)
+    };
+    let manifest: BundleManifest = serde_json::from_str(&read("manifest.json")?)?;
+    if manifest.version > BUNDLE_VERSION {
+        return Err(CliError::ConfigurationError(format!(
+            "Debug bundle version {} is newer than this CLI supports ({})",
+            manifest.version, BUNDLE_VERSION
+        )));
+    }
+
+    let variables: serde_json::Map<String, Value> = serde_json::from_str(&read("context.json")?)?;
+    let mut context = VariableContext::new();
+    for (name, value) in variables {
+        context.insert(name, value);
+    }
+
+    let templates_dir = dir.path().join("templates");
+    let templates = if templates_dir.is_dir() {
+        TemplateDiscovery::new(templates_dir)
+            .discover_templates()
+            .map_err(|e| CliError::DiscoveryError(e.to_string()))?
+    } else {
+        Vec::new()
+    };
+
+    Ok(LoadedBundle {
+        manifest,
+        context,
+        templates,
+        _dir: dir,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact_masks_sensitive_names() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("payments"));
+        context.insert("resources[0].configuration.db_password".to_string(), json!("hunter2"));
+        context.insert(
+            "resources".to_string(),
+            json!([{"configuration": {"apiToken": "abc", "size": "large"}}]),
+        );
+        // Resolved from ssm:// under a name that doesn't look sensitive
+        context.insert("database.url".to_string(), json!("postgres://app:pw@db/app"));
+        context.mark_secret("postgres://app:pw@db/app".to_string());
+
+        let (variables, count) = redact_variables(&context);
+        assert_eq!(count, 3);
+        assert_eq!(variables["database.url"], REDACTED);
+        assert_eq!(variables["stack.name"], "payments");
+        assert_eq!(variables["resources[0].configuration.db_password"], REDACTED);
+        assert_eq!(variables["resources"][0]["configuration"]["apiToken"], REDACTED);
+        assert_eq!(variables["resources"][0]["configuration"]["size"], "large");
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let template_dir = TempDir::new().unwrap();
+        std::fs::create_dir(template_dir.path().join("k8s")).unwrap();
+        std::fs::write(template_dir.path().join("k8s/app.yaml"), "name: {{stack.name}}").unwrap();
+        let templates = TemplateDiscovery::new(template_dir.path().to_path_buf())
+            .discover_templates()
+            .unwrap();
+
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("payments"));
+        context.insert("secret_key".to_string(), json!("s3cr3t"));
+        let snapshot = Snapshot {
+            context: Some(context),
+            templates,
+            strategy: Some("default".to_string()),
+        };
+        let error = CliError::GenerationError("boom".to_string());
+        let manifest = BundleManifest::new("stack", "payments", template_dir.path(), &error);
+
+        let out = TempDir::new().unwrap();
+        let path = out.path().join("bundle.tar.gz");
+        std::fs::write(&path, build_bundle(&snapshot, manifest).unwrap()).unwrap();
+
+        let bundle = load_bundle(&path).unwrap();
+        assert_eq!(bundle.manifest.templates, vec!["k8s/app.yaml".to_string()]);
+        assert_eq!(bundle.manifest.redacted_values, 1);
+        assert_eq!(bundle.context.get("secret_key"), Some(&json!(REDACTED)));
+        assert_eq!(bundle.templates.len(), 1);
+        assert_eq!(bundle.templates[0].relative_path, PathBuf::from("k8s/app.yaml"));
+    }
+
+    #[test]
+    fn test_bundle_target_parse() {
+        assert_eq!(BundleTarget::parse("./debug").unwrap(), BundleTarget::Dir(PathBuf::from("./debug")));
+        assert_eq!(
+            BundleTarget::parse("s3://support/bundles/").unwrap(),
+            BundleTarget::S3 {
+                bucket: "support".to_string(),
+                prefix: "bundles".to_string()
+            }
+        );
+        assert!(BundleTarget::parse("s3://").is_err());
+    }
+}
@@ -0,0 +1,344 @@
+use crate::resource_mapper::ResourceKind;
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap};
+
+/// Number of ranks in the category heuristic
+const RANKS: usize = 4;
+
+/// Words in resource type names of security resources, which have no resource kind
+const SECURITY_WORDS: &[&str] = &["security", "identity", "iam", "firewall", "secret", "secrets", "kms", "vault"];
+
+/// Dependency graph between the resources of a blueprint or stack
+///
+/// Edges come from two sources:
+/// - Configuration references: any string in a resource's configuration that
+///   equals another resource's name (this includes explicit `depends_on` lists)
+/// - Category heuristics: compute resources depend on data stores, data stores
+///   on security resources, and everything on networking
+///
+/// The graph works on the JSON resource objects from the variable context
+/// (`resources` or `stack_resources`), so it applies to blueprints and stacks.
+/// Resources sharing a name are one node, at the first resource's index.
+///
+/// Category edges are not stored: with hundreds of compute resources and data
+/// stores they number in the millions. Each node keeps its category ranks
+/// instead, and the edges are derived from them when needed.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    /// Resource names in their original order
+    names: Vec<String>,
+    /// Resource name -> index of the first resource with that name
+    index_of: HashMap<String, usize>,
+    /// Resource index -> indices of the resources its configuration refers to
+    references: Vec<BTreeSet<usize>>,
+    /// Lowest category rank among the resources sharing a node's name;
+    /// resources of a higher rank depend on the node
+    lowest_rank: Vec<Option<u8>>,
+    /// Highest category rank among the resources sharing a node's name;
+    /// the node depends on every node of a lower rank
+    highest_rank: Vec<Option<u8>>,
+    /// Nodes by their lowest rank, in original order
+    by_rank: [Vec<usize>; RANKS],
+    /// Position of each node's name in name order, so dependencies sort without comparing strings
+    name_order: Vec<usize>,
+}
+
+impl DependencyGraph {
+    /// Build the graph from resource objects with `name`, `resource_type.name`
+    /// and `configuration` fields
+    pub fn from_resources(resources: &[Value]) -> Self {
+        let names: Vec<String> = resources
+            .iter()
+            .map(|r| r.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string())
+            .collect();
+        let mut index_of: HashMap<String, usize> = HashMap::with_capacity(names.len());
+        for (index, name) in names.iter().enumerate() {
+            index_of.entry(name.clone()).or_insert(index);
+        }
+
+        let mut references: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); names.len()];
+        let mut lowest_rank: Vec<Option<u8>> = vec![None; names.len()];
+        let mut highest_rank: Vec<Option<u8>> = vec![None; names.len()];
+        for (resource, name) in resources.iter().zip(&names) {
+            let node = index_of[name];
+
+            // Explicit references from the configuration
+            let mut strings = Vec::new();
+            if let Some(configuration) = resource.get("configuration") {
+                collect_strings(configuration, &mut strings);
+            }
+            for reference in strings {
+                if reference != name.as_str() {
+                    if let Some(&dependency) = index_of.get(reference) {
+                        references[node].insert(dependency);
+                    }
+                }
+            }
+
+            // Category heuristics
+            if let Some(rank) = category_rank(resource) {
+                lowest_rank[node] = Some(lowest_rank[node].map_or(rank, |lowest| lowest.min(rank)));
+                highest_rank[node] = Some(highest_rank[node].map_or(rank, |highest| highest.max(rank)));
+            }
+        }
+
+        let mut by_rank: [Vec<usize>; RANKS] = Default::default();
+        for (index, name) in names.iter().enumerate() {
+            if let (true, Some(rank)) = (index_of[name] == index, lowest_rank[index]) {
+                by_rank[rank as usize].push(index);
+            }
+        }
+
+        let mut nodes: Vec<usize> = index_of.values().copied().collect();
+        nodes.sort_unstable_by(|a, b| names[*a].cmp(&names[*b]));
+        let mut name_order = vec![0; names.len()];
+        for (position, node) in nodes.into_iter().enumerate() {
+            name_order[node] = position;
+        }
+
+        Self { names, index_of, references, lowest_rank, highest_rank, by_rank, name_order }
+    }
+
+    /// Names of the resources a resource depends on, sorted by name
+    pub fn dependencies_of(&self, name: &str) -> Vec<String> {
+        let Some(&node) = self.index_of.get(name) else {
+            return Vec::new();
+        };
+        let below = self.highest_rank[node].map_or(0, |highest| highest as usize);
+        let mut dependencies: Vec<usize> = self.references[node]
+            .iter()
+            .chain(self.by_rank[..below].iter().flatten().filter(|&&other| other != node))
+            .copied()
+            .collect();
+        dependencies.sort_unstable_by_key(|&dependency| self.name_order[dependency]);
+        dependencies.dedup();
+        dependencies.into_iter().map(|dependency| self.names[dependency].clone()).collect()
+    }
+
+    /// Resource indices in dependency order (dependencies first)
+    ///
+    /// Resources without an ordering constraint keep their original order.
+    /// Resources that are part of a cycle are appended in original order.
+    pub fn topological_order(&self) -> Vec<usize> {
+        let mut order = self.resolvable_order();
+        let placed: BTreeSet<usize> = order.iter().copied().collect();
+        order.extend((0..self.names.len()).filter(|index| !placed.contains(index)));
+        order
+    }
+
+    /// Check whether the graph contains a dependency cycle
+    pub fn has_cycle(&self) -> bool {
+        self.resolvable_order().len() < self.names.len()
+    }
+
+    /// Order of every resource whose dependencies can all be satisfied
+    ///
+    /// Kahn's algorithm, always taking the lowest ready index so that
+    /// unconstrained resources keep their original order. Category edges are
+    /// satisfied once every node of a lower rank has been placed, which is
+    /// tracked with a count of unplaced nodes per rank.
+    fn resolvable_order(&self) -> Vec<usize> {
+        let mut waiting_on: Vec<usize> = self.references.iter().map(BTreeSet::len).collect();
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); self.names.len()];
+        for (node, references) in self.references.iter().enumerate() {
+            for &dependency in references {
+                dependents[dependency].push(node);
+            }
+        }
+        let mut unplaced: [usize; RANKS] = std::array::from_fn(|rank| self.by_rank[rank].len());
+        let category_ready = |node: usize, unplaced: &[usize; RANKS]| {
+            let below = self.highest_rank[node].map_or(0, |highest| highest as usize);
+            // A node never waits for itself
+            (0..below).all(|rank| unplaced[rank] == usize::from(self.lowest_rank[node] == Some(rank as u8)))
+        };
+
+        // Only the first resource with a name is a node
+        let mut ready = BinaryHeap::new();
+        let mut blocked = Vec::new();
+        for &node in self.index_of.values().filter(|&&node| waiting_on[node] == 0) {
+            if category_ready(node, &unplaced) {
+                ready.push(Reverse(node));
+            } else {
+                blocked.push(node);
+            }
+        }
+        let mut order = Vec::with_capacity(self.names.len());
+        while let Some(Reverse(node)) = ready.pop() {
+            order.push(node);
+            for &dependent in &dependents[node] {
+                waiting_on[dependent] -= 1;
+                if waiting_on[dependent] > 0 {
+                    continue;
+                }
+                if category_ready(dependent, &unplaced) {
+                    ready.push(Reverse(dependent));
+                } else {
+                    blocked.push(dependent);
+                }
+            }
+            if let Some(rank) = self.lowest_rank[node] {
+                unplaced[rank as usize] -= 1;
+                // Blocked nodes can only become ready once a rank is (nearly) placed
+                if unplaced[rank as usize] <= 1 {
+                    let (now_ready, still_blocked): (Vec<usize>, Vec<usize>) =
+                        blocked.into_iter().partition(|&node| category_ready(node, &unplaced));
+                    ready.extend(now_ready.into_iter().map(Reverse));
+                    blocked = still_blocked;
+                }
+            }
+        }
+
+        order
+    }
+}
+
+/// Sort resource objects so that every resource comes after its dependencies
+pub fn topo_sort_resources(resources: &[Value]) -> Vec<Value> {
+    DependencyGraph::from_resources(resources)
+        .topological_order()
+        .into_iter()
+        .map(|index| resources[index].clone())
+        .collect()
+}
+
+/// Rank of a resource in the category heuristic (lower is provisioned first)
+///
+/// The API's category only says whether a resource type is shared, so the
+/// rank comes from the kind of resource its type name stands for.
+fn category_rank(resource: &Value) -> Option<u8> {
+    let name = resource.get("resource_type").and_then(|t| t.get("name")).and_then(|n| n.as_str())?;
+    if name
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| SECURITY_WORDS.contains(&word))
+    {
+        return Some(1);
+    }
+
+    let rank = match ResourceKind::for_resource_type(name)? {
+        ResourceKind::Network => 0,
+        ResourceKind::Database
+        | ResourceKind::NoSqlDatabase
+        | ResourceKind::Cache
+        | ResourceKind::Queue
+        | ResourceKind::MessageBus
+        | ResourceKind::Bucket => 2,
+        ResourceKind::Cluster | ResourceKind::Compute => 3,
+    };
+    Some(rank)
+}
+
+fn collect_strings<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+    match value {
+        Value::String(s) => out.push(s),
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn resource(name: &str, type_name: &str, configuration: Value) -> Value {
+        json!({
+            "name": name,
+            "resource_type": { "name": type_name, "category": "NON_SHARED" },
+            "configuration": configuration,
+        })
+    }
+
+    fn names(resources: &[Value]) -> Vec<&str> {
+        resources.iter().map(|r| r["name"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_configuration_references_create_edges() {
+        let resources = vec![
+            resource("api", "other", json!({ "database": "orders-db", "depends_on": ["queue"] })),
+            resource("orders-db", "other", json!({})),
+            resource("queue", "other", json!({ "name": "queue" })),
+        ];
+        let graph = DependencyGraph::from_resources(&resources);
+        assert_eq!(graph.dependencies_of("api"), vec!["orders-db", "queue"]);
+        assert!(graph.dependencies_of("queue").is_empty());
+    }
+
+    #[test]
+    fn test_category_heuristics() {
+        let resources = vec![
+            resource("web", "Virtual Machine", json!({})),
+            resource("db", "Database", json!({})),
+            resource("vpc", "VPC", json!({})),
+            resource("keys", "Key Vault", json!({})),
+            resource("misc", "Monitoring", json!({})),
+        ];
+        let graph = DependencyGraph::from_resources(&resources);
+        assert_eq!(graph.dependencies_of("web"), vec!["db", "keys", "vpc"]);
+        assert_eq!(graph.dependencies_of("db"), vec!["keys", "vpc"]);
+        assert_eq!(graph.dependencies_of("keys"), vec!["vpc"]);
+        assert!(graph.dependencies_of("misc").is_empty());
+
+        let sorted = topo_sort_resources(&resources);
+        assert_eq!(names(&sorted), vec!["vpc", "keys", "db", "web", "misc"]);
+    }
+
+    #[test]
+    fn test_category_heuristics_with_api_resource_types() {
+        // Resource types seeded by the API; their category is SHARED, NON_SHARED or BOTH
+        let resources = vec![
+            resource("platform", "Managed Container Orchestrator", json!({})),
+            resource("ledger", "Relational Database Server", json!({})),
+            resource("events", "Service Bus", json!({})),
+            resource("assets", "Storage", json!({})),
+            resource("orders", "NoSQL Database", json!({})),
+            resource("jobs", "Queue", json!({})),
+            resource("sessions", "Cache", json!({})),
+        ];
+        let graph = DependencyGraph::from_resources(&resources);
+        assert_eq!(
+            graph.dependencies_of("platform"),
+            vec!["assets", "events", "jobs", "ledger", "orders", "sessions"]
+        );
+        assert!(graph.dependencies_of("ledger").is_empty());
+        assert_eq!(names(&topo_sort_resources(&resources)).last(), Some(&"platform"));
+    }
+
+    #[test]
+    fn test_cycle_keeps_all_resources() {
+        let resources = vec![
+            resource("a", "other", json!({ "peer": "b" })),
+            resource("b", "other", json!({ "peer": "a" })),
+            resource("c", "other", json!({})),
+        ];
+        let graph = DependencyGraph::from_resources(&resources);
+        assert!(graph.has_cycle());
+        assert_eq!(names(&topo_sort_resources(&resources)), vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_large_graph_orders_every_resource() {
+        // A chain through configuration references, in reverse order
+        let count = 5000;
+        let resources: Vec<Value> = (0..count)
+            .map(|i| resource(&format!("r{}", i), "other", json!({ "after": format!("r{}", i + 1) })))
+            .collect();
+        let graph = DependencyGraph::from_resources(&resources);
+        assert!(!graph.has_cycle());
+        assert_eq!(graph.topological_order(), (0..count).rev().collect::<Vec<_>>());
+
+        // Every compute resource depends on every database
+        let resources: Vec<Value> = (0..count)
+            .map(|i| resource(&format!("r{}", i), if i % 2 == 0 { "Virtual Machine" } else { "Database" }, json!({})))
+            .collect();
+        let graph = DependencyGraph::from_resources(&resources);
+        let order = graph.topological_order();
+        assert!(order[..count / 2].iter().all(|index| index % 2 == 1));
+        assert!(order[..count / 2].windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(graph.dependencies_of("r0").len(), count / 2);
+        assert!(graph.dependencies_of("r1").is_empty());
+    }
+}
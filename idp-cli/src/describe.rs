@@ -0,0 +1,306 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::models::{Blueprint, Stack};
+
+/// Maximum number of configuration entries shown per resource
+const MAX_HIGHLIGHTS: usize = 5;
+
+/// Structured summary of a blueprint or stack for the describe command
+#[derive(Debug, Clone, Serialize)]
+pub struct Description {
+    /// "blueprint" or "stack"
+    pub kind: String,
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Stack cloud name (stacks only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud_name: Option<String>,
+    /// Stack type (stacks only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_type: Option<String>,
+    /// Supported cloud provider names (blueprints only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub supported_cloud_providers: Vec<String>,
+    pub resource_count: usize,
+    /// Resources grouped by cloud provider, then by resource type
+    pub resources: BTreeMap<String, BTreeMap<String, Vec<ResourceSummary>>>,
+    /// Blueprint linked to a stack, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub linked_blueprint: Option<LinkedBlueprint>,
+}
+
+/// Summary of a single resource
+#[derive(Debug, Clone, Serialize)]
+pub struct ResourceSummary {
+    pub name: String,
+    pub category: String,
+    /// First few scalar configuration values, sorted by key
+    pub highlights: BTreeMap<String, Value>,
+}
+
+/// Reference to the blueprint a stack was created from
+#[derive(Debug, Clone, Serialize)]
+pub struct LinkedBlueprint {
+    pub id: String,
+    pub name: String,
+    pub resource_count: usize,
+}
+
+impl Description {
+    /// Build a description from a blueprint
+    pub fn from_blueprint(blueprint: &Blueprint) -> Self {
+        let mut resources = BTreeMap::new();
+        for resource in &blueprint.resources {
+            let highlights = match &resource.configuration {
+                Value::Object(map) => configuration_highlights(map.iter()),
+                _ => BTreeMap::new(),
+            };
+            add_resource(
+                &mut resources,
+                &resource.cloud_provider.name,
+                &resource.resource_type.name,
+                ResourceSummary {
+                    name: resource.name.clone(),
+                    category: resource.resource_type.category.clone(),
+                    highlights,
+                },
+            );
+        }
+
+        Self {
+            kind: "blueprint".to_string(),
+            id: blueprint.id.to_string(),
+            name: blueprint.name.clone(),
+            description: blueprint.description.clone(),
+            cloud_name: None,
+            stack_type: None,
+            supported_cloud_providers: blueprint
+                .supported_cloud_providers
+                .iter()
+                .map(|p| p.name.clone())
+                .collect(),
+            resource_count: blueprint.resources.len(),
+            resources,
+            linked_blueprint: None,
+        }
+    }
+
+    /// Build a description from a stack
+    pub fn from_stack(stack: &Stack) -> Self {
+        let mut resources = BTreeMap::new();
+        for resource in &stack.stack_resources {
+            add_resource(
+                &mut resources,
+                &resource.cloud_provider.name,
+                &resource.resource_type.name,
+                ResourceSummary {
+                    name: resource.name.clone(),
+                    category: resource.resource_type.category.clone(),
+                    highlights: configuration_highlights(resource.configuration.iter()),
+                },
+            );
+        }
+
+        Self {
+            kind: "stack".to_string(),
+            id: stack.id.to_string(),
+            name: stack.name.clone(),
+            description: stack.description.clone(),
+            cloud_name: Some(stack.cloud_name.clone()),
+            stack_type: Some(stack.stack_type.clone()),
+            supported_cloud_providers: Vec::new(),
+            resource_count: stack.stack_resources.len(),
+            resources,
+            linked_blueprint: stack.blueprint.as_ref().map(|b| LinkedBlueprint {
+                id: b.id.to_string(),
+                name: b.name.clone(),
+                resource_count: b.resources.len(),
+            }),
+        }
+    }
+
+    /// Format the description for terminal output
+    pub fn render_human(&self) -> String {
+        let title = format!("{} {}", capitalize(&self.kind), self.name);
+        let mut lines = vec![title.clone(), "=".repeat(title.len()), String::new()];
+
+        lines.push(format!("  ID:          {}", self.id));
+        if let Some(description) = &self.description {
+            lines.push(format!("  Description: {}", description));
+        }
+        if let Some(stack_type) = &self.stack_type {
+            lines.push(format!("  Stack type:  {}", stack_type));
+        }
+        if let Some(cloud_name) = &self.cloud_name {
+            lines.push(format!("  Cloud:       {}", cloud_name));
+        }
+        if !self.supported_cloud_providers.is_empty() {
+            lines.push(format!(
+                "  Providers:   {}",
+                self.supported_cloud_providers.join(", ")
+            ));
+        }
+        if let Some(blueprint) = &self.linked_blueprint {
+            lines.push(format!(
+                "  Blueprint:   {} ({}, {} resource(s))",
+                blueprint.name, blueprint.id, blueprint.resource_count
+            ));
+        }
+
+        lines.push(String::new());
+        lines.push(format!("Resources ({})", self.resource_count));
+
+        if self.resources.is_empty() {
+            lines.push("  (none)".to_string());
+        }
+
+        for (provider, types) in &self.resources {
+            lines.push(format!("  {}", provider));
+            for (resource_type, resources) in types {
+                lines.push(format!("    {} ({})", resource_type, resources.len()));
+                for resource in resources {
+                    lines.push(format!("      - {} [{}]", resource.name, resource.category));
+                    for (key, value) in &resource.highlights {
+                        lines.push(format!("          {}: {}", key, display_scalar(value)));
+                    }
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
+}
+
+fn add_resource(
+    resources: &mut BTreeMap<String, BTreeMap<String, Vec<ResourceSummary>>>,
+    provider: &str,
+    resource_type: &str,
+    summary: ResourceSummary,
+) {
+    resources
+        .entry(provider.to_string())
+        .or_default()
+        .entry(resource_type.to_string())
+        .or_default()
+        .push(summary);
+}
+
+/// Pick the first few scalar configuration values (sorted by key)
+fn configuration_highlights<'a>(
+    configuration: impl Iterator<Item = (&'a String, &'a Value)>,
+) -> BTreeMap<String, Value> {
+    let scalars: BTreeMap<String, Value> = configuration
+        .filter(|(_, v)| !v.is_object() && !v.is_array() && !v.is_null())
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    scalars.into_iter().take(MAX_HIGHLIGHTS).collect()
+}
+
+fn display_scalar(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CloudProvider, ResourceType, StackResource};
+    use serde_json::json;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn stack_resource(name: &str, provider: &str, resource_type: &str) -> StackResource {
+        let mut configuration = HashMap::new();
+        configuration.insert("port".to_string(), json!(5432));
+        configuration.insert("engine".to_string(), json!("postgres"));
+        configuration.insert("tags".to_string(), json!({"team": "payments"}));
+        StackResource {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            resource_type: ResourceType {
+                id: Uuid::new_v4(),
+                name: resource_type.to_string(),
+                category: "database".to_string(),
+            },
+            cloud_provider: CloudProvider {
+                id: Uuid::new_v4(),
+                name: provider.to_string(),
+                display_name: provider.to_string(),
+            },
+            configuration,
+        }
+    }
+
+    fn create_test_stack() -> Stack {
+        Stack {
+            id: Uuid::new_v4(),
+            name: "payments-prod".to_string(),
+            description: Some("Payments".to_string()),
+            cloud_name: "aws-us-east-1".to_string(),
+            stack_type: "RESTFUL_API".to_string(),
+            revision: None,
+            updated_at: None,
+            stack_resources: vec![
+                stack_resource("orders-db", "AWS", "RelationalDatabaseServer"),
+                stack_resource("ledger-db", "AWS", "RelationalDatabaseServer"),
+                stack_resource("cache", "Azure", "Cache"),
+            ],
+            blueprint: Some(Blueprint {
+                id: Uuid::new_v4(),
+                name: "payments-blueprint".to_string(),
+                description: None,
+                resources: vec![],
+                revision: None,
+                updated_at: None,
+                supported_cloud_providers: vec![],
+            }),
+        }
+    }
+
+    #[test]
+    fn test_from_stack_groups_resources() {
+        let description = Description::from_stack(&create_test_stack());
+        assert_eq!(description.kind, "stack");
+        assert_eq!(description.resource_count, 3);
+        assert_eq!(description.resources["AWS"]["RelationalDatabaseServer"].len(), 2);
+        assert_eq!(description.resources["Azure"]["Cache"][0].name, "cache");
+        assert_eq!(description.linked_blueprint.as_ref().unwrap().name, "payments-blueprint");
+    }
+
+    #[test]
+    fn test_highlights_skip_nested_values() {
+        let description = Description::from_stack(&create_test_stack());
+        let highlights = &description.resources["Azure"]["Cache"][0].highlights;
+        assert_eq!(highlights.get("port"), Some(&json!(5432)));
+        assert!(!highlights.contains_key("tags"));
+    }
+
+    #[test]
+    fn test_render_human_and_json() {
+        let description = Description::from_stack(&create_test_stack());
+        let output = description.render_human();
+        assert!(output.starts_with("Stack payments-prod"));
+        assert!(output.contains("    RelationalDatabaseServer (2)"));
+        assert!(output.contains("          engine: postgres"));
+        assert!(output.contains("Blueprint:   payments-blueprint"));
+
+        let json = serde_json::to_value(&description).unwrap();
+        assert_eq!(json["stack_type"], "RESTFUL_API");
+        assert!(json.get("supported_cloud_providers").is_none());
+    }
+}
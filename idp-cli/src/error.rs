@@ -1,5 +1,6 @@
 use thiserror::Error;
 
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Error)]
 pub enum CliError {
     #[error("Authentication failed: {0}")]
@@ -32,6 +33,9 @@ pub enum CliError {
     #[error("Template processing error: {0}")]
     ProcessingError(String),
 
+    #[error("Output validation error: {0}")]
+    ValidationError(String),
+
     #[error("Template syntax error at line {line}: {message}")]
     TemplateSyntaxError { line: usize, message: String },
 
@@ -49,9 +53,343 @@ pub enum CliError {
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
+
+    #[error("Generated output is stale: {0}")]
+    StaleOutputError(String),
+
+    #[error("Template rendering stopped: {0}")]
+    RenderLimitError(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceededError(String),
+
+    #[error("Invalid region: {0}")]
+    InvalidRegionError(String),
+
+    #[error("Failed to resolve secret {0}")]
+    SecretError(String),
+
+    #[error("Output path conflict: {0}")]
+    OutputConflictError(String),
+
+    #[error("Template lint failed: {0}")]
+    LintError(String),
+
+    #[error("Unsafe output directory: {0}")]
+    UnsafeOutputDirError(String),
+
+    #[error("Stack state check failed: {0}")]
+    StackStateError(String),
+
+    #[error("API response too large: {0}")]
+    ResponseTooLargeError(String),
+
+    #[error("Refusing to overwrite existing files: {0}")]
+    ExistingFileError(String),
+
+    #[error("Post-generation hook failed: {0}")]
+    HookFailedError(String),
+
+    #[error("Policy violation: {0}")]
+    PolicyViolationError(String),
+
+    #[error("IDP API unavailable: {0}")]
+    ApiUnavailableError(String),
+}
+
+/// Link to a section of the README
+macro_rules! docs {
+    ($anchor:literal) => {
+        Some(concat!("https://github.com/angryss/idp-cli#", $anchor))
+    };
+}
+
+/// Catalog entry describing a kind of error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorInfo {
+    /// Stable code, safe to match on in scripts and support tickets
+    pub code: &'static str,
+    /// One-line description of the error kind
+    pub summary: &'static str,
+    /// Extended guidance shown with `--verbose-errors`
+    pub remediation: &'static str,
+    /// Documentation covering the error, if any
+    pub docs_url: Option<&'static str>,
 }
 
 impl CliError {
+    /// Catalog entry for this error
+    pub fn info(&self) -> ErrorInfo {
+        let (code, summary, remediation, docs_url) = match self {
+            CliError::AuthenticationError(_) => (
+                "IDP001",
+                "Authentication failed",
+                "- Verify the API key is set: echo $IDP_API_KEY\n\
+                 - Check the key is valid and has not expired in IDP\n\
+                 - Use --api-key to override the environment variable",
+                docs!("troubleshooting"),
+            ),
+            CliError::NotFoundError(_) => (
+                "IDP002",
+                "Resource not found",
+                "- Check the spelling of the identifier\n\
+                 - Try the UUID instead of the name\n\
+                 - Check you have access to the resource in IDP\n\
+                 - Verify the API URL (--api-url)",
+                docs!("troubleshooting"),
+            ),
+            CliError::ApiError(_) => (
+                "IDP003",
+                "IDP API returned an error",
+                "- Retry the request; transient API errors are retried automatically\n\
+                 - Check the API logs for the request if the error persists",
+                None,
+            ),
+            CliError::NetworkError(_) => (
+                "IDP004",
+                "Could not reach the IDP API",
+                "- Verify the API is running: curl $IDP_API_URL/health\n\
+                 - Check the API URL: echo $IDP_API_URL\n\
+                 - Check network connectivity, proxies and firewall rules",
+                docs!("troubleshooting"),
+            ),
+            CliError::IoError(_) => (
+                "IDP005",
+                "File system error",
+                "- Check the path exists and is readable/writable by the current user\n\
+                 - Check there is enough free disk space",
+                None,
+            ),
+            CliError::ConfigurationError(_) => (
+                "IDP006",
+                "Invalid configuration",
+                "- Check the command-line arguments with --help\n\
+                 - Check environment variables and configuration files",
+                docs!("configuration-precedence"),
+            ),
+            CliError::GenerationError(_) => (
+                "IDP007",
+                "Code generation failed",
+                "- Review the individual errors reported above\n\
+                 - Re-run with --debug-bundle to capture the failing inputs",
+                None,
+            ),
+            CliError::VariableFileError(_) => (
+                "IDP008",
+                "Invalid variable file",
+                "- Variable files must be a JSON or YAML object, or Terraform variable assignments\n\
+                 - Check the file extension matches its format (.json, .yaml, .yml, .tfvars, .hcl)\n\
+                 - Values in .tfvars files must be constants, without var.* references or function calls",
+                docs!("custom-variables"),
+            ),
+            CliError::DiscoveryError(_) => (
+                "IDP009",
+                "Template discovery failed",
+                "- Verify the template directory exists: ls -la <template-dir>\n\
+                 - Use an absolute path with --template-dir\n\
+                 - Check the current working directory: pwd",
+                docs!("template-errors"),
+            ),
+            CliError::ProcessingError(_) => (
+                "IDP010",
+                "Template processing failed",
+                "- Verify all {{variable}} placeholders have corresponding values\n\
+                 - Check that nested access paths are correct (e.g., {{resource.name}})\n\
+                 - Ensure array indices are valid (e.g., {{resources.0.name}})\n\
+                 - Use the 'list-variables' command to see available variables",
+                docs!("template-errors"),
+            ),
+            CliError::ValidationError(_) => (
+                "IDP011",
+                "Generated output is not valid",
+                "This usually means:\n\
+                 - A variable substitution resulted in an invalid structure\n\
+                 - Missing or incorrect indentation\n\
+                 - Unquoted special characters, e.g. use name: \"{{stack.name}}\"",
+                docs!("template-errors"),
+            ),
+            CliError::TemplateSyntaxError { .. } => (
+                "IDP012",
+                "Template syntax error",
+                "Common template syntax issues:\n\
+                 - Unclosed braces: {{ variable (missing closing }})\n\
+                 - Invalid helper syntax: {{helper param1 param2}}\n\
+                 - Mismatched block helpers: {{#if}} without {{/if}}",
+                docs!("template-syntax"),
+            ),
+            CliError::VariableNotFoundError { .. } => (
+                "IDP013",
+                "Variable not found",
+                "- Use the 'list-variables' command to see available variables\n\
+                 - Use dot notation for nested access (e.g., {{blueprint.name}})\n\
+                 - Add a default value: {{variable|default:\"value\"}}",
+                docs!("variable-discovery-issues"),
+            ),
+            CliError::YamlParseError(_) => (
+                "IDP014",
+                "Invalid YAML input",
+                "- Check indentation and quoting in the YAML document\n\
+                 - Validate the file with a YAML linter",
+                None,
+            ),
+            CliError::JsonParseError(_) => (
+                "IDP015",
+                "Invalid JSON input",
+                "- Check for trailing commas and unquoted keys\n\
+                 - Validate the document with a JSON linter (e.g., jq .)",
+                None,
+            ),
+            CliError::S3Error(_) => (
+                "IDP016",
+                "S3 request failed",
+                "- Check the bucket name and region\n\
+                 - Check the IAM role allows s3:GetObject, s3:PutObject and s3:ListBucket",
+                docs!("environment-variables"),
+            ),
+            CliError::ConfigError(_) => (
+                "IDP017",
+                "Missing or invalid environment configuration",
+                "- Check the required environment variables are set\n\
+                 - See the environment variable reference for accepted values",
+                docs!("environment-variables"),
+            ),
+            CliError::StaleOutputError(_) => (
+                "IDP018",
+                "Generated output is out of date",
+                "- Regenerate the output with the same generate command\n\
+                 - Review the changes with 'generate --diff' before committing\n\
+                 - Commit the regenerated files together with .idp-cli/manifest.json",
+                docs!("check-freshness"),
+            ),
+            CliError::RenderLimitError(_) => (
+                "IDP019",
+                "Template exceeded its render time or sandbox limits",
+                "- Look for custom helpers that call themselves or loop over very large lists\n\
+                 - Raise --render-timeout (RENDER_TIMEOUT_SECS for Lambda) if the template is just slow\n\
+                 - Allowlist what the template reads with --allow-env and --allow-file",
+                docs!("sandboxing"),
+            ),
+            CliError::QuotaExceededError(_) => (
+                "IDP020",
+                "Generated resources exceed an organization quota",
+                "- Reduce the resource count or size in the blueprint or stack\n\
+                 - Request a quota increase, then raise the limit in the quotas file\n\
+                 - Set 'severity: warning' on the quota to report it without failing",
+                docs!("resource-quotas"),
+            ),
+            CliError::InvalidRegionError(_) => (
+                "IDP021",
+                "Configuration names a region that does not exist",
+                "- Fix the region in the blueprint, stack or variables file\n\
+                 - Check the suggested spelling, e.g. us-east-1 for AWS and us-east1 for GCP\n\
+                 - Pass --skip-region-check for regions newer than this CLI",
+                docs!("region-validation"),
+            ),
+            CliError::SecretError(_) => (
+                "IDP022",
+                "A secret referenced by the variables file could not be read",
+                "- Check the ssm:// or secretsmanager:// reference for typos\n\
+                 - Check AWS credentials and region (AWS_PROFILE, AWS_REGION)\n\
+                 - Grant ssm:GetParameter, secretsmanager:GetSecretValue and kms:Decrypt on the secret",
+                docs!("secret-references"),
+            ),
+            CliError::OutputConflictError(_) => (
+                "IDP023",
+                "Several templates render to the same output file",
+                "- Rename or remove one of the listed templates\n\
+                 - Keep overrides in template sets and pass --on-conflict precedence to let the higher set win\n\
+                 - Rename files whose paths differ only in case\n\
+                 - Make templated file names unique, e.g. include {{name}} in paths that loop over a list",
+                docs!("output-path-conflicts"),
+            ),
+            CliError::LintError(_) => (
+                "IDP024",
+                "Templates reference variables or helpers that are not available",
+                "- Fix the reported lines, or guard optional values with {{#if}} or {{default}}\n\
+                 - Use the 'list-variables' command to see the variables the data source provides\n\
+                 - Define custom helpers in the template directory's helpers/ directory or --helpers-file",
+                docs!("lint-templates"),
+            ),
+            CliError::UnsafeOutputDirError(_) => (
+                "IDP025",
+                "Refused to generate into a directory where files could be lost",
+                "- Point --output-dir at a dedicated directory, e.g. ./output\n\
+                 - Commit or stash the repository's changes so the generated files can be reviewed with git diff\n\
+                 - Pass --force if writing there is intended",
+                docs!("output-directory-safety"),
+            ),
+            CliError::StackStateError(_) => (
+                "IDP026",
+                "The stack is not in a state required for generation",
+                "- Check the stack's state with: idp-cli stack status <STACK>\n\
+                 - Wait for provisioning to finish, or fix the failed resources (idp-cli stack resources <STACK>)\n\
+                 - Pass --on-state-mismatch warn to generate anyway",
+                docs!("stack-state-gating"),
+            ),
+            CliError::ResponseTooLargeError(_) => (
+                "IDP027",
+                "The API returned a response larger than the configured limit",
+                "- Raise the limit with --max-response-bytes or IDP_MAX_RESPONSE_BYTES\n\
+                 - Check that --api-url points at the IDP API and not at a proxy or file server",
+                docs!("large-stacks"),
+            ),
+            CliError::ExistingFileError(_) => (
+                "IDP028",
+                "Generated files already exist in the output directory",
+                "- Pass --on-existing overwrite, skip or backup to decide what happens to existing files\n\
+                 - Or generate into an empty directory with --output-dir\n\
+                 - For eject, pass --force to overwrite the templates",
+                docs!("existing-files"),
+            ),
+            CliError::HookFailedError(_) => (
+                "IDP029",
+                "A post-generation hook exited with a non-zero status",
+                "- Fix the problems the hook reported in the generated files or the templates\n\
+                 - Or drop --strict-hooks to report hook failures as warnings",
+                docs!("post-generation-hooks"),
+            ),
+            CliError::PolicyViolationError(_) => (
+                "IDP030",
+                "The generated files break a deny rule of a policy in --policy-dir",
+                "- Change the blueprint, stack or templates so the generated files satisfy the policy\n\
+                 - Or ask the policy owners whether the rule should be a warn rule",
+                docs!("policy-checks"),
+            ),
+            CliError::ApiUnavailableError(_) => (
+                "IDP031",
+                "Requests to the IDP API are paused after repeated failures",
+                "- Check that the IDP API is up and that --api-url points at it\n\
+                 - Retry after the pause; a long-running server resumes on its own\n\
+                 - To work on templates meanwhile, render a debug bundle from an earlier run with 'idp-cli replay'",
+                docs!("api-failures"),
+            ),
+        };
+        ErrorInfo {
+            code,
+            summary,
+            remediation,
+            docs_url,
+        }
+    }
+
+    /// Stable code identifying the kind of error (e.g. `IDP012`)
+    pub fn code(&self) -> &'static str {
+        self.info().code
+    }
+
+    /// Error report for the terminal; `verbose` adds remediation and docs
+    pub fn report(&self, verbose: bool) -> String {
+        let info = self.info();
+        let mut report = format!("Error [{}]: {}", info.code, self.user_message());
+        if verbose {
+            report.push_str(&format!("\n\n{}\n{}", info.summary, info.remediation));
+            if let Some(url) = info.docs_url {
+                report.push_str(&format!("\n\nSee: {}", url));
+            }
+        }
+        report
+    }
+
     pub fn user_message(&self) -> String {
         match self {
             CliError::AuthenticationError(_) => {
@@ -82,3 +420,63 @@ impl From<std::io::Error> for CliError {
         CliError::IoError(err.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn all_variants() -> Vec<CliError> {
+        vec![
+            CliError::AuthenticationError("x".into()),
+            CliError::NotFoundError("x".into()),
+            CliError::ApiError("x".into()),
+            CliError::IoError("x".into()),
+            CliError::ConfigurationError("x".into()),
+            CliError::GenerationError("x".into()),
+            CliError::VariableFileError("x".into()),
+            CliError::DiscoveryError("x".into()),
+            CliError::ProcessingError("x".into()),
+            CliError::ValidationError("x".into()),
+            CliError::TemplateSyntaxError { line: 1, message: "x".into() },
+            CliError::VariableNotFoundError { variable: "x".into(), suggestion: String::new() },
+            CliError::YamlParseError(serde_yaml::from_str::<u32>("x").unwrap_err()),
+            CliError::JsonParseError(serde_json::from_str::<u32>("x").unwrap_err()),
+            CliError::S3Error("x".into()),
+            CliError::ConfigError("x".into()),
+            CliError::StaleOutputError("x".into()),
+            CliError::RenderLimitError("x".into()),
+            CliError::QuotaExceededError("x".into()),
+            CliError::InvalidRegionError("x".into()),
+            CliError::SecretError("x".into()),
+            CliError::OutputConflictError("x".into()),
+            CliError::LintError("x".into()),
+            CliError::UnsafeOutputDirError("x".into()),
+            CliError::StackStateError("x".into()),
+            CliError::ResponseTooLargeError("x".into()),
+            CliError::ExistingFileError("x".into()),
+            CliError::HookFailedError("x".into()),
+            CliError::PolicyViolationError("x".into()),
+            CliError::ApiUnavailableError("x".into()),
+        ]
+    }
+
+    #[test]
+    fn test_error_codes_are_unique() {
+        let codes: HashSet<_> = all_variants().iter().map(|e| e.code()).collect();
+        assert_eq!(codes.len(), all_variants().len());
+        assert!(codes.iter().all(|code| code.starts_with("IDP")));
+    }
+
+    #[test]
+    fn test_report_adds_remediation_only_when_verbose() {
+        let error = CliError::TemplateSyntaxError { line: 3, message: "unclosed".into() };
+        let short = error.report(false);
+        assert!(short.starts_with("Error [IDP012]: Template syntax error at line 3"));
+        assert!(!short.contains("Common template syntax issues"));
+
+        let verbose = error.report(true);
+        assert!(verbose.contains("Common template syntax issues"));
+        assert!(verbose.contains("See: https://github.com/angryss/idp-cli#template-syntax"));
+    }
+}
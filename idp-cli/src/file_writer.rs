@@ -1,51 +1,473 @@
+use crate::cli::ExistingFilePolicy;
 use crate::error::CliError;
+use crate::output_style::OutputStyle;
+use crate::paths::{long_path, to_slash};
+use crate::s3_client::{self, S3Client, S3Location};
 use crate::template_processor::ProcessedFile;
+use serde::Serialize;
+use similar::TextDiff;
 use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tempfile::TempDir;
 
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
+/// Directory under the output directory holding annotated copies from `--debug-render`
+pub const DEBUG_RENDER_DIR: &str = ".idp-cli/debug-render";
+
+/// How writing a file would change the output directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileChange {
+    Created,
+    Modified,
+    Unchanged,
+}
+
+/// What writing a processed file would do, reported by `--dry-run`
+#[derive(Debug, Clone)]
+pub struct FilePreview {
+    pub path: PathBuf,
+    pub bytes: usize,
+    pub change: FileChange,
+    /// Unified diff against the existing file, or against /dev/null for new files
+    pub diff: Option<String>,
+}
+
+/// What writing one processed file did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WriteOutcome {
+    /// Written where no file existed
+    Created(PathBuf),
+    /// Replaced an existing file
+    Overwritten(PathBuf),
+    /// Replaced an existing file after moving it to `backup`
+    BackedUp { path: PathBuf, backup: PathBuf },
+    /// Left the existing file as it was
+    Skipped(PathBuf),
+}
+
+impl WriteOutcome {
+    /// Path of the generated file, whether or not it was written
+    pub fn path(&self) -> &Path {
+        match self {
+            WriteOutcome::Created(path)
+            | WriteOutcome::Overwritten(path)
+            | WriteOutcome::BackedUp { path, .. }
+            | WriteOutcome::Skipped(path) => path,
+        }
+    }
+
+    /// Path of the file if it was written
+    pub fn written(&self) -> Option<&Path> {
+        match self {
+            WriteOutcome::Skipped(_) => None,
+            _ => Some(self.path()),
+        }
+    }
+}
+
 pub struct FileWriter {
     output_dir: PathBuf,
+    warn_on_overwrite: bool,
+    on_existing: ExistingFilePolicy,
+    /// Set when the user answered "all" to an overwrite prompt
+    overwrite_all: AtomicBool,
+    staging: Option<Staging>,
+}
+
+/// Files written since [`FileWriter::with_staging`], waiting for [`FileWriter::commit`]
+struct Staging {
+    /// `.idp-cli` in the output directory, where the staging directory is created
+    parent: PathBuf,
+    /// Whether `parent` was created for staging, so it is removed again if left empty
+    created_parent: AtomicBool,
+    /// Created on the first write and removed on drop, with anything never committed
+    dir: Mutex<Option<TempDir>>,
+    files: Mutex<Vec<StagedFile>>,
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        if let Some(dir) = self.dir.get_mut().ok().and_then(Option::take) {
+            let _ = dir.close();
+        }
+        if *self.created_parent.get_mut() {
+            let _ = fs::remove_dir(long_path(&self.parent));
+        }
+    }
+}
+
+struct StagedFile {
+    staged: PathBuf,
+    target: PathBuf,
+    backup: Option<PathBuf>,
 }
 
 impl FileWriter {
     pub fn new(output_dir: PathBuf) -> Self {
-        Self { output_dir }
+        Self {
+            output_dir,
+            warn_on_overwrite: true,
+            on_existing: ExistingFilePolicy::default(),
+            overwrite_all: AtomicBool::new(false),
+            staging: None,
+        }
+    }
+
+    /// Stage written files and move them into place together on [`FileWriter::commit`]
+    /// 
+    /// The staging directory is created under `.idp-cli` in the output
+    /// directory on the first write, so moving a file into place is a rename
+    /// on the same file system. It is removed when the writer is dropped, so a
+    /// run that fails before committing leaves the output directory as it was.
+    pub fn with_staging(mut self) -> Self {
+        self.staging = Some(Staging {
+            parent: self.output_dir.join(".idp-cli"),
+            created_parent: AtomicBool::new(false),
+            dir: Mutex::new(None),
+            files: Mutex::new(Vec::new()),
+        });
+        self
+    }
+
+    /// What to do with generated files that already exist (default: overwrite)
+    pub fn with_existing_policy(mut self, on_existing: ExistingFilePolicy) -> Self {
+        self.on_existing = on_existing;
+        self
+    }
+
+    /// Don't print a warning to stderr when overwriting a file
+    /// 
+    /// For callers that report overwrites themselves, e.g. as progress events.
+    pub fn without_overwrite_warnings(mut self) -> Self {
+        self.warn_on_overwrite = false;
+        self
+    }
+
+    /// Refuse output directories where generated files could destroy work
+    /// 
+    /// Fails with `UnsafeOutputDirError` when the output directory is the file
+    /// system root, the home directory, or a git working tree with uncommitted
+    /// changes. Directories that don't exist yet are always safe. Callers skip
+    /// this check for `--force`.
+    pub fn check_output_dir(&self) -> Result<(), CliError> {
+        let Ok(dir) = self.output_dir.canonicalize() else {
+            return Ok(());
+        };
+        let reason = if dir.parent().is_none() {
+            "is the root of the file system"
+        } else if home_dir().is_some_and(|home| home == dir) {
+            "is the home directory"
+        } else if dir.join(".git").exists() && has_uncommitted_changes(&dir) {
+            "is a git repository with uncommitted changes"
+        } else {
+            return Ok(());
+        };
+        Err(CliError::UnsafeOutputDirError(format!(
+            "'{}' {}; pass --force to write there anyway",
+            self.output_dir.display(),
+            reason
+        )))
+    }
+
+    /// Warning for an output directory inside the template directory
+    /// 
+    /// Generated files written there are discovered as templates on the next run.
+    pub fn nested_output_warning(&self, template_dir: &Path) -> Option<String> {
+        let resolve = |path: &Path| path.canonicalize().or_else(|_| std::path::absolute(path)).ok();
+        let (output, templates) = (resolve(&self.output_dir)?, resolve(template_dir)?);
+        output.starts_with(&templates).then(|| {
+            format!(
+                "Output directory {} is inside the template directory {}; generated files will be rendered as templates on the next run",
+                self.output_dir.display(),
+                template_dir.display()
+            )
+        })
     }
 
     /// Write processed template files to the output directory
     /// 
     /// Preserves the directory structure from the template directory and creates
-    /// parent directories as needed. Warns before overwriting existing files.
-    /// Uses atomic writes (write to temp, then rename) for safety.
+    /// parent directories as needed. Existing files are handled by the
+    /// writer's [`ExistingFilePolicy`]. Uses atomic writes (write to temp, then
+    /// rename) for safety.
     /// 
     /// # Arguments
     /// * `files` - Vector of ProcessedFile instances to write
     /// 
     /// # Returns
-    /// * `Ok(Vec<PathBuf>)` - Paths to all written files
+    /// * `Ok(Vec<PathBuf>)` - Paths to all written files, without skipped ones
     /// * `Err(CliError)` - If file writing fails
     pub fn write_processed_files(&self, files: &[ProcessedFile]) -> Result<Vec<PathBuf>, CliError> {
-        let mut written_files = Vec::new();
+        let mut written = Vec::new();
+        for file in files {
+            if let Some(path) = self.write_processed_file(file)?.written() {
+                written.push(path.to_path_buf());
+            }
+        }
+        Ok(written)
+    }
 
-        for processed_file in files {
-            // Construct the full output path by joining output_dir with relative_path
-            let output_path = self.output_dir.join(&processed_file.relative_path);
+    /// Write a single processed file to the output directory
+    /// 
+    /// Used by the streaming pipeline to write each file as soon as it is rendered.
+    /// 
+    /// # Returns
+    /// * `Ok(WriteOutcome)` - Whether the file was created, replaced or skipped
+    /// * `Err(CliError)` - If file writing fails, or the file exists and the
+    ///   policy is `fail` (or `prompt` without a terminal)
+    pub fn write_processed_file(&self, processed_file: &ProcessedFile) -> Result<WriteOutcome, CliError> {
+        // Construct the full output path by joining output_dir with relative_path
+        let output_path = self.output_dir.join(&processed_file.relative_path);
 
-            // Ensure parent directory exists
-            if let Some(parent) = output_path.parent() {
-                self.ensure_directory_exists(parent)?;
+        let outcome = if !long_path(&output_path).exists() {
+            WriteOutcome::Created(output_path)
+        } else {
+            match self.on_existing {
+                ExistingFilePolicy::Overwrite => WriteOutcome::Overwritten(output_path),
+                ExistingFilePolicy::Skip => return Ok(WriteOutcome::Skipped(output_path)),
+                ExistingFilePolicy::Fail => return Err(existing_file_error(&output_path)),
+                ExistingFilePolicy::Prompt => {
+                    if !self.overwrite_all.load(Ordering::Relaxed) && !self.confirm_overwrite(&output_path)? {
+                        return Ok(WriteOutcome::Skipped(output_path));
+                    }
+                    WriteOutcome::Overwritten(output_path)
+                }
+                ExistingFilePolicy::Backup => {
+                    // Unchanged files need no backup
+                    let unchanged = fs::read_to_string(long_path(&output_path))
+                        .is_ok_and(|existing| existing == processed_file.content);
+                    if unchanged {
+                        WriteOutcome::Overwritten(output_path)
+                    } else {
+                        let backup = backup_path(&output_path);
+                        WriteOutcome::BackedUp { path: output_path, backup }
+                    }
+                }
             }
+        };
 
-            // Write file with warning if it exists
-            self.write_with_warning(&output_path, &processed_file.content)?;
+        match &self.staging {
+            Some(staging) => self.stage(staging, processed_file, &outcome)?,
+            None => self.write_in_place(&outcome, &processed_file.content)?,
+        }
+        Ok(outcome)
+    }
 
-            written_files.push(output_path);
+    /// Write a file straight to its place in the output directory
+    fn write_in_place(&self, outcome: &WriteOutcome, content: &str) -> Result<(), CliError> {
+        let output_path = outcome.path();
+        // Ensure parent directory exists
+        if let Some(parent) = output_path.parent() {
+            self.ensure_directory_exists(parent)?;
         }
+        match outcome {
+            WriteOutcome::Overwritten(path) => self.write_with_warning(path, content),
+            WriteOutcome::BackedUp { path, backup } => {
+                fs::rename(long_path(path), long_path(backup)).map_err(|e| {
+                    CliError::IoError(format!("Failed to back up {}: {}", path.display(), e))
+                })?;
+                self.write_atomic(path, content)
+            }
+            _ => self.write_atomic(output_path, content),
+        }
+    }
 
-        Ok(written_files)
+    /// Write a file to the staging directory, to be moved into place by [`FileWriter::commit`]
+    fn stage(&self, staging: &Staging, processed_file: &ProcessedFile, outcome: &WriteOutcome) -> Result<(), CliError> {
+        if self.warn_on_overwrite && matches!(outcome, WriteOutcome::Overwritten(_)) {
+            eprintln!("Warning: Overwriting existing file: {}", outcome.path().display());
+        }
+        let backup = match outcome {
+            WriteOutcome::BackedUp { backup, .. } => Some(backup.clone()),
+            _ => None,
+        };
+        let staged = Path::new("files").join(&processed_file.relative_path);
+        self.stage_content(staging, &staged, &processed_file.content, outcome.path(), backup)
+    }
+
+    /// Write `content` to `staged` in the staging directory, to be moved to `target`
+    fn stage_content(
+        &self,
+        staging: &Staging,
+        staged: &Path,
+        content: &str,
+        target: &Path,
+        backup: Option<PathBuf>,
+    ) -> Result<(), CliError> {
+        let staged = self.staging_dir(staging)?.join(staged);
+        if let Some(parent) = staged.parent() {
+            self.ensure_directory_exists(parent)?;
+        }
+        self.write_atomic(&staged, content)?;
+        staging.files.lock().unwrap().push(StagedFile { staged, target: target.to_path_buf(), backup });
+        Ok(())
+    }
+
+    /// The staging directory, created on first use
+    fn staging_dir(&self, staging: &Staging) -> Result<PathBuf, CliError> {
+        let mut dir = staging.dir.lock().unwrap();
+        if let Some(dir) = dir.as_ref() {
+            return Ok(dir.path().to_path_buf());
+        }
+        if !long_path(&staging.parent).exists() {
+            self.ensure_directory_exists(&staging.parent)?;
+            staging.created_parent.store(true, Ordering::Relaxed);
+        }
+        let created = tempfile::Builder::new().prefix("staging-").tempdir_in(long_path(&staging.parent)).map_err(|e| {
+            CliError::IoError(format!("Failed to create a staging directory in {}: {}", staging.parent.display(), e))
+        })?;
+        Ok(dir.insert(created).path().to_path_buf())
+    }
+
+    /// Move the staged files into the output directory
+    /// 
+    /// Existing files are moved aside (to their backup path, for the `backup`
+    /// policy) before being replaced. If any move fails, every file moved so far
+    /// is put back and directories created for them are removed, so the output
+    /// directory is either fully updated or left as it was. Does nothing for a
+    /// writer without staging.
+    pub fn commit(&self) -> Result<(), CliError> {
+        let Some(staging) = &self.staging else {
+            return Ok(());
+        };
+        let staged = std::mem::take(&mut *staging.files.lock().unwrap());
+        if staged.is_empty() {
+            return Ok(());
+        }
+        let staging_dir = self.staging_dir(staging)?;
+        let mut moved: Vec<(&StagedFile, Option<PathBuf>)> = Vec::new();
+        let mut created_dirs = Vec::new();
+        for (index, file) in staged.iter().enumerate() {
+            let aside = file.backup.clone().unwrap_or_else(|| staging_dir.join("replaced").join(index.to_string()));
+            match self.move_into_place(file, aside, &mut created_dirs) {
+                Ok(displaced) => moved.push((file, displaced)),
+                Err(e) => {
+                    for (file, displaced) in moved.iter().rev() {
+                        let _ = fs::remove_file(long_path(&file.target));
+                        if let Some(displaced) = displaced {
+                            let _ = fs::rename(long_path(displaced), long_path(&file.target));
+                        }
+                    }
+                    for dir in created_dirs.iter().rev() {
+                        let _ = fs::remove_dir(long_path(dir));
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rename one staged file to its target, returning where the file it replaced went
+    fn move_into_place(
+        &self,
+        file: &StagedFile,
+        aside: PathBuf,
+        created_dirs: &mut Vec<PathBuf>,
+    ) -> Result<Option<PathBuf>, CliError> {
+        if let Some(parent) = file.target.parent() {
+            let missing: Vec<PathBuf> =
+                parent.ancestors().take_while(|dir| !long_path(dir).exists()).map(Path::to_path_buf).collect();
+            self.ensure_directory_exists(parent)?;
+            created_dirs.extend(missing.into_iter().rev());
+        }
+        let displaced = if long_path(&file.target).exists() {
+            if let Some(parent) = aside.parent() {
+                self.ensure_directory_exists(parent)?;
+            }
+            fs::rename(long_path(&file.target), long_path(&aside)).map_err(|e| {
+                CliError::IoError(format!("Failed to move {} aside: {}", file.target.display(), e))
+            })?;
+            Some(aside)
+        } else {
+            None
+        };
+        if let Err(e) = fs::rename(long_path(&file.staged), long_path(&file.target)) {
+            if let Some(displaced) = &displaced {
+                let _ = fs::rename(long_path(displaced), long_path(&file.target));
+            }
+            return Err(CliError::IoError(format!("Failed to move {} into place: {}", file.target.display(), e)));
+        }
+        Ok(displaced)
+    }
+
+    /// Ask on the terminal whether to overwrite a file
+    fn confirm_overwrite(&self, path: &Path) -> Result<bool, CliError> {
+        if !std::io::stdin().is_terminal() {
+            return Err(CliError::ExistingFileError(format!(
+                "{} (--on-existing prompt needs an interactive terminal)",
+                path.display()
+            )));
+        }
+        eprint!("Overwrite {}? [y]es, [N]o, [a]ll: ", path.display());
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer)?;
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "a" | "all" => {
+                self.overwrite_all.store(true, Ordering::Relaxed);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Compare a processed file with what is on disk without writing anything
+    pub fn preview(&self, processed_file: &ProcessedFile) -> Result<FilePreview, CliError> {
+        let output_path = self.output_dir.join(&processed_file.relative_path);
+        let path = to_slash(&processed_file.relative_path);
+        let diff = |existing: &str, old_header: &str| {
+            TextDiff::from_lines(existing, &processed_file.content)
+                .unified_diff()
+                .context_radius(3)
+                .header(old_header, &format!("b/{}", path))
+                .to_string()
+        };
+        let (change, diff) = match fs::read_to_string(long_path(&output_path)) {
+            Ok(existing) if existing == processed_file.content => (FileChange::Unchanged, None),
+            Ok(existing) => (FileChange::Modified, Some(diff(&existing, &format!("a/{}", path)))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (FileChange::Created, Some(diff("", "/dev/null"))),
+            Err(e) => {
+                return Err(CliError::IoError(format!("Failed to read {}: {}", output_path.display(), e)));
+            }
+        };
+
+        Ok(FilePreview {
+            path: output_path,
+            bytes: processed_file.content.len(),
+            change,
+            diff,
+        })
+    }
+
+    /// Write the annotated copy of a processed file under [`DEBUG_RENDER_DIR`], if it has one
+    /// 
+    /// Annotated copies are replaced on every run without a warning. With
+    /// staging, they are moved into place with the generated files.
+    pub fn write_annotated_file(&self, processed_file: &ProcessedFile) -> Result<Option<PathBuf>, CliError> {
+        let Some(annotated) = &processed_file.annotated else {
+            return Ok(None);
+        };
+        let output_path = self.output_dir.join(DEBUG_RENDER_DIR).join(&processed_file.relative_path);
+        if let Some(staging) = &self.staging {
+            let staged = Path::new("annotated").join(&processed_file.relative_path);
+            self.stage_content(staging, &staged, annotated, &output_path, None)?;
+            return Ok(Some(output_path));
+        }
+        if let Some(parent) = output_path.parent() {
+            self.ensure_directory_exists(parent)?;
+        }
+        self.write_atomic(&output_path, annotated)?;
+        Ok(Some(output_path))
     }
 
     /// Write file content with warning if file exists, using atomic write
@@ -65,31 +487,36 @@ impl FileWriter {
     /// * `Err(CliError)` - If writing fails
     fn write_with_warning(&self, path: &Path, content: &str) -> Result<(), CliError> {
         // Check if file exists and warn user
-        if path.exists() {
+        if self.warn_on_overwrite && long_path(path).exists() {
             eprintln!("Warning: Overwriting existing file: {}", path.display());
         }
+        self.write_atomic(path, content)
+    }
 
+    /// Write file content through a temporary file and rename, with 0600 permissions on Unix
+    fn write_atomic(&self, path: &Path, content: &str) -> Result<(), CliError> {
         // Create a temporary file in the same directory for atomic write
         let temp_path = path.with_extension("tmp");
+        let (fs_path, fs_temp_path) = (long_path(path), long_path(&temp_path));
         
         // Write content to temporary file
-        fs::write(&temp_path, content).map_err(|e| {
+        fs::write(&fs_temp_path, content).map_err(|e| {
             CliError::IoError(format!("Failed to write temporary file {}: {}", temp_path.display(), e))
         })?;
 
         // Set restrictive file permissions (0600) on Unix systems before rename
         #[cfg(unix)]
         {
-            let metadata = fs::metadata(&temp_path)?;
+            let metadata = fs::metadata(&fs_temp_path)?;
             let mut permissions = metadata.permissions();
             permissions.set_mode(0o600);
-            fs::set_permissions(&temp_path, permissions)?;
+            fs::set_permissions(&fs_temp_path, permissions)?;
         }
 
         // Atomically rename temp file to target path
-        fs::rename(&temp_path, path).map_err(|e| {
+        fs::rename(&fs_temp_path, &fs_path).map_err(|e| {
             // Clean up temp file if rename fails
-            let _ = fs::remove_file(&temp_path);
+            let _ = fs::remove_file(&fs_temp_path);
             CliError::IoError(format!("Failed to rename {} to {}: {}", temp_path.display(), path.display(), e))
         })?;
 
@@ -105,8 +532,9 @@ impl FileWriter {
     /// * `Ok(())` - If directory exists or was created successfully
     /// * `Err(CliError)` - If directory creation fails
     fn ensure_directory_exists(&self, path: &Path) -> Result<(), CliError> {
-        if !path.exists() {
-            fs::create_dir_all(path).map_err(|e| {
+        let fs_path = long_path(path);
+        if !fs_path.exists() {
+            fs::create_dir_all(&fs_path).map_err(|e| {
                 CliError::IoError(format!("Failed to create directory {}: {}", path.display(), e))
             })?;
         }
@@ -114,11 +542,229 @@ impl FileWriter {
     }
 }
 
+/// Key, under the upload prefix, of the manifest listing the uploaded files
+pub const UPLOAD_MANIFEST_KEY: &str = ".idp-cli/upload-manifest.json";
+
+/// A generated file stored in S3
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UploadedFile {
+    pub key: String,
+    pub content_type: &'static str,
+    pub bytes: usize,
+}
+
+/// Manifest object written after the last upload
+#[derive(Debug, Serialize)]
+struct UploadManifest<'a> {
+    bucket: &'a str,
+    generated_at: u64,
+    files: &'a [UploadedFile],
+}
+
+/// Writes generated files under an S3 prefix instead of (or besides) an output directory
+///
+/// Each file keeps its relative path below the prefix and gets a content type
+/// from its extension. [`S3Sink::finish`] writes a manifest object listing
+/// every uploaded key, so consumers can tell a complete upload from a partial one.
+pub struct S3Sink<'a> {
+    client: &'a S3Client,
+    location: S3Location,
+    kms_key: Option<String>,
+    files: Vec<UploadedFile>,
+}
+
+impl<'a> S3Sink<'a> {
+    /// Upload to `location` through a client whose outputs bucket is `location.bucket`
+    pub fn new(client: &'a S3Client, location: S3Location) -> Self {
+        Self { client, location, kms_key: None, files: Vec::new() }
+    }
+
+    /// Encrypt the uploaded objects with this KMS key (ID, alias or ARN)
+    pub fn with_kms_key(mut self, kms_key: Option<String>) -> Self {
+        self.kms_key = kms_key;
+        self
+    }
+
+    /// Upload one file and return its key
+    pub async fn upload(&mut self, processed_file: &ProcessedFile) -> Result<String, CliError> {
+        self.put(&processed_file.path_str(), processed_file.content.clone().into_bytes()).await
+    }
+
+    /// Upload a file as it is on disk, keyed by its path relative to `output_dir`, and return its key
+    pub async fn upload_file(&mut self, output_dir: &Path, path: &Path) -> Result<String, CliError> {
+        let relative = path.strip_prefix(output_dir).unwrap_or(path);
+        let content = fs::read(long_path(path))
+            .map_err(|e| CliError::IoError(format!("Failed to read {} for upload: {}", path.display(), e)))?;
+        self.put(&to_slash(relative), content).await
+    }
+
+    async fn put(&mut self, path: &str, content: Vec<u8>) -> Result<String, CliError> {
+        let key = format!("{}{}", self.location.key_prefix(), path);
+        let content_type = s3_client::content_type(path);
+        let bytes = content.len();
+        self.client.put_output(&key, content, content_type, self.kms_key.as_deref()).await?;
+        self.files.push(UploadedFile { key: key.clone(), content_type, bytes });
+        Ok(key)
+    }
+
+    /// Write the manifest object and return the uploaded files
+    pub async fn finish(self) -> Result<Vec<UploadedFile>, CliError> {
+        let manifest = UploadManifest {
+            bucket: &self.location.bucket,
+            generated_at: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default(),
+            files: &self.files,
+        };
+        let content = serde_json::to_vec_pretty(&manifest)?;
+        let key = format!("{}{}", self.location.key_prefix(), UPLOAD_MANIFEST_KEY);
+        self.client
+            .put_output(&key, content, "application/json", self.kms_key.as_deref())
+            .await?;
+        Ok(self.files)
+    }
+}
+
+/// `<file>.bak.<unix seconds>` next to the file
+/// 
+/// Backups made in the same second get a `.1`, `.2`, ... suffix, so an
+/// earlier backup is never replaced.
+fn backup_path(path: &Path) -> PathBuf {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".bak.{}", timestamp));
+    let mut backup = PathBuf::from(&name);
+    let mut counter = 0;
+    while long_path(&backup).exists() {
+        counter += 1;
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", counter));
+        backup = PathBuf::from(numbered);
+    }
+    backup
+}
+
+fn existing_file_error(path: &Path) -> CliError {
+    CliError::ExistingFileError(path.display().to_string())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var)
+        .filter(|home| !home.is_empty())
+        .and_then(|home| PathBuf::from(home).canonicalize().ok())
+}
+
+/// Whether `git status` lists changes in `dir`; false when git can't tell
+fn has_uncommitted_changes(dir: &Path) -> bool {
+    Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["status", "--porcelain"])
+        .output()
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+/// Format `--dry-run` results: one line per file, diffs of modified files and totals
+pub fn render_previews(previews: &[FilePreview], style: OutputStyle) -> String {
+    let mut lines = Vec::new();
+    for preview in previews {
+        let (symbol, word) = match preview.change {
+            FileChange::Created => ('+', "new"),
+            FileChange::Modified => ('~', "modified"),
+            FileChange::Unchanged => ('=', "unchanged"),
+        };
+        lines.push(if style.is_plain() {
+            format!("{}: {} ({} bytes)", word, preview.path.display(), preview.bytes)
+        } else {
+            format!("  {} {} ({} bytes, {})", symbol, preview.path.display(), preview.bytes, word)
+        });
+        // New files are listed without their content
+        if let (FileChange::Modified, Some(diff)) = (preview.change, &preview.diff) {
+            lines.push(diff.trim_end().to_string());
+        }
+    }
+
+    let count = |change| previews.iter().filter(|p| p.change == change).count();
+    lines.push(format!(
+        "\n{} file(s) would be created, {} modified, {} unchanged",
+        count(FileChange::Created),
+        count(FileChange::Modified),
+        count(FileChange::Unchanged)
+    ));
+    lines.join("\n")
+}
+
+/// Concatenated unified diffs of new and modified files, reported by `--diff`
+pub fn render_diff(previews: &[FilePreview]) -> String {
+    previews.iter().filter_map(|preview| preview.diff.as_deref()).collect()
+}
+
+/// Turn an arbitrary value into a single safe directory name
+/// 
+/// Keeps ASCII letters, digits, `-`, `_` and `.`; every other character
+/// (including path separators) becomes `-`. Leading dots are removed so the
+/// result can't be `..` or a hidden directory. Empty results become `unassigned`.
+pub fn sanitize_path_segment(value: &str) -> String {
+    let sanitized: String = value
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '-' })
+        .collect();
+    let sanitized = sanitized.trim_start_matches('.');
+
+    if sanitized.is_empty() {
+        "unassigned".to_string()
+    } else {
+        sanitized.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_preview_reports_changes_without_writing() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_path_buf();
+        fs::write(output_dir.join("same.tf"), "a = 1\n").unwrap();
+        fs::write(output_dir.join("changed.tf"), "a = 1\nb = 2\n").unwrap();
+
+        let writer = FileWriter::new(output_dir.clone());
+        let previews: Vec<FilePreview> = [("same.tf", "a = 1\n"), ("changed.tf", "a = 1\nb = 3\n"), ("new.tf", "c = 4\n")]
+            .iter()
+            .map(|(name, content)| {
+                let file = ProcessedFile::new(PathBuf::from(name), content.to_string());
+                writer.preview(&file).unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            previews.iter().map(|p| p.change).collect::<Vec<_>>(),
+            vec![FileChange::Unchanged, FileChange::Modified, FileChange::Created]
+        );
+        assert!(!output_dir.join("new.tf").exists());
+        assert_eq!(fs::read_to_string(output_dir.join("changed.tf")).unwrap(), "a = 1\nb = 2\n");
+
+        let report = render_previews(&previews, OutputStyle::default());
+        assert!(report.contains("--- a/changed.tf\n+++ b/changed.tf\n@@ -1,2 +1,2 @@\n a = 1\n-b = 2\n+b = 3"));
+        assert!(report.contains(&format!("  + {} (6 bytes, new)", output_dir.join("new.tf").display())));
+        assert!(report.ends_with("1 file(s) would be created, 1 modified, 1 unchanged"));
+        assert!(!report.contains("c = 4"));
+
+        let patch = render_diff(&previews);
+        assert!(patch.starts_with("--- a/changed.tf\n"));
+        assert!(patch.ends_with("--- /dev/null\n+++ b/new.tf\n@@ -0,0 +1 @@\n+c = 4\n"));
+    }
+
+    #[test]
+    fn test_sanitize_path_segment() {
+        assert_eq!(sanitize_path_segment("payments-team"), "payments-team");
+        assert_eq!(sanitize_path_segment("Team A/B"), "Team-A-B");
+        assert_eq!(sanitize_path_segment("../etc"), "-etc");
+        assert_eq!(sanitize_path_segment("  "), "unassigned");
+    }
+
     #[test]
     fn test_ensure_directory_exists_creates_directory() {
         let temp_dir = TempDir::new().unwrap();
@@ -303,6 +949,137 @@ mod tests {
         assert_eq!(main_content, "new content");
     }
 
+    #[test]
+    fn test_existing_file_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_path_buf();
+        fs::write(output_dir.join("main.tf"), "old").unwrap();
+        let file = ProcessedFile::new(PathBuf::from("main.tf"), "new".to_string());
+        let writer = |policy| FileWriter::new(output_dir.clone()).with_existing_policy(policy);
+
+        let outcome = writer(ExistingFilePolicy::Skip).write_processed_file(&file).unwrap();
+        assert_eq!(outcome, WriteOutcome::Skipped(output_dir.join("main.tf")));
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "old");
+
+        let error = writer(ExistingFilePolicy::Fail).write_processed_file(&file).unwrap_err();
+        assert_eq!(error.code(), "IDP028");
+
+        let WriteOutcome::BackedUp { backup, .. } = writer(ExistingFilePolicy::Backup).write_processed_file(&file).unwrap()
+        else {
+            panic!("existing file was not backed up");
+        };
+        assert!(backup.file_name().unwrap().to_string_lossy().starts_with("main.tf.bak."));
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "old");
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "new");
+        // Nothing changed, so there is nothing to back up
+        let outcome = writer(ExistingFilePolicy::Backup).write_processed_file(&file).unwrap();
+        assert_eq!(outcome, WriteOutcome::Overwritten(output_dir.join("main.tf")));
+    }
+
+    #[test]
+    fn test_backups_in_the_same_second_are_kept() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_path_buf();
+        fs::write(output_dir.join("main.tf"), "first").unwrap();
+        let write = |content: &str, staged: bool| {
+            let mut writer = FileWriter::new(output_dir.clone()).with_existing_policy(ExistingFilePolicy::Backup);
+            if staged {
+                writer = writer.with_staging();
+            }
+            let file = ProcessedFile::new(PathBuf::from("main.tf"), content.to_string());
+            let WriteOutcome::BackedUp { backup, .. } = writer.write_processed_file(&file).unwrap() else {
+                panic!("existing file was not backed up");
+            };
+            writer.commit().unwrap();
+            backup
+        };
+
+        let backups = [write("second", false), write("third", false), write("fourth", true), write("fifth", true)];
+        let contents: Vec<String> = backups.iter().map(|backup| fs::read_to_string(backup).unwrap()).collect();
+        assert_eq!(contents, ["first", "second", "third", "fourth"]);
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "fifth");
+    }
+
+    #[test]
+    fn test_staged_writes_are_all_or_nothing() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_path_buf();
+        fs::write(output_dir.join("main.tf"), "old").unwrap();
+        let files = vec![
+            ProcessedFile::new(PathBuf::from("main.tf"), "new".to_string()),
+            ProcessedFile::new(PathBuf::from("modules/vpc/vpc.tf"), "vpc".to_string()),
+        ];
+
+        // Nothing reaches the output directory before the commit
+        let writer = FileWriter::new(output_dir.clone()).with_staging();
+        assert!(!output_dir.join(".idp-cli").exists(), "staging starts with the first write");
+        writer.write_processed_files(&files).unwrap();
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "old");
+        assert!(!output_dir.join("modules").exists());
+
+        // A file in the way of modules/ makes the second move fail
+        fs::write(output_dir.join("modules"), "").unwrap();
+        assert!(writer.commit().is_err());
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "old");
+        drop(writer);
+        assert!(!output_dir.join(".idp-cli").exists());
+
+        fs::remove_file(output_dir.join("modules")).unwrap();
+        let writer = FileWriter::new(output_dir.clone()).with_staging();
+        writer.write_processed_files(&files).unwrap();
+        writer.commit().unwrap();
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(output_dir.join("modules/vpc/vpc.tf")).unwrap(), "vpc");
+    }
+
+    #[test]
+    fn test_staging_leaves_nothing_behind_when_nothing_is_written() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_path_buf();
+        fs::write(output_dir.join("main.tf"), "old").unwrap();
+        let file = ProcessedFile::new(PathBuf::from("main.tf"), "new".to_string());
+
+        let writer = FileWriter::new(output_dir.clone()).with_staging();
+        writer.commit().unwrap();
+        drop(writer);
+        assert!(!output_dir.join(".idp-cli").exists());
+
+        // Skipped files are never staged
+        let writer = FileWriter::new(output_dir.clone()).with_staging().with_existing_policy(ExistingFilePolicy::Skip);
+        assert_eq!(writer.write_processed_file(&file).unwrap(), WriteOutcome::Skipped(output_dir.join("main.tf")));
+        writer.commit().unwrap();
+        drop(writer);
+        assert!(!output_dir.join(".idp-cli").exists());
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_annotated_copies_are_staged_with_the_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let output_dir = temp_dir.path().to_path_buf();
+        let mut file = ProcessedFile::new(PathBuf::from("main.tf"), "new".to_string());
+        file.annotated = Some("# main.tf:1 {{name}}\nnew".to_string());
+        let annotated = output_dir.join(DEBUG_RENDER_DIR).join("main.tf");
+
+        let writer = FileWriter::new(output_dir.clone()).with_staging();
+        writer.write_processed_file(&file).unwrap();
+        assert_eq!(writer.write_annotated_file(&file).unwrap(), Some(annotated.clone()));
+        assert!(!annotated.exists());
+        writer.commit().unwrap();
+        assert_eq!(fs::read_to_string(&annotated).unwrap(), "# main.tf:1 {{name}}\nnew");
+
+        // Replaced on the next run, and dropped with a run that never commits
+        file.annotated = Some("# changed".to_string());
+        let writer = FileWriter::new(output_dir.clone()).with_staging();
+        writer.write_annotated_file(&file).unwrap();
+        drop(writer);
+        assert_eq!(fs::read_to_string(&annotated).unwrap(), "# main.tf:1 {{name}}\nnew");
+        let writer = FileWriter::new(output_dir.clone()).with_staging();
+        writer.write_annotated_file(&file).unwrap();
+        writer.commit().unwrap();
+        assert_eq!(fs::read_to_string(&annotated).unwrap(), "# changed");
+    }
+
     #[test]
     fn test_returns_list_of_written_files() {
         let temp_dir = TempDir::new().unwrap();
@@ -433,4 +1210,55 @@ mod tests {
             assert_eq!(permissions.mode() & 0o777, 0o600);
         }
     }
+
+    #[test]
+    fn test_write_annotated_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = FileWriter::new(temp_dir.path().to_path_buf());
+
+        let plain = ProcessedFile::new(PathBuf::from("k8s/app.yaml"), "a: 1\n".to_string());
+        assert!(writer.write_annotated_file(&plain).unwrap().is_none());
+
+        let annotated = ProcessedFile {
+            annotated: Some("a: 1 # <- {{a}} = \"1\"\n".to_string()),
+            ..plain
+        };
+        let path = writer.write_annotated_file(&annotated).unwrap().unwrap();
+        assert_eq!(path, temp_dir.path().join(DEBUG_RENDER_DIR).join("k8s/app.yaml"));
+        assert_eq!(fs::read_to_string(path).unwrap(), "a: 1 # <- {{a}} = \"1\"\n");
+        assert!(!temp_dir.path().join("k8s/app.yaml").exists());
+    }
+
+    #[test]
+    fn test_check_output_dir_refuses_dirty_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = FileWriter::new(temp_dir.path().to_path_buf());
+        assert!(writer.check_output_dir().is_ok());
+        assert!(FileWriter::new(temp_dir.path().join("missing")).check_output_dir().is_ok());
+        assert!(matches!(
+            FileWriter::new(PathBuf::from("/")).check_output_dir(),
+            Err(CliError::UnsafeOutputDirError(_))
+        ));
+
+        let initialized = Command::new("git").arg("init").arg("-q").arg(temp_dir.path()).status();
+        if !initialized.is_ok_and(|status| status.success()) {
+            return; // git is not installed
+        }
+        assert!(writer.check_output_dir().is_ok());
+        fs::write(temp_dir.path().join("main.tf"), "a = 1\n").unwrap();
+        let error = writer.check_output_dir().unwrap_err();
+        assert!(error.to_string().contains("uncommitted changes"));
+    }
+
+    #[test]
+    fn test_nested_output_warning() {
+        let temp_dir = TempDir::new().unwrap();
+        let templates = temp_dir.path().join("templates");
+        fs::create_dir(&templates).unwrap();
+
+        let nested = FileWriter::new(templates.join("output"));
+        assert!(nested.nested_output_warning(&templates).unwrap().contains("inside the template directory"));
+        let sibling = FileWriter::new(temp_dir.path().join("output"));
+        assert!(sibling.nested_output_warning(&templates).is_none());
+    }
 }
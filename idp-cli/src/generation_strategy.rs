@@ -0,0 +1,483 @@
+use std::collections::BTreeMap;
+use std::path::{Component, PathBuf};
+
+use crate::paths;
+use crate::template_discovery::TemplateFile;
+use crate::variable_context::VariableContext;
+
+/// Name of the template set that every strategy includes
+pub const COMMON_TEMPLATE_SET: &str = "common";
+
+/// A generation strategy selects which template sets are rendered for a stack
+///
+/// Template sets are top-level subdirectories of the template directory
+/// (e.g. `common/`, `infrastructure/`, `kubernetes/`). Sets are applied in the
+/// order returned by `template_sets`; when two sets contain a file with the
+/// same relative path, the later set wins.
+pub trait GenerationStrategy: Send + Sync {
+    /// Short name used in logs and output (e.g. "web-application")
+    fn name(&self) -> &str;
+
+    /// Whether this strategy handles the given normalized stack type
+    fn supports(&self, stack_type: &str) -> bool;
+
+    /// Template sets to render, in order of increasing precedence
+    fn template_sets(&self) -> Vec<String>;
+}
+
+/// Strategy defined by a fixed list of stack types and template sets
+#[derive(Debug, Clone)]
+pub struct StaticStrategy {
+    name: String,
+    stack_types: Vec<String>,
+    template_sets: Vec<String>,
+}
+
+impl StaticStrategy {
+    /// Creates a new strategy
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Strategy name
+    /// * `stack_types` - Stack types handled by this strategy (normalized form)
+    /// * `template_sets` - Template sets to render, in order of precedence
+    pub fn new(name: &str, stack_types: &[&str], template_sets: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            stack_types: stack_types.iter().map(|s| s.to_string()).collect(),
+            template_sets: template_sets.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl GenerationStrategy for StaticStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn supports(&self, stack_type: &str) -> bool {
+        self.stack_types.iter().any(|t| t == stack_type)
+    }
+
+    fn template_sets(&self) -> Vec<String> {
+        self.template_sets.clone()
+    }
+}
+
+/// Registry of generation strategies keyed by stack type
+///
+/// Strategies are checked in registration order and the first one that
+/// supports the stack type is used. Blueprints and unknown stack types fall
+/// back to the default strategy.
+pub struct StrategyRegistry {
+    strategies: Vec<Box<dyn GenerationStrategy>>,
+    default_strategy: Box<dyn GenerationStrategy>,
+}
+
+impl StrategyRegistry {
+    /// Creates an empty registry with the given fallback strategy
+    pub fn new(default_strategy: Box<dyn GenerationStrategy>) -> Self {
+        Self {
+            strategies: Vec::new(),
+            default_strategy,
+        }
+    }
+
+    /// Registers a strategy
+    ///
+    /// Strategies registered later are checked after existing ones, so the
+    /// built-in strategies can't be shadowed accidentally.
+    pub fn register(&mut self, strategy: Box<dyn GenerationStrategy>) {
+        self.strategies.push(strategy);
+    }
+
+    /// Selects the strategy for a stack type
+    ///
+    /// # Arguments
+    ///
+    /// * `stack_type` - Raw stack type from the API, or None for blueprints
+    pub fn select(&self, stack_type: Option<&str>) -> &dyn GenerationStrategy {
+        let normalized = match stack_type {
+            Some(stack_type) => normalize_stack_type(stack_type),
+            None => return self.default_strategy.as_ref(),
+        };
+
+        self.strategies
+            .iter()
+            .find(|s| s.supports(&normalized))
+            .map(|s| s.as_ref())
+            .unwrap_or(self.default_strategy.as_ref())
+    }
+
+    /// Selects the strategy for a variable context using `stack.stack_type`
+    pub fn select_for_context(&self, context: &VariableContext) -> &dyn GenerationStrategy {
+        self.select(context.get("stack.stack_type").and_then(|v| v.as_str()))
+    }
+
+    /// Names of every template set known to the registry
+    pub fn known_template_sets(&self) -> Vec<String> {
+        let mut sets: Vec<String> = self
+            .strategies
+            .iter()
+            .chain(std::iter::once(&self.default_strategy))
+            .flat_map(|s| s.template_sets())
+            .collect();
+        sets.sort();
+        sets.dedup();
+        sets
+    }
+
+    /// Applies a strategy to discovered templates
+    ///
+    /// If the template directory does not use template sets (no top-level
+    /// directory matches a known set name) the templates are returned
+    /// unchanged. Otherwise, files at the root of the template directory are
+    /// always kept, files in the strategy's sets are kept with the set
+    /// directory stripped from their relative path, and files in other sets are
+    /// dropped.
+    pub fn apply(
+        &self,
+        strategy: &dyn GenerationStrategy,
+        templates: Vec<TemplateFile>,
+    ) -> Vec<TemplateFile> {
+        if !self.uses_sets(&templates) {
+            return templates;
+        }
+
+        let mut selected: BTreeMap<PathBuf, (usize, TemplateFile)> = BTreeMap::new();
+        for (precedence, relative_path, template) in self.placements(strategy, &templates) {
+            let keep = selected
+                .get(&relative_path)
+                .map(|(existing, _)| precedence >= *existing)
+                .unwrap_or(true);
+
+            if keep {
+                selected.insert(
+                    relative_path.clone(),
+                    (
+                        precedence,
+                        TemplateFile {
+                            relative_path,
+                            ..template.clone()
+                        },
+                    ),
+                );
+            }
+        }
+
+        selected.into_values().map(|(_, t)| t).collect()
+    }
+
+    /// Finds templates that `apply` would render to the same output path
+    ///
+    /// Besides files shared by template sets (or by a set and the template
+    /// root), this reports paths that differ only in case, which overwrite each
+    /// other on case-insensitive file systems.
+    pub fn conflicts(&self, strategy: &dyn GenerationStrategy, templates: &[TemplateFile]) -> Vec<OutputConflict> {
+        let placements = if self.uses_sets(templates) {
+            self.placements(strategy, templates)
+        } else {
+            templates.iter().map(|t| (0, t.relative_path.clone(), t)).collect()
+        };
+
+        let mut by_path: BTreeMap<String, Vec<(usize, PathBuf, &TemplateFile)>> = BTreeMap::new();
+        for placement in placements {
+            by_path.entry(paths::case_key(&placement.1)).or_default().push(placement);
+        }
+
+        by_path
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .map(|mut group| {
+                group.sort_by(|a, b| (a.0, &a.2.relative_path).cmp(&(b.0, &b.2.relative_path)));
+                let same_path = group.iter().all(|(_, path, _)| *path == group[0].1);
+                let mut precedences: Vec<usize> = group.iter().map(|(precedence, _, _)| *precedence).collect();
+                precedences.dedup();
+                OutputConflict {
+                    output_path: group[0].1.clone(),
+                    templates: group.iter().map(|(_, _, t)| t.relative_path.clone()).collect(),
+                    resolved_by_precedence: same_path && precedences.len() == group.len(),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether any top-level directory is a known template set
+    fn uses_sets(&self, templates: &[TemplateFile]) -> bool {
+        let known_sets = self.known_template_sets();
+        templates.iter().any(|t| {
+            template_set_of(t)
+                .map(|set| known_sets.contains(&set))
+                .unwrap_or(false)
+        })
+    }
+
+    /// Precedence and output path of each template in the strategy's sets
+    ///
+    /// Root files have precedence 0 and keep their path; files in a selected
+    /// set rank by the set's position and lose the set directory. Files in
+    /// other sets are left out.
+    fn placements<'a>(
+        &self,
+        strategy: &dyn GenerationStrategy,
+        templates: &'a [TemplateFile],
+    ) -> Vec<(usize, PathBuf, &'a TemplateFile)> {
+        let selected_sets = strategy.template_sets();
+        templates
+            .iter()
+            .filter_map(|template| match template_set_of(template) {
+                None => Some((0, template.relative_path.clone(), template)),
+                Some(set) => selected_sets.iter().position(|s| *s == set).map(|index| {
+                    (index + 1, template.relative_path.components().skip(1).collect(), template)
+                }),
+            })
+            .collect()
+    }
+}
+
+/// Templates that render to the same output path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputConflict {
+    pub output_path: PathBuf,
+    /// Template paths relative to the template directory, lowest precedence first
+    pub templates: Vec<PathBuf>,
+    /// Whether template set precedence picks one template: the paths are
+    /// identical and every template comes from a different set
+    pub resolved_by_precedence: bool,
+}
+
+impl std::fmt::Display for OutputConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let templates: Vec<String> = self.templates.iter().map(|t| t.display().to_string()).collect();
+        if self.resolved_by_precedence {
+            write!(
+                f,
+                "{} is rendered from {}; {} has the highest precedence",
+                self.output_path.display(),
+                templates.join(", "),
+                templates.last().map(String::as_str).unwrap_or_default()
+            )
+        } else {
+            write!(
+                f,
+                "{} are rendered to the same output path {} (paths are compared ignoring case)",
+                templates.join(" and "),
+                self.output_path.display()
+            )
+        }
+    }
+}
+
+impl Default for StrategyRegistry {
+    /// Creates a registry with the built-in strategies
+    ///
+    /// - `infrastructure` (default): INFRASTRUCTURE, INFRASTRUCTURE_ONLY
+    /// - `web-application`: WEB_APPLICATION, JAVASCRIPT_WEB_APPLICATION
+    /// - `container-api`: RESTFUL_API, EVENT_DRIVEN_API
+    /// - `serverless`: RESTFUL_SERVERLESS, EVENT_DRIVEN_SERVERLESS
+    fn default() -> Self {
+        let mut registry = Self::new(Box::new(StaticStrategy::new(
+            "infrastructure",
+            &["INFRASTRUCTURE", "INFRASTRUCTURE_ONLY"],
+            &[COMMON_TEMPLATE_SET, "infrastructure"],
+        )));
+
+        registry.register(Box::new(StaticStrategy::new(
+            "web-application",
+            &["WEB_APPLICATION", "JAVASCRIPT_WEB_APPLICATION"],
+            &[COMMON_TEMPLATE_SET, "infrastructure", "web-application", "kubernetes"],
+        )));
+        registry.register(Box::new(StaticStrategy::new(
+            "container-api",
+            &["RESTFUL_API", "EVENT_DRIVEN_API"],
+            &[COMMON_TEMPLATE_SET, "infrastructure", "ecs", "kubernetes"],
+        )));
+        registry.register(Box::new(StaticStrategy::new(
+            "serverless",
+            &["RESTFUL_SERVERLESS", "EVENT_DRIVEN_SERVERLESS"],
+            &[COMMON_TEMPLATE_SET, "infrastructure", "serverless"],
+        )));
+
+        registry
+    }
+}
+
+/// Normalizes a stack type to SCREAMING_SNAKE_CASE
+///
+/// Accepts the API form (`RESTFUL_API`), kebab case (`restful-api`) and
+/// Pascal case (`RestfulApi`).
+pub fn normalize_stack_type(stack_type: &str) -> String {
+    let mut normalized = String::with_capacity(stack_type.len() + 4);
+    let mut prev_lower = false;
+
+    for c in stack_type.trim().chars() {
+        if c == '-' || c == ' ' || c == '_' {
+            if !normalized.ends_with('_') {
+                normalized.push('_');
+            }
+            prev_lower = false;
+        } else if c.is_uppercase() {
+            if prev_lower {
+                normalized.push('_');
+            }
+            normalized.push(c);
+            prev_lower = false;
+        } else {
+            normalized.extend(c.to_uppercase());
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+    }
+
+    normalized
+}
+
+/// Returns the top-level directory of a template, if it is nested
+fn template_set_of(template: &TemplateFile) -> Option<String> {
+    let mut components = template.relative_path.components();
+    let first = components.next()?;
+    components.next()?;
+    match first {
+        Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_discovery::TemplateFileType;
+    use serde_json::json;
+
+    fn template(relative: &str) -> TemplateFile {
+        TemplateFile {
+            path: PathBuf::from("/templates").join(relative),
+            relative_path: PathBuf::from(relative),
+            file_type: TemplateFileType::Terraform,
+        }
+    }
+
+    fn relative_paths(templates: &[TemplateFile]) -> Vec<String> {
+        templates
+            .iter()
+            .map(|t| t.relative_path.to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_normalize_stack_type() {
+        assert_eq!(normalize_stack_type("RESTFUL_API"), "RESTFUL_API");
+        assert_eq!(normalize_stack_type("RestfulApi"), "RESTFUL_API");
+        assert_eq!(normalize_stack_type("restful-api"), "RESTFUL_API");
+        assert_eq!(normalize_stack_type("web application"), "WEB_APPLICATION");
+    }
+
+    #[test]
+    fn test_select_by_stack_type() {
+        let registry = StrategyRegistry::default();
+        assert_eq!(registry.select(Some("JAVASCRIPT_WEB_APPLICATION")).name(), "web-application");
+        assert_eq!(registry.select(Some("RestfulApi")).name(), "container-api");
+        assert_eq!(registry.select(Some("EVENT_DRIVEN_SERVERLESS")).name(), "serverless");
+        assert_eq!(registry.select(Some("INFRASTRUCTURE_ONLY")).name(), "infrastructure");
+        assert_eq!(registry.select(Some("SOMETHING_NEW")).name(), "infrastructure");
+        assert_eq!(registry.select(None).name(), "infrastructure");
+    }
+
+    #[test]
+    fn test_select_for_context() {
+        let registry = StrategyRegistry::default();
+        let mut context = VariableContext::new();
+        assert_eq!(registry.select_for_context(&context).name(), "infrastructure");
+
+        context.insert("stack.stack_type".to_string(), json!("WEB_APPLICATION"));
+        assert_eq!(registry.select_for_context(&context).name(), "web-application");
+    }
+
+    #[test]
+    fn test_register_custom_strategy() {
+        let mut registry = StrategyRegistry::default();
+        registry.register(Box::new(StaticStrategy::new(
+            "data-pipeline",
+            &["DATA_PIPELINE"],
+            &[COMMON_TEMPLATE_SET, "airflow"],
+        )));
+        assert_eq!(registry.select(Some("data-pipeline")).name(), "data-pipeline");
+        assert!(registry.known_template_sets().contains(&"airflow".to_string()));
+    }
+
+    #[test]
+    fn test_apply_without_template_sets_keeps_everything() {
+        let registry = StrategyRegistry::default();
+        let templates = vec![template("main.tf"), template("modules/vpc/main.tf")];
+        let strategy = registry.select(Some("WEB_APPLICATION"));
+        let result = registry.apply(strategy, templates.clone());
+        assert_eq!(result, templates);
+    }
+
+    #[test]
+    fn test_apply_selects_sets_and_strips_prefix() {
+        let registry = StrategyRegistry::default();
+        let templates = vec![
+            template("README.json"),
+            template("common/providers.tf"),
+            template("infrastructure/main.tf"),
+            template("kubernetes/deployment.yaml"),
+            template("serverless/lambda.tf"),
+        ];
+
+        let strategy = registry.select(Some("WEB_APPLICATION"));
+        let result = registry.apply(strategy, templates.clone());
+        assert_eq!(
+            relative_paths(&result),
+            vec!["README.json", "deployment.yaml", "main.tf", "providers.tf"]
+        );
+
+        let strategy = registry.select(Some("INFRASTRUCTURE_ONLY"));
+        let result = registry.apply(strategy, templates);
+        assert_eq!(relative_paths(&result), vec!["README.json", "main.tf", "providers.tf"]);
+    }
+
+    #[test]
+    fn test_apply_later_set_overrides_earlier() {
+        let registry = StrategyRegistry::default();
+        let templates = vec![
+            template("web-application/main.tf"),
+            template("common/main.tf"),
+            template("infrastructure/main.tf"),
+        ];
+
+        let strategy = registry.select(Some("WEB_APPLICATION"));
+        let result = registry.apply(strategy, templates);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].path, PathBuf::from("/templates/web-application/main.tf"));
+        assert_eq!(result[0].relative_path, PathBuf::from("main.tf"));
+    }
+
+    #[test]
+    fn test_conflicts_list_every_source() {
+        let registry = StrategyRegistry::default();
+        let templates = vec![
+            template("main.tf"),
+            template("common/main.tf"),
+            template("infrastructure/main.tf"),
+            template("common/providers.tf"),
+            template("kubernetes/main.tf"),
+        ];
+
+        let conflicts = registry.conflicts(registry.select(None), &templates);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].output_path, PathBuf::from("main.tf"));
+        assert_eq!(relative_paths_of(&conflicts[0].templates), vec!["main.tf", "common/main.tf", "infrastructure/main.tf"]);
+        assert!(conflicts[0].resolved_by_precedence);
+        assert!(conflicts[0].to_string().ends_with("infrastructure/main.tf has the highest precedence"));
+
+        // Without template sets only case-insensitive clashes remain
+        let conflicts = registry.conflicts(registry.select(None), &[template("Main.tf"), template("main.tf")]);
+        assert_eq!(conflicts.len(), 1);
+        assert!(!conflicts[0].resolved_by_precedence);
+        assert!(registry.conflicts(registry.select(None), &[template("a/main.tf"), template("b/main.tf")]).is_empty());
+    }
+
+    fn relative_paths_of(paths: &[PathBuf]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string_lossy().to_string()).collect()
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::error::CliError;
+use crate::run_summary::RunSummary;
+
+/// Files a GitHub Actions step writes its job summary and outputs to
+///
+/// Runs inside GitHub Actions are detected by `GITHUB_ACTIONS=true`. The
+/// summary is appended to `$GITHUB_STEP_SUMMARY` as markdown, and the step
+/// outputs `files_changed` and `manifest_path` to `$GITHUB_OUTPUT`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GithubActions {
+    pub step_summary: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+}
+
+impl GithubActions {
+    /// Detect GitHub Actions from the environment
+    pub fn from_env() -> Option<Self> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Detect GitHub Actions from variables read with `lookup`
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Option<Self> {
+        if lookup("GITHUB_ACTIONS").as_deref() != Some("true") {
+            return None;
+        }
+        let path = |name| lookup(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+        Some(Self { step_summary: path("GITHUB_STEP_SUMMARY"), output: path("GITHUB_OUTPUT") })
+    }
+
+    /// Append the job summary and step outputs of a generate run
+    pub fn report(&self, summary: &RunSummary) -> Result<(), CliError> {
+        if let Some(path) = &self.step_summary {
+            append(path, &render_job_summary(summary))?;
+        }
+        if let Some(path) = &self.output {
+            let mut outputs = format!("files_changed={}\n", files_changed(summary));
+            if let Some(manifest_path) = &summary.manifest_path {
+                outputs.push_str(&format!("manifest_path={}\n", manifest_path));
+            }
+            append(path, &outputs)?;
+        }
+        Ok(())
+    }
+}
+
+/// Files added, modified or removed by the run
+fn files_changed(summary: &RunSummary) -> usize {
+    summary
+        .changes
+        .as_ref()
+        .map(|changes| changes.added.len() + changes.modified.len() + changes.removed.len())
+        .unwrap_or(0)
+}
+
+/// Markdown job summary of a generate run
+pub fn render_job_summary(summary: &RunSummary) -> String {
+    let status = if summary.success { "✅" } else { "❌" };
+    let mut lines = vec![
+        format!("## {} idp-cli {} {} `{}`", status, summary.command, summary.data_source, summary.identifier),
+        String::new(),
+    ];
+    if let Some(error) = &summary.error {
+        lines.push(format!("**Failed:** {}", error));
+        lines.push(String::new());
+    }
+
+    lines.push("| | |".to_string());
+    lines.push("|---|---|".to_string());
+    lines.push(format!("| Templates | {} |", summary.templates_discovered));
+    lines.push(format!("| Files written | {} |", summary.files_written));
+    if let Some(changes) = &summary.changes {
+        lines.push(format!(
+            "| Changes | {} added, {} modified, {} removed |",
+            changes.added.len(),
+            changes.modified.len(),
+            changes.removed.len()
+        ));
+    }
+    lines.push(format!("| Warnings | {} |", summary.warnings.len()));
+    lines.push(format!("| Duration | {} ms |", summary.total_duration_ms));
+    if let Some(manifest_path) = &summary.manifest_path {
+        lines.push(format!("| Manifest | `{}` |", manifest_path));
+    }
+
+    if let Some(changes) = summary.changes.as_ref().filter(|changes| !changes.is_empty()) {
+        lines.push(String::new());
+        lines.push("<details><summary>Changed files</summary>".to_string());
+        lines.push(String::new());
+        lines.extend(changes.added.iter().map(|path| format!("- Added `{}`", path)));
+        lines.extend(changes.modified.iter().map(|path| format!("- Modified `{}`", path)));
+        lines.extend(changes.removed.iter().map(|path| format!("- Removed `{}`", path)));
+        lines.push(String::new());
+        lines.push("</details>".to_string());
+    }
+
+    if !summary.warnings.is_empty() {
+        lines.push(String::new());
+        lines.push("### Warnings".to_string());
+        lines.push(String::new());
+        lines.extend(summary.warnings.iter().map(|warning| format!("- {}", warning)));
+    }
+
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+fn append(path: &Path, content: &str) -> Result<(), CliError> {
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| CliError::IoError(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_summary::FileChanges;
+
+    #[test]
+    fn test_from_lookup_needs_github_actions() {
+        assert_eq!(GithubActions::from_lookup(|_| None), None);
+        let env = |name: &str| match name {
+            "GITHUB_ACTIONS" => Some("true".to_string()),
+            "GITHUB_STEP_SUMMARY" => Some("/tmp/summary.md".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            GithubActions::from_lookup(env),
+            Some(GithubActions { step_summary: Some(PathBuf::from("/tmp/summary.md")), output: None })
+        );
+    }
+
+    #[test]
+    fn test_report_appends_summary_and_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let github = GithubActions {
+            step_summary: Some(dir.path().join("summary.md")),
+            output: Some(dir.path().join("output")),
+        };
+        std::fs::write(dir.path().join("output"), "earlier=1\n").unwrap();
+
+        let mut summary = RunSummary::start("generate", "stack", "payments-prod");
+        summary.templates_discovered = 2;
+        summary.files_written = 2;
+        summary.warn("Overwrote existing file: main.tf");
+        summary.changes = Some(FileChanges {
+            added: vec!["outputs.tf".to_string()],
+            modified: vec!["main.tf".to_string()],
+            removed: vec!["old.tf".to_string()],
+        });
+        summary.manifest_path = Some("out/.idp-cli/manifest.json".to_string());
+        summary.finish(&Ok(()));
+        github.report(&summary).unwrap();
+
+        let markdown = std::fs::read_to_string(dir.path().join("summary.md")).unwrap();
+        assert!(markdown.starts_with("## ✅ idp-cli generate stack `payments-prod`\n"), "{}", markdown);
+        assert!(markdown.contains("| Changes | 1 added, 1 modified, 1 removed |"), "{}", markdown);
+        assert!(markdown.contains("- Removed `old.tf`"), "{}", markdown);
+        assert!(markdown.contains("- Overwrote existing file: main.tf"), "{}", markdown);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("output")).unwrap(),
+            "earlier=1\nfiles_changed=3\nmanifest_path=out/.idp-cli/manifest.json\n"
+        );
+    }
+}
@@ -0,0 +1,669 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use log::{info, warn};
+use tokio::sync::{mpsc, OwnedSemaphorePermit};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+use crate::api_client::ApiClient;
+use crate::audit::{AuditLog, AuditRecord, AuditStatus};
+use crate::api_keys::{AuthRejection, KeyStore, Scope};
+use crate::cli::DataSource;
+use crate::error::CliError;
+use crate::generation_strategy::StrategyRegistry;
+use crate::paths::{self, local_template_path};
+use crate::pipeline::{file_channel, join_results, render_to_channel, DEFAULT_CHANNEL_CAPACITY};
+use crate::render_sandbox::SandboxPolicy;
+use crate::request_guards::{GuardConfig, GuardRejection, RequestGuards};
+use crate::template_discovery::{templates_digest, TemplateDiscovery, TemplateFile};
+use crate::template_processor::TemplateProcessor;
+use crate::variable_context::{VariableContext, VariableContextBuilder};
+
+/// Generated protobuf types and service traits
+pub mod proto {
+    tonic::include_proto!("idp_cli.v1");
+}
+
+use proto::generator_server::{Generator, GeneratorServer};
+use proto::{
+    GenerateRequest, GeneratedFile, ListVariablesRequest, ListVariablesResponse, TemplateError,
+    ValidateResponse, Variable,
+};
+
+/// How long a fetched blueprint/stack context is reused between requests
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Most blueprint/stack contexts kept in the cache at once
+pub const DEFAULT_CACHE_CAPACITY: usize = 256;
+
+/// gRPC implementation of the generator service
+///
+/// Keeps the API client and recently built variable contexts warm across
+/// requests, so repeated calls for the same blueprint or stack skip the API.
+pub struct GeneratorService {
+    api_client: ApiClient,
+    cache_ttl: Duration,
+    cache_capacity: usize,
+    keys: Option<KeyStore>,
+    /// Where generate and validate requests are recorded, like the Lambda and HTTP handlers'
+    audit: Arc<AuditLog>,
+    /// Same request size, rate and concurrency limits as the Lambda and HTTP handlers
+    guards: RequestGuards,
+    /// Directory `template_dir` in requests is resolved in
+    templates_root: PathBuf,
+    contexts: Mutex<HashMap<(DataSource, String), (Instant, VariableContext)>>,
+}
+
+impl GeneratorService {
+    /// Create the service; without `keys`, requests are not authenticated
+    ///
+    /// Templates are read from the current directory unless
+    /// [`with_templates_root`](Self::with_templates_root) names another.
+    pub fn new(api_client: ApiClient, cache_ttl: Duration, keys: Option<KeyStore>) -> Self {
+        Self {
+            api_client,
+            cache_ttl,
+            cache_capacity: DEFAULT_CACHE_CAPACITY,
+            keys,
+            audit: Arc::new(AuditLog::disabled()),
+            guards: RequestGuards::new(GuardConfig::default()),
+            templates_root: PathBuf::from("."),
+            contexts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Read templates from this directory; requests can't name a `template_dir` outside it
+    pub fn with_templates_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.templates_root = root.into();
+        self
+    }
+
+    /// Record generate and validate requests in this audit log
+    pub fn with_audit(mut self, audit: AuditLog) -> Self {
+        self.audit = Arc::new(audit);
+        self
+    }
+
+    /// Limit request size, rate per client and concurrent generations
+    pub fn with_guards(mut self, guards: RequestGuards) -> Self {
+        self.guards = guards;
+        self
+    }
+
+    /// Keep at most this many blueprint/stack contexts cached
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+
+    /// Check the request's size and the client's rate limit, before authentication like the HTTP API
+    fn check_guards<T: prost::Message>(&self, request: &Request<T>) -> Result<(), GuardRejection> {
+        self.guards.check_body_size(request.get_ref().encoded_len())?;
+        let client = client_address(request);
+        self.guards.check_rate(&client).inspect_err(|_| info!("Rate limited client {}", client))
+    }
+
+    /// Check the request's API key (`authorization: Bearer <key>` or `x-api-key` metadata)
+    ///
+    /// Returns the caller for audit records: the key's name, or the client
+    /// address when requests are not authenticated.
+    fn authorize<T>(&self, request: &Request<T>, required: &[Scope]) -> Result<String, AuthRejection> {
+        let Some(keys) = &self.keys else {
+            return Ok(client_address(request));
+        };
+        let metadata = request.metadata();
+        let presented = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .or_else(|| metadata.get("x-api-key").and_then(|value| value.to_str().ok()))
+            .map(str::trim)
+            .filter(|key| !key.is_empty());
+
+        let key = keys.authorize(presented, required)?;
+        info!("gRPC caller: {}", key.name);
+        Ok(key.name.clone())
+    }
+
+    /// Fetch the variable context for a blueprint or stack, using the cache when fresh
+    async fn context(&self, data_source: DataSource, identifier: &str) -> Result<VariableContext, CliError> {
+        let key = (data_source, identifier.to_string());
+        if let Some((fetched, context)) = self.contexts.lock().unwrap().get(&key) {
+            if fetched.elapsed() < self.cache_ttl {
+                return Ok(context.clone());
+            }
+        }
+
+        let api_client = &self.api_client;
+        let context = match data_source {
+            DataSource::Blueprint => {
+                VariableContextBuilder::from_blueprint(&api_client.get_blueprint(identifier).await?)
+            }
+            DataSource::Stack => VariableContextBuilder::from_stack(&api_client.get_stack(identifier).await?),
+        };
+
+        self.cache_context(key, context.clone());
+        Ok(context)
+    }
+
+    /// Cache a context, dropping expired ones and then the oldest when full
+    fn cache_context(&self, key: (DataSource, String), context: VariableContext) {
+        let mut contexts = self.contexts.lock().unwrap();
+        contexts.retain(|_, (fetched, _)| fetched.elapsed() < self.cache_ttl);
+        while contexts.len() >= self.cache_capacity.max(1) && !contexts.contains_key(&key) {
+            let oldest = contexts.iter().min_by_key(|(_, (fetched, _))| *fetched).map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => contexts.remove(&oldest),
+                None => break,
+            };
+        }
+        contexts.insert(key, (Instant::now(), context));
+    }
+
+    /// Start a generate/validate request under the concurrency limit, auditing a rejection
+    fn start_generation(&self, record: &mut AuditRecord) -> Result<OwnedSemaphorePermit, GuardRejection> {
+        self.guards.try_start_generation().inspect_err(|rejection| {
+            record.status = AuditStatus::Rejected;
+            record.error = Some(rejection.message());
+        })
+    }
+
+    /// Build the context for a generate/validate request and discover the
+    /// templates in its `template_dir`, inside the templates root
+    async fn prepare(
+        &self,
+        request: &GenerateRequest,
+        record: &mut AuditRecord,
+    ) -> Result<(VariableContext, Vec<TemplateFile>), CliError> {
+        let context = self.request_context(request).await?;
+        let template_path = local_template_path(&self.templates_root, &request.template_dir)?;
+        record.template_source = template_path.display().to_string();
+        let templates = load_templates(&template_path, &context)?;
+        record.template_digest = templates_digest(&templates).ok();
+        Ok((context, templates))
+    }
+
+    /// Build the context for a generate/validate request, including custom variables
+    async fn request_context(&self, request: &GenerateRequest) -> Result<VariableContext, CliError> {
+        let data_source = parse_data_source(&request.data_source)?;
+        let mut context = self.context(data_source, &request.identifier).await?;
+
+        if !request.variables_json.is_empty() {
+            let variables: serde_json::Value = serde_json::from_str(&request.variables_json)?;
+            let object = variables.as_object().ok_or_else(|| {
+                CliError::VariableFileError("variables_json must be a JSON object".to_string())
+            })?;
+            for (key, value) in object {
+                context.insert(key.clone(), value.clone());
+            }
+        }
+
+        Ok(context)
+    }
+}
+
+#[tonic::async_trait]
+impl Generator for GeneratorService {
+    type GenerateStream = ReceiverStream<Result<GeneratedFile, Status>>;
+
+    async fn generate(
+        &self,
+        request: Request<GenerateRequest>,
+    ) -> Result<Response<Self::GenerateStream>, Status> {
+        self.check_guards(&request).map_err(rejection_status)?;
+        let caller = self.authorize(&request, &[Scope::Generate]).map_err(auth_status)?;
+        let request = request.into_inner();
+        info!("gRPC generate: {} {}", request.data_source, request.identifier);
+        let mut record = AuditRecord::new(&caller, "generate", &request.data_source, &request.identifier);
+
+        // Held until the stream finishes
+        let permit = match self.start_generation(&mut record) {
+            Ok(permit) => permit,
+            Err(rejection) => {
+                self.audit.record_or_log(&record).await;
+                return Err(rejection_status(rejection));
+            }
+        };
+        let (context, templates) = match self.prepare(&request, &mut record).await {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                record.error = Some(e.to_string());
+                self.audit.record_or_log(&record).await;
+                return Err(to_status(e));
+            }
+        };
+
+        let audit = Arc::clone(&self.audit);
+        let (results, stream) = mpsc::channel(DEFAULT_CHANNEL_CAPACITY);
+        tokio::spawn(async move {
+            let _permit = permit;
+            let processor = TemplateProcessor::new(&context).with_sandbox(SandboxPolicy::from_env());
+            let (sender, mut receiver) = file_channel(DEFAULT_CHANNEL_CAPACITY);
+            // Requests already run concurrently, so each renders its templates one at a time,
+            // on the blocking pool rather than this runtime worker
+            let (rendered, forwarded) = tokio::join!(
+                render_to_channel(&processor, &templates, sender, 1),
+                async {
+                    while let Some(file) = receiver.recv().await {
+                        let message = GeneratedFile {
+                            path: file.path_str(),
+                            content: file.content,
+                        };
+                        if results.send(Ok(message)).await.is_err() {
+                            return Err(CliError::GenerationError("Client disconnected".to_string()));
+                        }
+                    }
+                    Ok(())
+                }
+            );
+            match join_results(rendered, forwarded) {
+                Ok((stats, ())) => {
+                    record.status = AuditStatus::Success;
+                    record.files_generated = stats.files_rendered;
+                }
+                Err(e) => {
+                    record.error = Some(e.to_string());
+                    let _ = results.send(Err(to_status(e))).await;
+                }
+            }
+            audit.record_or_log(&record).await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(stream)))
+    }
+
+    async fn list_variables(
+        &self,
+        request: Request<ListVariablesRequest>,
+    ) -> Result<Response<ListVariablesResponse>, Status> {
+        self.check_guards(&request).map_err(rejection_status)?;
+        self.authorize(&request, &[Scope::Read]).map_err(auth_status)?;
+        let request = request.into_inner();
+        info!("gRPC list-variables: {} {}", request.data_source, request.identifier);
+
+        let data_source = parse_data_source(&request.data_source).map_err(to_status)?;
+        let context = self.context(data_source, &request.identifier).await.map_err(to_status)?;
+        let variables = context
+            .list_all()
+            .into_iter()
+            .map(|(name, value)| Variable {
+                name,
+                value_json: value.to_string(),
+            })
+            .collect();
+
+        Ok(Response::new(ListVariablesResponse { variables }))
+    }
+
+    async fn validate(
+        &self,
+        request: Request<GenerateRequest>,
+    ) -> Result<Response<ValidateResponse>, Status> {
+        self.check_guards(&request).map_err(rejection_status)?;
+        let caller = self.authorize(&request, &[Scope::Read]).map_err(auth_status)?;
+        let request = request.into_inner();
+        info!("gRPC validate: {} {}", request.data_source, request.identifier);
+        let mut record = AuditRecord::new(&caller, "validate", &request.data_source, &request.identifier);
+
+        let _permit = match self.start_generation(&mut record) {
+            Ok(permit) => permit,
+            Err(rejection) => {
+                self.audit.record_or_log(&record).await;
+                return Err(rejection_status(rejection));
+            }
+        };
+        let (context, templates) = match self.prepare(&request, &mut record).await {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                record.error = Some(e.to_string());
+                self.audit.record_or_log(&record).await;
+                return Err(to_status(e));
+            }
+        };
+
+        // Rendering blocks, so keep it off the runtime workers
+        let validated = tokio::task::spawn_blocking(move || validate_templates(&context, &templates)).await;
+        match &validated {
+            Ok(response) if response.valid => record.status = AuditStatus::Success,
+            Ok(response) => {
+                record.error = Some(format!(
+                    "{} of {} templates failed to render",
+                    response.errors.len(),
+                    response.templates_checked
+                ))
+            }
+            Err(_) => record.error = Some("Template validation panicked".to_string()),
+        }
+        self.audit.record_or_log(&record).await;
+        validated
+            .map(Response::new)
+            .map_err(|_| Status::internal("Template validation panicked"))
+    }
+}
+
+/// Serve the generator over gRPC until the process is stopped
+///
+/// Templates are read from `TEMPLATES_DIR`, or the current directory if it is not set.
+pub async fn serve(addr: SocketAddr, api_client: ApiClient) -> Result<(), CliError> {
+    let keys = KeyStore::from_env().await?;
+    if keys.is_none() {
+        warn!("No API keys configured (API_KEYS_FILE or API_KEYS_SECRET); requests are not authenticated");
+    }
+
+    let audit = AuditLog::from_env().await?;
+    let templates_root = std::env::var("TEMPLATES_DIR").unwrap_or_else(|_| ".".to_string());
+
+    println!("Serving gRPC on {}", addr);
+    tonic::transport::Server::builder()
+        .add_service(GeneratorServer::new(
+            GeneratorService::new(api_client, DEFAULT_CACHE_TTL, keys)
+                .with_guards(RequestGuards::new(GuardConfig::from_env()))
+                .with_audit(audit)
+                .with_templates_root(templates_root),
+        ))
+        .serve(addr)
+        .await
+        .map_err(|e| CliError::IoError(format!("gRPC server failed: {}", e)))
+}
+
+fn parse_data_source(value: &str) -> Result<DataSource, CliError> {
+    DataSource::from_str(value, true).map_err(|_| {
+        CliError::ConfigurationError(format!(
+            "Data source must be 'blueprint' or 'stack', got: {}",
+            value
+        ))
+    })
+}
+
+/// Discover templates and select the ones for the context's generation strategy
+fn load_templates(template_dir: &Path, context: &VariableContext) -> Result<Vec<TemplateFile>, CliError> {
+    let templates = TemplateDiscovery::new(PathBuf::from(template_dir))
+        .discover_templates()
+        .map_err(|e| CliError::DiscoveryError(e.to_string()))?;
+
+    let registry = StrategyRegistry::default();
+    let strategy = registry.select_for_context(context);
+    let templates = registry.apply(strategy, templates);
+
+    if templates.is_empty() {
+        return Err(CliError::DiscoveryError(format!(
+            "No template files in '{}' apply to the '{}' generation strategy",
+            template_dir.display(),
+            strategy.name()
+        )));
+    }
+    Ok(templates)
+}
+
+/// Render every template, collecting errors instead of stopping at the first one
+fn validate_templates(context: &VariableContext, templates: &[TemplateFile]) -> ValidateResponse {
+    let processor = TemplateProcessor::new(context).with_sandbox(SandboxPolicy::from_env());
+    let errors: Vec<TemplateError> = templates
+        .iter()
+        .filter_map(|template| {
+            processor.process_file_outputs(template).err().map(|e| TemplateError {
+                path: paths::to_slash(&template.relative_path),
+                message: e.to_string(),
+            })
+        })
+        .collect();
+
+    ValidateResponse {
+        valid: errors.is_empty(),
+        templates_checked: templates.len() as u32,
+        errors,
+    }
+}
+
+fn to_status(error: CliError) -> Status {
+    let message = error.to_string();
+    match error {
+        CliError::AuthenticationError(_) => Status::unauthenticated(message),
+        CliError::NotFoundError(_) => Status::not_found(message),
+        CliError::ConfigurationError(_)
+        | CliError::VariableFileError(_)
+        | CliError::JsonParseError(_)
+        | CliError::ProcessingError(_)
+        | CliError::ValidationError(_)
+        | CliError::TemplateSyntaxError { .. }
+        | CliError::VariableNotFoundError { .. } => Status::invalid_argument(message),
+        CliError::DiscoveryError(_) => Status::failed_precondition(message),
+        CliError::ApiUnavailableError(_) => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
+
+/// Status for a request refused by the request guards, with `retry-after` metadata when retrying helps
+fn rejection_status(rejection: GuardRejection) -> Status {
+    let mut status = match rejection {
+        GuardRejection::PayloadTooLarge { .. } => Status::invalid_argument(rejection.message()),
+        GuardRejection::RateLimited { .. } | GuardRejection::Busy => Status::resource_exhausted(rejection.message()),
+    };
+    if let Some(seconds) = rejection.retry_after_secs() {
+        status.metadata_mut().insert("retry-after", seconds.into());
+    }
+    status
+}
+
+/// Client address for rate limiting, like the HTTP API's
+fn client_address<T>(request: &Request<T>) -> String {
+    request
+        .remote_addr()
+        .map_or_else(|| "anonymous".to_string(), |addr| addr.ip().to_string())
+}
+
+fn auth_status(rejection: AuthRejection) -> Status {
+    match rejection {
+        AuthRejection::Forbidden { .. } => Status::permission_denied(rejection.message()),
+        _ => Status::unauthenticated(rejection.message()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditSink;
+    use crate::template_discovery::TemplateFileType;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_templates_collects_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let good = temp_dir.path().join("good.tf");
+        let bad = temp_dir.path().join("bad.tf");
+        std::fs::write(&good, "name = \"{{name}}\"").unwrap();
+        std::fs::write(&bad, "{{#if}}").unwrap();
+
+        let templates: Vec<TemplateFile> = [good, bad]
+            .into_iter()
+            .map(|path| TemplateFile {
+                relative_path: PathBuf::from(path.file_name().unwrap()),
+                path,
+                file_type: TemplateFileType::Terraform,
+            })
+            .collect();
+        let mut context = VariableContext::new();
+        context.insert("name".to_string(), json!("demo"));
+
+        let response = validate_templates(&context, &templates);
+        assert!(!response.valid);
+        assert_eq!(response.templates_checked, 2);
+        assert_eq!(response.errors.len(), 1);
+        assert_eq!(response.errors[0].path, "bad.tf");
+    }
+
+    #[tokio::test]
+    async fn test_generate_streams_files_and_caches_context() {
+        let mut server = mockito::Server::new_async().await;
+        let blueprint = json!({
+            "id": uuid::Uuid::new_v4(),
+            "name": "web-app",
+            "description": null,
+            "resources": [],
+            "supported_cloud_providers": [],
+        });
+        let mock = server
+            .mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "web-app".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(blueprint.to_string())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let templates_root = TempDir::new().unwrap();
+        let template_dir = templates_root.path().join("terraform");
+        std::fs::create_dir(&template_dir).unwrap();
+        std::fs::write(template_dir.join("main.tf"), "# {{blueprint.name}} {{env}}").unwrap();
+        std::fs::write(template_dir.join("outputs.tf"), "# outputs").unwrap();
+
+        let audit_dir = TempDir::new().unwrap();
+        let service = GeneratorService::new(ApiClient::new(server.url(), "key".to_string()), DEFAULT_CACHE_TTL, None)
+            .with_templates_root(templates_root.path())
+            .with_audit(AuditLog::new(AuditSink::File { dir: audit_dir.path().to_path_buf() }, 30));
+        let request = GenerateRequest {
+            data_source: "blueprint".to_string(),
+            identifier: "web-app".to_string(),
+            template_dir: "terraform".to_string(),
+            variables_json: r#"{"env": "prod"}"#.to_string(),
+        };
+
+        let response = service.generate(Request::new(request.clone())).await.unwrap();
+        let mut stream = response.into_inner().into_inner();
+        let mut files = Vec::new();
+        while let Some(file) = stream.recv().await {
+            files.push(file.unwrap());
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].content, "# web-app prod");
+
+        // The second request is served from the context cache
+        let response = service.validate(Request::new(request.clone())).await.unwrap().into_inner();
+        assert!(response.valid);
+        mock.assert_async().await;
+
+        // Both renders are audited like Lambda generations
+        let audit_file = std::fs::read_dir(audit_dir.path()).unwrap().next().unwrap().unwrap().path();
+        let records: Vec<serde_json::Value> = std::fs::read_to_string(audit_file)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["operation"], "generate");
+        assert_eq!(records[0]["status"], "success");
+        assert_eq!(records[0]["files_generated"], 2);
+        assert!(records[0]["template_digest"].is_string());
+        assert_eq!(records[1]["operation"], "validate");
+
+        // Template directories outside the templates root are refused
+        for template_dir in [template_dir.display().to_string(), "../terraform".to_string()] {
+            let request = GenerateRequest { template_dir, ..request.clone() };
+            let status = service.validate(Request::new(request)).await.unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn test_context_cache_is_bounded() {
+        let service = GeneratorService::new(
+            ApiClient::new("http://127.0.0.1:9".to_string(), "key".to_string()),
+            DEFAULT_CACHE_TTL,
+            None,
+        )
+        .with_cache_capacity(2);
+        for name in ["a", "b", "c"] {
+            service.cache_context((DataSource::Stack, name.to_string()), VariableContext::new());
+        }
+
+        let contexts = service.contexts.lock().unwrap();
+        assert_eq!(contexts.len(), 2);
+        assert!(!contexts.contains_key(&(DataSource::Stack, "a".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_api_key_scopes_are_enforced() {
+        let keys = KeyStore::parse(r#"{"keys": [{"name": "viewer", "key": "secret", "scopes": ["read"]}]}"#).unwrap();
+        let service = GeneratorService::new(
+            ApiClient::new("http://127.0.0.1:9".to_string(), "key".to_string()),
+            DEFAULT_CACHE_TTL,
+            Some(keys),
+        );
+        let request = |key: Option<&str>| {
+            let mut request = Request::new(GenerateRequest {
+                data_source: "cluster".to_string(),
+                ..GenerateRequest::default()
+            });
+            if let Some(key) = key {
+                request.metadata_mut().insert("x-api-key", key.parse().unwrap());
+            }
+            request
+        };
+
+        let status = service.validate(request(None)).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+        let status = service.generate(request(Some("secret"))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        // Authorized, so the request fails on the data source instead
+        let status = service.validate(request(Some("secret"))).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_request_guards_apply_to_every_rpc() {
+        let service = |config: GuardConfig| {
+            GeneratorService::new(
+                ApiClient::new("http://127.0.0.1:9".to_string(), "key".to_string()),
+                DEFAULT_CACHE_TTL,
+                None,
+            )
+            .with_guards(RequestGuards::new(config))
+        };
+        let request = || {
+            Request::new(GenerateRequest {
+                data_source: "cluster".to_string(),
+                ..GenerateRequest::default()
+            })
+        };
+
+        let limited = service(GuardConfig { rate_limit_per_minute: 1, ..GuardConfig::default() });
+        // Past the guards, so the request fails on the data source instead
+        assert_eq!(limited.validate(request()).await.unwrap_err().code(), tonic::Code::InvalidArgument);
+        let status = limited
+            .list_variables(Request::new(ListVariablesRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.metadata().get("retry-after").is_some());
+
+        let small = service(GuardConfig { max_body_bytes: 4, ..GuardConfig::default() });
+        let status = small.generate(request()).await.unwrap_err();
+        assert_eq!(status.message(), "Request body exceeds the 4 byte limit");
+
+        let busy = service(GuardConfig { max_concurrent_generations: 1, ..GuardConfig::default() });
+        let _running = busy.guards.try_start_generation().unwrap();
+        let status = busy.validate(request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+    }
+
+    #[test]
+    fn test_error_status_codes() {
+        assert_eq!(
+            to_status(CliError::NotFoundError("x".to_string())).code(),
+            tonic::Code::NotFound
+        );
+        assert_eq!(
+            to_status(parse_data_source("cluster").unwrap_err()).code(),
+            tonic::Code::InvalidArgument
+        );
+        assert!(matches!(parse_data_source("Stack"), Ok(DataSource::Stack)));
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::CliError;
+
+/// Built-in guidance, see `guidance.yaml` for the format
+const BUILTIN_GUIDANCE: &str = include_str!("guidance.yaml");
+
+/// Next-steps guidance printed after a successful generate run
+///
+/// Sections are selected by the extensions and file names of the generated
+/// files. A user file (`--guidance-file`) can replace built-in sections by
+/// id, disable them, add org-specific sections and add tips.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GuidanceConfig {
+    #[serde(default)]
+    pub sections: Vec<GuidanceSection>,
+    #[serde(default)]
+    pub tips: Vec<String>,
+    /// Replace the built-in tips instead of adding to them (override files only)
+    #[serde(default)]
+    pub replace_tips: bool,
+}
+
+/// Steps for one file type or tool
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuidanceSection {
+    pub id: String,
+    #[serde(default)]
+    pub title: String,
+    /// When to show the section; always shown if omitted
+    #[serde(default)]
+    pub when: Option<GuidanceCondition>,
+    #[serde(default)]
+    pub steps: Vec<GuidanceStep>,
+    /// Hide a built-in section with the same id
+    #[serde(default)]
+    pub disabled: bool,
+}
+
+/// Generated files a section applies to
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GuidanceCondition {
+    /// File extensions without the dot, e.g. `tf`
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// Exact file names, e.g. `Chart.yaml`
+    #[serde(default)]
+    pub files: Vec<String>,
+    /// Ids of sections that take precedence over this one
+    #[serde(default)]
+    pub unless: Vec<String>,
+}
+
+/// A numbered step, optionally followed by commands to run
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum GuidanceStep {
+    Text(String),
+    Commands {
+        text: String,
+        #[serde(default)]
+        run: Vec<String>,
+    },
+}
+
+impl GuidanceConfig {
+    /// Guidance shipped with the CLI
+    pub fn builtin() -> Self {
+        serde_yaml::from_str(BUILTIN_GUIDANCE).expect("built-in guidance.yaml is valid")
+    }
+
+    /// Built-in guidance with an optional override file applied
+    pub fn load(override_file: Option<&Path>) -> Result<Self, CliError> {
+        let mut config = Self::builtin();
+        if let Some(path) = override_file {
+            let content = std::fs::read_to_string(path).map_err(|e| {
+                CliError::ConfigurationError(format!("Failed to read guidance file {}: {}", path.display(), e))
+            })?;
+            let overrides: GuidanceConfig = serde_yaml::from_str(&content).map_err(|e| {
+                CliError::ConfigurationError(format!("Invalid guidance file {}: {}", path.display(), e))
+            })?;
+            config.merge(overrides);
+        }
+        Ok(config)
+    }
+
+    /// Apply overrides: sections replace built-ins with the same id or are appended
+    pub fn merge(&mut self, overrides: GuidanceConfig) {
+        for section in overrides.sections {
+            match self.sections.iter_mut().find(|existing| existing.id == section.id) {
+                Some(existing) => *existing = section,
+                None => self.sections.push(section),
+            }
+        }
+        if overrides.replace_tips {
+            self.tips = overrides.tips;
+        } else {
+            self.tips.extend(overrides.tips);
+        }
+    }
+
+    /// Render the guidance for a set of generated files
+    pub fn render(&self, written_files: &[PathBuf], template_dir: &Path) -> String {
+        let matched: HashSet<&str> = self
+            .sections
+            .iter()
+            .filter(|section| !section.disabled && first_match(section, written_files).is_some())
+            .map(|section| section.id.as_str())
+            .collect();
+
+        let mut guidance = String::from("Next steps:");
+
+        for section in self.sections.iter().filter(|section| !section.disabled) {
+            let Some(file) = first_match(section, written_files) else {
+                continue;
+            };
+            let unless = section.when.as_ref().map(|when| when.unless.as_slice()).unwrap_or_default();
+            if unless.iter().any(|id| matched.contains(id.as_str())) {
+                continue;
+            }
+
+            let dir = file
+                .and_then(|f| f.parent())
+                .or_else(|| written_files.first().and_then(|f| f.parent()))
+                .unwrap_or(Path::new("."));
+            let fill = |text: &str| {
+                text.replace("{dir}", &dir.display().to_string())
+                    .replace("{file}", &file.map(|f| f.display().to_string()).unwrap_or_default())
+                    .replace("{template_dir}", &template_dir.display().to_string())
+            };
+
+            guidance.push_str(&format!("\n\n{}:", fill(&section.title)));
+            for (number, step) in section.steps.iter().enumerate() {
+                match step {
+                    GuidanceStep::Text(text) => guidance.push_str(&format!("\n  {}. {}", number + 1, fill(text))),
+                    GuidanceStep::Commands { text, run } => {
+                        let colon = if run.is_empty() { "" } else { ":" };
+                        guidance.push_str(&format!("\n  {}. {}{}", number + 1, fill(text), colon));
+                        for command in run {
+                            guidance.push_str(&format!("\n     {}", fill(command)));
+                        }
+                    }
+                }
+            }
+        }
+
+        if !self.tips.is_empty() {
+            guidance.push_str("\n\nGeneral tips:");
+            for tip in &self.tips {
+                guidance.push_str(&format!(
+                    "\n  - {}",
+                    tip.replace("{template_dir}", &template_dir.display().to_string())
+                ));
+            }
+        }
+
+        guidance
+    }
+}
+
+/// First generated file a section applies to
+///
+/// Returns `Some(None)` for sections shown regardless of the files.
+fn first_match<'a>(section: &GuidanceSection, written_files: &'a [PathBuf]) -> Option<Option<&'a PathBuf>> {
+    let Some(when) = &section.when else {
+        return Some(None);
+    };
+    written_files
+        .iter()
+        .find(|path| {
+            let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            when.extensions.iter().any(|ext| ext == extension) || when.files.iter().any(|file| file == name)
+        })
+        .map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_builtin_terraform_guidance() {
+        let guidance = GuidanceConfig::builtin().render(&files(&["out/main.tf", "out/vars.json"]), Path::new("tpl"));
+        assert!(guidance.starts_with("Next steps:\n\nFor Terraform/OpenTofu:\n  1. Review"));
+        assert!(guidance.contains("  2. Initialize Terraform:\n     cd out\n     terraform init"));
+        // JSON steps are left out when Terraform files were generated
+        assert!(!guidance.contains("For JSON"));
+        assert!(guidance.ends_with("updated templates in tpl"));
+    }
+
+    #[test]
+    fn test_detected_tools_take_precedence_over_plain_kubernetes() {
+        let guidance =
+            GuidanceConfig::builtin().render(&files(&["k8s/kustomization.yaml", "k8s/deploy.yaml"]), Path::new("t"));
+        assert!(guidance.contains("kubectl apply -k k8s"));
+        assert!(!guidance.contains("For Kubernetes:"));
+    }
+
+    #[test]
+    fn test_override_file_replaces_disables_and_adds() {
+        let mut config = GuidanceConfig::builtin();
+        let overrides: GuidanceConfig = serde_yaml::from_str(
+            r#"
+sections:
+  - id: terraform
+    title: For Terraform (Acme)
+    when: { extensions: [tf] }
+    steps:
+      - text: Open a pull request in infra-live
+        run: ["gh pr create --fill"]
+  - id: json
+    disabled: true
+  - id: acme
+    title: Before you merge
+    steps: ["Ask #platform for a review"]
+tips: ["Runbooks: https://wiki.acme.test/iac"]
+"#,
+        )
+        .unwrap();
+        config.merge(overrides);
+
+        let guidance = config.render(&files(&["main.tf", "config.json"]), Path::new("t"));
+        assert!(guidance.contains("For Terraform (Acme):\n  1. Open a pull request in infra-live:\n     gh pr create --fill"));
+        assert!(!guidance.contains("terraform init"));
+        assert!(guidance.contains("Before you merge:\n  1. Ask #platform for a review"));
+        assert!(guidance.contains("  - Use version control"));
+        assert!(guidance.ends_with("  - Runbooks: https://wiki.acme.test/iac"));
+    }
+}
@@ -0,0 +1,140 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::error::CliError;
+use crate::resource_mapper::{kubernetes_name, source_metadata};
+use crate::template_discovery::{TemplateFile, TemplateFileType};
+use crate::template_processor::ProcessedFile;
+use crate::variable_context::VariableContext;
+
+/// Chart metadata file at the root of the chart
+pub const CHART_FILE: &str = "Chart.yaml";
+
+/// Default values file at the root of the chart
+pub const VALUES_FILE: &str = "values.yaml";
+
+/// Directory of the chart holding the rendered manifests
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// Version written to Chart.yaml; charts are regenerated rather than released
+const CHART_VERSION: &str = "0.1.0";
+
+/// Contents of Chart.yaml
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChartMetadata {
+    api_version: &'static str,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(rename = "type")]
+    chart_type: &'static str,
+    version: &'static str,
+}
+
+/// Move YAML templates under the chart's `templates/` directory
+///
+/// Other templates, e.g. Terraform files, keep their paths next to the chart.
+pub fn chart_templates(templates: Vec<TemplateFile>) -> Vec<TemplateFile> {
+    templates
+        .into_iter()
+        .map(|template| match template.file_type {
+            TemplateFileType::Yaml => TemplateFile {
+                relative_path: Path::new(TEMPLATES_DIR).join(&template.relative_path),
+                ..template
+            },
+            _ => template,
+        })
+        .collect()
+}
+
+/// Chart.yaml and values.yaml for the blueprint or stack in the context
+///
+/// The chart is named after the stack (or blueprint), and values.yaml holds
+/// the configuration of each resource under `resources.<name>`, so the
+/// chart's defaults match what was generated.
+pub fn chart_files(context: &VariableContext) -> Result<Vec<ProcessedFile>, CliError> {
+    let data = context.to_template_data();
+    let (source, name, description) = source_metadata(&data);
+    let name = name
+        .map(kubernetes_name)
+        .ok_or_else(|| CliError::GenerationError(format!("The {} has no name to name the Helm chart after", source)))?;
+
+    let chart = ChartMetadata {
+        api_version: "v2",
+        name: name.clone(),
+        description: description.map(str::to_string),
+        chart_type: "application",
+        version: CHART_VERSION,
+    };
+
+    let resources: Map<String, Value> = data
+        .get("stack_resources")
+        .or_else(|| data.get("resources"))
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|resource| {
+            let name = resource.get("name")?.as_str()?;
+            let configuration = resource.get("configuration").cloned().unwrap_or_else(|| Value::Object(Map::new()));
+            Some((name.to_string(), configuration))
+        })
+        .collect();
+    let mut values = Map::new();
+    values.insert("name".to_string(), Value::String(name));
+    values.insert("resources".to_string(), Value::Object(resources));
+
+    Ok(vec![yaml_file(CHART_FILE, &chart)?, yaml_file(VALUES_FILE, &Value::Object(values))?])
+}
+
+fn yaml_file(file: &str, value: &impl Serialize) -> Result<ProcessedFile, CliError> {
+    let content = serde_yaml::to_string(value)
+        .map_err(|e| CliError::GenerationError(format!("Failed to write {}: {}", file, e)))?;
+    Ok(ProcessedFile::new(PathBuf::from(file), content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_chart_files_from_stack() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("Payments Prod"));
+        context.insert("stack.description".to_string(), json!("Payments API"));
+        context.insert(
+            "stack_resources".to_string(),
+            json!([{"name": "api", "configuration": {"replicas": 3}}, {"name": "cache"}]),
+        );
+
+        let files = chart_files(&context).unwrap();
+        assert_eq!(files[0].relative_path, PathBuf::from("Chart.yaml"));
+        assert_eq!(
+            files[0].content,
+            "apiVersion: v2\nname: payments-prod\ndescription: Payments API\ntype: application\nversion: 0.1.0\n"
+        );
+        let values: Value = serde_yaml::from_str(&files[1].content).unwrap();
+        assert_eq!(
+            values,
+            json!({"name": "payments-prod", "resources": {"api": {"replicas": 3}, "cache": {}}})
+        );
+    }
+
+    #[test]
+    fn test_chart_templates_moves_only_yaml() {
+        let template = |path: &str, file_type| TemplateFile {
+            path: PathBuf::from(path),
+            relative_path: PathBuf::from(path),
+            file_type,
+        };
+        let moved = chart_templates(vec![
+            template("k8s/deployment.yaml", TemplateFileType::Yaml),
+            template("main.tf", TemplateFileType::Terraform),
+        ]);
+        assert_eq!(moved[0].relative_path, PathBuf::from("templates/k8s/deployment.yaml"));
+        assert_eq!(moved[1].relative_path, PathBuf::from("main.tf"));
+    }
+}
@@ -0,0 +1,424 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, RenderContext, RenderError, RenderErrorReason, ScopedJson, Template,
+};
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::CliError;
+use crate::render_sandbox;
+use crate::template_processor::{TemplateData, TemplateProcessor};
+
+/// Directory inside the template directory that holds template-defined helpers
+pub const HELPERS_DIR: &str = "helpers";
+
+/// Extension of helper definitions in [`HELPERS_DIR`]
+pub const HELPER_EXTENSION: &str = "hbs";
+
+/// Extension of Rhai script helpers in [`HELPERS_DIR`]
+pub const SCRIPT_HELPER_EXTENSION: &str = "rhai";
+
+/// Helpers registered on top of the built-in ones
+///
+/// Helpers come from three places:
+/// - `<template-dir>/helpers/<name>.rhai` or `<name>.hbs`, one helper per file
+/// - a `--helpers-file` YAML file mapping helper names to definitions
+/// - library consumers, with [`HelperSet::register`]
+///
+/// A script helper is a [Rhai](https://rhai.rs) script evaluated with the
+/// helper's positional parameters as the array `params`, its hash parameters
+/// as the map `hash` and the template variables as `root`. The value of the
+/// script's last expression is the helper's value:
+///
+/// ```text
+/// // helpers/subnet_cidr.rhai, used as {{subnet_cidr "10.0" 2}}
+/// let index = params[1];
+/// `${params[0]}.${index * 16}.0/20`
+/// ```
+///
+/// A template-defined helper is a Handlebars template rendered with the
+/// helper's positional parameters as `args`, its hash parameters as `hash`
+/// and the template variables as `root`:
+///
+/// ```text
+/// {{!-- helpers/resource_id.hbs, used as {{resource_id "db" env="prod"}} --}}
+/// {{hash.env}}-{{lowercase (lookup args 0)}}-{{root.blueprint.name}}
+/// ```
+#[derive(Clone, Default)]
+pub struct HelperSet {
+    helpers: BTreeMap<String, Arc<dyn HelperDef + Send + Sync>>,
+}
+
+impl std::fmt::Debug for HelperSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.helpers.keys()).finish()
+    }
+}
+
+impl HelperSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a helper implemented in Rust
+    pub fn register(&mut self, name: &str, helper: impl HelperDef + Send + Sync + 'static) -> &mut Self {
+        self.helpers.insert(name.to_string(), Arc::new(helper));
+        self
+    }
+
+    /// Add a helper defined as a Handlebars template
+    pub fn register_template(&mut self, name: &str, source: &str) -> Result<&mut Self, CliError> {
+        check_name(name)?;
+        Template::compile(source).map_err(|e| CliError::TemplateSyntaxError {
+            line: e.pos().map(|(line, _)| line).unwrap_or(0),
+            message: format!("in helper '{}': {}", name, e),
+        })?;
+        Ok(self.register(name, TemplateHelper { source: source.trim_end_matches('\n').to_string() }))
+    }
+
+    /// Add a helper defined as a Rhai script
+    pub fn register_script(&mut self, name: &str, source: &str) -> Result<&mut Self, CliError> {
+        check_name(name)?;
+        let script = script_engine().compile(source).map_err(|e| CliError::TemplateSyntaxError {
+            line: e.position().line().unwrap_or(0),
+            message: format!("in helper '{}': {}", name, e),
+        })?;
+        Ok(self.register(name, ScriptHelper { script }))
+    }
+
+    /// Helpers for a template directory: its `helpers/` directory, then an optional helpers file
+    ///
+    /// Definitions in the helpers file replace ones with the same name.
+    pub fn for_templates(template_dir: &Path, helpers_file: Option<&Path>) -> Result<Self, CliError> {
+        let mut helpers = Self::new();
+        let dir = template_dir.join(HELPERS_DIR);
+        if dir.is_dir() {
+            helpers.load_dir(&dir)?;
+        }
+        if let Some(path) = helpers_file {
+            helpers.load_file(path)?;
+        }
+        Ok(helpers)
+    }
+
+    /// Load `<name>.rhai` and `<name>.hbs` files from a directory
+    pub fn load_dir(&mut self, dir: &Path) -> Result<&mut Self, CliError> {
+        let entries = std::fs::read_dir(dir).map_err(|e| {
+            CliError::IoError(format!("Failed to read helpers directory {}: {}", dir.display(), e))
+        })?;
+        let mut paths: Vec<_> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == HELPER_EXTENSION || ext == SCRIPT_HELPER_EXTENSION))
+            .collect();
+        paths.sort();
+        let mut loaded = std::collections::BTreeSet::new();
+        for path in paths {
+            let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+            if !loaded.insert(name.clone()) {
+                return Err(CliError::ConfigurationError(format!(
+                    "Helper '{}' is defined more than once in {}",
+                    name,
+                    dir.display()
+                )));
+            }
+            let source = std::fs::read_to_string(&path).map_err(|e| {
+                CliError::IoError(format!("Failed to read helper {}: {}", path.display(), e))
+            })?;
+            if path.extension().is_some_and(|ext| ext == SCRIPT_HELPER_EXTENSION) {
+                self.register_script(&name, &source)?;
+            } else {
+                self.register_template(&name, &source)?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Load a YAML file mapping helper names to definitions
+    ///
+    /// A string is a template definition; `{rhai: <script>}` is a script.
+    pub fn load_file(&mut self, path: &Path) -> Result<&mut Self, CliError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            CliError::ConfigurationError(format!("Failed to read helpers file {}: {}", path.display(), e))
+        })?;
+        let definitions: BTreeMap<String, Definition> = serde_yaml::from_str(&content).map_err(|e| {
+            CliError::ConfigurationError(format!("Invalid helpers file {}: {}", path.display(), e))
+        })?;
+        for (name, definition) in &definitions {
+            match definition {
+                Definition::Template(source) => self.register_template(name, source)?,
+                Definition::Script(script) => self.register_script(name, &script.rhai)?,
+            };
+        }
+        Ok(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.helpers.is_empty()
+    }
+
+    /// Names of the registered helpers, sorted
+    pub fn names(&self) -> Vec<&str> {
+        self.helpers.keys().map(String::as_str).collect()
+    }
+
+    /// Register the helpers, refusing to replace built-in ones
+    pub(crate) fn apply(&self, handlebars: &mut Handlebars) -> Result<(), CliError> {
+        for (name, helper) in &self.helpers {
+            if TemplateProcessor::BUILTIN_HELPERS.contains(&name.as_str()) {
+                return Err(CliError::ConfigurationError(format!(
+                    "Helper '{}' conflicts with a built-in helper",
+                    name
+                )));
+            }
+            handlebars.register_helper(name, Box::new(SharedHelper(Arc::clone(helper))));
+        }
+        Ok(())
+    }
+}
+
+/// A helper definition in a helpers file
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Definition {
+    Template(String),
+    Script(ScriptDefinition),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptDefinition {
+    rhai: String,
+}
+
+fn check_name(name: &str) -> Result<(), CliError> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(CliError::ConfigurationError(format!(
+            "Invalid helper name '{}': use letters, digits, '_' and '-'",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// A helper shared between the processors created from one [`HelperSet`]
+struct SharedHelper(Arc<dyn HelperDef + Send + Sync>);
+
+impl HelperDef for SharedHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let _depth = render_sandbox::enter_helper(h.name())?;
+        self.0.call_inner(h, r, ctx, rc)
+    }
+
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn handlebars::Output,
+    ) -> handlebars::HelperResult {
+        let _depth = render_sandbox::enter_helper(h.name())?;
+        self.0.call(h, r, ctx, rc, out)
+    }
+}
+
+/// Helper whose output is a Handlebars template rendered with its parameters
+struct TemplateHelper {
+    source: String,
+}
+
+impl HelperDef for TemplateHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        r: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let args: Vec<Value> = h.params().iter().map(|p| p.value().clone()).collect();
+        let hash: serde_json::Map<String, Value> =
+            h.hash().iter().map(|(k, v)| (k.to_string(), v.value().clone())).collect();
+        let data = json!({ "args": args, "hash": hash, "root": ctx.data() });
+        let rendered = r.render_template(&self.source, &data).map_err(|e| {
+            RenderError::from(RenderErrorReason::Other(format!("helper '{}' failed: {}", h.name(), e)))
+        })?;
+        Ok(ScopedJson::Derived(Value::String(rendered)))
+    }
+}
+
+/// Rhai engine running script helpers
+///
+/// Scripts can't import modules or print, and a script still running when
+/// the render deadline passes is stopped.
+fn script_engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(|| {
+        let mut engine = Engine::new();
+        engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+        engine.on_print(|_| {});
+        engine.on_debug(|_, _, _| {});
+        engine.on_progress(|operations| {
+            if operations % 1024 != 0 {
+                return None;
+            }
+            render_sandbox::check_deadline().err().map(|e| Dynamic::from(e.to_string()))
+        });
+        engine
+    })
+}
+
+/// Helper whose value is a Rhai script evaluated with its parameters
+struct ScriptHelper {
+    script: AST,
+}
+
+impl HelperDef for ScriptHelper {
+    fn call_inner<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+    ) -> Result<ScopedJson<'rc>, RenderError> {
+        let params: Vec<&Value> = h.params().iter().map(|p| p.value()).collect();
+        let hash: BTreeMap<&str, &Value> = h.hash().iter().map(|(k, v)| (*k, v.value())).collect();
+        let mut scope = Scope::new();
+        scope.push_dynamic("params", to_dynamic(params).map_err(RenderErrorReason::from)?);
+        scope.push_dynamic("hash", to_dynamic(hash).map_err(RenderErrorReason::from)?);
+        // Shared with the script helper calls of the whole render, so constant
+        scope.push_constant_dynamic("root", TemplateData::script_root(ctx).map_err(RenderErrorReason::from)?);
+        let result = script_engine().eval_ast_with_scope::<Dynamic>(&mut scope, &self.script).map_err(|e| {
+            match *e {
+                // Stopped at the render deadline, which is reported as the violation
+                EvalAltResult::ErrorTerminated(reason, _) => RenderErrorReason::Other(reason.to_string()),
+                e => RenderErrorReason::Other(format!("helper '{}' failed: {}", h.name(), e)),
+            }
+        })?;
+        Ok(ScopedJson::Derived(from_dynamic(&result).map_err(RenderErrorReason::from)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variable_context::VariableContext;
+    use handlebars::handlebars_helper;
+
+    fn context() -> VariableContext {
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("web-app"));
+        context
+    }
+
+    #[test]
+    fn test_template_helper_sees_args_hash_and_root() {
+        let mut helpers = HelperSet::new();
+        helpers
+            .register_template("resource_id", "{{hash.env}}-{{lowercase (lookup args 0)}}-{{root.blueprint.name}}\n")
+            .unwrap();
+
+        let context = context();
+        let processor = TemplateProcessor::new(&context).with_helpers(&helpers).unwrap();
+        let output = processor.process_template(r#"name = "{{resource_id "DB" env="prod"}}""#).unwrap();
+        assert_eq!(output, r#"name = "prod-db-web-app""#);
+        // Usable as a subexpression
+        let output = processor.process_template(r#"{{uppercase (resource_id "db" env="qa")}}"#).unwrap();
+        assert_eq!(output, "QA-DB-WEB-APP");
+    }
+
+    #[test]
+    fn test_script_helper_computes_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let helpers_dir = dir.path().join(HELPERS_DIR);
+        std::fs::create_dir(&helpers_dir).unwrap();
+        std::fs::write(
+            helpers_dir.join("subnet_cidr.rhai"),
+            "let index = params[1];\n`${params[0]}.${index * 16}.0/20`\n",
+        )
+        .unwrap();
+        let file = dir.path().join("helpers.yaml");
+        std::fs::write(&file, "port:\n  rhai: params[0] + hash.offset\nowner:\n  rhai: root.blueprint.name + \"-team\"\n")
+            .unwrap();
+
+        let helpers = HelperSet::for_templates(dir.path(), Some(&file)).unwrap();
+        assert_eq!(helpers.names(), vec!["owner", "port", "subnet_cidr"]);
+        let context = context();
+        let processor = TemplateProcessor::new(&context).with_helpers(&helpers).unwrap();
+        let output = processor
+            .process_template(r#"{{subnet_cidr "10.0" 2}} {{port 8000 offset=80}} {{uppercase (owner)}}"#)
+            .unwrap();
+        assert_eq!(output, "10.0.32.0/20 8080 WEB-APP-TEAM");
+
+        // `root` is shared by every call of the render, so scripts can only change copies of it
+        std::fs::write(
+            &file,
+            "rename:\n  rhai: root.blueprint.name = \"other\"\ncopy:\n  rhai: \"let b = root.blueprint; b.name = `x`; b.name\"\n",
+        )
+        .unwrap();
+        let helpers = HelperSet::for_templates(dir.path(), Some(&file)).unwrap();
+        let processor = TemplateProcessor::new(&context).with_helpers(&helpers).unwrap();
+        assert!(processor.process_template("{{rename}}").is_err());
+        assert_eq!(processor.process_template("{{copy}} {{blueprint.name}}").unwrap(), "x web-app");
+
+        // Scripts are compiled when loaded, and a helper can't be both a script and a template
+        std::fs::write(helpers_dir.join("broken.rhai"), "let x = ;").unwrap();
+        assert!(matches!(
+            HelperSet::for_templates(dir.path(), None),
+            Err(CliError::TemplateSyntaxError { .. })
+        ));
+        std::fs::remove_file(helpers_dir.join("broken.rhai")).unwrap();
+        std::fs::write(helpers_dir.join("subnet_cidr.hbs"), "{{args.[0]}}").unwrap();
+        let error = HelperSet::for_templates(dir.path(), None).unwrap_err();
+        assert!(error.to_string().contains("'subnet_cidr' is defined more than once"), "{}", error);
+    }
+
+    #[test]
+    fn test_register_rust_helper_and_reject_builtin_names() {
+        handlebars_helper!(double: |n: u64| n * 2);
+        let mut helpers = HelperSet::new();
+        helpers.register("double", double);
+
+        let context = context();
+        let processor = TemplateProcessor::new(&context).with_helpers(&helpers).unwrap();
+        assert_eq!(processor.process_template("{{double 21}}").unwrap(), "42");
+
+        helpers.register_template("uppercase", "{{args.[0]}}").unwrap();
+        let error = TemplateProcessor::new(&context).with_helpers(&helpers).err().unwrap();
+        assert!(error.to_string().contains("'uppercase' conflicts with a built-in helper"));
+    }
+
+    #[test]
+    fn test_load_helpers_dir_and_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let helpers_dir = dir.path().join(HELPERS_DIR);
+        std::fs::create_dir(&helpers_dir).unwrap();
+        std::fs::write(helpers_dir.join("tag.hbs"), "{{args.[0]}}:dir").unwrap();
+        std::fs::write(helpers_dir.join("notes.txt"), "ignored").unwrap();
+        let file = dir.path().join("helpers.yaml");
+        std::fs::write(&file, "tag: \"{{args.[0]}}:file\"\nprefix: \"acme-{{args.[0]}}\"\n").unwrap();
+
+        let helpers = HelperSet::for_templates(dir.path(), Some(&file)).unwrap();
+        assert_eq!(helpers.names(), vec!["prefix", "tag"]);
+        let context = context();
+        let processor = TemplateProcessor::new(&context).with_helpers(&helpers).unwrap();
+        assert_eq!(processor.process_template("{{tag \"x\"}} {{prefix \"y\"}}").unwrap(), "x:file acme-y");
+
+        std::fs::write(helpers_dir.join("broken.hbs"), "{{#if}}").unwrap();
+        assert!(matches!(
+            HelperSet::for_templates(dir.path(), None),
+            Err(CliError::TemplateSyntaxError { .. })
+        ));
+    }
+}
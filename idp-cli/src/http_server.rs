@@ -0,0 +1,279 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::body::{to_bytes, Body as AxumBody};
+use axum::extract::{ConnectInfo, Request as AxumRequest, State};
+use axum::response::Response as AxumResponse;
+use axum::Router;
+use lambda_http::Body;
+use log::{error, info, warn};
+
+use crate::api_client::ApiClient;
+use crate::error::CliError;
+use crate::lambda_handler::{handle_request, HandlerState};
+use crate::request_guards::GuardRejection;
+
+/// Build the router serving the Lambda handler's routes
+///
+/// Like a Lambda function URL, every path and method is passed to the handler,
+/// which dispatches on the `operation` field of the JSON body.
+pub fn router(state: HandlerState) -> Router {
+    router_with_state(Arc::new(state))
+}
+
+fn router_with_state(state: Arc<HandlerState>) -> Router {
+    Router::new().fallback(invoke).with_state(state)
+}
+
+/// Serve the Lambda handler over HTTP until the process is stopped
+pub async fn serve(addr: SocketAddr, api_client: ApiClient) -> Result<(), CliError> {
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| CliError::IoError(format!("Failed to bind {}: {}", addr, e)))?;
+    println!("Serving HTTP on {}", addr);
+
+    // Same settings as the Lambda function, including the idempotency store
+    let state = Arc::new(HandlerState::from_env_with_client(api_client).await?);
+    info!("Request limits: {:?}", state.guards.config());
+    if state.keys.is_none() {
+        warn!("No API keys configured (API_KEYS_FILE or API_KEYS_SECRET); requests are not authenticated");
+    }
+
+    // Warm up in the background; /readyz reports 503 until it finishes
+    let warming = state.clone();
+    tokio::spawn(async move {
+        if let Err(e) = warming.warm_up().await {
+            error!("Warm-up failed: {}", e);
+        }
+    });
+
+    let app = router_with_state(state).into_make_service_with_connect_info::<SocketAddr>();
+    axum::serve(listener, app)
+        .await
+        .map_err(|e| CliError::IoError(format!("HTTP server failed: {}", e)))
+}
+
+/// Convert the axum request, run the Lambda handler and convert the response back
+async fn invoke(
+    State(state): State<Arc<HandlerState>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    request: AxumRequest,
+) -> AxumResponse {
+    let (parts, body) = request.into_parts();
+    info!("HTTP {} {}", parts.method, parts.uri);
+
+    // Stop reading as soon as the body exceeds the limit
+    let limit = state.guards.config().max_body_bytes;
+    let bytes = match to_bytes(body, limit).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let message = GuardRejection::PayloadTooLarge { limit }.message();
+            return error_response(413, &message);
+        }
+    };
+    let client = peer
+        .map(|ConnectInfo(addr)| addr.ip().to_string())
+        .unwrap_or_else(|| "anonymous".to_string());
+    let body = if bytes.is_empty() {
+        Body::Empty
+    } else {
+        Body::Binary(bytes.to_vec())
+    };
+
+    let request = lambda_http::Request::from_parts(parts, body);
+    match handle_request(request, &state, &client).await {
+        Ok(response) => {
+            let (parts, body) = response.into_parts();
+            let body = match body {
+                Body::Empty => AxumBody::empty(),
+                Body::Text(text) => AxumBody::from(text),
+                Body::Binary(bytes) => AxumBody::from(bytes),
+            };
+            AxumResponse::from_parts(parts, body)
+        }
+        Err(e) if e.is::<serde_json::Error>() => {
+            error_response(400, &format!("Invalid request body: {}", e))
+        }
+        Err(e) => {
+            error!("Request failed: {}", e);
+            error_response(500, &e.to_string())
+        }
+    }
+}
+
+/// JSON error in the same shape as the handler's own failure responses
+fn error_response(status: u16, message: &str) -> AxumResponse {
+    let body = serde_json::json!({
+        "success": false,
+        "message": message,
+        "error": message,
+    });
+    AxumResponse::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(AxumBody::from(body.to_string()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_keys::KeyStore;
+    use crate::idempotency;
+    use crate::request_guards::{GuardConfig, RequestGuards};
+    use tower::ServiceExt;
+
+    fn state(config: GuardConfig) -> HandlerState {
+        HandlerState::new(
+            ApiClient::new("http://127.0.0.1:9".to_string(), "key".to_string()),
+            RequestGuards::new(config),
+        )
+    }
+
+    async fn call(body: &str) -> (u16, serde_json::Value) {
+        call_with(GuardConfig::default(), body).await
+    }
+
+    async fn call_with(config: GuardConfig, body: &str) -> (u16, serde_json::Value) {
+        let request = AxumRequest::builder()
+            .method("POST")
+            .uri("/")
+            .body(AxumBody::from(body.to_string()))
+            .unwrap();
+        let response = router(state(config)).oneshot(request).await.unwrap();
+        let status = response.status().as_u16();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_empty_body_is_rejected() {
+        let (status, body) = call("").await;
+        assert_eq!(status, 400);
+        assert_eq!(body["message"], "Empty request body");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_json_is_bad_request() {
+        let (status, body) = call("{\"operation\": \"deploy\"}").await;
+        assert_eq!(status, 400);
+        assert_eq!(body["success"], false);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_data_source_uses_handler_response() {
+        let (status, body) = call(
+            r#"{"operation": "list-variables", "data_source": "cluster", "identifier": "x"}"#,
+        )
+        .await;
+        assert_eq!(status, 500);
+        assert_eq!(body["message"], "Invalid data source");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected() {
+        let config = GuardConfig {
+            max_body_bytes: 16,
+            ..GuardConfig::default()
+        };
+        let (status, body) = call_with(config, &"x".repeat(17)).await;
+        assert_eq!(status, 413);
+        assert_eq!(body["message"], "Request body exceeds the 16 byte limit");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_client_gets_429() {
+        let app = router(state(GuardConfig {
+            rate_limit_per_minute: 1,
+            ..GuardConfig::default()
+        }));
+        let request = || AxumRequest::builder().method("POST").uri("/").body(AxumBody::empty()).unwrap();
+
+        let first = app.clone().oneshot(request()).await.unwrap();
+        assert_eq!(first.status().as_u16(), 400);
+        let second = app.oneshot(request()).await.unwrap();
+        assert_eq!(second.status().as_u16(), 429);
+        assert_eq!(second.headers()["retry-after"], "60");
+    }
+
+    #[tokio::test]
+    async fn test_health_routes_skip_limits_and_keys() {
+        let mut state = state(GuardConfig {
+            rate_limit_per_minute: 1,
+            ..GuardConfig::default()
+        });
+        state.keys = Some(KeyStore::parse(r#"{"keys": []}"#).unwrap());
+        let app = router(state);
+        let get = |path: &str| AxumRequest::builder().uri(path).body(AxumBody::empty()).unwrap();
+
+        for _ in 0..3 {
+            let health = app.clone().oneshot(get("/healthz")).await.unwrap();
+            assert_eq!(health.status().as_u16(), 200);
+        }
+        // Not warmed up yet
+        let ready = app.clone().oneshot(get("/readyz")).await.unwrap();
+        assert_eq!(ready.status().as_u16(), 503);
+
+        // Warm-up does real work, so it needs a key like any other request
+        let warmup = app.oneshot(get("/warmup")).await.unwrap();
+        assert_eq!(warmup.status().as_u16(), 401);
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key_replays_stored_result() {
+        let body = r#"{"operation": "generate", "data_source": "stack", "identifier": "payments"}"#;
+        let state = state(GuardConfig::default());
+        let location = idempotency::storage_location("anonymous", "delivery-1");
+        let stored = serde_json::json!({"success": true, "message": "Successfully generated 2 files"});
+        state
+            .idempotency
+            .put(&location, &idempotency::request_hash(body.as_bytes()), &stored)
+            .await;
+        let app = router(state);
+        let request = |body: &str| {
+            AxumRequest::builder()
+                .method("POST")
+                .uri("/")
+                .header("idempotency-key", "delivery-1")
+                .body(AxumBody::from(body.to_string()))
+                .unwrap()
+        };
+
+        let replayed = app.clone().oneshot(request(body)).await.unwrap();
+        assert_eq!(replayed.status().as_u16(), 200);
+        assert_eq!(replayed.headers()["idempotent-replayed"], "true");
+
+        let conflict = app.oneshot(request(&body.replace("payments", "billing"))).await.unwrap();
+        assert_eq!(conflict.status().as_u16(), 422);
+    }
+
+    #[tokio::test]
+    async fn test_api_key_scopes_are_enforced() {
+        let mut state = state(GuardConfig::default());
+        state.keys = Some(
+            KeyStore::parse(r#"{"keys": [{"name": "viewer", "key": "secret", "scopes": ["read"]}]}"#).unwrap(),
+        );
+        let app = router(state);
+        let request = |key: Option<&str>, operation: &str| {
+            let mut builder = AxumRequest::builder().method("POST").uri("/");
+            if let Some(key) = key {
+                builder = builder.header("authorization", format!("Bearer {}", key));
+            }
+            let body = format!(r#"{{"operation": "{}", "data_source": "cluster", "identifier": "x"}}"#, operation);
+            builder.body(AxumBody::from(body)).unwrap()
+        };
+
+        let missing = app.clone().oneshot(request(None, "list-variables")).await.unwrap();
+        assert_eq!(missing.status().as_u16(), 401);
+        assert_eq!(missing.headers()["www-authenticate"], "Bearer");
+        let invalid = app.clone().oneshot(request(Some("wrong"), "list-variables")).await.unwrap();
+        assert_eq!(invalid.status().as_u16(), 401);
+
+        let forbidden = app.clone().oneshot(request(Some("secret"), "generate")).await.unwrap();
+        assert_eq!(forbidden.status().as_u16(), 403);
+
+        // Authorized, so the request reaches the handler and fails on the data source
+        let allowed = app.oneshot(request(Some("secret"), "list-variables")).await.unwrap();
+        assert_eq!(allowed.status().as_u16(), 500);
+    }
+}
@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use crate::error::CliError;
+use crate::template_discovery::sha256_hex;
+
+/// Request header carrying the idempotency key in server mode
+pub const IDEMPOTENCY_HEADER: &str = "idempotency-key";
+
+/// Response header set when a stored result is returned
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Longest accepted idempotency key
+pub const MAX_KEY_LENGTH: usize = 255;
+
+/// Directory under the output directory holding records of completed CLI runs
+pub const LOCAL_RECORD_DIR: &str = ".idp-cli/idempotency";
+
+/// Check that a key is non-empty, at most 255 characters and printable ASCII
+pub fn validate_key(key: &str) -> Result<&str, CliError> {
+    let key = key.trim();
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH {
+        return Err(CliError::ConfigurationError(format!(
+            "Idempotency key must be 1 to {} characters long",
+            MAX_KEY_LENGTH
+        )));
+    }
+    if !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(CliError::ConfigurationError(
+            "Idempotency key must contain only printable ASCII characters without spaces".to_string(),
+        ));
+    }
+    Ok(key)
+}
+
+/// Hash identifying a request, used to detect a key reused for a different request
+pub fn request_hash(request: &[u8]) -> String {
+    sha256_hex(request)
+}
+
+/// Where a server-mode result is stored; keys are scoped to the caller
+pub fn storage_location(caller: &str, key: &str) -> String {
+    format!("idempotency/{}", sha256_hex(format!("{}\n{}", caller, key).as_bytes()))
+}
+
+/// Completed CLI generation for an idempotency key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalRecord {
+    pub key: String,
+    /// Hash of the request (see [`request_hash`])
+    pub request: String,
+    /// Seconds since the Unix epoch
+    pub completed_at: u64,
+    pub files_written: usize,
+}
+
+impl LocalRecord {
+    pub fn new(key: &str, request: &str, files_written: usize) -> Self {
+        Self {
+            key: key.to_string(),
+            request: request.to_string(),
+            completed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            files_written,
+        }
+    }
+}
+
+/// Records of completed CLI runs, kept next to the outputs they produced
+pub struct LocalStore {
+    dir: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(output_dir: &Path) -> Self {
+        Self {
+            dir: output_dir.join(LOCAL_RECORD_DIR),
+        }
+    }
+
+    fn record_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", sha256_hex(key.as_bytes())))
+    }
+
+    /// Record for a key, if a run with it has completed
+    pub fn load(&self, key: &str) -> Result<Option<LocalRecord>, CliError> {
+        let path = self.record_path(key);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(CliError::IoError(format!(
+                "Failed to read idempotency record {}: {}",
+                path.display(),
+                e
+            ))),
+        }
+    }
+
+    pub fn save(&self, record: &LocalRecord) -> Result<(), CliError> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| {
+            CliError::IoError(format!("Failed to create directory {}: {}", self.dir.display(), e))
+        })?;
+        let path = self.record_path(&record.key);
+        std::fs::write(&path, serde_json::to_string_pretty(record)?).map_err(|e| {
+            CliError::IoError(format!("Failed to write idempotency record {}: {}", path.display(), e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validate_key() {
+        assert_eq!(validate_key(" deploy-42 ").unwrap(), "deploy-42");
+        assert!(validate_key("").is_err());
+        assert!(validate_key("has space").is_err());
+        assert!(validate_key(&"k".repeat(MAX_KEY_LENGTH + 1)).is_err());
+    }
+
+    #[test]
+    fn test_storage_location_is_scoped_to_caller() {
+        assert_eq!(storage_location("ci", "k"), storage_location("ci", "k"));
+        assert_ne!(storage_location("ci", "k"), storage_location("other", "k"));
+        assert!(storage_location("ci", "../k").starts_with("idempotency/"));
+    }
+
+    #[test]
+    fn test_local_store_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = LocalStore::new(temp_dir.path());
+        assert!(store.load("deploy-42").unwrap().is_none());
+
+        store.save(&LocalRecord::new("deploy-42", "abc", 3)).unwrap();
+        let record = store.load("deploy-42").unwrap().unwrap();
+        assert_eq!(record.request, "abc");
+        assert_eq!(record.files_written, 3);
+        assert!(store.load("deploy-43").unwrap().is_none());
+    }
+}
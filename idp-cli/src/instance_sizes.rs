@@ -0,0 +1,183 @@
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError, RenderErrorReason,
+};
+
+use crate::region_check::Cloud;
+
+/// Abstract sizes blueprints can use instead of provider instance types
+pub const SIZES: &[&str] = &["small", "medium", "large", "xlarge"];
+
+/// What a size is translated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceKind {
+    /// Virtual machines and Kubernetes nodes
+    Compute,
+    /// Managed relational databases
+    Database,
+    /// Managed Redis/Memcached
+    Cache,
+}
+
+impl InstanceKind {
+    /// Kind for a `kind=` argument such as `database`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "compute" | "vm" | "node" => Some(InstanceKind::Compute),
+            "database" | "db" => Some(InstanceKind::Database),
+            "cache" => Some(InstanceKind::Cache),
+            _ => None,
+        }
+    }
+
+    /// Kind for a resource type, from its category or, failing that, its name
+    ///
+    /// Resource types that are neither databases nor caches are sized as compute.
+    pub fn for_resource_type(category: &str, name: &str) -> Self {
+        let category = category.to_lowercase();
+        let name = name.to_lowercase();
+        if category == "database" || ["database", "rds", "sql", "postgres", "mysql"].iter().any(|n| name.contains(n)) {
+            InstanceKind::Database
+        } else if category == "cache" || ["cache", "redis", "memcache"].iter().any(|n| name.contains(n)) {
+            InstanceKind::Cache
+        } else {
+            InstanceKind::Compute
+        }
+    }
+}
+
+/// Instance types by cloud and kind, in the order of [`SIZES`]
+const CATALOG: &[(Cloud, InstanceKind, [&str; 4])] = &[
+    (Cloud::Aws, InstanceKind::Compute, ["t3.small", "m6i.large", "m6i.xlarge", "m6i.2xlarge"]),
+    (Cloud::Aws, InstanceKind::Database, ["db.t3.small", "db.m6g.large", "db.m6g.xlarge", "db.m6g.2xlarge"]),
+    (Cloud::Aws, InstanceKind::Cache, ["cache.t3.small", "cache.m6g.large", "cache.m6g.xlarge", "cache.m6g.2xlarge"]),
+    (Cloud::Azure, InstanceKind::Compute, ["Standard_B2s", "Standard_D2s_v5", "Standard_D4s_v5", "Standard_D8s_v5"]),
+    (
+        Cloud::Azure,
+        InstanceKind::Database,
+        ["B_Standard_B1ms", "GP_Standard_D2s_v3", "GP_Standard_D4s_v3", "GP_Standard_D8s_v3"],
+    ),
+    (Cloud::Azure, InstanceKind::Cache, ["C1", "C2", "C3", "C4"]),
+    (Cloud::Gcp, InstanceKind::Compute, ["e2-small", "n2-standard-2", "n2-standard-4", "n2-standard-8"]),
+    (
+        Cloud::Gcp,
+        InstanceKind::Database,
+        ["db-g1-small", "db-custom-2-7680", "db-custom-4-15360", "db-custom-8-30720"],
+    ),
+    (Cloud::Gcp, InstanceKind::Cache, ["BASIC_1GB", "STANDARD_HA_5GB", "STANDARD_HA_13GB", "STANDARD_HA_26GB"]),
+];
+
+/// Whether a value is an abstract size rather than a provider instance type
+pub fn is_abstract(size: &str) -> bool {
+    SIZES.contains(&size.to_lowercase().as_str())
+}
+
+/// Provider instance type for an abstract size
+pub fn translate(cloud: Cloud, kind: InstanceKind, size: &str) -> Option<&'static str> {
+    let index = SIZES.iter().position(|s| *s == size.to_lowercase())?;
+    CATALOG.iter().find(|(c, k, _)| *c == cloud && *k == kind).map(|(_, _, types)| types[index])
+}
+
+/// Instance size helper: {{instance_size configuration.size}}
+///
+/// Translates an abstract size to the instance type of the current
+/// resource's cloud provider and resource type, so it is meant for use inside
+/// `{{#each resources}}`. `cloud="aws"` and `kind="database"` override what
+/// the resource says. Values that aren't abstract sizes, such as
+/// `db.t3.micro`, are rendered unchanged.
+pub(crate) struct InstanceSizeHelper;
+
+impl HelperDef for InstanceSizeHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        ctx: &'rc Context,
+        rc: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let size = h.param(0).and_then(|p| p.value().as_str()).ok_or_else(|| {
+            RenderError::from(RenderErrorReason::Other("instance_size helper requires a size".to_string()))
+        })?;
+        if !is_abstract(size) {
+            out.write(size)?;
+            return Ok(());
+        }
+
+        // The current resource, when called inside {{#each resources}}
+        let lookup = |path: &str| -> Option<String> {
+            rc.evaluate(ctx, path).ok().and_then(|value| value.as_json().as_str().map(str::to_string))
+        };
+        let provider = h
+            .hash_get("cloud")
+            .and_then(|v| v.value().as_str().map(str::to_string))
+            .or_else(|| lookup("cloud_provider.name"));
+        let cloud = provider.as_deref().and_then(Cloud::from_provider_name).ok_or_else(|| {
+            RenderError::from(RenderErrorReason::Other(format!(
+                "instance_size can't tell the cloud for \"{}\"; use it inside {{{{#each resources}}}} or pass cloud=\"aws\"",
+                size
+            )))
+        })?;
+        let kind = match h.hash_get("kind").and_then(|v| v.value().as_str()) {
+            Some(name) => InstanceKind::parse(name).ok_or_else(|| {
+                RenderError::from(RenderErrorReason::Other(format!(
+                    "instance_size kind must be compute, database or cache, not \"{}\"",
+                    name
+                )))
+            })?,
+            None => InstanceKind::for_resource_type(
+                &lookup("resource_type.category").unwrap_or_default(),
+                &lookup("resource_type.name").unwrap_or_default(),
+            ),
+        };
+
+        let instance_type = translate(cloud, kind, size).ok_or_else(|| {
+            RenderError::from(RenderErrorReason::Other(format!("no {:?} instance type for size \"{}\"", kind, size)))
+        })?;
+        out.write(instance_type)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_processor::TemplateProcessor;
+    use crate::variable_context::VariableContext;
+    use serde_json::json;
+
+    #[test]
+    fn test_translate_sizes() {
+        assert_eq!(translate(Cloud::Aws, InstanceKind::Database, "medium"), Some("db.m6g.large"));
+        assert_eq!(translate(Cloud::Gcp, InstanceKind::Compute, "Small"), Some("e2-small"));
+        assert_eq!(translate(Cloud::Azure, InstanceKind::Compute, "huge"), None);
+        assert_eq!(InstanceKind::for_resource_type("Storage", "PostgreSQL"), InstanceKind::Database);
+        assert_eq!(InstanceKind::for_resource_type("cache", "Session Store"), InstanceKind::Cache);
+        assert_eq!(InstanceKind::for_resource_type("container", "EKS"), InstanceKind::Compute);
+    }
+
+    #[test]
+    fn test_instance_size_helper_uses_the_current_resource() {
+        let mut context = VariableContext::new();
+        context.insert(
+            "resources".to_string(),
+            json!([
+                {"name": "db", "resource_type": {"name": "PostgreSQL", "category": "database"},
+                 "cloud_provider": {"name": "AWS"}, "configuration": {"size": "medium"}},
+                {"name": "web", "resource_type": {"name": "VM", "category": "compute"},
+                 "cloud_provider": {"name": "Azure"}, "configuration": {"size": "large"}},
+                {"name": "legacy", "resource_type": {"name": "VM", "category": "compute"},
+                 "cloud_provider": {"name": "AWS"}, "configuration": {"size": "t2.micro"}}
+            ]),
+        );
+        let processor = TemplateProcessor::new(&context);
+        let output = processor
+            .process_template("{{#each resources}}{{name}}={{instance_size configuration.size}}\n{{/each}}")
+            .unwrap();
+        assert_eq!(output, "db=db.m6g.large\nweb=Standard_D4s_v5\nlegacy=t2.micro\n");
+
+        let output = processor.process_template(r#"{{instance_size "small" cloud="gcp" kind="cache"}}"#).unwrap();
+        assert_eq!(output, "BASIC_1GB");
+        let error = processor.process_template(r#"{{instance_size "small"}}"#).unwrap_err();
+        assert!(error.to_string().contains("can't tell the cloud"));
+    }
+}
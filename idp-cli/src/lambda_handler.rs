@@ -1,17 +1,29 @@
 use lambda_http::{run, service_fn, Body, Error, Request, Response};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use tokio::sync::OnceCell;
 use tempfile::TempDir;
 use log::{error, info};
 
 use crate::api_client::ApiClient;
 use crate::cli::DataSource;
-use crate::error::CliError;
-use crate::variable_context::VariableContextBuilder;
-use crate::template_discovery::TemplateDiscovery;
+use crate::variable_context::{VariableContext, VariableContextBuilder};
+use crate::template_discovery::{templates_digest, TemplateDiscovery};
+use crate::generation_strategy::StrategyRegistry;
 use crate::template_processor::TemplateProcessor;
-use crate::file_writer::FileWriter;
-use crate::s3_client::S3Client;
+use crate::render_sandbox::SandboxPolicy;
+use crate::file_writer::{FileWriter, S3Sink};
+use crate::error::CliError;
+use crate::paths::local_template_path;
+use crate::pipeline::{default_concurrency, file_channel, render_to_channel, DEFAULT_CHANNEL_CAPACITY};
+use crate::s3_client::{S3Client, S3Location};
+use crate::request_guards::{GuardConfig, GuardRejection, RequestGuards};
+use crate::audit::{AuditLog, AuditRecord, AuditStatus};
+use crate::api_keys::{presented_key, AuthRejection, KeyStore, Scope};
+use crate::response_cache::{fingerprint, ResponseCache, DEFAULT_CACHE_TTL};
+use crate::idempotency::{request_hash, storage_location, validate_key, IDEMPOTENCY_HEADER, REPLAYED_HEADER};
 
 /// Lambda request payload for CLI operations
 #[derive(Debug, Deserialize)]
@@ -31,8 +43,23 @@ enum LambdaRequest {
     },
 }
 
+impl LambdaRequest {
+    /// Scopes an API key needs for the operation
+    /// 
+    /// Generation also needs `push` when outputs are uploaded to `OUTPUTS_BUCKET`.
+    fn required_scopes(&self) -> Vec<Scope> {
+        match self {
+            LambdaRequest::Generate { .. } if std::env::var("OUTPUTS_BUCKET").is_ok() => {
+                vec![Scope::Generate, Scope::Push]
+            }
+            LambdaRequest::Generate { .. } => vec![Scope::Generate],
+            LambdaRequest::ListVariables { .. } => vec![Scope::Read],
+        }
+    }
+}
+
 /// Lambda response payload
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 struct LambdaResponse {
     success: bool,
     message: String,
@@ -46,66 +73,406 @@ struct LambdaResponse {
     error: Option<String>,
 }
 
+/// Liveness probe route
+pub const HEALTH_PATH: &str = "/healthz";
+
+/// Readiness probe route
+pub const READY_PATH: &str = "/readyz";
+
+/// Route that pre-initializes clients and the template engine
+pub const WARMUP_PATH: &str = "/warmup";
+
+/// State shared by every request a handler serves
+pub struct HandlerState {
+    pub api_client: ApiClient,
+    pub guards: RequestGuards,
+    pub audit: AuditLog,
+    /// Accepted API keys; `None` leaves requests unauthenticated
+    pub keys: Option<KeyStore>,
+    pub cache: ResponseCache,
+    /// Results of generate requests sent with an `Idempotency-Key` header
+    pub idempotency: ResponseCache,
+    /// Idempotency keys whose generation is still running
+    in_flight: Mutex<HashSet<String>>,
+    /// Created on first use (or warm-up) and reused by later requests
+    s3_client: OnceCell<S3Client>,
+}
+
+impl HandlerState {
+    /// Create state with auditing, authentication and caching disabled
+    /// and idempotency results kept in memory
+    pub fn new(api_client: ApiClient, guards: RequestGuards) -> Self {
+        Self {
+            api_client,
+            guards,
+            audit: AuditLog::disabled(),
+            keys: None,
+            cache: ResponseCache::disabled(),
+            idempotency: ResponseCache::memory(DEFAULT_CACHE_TTL),
+            in_flight: Mutex::new(HashSet::new()),
+            s3_client: OnceCell::new(),
+        }
+    }
+
+    /// Build the state from environment variables
+    /// 
+    /// Uses `IDP_API_URL`, `IDP_API_KEY` and `IDP_MAX_RESPONSE_BYTES` for the API client, plus the
+    /// request limit, audit, API key, response cache and idempotency store settings.
+    pub async fn from_env() -> Result<Self, Error> {
+        let api_url = std::env::var("IDP_API_URL")
+            .unwrap_or_else(|_| "http://localhost:8082/api/v1".to_string());
+        let api_key = std::env::var("IDP_API_KEY")
+            .map_err(|_| "IDP_API_KEY environment variable not set")?;
+
+        let mut api_client = ApiClient::new(api_url, api_key);
+        if let Some(max_response_bytes) = std::env::var("IDP_MAX_RESPONSE_BYTES").ok().and_then(|v| v.parse().ok()) {
+            api_client = api_client.with_max_response_bytes(max_response_bytes);
+        }
+
+        Ok(Self::from_env_with_client(api_client).await?)
+    }
+
+    /// Build the state around an existing API client, reading everything
+    /// else from environment variables like [`from_env`](Self::from_env)
+    /// 
+    /// Used by `serve --http`, which configures the API client like the other commands.
+    pub async fn from_env_with_client(api_client: ApiClient) -> Result<Self, CliError> {
+        let mut state = Self::new(api_client, RequestGuards::new(GuardConfig::from_env()));
+        state.audit = AuditLog::from_env().await?;
+        state.keys = KeyStore::from_env().await?;
+        state.cache = ResponseCache::from_env().await?;
+        state.idempotency = ResponseCache::from_env_named("IDEMPOTENCY_STORE", "memory").await?;
+        Ok(state)
+    }
+
+    /// S3 client for the `TEMPLATES_BUCKET` and `OUTPUTS_BUCKET` buckets
+    async fn s3_client(&self) -> Result<&S3Client, CliError> {
+        self.s3_client
+            .get_or_try_init(|| async {
+                let templates_bucket = std::env::var("TEMPLATES_BUCKET")
+                    .unwrap_or_else(|_| "visuidp-templates".to_string());
+                let outputs_bucket = std::env::var("OUTPUTS_BUCKET").ok();
+                let aws_region = std::env::var("AWS_REGION").ok();
+                S3Client::new(templates_bucket, outputs_bucket, aws_region).await
+            })
+            .await
+    }
+
+    /// Initialize everything the first generation would otherwise pay for
+    /// 
+    /// Loads the AWS configuration and S3 client, builds the strategy registry
+    /// and renders a probe template so the Handlebars engine and helpers are ready.
+    pub async fn warm_up(&self) -> Result<(), CliError> {
+        let started = std::time::Instant::now();
+        self.s3_client().await?;
+
+        let context = VariableContext::new();
+        let registry = StrategyRegistry::default();
+        info!("Warm-up selected the '{}' strategy", registry.select_for_context(&context).name());
+        TemplateProcessor::new(&context).process_template("{{uppercase \"ok\"}}")?;
+
+        info!("Warm-up finished in {:?}", started.elapsed());
+        Ok(())
+    }
+
+    /// Whether the handler can serve generation requests
+    /// 
+    /// Ready once warmed up, with the local templates root (if configured) readable.
+    pub fn is_ready(&self) -> bool {
+        let templates_ready = match std::env::var("TEMPLATES_DIR") {
+            Ok(root) => Path::new(&root).is_dir(),
+            Err(_) => true,
+        };
+        self.s3_client.initialized() && templates_ready
+    }
+}
+
+/// State for the Lambda runtime, shared by all requests a warm instance handles
+static LAMBDA_STATE: OnceCell<HandlerState> = OnceCell::const_new();
+
 /// Main Lambda handler function
 pub async fn function_handler(event: Request) -> Result<Response<Body>, Error> {
-    // Parse request body
+    let state = LAMBDA_STATE.get_or_try_init(HandlerState::from_env).await?;
+    let client = forwarded_client(&event).unwrap_or_else(|| "anonymous".to_string());
+
+    handle_request(event, state, &client).await
+}
+
+/// Client address from the `X-Forwarded-For` header
+/// 
+/// API Gateway and ALB append the caller's address, so the last entry is used.
+pub fn forwarded_client(event: &Request) -> Option<String> {
+    event
+        .headers()
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .map(|client| client.trim().to_string())
+        .filter(|client| !client.is_empty())
+}
+
+/// Handle a request with shared handler state
+/// 
+/// Shared by the Lambda runtime and `idp-cli serve --http`, so both expose
+/// the same operations, responses, limits and auditing. `client` identifies
+/// the caller for rate limiting and audit records.
+pub async fn handle_request(
+    event: Request,
+    state: &HandlerState,
+    client: &str,
+) -> Result<Response<Body>, Error> {
+    // Health routes skip limits and authentication so probes always get an answer
+    match event.uri().path() {
+        HEALTH_PATH => return status_response(200, "ok"),
+        READY_PATH if state.is_ready() => return status_response(200, "ready"),
+        READY_PATH => return status_response(503, "not ready"),
+        _ => {}
+    }
+
+    let HandlerState { api_client, guards, audit, keys, .. } = state;
     let body = event.body();
+    if let Err(rejection) = guards.check_body_size(body.len()) {
+        return rejection_response(&rejection);
+    }
+    if let Err(rejection) = guards.check_rate(client) {
+        info!("Rate limited client {}", client);
+        return rejection_response(&rejection);
+    }
+
+    // Authenticate before reading the request; scopes are checked once the operation is known
+    let key = match keys.as_ref().map(|keys| keys.authenticate(presented_key(event.headers()))) {
+        Some(Ok(key)) => Some(key),
+        Some(Err(rejection)) => {
+            info!("Unauthenticated request from {}", client);
+            return auth_response(&rejection);
+        }
+        None => None,
+    };
+    let caller = key.map_or(client, |key| key.name.as_str());
+
+    // Warm-up creates clients and renders, so it is limited and authenticated like generation
+    if event.uri().path() == WARMUP_PATH {
+        return match state.warm_up().await {
+            Ok(()) => status_response(200, "warm"),
+            Err(e) => {
+                error!("Warm-up failed: {}", e);
+                status_response(503, "warm-up failed")
+            }
+        };
+    }
+
+    let idempotency_key = match event.headers().get(IDEMPOTENCY_HEADER) {
+        Some(value) => match value.to_str().map_err(|e| CliError::ConfigurationError(e.to_string())).and_then(validate_key) {
+            Ok(key) => Some(key),
+            Err(e) => return error_response(400, "Invalid Idempotency-Key header", &e.to_string()),
+        },
+        None => None,
+    };
+
+    // Parse request body
     let request: LambdaRequest = match body {
         Body::Text(text) => serde_json::from_str(text)?,
         Body::Binary(bytes) => serde_json::from_slice(bytes)?,
         Body::Empty => {
-            return Ok(Response::builder()
-                .status(400)
-                .body(Body::from(serde_json::to_string(&LambdaResponse {
-                    success: false,
-                    message: "Empty request body".to_string(),
-                    files: None,
-                    s3_keys: None,
-                    variables: None,
-                    error: Some("Request body is required".to_string()),
-                })?))
-                .unwrap());
+            return error_response(400, "Empty request body", "Request body is required");
         }
     };
 
-    // Get API configuration from environment variables
-    let api_url = std::env::var("IDP_API_URL")
-        .unwrap_or_else(|_| "http://localhost:8082/api/v1".to_string());
-    let api_key = std::env::var("IDP_API_KEY")
-        .map_err(|_| "IDP_API_KEY environment variable not set")?;
-
-    // Create API client
-    let api_client = ApiClient::new(api_url, api_key);
+    if let Some(key) = key {
+        if let Err(rejection) = key.require(&request.required_scopes()) {
+            info!("Forbidden request from {}: {}", caller, rejection.message());
+            return auth_response(&rejection);
+        }
+    }
 
     // Route to appropriate handler
     let response = match request {
         LambdaRequest::Generate { data_source, identifier, template_dir, variables } => {
-            handle_generate_lambda(data_source, identifier, template_dir, variables, &api_client).await
+            // A retry with the same key gets the original result instead of a new generation
+            let in_flight = match idempotency_key {
+                Some(key) => {
+                    let location = storage_location(caller, key);
+                    let request = request_hash(body.as_ref());
+                    match state.idempotency.lookup::<LambdaResponse>(&location).await {
+                        Some((stored, response)) if stored == request => {
+                            info!("Replaying result for idempotency key from {}", caller);
+                            return json_response(200, &response, true);
+                        }
+                        Some(_) => {
+                            return error_response(
+                                422,
+                                "Idempotency key was already used for a different request",
+                                "Use a new Idempotency-Key for a different request",
+                            );
+                        }
+                        None => {}
+                    }
+                    match InFlight::start(&state.in_flight, location, request) {
+                        Some(in_flight) => Some(in_flight),
+                        None => {
+                            return error_response(
+                                409,
+                                "A request with this idempotency key is in progress",
+                                "Retry after the original request finishes",
+                            );
+                        }
+                    }
+                }
+                None => None,
+            };
+
+            let mut record = AuditRecord::new(caller, "generate", &data_source, &identifier);
+
+            // Held until generation finishes
+            let _permit = match guards.try_start_generation() {
+                Ok(permit) => permit,
+                Err(rejection) => {
+                    record.status = AuditStatus::Rejected;
+                    record.error = Some(rejection.message());
+                    audit.record_or_log(&record).await;
+                    return rejection_response(&rejection);
+                }
+            };
+
+            let response = handle_generate_lambda(
+                data_source,
+                identifier,
+                template_dir,
+                variables,
+                state,
+                &mut record,
+            ).await;
+
+            record.status = if response.success { AuditStatus::Success } else { AuditStatus::Failure };
+            record.files_generated = response.files.as_ref().map_or(0, Vec::len);
+            if !response.success {
+                record.error = Some(response.error.clone().unwrap_or_else(|| response.message.clone()));
+            }
+            audit.record_or_log(&record).await;
+
+            // Failures are not stored, so a retry runs the generation again
+            if let (Some(in_flight), true) = (&in_flight, response.success) {
+                state.idempotency.put(&in_flight.location, &in_flight.request, &response).await;
+            }
+            response
         }
         LambdaRequest::ListVariables { data_source, identifier } => {
-            handle_list_variables_lambda(data_source, identifier, &api_client).await
+            handle_list_variables_lambda(data_source, identifier, api_client).await
         }
     };
 
     // Convert response to HTTP response
-    let response_body = serde_json::to_string(&response)?;
     let status_code = if response.success { 200 } else { 500 };
+    json_response(status_code, &response, false)
+}
+
+/// Reservation of an idempotency key while its generation runs
+struct InFlight<'a> {
+    keys: &'a Mutex<HashSet<String>>,
+    location: String,
+    request: String,
+}
 
+impl<'a> InFlight<'a> {
+    /// Reserve the key, or `None` if another request holds it
+    fn start(keys: &'a Mutex<HashSet<String>>, location: String, request: String) -> Option<Self> {
+        keys.lock().unwrap().insert(location.clone()).then_some(Self { keys, location, request })
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.keys.lock().unwrap().remove(&self.location);
+    }
+}
+
+/// JSON response for a handler result
+fn json_response(status: u16, response: &LambdaResponse, replayed: bool) -> Result<Response<Body>, Error> {
+    let mut builder = Response::builder()
+        .status(status)
+        .header("content-type", "application/json");
+    if replayed {
+        builder = builder.header(REPLAYED_HEADER, "true");
+    }
+    Ok(builder.body(Body::from(serde_json::to_string(response)?)).unwrap())
+}
+
+/// Failure response for a request that could not be handled
+fn error_response(status: u16, message: &str, error: &str) -> Result<Response<Body>, Error> {
+    json_response(
+        status,
+        &LambdaResponse {
+            success: false,
+            message: message.to_string(),
+            files: None,
+            s3_keys: None,
+            variables: None,
+            error: Some(error.to_string()),
+        },
+        false,
+    )
+}
+
+/// Response for the health, readiness and warm-up routes
+fn status_response(status: u16, message: &str) -> Result<Response<Body>, Error> {
+    let body = serde_json::json!({ "status": message });
     Ok(Response::builder()
-        .status(status_code)
+        .status(status)
         .header("content-type", "application/json")
-        .body(Body::from(response_body))
+        .body(Body::from(body.to_string()))
         .unwrap())
 }
 
+/// Response for a request refused by the request guards (413 or 429)
+fn rejection_response(rejection: &GuardRejection) -> Result<Response<Body>, Error> {
+    let mut builder = Response::builder()
+        .status(rejection.status())
+        .header("content-type", "application/json");
+    if let Some(seconds) = rejection.retry_after_secs() {
+        builder = builder.header("retry-after", seconds.to_string());
+    }
+
+    let body = serde_json::to_string(&LambdaResponse {
+        success: false,
+        message: rejection.message(),
+        files: None,
+        s3_keys: None,
+        variables: None,
+        error: Some(rejection.message()),
+    })?;
+    Ok(builder.body(Body::from(body)).unwrap())
+}
+
+/// Response for a request without a valid API key (401) or scope (403)
+fn auth_response(rejection: &AuthRejection) -> Result<Response<Body>, Error> {
+    let mut builder = Response::builder()
+        .status(rejection.status())
+        .header("content-type", "application/json");
+    if rejection.status() == 401 {
+        builder = builder.header("www-authenticate", "Bearer");
+    }
+
+    let body = serde_json::to_string(&LambdaResponse {
+        success: false,
+        message: rejection.message(),
+        files: None,
+        s3_keys: None,
+        variables: None,
+        error: Some(rejection.message()),
+    })?;
+    Ok(builder.body(Body::from(body)).unwrap())
+}
+
 /// Handle generate operation in Lambda
 async fn handle_generate_lambda(
     data_source: String,
     identifier: String,
     template_dir: Option<String>,
     variables: Option<serde_json::Value>,
-    api_client: &ApiClient,
+    state: &HandlerState,
+    record: &mut AuditRecord,
 ) -> LambdaResponse {
+    let HandlerState { api_client, cache, .. } = state;
     info!("Lambda generate: {} {}", data_source, identifier);
 
     // Parse data source
@@ -128,11 +495,9 @@ async fn handle_generate_lambda(
     let templates_bucket = std::env::var("TEMPLATES_BUCKET")
         .unwrap_or_else(|_| "visuidp-templates".to_string());
     let outputs_bucket = std::env::var("OUTPUTS_BUCKET").ok();
-    let aws_region = std::env::var("AWS_REGION").ok();
     let template_prefix = template_dir.unwrap_or_else(|| "templates/".to_string());
 
-    // Create S3 client
-    let s3_client = match S3Client::new(templates_bucket.clone(), outputs_bucket.clone(), aws_region).await {
+    let s3_client = match state.s3_client().await {
         Ok(client) => client,
         Err(e) => {
             return LambdaResponse {
@@ -175,28 +540,52 @@ async fn handle_generate_lambda(
         }
     };
 
-    // Download templates from S3
-    info!("Downloading templates from S3 bucket: {}, prefix: {}", templates_bucket, template_prefix);
-    if let Err(e) = s3_client.download_templates(&template_prefix, temp_templates_dir.path()).await {
-        return LambdaResponse {
-            success: false,
-            message: format!("Failed to download templates from S3: {}", e.user_message()),
-            files: None,
-            s3_keys: None,
-            variables: None,
-            error: Some(format!("{:?}", e)),
-        };
-    }
+    // Templates are read from a local directory when TEMPLATES_DIR is set
+    // (self-hosted `idp-cli serve --http`), otherwise downloaded from S3
+    let template_path = match std::env::var("TEMPLATES_DIR") {
+        Ok(root) => match local_template_path(Path::new(&root), &template_prefix) {
+            Ok(path) => {
+                record.template_source = path.display().to_string();
+                path
+            }
+            Err(e) => {
+                return LambdaResponse {
+                    success: false,
+                    message: "Invalid template directory".to_string(),
+                    files: None,
+                    s3_keys: None,
+                    variables: None,
+                    error: Some(e.to_string()),
+                };
+            }
+        },
+        Err(_) => {
+            record.template_source = format!("s3://{}/{}", templates_bucket, template_prefix);
 
-    let template_path = temp_templates_dir.path().to_path_buf();
+            // Download templates from S3
+            info!("Downloading templates from S3 bucket: {}, prefix: {}", templates_bucket, template_prefix);
+            if let Err(e) = s3_client.download_templates(&template_prefix, temp_templates_dir.path()).await {
+                return LambdaResponse {
+                    success: false,
+                    message: format!("Failed to download templates from S3: {}", e.user_message()),
+                    files: None,
+                    s3_keys: None,
+                    variables: None,
+                    error: Some(format!("{:?}", e)),
+                };
+            }
 
-    // Fetch data and build context
-    let mut context = match data_source {
+            temp_templates_dir.path().to_path_buf()
+        }
+    };
+
+    // Fetch data and build context; the fetched data is kept for the cache fingerprint
+    let (mut context, source) = match data_source {
         DataSource::Blueprint => {
             match api_client.get_blueprint(&identifier).await {
                 Ok(blueprint) => {
                     info!("Retrieved blueprint: {}", blueprint.name);
-                    VariableContextBuilder::from_blueprint(&blueprint)
+                    (VariableContextBuilder::from_blueprint(&blueprint), serde_json::to_value(&blueprint))
                 }
                 Err(e) => {
                     return LambdaResponse {
@@ -214,7 +603,7 @@ async fn handle_generate_lambda(
             match api_client.get_stack(&identifier).await {
                 Ok(stack) => {
                     info!("Retrieved stack: {}", stack.name);
-                    VariableContextBuilder::from_stack(&stack)
+                    (VariableContextBuilder::from_stack(&stack), serde_json::to_value(&stack))
                 }
                 Err(e) => {
                     return LambdaResponse {
@@ -231,7 +620,7 @@ async fn handle_generate_lambda(
     };
 
     // Merge custom variables if provided
-    if let Some(vars) = variables {
+    if let Some(vars) = &variables {
         if let Some(obj) = vars.as_object() {
             for (key, value) in obj {
                 context.insert(key.clone(), value.clone());
@@ -266,33 +655,92 @@ async fn handle_generate_lambda(
         };
     }
 
-    info!("Discovered {} template files", template_files.len());
+    // Select template sets based on the stack type
+    let registry = StrategyRegistry::default();
+    let strategy = registry.select_for_context(&context);
+    info!("Using '{}' generation strategy", strategy.name());
+    let template_files = registry.apply(strategy, template_files);
 
-    // Process templates
-    let processor = TemplateProcessor::new(&context);
-    let mut processed_files = Vec::new();
+    if template_files.is_empty() {
+        return LambdaResponse {
+            success: false,
+            message: format!("No templates apply to the '{}' generation strategy", strategy.name()),
+            files: None,
+            s3_keys: None,
+            variables: None,
+            error: Some(format!("Template sets: {}", strategy.template_sets().join(", "))),
+        };
+    }
 
-    for template_file in &template_files {
-        match processor.process_file(template_file) {
-            Ok(processed) => processed_files.push(processed),
-            Err(e) => {
-                return LambdaResponse {
-                    success: false,
-                    message: format!("Failed to process template: {}", e.user_message()),
-                    files: None,
-                    s3_keys: None,
-                    variables: None,
-                    error: Some(format!("{:?}", e)),
-                };
-            }
+    info!("Discovered {} template files", template_files.len());
+    record.template_digest = templates_digest(&template_files).ok();
+
+    // Identical inputs for the same output location return the cached response
+    let output_prefix = format!("outputs/{}/{}", data_source.to_string().to_lowercase(), identifier);
+    let fingerprint = match (&source, &record.template_digest) {
+        (Ok(source), Some(digest)) if cache.is_enabled() => {
+            fingerprint(source, digest, variables.as_ref(), outputs_bucket.as_deref()).ok()
+        }
+        _ => None,
+    };
+    if let Some(fingerprint) = &fingerprint {
+        if let Some(mut cached) = cache.get::<LambdaResponse>(&output_prefix, fingerprint).await {
+            cached.message = format!("{} (cached)", cached.message);
+            return cached;
         }
     }
 
-    // Write files to temporary directory
+    // Render templates and stream each file to S3, or to disk without an
+    // outputs bucket, as soon as it is ready, so large outputs don't
+    // accumulate in memory
+    let processor = TemplateProcessor::new(&context).with_sandbox(SandboxPolicy::from_env());
     let file_writer = FileWriter::new(temp_output_dir.path().to_path_buf());
-    let written_files = match file_writer.write_processed_files(&processed_files) {
-        Ok(files) => files,
-        Err(e) => {
+    let mut sink = outputs_bucket.as_ref().map(|bucket| {
+        let location = S3Location { bucket: bucket.clone(), prefix: output_prefix.clone() };
+        S3Sink::new(s3_client, location).with_kms_key(std::env::var("OUTPUTS_KMS_KEY_ID").ok())
+    });
+    if sink.is_none() {
+        info!("Outputs bucket not configured, skipping S3 upload");
+    }
+    let (sender, mut receiver) = file_channel(DEFAULT_CHANNEL_CAPACITY);
+
+    let (rendered, consumed) = tokio::join!(
+        render_to_channel(&processor, &template_files, sender, default_concurrency()),
+        async {
+            let mut written_files = Vec::new();
+            let mut upload_failed = false;
+            while let Some(processed) = receiver.recv().await {
+                match &mut sink {
+                    // The Lambda has no persistent disk, so files go straight from memory to S3
+                    Some(sink) if !upload_failed => {
+                        if let Err(e) = sink.upload(&processed).await {
+                            error!("Failed to upload outputs to S3: {}", e);
+                            // Don't fail the request, just log the error
+                            upload_failed = true;
+                        }
+                        written_files.push(processed.relative_path);
+                    }
+                    Some(_) => written_files.push(processed.relative_path),
+                    None => written_files.push(file_writer.write_processed_file(&processed)?.path().to_path_buf()),
+                }
+            }
+            let s3_keys = match sink.filter(|_| !upload_failed) {
+                Some(sink) => match sink.finish().await {
+                    Ok(uploaded) => Some(uploaded.into_iter().map(|file| file.key).collect::<Vec<_>>()),
+                    Err(e) => {
+                        error!("Failed to write the upload manifest to S3: {}", e);
+                        None
+                    }
+                },
+                None => None,
+            };
+            Ok::<_, CliError>((written_files, s3_keys))
+        }
+    );
+
+    // The renderer also fails when the writer stops, so a write error is reported first
+    let (written_files, s3_keys) = match (rendered, consumed) {
+        (_, Err(e)) => {
             return LambdaResponse {
                 success: false,
                 message: format!("Failed to write files: {}", e),
@@ -302,43 +750,45 @@ async fn handle_generate_lambda(
                 error: Some(e.to_string()),
             };
         }
+        (Err(e), Ok(_)) => {
+            return LambdaResponse {
+                success: false,
+                message: format!("Failed to process template: {}", e.user_message()),
+                files: None,
+                s3_keys: None,
+                variables: None,
+                error: Some(format!("{:?}", e)),
+            };
+        }
+        (Ok(_), Ok(outputs)) => outputs,
     };
 
+    if let Some(keys) = &s3_keys {
+        info!("Uploaded {} files to S3", keys.len());
+    }
+
     // Convert file paths to strings for response
     let file_paths: Vec<String> = written_files
         .iter()
         .map(|p| p.to_string_lossy().to_string())
         .collect();
 
-    // Upload to S3 if outputs bucket is configured
-    let s3_keys = if outputs_bucket.is_some() {
-        let output_prefix = format!("outputs/{}/{}", data_source.to_string().to_lowercase(), identifier);
-        match s3_client.upload_outputs(&written_files, &output_prefix).await {
-            Ok(keys) => {
-                info!("Uploaded {} files to S3", keys.len());
-                Some(keys)
-            }
-            Err(e) => {
-                error!("Failed to upload outputs to S3: {}", e);
-                // Don't fail the request, just log the error
-                None
-            }
-        }
-    } else {
-        info!("Outputs bucket not configured, skipping S3 upload");
-        None
-    };
-
     info!("Successfully generated {} files", file_paths.len());
 
-    LambdaResponse {
+    let upload_complete = outputs_bucket.is_none() || s3_keys.is_some();
+    let response = LambdaResponse {
         success: true,
         message: format!("Successfully generated {} files", file_paths.len()),
         files: Some(file_paths),
         s3_keys,
         variables: None,
         error: None,
+    };
+    // A failed upload leaves stale outputs behind, so it must not be reused
+    if let (Some(fingerprint), true) = (&fingerprint, upload_complete) {
+        cache.put(&output_prefix, fingerprint, &response).await;
     }
+    response
 }
 
 /// Handle list-variables operation in Lambda
@@ -428,6 +878,16 @@ pub async fn run_lambda() -> Result<(), Error> {
 
     info!("Starting IDP CLI Lambda function");
 
+    // Warm up during the init phase so the first invocation doesn't pay for it
+    match LAMBDA_STATE.get_or_try_init(HandlerState::from_env).await {
+        Ok(state) => {
+            if let Err(e) = state.warm_up().await {
+                error!("Warm-up failed: {}", e);
+            }
+        }
+        Err(e) => error!("Failed to initialize handler: {}", e),
+    }
+
     // Run the Lambda runtime
     run(service_fn(function_handler)).await
 }
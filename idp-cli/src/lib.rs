@@ -1,13 +1,76 @@
 pub mod cli;
+pub mod config_file;
 pub mod error;
 pub mod api_client;
+pub mod circuit_breaker;
 pub mod models;
+pub mod describe;
+pub mod search;
+pub mod listing;
+pub mod stack_state;
 pub mod file_writer;
 pub mod template_discovery;
+pub mod builtin_templates;
+pub mod paths;
+pub mod generation_strategy;
+pub mod backend_config;
+pub mod helm_generator;
+pub mod resource_mapper;
+pub mod crossplane_generator;
+pub mod pulumi_generator;
+pub mod cdktf_generator;
+pub mod dependency_graph;
+pub mod composition;
 pub mod variable_context;
+pub mod variable_export;
+pub mod secret_resolver;
 pub mod template_processor;
+pub mod validators;
+pub mod post_hooks;
+pub mod helper_plugins;
+pub mod instance_sizes;
+pub mod render_sandbox;
+pub mod source_map;
+pub mod var_trace;
+pub mod template_lint;
+pub mod variable_usage;
+pub mod template_inspect;
+pub mod repl;
+pub mod pipeline;
+pub mod progress;
 pub mod s3_client;
+pub mod request_guards;
+pub mod audit;
+pub mod api_keys;
+pub mod response_cache;
+pub mod idempotency;
+pub mod debug_bundle;
+pub mod run_summary;
+pub mod github_actions;
+pub mod reports;
+pub mod output_manifest;
+pub mod changelog;
+pub mod target_info;
+pub mod tool_versions;
+pub mod quotas;
+pub mod policies;
+pub mod region_check;
+pub mod guidance;
+pub mod output_style;
+pub mod reporter;
 
 // Lambda handler module (only compiled when lambda feature is enabled)
 #[cfg(feature = "lambda")]
 pub mod lambda_handler;
+
+// Synthetic workloads for benchmarking (only compiled when bench feature is enabled)
+#[cfg(feature = "bench")]
+pub mod bench;
+
+// gRPC service mode (only compiled when grpc feature is enabled)
+#[cfg(feature = "grpc")]
+pub mod grpc_server;
+
+// Self-hosted HTTP server mode (only compiled when http feature is enabled)
+#[cfg(feature = "http")]
+pub mod http_server;
@@ -0,0 +1,150 @@
+use serde::Serialize;
+
+use crate::models::{Blueprint, Stack};
+use crate::output_style::OutputStyle;
+
+/// A blueprint or stack in the list-blueprints/list-stacks output
+#[derive(Debug, Clone, Serialize)]
+pub struct ListEntry {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub resources: usize,
+    /// Supported cloud providers (blueprints)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cloud_providers: Vec<String>,
+    /// Stack type (stacks)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stack_type: Option<String>,
+    /// Cloud the stack is deployed to (stacks)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cloud_name: Option<String>,
+    /// Name of the linked blueprint (stacks)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blueprint: Option<String>,
+}
+
+impl ListEntry {
+    pub fn from_blueprint(blueprint: &Blueprint) -> Self {
+        Self {
+            id: blueprint.id.to_string(),
+            name: blueprint.name.clone(),
+            description: blueprint.description.clone(),
+            resources: blueprint.resources.len(),
+            cloud_providers: blueprint.supported_cloud_providers.iter().map(|p| p.name.clone()).collect(),
+            stack_type: None,
+            cloud_name: None,
+            blueprint: None,
+        }
+    }
+
+    pub fn from_stack(stack: &Stack) -> Self {
+        Self {
+            id: stack.id.to_string(),
+            name: stack.name.clone(),
+            description: stack.description.clone(),
+            resources: stack.stack_resources.len(),
+            cloud_providers: Vec::new(),
+            stack_type: Some(stack.stack_type.clone()),
+            cloud_name: Some(stack.cloud_name.clone()),
+            blueprint: stack.blueprint.as_ref().map(|b| b.name.clone()),
+        }
+    }
+
+    /// Short summary of the kind-specific fields
+    fn details(&self) -> String {
+        match (&self.stack_type, &self.cloud_name) {
+            (Some(stack_type), Some(cloud)) => {
+                let mut details = format!("{} on {}", stack_type, cloud);
+                if let Some(blueprint) = &self.blueprint {
+                    details.push_str(&format!(" (blueprint {})", blueprint));
+                }
+                details
+            }
+            _ if self.cloud_providers.is_empty() => "-".to_string(),
+            _ => self.cloud_providers.join(", "),
+        }
+    }
+}
+
+/// Format listed blueprints or stacks as a table
+///
+/// `kind` is "blueprint" or "stack".
+pub fn render_table(entries: &[ListEntry], kind: &str, style: OutputStyle) -> String {
+    if entries.is_empty() {
+        return format!("No {}s found.", kind);
+    }
+
+    let mut lines = Vec::new();
+    if style.is_plain() {
+        for entry in entries {
+            lines.push(format!(
+                "{}: {} resource(s), {}, id {}",
+                entry.name,
+                entry.resources,
+                entry.details(),
+                entry.id
+            ));
+        }
+    } else {
+        let name_width = entries.iter().map(|e| e.name.len()).max().unwrap_or(0).max("NAME".len());
+        let details: Vec<String> = entries.iter().map(ListEntry::details).collect();
+        let details_width = details.iter().map(String::len).max().unwrap_or(0).max("DETAILS".len());
+
+        lines.push(format!(
+            "{:<name_width$}  {:<9}  {:<details_width$}  ID",
+            "NAME", "RESOURCES", "DETAILS"
+        ));
+        for (entry, details) in entries.iter().zip(&details) {
+            lines.push(format!(
+                "{:<name_width$}  {:<9}  {:<details_width$}  {}",
+                entry.name, entry.resources, details, entry.id
+            ));
+        }
+    }
+
+    lines.push(format!("\n{} {}(s)", entries.len(), kind));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    fn stack(name: &str) -> Stack {
+        Stack {
+            id: Uuid::nil(),
+            name: name.to_string(),
+            description: None,
+            cloud_name: "aws-prod".to_string(),
+            stack_type: "RestfulApi".to_string(),
+            revision: None,
+            updated_at: None,
+            stack_resources: Vec::new(),
+            blueprint: None,
+        }
+    }
+
+    #[test]
+    fn test_render_table_aligns_columns() {
+        let entries = vec![ListEntry::from_stack(&stack("payments")), ListEntry::from_stack(&stack("ui"))];
+        let output = render_table(&entries, "stack", OutputStyle::default());
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("NAME      RESOURCES  DETAILS"));
+        assert!(lines[2].starts_with("ui        0          RestfulApi on aws-prod"));
+        assert!(output.ends_with("2 stack(s)"));
+    }
+
+    #[test]
+    fn test_render_table_plain_and_empty() {
+        let entries = vec![ListEntry::from_stack(&stack("payments"))];
+        let output = render_table(&entries, "stack", OutputStyle::Plain);
+        assert!(output.starts_with(&format!(
+            "payments: 0 resource(s), RestfulApi on aws-prod, id {}",
+            Uuid::nil()
+        )));
+        assert_eq!(render_table(&[], "blueprint", OutputStyle::default()), "No blueprints found.");
+    }
+}
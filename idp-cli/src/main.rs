@@ -1,20 +1,48 @@
-use clap::Parser;
 use log::{error, info};
 use std::process;
+use std::time::{Duration, Instant};
 
-mod cli;
-mod api_client;
-mod models;
-mod file_writer;
-mod error;
-mod template_discovery;
-mod variable_context;
-mod template_processor;
-
-use api_client::ApiClient;
-use cli::{CliArgs, Command, DataSource};
-use error::CliError;
-use variable_context::{VariableContext, VariableContextBuilder};
+use idp_cli::api_client::{ApiClient, ListQuery};
+use idp_cli::builtin_templates::BuiltinPack;
+use idp_cli::cli::{
+    CliArgs, Command, ConfigAction, ConflictPolicy, DataSource, DescribeFormat, GenerateOptions, GenerationTarget,
+    Invocation, ListFormat, ListOptions, ListVariablesOptions, OutputFormat, ServeOptions, StackAction,
+    StateMismatchPolicy, VariableFormat, VariableInputs,
+};
+use idp_cli::cdktf_generator::CdktfLanguage;
+use idp_cli::config_file::{self, ConfigFile, ConfigSource, LayeredConfig};
+use idp_cli::describe::Description;
+use idp_cli::github_actions::GithubActions;
+use idp_cli::guidance::GuidanceConfig;
+use idp_cli::post_hooks::PostHook;
+use idp_cli::listing::{self, ListEntry};
+use idp_cli::search::{self, SearchCriteria};
+use idp_cli::error::CliError;
+use idp_cli::idempotency::{self, LocalRecord, LocalStore};
+use idp_cli::output_manifest::{Freshness, OutputManifest};
+use idp_cli::output_style::OutputStyle;
+use idp_cli::progress::{ProgressEvent, ProgressReporter};
+use idp_cli::quotas::{QuotaConfig, QuotaSeverity, QuotaTally};
+use idp_cli::policies::{PolicySet, PolicySeverity, PolicyViolation};
+use idp_cli::reports::{write_reports, Finding, Findings, ReportTarget};
+use idp_cli::render_sandbox::{self, SandboxPolicy};
+use idp_cli::reporter::Reporter;
+use idp_cli::run_summary::{FileChanges, FileRecord, RunSummary, RUN_SUMMARY_FILE};
+use idp_cli::s3_client::{S3Client, S3Location};
+use idp_cli::target_info::TargetInfo;
+use idp_cli::variable_context::{VariableContext, VariableContextBuilder};
+use idp_cli::var_trace::VariableTrace;
+use idp_cli::template_lint::TemplateLinter;
+use idp_cli::variable_usage::VariableUsage;
+use idp_cli::template_inspect::TemplateInspection;
+use idp_cli::repl::{Repl, ReplOutcome};
+use rustyline::error::ReadlineError;
+use idp_cli::validators::ValidatorRegistry;
+use idp_cli::{
+    backend_config, cdktf_generator, changelog, composition, crossplane_generator, debug_bundle, file_writer,
+    generation_strategy, helm_generator, helper_plugins, paths, pipeline, pulumi_generator, region_check, stack_state,
+    template_discovery, template_processor, tool_versions, variable_export,
+};
 
 #[tokio::main]
 async fn main() {
@@ -22,25 +50,128 @@ async fn main() {
     env_logger::init();
 
     // Parse command-line arguments
-    let args = CliArgs::parse();
+    let mut args = match CliArgs::parse_invocation() {
+        Invocation::Run(args) => *args,
+        // Handle --target-info (doesn't require a command or validation)
+        Invocation::TargetInfo { output } => {
+            if output == OutputFormat::Json {
+                println!("{}", serde_json::to_string_pretty(&TargetInfo::current()).unwrap_or_default());
+            } else {
+                println!("{}", TargetInfo::current().display());
+            }
+            process::exit(0);
+        }
+    };
+    let verbose_errors = args.verbose_errors;
+    let reporter = Reporter::new(args.output, OutputStyle::from_flag(args.plain));
+
+    // Commands that print free-form text have no JSON form
+    if let Err(e) = args.check_output_format() {
+        reporter.error(&e, verbose_errors);
+        log_error(&e);
+        process::exit(1);
+    }
 
     // Handle version command (doesn't require validation)
     if matches!(args.command, Command::Version) {
-        println!("idp-cli version {}", env!("CARGO_PKG_VERSION"));
-        println!("OpenTofu infrastructure-as-code generator for IDP");
+        if reporter.is_json() {
+            let version = serde_json::json!({ "name": "idp-cli", "version": env!("CARGO_PKG_VERSION") });
+            println!("{}", serde_json::to_string_pretty(&version).unwrap_or_default());
+        } else {
+            println!("idp-cli version {}", env!("CARGO_PKG_VERSION"));
+            println!("OpenTofu infrastructure-as-code generator for IDP");
+        }
+        process::exit(0);
+    }
+
+    // Handle bench command (runs offline, doesn't require validation)
+    #[cfg(feature = "bench")]
+    if let Command::Bench { synthetic } = args.command {
+        match idp_cli::bench::run_synthetic(synthetic) {
+            Ok(summary) => {
+                println!("Synthetic workload: {} resource(s), {} template(s)", synthetic, synthetic);
+                println!("{}", summary.render_table());
+                process::exit(0);
+            }
+            Err(e) => {
+                reporter.error(&e, verbose_errors);
+                log_error(&e);
+                process::exit(1);
+            }
+        }
+    }
+
+    // Layer the config files under flags and environment variables
+    let config = match LayeredConfig::discover() {
+        Ok(config) => config,
+        Err(e) => {
+            reporter.error(&e, verbose_errors);
+            log_error(&e);
+            process::exit(1);
+        }
+    };
+
+    // Handle config command (before the files are applied, so sources can be told apart)
+    if let Command::Config { action } = &args.command {
+        if let Err(e) = handle_config(action, &config, &args, reporter) {
+            reporter.error(&e, verbose_errors);
+            log_error(&e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+    args.apply_config(&config.effective());
+
+    // Handle replay command (runs offline from the bundle)
+    if let Command::Replay { bundle, write } = &args.command {
+        if let Err(e) = handle_replay(bundle, *write, &args.get_output_dir(), OutputStyle::from_flag(args.plain)) {
+            reporter.error(&e, verbose_errors);
+            log_error(&e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Handle changelog command (runs offline from the manifest history)
+    if let Command::Changelog { last } = &args.command {
+        if let Err(e) = handle_changelog(*last, &args.get_output_dir()) {
+            reporter.error(&e, verbose_errors);
+            log_error(&e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Handle inspect-template command (parses a single file offline)
+    if let Command::InspectTemplate { file, format } = &args.command {
+        if let Err(e) = handle_inspect_template(file, json_or(reporter, *format)) {
+            reporter.error(&e, verbose_errors);
+            log_error(&e);
+            process::exit(1);
+        }
+        process::exit(0);
+    }
+
+    // Handle eject command (writes a built-in pack offline)
+    if let Command::Eject { pack, out, force, format } = &args.command {
+        if let Err(e) = handle_eject(*pack, out, *force, json_or(reporter, *format)) {
+            reporter.error(&e, verbose_errors);
+            log_error(&e);
+            process::exit(1);
+        }
         process::exit(0);
     }
 
     // Validate arguments for other commands
     if let Err(e) = args.validate() {
-        eprintln!("Error: {}", e.user_message());
+        reporter.error(&e, verbose_errors);
         log_error(&e);
         process::exit(1);
     }
 
     // Execute the command and handle the result
-    if let Err(e) = run(args).await {
-        eprintln!("Error: {}", e.user_message());
+    if let Err(e) = run(args, reporter).await {
+        reporter.error(&e, verbose_errors);
         log_error(&e);
         process::exit(1);
     }
@@ -50,22 +181,29 @@ async fn main() {
 }
 
 /// Main execution logic
-async fn run(args: CliArgs) -> Result<(), CliError> {
+async fn run(args: CliArgs, reporter: Reporter) -> Result<(), CliError> {
     // Get validated configuration
     let api_key = args.get_api_key()?;
     let api_url = args.get_api_url();
     let output_dir = args.get_output_dir();
+    let style = reporter.style();
 
     info!("IDP CLI - OpenTofu code generator");
     info!("API URL: {}", api_url);
     info!("Output directory: {}", output_dir.display());
 
     // Create API client
-    let api_client = ApiClient::new(api_url.to_string(), api_key.to_string());
+    let mut api_client = ApiClient::new(api_url.to_string(), api_key.to_string()).with_headers(&args.headers)?;
+    if let Some(caller) = &args.user_agent {
+        api_client = api_client.with_user_agent(caller);
+    }
+    if let Some(max_response_bytes) = args.max_response_bytes {
+        api_client = api_client.with_max_response_bytes(max_response_bytes);
+    }
 
     // Route to appropriate handler based on command
     match args.command {
-        Command::Generate { data_source, identifier, template_dir, variables_file } => {
+        Command::Generate { data_source, identifier, template_dir, variables, options } => {
             info!("Generating code from {}: {}", 
                 match data_source {
                     DataSource::Blueprint => "blueprint",
@@ -73,16 +211,36 @@ async fn run(args: CliArgs) -> Result<(), CliError> {
                 },
                 identifier
             );
+            // Templates from S3 live in a temporary directory for the rest of the run
+            let (template_dir, _downloaded) = template_dir_for(template_dir, &options).await?;
             handle_generate(
                 data_source,
                 &identifier,
                 &template_dir,
-                variables_file.as_deref(),
+                &variables,
+                &options,
                 &api_client,
                 &output_dir,
+                reporter,
             ).await?;
         }
-        Command::ListVariables { data_source, identifier } => {
+        Command::Compose { blueprints, template_dir, variables, mut options } => {
+            info!("Composing blueprints: {}", blueprints.join(", "));
+            let (template_dir, _downloaded) = template_dir_for(template_dir, &options).await?;
+            let identifier = blueprints.join("+");
+            options.composed_blueprints = blueprints;
+            handle_generate(
+                DataSource::Blueprint,
+                &identifier,
+                &template_dir,
+                &variables,
+                &options,
+                &api_client,
+                &output_dir,
+                reporter,
+            ).await?;
+        }
+        Command::ListVariables { data_source, identifier, variables, options } => {
             info!("Listing variables from {}: {}", 
                 match data_source {
                     DataSource::Blueprint => "blueprint",
@@ -93,12 +251,112 @@ async fn run(args: CliArgs) -> Result<(), CliError> {
             handle_list_variables(
                 data_source,
                 &identifier,
+                &variables,
+                &options,
                 &api_client,
+                reporter,
             ).await?;
         }
-        Command::Version => {
+        Command::Describe { data_source, identifier, format } => {
+            info!("Describing {}: {}", data_source, identifier);
+            handle_describe(data_source, &identifier, json_or(reporter, format), &api_client).await?;
+        }
+        Command::Preview { data_source, identifier, revision, against, template_dir, variables } => {
+            info!("Previewing {} {} revision {}", data_source, identifier, revision);
+            let template_dir = template_dir
+                .ok_or_else(|| CliError::ConfigurationError("--template-dir is required".to_string()))?;
+            handle_preview(
+                data_source,
+                &identifier,
+                &revision,
+                against.as_deref(),
+                &template_dir,
+                &variables,
+                &api_client,
+            )
+            .await?;
+        }
+        Command::CheckFreshness { format } => {
+            handle_check_freshness(json_or(reporter, format), &api_client, &output_dir).await?;
+        }
+        Command::Search { data_source, provider, resource_type, name_contains, format } => {
+            let criteria = SearchCriteria { provider, resource_type, name_contains };
+            handle_search(data_source, &criteria, json_or(reporter, format), &api_client).await?;
+        }
+        Command::ListBlueprints { mut options } => {
+            if reporter.is_json() {
+                options.format = ListFormat::Json;
+            }
+            handle_list_blueprints(&options, &api_client, style).await?;
+        }
+        Command::ListStacks { mut options } => {
+            if reporter.is_json() {
+                options.format = ListFormat::Json;
+            }
+            handle_list_stacks(&options, &api_client, style).await?;
+        }
+        Command::Stack { action } => {
+            handle_stack(&action, reporter, &api_client, style).await?;
+        }
+        Command::TraceVar { data_source, identifier, variable, template_dir, variables, format } => {
+            info!("Tracing variable {} in {}: {}", variable, data_source, identifier);
+            handle_trace_var(
+                data_source,
+                &identifier,
+                &variable,
+                template_dir.as_deref(),
+                &variables,
+                json_or(reporter, format),
+                &api_client,
+            )
+            .await?;
+        }
+        Command::LintTemplates { data_source, identifier, template_dir, variables, helpers_file, format, report } => {
+            info!("Linting templates against {}: {}", data_source, identifier);
+            let template_dir = template_dir.expect("validated: --template-dir is required");
+            handle_lint_templates(
+                data_source,
+                &identifier,
+                &template_dir,
+                &variables,
+                helpers_file.as_deref(),
+                json_or(reporter, format),
+                &report,
+                &api_client,
+            )
+            .await?;
+        }
+        Command::Repl { blueprint, stack, template_dir, variables, helpers_file } => {
+            let (data_source, identifier) = match (blueprint, stack) {
+                (Some(blueprint), _) => (DataSource::Blueprint, blueprint),
+                (None, Some(stack)) => (DataSource::Stack, stack),
+                (None, None) => unreachable!("clap requires --blueprint or --stack"),
+            };
+            handle_repl(
+                data_source,
+                &identifier,
+                template_dir.as_deref(),
+                &variables,
+                helpers_file.as_deref(),
+                &api_client,
+            )
+            .await?;
+        }
+        Command::Serve { options } => {
+            handle_serve(&options, api_client).await?;
+        }
+        Command::Version
+        | Command::Replay { .. }
+        | Command::Changelog { .. }
+        | Command::InspectTemplate { .. }
+        | Command::Eject { .. }
+        | Command::Config { .. } => {
             // Already handled above, but included for completeness
-            unreachable!("Version command should have been handled earlier");
+            unreachable!("Version, replay, changelog, inspect-template, eject and config commands should have been handled earlier");
+        }
+        #[cfg(feature = "bench")]
+        Command::Bench { .. } => {
+            unreachable!("Bench command should have been handled earlier");
         }
     }
 
@@ -111,66 +369,483 @@ async fn run(args: CliArgs) -> Result<(), CliError> {
 /// 1. Fetches blueprint or stack data from the IDP API
 /// 2. Builds a variable context from the API response
 /// 3. Merges custom variables if a variables file is provided
-/// 4. (Future tasks will add template discovery and processing)
+/// 4. Discovers, renders and writes templates
+/// 5. Writes run metrics to run-summary.json (or `--summary-file`)
 /// 
 /// # Arguments
 /// * `data_source` - Whether to use a blueprint or stack as the data source
 /// * `identifier` - The name or UUID of the blueprint/stack
 /// * `template_dir` - Path to the directory containing template files
-/// * `variables_file` - Optional path to a JSON/YAML file with custom variables
+/// * `variables` - Variables files and `--var` assignments merged over the API data
+/// * `options` - Additional generate options (output partitioning, summary file)
 /// * `api_client` - The API client for fetching data
 /// * `output_dir` - The directory where generated files will be written
+/// * `reporter` - Human (decorated or `--plain`) or JSON (`--output json`) output
 /// 
 /// # Returns
 /// * `Ok(())` if generation succeeds
 /// * `Err(CliError)` if any step fails
+#[allow(clippy::too_many_arguments)]
 async fn handle_generate(
     data_source: DataSource,
     identifier: &str,
     template_dir: &std::path::Path,
-    variables_file: Option<&std::path::Path>,
+    variables: &VariableInputs,
+    options: &GenerateOptions,
+    api_client: &ApiClient,
+    output_dir: &std::path::Path,
+    reporter: Reporter,
+) -> Result<(), CliError> {
+    let style = reporter.style();
+    // Refuse before anything, including the run summary, is written
+    if !options.is_preview() && !options.force {
+        file_writer::FileWriter::new(output_dir.to_path_buf()).check_output_dir()?;
+    }
+    // A retry of a completed run returns without touching the outputs
+    // A dry run neither skips on nor records the key
+    let idempotency = match &options.idempotency_key {
+        Some(key) if !options.is_preview() => {
+            let key = idempotency::validate_key(key)?;
+            let request = generate_request_hash(data_source, identifier, template_dir, variables, options)?;
+            let store = LocalStore::new(output_dir);
+            if let Some(record) = store.load(key)? {
+                if record.request != request {
+                    return Err(CliError::ConfigurationError(format!(
+                        "Idempotency key '{}' was already used for a different request",
+                        key
+                    )));
+                }
+                if reporter.is_json() {
+                    reporter.json(&serde_json::json!({
+                        "skipped": true,
+                        "idempotency_key": key,
+                        "files_written": record.files_written,
+                    }))?;
+                } else {
+                    println!(
+                        "{}",
+                        style.success(&format!(
+                            "Already generated with idempotency key '{}' ({} file(s)); outputs left unchanged",
+                            key, record.files_written
+                        ))
+                    );
+                }
+                return Ok(());
+            }
+            Some((store, key, request))
+        }
+        _ => None,
+    };
+
+    let command = if options.composed_blueprints.is_empty() { "generate" } else { "compose" };
+    let mut summary = RunSummary::start(command, &data_source.to_string(), identifier);
+    let mut snapshot = debug_bundle::Snapshot::default();
+    let findings = Findings::default();
+    let progress = ProgressReporter::from_flag(options.progress_json);
+    progress.emit(&ProgressEvent::Started {
+        data_source: data_source.to_string(),
+        identifier: identifier.to_string(),
+    });
+    let result = run_generate_pipeline(
+        data_source,
+        identifier,
+        template_dir,
+        variables,
+        options,
+        api_client,
+        output_dir,
+        &mut summary,
+        &mut snapshot,
+        &findings,
+        &progress,
+        reporter,
+    ).await;
+    summary.finish(&result);
+    progress.emit(&ProgressEvent::Finished {
+        success: summary.success,
+        files_written: summary.files_written,
+        duration_ms: summary.total_duration_ms,
+        error: summary.error.clone(),
+    });
+
+    // The summary is written even when generation fails
+    let summary_path = options
+        .summary_file
+        .clone()
+        .unwrap_or_else(|| output_dir.join(RUN_SUMMARY_FILE));
+    // A dry run leaves the output directory untouched
+    if !options.is_preview() {
+        match summary.write_to(&summary_path) {
+            Ok(()) => {
+                if result.is_ok() {
+                    reporter.line(format!("Run summary ({}):", summary_path.display()));
+                    reporter.line(format!("{}\n", summary.render_table()));
+                }
+            }
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+
+    // Findings are most useful when generation fails, so they are reported either way
+    if let Err(e) = write_reports(&options.report, command, &findings) {
+        eprintln!("Warning: {}", e);
+    }
+
+    // Inside GitHub Actions the results also go to the job summary and step outputs
+    if let Some(github) = GithubActions::from_env() {
+        if let Err(e) = github.report(&summary) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    // Drift reported by --diff, quota breaches and policy violations are not failures worth a bundle
+    let failed = result.as_ref().err().filter(|e| {
+        !matches!(
+            e,
+            CliError::StaleOutputError(_) | CliError::QuotaExceededError(_) | CliError::PolicyViolationError(_)
+        )
+    });
+    if let (Some(error), Some(target)) = (failed, &options.debug_bundle) {
+        let manifest = debug_bundle::BundleManifest::new(&data_source.to_string(), identifier, template_dir, error);
+        match debug_bundle::save_bundle(&snapshot, manifest, target).await {
+            Ok(location) => eprintln!("Debug bundle written to {} (replay with: idp-cli replay <bundle>)", location),
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+
+    if let (Ok(()), Some((store, key, request))) = (&result, &idempotency) {
+        if let Err(e) = store.save(&LocalRecord::new(key, request, summary.files_written)) {
+            eprintln!("Warning: {}", e);
+        }
+    }
+
+    if result.is_ok() && reporter.is_json() {
+        reporter.json(&summary)?;
+    }
+    result
+}
+
+/// Hash of everything that identifies a generate request for idempotency checks
+///
+/// Covers every option that changes what is written or whether the run
+/// succeeds, including the contents of the variables, helpers and quotas
+/// files and of the policy and schema directories, so a retry with different
+/// settings is refused instead of skipped.
+fn generate_request_hash(
+    data_source: DataSource,
+    identifier: &str,
+    template_dir: &std::path::Path,
+    variables: &VariableInputs,
+    options: &GenerateOptions,
+) -> Result<String, CliError> {
+    // Downloaded and built-in templates land in a different temporary directory on every run
+    let templates = template_origin(template_dir, options);
+    let file_hash = |path: Option<&std::path::Path>| -> Result<Option<String>, CliError> {
+        path.map(|path| {
+            std::fs::read(path)
+                .map(|content| idempotency::request_hash(&content))
+                .map_err(|e| CliError::VariableFileError(format!("Failed to read {}: {}", path.display(), e)))
+        })
+        .transpose()
+    };
+    let dir_hash = |dir: Option<&std::path::Path>| -> Result<Option<String>, CliError> {
+        dir.map(|dir| {
+            let read_error = |e: &dyn std::fmt::Display| {
+                CliError::ConfigurationError(format!("Failed to read {}: {}", dir.display(), e))
+            };
+            let files = template_discovery::TemplateDiscovery::new(dir.to_path_buf())
+                .with_extra_types(["rego"])
+                .discover_templates()
+                .map_err(|e| read_error(&e))?;
+            template_discovery::templates_digest(&files).map_err(|e| read_error(&e))
+        })
+        .transpose()
+    };
+    let request = serde_json::json!({
+        "data_source": data_source.to_string(),
+        "identifier": identifier,
+        "templates": templates,
+        "partition_by": options.partition_by,
+        "revision": options.revision,
+        "environment": options.environment,
+        "backend": options.backend.map(backend_config::BackendKind::name),
+        "backend_config": options.backend_config,
+        "var_files": variables
+            .var_files
+            .iter()
+            .map(|path| file_hash(Some(path)))
+            .collect::<Result<Vec<_>, _>>()?,
+        "vars": variables.vars,
+        "helpers_file": file_hash(options.helpers_file.as_deref())?,
+        "default_variables": options.default_variables,
+        "composed_blueprints": options.composed_blueprints,
+        "validators": options.validators,
+        "allow_env": options.allow_env,
+        "allow_file": options.allow_file,
+        "on_conflict": format!("{:?}", options.on_conflict),
+        "on_existing": format!("{:?}", options.on_existing),
+        "target": format!("{:?}", options.target),
+        "debug_render": options.debug_render,
+        "strict_variables": options.strict_variables,
+        "upload_to": options.upload_to.as_ref().map(ToString::to_string),
+        "upload_kms_key": options.upload_kms_key,
+        "quotas_file": file_hash(options.quotas_file.as_deref())?,
+        "policy_dir": dir_hash(options.policy_dir.as_deref())?,
+        "schema_dir": dir_hash(options.schema_dir.as_deref())?,
+        "post_hooks": options.post_hook,
+        "hooks": options.hooks,
+        "strict_hooks": options.strict_hooks,
+        "require_state": options.require_state,
+        "on_state_mismatch": format!("{:?}", options.on_state_mismatch),
+    });
+    Ok(idempotency::request_hash(request.to_string().as_bytes()))
+}
+
+/// Template directory for generate and compose
+///
+/// Templates from `--template-source` are downloaded, and those of
+/// `--builtin-templates` extracted, into a temporary directory, returned
+/// alongside so it outlives the run.
+async fn template_dir_for(
+    template_dir: Option<std::path::PathBuf>,
+    options: &GenerateOptions,
+) -> Result<(std::path::PathBuf, Option<tempfile::TempDir>), CliError> {
+    match (&options.template_source, options.builtin_templates, template_dir) {
+        (Some(source), _, _) => {
+            let downloaded = download_template_source(source).await?;
+            Ok((downloaded.path().to_path_buf(), Some(downloaded)))
+        }
+        (None, Some(pack), _) => {
+            let dir = tempfile::TempDir::new().map_err(|e| {
+                CliError::IoError(format!("Failed to create temporary template directory: {}", e))
+            })?;
+            pack.extract(dir.path())?;
+            info!("Using the built-in {} template pack", pack.name());
+            Ok((dir.path().to_path_buf(), Some(dir)))
+        }
+        (None, None, Some(dir)) => Ok((dir, None)),
+        (None, None, None) => Err(CliError::ConfigurationError(
+            "One of --template-dir, --template-source or --builtin-templates is required".to_string(),
+        )),
+    }
+}
+
+/// Where the templates of a run come from, for the request hash and the manifest
+fn template_origin(template_dir: &std::path::Path, options: &GenerateOptions) -> String {
+    match (&options.template_source, options.builtin_templates) {
+        (Some(source), _) => source.to_string(),
+        (None, Some(pack)) => pack.to_string(),
+        (None, None) => template_dir.display().to_string(),
+    }
+}
+
+/// Download the template tree under an S3 prefix into a temporary directory
+async fn download_template_source(source: &S3Location) -> Result<tempfile::TempDir, CliError> {
+    let dir = tempfile::TempDir::new().map_err(|e| {
+        CliError::IoError(format!("Failed to create temporary template directory: {}", e))
+    })?;
+    let client = S3Client::new(source.bucket.clone(), None, None).await?;
+    let files = client.download_templates(&source.key_prefix(), dir.path()).await?;
+    if files.is_empty() {
+        return Err(CliError::DiscoveryError(format!("No templates found at {}", source)));
+    }
+    info!("Downloaded {} template file(s) from {}", files.len(), source);
+    Ok(dir)
+}
+
+/// Run the generate pipeline, recording metrics in `summary`
+#[allow(clippy::too_many_arguments)]
+async fn run_generate_pipeline(
+    data_source: DataSource,
+    identifier: &str,
+    template_dir: &std::path::Path,
+    variables: &VariableInputs,
+    options: &GenerateOptions,
     api_client: &ApiClient,
     output_dir: &std::path::Path,
+    summary: &mut RunSummary,
+    snapshot: &mut debug_bundle::Snapshot,
+    findings: &Findings,
+    progress: &ProgressReporter,
+    reporter: Reporter,
 ) -> Result<(), CliError> {
+    let style = reporter.style();
+    // Load next-steps guidance up front so a bad guidance file fails before any work
+    let guidance = GuidanceConfig::load(options.guidance_file.as_deref())?;
+    let quotas = options.quotas_file.as_deref().map(QuotaConfig::load).transpose()?;
+    let mut policies = options.policy_dir.as_deref().map(PolicySet::load).transpose()?;
+    let hooks = PostHook::resolve(&options.post_hook, &options.hooks)?;
+    // Custom helpers too, so a broken helper definition fails before any API calls
+    let helpers = helper_plugins::HelperSet::for_templates(template_dir, options.helpers_file.as_deref())?;
+    if !helpers.is_empty() {
+        info!("Loaded custom helpers: {}", helpers.names().join(", "));
+    }
+    let mut validators = ValidatorRegistry::from_config(&options.validators)?;
+    if let Some(schema_dir) = &options.schema_dir {
+        validators = validators.with_schema_dir(schema_dir)?;
+    }
+    if let Some(warning) = file_writer::FileWriter::new(output_dir.to_path_buf()).nested_output_warning(template_dir) {
+        emit_warnings(progress, summary, [warning]);
+    }
+
     // Step 1: Fetch blueprint or stack data based on data_source
     info!("Fetching {} data from API...", match data_source {
         DataSource::Blueprint => "blueprint",
         DataSource::Stack => "stack",
     });
 
-    let mut context = match data_source {
+    let (identifier, revision) = options.pinned_identifier(identifier)?;
+    if let Some(revision) = revision {
+        info!("Pinned to revision {}", revision);
+    }
+    let revision_param = revision.map(|r| r.to_string());
+
+    let stage = Instant::now();
+    let (mut context, mut manifest) = match data_source {
+        // compose: several blueprints merged into one context
+        DataSource::Blueprint if !options.composed_blueprints.is_empty() => {
+            let pinned = options
+                .composed_blueprints
+                .iter()
+                .map(|identifier| {
+                    let (identifier, revision) = options.pinned_identifier(identifier)?;
+                    Ok((identifier, revision.map(|r| r.to_string())))
+                })
+                .collect::<Result<Vec<_>, CliError>>()?;
+            let requests: Vec<_> = pinned.iter().map(|(identifier, revision)| (*identifier, revision.as_deref())).collect();
+            // Fetched concurrently, so the call is recorded once for the whole set
+            let started = Instant::now();
+            let blueprints = api_client.get_blueprints_at(&requests).await?;
+            summary.record_api_call(started.elapsed());
+            for blueprint in &blueprints {
+                info!("Successfully retrieved blueprint: {}", blueprint.name);
+            }
+
+            info!("Composing variable context from {} blueprints...", blueprints.len());
+            let composition = composition::compose(&blueprints)?;
+            for (name, provider) in &composition.providers {
+                info!("Provider {}: {}", name, provider.version.as_deref().unwrap_or("any version"));
+            }
+            emit_warnings(progress, summary, composition.warnings);
+            (composition.context, OutputManifest::from_composition(&blueprints))
+        }
         DataSource::Blueprint => {
             // Fetch blueprint from API
-            let blueprint = api_client.get_blueprint(identifier).await?;
+            let started = Instant::now();
+            let blueprint = api_client.get_blueprint_at(identifier, revision_param.as_deref()).await?;
+            summary.record_api_call(started.elapsed());
             info!("Successfully retrieved blueprint: {}", blueprint.name);
             
             // Build variable context from blueprint
             info!("Building variable context from blueprint...");
-            VariableContextBuilder::from_blueprint(&blueprint)
+            (VariableContextBuilder::from_blueprint(&blueprint), OutputManifest::from_blueprint(&blueprint))
         }
         DataSource::Stack => {
-            // Fetch stack from API
-            let stack = api_client.get_stack(identifier).await?;
+            // Fetch stack from API, along with its state when one is required
+            let started = Instant::now();
+            let (stack, status) = if options.require_state.is_empty() {
+                (api_client.get_stack_at(identifier, revision_param.as_deref()).await?, None)
+            } else {
+                let (stack, status) = api_client.get_stack_with_status(identifier, revision_param.as_deref()).await?;
+                (stack, Some(status))
+            };
+            summary.record_api_call(started.elapsed());
             info!("Successfully retrieved stack: {}", stack.name);
+            if let Some(status) = status {
+                if let Some(mismatch) = stack_state::state_mismatch(&stack.name, &status, &options.require_state) {
+                    match options.on_state_mismatch {
+                        StateMismatchPolicy::Error => return Err(CliError::StackStateError(mismatch)),
+                        StateMismatchPolicy::Warn => emit_warnings(progress, summary, [mismatch]),
+                    }
+                }
+            }
             
             // Build variable context from stack
             info!("Building variable context from stack...");
-            VariableContextBuilder::from_stack(&stack)
+            (VariableContextBuilder::from_stack(&stack), OutputManifest::from_stack(&stack))
         }
     };
+    // A pin the API didn't confirm is recorded as requested, not as the source revision
+    if let (Some(revision), None) = (revision, manifest.revision) {
+        emit_warnings(progress, summary, [format!(
+            "The API did not report a revision for '{}'; revision {} was requested but can't be confirmed",
+            identifier, revision
+        )]);
+        manifest.requested_revision = Some(revision);
+    }
 
     info!("Variable context built with {} variables", context.len());
+    summary.record_stage("fetch", stage.elapsed());
+    progress.emit(&ProgressEvent::ContextBuilt { variables: context.len() });
 
-    // Step 2: Merge custom variables if variables_file is provided
-    if let Some(vars_file) = variables_file {
-        info!("Loading custom variables from {}...", vars_file.display());
-        VariableContextBuilder::merge_custom_variables(&mut context, vars_file)?;
+    // Step 2: Fill in default variables from the config files, then merge
+    // the variables files in order and the --var assignments
+    let base_context = (!options.default_variables.is_empty() || !variables.is_empty()).then(|| context.clone());
+    if !options.default_variables.is_empty() {
+        VariableContextBuilder::merge_defaults(&mut context, &options.default_variables);
+    }
+    if !variables.is_empty() {
+        info!("Merging {} variables file(s) and {} --var assignment(s)...", variables.var_files.len(), variables.vars.len());
+        variables.merge_into(&mut context)?;
         info!("Custom variables merged successfully");
     }
+    if options.debug_bundle.is_some() {
+        snapshot.context = Some(context.clone());
+    }
+    manifest.record_variables(&context);
+    if let Some(base_context) = &base_context {
+        manifest.record_overrides(base_context, &context);
+    }
+
+    // Catch region typos now instead of at plan/apply time
+    if !options.skip_region_check {
+        let issues = region_check::check_regions(&context);
+        if !issues.is_empty() {
+            let issues: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+            return Err(CliError::InvalidRegionError(issues.join("; ")));
+        }
+    }
+
+    // Partition output by a variable value if requested
+    let partition = match &options.partition_by {
+        Some(path) => {
+            let value = context.get_scalar_string(path).unwrap_or_else(|| {
+                let warning = format!("Partition variable '{}' is not set; using 'unassigned'", path);
+                emit_warnings(progress, summary, [warning]);
+                String::new()
+            });
+            Some((path.as_str(), file_writer::sanitize_path_segment(&value)))
+        }
+        None => None,
+    };
+    let output_dir = match &partition {
+        Some((_, dir_name)) => output_dir.join(dir_name),
+        None => output_dir.to_path_buf(),
+    };
+
+    // Warn when the existing output was generated from an older revision
+    let previous = OutputManifest::load(&output_dir)?;
+    if let Some(previous) = previous.as_ref().filter(|previous| previous.same_source(&manifest)) {
+        if let Some(warning) = previous.freshness(manifest.updated_at.as_deref()).warning(previous, &output_dir) {
+            emit_warnings(progress, summary, [warning]);
+        }
+    }
 
     // Step 3: Discover templates in template_dir
     info!("Discovering templates in {}...", template_dir.display());
-    let discovery = template_discovery::TemplateDiscovery::new(template_dir.to_path_buf());
+    let stage = Instant::now();
+    // Files with a configured validator (e.g. `sh`) are templates too
+    let discovery = template_discovery::TemplateDiscovery::new(template_dir.to_path_buf())
+        .with_extra_types(validators.keys())
+        .with_environment(options.environment.as_deref());
+    if let Some(environment) = &options.environment {
+        // The base templates are rendered as is; say so in case the name is a typo
+        let overlay_dir = template_dir.join(template_discovery::OVERLAYS_DIR).join(environment);
+        if !overlay_dir.is_dir() {
+            let warning = format!("No overlays for environment '{}' in {}", environment, overlay_dir.display());
+            emit_warnings(progress, summary, [warning]);
+        }
+    }
     let template_files = discovery.discover_templates().map_err(|e| {
         CliError::DiscoveryError(format!(
             "Failed to discover templates in '{}': {}",
@@ -186,61 +861,631 @@ async fn handle_generate(
         )));
     }
     
+    // Select template sets based on the stack type
+    let registry = generation_strategy::StrategyRegistry::default();
+    let strategy = registry.select_for_context(&context);
+    info!("Using '{}' generation strategy", strategy.name());
+    // Several templates writing one file would otherwise be last-writer-wins
+    let mut conflicts = Vec::new();
+    for conflict in registry.conflicts(strategy, &template_files) {
+        if conflict.resolved_by_precedence && options.on_conflict == ConflictPolicy::Precedence {
+            emit_warnings(progress, summary, [conflict.to_string()]);
+        } else {
+            conflicts.push(conflict.to_string());
+        }
+    }
+    if !conflicts.is_empty() {
+        return Err(CliError::OutputConflictError(conflicts.join("; ")));
+    }
+    let template_files = registry.apply(strategy, template_files);
+    let template_files = match options.target {
+        GenerationTarget::Helm => helm_generator::chart_templates(template_files),
+        GenerationTarget::Raw
+        | GenerationTarget::Crossplane
+        | GenerationTarget::PulumiYaml
+        | GenerationTarget::CdktfTypescript
+        | GenerationTarget::CdktfPython => template_files,
+    };
+    if options.debug_bundle.is_some() {
+        snapshot.templates = template_files.clone();
+        snapshot.strategy = Some(strategy.name().to_string());
+    }
+
+    if template_files.is_empty() {
+        return Err(CliError::DiscoveryError(format!(
+            "No template files in '{}' apply to the '{}' generation strategy (template sets: {})",
+            template_dir.display(),
+            strategy.name(),
+            strategy.template_sets().join(", ")
+        )));
+    }
+
+    manifest.record_templates(template_origin(template_dir, options), &template_files)?;
+
+    summary.record_stage("discover", stage.elapsed());
+    summary.templates_discovered = template_files.len();
+    progress.emit(&ProgressEvent::TemplatesDiscovered {
+        count: template_files.len(),
+        strategy: strategy.name().to_string(),
+    });
     info!("Discovered {} template file(s)", template_files.len());
     for template_file in &template_files {
         info!("  - {}", template_file.relative_path.display());
     }
 
-    // Step 4: Create TemplateProcessor and process each template file
+    // Step 4: Render templates and stream each processed file to the writer
+    // as soon as it completes, so only a few outputs are held in memory
     info!("Processing templates with variable substitution...");
-    let processor = template_processor::TemplateProcessor::new(&context);
-    
-    let mut processed_files = Vec::new();
-    for template_file in &template_files {
-        info!("Processing {}...", template_file.relative_path.display());
-        let processed_file = processor.process_file(template_file)?;
-        processed_files.push(processed_file);
+    info!("Writing processed files to {}...", output_dir.display());
+    let processor = template_processor::TemplateProcessor::new(&context)
+        .with_debug_render(options.debug_render)
+        .with_strict_variables(options.strict_variables)
+        .with_sandbox(options.sandbox_policy())
+        .with_validators(validators)
+        .with_findings(findings.clone())
+        .with_helpers(&helpers)?;
+    let mut file_writer = file_writer::FileWriter::new(output_dir.clone()).with_existing_policy(options.on_existing);
+    if progress.is_enabled() {
+        // Overwrites are reported as warning events instead
+        file_writer = file_writer.without_overwrite_warnings();
     }
-    
-    info!("Successfully processed {} template file(s)", processed_files.len());
+    if !options.is_preview() {
+        // Files are staged and moved into place together once every one is written
+        file_writer = file_writer.with_staging();
+    }
+    // backend.tf goes through the same path checks and writer as the rendered files
+    let mut generated_files = match options.backend {
+        Some(kind) => vec![backend_config::generate_backend_tf(kind, &options.backend_config, &processor)?],
+        None => Vec::new(),
+    };
+    match options.target {
+        GenerationTarget::Raw => {}
+        GenerationTarget::Helm => generated_files.extend(helm_generator::chart_files(&context)?),
+        GenerationTarget::Crossplane => {
+            let (files, warnings) = crossplane_generator::crossplane_files(&context)?;
+            emit_warnings(progress, summary, warnings);
+            generated_files.extend(files);
+        }
+        GenerationTarget::PulumiYaml => {
+            let (program, warnings) = pulumi_generator::pulumi_program(&context)?;
+            emit_warnings(progress, summary, warnings);
+            generated_files.push(program);
+        }
+        GenerationTarget::CdktfTypescript | GenerationTarget::CdktfPython => {
+            let language = match options.target {
+                GenerationTarget::CdktfPython => CdktfLanguage::Python,
+                _ => CdktfLanguage::TypeScript,
+            };
+            let (files, warnings) = cdktf_generator::cdktf_files(&context, language)?;
+            emit_warnings(progress, summary, warnings);
+            generated_files.extend(files);
+        }
+    }
+    let upload_client = match options.upload_to.as_ref().filter(|_| !options.is_preview()) {
+        Some(location) => Some(S3Client::new(String::new(), Some(location.bucket.clone()), None).await?),
+        None => None,
+    };
+    let sink = upload_client.as_ref().zip(options.upload_to.clone()).map(|(client, location)| {
+        file_writer::S3Sink::new(client, location).with_kms_key(options.upload_kms_key.clone())
+    });
+    let (sender, mut receiver) = pipeline::file_channel(pipeline::DEFAULT_CHANNEL_CAPACITY);
+    let mut quota_tally = quotas.as_ref().map(QuotaTally::new);
 
-    // Step 5: Write processed files to output directory
-    info!("Writing processed files to {}...", output_dir.display());
-    let file_writer = file_writer::FileWriter::new(output_dir.to_path_buf());
-    
-    let written_files = file_writer.write_processed_files(&processed_files)
-        .map_err(|e| {
-            CliError::IoError(format!("Failed to write processed files: {}", e))
-        })?;
-    
+    let (rendered, consumed) = tokio::join!(
+        pipeline::render_to_channel_with(
+            &processor,
+            &template_files,
+            generated_files,
+            sender,
+            options.concurrency(),
+        ),
+        async {
+            // Step 5: Write processed files to output directory
+            let mut written_files = Vec::new();
+            let mut previews = Vec::new();
+            let mut write_time = Duration::ZERO;
+            let mut write = |processed_file: template_processor::ProcessedFile| -> Result<(), CliError> {
+                info!("Writing {}...", processed_file.relative_path.display());
+                let started = Instant::now();
+                let outcome = file_writer.write_processed_file(&processed_file).map_err(|e| match e {
+                    CliError::ExistingFileError(_) => e,
+                    e => CliError::IoError(format!("Failed to write processed files: {}", e)),
+                })?;
+                let warning = match &outcome {
+                    file_writer::WriteOutcome::Created(_) => None,
+                    file_writer::WriteOutcome::Overwritten(path) => Some(format!("Overwrote existing file: {}", path.display())),
+                    file_writer::WriteOutcome::BackedUp { path, backup } => {
+                        Some(format!("Overwrote existing file: {} (backup: {})", path.display(), backup.display()))
+                    }
+                    file_writer::WriteOutcome::Skipped(path) => Some(format!("Kept existing file: {}", path.display())),
+                };
+                if let Some(warning) = warning {
+                    progress.emit(&ProgressEvent::Warning { message: warning.clone() });
+                    summary.warn(warning);
+                }
+                if let Some(written) = outcome.written() {
+                    progress.emit(&ProgressEvent::FileWritten { path: written.display().to_string() });
+                    written_files.push(written.to_path_buf());
+                    // Staged with the file, so a failed run leaves no annotated copy behind
+                    if let Some(annotated) = file_writer.write_annotated_file(&processed_file)? {
+                        info!("Wrote annotated copy to {}", annotated.display());
+                    }
+                }
+                write_time += started.elapsed();
+                Ok(())
+            };
+
+            let mut violations = Vec::new();
+            while let Some(processed_file) = receiver.recv().await {
+                progress.emit(&ProgressEvent::FileRendered {
+                    path: processed_file.path_str(),
+                    bytes: processed_file.content_len(),
+                });
+                if let Some(tally) = &mut quota_tally {
+                    tally.add_file(&processed_file.relative_path, &processed_file.content);
+                }
+                if let Some(policies) = &mut policies {
+                    let found = policies.check_file(&processed_file.relative_path, &processed_file.content)?;
+                    findings.check(output_dir.join(&processed_file.relative_path));
+                    for violation in &found {
+                        findings.push(Finding::from_policy(violation, &output_dir));
+                    }
+                    violations.extend(found);
+                }
+                if options.is_preview() {
+                    previews.push(file_writer.preview(&processed_file)?);
+                } else {
+                    write(processed_file)?;
+                }
+            }
+            Ok::<_, CliError>((written_files, previews, write_time, violations))
+        }
+    );
+    let (stats, (written_files, previews, write_time, violations)) = pipeline::join_results(rendered, consumed)?;
+
+    // Quotas are checked before the output is applied, so breaches don't wait for apply time
+    if let Some(tally) = &quota_tally {
+        let breaches = tally.breaches();
+        let warnings = breaches.iter().filter(|b| b.severity == QuotaSeverity::Warning).map(|b| b.to_string());
+        emit_warnings(progress, summary, warnings);
+        quota_errors(tally)?;
+    }
+    let warnings = violations.iter().filter(|v| v.severity == PolicySeverity::Warning).map(|v| v.to_string());
+    emit_warnings(progress, summary, warnings);
+    policy_errors(&violations)?;
+
+    // Files are only staged until here, so a breach or denial above leaves the
+    // output directory as it was: dropping the writer discards them
+    file_writer.commit()?;
+
+    // Variables no template uses, and references that rendered as empty strings
+    summary.variables = Some(VariableUsage::build(&context, &template_files, &helpers.names())?);
+
+    if options.is_preview() {
+        summary.files = previews
+            .iter()
+            .map(|p| FileRecord { path: p.path.display().to_string(), change: Some(p.change) })
+            .collect();
+        summary.changes = Some(FileChanges::from_previews(&previews));
+    }
+    if options.diff {
+        summary.record_stage("render", stats.render_time);
+        // Only the patch goes to stdout so it can be piped to a file or `git apply`
+        if !reporter.is_json() {
+            print!("{}", file_writer::render_diff(&previews));
+        }
+        let changed = previews.iter().filter(|p| p.change != file_writer::FileChange::Unchanged).count();
+        if changed > 0 {
+            return Err(CliError::StaleOutputError(format!(
+                "{} of {} file(s) in {} differ from the rendered templates",
+                changed,
+                previews.len(),
+                output_dir.display()
+            )));
+        }
+        eprintln!("No differences: {} file(s) match the rendered templates", previews.len());
+        return Ok(());
+    }
+    if options.dry_run {
+        summary.record_stage("render", stats.render_time);
+        reporter.line(format!("\nDry run: {} file(s) rendered, nothing was written\n", previews.len()));
+        reporter.line(file_writer::render_previews(&previews, style));
+        return Ok(());
+    }
+
+    // Rendering and writing overlap, so each stage reports its own busy time
+    summary.record_stage("render", stats.render_time);
+    summary.record_stage("write", write_time);
+    summary.files_written = written_files.len();
+    summary.files = written_files
+        .iter()
+        .map(|path| FileRecord { path: path.display().to_string(), change: None })
+        .collect();
+    summary.bytes_written = stats.bytes_rendered;
+    info!("Successfully processed {} template file(s)", stats.files_rendered);
     info!("Successfully wrote {} file(s)", written_files.len());
 
+    // Hooks such as fmt may rewrite files, so they run before the checksums are recorded
+    let mut hook_failures = Vec::new();
+    if !hooks.is_empty() {
+        reporter.line("\nPost-generation hooks:");
+        for hook in &hooks {
+            info!("Running post-generation hook {}: {}", hook.name, hook.command_line());
+            let result = hook.run(&output_dir);
+            let label = format!("{}: {}", result.name, result.command);
+            reporter.line(if result.success { style.item_ok(&label) } else { style.item_failed(&label) });
+            for line in result.output.lines() {
+                reporter.line(format!("    {}", line));
+            }
+            hook_failures.extend(result.failure());
+            summary.hooks.push(result);
+        }
+        if !options.strict_hooks {
+            emit_warnings(progress, summary, std::mem::take(&mut hook_failures));
+        }
+    }
+
+    manifest.record_files(&output_dir, &written_files)?;
+    let manifest_path = manifest.write_to(&output_dir)?;
+    info!("Recorded source revision in {}", manifest_path.display());
+    summary.changes = Some(FileChanges::between(previous.as_ref(), &manifest));
+    summary.manifest_path = Some(manifest_path.display().to_string());
+    // The files are in place, so a failed hook is reported once the manifest matches them
+    if !hook_failures.is_empty() {
+        return Err(CliError::HookFailedError(hook_failures.join("; ")));
+    }
+    // Uploaded from disk once hooks have run, so S3 gets the same files the manifest records
+    if let (Some(mut sink), Some(location)) = (sink, &options.upload_to) {
+        for path in &written_files {
+            sink.upload_file(&output_dir, path).await?;
+        }
+        let uploaded = sink.finish().await?;
+        info!("Uploaded {} file(s) to {}", uploaded.len(), location);
+        reporter.line(format!("\nUploaded {} file(s) to {}", uploaded.len(), location));
+    }
+
+    // Step 6: Warn when installed tools are too old for the generated syntax
+    if !options.skip_tool_check {
+        let mut generated = Vec::new();
+        let mut unread = Vec::new();
+        for path in &written_files {
+            match std::fs::read_to_string(paths::long_path(path)) {
+                Ok(content) => generated.push((path.clone(), content)),
+                Err(e) => unread.push(format!("Tool version check skipped {}: {}", path.display(), e)),
+            }
+        }
+        emit_warnings(progress, summary, unread);
+        let requirements = tool_versions::scan_requirements(&generated);
+        summary.tool_checks = tool_versions::check_requirements(&requirements, tool_versions::detect_version);
+        let warnings: Vec<String> = summary.tool_checks.iter().filter_map(|check| check.warning()).collect();
+        emit_warnings(progress, summary, warnings);
+    }
+
     // Display success message with generated file paths
-    println!("\n✓ Successfully generated {} file(s) from templates", written_files.len());
-    println!("\nGenerated files:");
+    reporter.line(format!(
+        "\n{}",
+        style.success(&format!("Successfully generated {} file(s) from templates", written_files.len()))
+    ));
+    if let Some((path, dir_name)) = &partition {
+        reporter.line(format!("\nPartition ({}): {}", path, dir_name));
+    }
+    reporter.line("\nGenerated files:");
     for file_path in &written_files {
-        println!("  ✓ {}", file_path.display());
+        reporter.line(style.item_ok(&file_path.display().to_string()));
+    }
+    if options.debug_render {
+        reporter.line(format!("\nAnnotated copies: {}", output_dir.join(file_writer::DEBUG_RENDER_DIR).display()));
+    }
+    if let Some(usage) = &summary.variables {
+        reporter.line(format!("\n{}", usage.render_human()));
     }
     
     // Provide next steps guidance based on file types
-    println!("\n{}", get_next_steps_guidance(&written_files, template_dir));
+    reporter.line(format!("\n{}", guidance.render(&written_files, template_dir)));
 
     Ok(())
 }
 
 
 
-/// Handle list-variables command
-/// 
-/// This function implements the list-variables command workflow:
-/// 1. Fetches blueprint or stack data from the IDP API
-/// 2. Builds a variable context from the API response
-/// 3. Displays variables in a readable format with types and sample values
+/// Report warnings as progress events (or on stderr) and record them in the run summary
+fn emit_warnings(progress: &ProgressReporter, summary: &mut RunSummary, warnings: impl IntoIterator<Item = String>) {
+    for warning in warnings {
+        if progress.is_enabled() {
+            progress.emit(&ProgressEvent::Warning { message: warning.clone() });
+        } else {
+            eprintln!("Warning: {}", warning);
+        }
+        summary.warn(warning);
+    }
+}
+
+/// Fail when the output breaches a quota with `severity: error`
+fn quota_errors(tally: &QuotaTally) -> Result<(), CliError> {
+    let errors: Vec<String> = tally
+        .breaches()
+        .iter()
+        .filter(|b| b.severity == QuotaSeverity::Error)
+        .map(|b| b.to_string())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::QuotaExceededError(errors.join("; ")))
+    }
+}
+
+/// Fail when the output breaks a `deny` rule of a policy
+fn policy_errors(violations: &[PolicyViolation]) -> Result<(), CliError> {
+    let errors: Vec<String> = violations
+        .iter()
+        .filter(|v| v.severity == PolicySeverity::Error)
+        .map(|v| v.to_string())
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::PolicyViolationError(errors.join("; ")))
+    }
+}
+
+/// Handle replay command
 /// 
-/// # Arguments
+/// Renders every template in a debug bundle with the bundle's context and
+/// reports the ones that fail. Masked values render as `***REDACTED***`.
+fn handle_replay(
+    bundle_path: &std::path::Path,
+    write: bool,
+    output_dir: &std::path::Path,
+    style: OutputStyle,
+) -> Result<(), CliError> {
+    let bundle = debug_bundle::load_bundle(bundle_path)?;
+    let manifest = &bundle.manifest;
+    println!("Replaying {} {} (idp-cli {})", manifest.data_source, manifest.identifier, manifest.cli_version);
+    println!("Original error: {}", manifest.error);
+    if let Some(strategy) = &manifest.strategy {
+        println!("Strategy: {}", strategy);
+    }
+    println!("Masked values: {}\n", manifest.redacted_values);
+
+    if bundle.templates.is_empty() {
+        println!("The run failed before any templates were selected; nothing to render.");
+        return Ok(());
+    }
+
+    let processor = template_processor::TemplateProcessor::new(&bundle.context);
+    let writer = file_writer::FileWriter::new(output_dir.to_path_buf());
+    let mut failures = 0;
+    for template in &bundle.templates {
+        match processor.process_file_outputs(template) {
+            Ok(outputs) => {
+                println!("{}", style.item_ok(&template.relative_path.display().to_string()));
+                if write {
+                    for processed in &outputs {
+                        writer.write_processed_file(processed)?;
+                    }
+                }
+            }
+            Err(e) => {
+                failures += 1;
+                println!("{}\n{}", style.item_failed(&template.relative_path.display().to_string()), e.user_message());
+            }
+        }
+    }
+
+    if write {
+        println!("\nRendered files written to {}", output_dir.display());
+    }
+    if failures > 0 {
+        return Err(CliError::GenerationError(format!(
+            "{} of {} template(s) failed to render",
+            failures,
+            bundle.templates.len()
+        )));
+    }
+    println!("\nAll {} template(s) rendered successfully", bundle.templates.len());
+    Ok(())
+}
+
+/// Handle changelog command
+/// 
+/// Prints a markdown summary of what changed between the last `last`
+/// generations recorded in the output directory's manifest history.
+fn handle_changelog(last: usize, output_dir: &std::path::Path) -> Result<(), CliError> {
+    let history = OutputManifest::history(output_dir)?;
+    if history.is_empty() {
+        return Err(CliError::NotFoundError(format!(
+            "No generation history in {}; run generate first",
+            output_dir.display()
+        )));
+    }
+    let recent = &history[history.len().saturating_sub(last.max(2))..];
+    print!("{}", changelog::render_markdown(recent));
+    Ok(())
+}
+
+/// Handle config command
+///
+/// `show` lists each setting with the layer it came from; `set` writes one
+/// value to the project config file, or the global one with `--global`.
+///
+/// # Arguments
+/// * `action` - Show or set
+/// * `config` - The config files found from the current directory
+/// * `args` - Parsed arguments, before the config files were applied
+/// * `reporter` - Human or JSON output
+fn handle_config(
+    action: &ConfigAction,
+    config: &LayeredConfig,
+    args: &CliArgs,
+    reporter: Reporter,
+) -> Result<(), CliError> {
+    match action {
+        ConfigAction::Show => {
+            let settings = config_settings(config, args);
+            if reporter.is_json() {
+                let settings: Vec<_> = settings
+                    .iter()
+                    .map(|(key, value, source)| serde_json::json!({ "key": key, "value": value, "source": source.map(|s| s.to_string()) }))
+                    .collect();
+                return reporter.json(&serde_json::json!({
+                    "global_file": config.global.as_ref().map(|(path, _)| path),
+                    "project_file": config.project.as_ref().map(|(path, _)| path),
+                    "settings": settings,
+                }));
+            }
+            let file = |layer: &Option<(std::path::PathBuf, ConfigFile)>| {
+                layer.as_ref().map(|(path, _)| path.display().to_string()).unwrap_or_else(|| "(none)".to_string())
+            };
+            println!("Global config: {}", file(&config.global));
+            println!("Project config: {}\n", file(&config.project));
+            for (key, value, source) in settings {
+                match (value, source) {
+                    (Some(value), Some(source)) => println!("{} = {} ({})", key, value, source),
+                    _ => println!("{} is not set", key),
+                }
+            }
+            Ok(())
+        }
+        ConfigAction::Set { key, value, global } => {
+            let path = if *global {
+                config_file::global_path().ok_or_else(|| {
+                    CliError::ConfigurationError("Cannot find the home directory for --global".to_string())
+                })?
+            } else {
+                match &config.project {
+                    Some((path, _)) => path.clone(),
+                    None => std::env::current_dir()
+                        .map_err(|e| CliError::IoError(format!("Failed to read the current directory: {}", e)))?
+                        .join(config_file::CONFIG_FILE_NAME),
+                }
+            };
+            let mut file = ConfigFile::load(&path)?.unwrap_or_default();
+            file.set(key, value)?;
+            file.save(&path)?;
+            if key == "api_key" && !*global {
+                eprintln!("Warning: {} now holds an API key; keep it out of version control", path.display());
+            }
+            if reporter.is_json() {
+                reporter.json(&serde_json::json!({ "key": key, "file": path }))
+            } else {
+                println!("Set {} in {}", key, path.display());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Effective value and source of every setting, for `config show`
+fn config_settings(config: &LayeredConfig, args: &CliArgs) -> Vec<(String, Option<String>, Option<ConfigSource>)> {
+    let effective = config.effective();
+    // Flags and environment variables are both parsed into args; the environment
+    // is the source when it holds the same value
+    let from_args = |value: Option<String>, env: &str| {
+        value.map(|value| {
+            let source = if std::env::var(env).is_ok_and(|v| v == value) {
+                ConfigSource::Environment
+            } else {
+                ConfigSource::Flag
+            };
+            (value, source)
+        })
+    };
+    let display = |path: &Option<std::path::PathBuf>| path.as_ref().map(|p| p.display().to_string());
+    let layers = [
+        ("api_url", from_args(args.api_url.clone(), "IDP_API_URL"), Some("http://localhost:8082/api/v1")),
+        ("api_key", from_args(args.api_key.clone(), "IDP_API_KEY"), None),
+        ("output_dir", from_args(display(&args.output_dir), "IDP_OUTPUT_DIR"), Some("./output")),
+        ("template_dir", from_args(std::env::var("IDP_TEMPLATE_DIR").ok(), "IDP_TEMPLATE_DIR"), None),
+        ("user_agent", from_args(args.user_agent.clone(), "IDP_USER_AGENT"), None),
+    ];
+
+    let mut settings = Vec::new();
+    for (key, from_args, default) in layers {
+        let from_files = config.source_of(key).zip(effective.get(key)).map(|(source, value)| {
+            (value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string()), source)
+        });
+        let resolved = from_args
+            .or(from_files)
+            .or_else(|| default.map(|value| (value.to_string(), ConfigSource::Default)));
+        let (value, source) = match resolved {
+            Some((value, source)) if key == "api_key" => (Some(mask_api_key(&value)), Some(source)),
+            Some((value, source)) => (Some(value), Some(source)),
+            None => (None, None),
+        };
+        settings.push((key.to_string(), value, source));
+    }
+    for (name, value) in &effective.headers {
+        let key = format!("headers.{}", name);
+        if !args.headers.iter().any(|(flag, _)| flag.eq_ignore_ascii_case(name)) {
+            let source = config.source_of(&key);
+            settings.push((key, Some(value.clone()), source));
+        }
+    }
+    for (name, value) in &args.headers {
+        settings.push((format!("headers.{}", name), Some(value.clone()), Some(ConfigSource::Flag)));
+    }
+    for (name, value) in &effective.variables {
+        let key = format!("variables.{}", name);
+        let source = config.source_of(&key);
+        settings.push((key, Some(value.to_string()), source));
+    }
+    settings
+}
+
+/// Mask an API key, keeping the last four characters of long keys
+fn mask_api_key(key: &str) -> String {
+    match key.char_indices().rev().nth(3) {
+        Some((start, _)) if key.len() > 8 => format!("****{}", &key[start..]),
+        _ => "****".to_string(),
+    }
+}
+
+/// Handle serve command
+/// 
+/// Runs the configured listeners until the process is stopped.
+#[cfg_attr(not(any(feature = "grpc", feature = "http")), allow(unused_variables))]
+async fn handle_serve(options: &ServeOptions, api_client: ApiClient) -> Result<(), CliError> {
+    let mut servers = tokio::task::JoinSet::new();
+
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = options.grpc {
+        servers.spawn(idp_cli::grpc_server::serve(addr, api_client.clone()));
+    }
+
+    #[cfg(feature = "http")]
+    if let Some(addr) = options.http {
+        servers.spawn(idp_cli::http_server::serve(addr, api_client.clone()));
+    }
+
+    if servers.is_empty() {
+        return Err(CliError::ConfigurationError(
+            "No listener configured. Pass --grpc <ADDR> or --http <ADDR> (requires a build with the grpc or http feature).".to_string(),
+        ));
+    }
+
+    // Run until any listener stops
+    match servers.join_next().await {
+        Some(Ok(result)) => result,
+        Some(Err(e)) => Err(CliError::IoError(format!("Server task failed: {}", e))),
+        None => Ok(()),
+    }
+}
+
+/// Handle list-variables command
+/// 
+/// This function implements the list-variables command workflow:
+/// 1. Fetches blueprint or stack data from the IDP API
+/// 2. Builds a variable context from the API response and merges custom variables
+/// 3. Displays variables in a readable format with types, sample values and sources
+/// 
+/// # Arguments
 /// * `data_source` - Whether to use a blueprint or stack as the data source
 /// * `identifier` - The name or UUID of the blueprint/stack
+/// * `variables` - Variables files and `--var` assignments, as in generate
+/// * `options` - Filters applied before display (path, search, depth, type)
 /// * `api_client` - The API client for fetching data
+/// * `reporter` - Decorated or plain (`--plain`) tree output, or JSON (`--output json`)
 /// 
 /// # Returns
 /// * `Ok(())` if variables are successfully fetched and displayed
@@ -248,7 +1493,10 @@ async fn handle_generate(
 async fn handle_list_variables(
     data_source: DataSource,
     identifier: &str,
+    variables: &VariableInputs,
+    options: &ListVariablesOptions,
     api_client: &ApiClient,
+    reporter: Reporter,
 ) -> Result<(), CliError> {
     // Step 1: Fetch blueprint or stack data based on data_source
     info!("Fetching {} data from API...", match data_source {
@@ -256,7 +1504,7 @@ async fn handle_list_variables(
         DataSource::Stack => "stack",
     });
 
-    let context = match data_source {
+    let mut context = match data_source {
         DataSource::Blueprint => {
             // Fetch blueprint from API
             let blueprint = api_client.get_blueprint(identifier).await?;
@@ -277,175 +1525,792 @@ async fn handle_list_variables(
         }
     };
 
+    // Merge custom variables as generate does, recording where each value came from
+    variables.merge_into(&mut context)?;
     info!("Variable context built with {} variables", context.len());
 
-    // Step 2: Display variables in a readable format
-    display_variables(&context, data_source);
+    // Step 2: Apply filters, if any
+    let filter = options.to_filter();
+    let context = if filter.is_empty() {
+        context
+    } else {
+        let filtered = context.filter(&filter);
+        info!("Filter matched {} of {} variables", filtered.len(), context.len());
+        filtered
+    };
+
+    // Step 3: Display variables in the requested format
+    // A JSON Schema is already JSON; every other format becomes the variable list
+    if reporter.is_json() && options.format != VariableFormat::JsonSchema {
+        return reporter.json(&variable_export::to_json(&context));
+    }
+    match options.format {
+        VariableFormat::Tree => display_variables(&context, data_source, reporter.style()),
+        VariableFormat::Markdown => print!("{}", variable_export::to_markdown(&context)),
+        VariableFormat::Csv => print!("{}", variable_export::to_csv(&context)),
+        VariableFormat::JsonSchema => {
+            let schema = variable_export::to_json_schema(
+                &context,
+                &format!("{} {} variables", data_source, identifier),
+            );
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+        }
+    }
     
     Ok(())
 }
 
-/// Display variables in a readable format
+/// Handle describe command
 /// 
-/// Shows variable names, types, and sample values in a tree structure
-/// that makes it easy to understand the available template variables.
+/// Fetches the blueprint or stack and prints a structured summary in the
+/// requested format.
 /// 
 /// # Arguments
-/// * `context` - The variable context containing all variables
-/// * `data_source` - The data source type (for header display)
-fn display_variables(context: &VariableContext, data_source: DataSource) {
-    let source_name = match data_source {
-        DataSource::Blueprint => "Blueprint",
-        DataSource::Stack => "Stack",
+/// * `data_source` - Whether to describe a blueprint or stack
+/// * `identifier` - The name or UUID of the blueprint/stack
+/// * `format` - Human-readable or JSON output
+/// * `api_client` - The API client for fetching data
+async fn handle_describe(
+    data_source: DataSource,
+    identifier: &str,
+    format: DescribeFormat,
+    api_client: &ApiClient,
+) -> Result<(), CliError> {
+    let description = match data_source {
+        DataSource::Blueprint => Description::from_blueprint(&api_client.get_blueprint(identifier).await?),
+        DataSource::Stack => Description::from_stack(&api_client.get_stack(identifier).await?),
     };
 
-    println!("\n{} Variables", source_name);
-    println!("{}", "=".repeat(80));
-    println!("\nAvailable variables for use in templates:\n");
+    match format {
+        DescribeFormat::Human => println!("{}", description.render_human()),
+        DescribeFormat::Json => println!("{}", serde_json::to_string_pretty(&description)?),
+    }
 
-    // Get all variables sorted by key
-    let all_vars = context.list_all();
-    
-    if all_vars.is_empty() {
-        println!("No variables available.");
-        return;
+    Ok(())
+}
+
+/// Handle check-freshness command
+/// 
+/// Compares the revision recorded in each output manifest with the server's
+/// current `updated_at` and fails with a stale output error if any differ.
+/// 
+/// # Arguments
+/// * `format` - Human-readable or JSON output
+/// * `api_client` - The API client for fetching data
+/// * `output_dir` - The directory holding the generated files
+async fn handle_check_freshness(
+    format: DescribeFormat,
+    api_client: &ApiClient,
+    output_dir: &std::path::Path,
+) -> Result<(), CliError> {
+    let manifests = OutputManifest::find_all(output_dir)?;
+    if manifests.is_empty() {
+        return Err(CliError::NotFoundError(format!(
+            "No output manifest in {}; run generate first",
+            output_dir.display()
+        )));
     }
 
-    // Group variables by their root key (before first dot or bracket)
-    let mut root_groups: std::collections::HashMap<String, Vec<(String, &serde_json::Value)>> = 
-        std::collections::HashMap::new();
-    
-    for (key, value) in &all_vars {
-        let root = extract_root_key(key);
-        root_groups.entry(root.to_string())
-            .or_insert_with(Vec::new)
-            .push((key.to_string(), *value));
+    let mut stale = Vec::new();
+    let mut lines = Vec::new();
+    let mut checks = Vec::new();
+    // A composed output is checked blueprint by blueprint
+    let sources = manifests
+        .iter()
+        .flat_map(|(dir, manifest)| manifest.sources().into_iter().map(move |source| (dir, source)));
+    for (dir, manifest) in sources {
+        let current = match manifest.source() {
+            DataSource::Blueprint => api_client.get_blueprint(&manifest.id).await?.updated_at,
+            DataSource::Stack => api_client.get_stack(&manifest.id).await?.updated_at,
+        };
+        let freshness = manifest.freshness(current.as_deref());
+        let status = match &freshness {
+            Freshness::Current => "current",
+            Freshness::Stale { .. } => "stale",
+            Freshness::Unknown => "unknown",
+        };
+        match format {
+            DescribeFormat::Human => lines.push(format!(
+                "{}: {} ({} '{}', generated from {}, current {})",
+                dir.display(),
+                status,
+                manifest.data_source,
+                manifest.name,
+                manifest.updated_at.as_deref().unwrap_or("-"),
+                current.as_deref().unwrap_or("-")
+            )),
+            DescribeFormat::Json => checks.push(serde_json::json!({
+                "output_dir": dir,
+                "status": status,
+                "data_source": manifest.data_source,
+                "id": manifest.id,
+                "name": manifest.name,
+                "generated_from": manifest.updated_at,
+                "current": current,
+            })),
+        }
+        if let Some(warning) = freshness.warning(manifest, dir) {
+            stale.push(warning);
+        }
     }
 
-    // Sort root groups for consistent output
-    let mut sorted_roots: Vec<_> = root_groups.keys().collect();
-    sorted_roots.sort();
+    match format {
+        DescribeFormat::Human => println!("{}", lines.join("\n")),
+        DescribeFormat::Json => println!("{}", serde_json::to_string_pretty(&checks)?),
+    }
 
-    // Display each root group
-    for root in sorted_roots {
-        let vars = root_groups.get(root).unwrap();
-        display_variable_group(root, vars);
-        println!(); // Blank line between groups
+    if stale.is_empty() {
+        Ok(())
+    } else {
+        Err(CliError::StaleOutputError(stale.join("; ")))
     }
+}
 
-    // Display usage examples
-    println!("{}", "=".repeat(80));
-    println!("\nUsage Examples:");
-    println!("  {{{{variable_name}}}}                    - Simple variable substitution");
-    println!("  {{{{object.property}}}}                  - Nested property access");
-    println!("  {{{{array[0].property}}}}                - Array element access");
-    println!("  {{{{variable|default:\"value\"}}}}         - Default value if undefined");
-    println!("\nFor more information on template syntax, see the documentation.");
-    println!();
+/// Handle list-blueprints command
+/// 
+/// Fetches blueprints page by page until the limit is reached and prints
+/// them as a table or JSON.
+/// 
+/// # Arguments
+/// * `options` - Filter, limit and output format
+/// * `api_client` - The API client for fetching data
+/// * `style` - Decorated or plain (`--plain`) table output
+async fn handle_list_blueprints(
+    options: &ListOptions,
+    api_client: &ApiClient,
+    style: OutputStyle,
+) -> Result<(), CliError> {
+    let blueprints = api_client.list_blueprints(&options.to_query()).await?;
+    let entries: Vec<ListEntry> = blueprints.iter().map(ListEntry::from_blueprint).collect();
+    print_list(&entries, "blueprint", options.format, style)
 }
 
-/// Extract the root key from a variable path
+/// Handle list-stacks command
 /// 
-/// Examples:
-/// - "blueprint.name" -> "blueprint"
-/// - "resources[0].name" -> "resources"
-/// - "stack.id" -> "stack"
-fn extract_root_key(key: &str) -> &str {
-    if let Some(dot_pos) = key.find('.') {
-        &key[..dot_pos]
-    } else if let Some(bracket_pos) = key.find('[') {
-        &key[..bracket_pos]
-    } else {
-        key
+/// Fetches stacks page by page until the limit is reached and prints them
+/// as a table or JSON.
+/// 
+/// # Arguments
+/// * `options` - Filter, limit and output format
+/// * `api_client` - The API client for fetching data
+/// * `style` - Decorated or plain (`--plain`) table output
+async fn handle_list_stacks(
+    options: &ListOptions,
+    api_client: &ApiClient,
+    style: OutputStyle,
+) -> Result<(), CliError> {
+    let stacks = api_client.list_stacks(&options.to_query()).await?;
+    let entries: Vec<ListEntry> = stacks.iter().map(ListEntry::from_stack).collect();
+    print_list(&entries, "stack", options.format, style)
+}
+
+fn print_list(entries: &[ListEntry], kind: &str, format: ListFormat, style: OutputStyle) -> Result<(), CliError> {
+    match format {
+        ListFormat::Table => println!("{}", listing::render_table(entries, kind, style)),
+        ListFormat::Json => println!("{}", serde_json::to_string_pretty(entries)?),
     }
+    Ok(())
 }
 
-/// Display a group of variables with the same root key
+/// Handle stack command
 /// 
-/// Shows the structure in a tree format with types and sample values
-fn display_variable_group(root: &str, vars: &[(String, &serde_json::Value)]) {
-    println!("{}:", root);
-    
-    // Find the root variable itself (without dots or brackets)
-    let root_var = vars.iter().find(|(k, _)| k == root);
-    
-    if let Some((_, value)) = root_var {
-        // Display the root variable type and structure
-        let var_type = get_value_type(value);
-        println!("  Type: {}", var_type);
-        
-        // For complex types, show structure
-        match value {
-            serde_json::Value::Object(_) => {
-                println!("  Structure: Object with properties");
-                display_object_properties(root, vars, 2);
+/// Fetches the stack's state, resource states or event log from the API and
+/// prints it as text or JSON.
+/// 
+/// # Arguments
+/// * `action` - What to show, and about which stack
+/// * `reporter` - Switches to JSON output with `--output json`
+/// * `api_client` - The API client for fetching data
+/// * `style` - Decorated or plain (`--plain`) table output
+async fn handle_stack(
+    action: &StackAction,
+    reporter: Reporter,
+    api_client: &ApiClient,
+    style: OutputStyle,
+) -> Result<(), CliError> {
+    let output = match action {
+        StackAction::Status { name, format } => {
+            info!("Fetching status of stack: {}", name);
+            let status = api_client.get_stack_status(name).await?;
+            match json_or(reporter, *format) {
+                DescribeFormat::Human => stack_state::render_status(name, &status),
+                DescribeFormat::Json => serde_json::to_string_pretty(&status)?,
             }
-            serde_json::Value::Array(arr) => {
-                println!("  Structure: Array with {} element(s)", arr.len());
-                if !arr.is_empty() {
-                    display_array_elements(root, vars, 2);
-                }
+        }
+        StackAction::Resources { name, format } => {
+            info!("Fetching resources of stack: {}", name);
+            let resources = api_client.get_stack_resource_states(name).await?;
+            match json_or(reporter, *format) {
+                DescribeFormat::Human => stack_state::render_resources(&resources, style),
+                DescribeFormat::Json => serde_json::to_string_pretty(&resources)?,
             }
-            _ => {
-                // For primitive values, show the value
-                println!("  Value: {}", format_value_sample(value));
+        }
+        StackAction::Events { name, limit, format } => {
+            info!("Fetching events of stack: {}", name);
+            let events = api_client.get_stack_events(name, *limit).await?;
+            match json_or(reporter, *format) {
+                DescribeFormat::Human => stack_state::render_events(&events),
+                DescribeFormat::Json => serde_json::to_string_pretty(&events)?,
             }
         }
-    } else {
-        // No root variable, just show the nested properties
-        display_nested_properties(root, vars, 1);
-    }
+    };
+    println!("{}", output);
+    Ok(())
 }
 
-/// Display properties of an object
-fn display_object_properties(prefix: &str, vars: &[(String, &serde_json::Value)], indent: usize) {
-    let indent_str = "  ".repeat(indent);
-    
-    // Find all direct properties (one level deeper)
-    let mut properties: std::collections::HashSet<String> = std::collections::HashSet::new();
-    
-    for (key, _) in vars {
-        if key.starts_with(prefix) && key.len() > prefix.len() {
-            let suffix = &key[prefix.len()..];
-            if suffix.starts_with('.') {
-                let prop_part = &suffix[1..]; // Skip the dot
-                if let Some(next_dot) = prop_part.find('.') {
-                    properties.insert(prop_part[..next_dot].to_string());
-                } else if let Some(next_bracket) = prop_part.find('[') {
-                    properties.insert(prop_part[..next_bracket].to_string());
-                } else {
-                    properties.insert(prop_part.to_string());
-                }
-            }
-        }
-    }
-    
-    let mut sorted_props: Vec<_> = properties.into_iter().collect();
-    sorted_props.sort();
-    
-    for prop in sorted_props {
-        let prop_key = format!("{}.{}", prefix, prop);
-        if let Some((_, value)) = vars.iter().find(|(k, _)| k == &prop_key) {
-            let var_type = get_value_type(value);
-            let sample = format_value_sample(value);
-            println!("{}{}: {} = {}", indent_str, prop, var_type, sample);
-        }
+/// Handle trace-var command
+/// 
+/// Builds the variable context the same way generate does, optionally merges
+/// the variables file, and reports where the variable comes from, its value at
+/// each step and the template lines that reference it.
+/// 
+/// # Arguments
+/// * `data_source` - Whether to use a blueprint or stack as the data source
+/// * `identifier` - The name or UUID of the blueprint/stack
+/// * `variable` - Variable path to trace
+/// * `template_dir` - Templates to search for references (None skips the search)
+/// * `variables` - Custom variables to merge, as in generate
+/// * `format` - Human-readable or JSON output
+/// * `api_client` - The API client for fetching data
+async fn handle_trace_var(
+    data_source: DataSource,
+    identifier: &str,
+    variable: &str,
+    template_dir: Option<&std::path::Path>,
+    variables: &VariableInputs,
+    format: DescribeFormat,
+    api_client: &ApiClient,
+) -> Result<(), CliError> {
+    let context = match data_source {
+        DataSource::Blueprint => VariableContextBuilder::from_blueprint(&api_client.get_blueprint(identifier).await?),
+        DataSource::Stack => VariableContextBuilder::from_stack(&api_client.get_stack(identifier).await?),
+    };
+
+    let merged = if variables.is_empty() {
+        None
+    } else {
+        let mut merged = context.clone();
+        variables.merge_into(&mut merged)?;
+        Some(merged)
+    };
+
+    let templates = match template_dir {
+        Some(dir) => template_discovery::TemplateDiscovery::new(dir.to_path_buf())
+            .discover_templates()
+            .map_err(|e| {
+                CliError::DiscoveryError(format!("Failed to discover templates in '{}': {}", dir.display(), e))
+            })?,
+        None => Vec::new(),
+    };
+
+    let trace = VariableTrace::build(
+        variable,
+        data_source,
+        &context,
+        merged.as_ref(),
+        &templates,
+    )?;
+
+    match format {
+        DescribeFormat::Human => println!("{}", trace.render_human()),
+        DescribeFormat::Json => println!("{}", serde_json::to_string_pretty(&trace)?),
     }
+
+    Ok(())
 }
 
-/// Display elements of an array
-fn display_array_elements(prefix: &str, vars: &[(String, &serde_json::Value)], indent: usize) {
-    let indent_str = "  ".repeat(indent);
-    
-    // Find array indices
-    let mut indices: std::collections::HashSet<usize> = std::collections::HashSet::new();
-    
-    for (key, _) in vars {
-        if key.starts_with(prefix) && key.len() > prefix.len() {
-            let suffix = &key[prefix.len()..];
-            if suffix.starts_with('[') {
-                if let Some(bracket_end) = suffix.find(']') {
-                    if let Ok(index) = suffix[1..bracket_end].parse::<usize>() {
+/// Handle lint-templates command
+///
+/// Builds the variable context for a blueprint or stack, as generate does,
+/// and checks every discovered template against it.
+///
+/// # Arguments
+/// * `data_source` - Whether to use a blueprint or stack as the data source
+/// * `identifier` - The name or UUID of the blueprint/stack
+/// * `template_dir` - The directory containing template files
+/// * `variables` - Custom variables to merge, as in generate
+/// * `helpers_file` - Custom helpers file, as in generate
+/// * `format` - Human-readable or JSON output
+/// * `api_client` - The API client for fetching data
+///
+/// # Returns
+/// * `Err(CliError::LintError)` if any template has problems
+#[allow(clippy::too_many_arguments)]
+async fn handle_lint_templates(
+    data_source: DataSource,
+    identifier: &str,
+    template_dir: &std::path::Path,
+    variables: &VariableInputs,
+    helpers_file: Option<&std::path::Path>,
+    format: DescribeFormat,
+    reports: &[ReportTarget],
+    api_client: &ApiClient,
+) -> Result<(), CliError> {
+    let helpers = helper_plugins::HelperSet::for_templates(template_dir, helpers_file)?;
+    let mut context = match data_source {
+        DataSource::Blueprint => VariableContextBuilder::from_blueprint(&api_client.get_blueprint(identifier).await?),
+        DataSource::Stack => VariableContextBuilder::from_stack(&api_client.get_stack(identifier).await?),
+    };
+    variables.merge_into(&mut context)?;
+
+    let templates = template_discovery::TemplateDiscovery::new(template_dir.to_path_buf())
+        .discover_templates()
+        .map_err(|e| {
+            CliError::DiscoveryError(format!("Failed to discover templates in '{}': {}", template_dir.display(), e))
+        })?;
+    let report = TemplateLinter::new(context.to_template_data(), &helpers.names()).lint(&templates)?;
+    let findings = Findings::default();
+    for template in &templates {
+        findings.check(template_dir.join(&template.relative_path));
+    }
+    for issue in &report.issues {
+        findings.push(Finding::from_lint(issue, template_dir));
+    }
+    write_reports(reports, "lint-templates", &findings)?;
+
+    match format {
+        DescribeFormat::Human => println!("{}", report.render_human()),
+        DescribeFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+
+    if report.is_clean() {
+        Ok(())
+    } else {
+        Err(CliError::LintError(format!(
+            "{} problem(s) found in {}",
+            report.issues.len(),
+            template_dir.display()
+        )))
+    }
+}
+
+/// Handle repl command
+/// 
+/// Fetches the blueprint or stack once and renders snippets read from the
+/// terminal until `:quit` or end of input.
+async fn handle_repl(
+    data_source: DataSource,
+    identifier: &str,
+    template_dir: Option<&std::path::Path>,
+    variables: &VariableInputs,
+    helpers_file: Option<&std::path::Path>,
+    api_client: &ApiClient,
+) -> Result<(), CliError> {
+    let helpers = match template_dir {
+        Some(dir) => helper_plugins::HelperSet::for_templates(dir, helpers_file)?,
+        None => {
+            let mut helpers = helper_plugins::HelperSet::new();
+            if let Some(path) = helpers_file {
+                helpers.load_file(path)?;
+            }
+            helpers
+        }
+    };
+    let mut context = match data_source {
+        DataSource::Blueprint => VariableContextBuilder::from_blueprint(&api_client.get_blueprint(identifier).await?),
+        DataSource::Stack => VariableContextBuilder::from_stack(&api_client.get_stack(identifier).await?),
+    };
+    variables.merge_into(&mut context)?;
+
+    let mut repl = Repl::new(&context, &helpers)?;
+    let mut editor = rustyline::DefaultEditor::new()
+        .map_err(|e| CliError::IoError(format!("Failed to open the terminal: {}", e)))?;
+    println!("Rendering against {} '{}'. Type :help for commands, :quit to leave.", data_source, identifier);
+    let mut prompt = "idp> ";
+    loop {
+        match editor.readline(prompt) {
+            Ok(line) => {
+                // Recalling input is a convenience; a line history can't keep still runs
+                let _ = editor.add_history_entry(line.as_str());
+                prompt = "idp> ";
+                match repl.eval(&line) {
+                    ReplOutcome::Output(output) => println!("{}", output),
+                    ReplOutcome::Continue => prompt = "...> ",
+                    ReplOutcome::Quit => break,
+                }
+            }
+            // Ctrl-C drops the current line, as in a shell
+            Err(ReadlineError::Interrupted) => {
+                repl.cancel();
+                prompt = "idp> ";
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(CliError::IoError(format!("Failed to read input: {}", e))),
+        }
+    }
+    Ok(())
+}
+
+/// Handle inspect-template command
+/// 
+/// Prints the variable paths, helpers and blocks of one template file.
+fn handle_inspect_template(file: &std::path::Path, format: DescribeFormat) -> Result<(), CliError> {
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| CliError::IoError(format!("Failed to read template {}: {}", file.display(), e)))?;
+    let inspection = TemplateInspection::inspect(file, &content)?;
+    match format {
+        DescribeFormat::Human => println!("{}", inspection.render_human()),
+        DescribeFormat::Json => println!("{}", serde_json::to_string_pretty(&inspection)?),
+    }
+    Ok(())
+}
+
+/// Handle eject command
+/// 
+/// Writes the templates of a built-in pack into `out`, refusing to replace
+/// templates already there unless `force` is set.
+fn handle_eject(pack: BuiltinPack, out: &std::path::Path, force: bool, format: DescribeFormat) -> Result<(), CliError> {
+    let existing = pack.existing_files(out);
+    if !existing.is_empty() && !force {
+        return Err(CliError::ExistingFileError(format!(
+            "{} in {}; pass --force to overwrite them",
+            existing.join(", "),
+            out.display()
+        )));
+    }
+    pack.extract(out)?;
+    let files: Vec<&str> = pack.files().iter().map(|(relative_path, _)| *relative_path).collect();
+    match format {
+        DescribeFormat::Human => {
+            println!("Wrote {} template(s) of the {} pack to {}", files.len(), pack.name(), out.display());
+            for file in &files {
+                println!("  {}", file);
+            }
+            println!("\nGenerate from them with --template-dir {}", out.display());
+        }
+        DescribeFormat::Json => {
+            let result = serde_json::json!({ "pack": pack.name(), "out": out, "files": files });
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
+    }
+    Ok(())
+}
+
+/// Handle preview command
+/// 
+/// Renders the templates for a draft revision and for the revision it is
+/// compared with into temporary directories and prints a unified diff of
+/// the generated output.
+/// 
+/// # Arguments
+/// * `data_source` - Whether to use a blueprint or stack as the data source
+/// * `identifier` - The name or UUID of the blueprint/stack
+/// * `revision` - Revision to preview, e.g. `draft`
+/// * `against` - Revision to compare with (None is the published revision)
+/// * `template_dir` - The directory containing template files
+/// * `variables` - Custom variables merged into both revisions, as in generate
+/// * `api_client` - The API client for fetching data
+async fn handle_preview(
+    data_source: DataSource,
+    identifier: &str,
+    revision: &str,
+    against: Option<&str>,
+    template_dir: &std::path::Path,
+    variables: &VariableInputs,
+    api_client: &ApiClient,
+) -> Result<(), CliError> {
+    let base_dir = tempfile::TempDir::new().map_err(|e| {
+        CliError::IoError(format!("Failed to create temporary preview directory: {}", e))
+    })?;
+
+    let base = render_revision(data_source, identifier, against, template_dir, variables, api_client).await?;
+    let writer = file_writer::FileWriter::new(base_dir.path().to_path_buf()).without_overwrite_warnings();
+    writer.write_processed_files(&base)?;
+
+    let draft = render_revision(data_source, identifier, Some(revision), template_dir, variables, api_client).await?;
+    let previews = draft.iter().map(|file| writer.preview(file)).collect::<Result<Vec<_>, _>>()?;
+    let removed: Vec<_> = base
+        .iter()
+        .filter(|file| !draft.iter().any(|d| d.relative_path == file.relative_path))
+        .map(|file| file.path_str())
+        .collect();
+
+    println!(
+        "Previewing {} {} revision {} against {}\n",
+        data_source,
+        identifier,
+        revision,
+        against.map(|r| format!("revision {}", r)).unwrap_or_else(|| "the published revision".to_string())
+    );
+    print!("{}", file_writer::render_diff(&previews));
+    for path in &removed {
+        println!("Removed: {}", path);
+    }
+
+    let count = |change| previews.iter().filter(|p| p.change == change).count();
+    println!(
+        "\n{} file(s) would be created, {} modified, {} removed, {} unchanged",
+        count(file_writer::FileChange::Created),
+        count(file_writer::FileChange::Modified),
+        removed.len(),
+        count(file_writer::FileChange::Unchanged)
+    );
+    Ok(())
+}
+
+/// Render the templates for one revision of a blueprint or stack without writing
+async fn render_revision(
+    data_source: DataSource,
+    identifier: &str,
+    revision: Option<&str>,
+    template_dir: &std::path::Path,
+    variables: &VariableInputs,
+    api_client: &ApiClient,
+) -> Result<Vec<template_processor::ProcessedFile>, CliError> {
+    let mut context = match data_source {
+        DataSource::Blueprint => {
+            VariableContextBuilder::from_blueprint(&api_client.get_blueprint_at(identifier, revision).await?)
+        }
+        DataSource::Stack => VariableContextBuilder::from_stack(&api_client.get_stack_at(identifier, revision).await?),
+    };
+    variables.merge_into(&mut context)?;
+
+    let templates = template_discovery::TemplateDiscovery::new(template_dir.to_path_buf())
+        .discover_templates()
+        .map_err(|e| {
+            CliError::DiscoveryError(format!("Failed to discover templates in '{}': {}", template_dir.display(), e))
+        })?;
+    let registry = generation_strategy::StrategyRegistry::default();
+    let strategy = registry.select_for_context(&context);
+    let helpers = helper_plugins::HelperSet::for_templates(template_dir, None)?;
+    let processor = template_processor::TemplateProcessor::new(&context)
+        .with_sandbox(SandboxPolicy {
+            render_timeout: Some(render_sandbox::DEFAULT_RENDER_TIMEOUT),
+            ..SandboxPolicy::default()
+        })
+        .with_helpers(&helpers)?;
+    registry
+        .apply(strategy, templates)
+        .iter()
+        .map(|template| processor.process_file_outputs(template))
+        .collect::<Result<Vec<_>, _>>()
+        .map(|outputs| outputs.concat())
+}
+
+/// Handle search command
+/// 
+/// Lists blueprints and/or stacks from the API and prints the ones matching
+/// the search criteria.
+/// 
+/// # Arguments
+/// * `data_source` - Restrict the search to blueprints or stacks (None searches both)
+/// * `criteria` - Provider, resource type and name filters
+/// * `format` - Human-readable table or JSON
+/// * `api_client` - The API client for fetching data
+async fn handle_search(
+    data_source: Option<DataSource>,
+    criteria: &SearchCriteria,
+    format: DescribeFormat,
+    api_client: &ApiClient,
+) -> Result<(), CliError> {
+    let mut results = Vec::new();
+
+    if !matches!(data_source, Some(DataSource::Stack)) {
+        let blueprints = api_client.list_blueprints(&ListQuery::default()).await?;
+        info!("Searching {} blueprint(s)", blueprints.len());
+        results.extend(blueprints.iter().filter_map(|b| criteria.match_blueprint(b)));
+    }
+
+    if !matches!(data_source, Some(DataSource::Blueprint)) {
+        let stacks = api_client.list_stacks(&ListQuery::default()).await?;
+        info!("Searching {} stack(s)", stacks.len());
+        results.extend(stacks.iter().filter_map(|s| criteria.match_stack(s)));
+    }
+
+    match format {
+        DescribeFormat::Human => println!("{}", search::render_results(&results)),
+        DescribeFormat::Json => println!("{}", serde_json::to_string_pretty(&results)?),
+    }
+
+    Ok(())
+}
+
+/// Display variables in a readable format
+/// 
+/// Shows variable names, types, and sample values in a tree structure
+/// that makes it easy to understand the available template variables.
+/// 
+/// # Arguments
+/// * `context` - The variable context containing all variables
+/// * `data_source` - The data source type (for header display)
+fn display_variables(context: &VariableContext, data_source: DataSource, style: OutputStyle) {
+    let source_name = match data_source {
+        DataSource::Blueprint => "Blueprint",
+        DataSource::Stack => "Stack",
+    };
+
+    println!("\n{} Variables", source_name);
+    if let Some(rule) = style.rule(80) {
+        println!("{}", rule);
+    }
+    println!("\nAvailable variables for use in templates:\n");
+
+    // Get all variables sorted by key
+    let all_vars = context.list_all();
+    
+    if all_vars.is_empty() {
+        println!("No variables available.");
+        return;
+    }
+
+    // Group variables by their root key (before first dot or bracket)
+    let mut root_groups: std::collections::HashMap<String, Vec<(String, &serde_json::Value)>> = 
+        std::collections::HashMap::new();
+    
+    for (key, value) in &all_vars {
+        let root = extract_root_key(key);
+        root_groups.entry(root.to_string())
+            .or_default()
+            .push((key.to_string(), *value));
+    }
+
+    // Sort root groups for consistent output
+    let mut sorted_roots: Vec<_> = root_groups.keys().collect();
+    sorted_roots.sort();
+
+    // Display each root group
+    for root in sorted_roots {
+        let vars = root_groups.get(root).unwrap();
+        display_variable_group(root, vars, style);
+        println!(); // Blank line between groups
+    }
+
+    // Values that don't come from the blueprint or stack, and where they come from
+    let merged: Vec<_> = all_vars
+        .iter()
+        .filter_map(|(key, _)| context.source(key).map(|source| (key, source)))
+        .collect();
+    if !merged.is_empty() {
+        println!("Set by variables files, --var or config defaults:");
+        for (key, source) in merged {
+            println!("{}{} (from {})", style.indent(1), key, source);
+        }
+        println!();
+    }
+
+    // Display usage examples
+    if let Some(rule) = style.rule(80) {
+        println!("{}", rule);
+    }
+    println!("\nUsage Examples:");
+    println!("{}", style.example("{{variable_name}}", "Simple variable substitution"));
+    println!("{}", style.example("{{object.property}}", "Nested property access"));
+    println!("{}", style.example("{{array[0].property}}", "Array element access"));
+    println!("{}", style.example("{{variable|default:\"value\"}}", "Default value if undefined"));
+    println!("\nFor more information on template syntax, see the documentation.");
+    println!();
+}
+
+/// Extract the root key from a variable path
+/// 
+/// Examples:
+/// - "blueprint.name" -> "blueprint"
+/// - "resources[0].name" -> "resources"
+/// - "stack.id" -> "stack"
+fn extract_root_key(key: &str) -> &str {
+    if let Some(dot_pos) = key.find('.') {
+        &key[..dot_pos]
+    } else if let Some(bracket_pos) = key.find('[') {
+        &key[..bracket_pos]
+    } else {
+        key
+    }
+}
+
+/// Display a group of variables with the same root key
+/// 
+/// Shows the structure in a tree format with types and sample values. Plain
+/// output prints full variable paths instead of relying on indentation.
+fn display_variable_group(root: &str, vars: &[(String, &serde_json::Value)], style: OutputStyle) {
+    println!("{}:", root);
+    let label = |field: &str| {
+        if style.is_plain() {
+            format!("{} {}", root, field.to_lowercase())
+        } else {
+            format!("  {}", field)
+        }
+    };
+    
+    // Find the root variable itself (without dots or brackets)
+    let root_var = vars.iter().find(|(k, _)| k == root);
+    
+    if let Some((_, value)) = root_var {
+        // Display the root variable type and structure
+        let var_type = get_value_type(value);
+        println!("{}: {}", label("Type"), var_type);
+        
+        // For complex types, show structure
+        match value {
+            serde_json::Value::Object(_) => {
+                println!("{}: Object with properties", label("Structure"));
+                display_object_properties(root, vars, 2, style);
+            }
+            serde_json::Value::Array(arr) => {
+                println!("{}: Array with {} element(s)", label("Structure"), arr.len());
+                if !arr.is_empty() {
+                    display_array_elements(root, vars, 2, style);
+                }
+            }
+            _ => {
+                // For primitive values, show the value
+                println!("{}: {}", label("Value"), format_value_sample(value));
+            }
+        }
+    } else {
+        // No root variable, just show the nested properties
+        display_nested_properties(root, vars, 1, style);
+    }
+}
+
+/// Display properties of an object
+fn display_object_properties(prefix: &str, vars: &[(String, &serde_json::Value)], indent: usize, style: OutputStyle) {
+    let indent_str = style.indent(indent);
+    
+    // Find all direct properties (one level deeper)
+    let mut properties: std::collections::HashSet<String> = std::collections::HashSet::new();
+    
+    for (key, _) in vars {
+        if key.starts_with(prefix) && key.len() > prefix.len() {
+            let suffix = &key[prefix.len()..];
+            if let Some(prop_part) = suffix.strip_prefix('.') {
+                if let Some(next_dot) = prop_part.find('.') {
+                    properties.insert(prop_part[..next_dot].to_string());
+                } else if let Some(next_bracket) = prop_part.find('[') {
+                    properties.insert(prop_part[..next_bracket].to_string());
+                } else {
+                    properties.insert(prop_part.to_string());
+                }
+            }
+        }
+    }
+    
+    let mut sorted_props: Vec<_> = properties.into_iter().collect();
+    sorted_props.sort();
+    
+    for prop in sorted_props {
+        let prop_key = format!("{}.{}", prefix, prop);
+        if let Some((_, value)) = vars.iter().find(|(k, _)| k == &prop_key) {
+            let var_type = get_value_type(value);
+            let sample = format_value_sample(value);
+            let name = if style.is_plain() { &prop_key } else { &prop };
+            println!("{}{}: {} = {}", indent_str, name, var_type, sample);
+        }
+    }
+}
+
+/// Display elements of an array
+fn display_array_elements(prefix: &str, vars: &[(String, &serde_json::Value)], indent: usize, style: OutputStyle) {
+    let indent_str = style.indent(indent);
+    
+    // Find array indices
+    let mut indices: std::collections::HashSet<usize> = std::collections::HashSet::new();
+    
+    for (key, _) in vars {
+        if key.starts_with(prefix) && key.len() > prefix.len() {
+            let suffix = &key[prefix.len()..];
+            if suffix.starts_with('[') {
+                if let Some(bracket_end) = suffix.find(']') {
+                    if let Ok(index) = suffix[1..bracket_end].parse::<usize>() {
                         indices.insert(index);
                     }
                 }
@@ -463,23 +2328,32 @@ fn display_array_elements(prefix: &str, vars: &[(String, &serde_json::Value)], i
         let elem_key = format!("{}[{}]", prefix, index);
         if let Some((_, value)) = vars.iter().find(|(k, _)| k == &elem_key) {
             let var_type = get_value_type(value);
-            println!("{}[{}]: {}", indent_str, index, var_type);
+            if style.is_plain() {
+                println!("{}: {}", elem_key, var_type);
+            } else {
+                println!("{}[{}]: {}", indent_str, index, var_type);
+            }
             
             // Show properties of the element if it's an object
             if matches!(value, serde_json::Value::Object(_)) {
-                display_object_properties(&elem_key, vars, indent + 1);
+                display_object_properties(&elem_key, vars, indent + 1, style);
             }
         }
     }
     
     if sorted_indices.len() > display_count {
-        println!("{}... and {} more element(s)", indent_str, sorted_indices.len() - display_count);
+        let remaining = sorted_indices.len() - display_count;
+        if style.is_plain() {
+            println!("{}: {} more element(s) not shown", prefix, remaining);
+        } else {
+            println!("{}... and {} more element(s)", indent_str, remaining);
+        }
     }
 }
 
 /// Display nested properties when there's no root variable
-fn display_nested_properties(prefix: &str, vars: &[(String, &serde_json::Value)], indent: usize) {
-    let indent_str = "  ".repeat(indent);
+fn display_nested_properties(prefix: &str, vars: &[(String, &serde_json::Value)], indent: usize, style: OutputStyle) {
+    let indent_str = style.indent(indent);
     
     for (key, value) in vars {
         if key.starts_with(prefix) {
@@ -526,83 +2400,13 @@ fn format_value_sample(value: &serde_json::Value) -> String {
     }
 }
 
-/// Get next steps guidance based on generated file types
-/// 
-/// Analyzes the generated files and provides appropriate next steps
-/// for Terraform, Kubernetes, or other infrastructure-as-code tools.
-/// 
-/// # Arguments
-/// * `written_files` - List of paths to generated files
-/// * `template_dir` - The template directory path (for context)
-/// 
-/// # Returns
-/// A formatted string with next steps guidance
-fn get_next_steps_guidance(written_files: &[std::path::PathBuf], template_dir: &std::path::Path) -> String {
-    // Analyze file extensions to determine what was generated
-    let has_terraform = written_files.iter().any(|p| {
-        p.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext == "tf")
-            .unwrap_or(false)
-    });
-    
-    let has_kubernetes = written_files.iter().any(|p| {
-        p.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext == "yaml" || ext == "yml")
-            .unwrap_or(false)
-    });
-    
-    let has_json = written_files.iter().any(|p| {
-        p.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext == "json")
-            .unwrap_or(false)
-    });
-    
-    let mut guidance = String::from("Next steps:");
-    
-    if has_terraform {
-        guidance.push_str("\n\nFor Terraform/OpenTofu:");
-        guidance.push_str("\n  1. Review the generated files to ensure they match your requirements");
-        guidance.push_str("\n  2. Initialize Terraform:");
-        guidance.push_str(&format!("\n     cd {}", written_files[0].parent().unwrap_or(std::path::Path::new(".")).display()));
-        guidance.push_str("\n     terraform init");
-        guidance.push_str("\n  3. Validate the configuration:");
-        guidance.push_str("\n     terraform validate");
-        guidance.push_str("\n  4. Plan the infrastructure changes:");
-        guidance.push_str("\n     terraform plan");
-        guidance.push_str("\n  5. Apply the changes (when ready):");
-        guidance.push_str("\n     terraform apply");
-    }
-    
-    if has_kubernetes {
-        guidance.push_str("\n\nFor Kubernetes:");
-        guidance.push_str("\n  1. Review the generated manifests to ensure they match your requirements");
-        guidance.push_str("\n  2. Validate the manifests:");
-        guidance.push_str(&format!("\n     kubectl apply --dry-run=client -f {}", written_files[0].parent().unwrap_or(std::path::Path::new(".")).display()));
-        guidance.push_str("\n  3. Apply the manifests to your cluster:");
-        guidance.push_str(&format!("\n     kubectl apply -f {}", written_files[0].parent().unwrap_or(std::path::Path::new(".")).display()));
-        guidance.push_str("\n  4. Verify the deployment:");
-        guidance.push_str("\n     kubectl get all");
-    }
-    
-    if has_json && !has_terraform && !has_kubernetes {
-        guidance.push_str("\n\nFor JSON configuration files:");
-        guidance.push_str("\n  1. Review the generated files to ensure they match your requirements");
-        guidance.push_str("\n  2. Validate the JSON syntax:");
-        guidance.push_str(&format!("\n     jq . {} > /dev/null", written_files[0].display()));
-        guidance.push_str("\n  3. Use the configuration files with your infrastructure tools");
+/// The JSON format when `--output json` is set, otherwise the command's own
+fn json_or(reporter: Reporter, format: DescribeFormat) -> DescribeFormat {
+    if reporter.is_json() {
+        DescribeFormat::Json
+    } else {
+        format
     }
-    
-    // Add general guidance
-    guidance.push_str("\n\nGeneral tips:");
-    guidance.push_str("\n  - Use version control (git) to track changes to generated files");
-    guidance.push_str("\n  - Review all generated files before applying to production");
-    guidance.push_str("\n  - Use the 'list-variables' command to see available template variables");
-    guidance.push_str(&format!("\n  - Regenerate files by running the same command with updated templates in {}", template_dir.display()));
-    
-    guidance
 }
 
 /// Log error details for debugging
@@ -613,6 +2417,8 @@ fn log_error(error: &CliError) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use idp_cli::api_client::{LIST_PAGE_SIZE, MAX_LIST_PAGES};
+    use idp_cli::cli::ExistingFilePolicy;
     use std::fs;
     use tempfile::TempDir;
 
@@ -645,9 +2451,11 @@ mod tests {
             DataSource::Blueprint,
             "test-blueprint",
             &template_dir,
-            None,
+            &VariableInputs::default(),
+            &GenerateOptions::default(),
             &api_client,
             &output_dir,
+            Reporter::default(),
         ).await;
         
         // Should fail with network error (can't connect to fake API)
@@ -670,7 +2478,7 @@ mod tests {
         // when the API call succeeds (using a mock)
         
         use mockito::Server;
-        use crate::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
+        use idp_cli::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
         use uuid::Uuid;
         
         // Create temporary directories
@@ -727,6 +2535,8 @@ mod tests {
                     cloud_specific_properties: std::collections::HashMap::new(),
                 }
             ],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![],
         };
         
@@ -750,9 +2560,11 @@ mod tests {
             DataSource::Blueprint,
             "test-blueprint",
             &template_dir,
-            None,
+            &VariableInputs::default(),
+            &GenerateOptions::default(),
             &api_client,
             &output_dir,
+            Reporter::default(),
         ).await;
         
         // Verify the mock was called
@@ -772,7 +2584,7 @@ mod tests {
         // This test verifies that handle_generate fails gracefully when no templates are found
         
         use mockito::Server;
-        use crate::models::Blueprint;
+        use idp_cli::models::Blueprint;
         use uuid::Uuid;
         
         // Create temporary directories
@@ -794,6 +2606,8 @@ mod tests {
             name: "test-blueprint".to_string(),
             description: Some("Test blueprint".to_string()),
             resources: vec![],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![],
         };
         
@@ -817,9 +2631,11 @@ mod tests {
             DataSource::Blueprint,
             "test-blueprint",
             &template_dir,
-            None,
+            &VariableInputs::default(),
+            &GenerateOptions::default(),
             &api_client,
             &output_dir,
+            Reporter::default(),
         ).await;
         
         // Should fail with DiscoveryError
@@ -841,7 +2657,7 @@ mod tests {
         // and builds a variable context successfully
         
         use mockito::Server;
-        use crate::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
+        use idp_cli::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
         use uuid::Uuid;
         
         // Create a mock server
@@ -871,6 +2687,8 @@ mod tests {
                     cloud_specific_properties: std::collections::HashMap::new(),
                 }
             ],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![],
         };
         
@@ -893,7 +2711,10 @@ mod tests {
         let result = handle_list_variables(
             DataSource::Blueprint,
             "test-blueprint",
+            &VariableInputs::default(),
+            &ListVariablesOptions::default(),
             &api_client,
+            Reporter::default(),
         ).await;
         
         // Verify the mock was called
@@ -909,7 +2730,7 @@ mod tests {
         // and builds a variable context successfully
         
         use mockito::Server;
-        use crate::models::{Stack, StackResource, CloudProvider, ResourceType};
+        use idp_cli::models::{Stack, StackResource, CloudProvider, ResourceType};
         use uuid::Uuid;
         
         // Create a mock server
@@ -922,6 +2743,8 @@ mod tests {
             description: Some("Test stack".to_string()),
             cloud_name: "aws-us-east-1".to_string(),
             stack_type: "INFRASTRUCTURE_ONLY".to_string(),
+            revision: None,
+            updated_at: None,
             stack_resources: vec![
                 StackResource {
                     id: Uuid::new_v4(),
@@ -962,7 +2785,10 @@ mod tests {
         let result = handle_list_variables(
             DataSource::Stack,
             "test-stack",
+            &VariableInputs::default(),
+            &ListVariablesOptions::default(),
             &api_client,
+            Reporter::default(),
         ).await;
         
         // Verify the mock was called
@@ -972,6 +2798,391 @@ mod tests {
         assert!(result.is_ok(), "Expected success, got error: {:?}", result.err());
     }
 
+    #[tokio::test]
+    async fn test_handle_describe_blueprint_json() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+
+        let mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+
+        let result = handle_describe(
+            DataSource::Blueprint,
+            "test-blueprint",
+            DescribeFormat::Json,
+            &api_client,
+        ).await;
+
+        mock.assert_async().await;
+        assert!(result.is_ok(), "Expected success, got error: {:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn test_api_client_sends_custom_headers_and_user_agent() {
+        use mockito::Server;
+
+        let mut server = Server::new_async().await;
+        let mock = server.mock("GET", "/stacks")
+            .match_query(mockito::Matcher::Any)
+            .match_header("x-org-id", "payments")
+            .match_header("user-agent", format!("idp-cli/{} payments-ci/1.0", env!("CARGO_PKG_VERSION")).as_str())
+            .match_header("authorization", "Bearer test-api-key")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string())
+            .with_headers(&[("X-Org-Id".to_string(), "payments".to_string())])
+            .unwrap()
+            .with_user_agent("payments-ci/1.0");
+        api_client.list_stacks(&ListQuery::default()).await.unwrap();
+        mock.assert_async().await;
+
+        let error = ApiClient::new(server.url(), "test-api-key".to_string())
+            .with_headers(&[("Authorization".to_string(), "Bearer other".to_string())])
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("Authorization"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_api_client_rejects_oversized_response() {
+        use mockito::Server;
+
+        let mut server = Server::new_async().await;
+        let _mock = server
+            .mock("GET", "/stacks?page=0&size=100")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!("[{}]", vec!["{}"; 100].join(",")))
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string()).with_max_response_bytes(64);
+        let error = api_client.list_stacks(&ListQuery::default()).await.unwrap_err();
+        assert!(matches!(error, CliError::ResponseTooLargeError(_)), "{:?}", error);
+        assert_eq!(error.code(), "IDP027");
+    }
+
+    #[tokio::test]
+    async fn test_api_client_bounds_error_response_bodies() {
+        use mockito::Server;
+
+        let mut server = Server::new_async().await;
+        let _server_error = server
+            .mock("GET", "/stacks?page=0&size=100")
+            .with_status(500)
+            .with_body("x".repeat(10_000))
+            .create_async()
+            .await;
+        let _bad_request = server
+            .mock("GET", "/blueprints?page=0&size=100")
+            .with_status(400)
+            .with_body("y".repeat(3_000))
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string()).with_max_response_bytes(4096);
+        let error = api_client.list_stacks(&ListQuery::default()).await.unwrap_err();
+        assert_eq!(error.to_string(), CliError::ApiError("Server error (500): Unknown server error".to_string()).to_string());
+
+        let error = api_client.list_blueprints(&ListQuery::default()).await.unwrap_err().to_string();
+        assert!(error.contains(&format!("{}...", "y".repeat(1000))), "{}", error);
+        assert!(!error.contains(&"y".repeat(1001)), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_handle_stack_events_resolves_name() {
+        use mockito::Server;
+
+        let mut server = Server::new_async().await;
+        let id = "6f9619ff-8b86-d011-b42d-00c04fc964ff";
+        let stack_mock = server.mock("GET", "/stacks")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "payments-prod".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({
+                "id": id,
+                "name": "payments-prod",
+                "description": null,
+                "cloud_name": "aws",
+                "stack_type": "service",
+                "stack_resources": [],
+                "blueprint": null
+            }).to_string())
+            .create_async()
+            .await;
+        let events_mock = server.mock("GET", format!("/stacks/{}/events", id).as_str())
+            .match_query(mockito::Matcher::UrlEncoded("limit".into(), "5".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"timestamp": "2026-10-01T12:00:00Z", "event_type": "DEPLOYMENT_SUCCEEDED", "message": "Deployed"}]"#)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let action = StackAction::Events {
+            name: "payments-prod".to_string(),
+            limit: Some(5),
+            format: DescribeFormat::Json,
+        };
+
+        let result = handle_stack(&action, Reporter::default(), &api_client, OutputStyle::from_flag(false)).await;
+
+        stack_mock.assert_async().await;
+        events_mock.assert_async().await;
+        assert!(result.is_ok(), "Expected success, got error: {:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn test_api_client_prefetches_blueprints_and_stack_status() {
+        use mockito::Server;
+
+        let mut server = Server::new_async().await;
+        let mut mocks = Vec::new();
+        for name in ["networking", "app"] {
+            mocks.push(
+                server.mock("GET", "/blueprints")
+                    .match_query(mockito::Matcher::UrlEncoded("name".into(), name.into()))
+                    .with_status(200)
+                    .with_header("content-type", "application/json")
+                    .with_body(serde_json::json!({
+                        "id": uuid::Uuid::new_v4(),
+                        "name": name,
+                        "description": null,
+                        "resources": [],
+                        "supported_cloud_providers": []
+                    }).to_string())
+                    .create_async()
+                    .await,
+            );
+        }
+        let _missing_mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "missing".into()))
+            .with_status(404)
+            .create_async()
+            .await;
+        let id = "6f9619ff-8b86-d011-b42d-00c04fc964ff";
+        let stack_mock = server.mock("GET", "/stacks")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "payments-prod".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({
+                "id": id,
+                "name": "payments-prod",
+                "description": null,
+                "cloud_name": "aws",
+                "stack_type": "service",
+                "stack_resources": [],
+                "blueprint": null
+            }).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let status_mock = server.mock("GET", format!("/stacks/{}/status", id).as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"state": "ACTIVE"}"#)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let blueprints = api_client.get_blueprints_at(&[("app", None), ("networking", None)]).await.unwrap();
+        let names: Vec<_> = blueprints.iter().map(|blueprint| blueprint.name.as_str()).collect();
+        assert_eq!(names, ["app", "networking"], "blueprints keep the requested order");
+
+        let error = api_client.get_blueprints_at(&[("app", None), ("missing", None)]).await.unwrap_err();
+        assert!(matches!(error, CliError::NotFoundError(_)), "{:?}", error);
+
+        // A name is resolved once, and the status fetched by ID
+        let (stack, status) = api_client.get_stack_with_status("payments-prod", None).await.unwrap();
+        assert_eq!((stack.name.as_str(), status.state.as_str()), ("payments-prod", "ACTIVE"));
+        stack_mock.assert_async().await;
+        status_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_api_client_stops_calling_a_failing_api() {
+        use mockito::Server;
+
+        let mut server = Server::new_async().await;
+        let failing_mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::Any)
+            .with_status(503)
+            .expect(2)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string())
+            .with_circuit_breaker(2, std::time::Duration::from_secs(60));
+        for _ in 0..2 {
+            let error = api_client.get_blueprint("web-app").await.unwrap_err();
+            assert!(matches!(error, CliError::ApiError(_)), "{:?}", error);
+        }
+
+        // Clones share the breaker, so the next request is refused without reaching the API
+        let error = api_client.clone().get_blueprint("web-app").await.unwrap_err();
+        assert_eq!(error.code(), "IDP031");
+        assert!(error.to_string().contains("2 consecutive requests"), "{}", error);
+        assert!(error.to_string().contains("HTTP 503"), "{}", error);
+        failing_mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_handle_search_stacks_only() {
+        use mockito::Server;
+
+        let mut server = Server::new_async().await;
+        let stacks_mock = server.mock("GET", "/stacks")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body("[]")
+            .create_async()
+            .await;
+        let blueprints_mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::Any)
+            .with_status(200)
+            .with_body("[]")
+            .expect(0)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let criteria = SearchCriteria {
+            provider: Some("AWS".to_string()),
+            ..Default::default()
+        };
+
+        let result = handle_search(
+            Some(DataSource::Stack),
+            &criteria,
+            DescribeFormat::Human,
+            &api_client,
+        ).await;
+
+        stacks_mock.assert_async().await;
+        blueprints_mock.assert_async().await;
+        assert!(result.is_ok(), "Expected success, got error: {:?}", result.err());
+    }
+
+    #[tokio::test]
+    async fn test_list_stacks_follows_pages_until_limit() {
+        use mockito::{Matcher, Server};
+
+        let stack = |name: &str| serde_json::json!({
+            "id": uuid::Uuid::new_v4(),
+            "name": name,
+            "description": null,
+            "cloud_name": "aws",
+            "stack_type": "RestfulApi",
+            "stack_resources": [],
+            "blueprint": null
+        });
+        // A full first page, so the second one is fetched
+        let first_page: Vec<_> = [stack("payments-prod"), stack("payments-dev"), stack("ui-prod")]
+            .into_iter()
+            .chain((3..LIST_PAGE_SIZE).map(|i| stack(&format!("internal-dev-{}", i))))
+            .collect();
+        let mut server = Server::new_async().await;
+        let first = server.mock("GET", "/stacks")
+            .match_query(Matcher::UrlEncoded("page".into(), "0".into()))
+            .with_status(200)
+            .with_body(serde_json::json!({
+                "content": first_page,
+                "last": false
+            }).to_string())
+            .create_async()
+            .await;
+        let second = server.mock("GET", "/stacks")
+            .match_query(Matcher::UrlEncoded("page".into(), "1".into()))
+            .with_status(200)
+            .with_body(serde_json::json!({
+                "content": [stack("search-prod"), stack("orders-prod")],
+                "last": true
+            }).to_string())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = ListOptions {
+            filter: Some("PROD".to_string()),
+            limit: Some(3),
+            format: ListFormat::Json,
+        };
+        let stacks = api_client.list_stacks(&options.to_query()).await.unwrap();
+
+        first.assert_async().await;
+        second.assert_async().await;
+        let names: Vec<&str> = stacks.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["payments-prod", "ui-prod", "search-prod"]);
+        assert!(handle_list_stacks(&options, &api_client, OutputStyle::default()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_list_blueprints_stops_when_the_api_ignores_pages() {
+        use mockito::{Matcher, Server};
+
+        let blueprint = |name: &str| idp_cli::models::Blueprint {
+            id: uuid::Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        // Every page is the same and none sets `last`
+        let mut server = Server::new_async().await;
+        let short = server.mock("GET", "/blueprints")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body(serde_json::json!({ "content": [blueprint("web-app"), blueprint("api")] }).to_string())
+            .expect(1)
+            .create_async()
+            .await;
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let blueprints = api_client.list_blueprints(&ListQuery::default()).await.unwrap();
+        short.assert_async().await;
+        assert_eq!(blueprints.len(), 2);
+
+        // Full pages forever end after MAX_LIST_PAGES requests
+        let mut server = Server::new_async().await;
+        let full: Vec<_> = (0..LIST_PAGE_SIZE).map(|i| blueprint(&format!("app-{}", i))).collect();
+        let endless = server.mock("GET", "/blueprints")
+            .match_query(Matcher::Any)
+            .with_status(200)
+            .with_body(serde_json::json!({ "content": full }).to_string())
+            .expect(MAX_LIST_PAGES)
+            .create_async()
+            .await;
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let error = api_client.list_blueprints(&ListQuery::default()).await.unwrap_err();
+        endless.assert_async().await;
+        assert!(matches!(error, CliError::ApiError(_)), "{:?}", error);
+    }
+
     #[tokio::test]
     async fn test_handle_list_variables_blueprint_not_found() {
         // This test verifies that handle_list_variables handles 404 errors correctly
@@ -1000,7 +3211,10 @@ mod tests {
         let result = handle_list_variables(
             DataSource::Blueprint,
             "nonexistent-blueprint",
+            &VariableInputs::default(),
+            &ListVariablesOptions::default(),
             &api_client,
+            Reporter::default(),
         ).await;
         
         // Verify the mock was called
@@ -1023,7 +3237,7 @@ mod tests {
         // This test verifies that handle_generate writes files to the output directory
         
         use mockito::Server;
-        use crate::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
+        use idp_cli::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
         use uuid::Uuid;
         
         // Create temporary directories
@@ -1080,6 +3294,8 @@ mod tests {
                     cloud_specific_properties: std::collections::HashMap::new(),
                 }
             ],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![],
         };
         
@@ -1103,9 +3319,11 @@ mod tests {
             DataSource::Blueprint,
             "test-blueprint",
             &template_dir,
-            None,
+            &VariableInputs::default(),
+            &GenerateOptions::default(),
             &api_client,
             &output_dir,
+            Reporter::default(),
         ).await;
         
         // Verify the mock was called
@@ -1128,6 +3346,1234 @@ mod tests {
         
         let deployment_content = fs::read_to_string(output_dir.join("k8s/deployment.yaml")).unwrap();
         assert_eq!(deployment_content, "apiVersion: v1\nkind: Deployment\nmetadata:\n  name: test-resource");
+
+        // The run summary reports the variables no template uses
+        let summary: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join(RUN_SUMMARY_FILE)).unwrap()).unwrap();
+        let unused = summary["variables"]["unused"].as_array().unwrap();
+        assert!(unused.contains(&serde_json::json!("blueprint.name")), "{:?}", unused);
+        assert!(!unused.contains(&serde_json::json!("resources[0].name")), "{:?}", unused);
+        assert_eq!(summary["variables"]["undefined"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_partitions_output() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: Some("Team A/B".to_string()),
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions {
+            partition_by: Some("blueprint.description".to_string()),
+            ..Default::default()
+        };
+
+        let result = handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await;
+
+        assert!(result.is_ok(), "Expected success, got error: {:?}", result.err());
+        assert!(output_dir.join("Team-A-B/main.tf").exists());
+        assert!(!output_dir.join("main.tf").exists());
+        assert!(output_dir.join(RUN_SUMMARY_FILE).exists());
     }
 
+    #[tokio::test]
+    async fn test_handle_generate_writes_backend_tf() {
+        use mockito::Server;
+        use idp_cli::backend_config::BackendKind;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions {
+            backend: Some(BackendKind::S3),
+            backend_config: vec![("bucket".to_string(), "tf-state".to_string())],
+            ..Default::default()
+        };
+        let no_variables = VariableInputs::default();
+        let generate = || {
+            handle_generate(DataSource::Blueprint, "test-blueprint", &template_dir, &no_variables, &options, &api_client, &output_dir, Reporter::default())
+        };
+
+        generate().await.unwrap();
+        let backend = fs::read_to_string(output_dir.join("backend.tf")).unwrap();
+        assert!(backend.contains("backend \"s3\""), "{}", backend);
+        assert!(backend.contains("key = \"test-blueprint/terraform.tfstate\""), "{}", backend);
+        assert!(output_dir.join("main.tf").exists());
+
+        fs::write(template_dir.join("backend.tf"), "terraform {}").unwrap();
+        let err = generate().await.unwrap_err();
+        assert!(matches!(err, CliError::OutputConflictError(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_helm_target_writes_chart() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(template_dir.join("k8s")).unwrap();
+        fs::write(template_dir.join("k8s/deployment.yaml"), "name: {{blueprint.name}}\n").unwrap();
+        fs::write(template_dir.join("values.yaml"), "replicas: 2\n").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "web-app".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "web-app".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions { target: GenerationTarget::Helm, ..Default::default() };
+        handle_generate(DataSource::Blueprint, "web-app", &template_dir, &VariableInputs::default(), &options, &api_client, &output_dir, Reporter::default())
+            .await
+            .unwrap();
+
+        let chart = fs::read_to_string(output_dir.join("Chart.yaml")).unwrap();
+        assert!(chart.contains("name: web-app"), "{}", chart);
+        assert!(output_dir.join("values.yaml").exists());
+        assert_eq!(fs::read_to_string(output_dir.join("templates/k8s/deployment.yaml")).unwrap(), "name: web-app\n");
+        assert_eq!(fs::read_to_string(output_dir.join("templates/values.yaml")).unwrap(), "replicas: 2\n");
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_refuses_stack_in_wrong_state() {
+        use mockito::Server;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{stack.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let id = "6f9619ff-8b86-d011-b42d-00c04fc964ff";
+        let _stack_mock = server.mock("GET", format!("/stacks/{}", id).as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({
+                "id": id,
+                "name": "payments-prod",
+                "description": null,
+                "cloud_name": "aws",
+                "stack_type": "service",
+                "stack_resources": [],
+                "blueprint": null
+            }).to_string())
+            .create_async()
+            .await;
+        let _status_mock = server.mock("GET", format!("/stacks/{}/status", id).as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"state": "PROVISIONING"}"#)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions { require_state: vec!["ACTIVE".to_string()], ..Default::default() };
+        let result = handle_generate(DataSource::Stack, id, &template_dir, &VariableInputs::default(), &options, &api_client, &output_dir, Reporter::default()).await;
+        assert!(matches!(result, Err(CliError::StackStateError(_))), "{:?}", result);
+        assert!(!output_dir.join("main.tf").exists());
+
+        let options = GenerateOptions { on_state_mismatch: StateMismatchPolicy::Warn, ..options };
+        handle_generate(DataSource::Stack, id, &template_dir, &VariableInputs::default(), &options, &api_client, &output_dir, Reporter::default())
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "# payments-prod");
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_dry_run_writes_nothing() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::create_dir(&output_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+        fs::write(output_dir.join("main.tf"), "# old-name").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions {
+            dry_run: true,
+            idempotency_key: Some("preview-1".to_string()),
+            ..Default::default()
+        };
+
+        let result = handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await;
+
+        assert!(result.is_ok(), "Expected success, got error: {:?}", result.err());
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "# old-name");
+        let entries: Vec<_> = fs::read_dir(&output_dir).unwrap().collect();
+        assert_eq!(entries.len(), 1, "dry run must not create run-summary.json or records");
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_on_existing_fail_writes_nothing() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::create_dir(&output_dir).unwrap();
+        fs::write(template_dir.join("a.tf"), "# {{blueprint.name}}").unwrap();
+        fs::write(template_dir.join("z.tf"), "# {{blueprint.name}}").unwrap();
+        fs::write(output_dir.join("z.tf"), "# hand-edited").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions { on_existing: ExistingFilePolicy::Fail, ..Default::default() };
+        let error = handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await.unwrap_err();
+
+        assert!(matches!(error, CliError::ExistingFileError(_)), "{:?}", error);
+        assert!(!output_dir.join("a.tf").exists(), "no file may be written when one exists");
+        assert_eq!(fs::read_to_string(output_dir.join("z.tf")).unwrap(), "# hand-edited");
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_uploads_nothing_when_commit_fails() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(template_dir.join("modules")).unwrap();
+        fs::create_dir(&output_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+        fs::write(template_dir.join("modules/vpc.tf"), "# vpc").unwrap();
+        // A file in the way of modules/ makes moving the staged files into place fail
+        fs::write(output_dir.join("modules"), "").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions {
+            upload_to: Some(S3Location::parse("s3://idp-test-outputs/generated").unwrap()),
+            ..Default::default()
+        };
+        let error = handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await.unwrap_err();
+
+        // Uploading a file that never reached the output directory would fail on S3 first
+        assert!(
+            matches!(&error, CliError::IoError(message) if message.contains("into place")),
+            "{:?}",
+            error
+        );
+        assert!(!output_dir.join("main.tf").exists());
+        assert!(!output_dir.join(".idp-cli").exists());
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_strict_variables_fails_on_undefined_variable() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}\n# {{blueprint.nme}}\n").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions { strict_variables: true, ..Default::default() };
+        let error = handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await.unwrap_err();
+
+        match error {
+            CliError::VariableNotFoundError { variable, suggestion } => {
+                assert_eq!(variable, "blueprint.nme");
+                assert!(suggestion.contains("blueprint.name"), "{}", suggestion);
+            }
+            other => panic!("expected VariableNotFoundError, got {:?}", other),
+        }
+        assert!(!output_dir.join("main.tf").exists(), "nothing may be written with an undefined variable");
+    }
+
+    #[tokio::test]
+    async fn test_check_freshness_after_blueprint_update() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let mut blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: Some("2026-03-01T10:00:00Z".to_string()),
+            supported_cloud_providers: vec![],
+        };
+        let path = format!("/blueprints/{}", blueprint.id);
+        let original = server.mock("GET", path.as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        handle_generate(
+            DataSource::Blueprint,
+            &blueprint.id.to_string(),
+            &template_dir,
+            &VariableInputs::default(),
+            &GenerateOptions::default(),
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await.unwrap();
+
+        let manifest = OutputManifest::load(&output_dir).unwrap().unwrap();
+        assert_eq!(manifest.updated_at.as_deref(), Some("2026-03-01T10:00:00Z"));
+        let templates = manifest.templates.as_ref().unwrap();
+        assert_eq!(templates.source, template_dir.display().to_string());
+        assert_eq!(templates.digest.len(), 64);
+        assert!(handle_check_freshness(DescribeFormat::Human, &api_client, &output_dir).await.is_ok());
+
+        original.remove_async().await;
+        blueprint.updated_at = Some("2026-03-02T08:30:00Z".to_string());
+        let _updated = server.mock("GET", path.as_str())
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let result = handle_check_freshness(DescribeFormat::Json, &api_client, &output_dir).await;
+        match result {
+            Err(CliError::StaleOutputError(message)) => {
+                assert!(message.contains("updated at 2026-03-01T10:00:00Z"), "{}", message);
+                assert!(message.contains("updated at 2026-03-02T08:30:00Z"), "{}", message);
+            }
+            other => panic!("Expected stale output error, got {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_handle_generate_strict_hook_failure_still_records_manifest() {
+        use mockito::{Matcher, Server};
+        use idp_cli::models::Blueprint;
+        use idp_cli::post_hooks::HookConfig;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let options = GenerateOptions {
+            post_hook: vec!["check".to_string()],
+            strict_hooks: true,
+            hooks: [("check".to_string(), HookConfig { command: vec!["false".to_string()] })].into_iter().collect(),
+            ..Default::default()
+        };
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let result = handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await;
+
+        assert!(matches!(result, Err(CliError::HookFailedError(_))), "{:?}", result);
+        let manifest = OutputManifest::load(&output_dir).unwrap().unwrap();
+        assert!(manifest.files.contains_key("main.tf"), "{:?}", manifest.files);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_handle_generate_warns_about_files_the_tool_check_cannot_read() {
+        use mockito::{Matcher, Server};
+        use idp_cli::models::Blueprint;
+        use idp_cli::post_hooks::HookConfig;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        // The hook leaves main.tf as bytes that aren't UTF-8 text
+        let command = ["sh", "-c", "printf '\\377' > main.tf"].map(str::to_string).to_vec();
+        let options = GenerateOptions {
+            post_hook: vec!["garble".to_string()],
+            hooks: [("garble".to_string(), HookConfig { command })].into_iter().collect(),
+            ..Default::default()
+        };
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        ).await.unwrap();
+
+        let summary: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(output_dir.join(RUN_SUMMARY_FILE)).unwrap()).unwrap();
+        let warnings = summary["warnings"].as_array().unwrap();
+        assert!(
+            warnings.iter().any(|w| w.as_str().unwrap().starts_with("Tool version check skipped")),
+            "{:?}",
+            warnings
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_pinned_revision() {
+        use mockito::{Matcher, Server};
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: Some(3),
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("name".into(), "test-blueprint".into()),
+                Matcher::UrlEncoded("revision".into(), "3".into()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let generate = |identifier: &'static str, options: GenerateOptions| {
+            let (api_client, template_dir, output_dir) = (&api_client, &template_dir, &output_dir);
+            async move {
+                handle_generate(
+                    DataSource::Blueprint,
+                    identifier,
+                    template_dir,
+                    &VariableInputs::default(),
+                    &options,
+                    api_client,
+                    output_dir,
+                    Reporter::default(),
+                ).await
+            }
+        };
+
+        generate("test-blueprint@3", GenerateOptions::default()).await.unwrap();
+        let manifest = OutputManifest::load(&output_dir).unwrap().unwrap();
+        assert_eq!(manifest.revision, Some(3));
+
+        // The API ignoring the pin is an error rather than silently generating from another revision
+        let options = GenerateOptions { revision: Some(3), ..Default::default() };
+        assert!(generate("test-blueprint", options).await.is_ok());
+
+        // Without a revision in the response, the pin is kept apart from the source revision
+        let _unversioned = server.mock("GET", "/blueprints")
+            .match_query(Matcher::UrlEncoded("revision".into(), "4".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body({
+                let mut unversioned = serde_json::to_value(&blueprint).unwrap();
+                unversioned["revision"] = serde_json::Value::Null;
+                unversioned.to_string()
+            })
+            .create_async()
+            .await;
+        generate("test-blueprint@4", GenerateOptions::default()).await.unwrap();
+        let manifest = OutputManifest::load(&output_dir).unwrap().unwrap();
+        assert_eq!((manifest.revision, manifest.requested_revision), (None, Some(4)));
+        let _latest = server.mock("GET", "/blueprints")
+            .match_query(Matcher::UrlEncoded("revision".into(), "2".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+        let result = generate("test-blueprint@2", GenerateOptions::default()).await;
+        assert!(matches!(result, Err(CliError::ApiError(ref message)) if message.contains("revision pinning")));
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_diff_fails_on_drift() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::create_dir(&output_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+        fs::write(output_dir.join("main.tf"), "# old-name").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions { diff: true, ..Default::default() };
+        let no_variables = VariableInputs::default();
+        let diff = || handle_generate(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &no_variables,
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        );
+
+        let result = diff().await;
+        assert!(matches!(result, Err(CliError::StaleOutputError(_))), "Expected drift, got {:?}", result);
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "# old-name");
+        assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 1);
+
+        fs::write(output_dir.join("main.tf"), "# test-blueprint").unwrap();
+        assert!(diff().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_fails_on_quota_error() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(
+            template_dir.join("main.tf"),
+            "resource \"aws_db_instance\" \"{{blueprint.name}}\" {\n  count = 3\n}\n",
+        )
+        .unwrap();
+        let quotas_file = temp_dir.path().join("quotas.yaml");
+        fs::write(
+            &quotas_file,
+            "quotas:\n- name: RDS instances per account\n  resource: aws_db_instance\n  max: 2\n  severity: error\n",
+        )
+        .unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        for dry_run in [true, false] {
+            let options = GenerateOptions { dry_run, quotas_file: Some(quotas_file.clone()), ..Default::default() };
+            let result = handle_generate(
+                DataSource::Blueprint,
+                "test-blueprint",
+                &template_dir,
+                &VariableInputs::default(),
+                &options,
+                &api_client,
+                &output_dir,
+                Reporter::default(),
+            )
+            .await;
+            assert!(
+                matches!(result, Err(CliError::QuotaExceededError(ref m)) if m.contains("uses 3 of 2")),
+                "Expected quota error, got {:?}",
+                result
+            );
+            // The breach is found before anything is written
+            assert!(!output_dir.join("main.tf").exists());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_fails_on_policy_deny() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        let policy_dir = temp_dir.path().join("policies");
+        fs::create_dir(&template_dir).unwrap();
+        fs::create_dir(&policy_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "resource \"aws_s3_bucket\" \"{{blueprint.name}}\" {\n}\n").unwrap();
+        fs::write(template_dir.join("app.yaml"), "kind: ConfigMap\nmetadata:\n  name: {{blueprint.name}}\n").unwrap();
+        fs::write(
+            policy_dir.join("s3.rego"),
+            "package s3\n\ndeny contains msg if {\n    some name, bucket in input.content.resource.aws_s3_bucket\n    not bucket.tags\n    msg := sprintf(\"%s has no tags\", [name])\n}\n",
+        )
+        .unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "logs".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let _mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "logs".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let sarif_path = temp_dir.path().join("findings.sarif");
+        let options = GenerateOptions {
+            policy_dir: Some(policy_dir),
+            report: vec![ReportTarget::parse(&format!("sarif={}", sarif_path.display())).unwrap()],
+            ..Default::default()
+        };
+        let result = handle_generate(
+            DataSource::Blueprint,
+            "logs",
+            &template_dir,
+            &VariableInputs::default(),
+            &options,
+            &api_client,
+            &output_dir,
+            Reporter::default(),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(CliError::PolicyViolationError(ref m)) if m == "Policy data.s3 on main.tf: logs has no tags"),
+            "Expected policy violation, got {:?}",
+            result
+        );
+        // Files that pass are held back too, so nothing is written
+        assert!(!output_dir.join("main.tf").exists());
+        assert!(!output_dir.join("app.yaml").exists());
+
+        // The violation is reported against the generated file
+        let sarif: serde_json::Value = serde_json::from_str(&fs::read_to_string(&sarif_path).unwrap()).unwrap();
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "policy/data.s3");
+        assert_eq!(result["level"], "error");
+        let uri = result["locations"][0]["physicalLocation"]["artifactLocation"]["uri"].as_str().unwrap();
+        assert!(uri.ends_with("output/main.tf"), "{}", uri);
+    }
+
+    #[tokio::test]
+    async fn test_handle_preview_fetches_draft_and_published_revisions() {
+        use mockito::{Matcher, Server};
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.description}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let mut blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: Some("published".to_string()),
+            resources: vec![],
+            revision: Some(4),
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let published = server.mock("GET", "/blueprints")
+            .match_query(Matcher::Exact("name=test-blueprint".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .expect(1)
+            .create_async()
+            .await;
+        blueprint.description = Some("draft".to_string());
+        blueprint.revision = Some(5);
+        let draft = server.mock("GET", "/blueprints")
+            .match_query(Matcher::Exact("name=test-blueprint&revision=draft".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let result = handle_preview(
+            DataSource::Blueprint,
+            "test-blueprint",
+            "draft",
+            None,
+            &template_dir,
+            &VariableInputs::default(),
+            &api_client,
+        )
+        .await;
+
+        assert!(result.is_ok(), "Expected success, got error: {:?}", result.err());
+        published.assert_async().await;
+        draft.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_handle_lint_templates_fails_on_undefined_variables() {
+        use mockito::{Matcher, Server};
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}\n").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let mock = server.mock("GET", "/blueprints")
+            .match_query(Matcher::Exact("name=test-blueprint".to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .expect(2)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let sarif_path = temp_dir.path().join("lint.sarif");
+        let junit_path = temp_dir.path().join("lint.xml");
+        let reports = [
+            ReportTarget::parse(&format!("sarif={}", sarif_path.display())).unwrap(),
+            ReportTarget::parse(&format!("junit={}", junit_path.display())).unwrap(),
+        ];
+        let no_variables = VariableInputs::default();
+        let lint = || handle_lint_templates(
+            DataSource::Blueprint,
+            "test-blueprint",
+            &template_dir,
+            &no_variables,
+            None,
+            DescribeFormat::Json,
+            &reports,
+            &api_client,
+        );
+        assert!(lint().await.is_ok());
+
+        fs::write(template_dir.join("outputs.tf"), "# {{blueprint.description}}\n").unwrap();
+        let result = lint().await;
+        assert!(matches!(result, Err(CliError::LintError(_))), "{:?}", result);
+        mock.assert_async().await;
+
+        let sarif: serde_json::Value = serde_json::from_str(&fs::read_to_string(&sarif_path).unwrap()).unwrap();
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "lint/undefined_variable");
+        assert_eq!(result["locations"][0]["physicalLocation"]["region"]["startLine"], 1);
+        let junit = fs::read_to_string(&junit_path).unwrap();
+        assert!(junit.contains("<testsuite name=\"lint-templates\" tests=\"2\" failures=\"1\""), "{}", junit);
+        assert!(junit.contains("main.tf\"/>"), "{}", junit);
+        assert!(junit.contains("type=\"lint/undefined_variable\">line 1: "), "{}", junit);
+    }
+
+    #[tokio::test]
+    async fn test_handle_generate_idempotency_key_skips_retries() {
+        use mockito::Server;
+        use idp_cli::models::Blueprint;
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(template_dir.join("main.tf"), "# {{blueprint.name}}").unwrap();
+
+        let mut server = Server::new_async().await;
+        let blueprint = Blueprint {
+            id: Uuid::new_v4(),
+            name: "test-blueprint".to_string(),
+            description: None,
+            resources: vec![],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let mock = server.mock("GET", "/blueprints")
+            .match_query(mockito::Matcher::UrlEncoded("name".into(), "test-blueprint".into()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::to_string(&blueprint).unwrap())
+            .expect(1)
+            .create_async()
+            .await;
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions {
+            idempotency_key: Some("delivery-1".to_string()),
+            ..Default::default()
+        };
+        let no_variables = VariableInputs::default();
+        let generate = |identifier: &'static str| {
+            handle_generate(DataSource::Blueprint, identifier, &template_dir, &no_variables, &options, &api_client, &output_dir, Reporter::default())
+        };
+
+        generate("test-blueprint").await.unwrap();
+        fs::write(output_dir.join("main.tf"), "edited").unwrap();
+
+        // The retry neither calls the API nor rewrites outputs
+        generate("test-blueprint").await.unwrap();
+        assert_eq!(fs::read_to_string(output_dir.join("main.tf")).unwrap(), "edited");
+        mock.assert_async().await;
+
+        let reused = generate("other-blueprint").await.unwrap_err();
+        assert!(reused.to_string().contains("different request"), "{}", reused);
+    }
+
+    #[tokio::test]
+    async fn test_compose_renders_blueprints_into_one_project() {
+        use mockito::Server;
+        use idp_cli::models::{Blueprint, BlueprintResource, CloudProvider, ResourceType};
+        use uuid::Uuid;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path().join("templates");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir(&template_dir).unwrap();
+        fs::write(
+            template_dir.join("main.tf"),
+            "# {{blueprint.name}}\n# aws {{providers.aws.version}}\n{{#each resources}}# {{qualified_name}}\n{{/each}}",
+        )
+        .unwrap();
+
+        let blueprint = |name: &str, resource: &str, version: &str| Blueprint {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            resources: vec![BlueprintResource {
+                id: Uuid::new_v4(),
+                name: resource.to_string(),
+                description: None,
+                resource_type: ResourceType { id: Uuid::new_v4(), name: "VPC".to_string(), category: "network".to_string() },
+                cloud_provider: CloudProvider {
+                    id: Uuid::new_v4(),
+                    name: "AWS".to_string(),
+                    display_name: "Amazon Web Services".to_string(),
+                },
+                configuration: serde_json::json!({}),
+                cloud_specific_properties: [("provider_version".to_string(), serde_json::json!(version))].into(),
+            }],
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: vec![],
+        };
+        let mut server = Server::new_async().await;
+        let mut mocks = Vec::new();
+        for (name, resource, version) in [("networking", "vpc", "~> 5.0"), ("app", "main", ">= 5.31")] {
+            mocks.push(
+                server.mock("GET", "/blueprints")
+                    .match_query(mockito::Matcher::UrlEncoded("name".into(), name.into()))
+                    .with_status(200)
+                    .with_header("content-type", "application/json")
+                    .with_body(serde_json::to_string(&blueprint(name, resource, version)).unwrap())
+                    .create_async()
+                    .await,
+            );
+        }
+
+        let api_client = ApiClient::new(server.url(), "test-api-key".to_string());
+        let options = GenerateOptions {
+            composed_blueprints: vec!["networking".to_string(), "app".to_string()],
+            skip_tool_check: true,
+            ..Default::default()
+        };
+        handle_generate(DataSource::Blueprint, "networking+app", &template_dir, &VariableInputs::default(), &options, &api_client, &output_dir, Reporter::default())
+            .await
+            .unwrap();
+
+        let main_tf = fs::read_to_string(output_dir.join("main.tf")).unwrap();
+        assert_eq!(main_tf, "# networking + app\n# aws ~> 5.0, >= 5.31\n# networking_vpc\n# app_main\n");
+        let manifest = OutputManifest::load(&output_dir).unwrap().unwrap();
+        let components: Vec<&str> = manifest.sources().iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(components, vec!["networking", "app"]);
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    #[test]
+    fn test_request_hash_covers_output_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let helpers = temp_dir.path().join("helpers.yaml");
+        fs::write(&helpers, "helpers: {}").unwrap();
+        let hash = |options: &GenerateOptions| {
+            generate_request_hash(DataSource::Stack, "payments", temp_dir.path(), &VariableInputs::default(), options).unwrap()
+        };
+
+        let quotas = temp_dir.path().join("quotas.yaml");
+        fs::write(&quotas, "quotas: []").unwrap();
+        let policy_dir = temp_dir.path().join("policies");
+        fs::create_dir(&policy_dir).unwrap();
+        fs::write(policy_dir.join("s3.rego"), "package idp.s3\n").unwrap();
+        let schema_dir = temp_dir.path().join("schemas");
+        fs::create_dir(&schema_dir).unwrap();
+        fs::write(schema_dir.join("app.schema.json"), "{}").unwrap();
+
+        let base = GenerateOptions::default();
+        assert_eq!(hash(&base), hash(&GenerateOptions::default()));
+        let with_helpers = GenerateOptions { helpers_file: Some(helpers.clone()), ..Default::default() };
+        let with_quotas = GenerateOptions { quotas_file: Some(quotas.clone()), ..Default::default() };
+        let with_policies = GenerateOptions { policy_dir: Some(policy_dir.clone()), ..Default::default() };
+        let with_schemas = GenerateOptions { schema_dir: Some(schema_dir.clone()), ..Default::default() };
+        let changed = [
+            GenerateOptions { environment: Some("prod".to_string()), ..Default::default() },
+            GenerateOptions { backend: Some(backend_config::BackendKind::S3), ..Default::default() },
+            GenerateOptions { allow_env: vec!["REGION".to_string()], ..Default::default() },
+            GenerateOptions { upload_kms_key: Some("alias/idp".to_string()), ..Default::default() },
+            GenerateOptions { post_hook: vec!["fmt".to_string()], ..Default::default() },
+            GenerateOptions {
+                hooks: [("lint".to_string(), idp_cli::post_hooks::HookConfig::default())].into(),
+                ..Default::default()
+            },
+            GenerateOptions { strict_hooks: true, ..Default::default() },
+            GenerateOptions { require_state: vec!["ACTIVE".to_string()], ..Default::default() },
+            GenerateOptions { on_state_mismatch: StateMismatchPolicy::Warn, ..Default::default() },
+            with_helpers.clone(),
+            with_quotas.clone(),
+            with_policies.clone(),
+            with_schemas.clone(),
+        ];
+        for options in &changed {
+            assert_ne!(hash(options), hash(&base), "{:?}", options);
+        }
+
+        // Helpers, quotas, policies and schemas are hashed by content, not only by path
+        let before = hash(&with_helpers);
+        fs::write(&helpers, "helpers: {shout: '{{uppercase this}}'}").unwrap();
+        assert_ne!(hash(&with_helpers), before);
+        let before = hash(&with_quotas);
+        fs::write(&quotas, "quotas: [{resource: aws_instance, max: 1}]").unwrap();
+        assert_ne!(hash(&with_quotas), before);
+        let before = hash(&with_policies);
+        fs::write(policy_dir.join("s3.rego"), "package idp.s3\n\ndeny contains \"no\"\n").unwrap();
+        assert_ne!(hash(&with_policies), before);
+        let before = hash(&with_schemas);
+        fs::write(schema_dir.join("app.schema.json"), r#"{"type": "object"}"#).unwrap();
+        assert_ne!(hash(&with_schemas), before);
+
+        // Variables files are hashed by content and in order, --var assignments by value
+        let common = temp_dir.path().join("common.yaml");
+        let prod = temp_dir.path().join("prod.yaml");
+        fs::write(&common, "environment: staging").unwrap();
+        fs::write(&prod, "environment: production").unwrap();
+        let hash_variables = |var_files: &[&std::path::Path], vars: &[(&str, &str)]| {
+            let variables = VariableInputs {
+                var_files: var_files.iter().map(|path| path.to_path_buf()).collect(),
+                vars: vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            };
+            generate_request_hash(DataSource::Stack, "payments", temp_dir.path(), &variables, &base).unwrap()
+        };
+        let before = hash_variables(&[&common, &prod], &[]);
+        assert_ne!(before, hash(&base));
+        assert_ne!(before, hash_variables(&[&prod, &common], &[]));
+        assert_ne!(before, hash_variables(&[&common, &prod], &[("replicas", "3")]));
+        fs::write(&prod, "environment: prod").unwrap();
+        assert_ne!(before, hash_variables(&[&common, &prod], &[]));
+    }
+
+    #[test]
+    fn test_handle_eject_keeps_existing_templates_without_force() {
+        let temp_dir = TempDir::new().unwrap();
+        let out = temp_dir.path().join("templates");
+        handle_eject(BuiltinPack::K8sMicroservice, &out, false, DescribeFormat::Json).unwrap();
+        let deployment = out.join("deployment.yaml");
+        assert!(deployment.exists());
+        assert!(out.join("helpers/k8s_name.hbs").exists());
+
+        fs::write(&deployment, "# customized\n").unwrap();
+        let error = handle_eject(BuiltinPack::K8sMicroservice, &out, false, DescribeFormat::Json).unwrap_err();
+        assert!(matches!(error, CliError::ExistingFileError(_)), "{:?}", error);
+        assert_eq!(fs::read_to_string(&deployment).unwrap(), "# customized\n");
+
+        handle_eject(BuiltinPack::K8sMicroservice, &out, true, DescribeFormat::Json).unwrap();
+        assert_ne!(fs::read_to_string(&deployment).unwrap(), "# customized\n");
+    }
 }
@@ -9,6 +9,12 @@ pub struct Blueprint {
     pub description: Option<String>,
     pub resources: Vec<BlueprintResource>,
     pub supported_cloud_providers: Vec<CloudProvider>,
+    /// Revision number, if the API versions blueprints and stacks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    /// Last modification time (RFC 3339), if the API exposes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -31,6 +37,12 @@ pub struct Stack {
     pub stack_type: String,
     pub stack_resources: Vec<StackResource>,
     pub blueprint: Option<Blueprint>,
+    /// Revision number, if the API versions blueprints and stacks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    /// Last modification time (RFC 3339), if the API exposes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -56,3 +68,42 @@ pub struct CloudProvider {
     pub name: String,
     pub display_name: String,
 }
+
+/// Deployment state of a stack, from `GET /stacks/{id}/status`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StackStatus {
+    /// State name, e.g. `ACTIVE`, `PROVISIONING` or `FAILED`
+    pub state: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Time of the last state change (RFC 3339), if the API exposes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+}
+
+/// Deployment state of a stack resource, from `GET /stacks/{id}/resources`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StackResourceState {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_type: Option<String>,
+    pub state: String,
+    /// ID of the resource in its cloud, once provisioned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Entry of a stack's event log, from `GET /stacks/{id}/events`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StackEvent {
+    /// Time of the event (RFC 3339)
+    pub timestamp: String,
+    /// Event type, e.g. `DEPLOYMENT_STARTED`
+    pub event_type: String,
+    /// Resource the event is about, if not the whole stack
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource: Option<String>,
+    pub message: String,
+}
@@ -0,0 +1,407 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::cli::DataSource;
+use crate::debug_bundle;
+use crate::error::CliError;
+use crate::models::{Blueprint, Stack};
+use crate::paths;
+use crate::template_discovery::{self, TemplateFile};
+use crate::variable_context::VariableContext;
+
+/// Manifest path relative to the output directory
+pub const OUTPUT_MANIFEST_FILE: &str = ".idp-cli/manifest.json";
+
+/// Directory, relative to the output directory, with a copy of each generation's manifest
+pub const MANIFEST_HISTORY_DIR: &str = ".idp-cli/history";
+
+/// Number of manifests kept in the history
+pub const MANIFEST_HISTORY_LIMIT: usize = 20;
+
+/// Record of the blueprint or stack revision an output directory was generated from
+///
+/// Written next to the generated files so later runs and `check-freshness`
+/// can tell whether the committed output is behind the server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputManifest {
+    /// "blueprint" or "stack"
+    pub data_source: String,
+    pub id: String,
+    pub name: String,
+    /// Revision generated from, as reported by the API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    /// Revision pinned with `<name>@<revision>` or `--revision` when the API
+    /// didn't report one, so it could not be confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_revision: Option<u64>,
+    /// `updated_at` of the blueprint or stack, if the API exposes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
+    /// Generation time in seconds since the Unix epoch
+    pub generated_at: u64,
+    pub cli_version: String,
+    /// Names of the blueprint or stack resources
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub resources: Vec<String>,
+    /// SHA-256 of each generated file, by path relative to the output directory
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>,
+    /// Scalar variable values, with sensitive values masked
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, Value>,
+    /// Variables set or changed by config file defaults, `--var-file` or `--var`,
+    /// with sensitive values masked
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, Value>,
+    /// Templates the output was rendered from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub templates: Option<TemplateProvenance>,
+    /// Blueprints an output composed with `compose` was generated from
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<OutputManifest>,
+}
+
+/// Where the templates of a generation came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateProvenance {
+    /// Template directory, or the `--template-source` S3 URL
+    pub source: String,
+    /// SHA-256 over the paths and contents of the templates used
+    pub digest: String,
+}
+
+/// How a manifest compares with the server's current revision
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Freshness {
+    Current,
+    Stale { generated_from: String, current: String },
+    /// The manifest or the API has no `updated_at`
+    Unknown,
+}
+
+impl OutputManifest {
+    pub fn from_blueprint(blueprint: &Blueprint) -> Self {
+        let mut manifest =
+            Self::new(DataSource::Blueprint, blueprint.id.to_string(), &blueprint.name, blueprint.revision, &blueprint.updated_at);
+        manifest.resources = blueprint.resources.iter().map(|r| r.name.clone()).collect();
+        manifest
+    }
+
+    pub fn from_stack(stack: &Stack) -> Self {
+        let mut manifest = Self::new(DataSource::Stack, stack.id.to_string(), &stack.name, stack.revision, &stack.updated_at);
+        manifest.resources = stack.stack_resources.iter().map(|r| r.name.clone()).collect();
+        manifest
+    }
+
+    /// Manifest of a composed output, recording each blueprint as a component
+    pub fn from_composition(blueprints: &[Blueprint]) -> Self {
+        let components: Vec<Self> = blueprints.iter().map(Self::from_blueprint).collect();
+        let ids: Vec<&str> = components.iter().map(|c| c.id.as_str()).collect();
+        let names: Vec<&str> = components.iter().map(|c| c.name.as_str()).collect();
+        let mut manifest = Self::new(DataSource::Blueprint, ids.join("+"), &names.join(" + "), None, &None);
+        manifest.resources = components.iter().flat_map(|c| c.resources.clone()).collect();
+        manifest.components = components;
+        manifest
+    }
+
+    fn new(data_source: DataSource, id: String, name: &str, revision: Option<u64>, updated_at: &Option<String>) -> Self {
+        Self {
+            data_source: data_source.to_string(),
+            id,
+            name: name.to_string(),
+            revision,
+            requested_revision: None,
+            updated_at: updated_at.clone(),
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            cli_version: env!("CARGO_PKG_VERSION").to_string(),
+            resources: Vec::new(),
+            files: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            overrides: BTreeMap::new(),
+            templates: None,
+            components: Vec::new(),
+        }
+    }
+
+    /// Record the scalar variables templates saw, masking sensitive values
+    pub fn record_variables(&mut self, context: &VariableContext) {
+        let (variables, _) = debug_bundle::redact_variables(context);
+        self.variables = variables
+            .into_iter()
+            .filter(|(_, value)| !(value.is_object() || value.is_array()))
+            .collect();
+    }
+
+    /// Record the variables that differ from those built from the API data
+    ///
+    /// `base` is the context before defaults and custom variables were merged.
+    pub fn record_overrides(&mut self, base: &VariableContext, context: &VariableContext) {
+        let (variables, _) = debug_bundle::redact_variables(context);
+        self.overrides = variables
+            .into_iter()
+            .filter(|(name, value)| {
+                !(value.is_object() || value.is_array()) && base.get(name) != context.get(name)
+            })
+            .collect();
+    }
+
+    /// Record where the templates came from and a digest of their contents
+    pub fn record_templates(&mut self, source: String, templates: &[TemplateFile]) -> Result<(), CliError> {
+        let digest = template_discovery::templates_digest(templates)
+            .map_err(|e| CliError::IoError(format!("Failed to read templates: {}", e)))?;
+        self.templates = Some(TemplateProvenance { source, digest });
+        Ok(())
+    }
+
+    /// Record a digest of each generated file
+    pub fn record_files(&mut self, output_dir: &Path, written_files: &[PathBuf]) -> Result<(), CliError> {
+        for path in written_files {
+            let content = std::fs::read(path).map_err(|e| {
+                CliError::IoError(format!("Failed to read generated file {}: {}", path.display(), e))
+            })?;
+            let relative = path.strip_prefix(output_dir).unwrap_or(path);
+            self.files.insert(paths::to_slash(relative), template_discovery::sha256_hex(&content));
+        }
+        Ok(())
+    }
+
+    /// Data source recorded in the manifest
+    pub fn source(&self) -> DataSource {
+        match self.data_source.as_str() {
+            "stack" => DataSource::Stack,
+            _ => DataSource::Blueprint,
+        }
+    }
+
+    /// Blueprints or stacks whose freshness decides this output's: the
+    /// components of a composed output, otherwise the manifest itself
+    pub fn sources(&self) -> Vec<&OutputManifest> {
+        if self.components.is_empty() {
+            vec![self]
+        } else {
+            self.components.iter().collect()
+        }
+    }
+
+    /// Whether both manifests describe the same blueprint or stack
+    pub fn same_source(&self, other: &OutputManifest) -> bool {
+        self.data_source == other.data_source && self.id == other.id
+    }
+
+    /// Compare the recorded revision with the server's current `updated_at`
+    ///
+    /// Timestamps are compared as written by the API, so any difference
+    /// counts as stale.
+    pub fn freshness(&self, current: Option<&str>) -> Freshness {
+        match (self.updated_at.as_deref(), current) {
+            (Some(recorded), Some(current)) if recorded == current => Freshness::Current,
+            (Some(recorded), Some(current)) => Freshness::Stale {
+                generated_from: recorded.to_string(),
+                current: current.to_string(),
+            },
+            _ => Freshness::Unknown,
+        }
+    }
+
+    /// Read the manifest of an output directory, None if there is none
+    pub fn load(output_dir: &Path) -> Result<Option<Self>, CliError> {
+        let path = output_dir.join(OUTPUT_MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            CliError::IoError(format!("Failed to read output manifest {}: {}", path.display(), e))
+        })?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// Manifests in an output directory, or in its partition subdirectories
+    pub fn find_all(output_dir: &Path) -> Result<Vec<(PathBuf, Self)>, CliError> {
+        if let Some(manifest) = Self::load(output_dir)? {
+            return Ok(vec![(output_dir.to_path_buf(), manifest)]);
+        }
+        let mut found = Vec::new();
+        let Ok(entries) = std::fs::read_dir(output_dir) else {
+            return Ok(found);
+        };
+        let mut dirs: Vec<PathBuf> = entries.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_dir()).collect();
+        dirs.sort();
+        for dir in dirs {
+            if let Some(manifest) = Self::load(&dir)? {
+                found.push((dir, manifest));
+            }
+        }
+        Ok(found)
+    }
+
+    /// Write the manifest as pretty-printed JSON and add it to the history
+    ///
+    /// Only the newest [`MANIFEST_HISTORY_LIMIT`] history entries are kept.
+    pub fn write_to(&self, output_dir: &Path) -> Result<PathBuf, CliError> {
+        let json = serde_json::to_string_pretty(self)?;
+        let history_dir = output_dir.join(MANIFEST_HISTORY_DIR);
+        write_file(&history_dir.join(format!("manifest-{}.json", self.generated_at)), &json)?;
+        let path = output_dir.join(OUTPUT_MANIFEST_FILE);
+        write_file(&path, &json)?;
+
+        let mut entries = history_files(&history_dir);
+        if entries.len() > MANIFEST_HISTORY_LIMIT {
+            let expired = entries.len() - MANIFEST_HISTORY_LIMIT;
+            for entry in entries.drain(..expired) {
+                if let Err(e) = std::fs::remove_file(&entry) {
+                    log::warn!("Failed to remove old manifest {}: {}", entry.display(), e);
+                }
+            }
+        }
+        Ok(path)
+    }
+
+    /// Manifests of earlier generations in an output directory, oldest first
+    pub fn history(output_dir: &Path) -> Result<Vec<Self>, CliError> {
+        history_files(&output_dir.join(MANIFEST_HISTORY_DIR))
+            .iter()
+            .map(|path| {
+                let content = std::fs::read_to_string(path).map_err(|e| {
+                    CliError::IoError(format!("Failed to read output manifest {}: {}", path.display(), e))
+                })?;
+                Ok(serde_json::from_str(&content)?)
+            })
+            .collect()
+    }
+}
+
+/// History entries sorted by generation time
+fn history_files(history_dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(history_dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<(u64, PathBuf)> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let generated_at = name.strip_prefix("manifest-")?.strip_suffix(".json")?.parse().ok()?;
+            Some((generated_at, e.path()))
+        })
+        .collect();
+    files.sort();
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+fn write_file(path: &Path, content: &str) -> Result<(), CliError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            CliError::IoError(format!("Failed to create directory {}: {}", parent.display(), e))
+        })?;
+    }
+    std::fs::write(path, content).map_err(|e| {
+        CliError::IoError(format!("Failed to write output manifest {}: {}", path.display(), e))
+    })
+}
+
+impl Freshness {
+    /// Warning for stale output, e.g. when regenerating over it
+    pub fn warning(&self, manifest: &OutputManifest, output_dir: &Path) -> Option<String> {
+        match self {
+            Freshness::Stale { generated_from, current } => Some(format!(
+                "{} was generated from {} '{}' updated at {}, but the current revision was updated at {}",
+                output_dir.display(),
+                manifest.data_source,
+                manifest.name,
+                generated_from,
+                current
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(updated_at: Option<&str>) -> OutputManifest {
+        OutputManifest {
+            data_source: "blueprint".to_string(),
+            id: "b1".to_string(),
+            name: "web-app".to_string(),
+            revision: None,
+            requested_revision: None,
+            updated_at: updated_at.map(str::to_string),
+            generated_at: 0,
+            cli_version: "0.0.0".to_string(),
+            resources: Vec::new(),
+            files: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            overrides: BTreeMap::new(),
+            templates: None,
+            components: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_record_overrides_masks_secrets() {
+        let mut base = VariableContext::new();
+        base.insert("stack.name".to_string(), serde_json::json!("payments"));
+        base.insert("region".to_string(), serde_json::json!("us-east-1"));
+        let mut context = base.clone();
+        context.insert("region".to_string(), serde_json::json!("eu-west-1"));
+        context.insert("db_password".to_string(), serde_json::json!("hunter2"));
+        context.insert("tags".to_string(), serde_json::json!({"team": "payments"}));
+        context.insert("tags.team".to_string(), serde_json::json!("payments"));
+
+        let mut recorded = manifest(None);
+        recorded.record_overrides(&base, &context);
+        assert_eq!(
+            serde_json::to_value(&recorded.overrides).unwrap(),
+            serde_json::json!({"db_password": debug_bundle::REDACTED, "region": "eu-west-1", "tags.team": "payments"})
+        );
+    }
+
+    #[test]
+    fn test_freshness_compares_updated_at() {
+        let recorded = manifest(Some("2026-01-01T00:00:00Z"));
+        assert_eq!(recorded.freshness(Some("2026-01-01T00:00:00Z")), Freshness::Current);
+        assert_eq!(recorded.freshness(None), Freshness::Unknown);
+        assert_eq!(manifest(None).freshness(Some("2026-01-01T00:00:00Z")), Freshness::Unknown);
+
+        let stale = recorded.freshness(Some("2026-02-01T00:00:00Z"));
+        assert_eq!(
+            stale.warning(&recorded, Path::new("out")).unwrap(),
+            "out was generated from blueprint 'web-app' updated at 2026-01-01T00:00:00Z, \
+             but the current revision was updated at 2026-02-01T00:00:00Z"
+        );
+    }
+
+    #[test]
+    fn test_find_all_reads_partition_manifests() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(OutputManifest::find_all(dir.path()).unwrap().is_empty());
+
+        manifest(Some("t1")).write_to(&dir.path().join("us-east")).unwrap();
+        manifest(None).write_to(&dir.path().join("eu-west")).unwrap();
+        let found = OutputManifest::find_all(dir.path()).unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(found[0].0.ends_with("eu-west"));
+        assert_eq!(found[1].1.updated_at.as_deref(), Some("t1"));
+    }
+
+    #[test]
+    fn test_history_keeps_newest_generations() {
+        let dir = tempfile::tempdir().unwrap();
+        for generated_at in 0..(MANIFEST_HISTORY_LIMIT as u64 + 2) {
+            OutputManifest { generated_at, ..manifest(None) }.write_to(dir.path()).unwrap();
+        }
+        let history = OutputManifest::history(dir.path()).unwrap();
+        assert_eq!(history.len(), MANIFEST_HISTORY_LIMIT);
+        assert_eq!(history[0].generated_at, 2);
+        assert_eq!(OutputManifest::load(dir.path()).unwrap().unwrap().generated_at, MANIFEST_HISTORY_LIMIT as u64 + 1);
+    }
+}
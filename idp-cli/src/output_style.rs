@@ -0,0 +1,110 @@
+/// How human-readable command output is decorated
+///
+/// `Plain` drops box-drawing rules, check marks and column padding so every
+/// line reads on its own, which works better with screen readers and simple
+/// terminals. Both styles carry the same information.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputStyle {
+    #[default]
+    Decorated,
+    Plain,
+}
+
+impl OutputStyle {
+    /// Style selected by the `--plain` flag
+    pub fn from_flag(plain: bool) -> Self {
+        if plain {
+            OutputStyle::Plain
+        } else {
+            OutputStyle::Decorated
+        }
+    }
+
+    pub fn is_plain(&self) -> bool {
+        *self == OutputStyle::Plain
+    }
+
+    /// Horizontal rule of the given width, or None in plain mode
+    pub fn rule(&self, width: usize) -> Option<String> {
+        match self {
+            OutputStyle::Decorated => Some("=".repeat(width)),
+            OutputStyle::Plain => None,
+        }
+    }
+
+    /// Summary line for a completed operation
+    pub fn success(&self, message: &str) -> String {
+        match self {
+            OutputStyle::Decorated => format!("✓ {}", message),
+            OutputStyle::Plain => message.to_string(),
+        }
+    }
+
+    /// List entry for an item that succeeded
+    pub fn item_ok(&self, item: &str) -> String {
+        match self {
+            OutputStyle::Decorated => format!("  ✓ {}", item),
+            OutputStyle::Plain => format!("ok: {}", item),
+        }
+    }
+
+    /// List entry for an item that failed
+    pub fn item_failed(&self, item: &str) -> String {
+        match self {
+            OutputStyle::Decorated => format!("  ✗ {}", item),
+            OutputStyle::Plain => format!("failed: {}", item),
+        }
+    }
+
+    /// Indentation for a tree level; plain output is not indented
+    pub fn indent(&self, depth: usize) -> String {
+        match self {
+            OutputStyle::Decorated => "  ".repeat(depth),
+            OutputStyle::Plain => String::new(),
+        }
+    }
+
+    /// Example line pairing a snippet with its description
+    ///
+    /// Decorated output pads the snippet into a column; plain output puts the
+    /// description first so it is read before the symbols.
+    pub fn example(&self, snippet: &str, description: &str) -> String {
+        match self {
+            OutputStyle::Decorated => format!("  {:<36} - {}", snippet, description),
+            OutputStyle::Plain => format!("{}: {}", description, snippet),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_output_has_no_symbols_or_padding() {
+        let style = OutputStyle::from_flag(true);
+        let lines = [
+            style.success("Successfully generated 2 file(s)"),
+            style.item_ok("main.tf"),
+            style.item_failed("broken.yaml"),
+            style.example("{{variable_name}}", "Simple variable substitution"),
+        ];
+        for line in &lines {
+            assert!(line.is_ascii(), "{}", line);
+            assert!(!line.starts_with(' '), "{}", line);
+            assert!(!line.contains("  "), "{}", line);
+        }
+        assert_eq!(style.rule(80), None);
+        assert_eq!(style.indent(3), "");
+    }
+
+    #[test]
+    fn test_decorated_output_matches_previous_layout() {
+        let style = OutputStyle::default();
+        assert_eq!(style.item_ok("main.tf"), "  ✓ main.tf");
+        assert_eq!(
+            style.example("{{variable_name}}", "Simple variable substitution"),
+            "  {{variable_name}}                    - Simple variable substitution"
+        );
+    }
+}
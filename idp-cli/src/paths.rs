@@ -0,0 +1,97 @@
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+
+use crate::error::CliError;
+
+/// A relative path with `/` separators, as shown in output, manifests and digests
+///
+/// Keeps generated output the same on Windows and Unix.
+pub fn to_slash(path: &Path) -> String {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            Component::ParentDir => Some(Cow::Borrowed("..")),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Key under which two output paths overwrite each other on a case-insensitive
+/// file system (the default on Windows and macOS)
+pub fn case_key(path: &Path) -> String {
+    to_slash(path).to_lowercase()
+}
+
+/// The path to hand to file system calls
+///
+/// On Windows this is the absolute path with the `\\?\` prefix, which lifts the
+/// 260 character `MAX_PATH` limit that deep template trees and long resource
+/// names quickly exceed. Other platforms have no such limit and get the path
+/// unchanged.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        if let Some(verbatim) = std::path::absolute(path)
+            .ok()
+            .and_then(|absolute| absolute.to_str().and_then(verbatim))
+        {
+            return Cow::Owned(verbatim.into());
+        }
+    }
+    Cow::Borrowed(path)
+}
+
+/// Resolve a requested template directory inside a local templates root
+///
+/// Rejects absolute paths and `..` so requests can't read outside the root.
+pub fn local_template_path(root: &Path, template_dir: &str) -> Result<PathBuf, CliError> {
+    let relative = Path::new(template_dir);
+    if relative
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(CliError::ConfigurationError(format!(
+            "Template directory must be a relative path inside the templates root: {}",
+            template_dir
+        )));
+    }
+    Ok(root.join(relative))
+}
+
+/// `\\?\` form of an absolute Windows path, or None if it has one already or
+/// isn't a drive or UNC path
+#[cfg_attr(not(windows), allow(dead_code))]
+fn verbatim(absolute: &str) -> Option<String> {
+    if absolute.starts_with(r"\\?\") || absolute.starts_with(r"\\.\") {
+        None
+    } else if let Some(share) = absolute.strip_prefix(r"\\") {
+        Some(format!(r"\\?\UNC\{}", share))
+    } else if absolute.as_bytes().get(1) == Some(&b':') {
+        Some(format!(r"\\?\{}", absolute))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_paths_compare_the_same_on_every_platform() {
+        let path: PathBuf = ["infrastructure", "prod", "Main.tf"].iter().collect();
+        assert_eq!(to_slash(&path), "infrastructure/prod/Main.tf");
+        assert_eq!(to_slash(Path::new("./k8s/service.yaml")), "k8s/service.yaml");
+        assert_eq!(case_key(&path), case_key(Path::new("infrastructure/prod/main.tf")));
+        if !cfg!(windows) {
+            assert_eq!(long_path(&path).as_ref(), path.as_path());
+        }
+
+        assert_eq!(verbatim(r"C:\Users\dev\out\main.tf").as_deref(), Some(r"\\?\C:\Users\dev\out\main.tf"));
+        assert_eq!(verbatim(r"\\build\share\out").as_deref(), Some(r"\\?\UNC\build\share\out"));
+        assert_eq!(verbatim(r"\\?\C:\out"), None);
+        assert_eq!(verbatim("/home/dev/out"), None);
+    }
+}
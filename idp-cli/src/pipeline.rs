@@ -0,0 +1,340 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+use crate::error::CliError;
+use crate::paths;
+use crate::template_discovery::TemplateFile;
+use crate::template_processor::{ProcessedFile, TemplateProcessor};
+
+/// Number of rendered files that may wait for the writer at any time
+///
+/// Bounds peak memory to a handful of outputs regardless of how many templates
+/// a run renders.
+pub const DEFAULT_CHANNEL_CAPACITY: usize = 8;
+
+/// Counters collected while rendering into a channel
+#[derive(Debug, Clone, Default)]
+pub struct RenderStats {
+    pub files_rendered: usize,
+    pub bytes_rendered: u64,
+    /// Time spent waiting for renders, excluding time waiting for the consumer
+    pub render_time: Duration,
+}
+
+/// Create the bounded channel that connects the renderer to a writer/uploader
+pub fn file_channel(capacity: usize) -> (mpsc::Sender<ProcessedFile>, mpsc::Receiver<ProcessedFile>) {
+    mpsc::channel(capacity.max(1))
+}
+
+/// Templates to render at once when `--concurrency` is not given: one per CPU
+pub fn default_concurrency() -> usize {
+    std::thread::available_parallelism().map(usize::from).unwrap_or(1)
+}
+
+/// Render templates, sending each processed file in template order
+///
+/// A template whose path loops over a list sends one file per item; two
+/// files rendering to the same path, ignoring case, fail the run.
+///
+/// Up to `concurrency` templates render at once on the blocking thread pool,
+/// starting the next as soon as the oldest is sent, so rendering carries on
+/// while the consumer works. Files are still sent in the order of
+/// `templates`, and the first template (in that order) that fails stops the
+/// run. Waits whenever the channel is full, so at most `capacity` +
+/// `concurrency` rendered files are held in memory. Run this concurrently
+/// with the consumer (e.g. with `tokio::join!`) and combine the results with
+/// [`join_results`]. Rendering fails if the consumer hangs up, which it only
+/// does after an error of its own.
+pub async fn render_to_channel(
+    processor: &TemplateProcessor<'_>,
+    templates: &[TemplateFile],
+    sender: mpsc::Sender<ProcessedFile>,
+    concurrency: usize,
+) -> Result<RenderStats, CliError> {
+    render_to_channel_with(processor, templates, Vec::new(), sender, concurrency).await
+}
+
+/// [`render_to_channel`], then send `generated` files that don't come from a
+/// template, e.g. `backend.tf`
+///
+/// Generated files are checked against the rendered paths like rendered
+/// files are, so a template rendering to the same path fails the run
+/// instead of one overwriting the other.
+pub async fn render_to_channel_with(
+    processor: &TemplateProcessor<'_>,
+    templates: &[TemplateFile],
+    generated: Vec<ProcessedFile>,
+    sender: mpsc::Sender<ProcessedFile>,
+    concurrency: usize,
+) -> Result<RenderStats, CliError> {
+    let mut output = OutputSender { sender, stats: RenderStats::default(), paths: HashMap::new() };
+    let processor = Arc::new(processor.to_owned_context());
+    let mut queued = templates.iter();
+    let mut rendering = VecDeque::new();
+
+    loop {
+        while rendering.len() < concurrency.max(1) {
+            let Some(template) = queued.next() else { break };
+            let processor = Arc::clone(&processor);
+            let file = template.clone();
+            let render = tokio::task::spawn_blocking(move || processor.process_file_outputs(&file));
+            rendering.push_back((template, render));
+        }
+        let Some((template, render)) = rendering.pop_front() else { break };
+
+        let started = Instant::now();
+        let rendered = render.await.unwrap_or_else(|_| {
+            Err(CliError::ProcessingError(format!(
+                "Rendering '{}' panicked",
+                template.relative_path.display()
+            )))
+        });
+        output.stats.render_time += started.elapsed();
+
+        for processed in rendered? {
+            output.send(processed, false).await?;
+        }
+    }
+    for processed in generated {
+        output.send(processed, true).await?;
+    }
+
+    Ok(output.stats)
+}
+
+/// Sending side of the pipeline, tracking the paths sent so far
+struct OutputSender {
+    sender: mpsc::Sender<ProcessedFile>,
+    stats: RenderStats,
+    /// Sent paths by [`paths::case_key`]
+    paths: HashMap<String, PathBuf>,
+}
+
+impl OutputSender {
+    async fn send(&mut self, processed: ProcessedFile, generated: bool) -> Result<(), CliError> {
+        // Rendered paths can meet even when the template paths differ, also
+        // when only their case differs on a case-insensitive file system
+        let key = paths::case_key(&processed.relative_path);
+        if let Some(existing) = self.paths.insert(key, processed.relative_path.clone()) {
+            return Err(CliError::OutputConflictError(if generated {
+                format!(
+                    "'{}' is rendered from a template and also generated by an option such as --backend; remove one of them",
+                    paths::to_slash(&existing)
+                )
+            } else if existing == processed.relative_path {
+                format!("More than one template renders to '{}'", processed.path_str())
+            } else {
+                format!(
+                    "'{}' and '{}' differ only in case",
+                    paths::to_slash(&existing),
+                    processed.path_str()
+                )
+            }));
+        }
+        self.stats.files_rendered += 1;
+        self.stats.bytes_rendered += processed.content_len() as u64;
+
+        self.sender.send(processed).await.map_err(|_| {
+            CliError::GenerationError("The output writer stopped before every file was sent".to_string())
+        })
+    }
+}
+
+/// Combine the results of the renderer and its consumer
+///
+/// A consumer error wins, since the renderer fails too once the consumer
+/// stops. Otherwise a render error wins: the consumer only sees a closed
+/// channel in that case.
+pub fn join_results<T>(
+    rendered: Result<RenderStats, CliError>,
+    consumed: Result<T, CliError>,
+) -> Result<(RenderStats, T), CliError> {
+    let consumed = consumed?;
+    Ok((rendered?, consumed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_discovery::TemplateFileType;
+    use crate::variable_context::VariableContext;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn template(name: &str, content: &str) -> (TemplateFile, tempfile::NamedTempFile) {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), content).unwrap();
+        let template = TemplateFile {
+            path: file.path().to_path_buf(),
+            relative_path: PathBuf::from(name),
+            file_type: TemplateFileType::Terraform,
+        };
+        (template, file)
+    }
+
+    fn context() -> VariableContext {
+        let mut context = VariableContext::new();
+        context.insert("name".to_string(), json!("demo"));
+        context
+    }
+
+    #[tokio::test]
+    async fn test_streams_files_through_bounded_channel() {
+        let context = context();
+        let processor = TemplateProcessor::new(&context);
+        let (templates, _files): (Vec<_>, Vec<_>) = (0..5)
+            .map(|i| template(&format!("file-{}.tf", i), "name = \"{{name}}\""))
+            .unzip();
+
+        let (sender, mut receiver) = file_channel(1);
+        let (rendered, consumed) = tokio::join!(
+            render_to_channel(&processor, &templates, sender, 1),
+            async {
+                let mut paths = Vec::new();
+                while let Some(file) = receiver.recv().await {
+                    assert_eq!(file.content, "name = \"demo\"");
+                    paths.push(file.path_str());
+                }
+                Ok::<_, CliError>(paths)
+            }
+        );
+
+        let (stats, paths) = join_results(rendered, consumed).unwrap();
+        assert_eq!(stats.files_rendered, 5);
+        assert_eq!(paths.len(), 5);
+        assert_eq!(paths[0], "file-0.tf");
+    }
+
+    #[tokio::test]
+    async fn test_consumer_error_stops_rendering() {
+        let context = context();
+        let processor = TemplateProcessor::new(&context);
+        let (templates, _files): (Vec<_>, Vec<_>) = (0..5)
+            .map(|i| template(&format!("file-{}.tf", i), "{{name}}"))
+            .unzip();
+
+        let (sender, mut receiver) = file_channel(1);
+        let (rendered, consumed) = tokio::join!(
+            render_to_channel(&processor, &templates, sender, 1),
+            async move {
+                receiver.recv().await;
+                Err::<(), _>(CliError::IoError("disk full".to_string()))
+            }
+        );
+
+        assert!(rendered.is_err());
+        let err = join_results(rendered, consumed).unwrap_err();
+        assert!(err.to_string().contains("disk full"));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_rendering_keeps_template_order() {
+        let context = context();
+        let processor = TemplateProcessor::new(&context);
+        let (templates, _files): (Vec<_>, Vec<_>) = (0..7)
+            .map(|i| template(&format!("file-{}.tf", i), &format!("file{} = \"{{{{name}}}}\"", i)))
+            .unzip();
+
+        let (sender, mut receiver) = file_channel(2);
+        let (rendered, consumed) = tokio::join!(
+            render_to_channel(&processor, &templates, sender, 3),
+            async {
+                let mut contents = Vec::new();
+                while let Some(file) = receiver.recv().await {
+                    contents.push(file.content);
+                }
+                Ok::<_, CliError>(contents)
+            }
+        );
+
+        let (stats, contents) = join_results(rendered, consumed).unwrap();
+        assert_eq!(stats.files_rendered, 7);
+        assert_eq!(contents, (0..7).map(|i| format!("file{} = \"demo\"", i)).collect::<Vec<_>>());
+
+        // The first failing template in order wins, after the files before it are sent
+        let (bad, _bad_file) = template("bad.tf", "{{#if}}");
+        let (good, _good_file) = template("good.tf", "name = \"{{name}}\"");
+        let templates = vec![good.clone(), bad, good];
+        let (sender, mut receiver) = file_channel(4);
+        let (rendered, consumed) = tokio::join!(render_to_channel(&processor, &templates, sender, 3), async move {
+            let mut count = 0;
+            while receiver.recv().await.is_some() {
+                count += 1;
+            }
+            Ok::<_, CliError>(count)
+        });
+        assert_eq!(consumed.as_ref().unwrap(), &1);
+        assert!(join_results(rendered, consumed).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_render_error_wins() {
+        let context = context();
+        let processor = TemplateProcessor::new(&context);
+        let (good, _good_file) = template("good.tf", "name = \"{{name}}\"");
+        let (bad, _bad_file) = template("bad.tf", "{{#if}}");
+        let templates = vec![good, bad];
+
+        let (sender, mut receiver) = file_channel(4);
+        let (rendered, consumed) = tokio::join!(
+            render_to_channel(&processor, &templates, sender, 1),
+            async move {
+                let mut count = 0;
+                while receiver.recv().await.is_some() {
+                    count += 1;
+                }
+                Ok::<_, CliError>(count)
+            }
+        );
+
+        assert_eq!(consumed.as_ref().unwrap(), &1);
+        assert!(join_results(rendered, consumed).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_templated_paths_expand_and_must_not_collide() {
+        let mut context = context();
+        context.insert("envs".to_string(), json!(["dev", "prod"]));
+        let processor = TemplateProcessor::new(&context);
+        let (each, _each_file) = template("{{#each envs}}{{this}}/main.tf", "env = \"{{item}}\"");
+        let (named, _named_file) = template("{{name}}.tf", "name = \"{{name}}\"");
+
+        let (sender, mut receiver) = file_channel(4);
+        let templates = vec![each.clone(), named];
+        let (rendered, consumed) = tokio::join!(render_to_channel(&processor, &templates, sender, 2), async {
+            let mut files = Vec::new();
+            while let Some(file) = receiver.recv().await {
+                files.push((file.path_str(), file.content));
+            }
+            Ok::<_, CliError>(files)
+        });
+        let (stats, files) = join_results(rendered, consumed).unwrap();
+        assert_eq!(stats.files_rendered, 3);
+        assert_eq!(files[1], ("prod/main.tf".to_string(), "env = \"prod\"".to_string()));
+        assert_eq!(files[2].0, "demo.tf");
+
+        // Generated files are checked against the rendered paths, not the template paths
+        let generated = vec![ProcessedFile::new(PathBuf::from("DEMO.tf"), String::new())];
+        let (sender, mut receiver) = file_channel(4);
+        let (rendered, _) = tokio::join!(
+            render_to_channel_with(&processor, &templates[1..], generated, sender, 1),
+            async move { while receiver.recv().await.is_some() {} }
+        );
+        let err = rendered.unwrap_err();
+        assert!(err.to_string().contains("'demo.tf' is rendered from a template and also generated"), "{}", err);
+
+        let (fixed, _fixed_file) = template("DEV/main.tf", "");
+        let (sender, mut receiver) = file_channel(4);
+        let templates = vec![each, fixed];
+        let (rendered, _) = tokio::join!(render_to_channel(&processor, &templates, sender, 1), async move {
+            while receiver.recv().await.is_some() {}
+        });
+        let err = rendered.unwrap_err();
+        assert!(matches!(err, CliError::OutputConflictError(_)));
+        assert!(err.to_string().contains("'dev/main.tf' and 'DEV/main.tf' differ only in case"), "{}", err);
+    }
+}
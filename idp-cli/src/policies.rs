@@ -0,0 +1,237 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use regorus::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use walkdir::WalkDir;
+
+use crate::error::CliError;
+
+/// Rules read from every policy package, with what a result of each means
+const RULES: &[(&str, PolicySeverity)] = &[("deny", PolicySeverity::Error), ("warn", PolicySeverity::Warning)];
+
+/// What happens when generated output breaks a policy rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicySeverity {
+    /// A `warn` rule: print a warning and continue
+    Warning,
+    /// A `deny` rule: fail the run before anything is written
+    Error,
+}
+
+/// A message of a `deny` or `warn` rule for one generated file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PolicyViolation {
+    /// Output path relative to the output directory
+    pub path: PathBuf,
+    /// Package of the rule, e.g. `data.terraform.tags`
+    pub package: String,
+    pub message: String,
+    pub severity: PolicySeverity,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Policy {} on {}: {}", self.package, self.path.display(), self.message)
+    }
+}
+
+/// Rego policies from `--policy-dir`, evaluated against each rendered file
+///
+/// Every package may define `deny` and `warn` rules producing messages, either
+/// strings or objects with a `msg`. Each rendered file is passed as `input`
+/// with its output `path` and parsed `content`: Terraform as the JSON form of
+/// its HCL, YAML once per document, and JSON as is.
+///
+/// ```rego
+/// package terraform.tags
+///
+/// deny contains msg if {
+///     some name, bucket in input.content.resource.aws_s3_bucket
+///     not bucket.tags.owner
+///     msg := sprintf("aws_s3_bucket.%s has no owner tag", [name])
+/// }
+/// ```
+#[derive(Clone)]
+pub struct PolicySet {
+    engine: Engine,
+    packages: Vec<String>,
+}
+
+impl PolicySet {
+    /// Load the `.rego` files in a directory and its subdirectories
+    ///
+    /// Files ending in `_test.rego` hold OPA unit tests and are skipped.
+    pub fn load(dir: &Path) -> Result<Self, CliError> {
+        let mut files: Vec<PathBuf> = WalkDir::new(dir)
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| CliError::ConfigurationError(format!("Failed to read policy directory {}: {}", dir.display(), e)))?
+            .into_iter()
+            .map(walkdir::DirEntry::into_path)
+            .filter(|path| path.extension().is_some_and(|extension| extension == "rego"))
+            .filter(|path| !path.to_string_lossy().ends_with("_test.rego"))
+            .collect();
+        if files.is_empty() {
+            return Err(CliError::ConfigurationError(format!("No .rego policies found in {}", dir.display())));
+        }
+        files.sort();
+
+        let mut engine = Engine::new();
+        for path in &files {
+            let rego = std::fs::read_to_string(path).map_err(|e| {
+                CliError::ConfigurationError(format!("Failed to read policy {}: {}", path.display(), e))
+            })?;
+            engine
+                .add_policy(path.display().to_string(), rego)
+                .map_err(|e| CliError::ConfigurationError(format!("Invalid policy {}: {}", path.display(), e)))?;
+        }
+        let mut packages = engine
+            .get_packages()
+            .map_err(|e| CliError::ConfigurationError(format!("Invalid policies in {}: {}", dir.display(), e)))?;
+        packages.dedup();
+        Ok(Self { engine, packages })
+    }
+
+    /// Evaluate the policies against one rendered file
+    ///
+    /// Files that aren't Terraform, YAML or JSON, or don't parse, are not
+    /// checked; output validation reports the ones that are broken.
+    pub fn check_file(&mut self, path: &Path, content: &str) -> Result<Vec<PolicyViolation>, CliError> {
+        let mut violations = Vec::new();
+        for document in documents(path, content) {
+            self.engine.set_input(json!({ "path": path, "content": document }).into());
+            for package in &self.packages {
+                for (rule, severity) in RULES {
+                    let query = format!("{}.{}", package, rule);
+                    let results = self.engine.eval_query(query.clone(), false).map_err(|e| {
+                        CliError::ConfigurationError(format!("Failed to evaluate {} for {}: {}", query, path.display(), e))
+                    })?;
+                    let values = results
+                        .result
+                        .iter()
+                        .flat_map(|result| &result.expressions)
+                        .filter_map(|expression| serde_json::to_value(&expression.value).ok());
+                    for message in values.flat_map(messages) {
+                        violations.push(PolicyViolation {
+                            path: path.to_path_buf(),
+                            package: package.clone(),
+                            message,
+                            severity: *severity,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(violations)
+    }
+}
+
+/// Parsed documents of a rendered file, by extension
+fn documents(path: &Path, content: &str) -> Vec<Value> {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or_default() {
+        "tf" => hcl::from_str(content).into_iter().collect(),
+        "yaml" | "yml" => serde_yaml::Deserializer::from_str(content)
+            .filter_map(|document| Value::deserialize(document).ok())
+            .filter(|document| !document.is_null())
+            .collect(),
+        "json" => serde_json::from_str(content).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Messages in the value of a `deny` or `warn` rule
+fn messages(value: Value) -> Vec<String> {
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Null | Value::Bool(false) => Vec::new(),
+        value => vec![value],
+    };
+    items
+        .into_iter()
+        .map(|item| match item {
+            Value::String(message) => message,
+            Value::Object(ref fields) => match fields.get("msg") {
+                Some(Value::String(message)) => message.clone(),
+                _ => item.to_string(),
+            },
+            item => item.to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policies(files: &[(&str, &str)]) -> (tempfile::TempDir, PolicySet) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, rego) in files {
+            std::fs::write(dir.path().join(name), rego).unwrap();
+        }
+        let policies = PolicySet::load(dir.path()).unwrap();
+        (dir, policies)
+    }
+
+    #[test]
+    fn test_check_terraform_and_yaml() {
+        let (_dir, mut policies) = policies(&[
+            (
+                "tags.rego",
+                r#"package terraform.tags
+
+deny contains msg if {
+    some name, bucket in input.content.resource.aws_s3_bucket
+    not bucket.tags.owner
+    msg := sprintf("aws_s3_bucket.%s has no owner tag", [name])
+}
+"#,
+            ),
+            (
+                "k8s.rego",
+                r#"package k8s
+
+warn contains {"msg": sprintf("%s runs a single replica", [input.content.metadata.name])} if {
+    input.content.kind == "Deployment"
+    input.content.spec.replicas == 1
+}
+"#,
+            ),
+            ("tags_test.rego", "package terraform.tags_test\n\ntest_nothing if { true }\n"),
+        ]);
+
+        let tf = "resource \"aws_s3_bucket\" \"logs\" {\n  bucket = var.name\n}\n\nresource \"aws_s3_bucket\" \"data\" {\n  tags = { owner = \"payments\" }\n}\n";
+        let violations = policies.check_file(Path::new("storage.tf"), tf).unwrap();
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert_eq!(violations[0].severity, PolicySeverity::Error);
+        assert_eq!(
+            violations[0].to_string(),
+            "Policy data.terraform.tags on storage.tf: aws_s3_bucket.logs has no owner tag"
+        );
+
+        let yaml = "kind: Service\nmetadata:\n  name: api\n---\nkind: Deployment\nmetadata:\n  name: api\nspec:\n  replicas: 1\n";
+        let violations = policies.check_file(Path::new("api.yaml"), yaml).unwrap();
+        assert_eq!(violations.len(), 1, "{:?}", violations);
+        assert_eq!((violations[0].severity, violations[0].message.as_str()), (PolicySeverity::Warning, "api runs a single replica"));
+
+        // Expressions that aren't literals are passed as interpolations
+        assert_eq!(documents(Path::new("storage.tf"), tf)[0]["resource"]["aws_s3_bucket"]["logs"]["bucket"], "${var.name}");
+
+        // Output that doesn't parse is left to output validation
+        assert!(policies.check_file(Path::new("main.tf"), "resource {").unwrap().is_empty());
+        assert!(policies.check_file(Path::new("README.md"), "# notes").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_reports_invalid_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = PolicySet::load(dir.path()).err().unwrap();
+        assert!(error.to_string().contains("No .rego policies found"), "{}", error);
+
+        std::fs::write(dir.path().join("broken.rego"), "package broken\n\ndeny contains msg if {\n").unwrap();
+        let error = PolicySet::load(dir.path()).err().unwrap();
+        assert!(error.to_string().contains("Invalid policy"), "{}", error);
+    }
+}
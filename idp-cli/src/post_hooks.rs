@@ -0,0 +1,212 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::CliError;
+use crate::tool_versions::{self, Tool};
+
+/// Hooks that need no configuration
+pub const BUILTIN_HOOKS: &[&str] = &["fmt", "validate", "tflint"];
+
+/// A post-generation hook defined in a config file
+///
+/// ```toml
+/// post_hooks = ["fmt", "checkov"]
+///
+/// [hooks.checkov]
+/// command = ["checkov", "--quiet", "-d", "."]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HookConfig {
+    /// Program and arguments, run in the output directory
+    pub command: Vec<String>,
+}
+
+/// Commands run in the output directory after generate writes its files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PostHook {
+    pub name: String,
+    /// Programs and arguments, run in order until one fails
+    steps: Vec<Vec<String>>,
+}
+
+impl PostHook {
+    /// Hooks for the names given with `--post-hook` or `post_hooks`, in order
+    ///
+    /// `fmt`, `validate` and `tflint` are built in and use OpenTofu, or
+    /// Terraform when `tofu` isn't installed. Other names must be defined under
+    /// `[hooks.<name>]`; a definition replaces a built-in hook of the same name.
+    pub fn resolve(names: &[String], configured: &BTreeMap<String, HookConfig>) -> Result<Vec<Self>, CliError> {
+        let mut iac = None;
+        names
+            .iter()
+            .map(|name| {
+                let steps = match configured.get(name) {
+                    Some(config) if config.command.first().is_none_or(String::is_empty) => {
+                        return Err(CliError::ConfigurationError(format!(
+                            "Hook '{}': command must not be empty",
+                            name
+                        )));
+                    }
+                    Some(config) => vec![config.command.clone()],
+                    None => {
+                        let iac = *iac.get_or_insert_with(|| {
+                            if tool_versions::detect_version(Tool::Tofu).is_some() {
+                                "tofu"
+                            } else {
+                                "terraform"
+                            }
+                        });
+                        builtin_steps(name, iac).ok_or_else(|| {
+                            CliError::ConfigurationError(format!(
+                                "Unknown post-generation hook '{}': use {} or define it under [hooks.{}] in a config file",
+                                name,
+                                BUILTIN_HOOKS.join(", "),
+                                name
+                            ))
+                        })?
+                    }
+                };
+                Ok(Self { name: name.clone(), steps })
+            })
+            .collect()
+    }
+
+    /// The hook's commands as typed in a shell
+    pub fn command_line(&self) -> String {
+        self.steps.iter().map(|step| step.join(" ")).collect::<Vec<_>>().join(" && ")
+    }
+
+    /// Run the hook in `dir`, capturing the output of its commands
+    pub fn run(&self, dir: &Path) -> HookResult {
+        let started = Instant::now();
+        let mut output = Vec::new();
+        let mut exit_code = None;
+        let mut success = true;
+        for step in &self.steps {
+            let (program, args) = step.split_first().expect("hook commands are not empty");
+            match Command::new(program)
+                .args(args)
+                .current_dir(dir)
+                // Keep terraform from checking for updates over the network
+                .env("CHECKPOINT_DISABLE", "1")
+                .output()
+            {
+                Ok(result) => {
+                    output.extend(
+                        [result.stdout, result.stderr]
+                            .iter()
+                            .map(|stream| String::from_utf8_lossy(stream).trim_end().to_string())
+                            .filter(|text| !text.is_empty()),
+                    );
+                    exit_code = result.status.code();
+                    success = result.status.success();
+                }
+                Err(e) => {
+                    output.push(format!("failed to run '{}': {}", program, e));
+                    exit_code = None;
+                    success = false;
+                }
+            }
+            if !success {
+                break;
+            }
+        }
+        HookResult {
+            name: self.name.clone(),
+            command: self.command_line(),
+            success,
+            exit_code,
+            output: output.join("\n"),
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// Commands of a built-in hook
+fn builtin_steps(name: &str, iac: &str) -> Option<Vec<Vec<String>>> {
+    let steps: &[&[&str]] = match name {
+        "fmt" => &[&["fmt", "-recursive", "-no-color"]],
+        // Validation needs the providers, but not the state backend
+        "validate" => &[&["init", "-backend=false", "-input=false", "-no-color"], &["validate", "-no-color"]],
+        "tflint" => return Some(vec![vec!["tflint".to_string(), "--no-color".to_string()]]),
+        _ => return None,
+    };
+    Some(
+        steps
+            .iter()
+            .map(|args| std::iter::once(iac).chain(args.iter().copied()).map(str::to_string).collect())
+            .collect(),
+    )
+}
+
+/// What running a hook did, recorded in the run summary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HookResult {
+    pub name: String,
+    pub command: String,
+    pub success: bool,
+    /// Exit code of the last command run, if it ran and wasn't killed by a signal
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// Standard output and error of the commands
+    pub output: String,
+    pub duration_ms: u64,
+}
+
+impl HookResult {
+    /// Why the hook failed, or None if it succeeded
+    pub fn failure(&self) -> Option<String> {
+        if self.success {
+            return None;
+        }
+        let status = match self.exit_code {
+            Some(code) => format!("exited with code {}", code),
+            None => "did not finish".to_string(),
+        };
+        Some(format!("Hook '{}' ({}) {}", self.name, self.command, status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_hooks() {
+        let configured = BTreeMap::from([("check".to_string(), HookConfig { command: vec!["ls".to_string()] })]);
+        let hooks = PostHook::resolve(&["tflint".to_string(), "check".to_string()], &configured).unwrap();
+        assert_eq!(hooks[0].command_line(), "tflint --no-color");
+        assert_eq!(hooks[1].command_line(), "ls");
+        assert_eq!(builtin_steps("validate", "tofu").unwrap()[1], vec!["tofu", "validate", "-no-color"]);
+
+        let error = PostHook::resolve(&["lint".to_string()], &configured).unwrap_err();
+        assert!(error.to_string().contains("Unknown post-generation hook 'lint'"), "{}", error);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_captures_output_and_stops_at_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = |steps: &[&[&str]]| PostHook {
+            name: "check".to_string(),
+            steps: steps.iter().map(|step| step.iter().map(|s| s.to_string()).collect()).collect(),
+        };
+
+        let result = hook(&[&["sh", "-c", "pwd"]]).run(dir.path());
+        assert!(result.success);
+        assert!(result.output.ends_with(dir.path().file_name().unwrap().to_str().unwrap()), "{}", result.output);
+
+        let result = hook(&[&["sh", "-c", "echo bad >&2; exit 3"], &["touch", "never"]]).run(dir.path());
+        assert_eq!((result.success, result.exit_code, result.output.as_str()), (false, Some(3), "bad"));
+        assert!(!dir.path().join("never").exists());
+        assert_eq!(
+            result.failure().unwrap(),
+            "Hook 'check' (sh -c echo bad >&2; exit 3 && touch never) exited with code 3"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Mutex;
+
+/// A progress event, serialized as one JSON object per line
+///
+/// The `event` field names the variant, e.g.
+/// `{"event":"file_rendered","path":"main.tf","bytes":120}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// Generation started
+    Started {
+        data_source: String,
+        identifier: String,
+    },
+    /// Blueprint or stack data was fetched and the variable context built
+    ContextBuilt { variables: usize },
+    /// Templates were discovered and filtered by the generation strategy
+    TemplatesDiscovered { count: usize, strategy: String },
+    /// A template finished rendering
+    FileRendered { path: String, bytes: usize },
+    /// A rendered file was written to the output directory
+    FileWritten { path: String },
+    /// A non-fatal problem
+    Warning { message: String },
+    /// Generation finished, successfully or not
+    Finished {
+        success: bool,
+        files_written: usize,
+        duration_ms: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+/// Emits newline-delimited JSON progress events
+///
+/// Disabled reporters drop every event, so callers can emit unconditionally.
+pub struct ProgressReporter {
+    sink: Option<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl ProgressReporter {
+    /// Reporter that writes events to stderr
+    pub fn stderr() -> Self {
+        Self::to_writer(Box::new(std::io::stderr()))
+    }
+
+    /// Reporter that writes events to an arbitrary writer
+    pub fn to_writer(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            sink: Some(Mutex::new(writer)),
+        }
+    }
+
+    /// Reporter that ignores all events
+    pub fn disabled() -> Self {
+        Self { sink: None }
+    }
+
+    /// Reporter for the `--progress-json` flag
+    pub fn from_flag(enabled: bool) -> Self {
+        if enabled {
+            Self::stderr()
+        } else {
+            Self::disabled()
+        }
+    }
+
+    /// Whether events are being written
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    /// Write one event as a single JSON line
+    ///
+    /// Write failures are ignored: progress reporting never fails a run.
+    pub fn emit(&self, event: &ProgressEvent) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+        if let Ok(mut writer) = sink.lock() {
+            let _ = writeln!(writer, "{}", line);
+            let _ = writer.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Writer that shares its buffer so tests can inspect the output
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_emits_one_json_object_per_line() {
+        let buffer = SharedBuffer::default();
+        let reporter = ProgressReporter::to_writer(Box::new(buffer.clone()));
+        reporter.emit(&ProgressEvent::FileRendered {
+            path: "modules/main.tf".to_string(),
+            bytes: 42,
+        });
+        reporter.emit(&ProgressEvent::Finished {
+            success: true,
+            files_written: 1,
+            duration_ms: 5,
+            error: None,
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"event":"file_rendered","path":"modules/main.tf","bytes":42}"#
+        );
+        assert_eq!(
+            lines[1],
+            r#"{"event":"finished","success":true,"files_written":1,"duration_ms":5}"#
+        );
+    }
+
+    #[test]
+    fn test_disabled_reporter() {
+        let reporter = ProgressReporter::from_flag(false);
+        assert!(!reporter.is_enabled());
+        reporter.emit(&ProgressEvent::Warning {
+            message: "ignored".to_string(),
+        });
+    }
+}
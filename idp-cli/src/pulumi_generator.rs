@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde_json::{json, Map, Value};
+
+use crate::error::CliError;
+use crate::resource_mapper::{kubernetes_name, map_resources, pulumi_type, source_metadata};
+use crate::template_processor::ProcessedFile;
+use crate::variable_context::VariableContext;
+
+/// Pulumi YAML program written at the root of the output directory
+pub const PULUMI_FILE: &str = "Pulumi.yaml";
+
+/// Pulumi YAML program for the resources of a blueprint or stack
+///
+/// The project is named after the stack (or blueprint). Each resource becomes
+/// a resource of the provider for its cloud with its configuration as
+/// `properties`, and its ID is exported as `<name>Id`. The stack name is
+/// declared as the `name` config value. Resources that can't be mapped are
+/// skipped and reported in the returned warnings.
+pub fn pulumi_program(context: &VariableContext) -> Result<(ProcessedFile, Vec<String>), CliError> {
+    let data = context.to_template_data();
+    let (source, name, description) = source_metadata(&data);
+    let name = name.ok_or_else(|| {
+        CliError::GenerationError(format!("The {} has no name to name the Pulumi project after", source))
+    })?;
+    let (mapped, mut warnings) = map_resources(&data);
+
+    let mut resources = Map::new();
+    let mut outputs = Map::new();
+    let mut names: HashMap<String, &str> = HashMap::new();
+    for resource in mapped {
+        let Some(token) = pulumi_type(resource.cloud, resource.kind) else {
+            warnings.push(format!("Resource '{}' has no Pulumi mapping and was not generated", resource.name));
+            continue;
+        };
+        let logical_name = kubernetes_name(resource.name);
+        if let Some(other) = names.insert(logical_name.clone(), resource.name) {
+            return Err(CliError::GenerationError(format!(
+                "Resources '{}' and '{}' both map to the Pulumi name '{}'; rename one of them",
+                other, resource.name, logical_name
+            )));
+        }
+        let mut declaration = json!({"type": token});
+        if let Value::Object(properties) = resource.configuration {
+            if !properties.is_empty() {
+                declaration["properties"] = resource.configuration.clone();
+            }
+        }
+        outputs.insert(format!("{}Id", logical_name), json!(format!("${{{}.id}}", logical_name)));
+        resources.insert(logical_name, declaration);
+    }
+
+    let mut program = Map::new();
+    program.insert("name".to_string(), json!(kubernetes_name(name)));
+    program.insert("runtime".to_string(), json!("yaml"));
+    if let Some(description) = description {
+        program.insert("description".to_string(), json!(description));
+    }
+    program.insert("config".to_string(), json!({"name": {"type": "string", "default": name}}));
+    program.insert("resources".to_string(), Value::Object(resources));
+    program.insert("outputs".to_string(), Value::Object(outputs));
+
+    let content = serde_yaml::to_string(&program)
+        .map_err(|e| CliError::GenerationError(format!("Failed to write {}: {}", PULUMI_FILE, e)))?;
+    Ok((ProcessedFile::new(PathBuf::from(PULUMI_FILE), content), warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pulumi_program_from_blueprint() {
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("Web App"));
+        context.insert(
+            "resources".to_string(),
+            json!([
+                {
+                    "name": "assets",
+                    "resource_type": {"name": "Storage", "category": "BOTH"},
+                    "cloud_provider": {"name": "AWS"},
+                    "configuration": {"forceDestroy": true}
+                },
+                {"name": "db", "resource_type": {"name": "Postgres"}, "cloud_provider": {"name": "Heroku"}}
+            ]),
+        );
+
+        let (file, warnings) = pulumi_program(&context).unwrap();
+        assert_eq!(file.relative_path, PathBuf::from("Pulumi.yaml"));
+        let program: Value = serde_yaml::from_str(&file.content).unwrap();
+        assert_eq!(
+            program,
+            json!({
+                "name": "web-app",
+                "runtime": "yaml",
+                "config": {"name": {"type": "string", "default": "Web App"}},
+                "resources": {"assets": {"type": "aws:s3:BucketV2", "properties": {"forceDestroy": true}}},
+                "outputs": {"assetsId": "${assets.id}"}
+            })
+        );
+        assert_eq!(warnings, vec!["Resource 'db' uses an unknown cloud provider 'Heroku' and was not mapped"]);
+    }
+
+    #[test]
+    fn test_pulumi_program_with_api_resource_types() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("Payments"));
+        context.insert(
+            "stack_resources".to_string(),
+            json!([
+                {"name": "orders", "resource_type": {"name": "NoSQL Database", "category": "NON_SHARED"}, "cloud_provider": {"name": "AWS"}},
+                {"name": "ledger", "resource_type": {"name": "Relational Database Server", "category": "SHARED"}, "cloud_provider": {"name": "Azure"}},
+                {"name": "platform", "resource_type": {"name": "Managed Container Orchestrator", "category": "SHARED"}, "cloud_provider": {"name": "Google Cloud"}},
+                {"name": "jobs", "resource_type": {"name": "Queue", "category": "NON_SHARED"}, "cloud_provider": {"name": "AWS"}},
+                {"name": "edge", "resource_type": {"name": "CDN", "category": "BOTH"}, "cloud_provider": {"name": "AWS"}}
+            ]),
+        );
+
+        let (file, warnings) = pulumi_program(&context).unwrap();
+        let program: Value = serde_yaml::from_str(&file.content).unwrap();
+        let types: Vec<_> = ["orders", "ledger", "platform", "jobs"].iter().map(|name| program["resources"][name]["type"].clone()).collect();
+        assert_eq!(
+            types,
+            vec![
+                json!("aws:dynamodb:Table"),
+                json!("azure-native:dbforpostgresql:Server"),
+                json!("gcp:container:Cluster"),
+                json!("aws:sqs:Queue")
+            ]
+        );
+        assert!(program["resources"].get("edge").is_none());
+        assert_eq!(warnings, vec!["Resource 'edge' has a resource type 'CDN' with no known mapping and was not mapped"]);
+    }
+}
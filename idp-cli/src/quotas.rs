@@ -0,0 +1,286 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::CliError;
+
+/// What happens when generated output exceeds a quota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaSeverity {
+    /// Print a warning and continue
+    #[default]
+    Warning,
+    /// Fail the run
+    Error,
+}
+
+/// One organization limit, e.g. at most 5 RDS instances per account
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Quota {
+    /// Shown in warnings, e.g. "RDS instances per account"
+    pub name: String,
+    /// Terraform resource type to count, e.g. `aws_db_instance`
+    #[serde(default)]
+    pub resource: Option<String>,
+    /// Kubernetes kind to count, e.g. `Deployment`
+    #[serde(default)]
+    pub kind: Option<String>,
+    /// Sum this numeric attribute instead of counting, e.g. `desired_size` or `spec.replicas`
+    #[serde(default)]
+    pub attribute: Option<String>,
+    /// Largest allowed count or sum
+    pub max: u64,
+    #[serde(default)]
+    pub severity: QuotaSeverity,
+}
+
+/// Quotas file passed with `--quotas-file`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaConfig {
+    pub quotas: Vec<Quota>,
+}
+
+impl QuotaConfig {
+    /// Load and check a quotas file
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            CliError::ConfigurationError(format!("Failed to read quotas file {}: {}", path.display(), e))
+        })?;
+        let config: Self = serde_yaml::from_str(&content).map_err(|e| {
+            CliError::ConfigurationError(format!("Invalid quotas file {}: {}", path.display(), e))
+        })?;
+        for quota in &config.quotas {
+            if quota.resource.is_some() == quota.kind.is_some() {
+                return Err(CliError::ConfigurationError(format!(
+                    "Quota '{}' in {} needs exactly one of 'resource' or 'kind'",
+                    quota.name,
+                    path.display()
+                )));
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// A quota the generated output goes over
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaBreach {
+    pub quota: String,
+    pub used: u64,
+    pub max: u64,
+    pub severity: QuotaSeverity,
+}
+
+impl std::fmt::Display for QuotaBreach {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Quota '{}' exceeded: generated output uses {} of {}", self.quota, self.used, self.max)
+    }
+}
+
+/// Usage of each quota, added up as generated files stream past
+#[derive(Debug, Clone)]
+pub struct QuotaTally<'a> {
+    config: &'a QuotaConfig,
+    used: Vec<u64>,
+}
+
+impl<'a> QuotaTally<'a> {
+    pub fn new(config: &'a QuotaConfig) -> Self {
+        Self { config, used: vec![0; config.quotas.len()] }
+    }
+
+    /// Count the resources in a generated file
+    ///
+    /// Terraform resources with a literal `count` are counted that many
+    /// times; other files are read as Kubernetes manifests.
+    pub fn add_file(&mut self, path: &Path, content: &str) {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        match extension {
+            "tf" => {
+                for block in terraform_resources(content) {
+                    for (quota, used) in self.config.quotas.iter().zip(self.used.iter_mut()) {
+                        if quota.resource.as_deref() != Some(block.resource_type) {
+                            continue;
+                        }
+                        let per_instance = match &quota.attribute {
+                            Some(attribute) => hcl_number(&block.body, attribute).unwrap_or(0),
+                            None => 1,
+                        };
+                        *used += per_instance * hcl_number(&block.body, "count").unwrap_or(1);
+                    }
+                }
+            }
+            "yaml" | "yml" => {
+                for document in manifests(content) {
+                    let kind = document.get("kind").and_then(Value::as_str);
+                    for (quota, used) in self.config.quotas.iter().zip(self.used.iter_mut()) {
+                        if kind.is_none() || quota.kind.as_deref() != kind {
+                            continue;
+                        }
+                        *used += match &quota.attribute {
+                            Some(attribute) => lookup(&document, attribute).and_then(Value::as_u64).unwrap_or(0),
+                            None => 1,
+                        };
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Quotas that are exceeded, in the order of the quotas file
+    pub fn breaches(&self) -> Vec<QuotaBreach> {
+        self.config
+            .quotas
+            .iter()
+            .zip(&self.used)
+            .filter(|(quota, used)| **used > quota.max)
+            .map(|(quota, used)| QuotaBreach {
+                quota: quota.name.clone(),
+                used: *used,
+                max: quota.max,
+                severity: quota.severity,
+            })
+            .collect()
+    }
+}
+
+/// A top-level `resource "type" "name" { ... }` block
+struct ResourceBlock<'c> {
+    resource_type: &'c str,
+    /// Lines inside the block
+    body: Vec<&'c str>,
+}
+
+fn terraform_resources(content: &str) -> Vec<ResourceBlock<'_>> {
+    let mut blocks = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        let Some(rest) = line.strip_prefix("resource ") else {
+            continue;
+        };
+        let Some(resource_type) = rest.trim_start().strip_prefix('"').and_then(|r| r.split('"').next()) else {
+            continue;
+        };
+        let mut depth = braces(line);
+        let mut body = Vec::new();
+        while depth > 0 {
+            let Some(inner) = lines.next() else {
+                break;
+            };
+            depth += braces(inner);
+            body.push(inner);
+        }
+        blocks.push(ResourceBlock { resource_type, body });
+    }
+    blocks
+}
+
+/// Change in brace depth on a line, ignoring braces in strings and `#` comments
+fn braces(line: &str) -> i64 {
+    let mut depth = 0;
+    let mut in_string = false;
+    for c in line.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => break,
+            '{' if !in_string => depth += 1,
+            '}' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+    depth
+}
+
+/// First `name = <integer>` assignment in a block body, at any nesting level
+fn hcl_number(body: &[&str], name: &str) -> Option<u64> {
+    body.iter().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == name).then(|| value.trim().parse().ok())?
+    })
+}
+
+fn manifests(content: &str) -> Vec<Value> {
+    serde_yaml::Deserializer::from_str(content)
+        .filter_map(|document| Value::deserialize(document).ok())
+        .filter(Value::is_object)
+        .collect()
+}
+
+/// Value at a dotted path such as `spec.replicas`
+fn lookup<'v>(value: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.').try_fold(value, |value, key| value.get(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yaml: &str) -> QuotaConfig {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), yaml).unwrap();
+        QuotaConfig::load(file.path()).unwrap()
+    }
+
+    #[test]
+    fn test_counts_terraform_resources_and_attributes() {
+        let config = config(
+            "quotas:\n\
+             - name: RDS instances per account\n  resource: aws_db_instance\n  max: 2\n  severity: error\n\
+             - name: EKS nodes\n  resource: aws_eks_node_group\n  attribute: desired_size\n  max: 10\n\
+             - name: Buckets\n  resource: aws_s3_bucket\n  max: 5\n",
+        );
+        let main_tf = r#"
+resource "aws_db_instance" "primary" {
+  identifier = "db-{primary}"
+}
+
+resource "aws_db_instance" "replica" {
+  count = 2
+}
+
+resource "aws_eks_node_group" "workers" {
+  scaling_config {
+    desired_size = 8
+    max_size     = 12
+  }
+}
+"#;
+        let mut tally = QuotaTally::new(&config);
+        tally.add_file(Path::new("main.tf"), main_tf);
+        tally.add_file(Path::new("nodes.tf"), "resource \"aws_eks_node_group\" \"batch\" {\n  desired_size = 4\n}\n");
+
+        assert_eq!(
+            tally.breaches(),
+            vec![
+                QuotaBreach {
+                    quota: "RDS instances per account".to_string(),
+                    used: 3,
+                    max: 2,
+                    severity: QuotaSeverity::Error,
+                },
+                QuotaBreach { quota: "EKS nodes".to_string(), used: 12, max: 10, severity: QuotaSeverity::Warning },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_counts_kubernetes_kinds_and_rejects_ambiguous_quotas() {
+        let config = config("quotas:\n- name: Replicas\n  kind: Deployment\n  attribute: spec.replicas\n  max: 5\n");
+        let mut tally = QuotaTally::new(&config);
+        tally.add_file(
+            Path::new("deploy.yaml"),
+            "kind: Deployment\nspec:\n  replicas: 3\n---\nkind: Service\n---\nkind: Deployment\nspec:\n  replicas: 4\n",
+        );
+        assert_eq!(tally.breaches()[0].to_string(), "Quota 'Replicas' exceeded: generated output uses 7 of 5");
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), "quotas:\n- name: Both\n  kind: Pod\n  resource: aws_instance\n  max: 1\n").unwrap();
+        let error = QuotaConfig::load(file.path()).unwrap_err();
+        assert!(error.to_string().contains("needs exactly one of 'resource' or 'kind'"));
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::variable_context::VariableContext;
+
+const AWS_REGIONS: &[&str] = &[
+    "us-east-1", "us-east-2", "us-west-1", "us-west-2", "us-gov-east-1", "us-gov-west-1", "ca-central-1",
+    "ca-west-1", "mx-central-1", "sa-east-1", "eu-central-1", "eu-central-2", "eu-west-1", "eu-west-2",
+    "eu-west-3", "eu-south-1", "eu-south-2", "eu-north-1", "il-central-1", "me-south-1", "me-central-1",
+    "af-south-1", "ap-east-1", "ap-east-2", "ap-south-1", "ap-south-2", "ap-southeast-1", "ap-southeast-2",
+    "ap-southeast-3", "ap-southeast-4", "ap-southeast-5", "ap-southeast-6", "ap-southeast-7", "ap-northeast-1",
+    "ap-northeast-2", "ap-northeast-3", "cn-north-1", "cn-northwest-1",
+];
+
+const AZURE_LOCATIONS: &[&str] = &[
+    "eastus", "eastus2", "centralus", "northcentralus", "southcentralus", "westcentralus", "westus", "westus2",
+    "westus3", "canadacentral", "canadaeast", "mexicocentral", "brazilsouth", "brazilsoutheast", "chilecentral",
+    "northeurope", "westeurope", "uksouth", "ukwest", "francecentral", "francesouth", "germanywestcentral",
+    "germanynorth", "switzerlandnorth", "switzerlandwest", "norwayeast", "norwaywest", "swedencentral",
+    "polandcentral", "italynorth", "spaincentral", "eastasia", "southeastasia", "japaneast", "japanwest",
+    "koreacentral", "koreasouth", "centralindia", "southindia", "westindia", "jioindiawest", "jioindiacentral",
+    "indonesiacentral", "malaysiawest", "australiaeast", "australiasoutheast", "australiacentral",
+    "australiacentral2", "newzealandnorth", "uaenorth", "uaecentral", "qatarcentral", "israelcentral",
+    "southafricanorth", "southafricawest",
+];
+
+const GCP_REGIONS: &[&str] = &[
+    "us-central1", "us-east1", "us-east4", "us-east5", "us-south1", "us-west1", "us-west2", "us-west3",
+    "us-west4", "northamerica-northeast1", "northamerica-northeast2", "northamerica-south1",
+    "southamerica-east1", "southamerica-west1", "europe-central2", "europe-north1", "europe-north2",
+    "europe-southwest1", "europe-west1", "europe-west2", "europe-west3", "europe-west4", "europe-west6",
+    "europe-west8", "europe-west9", "europe-west10", "europe-west12", "me-central1", "me-central2", "me-west1",
+    "africa-south1", "asia-east1", "asia-east2", "asia-northeast1", "asia-northeast2", "asia-northeast3",
+    "asia-south1", "asia-south2", "asia-southeast1", "asia-southeast2", "australia-southeast1",
+    "australia-southeast2",
+];
+
+/// Cloud whose region names a value is checked against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cloud {
+    Aws,
+    Azure,
+    Gcp,
+}
+
+impl Cloud {
+    /// Cloud for a cloud provider name such as `AWS` or `Google Cloud`
+    pub fn from_provider_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        if name.contains("aws") || name.contains("amazon") {
+            Some(Cloud::Aws)
+        } else if name.contains("azure") || name.contains("microsoft") {
+            Some(Cloud::Azure)
+        } else if name.contains("gcp") || name.contains("google") {
+            Some(Cloud::Gcp)
+        } else {
+            None
+        }
+    }
+
+    /// Cloud implied by a configuration key, if the key names one
+    fn from_key(key: &str) -> Option<Self> {
+        match key {
+            "aws_region" => Some(Cloud::Aws),
+            "azure_location" => Some(Cloud::Azure),
+            "gcp_region" => Some(Cloud::Gcp),
+            _ => None,
+        }
+    }
+
+    pub fn regions(&self) -> &'static [&'static str] {
+        match self {
+            Cloud::Aws => AWS_REGIONS,
+            Cloud::Azure => AZURE_LOCATIONS,
+            Cloud::Gcp => GCP_REGIONS,
+        }
+    }
+
+    /// Whether a value is a region of this cloud
+    ///
+    /// Azure also accepts display names, e.g. `West Europe` for `westeurope`.
+    pub fn is_region(&self, value: &str) -> bool {
+        match self {
+            Cloud::Azure => AZURE_LOCATIONS.contains(&value.to_lowercase().replace(' ', "").as_str()),
+            _ => self.regions().contains(&value),
+        }
+    }
+}
+
+impl fmt::Display for Cloud {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cloud::Aws => write!(f, "AWS region"),
+            Cloud::Azure => write!(f, "Azure location"),
+            Cloud::Gcp => write!(f, "GCP region"),
+        }
+    }
+}
+
+/// A configuration value that is not a region of its cloud
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegionIssue {
+    /// Variable path, e.g. `resources[0].configuration.aws_region`
+    pub path: String,
+    pub value: String,
+    pub cloud: Cloud,
+    /// Closest valid region, if one is close enough to be a typo
+    pub suggestion: Option<&'static str>,
+}
+
+impl fmt::Display for RegionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = \"{}\" is not a known {}", self.path, self.value, self.cloud)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " (did you mean \"{}\"?)", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+/// Find region-like values that are not valid regions
+///
+/// `aws_region`, `azure_location` and `gcp_region` are always checked.
+/// `region` and `location` are checked inside resources whose cloud provider
+/// is known.
+pub fn check_regions(context: &VariableContext) -> Vec<RegionIssue> {
+    let mut issues = BTreeMap::new();
+    for (path, value) in context.list_all() {
+        walk(&path, value, None, &mut issues);
+    }
+    issues.into_values().collect()
+}
+
+fn walk(path: &str, value: &Value, cloud: Option<Cloud>, issues: &mut BTreeMap<String, RegionIssue>) {
+    match value {
+        Value::Object(map) => {
+            let cloud = map
+                .get("cloud_provider")
+                .and_then(|provider| provider.get("name"))
+                .and_then(Value::as_str)
+                .and_then(Cloud::from_provider_name)
+                .or(cloud);
+            for (key, child) in map {
+                walk(&format!("{}.{}", path, key), child, cloud, issues);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                walk(&format!("{}[{}]", path, index), item, cloud, issues);
+            }
+        }
+        Value::String(region) if !region.is_empty() => {
+            let key = path.rsplit('.').next().unwrap_or(path);
+            let cloud = match Cloud::from_key(key) {
+                Some(cloud) => cloud,
+                None if key == "region" || key == "location" => match cloud {
+                    Some(cloud) => cloud,
+                    None => return,
+                },
+                None => return,
+            };
+            if !cloud.is_region(region) {
+                issues.insert(
+                    path.to_string(),
+                    RegionIssue {
+                        path: path.to_string(),
+                        value: region.clone(),
+                        cloud,
+                        suggestion: closest(cloud.regions(), region),
+                    },
+                );
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Region within two edits of the value, ignoring case
+fn closest(regions: &[&'static str], value: &str) -> Option<&'static str> {
+    let value = value.to_lowercase();
+    regions
+        .iter()
+        .map(|region| (edit_distance(region, &value), *region))
+        .filter(|(distance, _)| *distance <= 2)
+        .min()
+        .map(|(_, region)| region)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_check_regions_by_key_and_resource_provider() {
+        let mut context = VariableContext::new();
+        context.insert(
+            "resources".to_string(),
+            json!([
+                {"cloud_provider": {"name": "AWS"}, "configuration": {"region": "us-east1", "size": "small"}},
+                {"cloud_provider": {"name": "GCP"}, "configuration": {"region": "us-east1"}},
+                {"cloud_provider": {"name": "Azure"}, "configuration": {"location": "West Europe"}}
+            ]),
+        );
+        context.insert("resources[0].configuration".to_string(), json!({"region": "us-east1", "size": "small"}));
+        context.insert("gcp_region".to_string(), json!("europe-west"));
+        context.insert("azure_location".to_string(), json!("westeurope"));
+        context.insert("region".to_string(), json!("anywhere"));
+
+        let issues: Vec<String> = check_regions(&context).iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(
+            issues,
+            vec![
+                r#"gcp_region = "europe-west" is not a known GCP region (did you mean "europe-west1"?)"#,
+                r#"resources[0].configuration.region = "us-east1" is not a known AWS region (did you mean "us-east-1"?)"#,
+            ]
+        );
+    }
+}
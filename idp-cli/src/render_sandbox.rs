@@ -0,0 +1,306 @@
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperDef, HelperResult, Output, RenderContext, RenderError, RenderErrorReason,
+};
+
+/// Render time allowed per template file unless configured otherwise
+pub const DEFAULT_RENDER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How deeply custom helpers may call other custom helpers
+pub const MAX_HELPER_DEPTH: usize = 32;
+
+/// What templates and helpers may do while rendering
+///
+/// By default templates can't read environment variables or files; the
+/// `env_var` and `read_file` helpers fail unless the name or path is allowlisted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SandboxPolicy {
+    /// Fail a file still rendering after this long (None for no limit)
+    ///
+    /// The deadline is checked each time the template writes output or calls
+    /// a custom helper, and while Rhai script helpers run. It doesn't
+    /// interrupt other helper calls in progress: a slow Rust or template
+    /// helper runs to completion before the render fails.
+    pub render_timeout: Option<Duration>,
+    /// Environment variables `{{env_var}}` may read; `PREFIX_*` allows a prefix
+    pub allowed_env: Vec<String>,
+    /// Files, or directories whose files, `{{read_file}}` may read
+    pub allowed_files: Vec<PathBuf>,
+}
+
+impl SandboxPolicy {
+    /// Read the policy from `RENDER_TIMEOUT_SECS`, `TEMPLATE_ALLOW_ENV` and
+    /// `TEMPLATE_ALLOW_FILES` (comma-separated), for the Lambda and server modes
+    pub fn from_env() -> Self {
+        let list = |name: &str| -> Vec<String> {
+            std::env::var(name)
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|entry| !entry.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let timeout_secs = std::env::var("RENDER_TIMEOUT_SECS")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .unwrap_or(DEFAULT_RENDER_TIMEOUT.as_secs());
+        Self {
+            render_timeout: timeout(timeout_secs),
+            allowed_env: list("TEMPLATE_ALLOW_ENV"),
+            allowed_files: list("TEMPLATE_ALLOW_FILES").into_iter().map(PathBuf::from).collect(),
+        }
+    }
+
+    /// Whether templates may read an environment variable
+    pub fn allows_env(&self, name: &str) -> bool {
+        self.allowed_env.iter().any(|allowed| match allowed.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == allowed,
+        })
+    }
+
+    /// Resolve a path templates want to read, if it is allowlisted
+    ///
+    /// Paths are compared after resolving symlinks and `..`, so an allowed
+    /// directory can't be escaped.
+    pub fn allowed_file(&self, path: &Path) -> Option<PathBuf> {
+        let resolved = path.canonicalize().ok()?;
+        self.allowed_files
+            .iter()
+            .filter_map(|allowed| allowed.canonicalize().ok())
+            .any(|allowed| resolved.starts_with(allowed))
+            .then_some(resolved)
+    }
+}
+
+/// Timeout from a number of seconds, where 0 means no limit
+pub fn timeout(secs: u64) -> Option<Duration> {
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+thread_local! {
+    static DEADLINE: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+    static HELPER_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static VIOLATION: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Run a render on this thread, failing it once it has taken longer than `timeout`
+///
+/// Returns the render result and the sandbox rule it broke, if any, so
+/// callers can tell a violation apart from an ordinary template error.
+pub(crate) fn guarded<T>(timeout: Option<Duration>, render: impl FnOnce() -> T) -> (T, Option<String>) {
+    DEADLINE.with(|d| d.set(timeout.map(|timeout| (Instant::now() + timeout, timeout))));
+    HELPER_DEPTH.with(|d| d.set(0));
+    VIOLATION.with(|v| v.borrow_mut().take());
+    let result = render();
+    DEADLINE.with(|d| d.set(None));
+    (result, VIOLATION.with(|v| v.borrow_mut().take()))
+}
+
+/// Fail a helper call: record the violation and turn it into a render error
+pub(crate) fn deny(message: String) -> RenderError {
+    VIOLATION.with(|v| *v.borrow_mut() = Some(message.clone()));
+    RenderError::from(RenderErrorReason::Other(message))
+}
+
+/// Fail if the render has run past its deadline
+pub(crate) fn check_deadline() -> Result<(), RenderError> {
+    match DEADLINE.with(Cell::get) {
+        Some((deadline, timeout)) if Instant::now() > deadline => Err(deny(format!(
+            "rendering took longer than {}s (--render-timeout)",
+            timeout.as_secs_f64()
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Rendered output that fails the render once it has run past its deadline
+///
+/// Handlebars' built-in helpers such as `{{#each}}` never look at the clock,
+/// so the deadline is checked on every write instead.
+#[derive(Default)]
+pub(crate) struct DeadlineOutput(String);
+
+impl DeadlineOutput {
+    pub(crate) fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Output for DeadlineOutput {
+    fn write(&mut self, seg: &str) -> Result<(), std::io::Error> {
+        check_write()?;
+        self.0.push_str(seg);
+        Ok(())
+    }
+}
+
+/// [`check_deadline`] for [`Output`] implementations, which fail with I/O errors
+pub(crate) fn check_write() -> Result<(), std::io::Error> {
+    check_deadline().map_err(|e| std::io::Error::other(e.to_string()))
+}
+
+/// Mark a custom helper call as in progress until the guard is dropped
+pub(crate) fn enter_helper(name: &str) -> Result<HelperDepthGuard, RenderError> {
+    check_deadline()?;
+    let depth = HELPER_DEPTH.with(|d| d.get()) + 1;
+    if depth > MAX_HELPER_DEPTH {
+        return Err(deny(format!(
+            "helper '{}' nested more than {} levels deep; does it call itself?",
+            name, MAX_HELPER_DEPTH
+        )));
+    }
+    HELPER_DEPTH.with(|d| d.set(depth));
+    Ok(HelperDepthGuard)
+}
+
+pub(crate) struct HelperDepthGuard;
+
+impl Drop for HelperDepthGuard {
+    fn drop(&mut self) {
+        HELPER_DEPTH.with(|d| d.set(d.get().saturating_sub(1)));
+    }
+}
+
+/// Environment variable helper: {{env_var "NAME"}}
+pub(crate) struct EnvHelper(pub Arc<SandboxPolicy>);
+
+impl HelperDef for EnvHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let name = h.param(0).and_then(|p| p.value().as_str()).ok_or_else(|| {
+            RenderError::from(RenderErrorReason::Other("env_var helper requires a variable name".to_string()))
+        })?;
+        if !self.0.allows_env(name) {
+            return Err(deny(format!("environment variable '{}' is not allowlisted (--allow-env)", name)));
+        }
+        out.write(&std::env::var(name).unwrap_or_default())?;
+        Ok(())
+    }
+}
+
+/// File helper: {{read_file "path"}}
+pub(crate) struct ReadFileHelper(pub Arc<SandboxPolicy>);
+
+impl HelperDef for ReadFileHelper {
+    fn call<'reg: 'rc, 'rc>(
+        &self,
+        h: &Helper<'rc>,
+        _: &'reg Handlebars<'reg>,
+        _: &'rc Context,
+        _: &mut RenderContext<'reg, 'rc>,
+        out: &mut dyn Output,
+    ) -> HelperResult {
+        let path = h.param(0).and_then(|p| p.value().as_str()).ok_or_else(|| {
+            RenderError::from(RenderErrorReason::Other("read_file helper requires a path".to_string()))
+        })?;
+        let Some(resolved) = self.0.allowed_file(Path::new(path)) else {
+            return Err(deny(format!("file '{}' is not allowlisted (--allow-file)", path)));
+        };
+        let content = std::fs::read_to_string(&resolved).map_err(|e| {
+            RenderError::from(RenderErrorReason::Other(format!("failed to read '{}': {}", path, e)))
+        })?;
+        out.write(&content)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CliError;
+    use crate::helper_plugins::HelperSet;
+    use crate::template_processor::TemplateProcessor;
+    use crate::variable_context::VariableContext;
+
+    #[test]
+    fn test_policy_allowlists() {
+        let dir = tempfile::tempdir().unwrap();
+        let allowed = dir.path().join("allowed");
+        std::fs::create_dir(&allowed).unwrap();
+        std::fs::write(allowed.join("ca.pem"), "cert").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "secret").unwrap();
+
+        let policy = SandboxPolicy {
+            render_timeout: None,
+            allowed_env: vec!["REGION".to_string(), "TF_VAR_*".to_string()],
+            allowed_files: vec![allowed.clone()],
+        };
+        assert!(policy.allows_env("REGION"));
+        assert!(policy.allows_env("TF_VAR_size"));
+        assert!(!policy.allows_env("AWS_SECRET_ACCESS_KEY"));
+
+        assert!(policy.allowed_file(&allowed.join("ca.pem")).is_some());
+        assert!(policy.allowed_file(&allowed.join("../secret.txt")).is_none());
+        assert!(policy.allowed_file(&dir.path().join("secret.txt")).is_none());
+        assert!(SandboxPolicy::default().allowed_file(&allowed.join("ca.pem")).is_none());
+    }
+
+    #[test]
+    fn test_env_helper_needs_allowlist() {
+        std::env::set_var("IDP_SANDBOX_TEST_REGION", "eu-west-1");
+        let context = VariableContext::new();
+        let template = r#"region = "{{env_var "IDP_SANDBOX_TEST_REGION"}}""#;
+
+        let error = TemplateProcessor::new(&context).process_template(template).unwrap_err();
+        assert!(matches!(&error, CliError::RenderLimitError(m) if m.contains("is not allowlisted (--allow-env)")));
+
+        let policy = SandboxPolicy { allowed_env: vec!["IDP_SANDBOX_TEST_*".to_string()], ..Default::default() };
+        let processor = TemplateProcessor::new(&context).with_sandbox(policy);
+        assert_eq!(processor.process_template(template).unwrap(), r#"region = "eu-west-1""#);
+    }
+
+    #[test]
+    fn test_render_timeout_and_helper_recursion() {
+        handlebars::handlebars_helper!(slow: |ms: u64| {
+            std::thread::sleep(Duration::from_millis(ms));
+            ms
+        });
+        let mut helpers = HelperSet::new();
+        helpers.register("slow", slow);
+        helpers.register_template("forever", "{{forever}}").unwrap();
+        helpers.register_script("spin", "loop {}").unwrap();
+        // A chain of template helpers whose innermost call is a script helper
+        for level in 1..MAX_HELPER_DEPTH {
+            helpers.register_template(&format!("level{}", level), &format!("{{{{level{}}}}}", level + 1)).unwrap();
+        }
+        helpers.register_template(&format!("level{}", MAX_HELPER_DEPTH), "{{innermost}}").unwrap();
+        helpers.register_script("innermost", "42").unwrap();
+        let context = VariableContext::new();
+        let processor = TemplateProcessor::new(&context)
+            .with_sandbox(SandboxPolicy { render_timeout: Some(Duration::from_millis(50)), ..Default::default() })
+            .with_helpers(&helpers)
+            .unwrap();
+
+        assert_eq!(processor.process_template("{{slow 1}}").unwrap(), "1");
+        let error = processor.process_template("{{slow 500}}").unwrap_err();
+        assert!(matches!(&error, CliError::RenderLimitError(m) if m.contains("took longer than 0.05s")));
+        let error = processor.process_template("{{forever}}").unwrap_err();
+        assert!(matches!(&error, CliError::RenderLimitError(m) if m.contains("'forever' nested more than 32 levels")));
+        // Script helpers are stopped while running, and count towards the nesting depth
+        let error = processor.process_template("{{spin}}").unwrap_err();
+        assert!(matches!(&error, CliError::RenderLimitError(m) if m.contains("took longer than 0.05s")), "{:?}", error);
+        assert_eq!(processor.process_template("{{level2}}").unwrap(), "42");
+        let error = processor.process_template("{{level1}}").unwrap_err();
+        assert!(matches!(&error, CliError::RenderLimitError(m) if m.contains("'innermost' nested more than 32 levels")));
+
+        // Built-in block helpers are stopped at their next write
+        let mut context = VariableContext::new();
+        context.insert("items".to_string(), serde_json::json!((0..1000).collect::<Vec<_>>()));
+        let processor = TemplateProcessor::new(&context)
+            .with_sandbox(SandboxPolicy { render_timeout: Some(Duration::from_nanos(1)), ..Default::default() });
+        let error = processor.process_template("{{#each items}}{{this}}{{/each}}").unwrap_err();
+        assert!(matches!(&error, CliError::RenderLimitError(m) if m.contains("took longer than")));
+    }
+}
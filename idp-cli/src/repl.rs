@@ -0,0 +1,152 @@
+use serde_json::Value;
+
+use crate::error::CliError;
+use crate::helper_plugins::HelperSet;
+use crate::template_processor::TemplateProcessor;
+use crate::variable_context::VariableContext;
+
+/// Help text printed by `:help`
+pub const HELP: &str = "\
+Type a template snippet to render it, e.g. {{uppercase blueprint.name}}.
+End a line with \\ to continue the snippet on the next line.
+
+Commands:
+  :vars [PREFIX]   List variables, optionally only those starting with PREFIX
+  :helpers         List built-in and custom helpers
+  :history         List the snippets rendered in this session
+  :help            Show this help
+  :quit            Leave the playground (or Ctrl-D)";
+
+/// What the playground does with a line of input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplOutcome {
+    /// Text to print
+    Output(String),
+    /// The line ends with `\`; read another before rendering
+    Continue,
+    /// Leave the playground
+    Quit,
+}
+
+/// Interactive template playground rendering snippets against a variable context
+pub struct Repl<'a> {
+    context: &'a VariableContext,
+    processor: TemplateProcessor<'a>,
+    helpers: Vec<String>,
+    /// Lines of a snippet continued with `\`
+    pending: Vec<String>,
+    history: Vec<String>,
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(context: &'a VariableContext, helpers: &HelperSet) -> Result<Self, CliError> {
+        let processor = TemplateProcessor::new(context).with_helpers(helpers)?;
+        let mut names: Vec<String> = TemplateProcessor::BUILTIN_HELPERS
+            .iter()
+            .chain(&helpers.names())
+            .map(|name| name.to_string())
+            .collect();
+        names.sort();
+        Ok(Self { context, processor, helpers: names, pending: Vec::new(), history: Vec::new() })
+    }
+
+    /// Handle a line of input: a command or (part of) a snippet to render
+    ///
+    /// Render errors are returned as output, so a typo doesn't end the session.
+    pub fn eval(&mut self, line: &str) -> ReplOutcome {
+        if let Some(continued) = line.strip_suffix('\\') {
+            self.pending.push(continued.to_string());
+            return ReplOutcome::Continue;
+        }
+        if self.pending.is_empty() {
+            if let Some(command) = line.trim().strip_prefix(':') {
+                return self.command(command);
+            }
+        }
+        self.pending.push(line.to_string());
+        let snippet = std::mem::take(&mut self.pending).join("\n");
+        if snippet.trim().is_empty() {
+            return ReplOutcome::Output(String::new());
+        }
+        self.history.push(snippet.clone());
+        ReplOutcome::Output(match self.processor.process_template(&snippet) {
+            Ok(rendered) => rendered,
+            Err(e) => format!("Error: {}", e),
+        })
+    }
+
+    /// Drop a snippet continued with `\`, e.g. on Ctrl-C
+    pub fn cancel(&mut self) {
+        self.pending.clear();
+    }
+
+    fn command(&self, command: &str) -> ReplOutcome {
+        let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+        let output = match name {
+            "q" | "quit" | "exit" => return ReplOutcome::Quit,
+            "vars" => self.variables(argument.trim()),
+            "helpers" => self.helpers.join("\n"),
+            "history" => self
+                .history
+                .iter()
+                .enumerate()
+                .map(|(index, snippet)| format!("{:>3}  {}", index + 1, snippet.replace('\n', "\n     ")))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "help" => HELP.to_string(),
+            _ => format!("Unknown command ':{}'; type :help for the commands", name),
+        };
+        ReplOutcome::Output(output)
+    }
+
+    fn variables(&self, prefix: &str) -> String {
+        let lines: Vec<String> = self
+            .context
+            .list_all()
+            .into_iter()
+            .filter(|(path, _)| path.starts_with(prefix))
+            .map(|(path, value)| match value {
+                Value::Array(items) => format!("{} = [{} item(s)]", path, items.len()),
+                Value::Object(map) => format!("{} = {{{} key(s)}}", path, map.len()),
+                scalar => format!("{} = {}", path, scalar),
+            })
+            .collect();
+        if lines.is_empty() {
+            format!("No variables start with '{}'", prefix)
+        } else {
+            lines.join("\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_eval_renders_snippets_and_commands() {
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("web-app"));
+        context.insert("resources".to_string(), json!([{"name": "db"}]));
+        let mut helpers = HelperSet::new();
+        helpers.register_template("shout", "{{uppercase this}}!").unwrap();
+        let mut repl = Repl::new(&context, &helpers).unwrap();
+
+        assert_eq!(repl.eval("{{uppercase blueprint.name}}"), ReplOutcome::Output("WEB-APP".to_string()));
+        assert_eq!(repl.eval("{{#each resources}}\\"), ReplOutcome::Continue);
+        assert_eq!(repl.eval("{{name}}{{/each}}"), ReplOutcome::Output("db".to_string()));
+        let ReplOutcome::Output(error) = repl.eval("{{#if}}") else { panic!() };
+        assert!(error.starts_with("Error: "), "{}", error);
+
+        assert_eq!(repl.eval(":vars blueprint"), ReplOutcome::Output("blueprint.name = \"web-app\"".to_string()));
+        assert_eq!(repl.eval(":vars res"), ReplOutcome::Output("resources = [1 item(s)]".to_string()));
+        let ReplOutcome::Output(helper_list) = repl.eval(":helpers") else { panic!() };
+        assert!(helper_list.lines().any(|line| line == "shout"), "{}", helper_list);
+        let ReplOutcome::Output(history) = repl.eval(":history") else { panic!() };
+        assert!(history.starts_with("  1  {{uppercase blueprint.name}}\n  2  {{#each resources}}\n     {{name}}"), "{}", history);
+        assert_eq!(repl.eval("{{blueprint.name}}\\"), ReplOutcome::Continue);
+        repl.cancel();
+        assert_eq!(repl.eval(":quit"), ReplOutcome::Quit);
+    }
+}
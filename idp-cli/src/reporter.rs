@@ -0,0 +1,91 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::cli::OutputFormat;
+use crate::error::CliError;
+use crate::output_style::OutputStyle;
+
+/// Prints command results as human-readable text or as one JSON document
+///
+/// With `--output json`, stdout carries only the command's result document,
+/// or an error document when the command fails, so it can be piped to `jq`.
+/// Logs, warnings and progress events still go to stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reporter {
+    format: OutputFormat,
+    style: OutputStyle,
+}
+
+impl Reporter {
+    pub fn new(format: OutputFormat, style: OutputStyle) -> Self {
+        Self { format, style }
+    }
+
+    pub fn is_json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+
+    /// Decoration of human-readable output (`--plain`)
+    pub fn style(&self) -> OutputStyle {
+        self.style
+    }
+
+    /// Print human-readable output; nothing in JSON mode
+    pub fn line(&self, text: impl Display) {
+        if !self.is_json() {
+            println!("{}", text);
+        }
+    }
+
+    /// Print a command's result document
+    pub fn json<T: Serialize + ?Sized>(&self, result: &T) -> Result<(), CliError> {
+        println!("{}", serde_json::to_string_pretty(result)?);
+        Ok(())
+    }
+
+    /// Report a failed command
+    ///
+    /// JSON mode prints [`error_document`] to stdout; otherwise the error
+    /// report goes to stderr, with remediation when `verbose` is set.
+    pub fn error(&self, error: &CliError, verbose: bool) {
+        if self.is_json() {
+            match serde_json::to_string_pretty(&error_document(error)) {
+                Ok(document) => println!("{}", document),
+                Err(_) => eprintln!("{}", error.report(verbose)),
+            }
+        } else {
+            eprintln!("{}", error.report(verbose));
+        }
+    }
+}
+
+/// JSON form of an error: code, message and the catalog entry for its kind
+pub fn error_document(error: &CliError) -> Value {
+    let info = error.info();
+    json!({
+        "error": {
+            "code": info.code,
+            "message": error.user_message(),
+            "summary": info.summary,
+            "remediation": info.remediation,
+            "docs_url": info.docs_url,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_document_carries_code_and_catalog_entry() {
+        let error = CliError::TemplateSyntaxError { line: 3, message: "unclosed".into() };
+        let document = error_document(&error);
+        assert_eq!(document["error"]["code"], "IDP012");
+        assert_eq!(document["error"]["message"], "Template syntax error at line 3: unclosed");
+        assert_eq!(document["error"]["summary"], error.info().summary);
+        assert!(document["error"]["docs_url"].is_string());
+    }
+}
@@ -0,0 +1,365 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::error::CliError;
+use crate::policies::{PolicySeverity, PolicyViolation};
+use crate::template_lint::LintIssue;
+
+/// SARIF version written by `--report sarif=...`
+pub const SARIF_VERSION: &str = "2.1.0";
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// File formats `--report` can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    /// SARIF, as read by GitHub code scanning
+    Sarif,
+    /// JUnit XML, as read by Jenkins and GitLab, with a test case per checked file
+    Junit,
+}
+
+/// A report file to write, from `--report FORMAT=PATH`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportTarget {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl ReportTarget {
+    /// Parse `sarif=findings.sarif`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let (format, path) = value
+            .split_once('=')
+            .filter(|(_, path)| !path.is_empty())
+            .ok_or_else(|| format!("expected FORMAT=PATH, e.g. sarif=findings.sarif, got '{}'", value))?;
+        let format = match format {
+            "sarif" => ReportFormat::Sarif,
+            "junit" => ReportFormat::Junit,
+            other => return Err(format!("unknown report format '{}' (expected sarif or junit)", other)),
+        };
+        Ok(Self { format, path: PathBuf::from(path) })
+    }
+}
+
+/// How serious a finding is, with SARIF's level names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FindingLevel {
+    Error,
+    Warning,
+}
+
+/// A problem in a template or a generated file: a lint issue, an output
+/// validation failure or a policy violation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    /// Stable rule id, e.g. `lint/undefined_variable` or `policy/data.terraform.tags`
+    pub rule_id: String,
+    /// Short description of the rule
+    pub rule_name: String,
+    pub level: FindingLevel,
+    pub message: String,
+    /// Template or generated file the finding is in
+    pub file: PathBuf,
+    /// 1-based line, if known
+    pub line: Option<usize>,
+}
+
+impl Finding {
+    /// A lint issue in a template under `template_dir`
+    pub fn from_lint(issue: &LintIssue, template_dir: &Path) -> Self {
+        let kind = serde_json::to_value(issue.kind).ok().and_then(|kind| kind.as_str().map(str::to_string));
+        Self {
+            rule_id: format!("lint/{}", kind.unwrap_or_default()),
+            rule_name: issue.kind.to_string(),
+            level: FindingLevel::Error,
+            message: issue.message.clone(),
+            file: template_dir.join(&issue.file),
+            line: Some(issue.line),
+        }
+    }
+
+    /// A policy violation in a file generated into `output_dir`
+    pub fn from_policy(violation: &PolicyViolation, output_dir: &Path) -> Self {
+        Self {
+            rule_id: format!("policy/{}", violation.package),
+            rule_name: format!("Policy {}", violation.package),
+            level: match violation.severity {
+                PolicySeverity::Error => FindingLevel::Error,
+                PolicySeverity::Warning => FindingLevel::Warning,
+            },
+            message: violation.message.clone(),
+            file: output_dir.join(&violation.path),
+            line: None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Collected {
+    findings: Vec<Finding>,
+    checked: Vec<PathBuf>,
+}
+
+/// Findings collected while a command runs, shared with the tasks that find
+/// them, along with the files that were checked
+#[derive(Debug, Clone, Default)]
+pub struct Findings(Arc<Mutex<Collected>>);
+
+impl Findings {
+    pub fn push(&self, finding: Finding) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).findings.push(finding);
+    }
+
+    /// Record a file that was checked, with or without findings
+    pub fn check(&self, file: impl Into<PathBuf>) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).checked.push(file.into());
+    }
+
+    /// Findings so far, sorted by file and line
+    pub fn to_vec(&self) -> Vec<Finding> {
+        let mut findings = self.0.lock().unwrap_or_else(|e| e.into_inner()).findings.clone();
+        findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        findings
+    }
+
+    /// Files checked or with findings, sorted and without duplicates
+    pub fn files(&self) -> Vec<PathBuf> {
+        let collected = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let mut files: Vec<PathBuf> =
+            collected.checked.iter().cloned().chain(collected.findings.iter().map(|f| f.file.clone())).collect();
+        files.sort();
+        files.dedup();
+        files
+    }
+}
+
+/// Write each requested report of what a command found; `command` names the
+/// JUnit test suite
+pub fn write_reports(targets: &[ReportTarget], command: &str, findings: &Findings) -> Result<(), CliError> {
+    for target in targets {
+        let content = match target.format {
+            ReportFormat::Sarif => serde_json::to_string_pretty(&render_sarif(&findings.to_vec()))?,
+            ReportFormat::Junit => render_junit(command, &findings.files(), &findings.to_vec()),
+        };
+        if let Some(parent) = target.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| CliError::IoError(format!("Failed to create {}: {}", parent.display(), e)))?;
+        }
+        std::fs::write(&target.path, content + "\n")
+            .map_err(|e| CliError::IoError(format!("Failed to write report {}: {}", target.path.display(), e)))?;
+    }
+    Ok(())
+}
+
+/// SARIF log with one run of idp-cli
+pub fn render_sarif(findings: &[Finding]) -> Value {
+    let mut rules: Vec<(&str, &str)> = findings.iter().map(|f| (f.rule_id.as_str(), f.rule_name.as_str())).collect();
+    rules.sort();
+    rules.dedup_by(|a, b| a.0 == b.0);
+
+    let results: Vec<Value> = findings
+        .iter()
+        .map(|finding| {
+            let mut location = json!({ "artifactLocation": { "uri": artifact_uri(&finding.file) } });
+            if let Some(line) = finding.line {
+                location["region"] = json!({ "startLine": line });
+            }
+            json!({
+                "ruleId": finding.rule_id,
+                "ruleIndex": rules.iter().position(|(id, _)| *id == finding.rule_id),
+                "level": finding.level,
+                "message": { "text": finding.message },
+                "locations": [{ "physicalLocation": location }],
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "idp-cli",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules
+                        .iter()
+                        .map(|(id, name)| json!({ "id": id, "shortDescription": { "text": name } }))
+                        .collect::<Vec<_>>(),
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+/// JUnit XML with a test case per file, failed by its error findings
+///
+/// Warnings don't fail a test case; they are listed in its `system-out`.
+pub fn render_junit(command: &str, files: &[PathBuf], findings: &[Finding]) -> String {
+    let describe = |finding: &Finding| match finding.line {
+        Some(line) => format!("line {}: {} ({})", line, finding.message, finding.rule_id),
+        None => format!("{} ({})", finding.message, finding.rule_id),
+    };
+    let mut cases = Vec::new();
+    let mut failures = 0;
+    for file in files {
+        let (errors, warnings): (Vec<&Finding>, Vec<&Finding>) =
+            findings.iter().filter(|f| &f.file == file).partition(|f| f.level == FindingLevel::Error);
+        let mut case = format!(
+            "    <testcase classname=\"{}\" name=\"{}\"",
+            xml_escape(command),
+            xml_escape(&artifact_uri(file))
+        );
+        if errors.is_empty() && warnings.is_empty() {
+            case.push_str("/>");
+            cases.push(case);
+            continue;
+        }
+        case.push('>');
+        if let Some(first) = errors.first() {
+            failures += 1;
+            let text: Vec<String> = errors.iter().map(|f| describe(f)).collect();
+            case.push_str(&format!(
+                "\n      <failure message=\"{}\" type=\"{}\">{}</failure>",
+                xml_escape(&format!("{} problem(s): {}", errors.len(), first.message)),
+                xml_escape(&first.rule_id),
+                xml_escape(&text.join("\n"))
+            ));
+        }
+        if !warnings.is_empty() {
+            let text: Vec<String> = warnings.iter().map(|f| format!("warning: {}", describe(f))).collect();
+            case.push_str(&format!("\n      <system-out>{}</system-out>", xml_escape(&text.join("\n"))));
+        }
+        case.push_str("\n    </testcase>");
+        cases.push(case);
+    }
+
+    let counts = format!("tests=\"{}\" failures=\"{}\" errors=\"0\"", files.len(), failures);
+    let mut xml = vec![
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>".to_string(),
+        format!("<testsuites name=\"idp-cli\" {}>", counts),
+        format!("  <testsuite name=\"{}\" {}>", xml_escape(command), counts),
+    ];
+    xml.extend(cases);
+    xml.push("  </testsuite>".to_string());
+    xml.push("</testsuites>".to_string());
+    xml.join("\n")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&apos;".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Path as written to reports, relative to the working directory when
+/// possible so code scanning can match it to a file in the repository
+fn artifact_uri(path: &Path) -> String {
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or_else(|| path.to_path_buf());
+    let uri = relative.to_string_lossy().replace('\\', "/");
+    uri.strip_prefix("./").map(str::to_string).unwrap_or(uri)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_lint::LintKind;
+
+    #[test]
+    fn test_parse_report_target() {
+        assert_eq!(
+            ReportTarget::parse("sarif=out/findings.sarif").unwrap(),
+            ReportTarget { format: ReportFormat::Sarif, path: PathBuf::from("out/findings.sarif") }
+        );
+        assert!(ReportTarget::parse("sarif").unwrap_err().contains("expected FORMAT=PATH"));
+        assert!(ReportTarget::parse("sarif=").is_err());
+        assert_eq!(ReportTarget::parse("junit=report.xml").unwrap().format, ReportFormat::Junit);
+        assert!(ReportTarget::parse("html=report.html").unwrap_err().contains("unknown report format 'html'"));
+    }
+
+    #[test]
+    fn test_render_sarif() {
+        let lint = LintIssue {
+            file: PathBuf::from("main.tf"),
+            line: 3,
+            kind: LintKind::UndefinedVariable,
+            expression: "{{stack.nmae}}".to_string(),
+            message: "'stack.nmae' is not defined".to_string(),
+        };
+        let policy = PolicyViolation {
+            path: PathBuf::from("storage.tf"),
+            package: "data.terraform.tags".to_string(),
+            message: "aws_s3_bucket.logs has no owner tag".to_string(),
+            severity: PolicySeverity::Warning,
+        };
+        let findings = Findings::default();
+        findings.push(Finding::from_policy(&policy, Path::new("./out")));
+        findings.push(Finding::from_lint(&lint, Path::new("templates")));
+
+        let sarif = render_sarif(&findings.to_vec());
+        assert_eq!(sarif["version"], "2.1.0");
+        let run = &sarif["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "lint/undefined_variable");
+        assert_eq!(run["tool"]["driver"]["rules"][1]["shortDescription"]["text"], "Policy data.terraform.tags");
+
+        // Sorted by file
+        let results = run["results"].as_array().unwrap();
+        assert_eq!(results[0]["ruleIndex"], 1);
+        assert_eq!(results[0]["level"], "warning");
+        assert_eq!(results[0]["message"]["text"], "aws_s3_bucket.logs has no owner tag");
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "out/storage.tf");
+        assert!(location.get("region").is_none());
+
+        assert_eq!(results[1]["ruleIndex"], 0);
+        assert_eq!(results[1]["level"], "error");
+        let location = &results[1]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "templates/main.tf");
+        assert_eq!(location["region"]["startLine"], 3);
+    }
+
+    #[test]
+    fn test_render_junit() {
+        let finding = |file: &str, level, message: &str| Finding {
+            rule_id: "validation/yaml".to_string(),
+            rule_name: "YAML validation".to_string(),
+            level,
+            message: message.to_string(),
+            file: PathBuf::from(file),
+            line: Some(4),
+        };
+        let findings = Findings::default();
+        findings.check("templates/main.tf");
+        findings.check("templates/app.yaml");
+        findings.check("templates/main.tf");
+        findings.push(finding("templates/app.yaml", FindingLevel::Error, "app.yaml: mapping values are not allowed <here>"));
+        findings.push(finding("out/db.tf", FindingLevel::Warning, "single replica"));
+
+        let xml = render_junit("generate", &findings.files(), &findings.to_vec());
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites name=\"idp-cli\" tests=\"3\" failures=\"1\""), "{}", xml);
+        assert!(xml.contains("<testcase classname=\"generate\" name=\"templates/main.tf\"/>"), "{}", xml);
+        assert!(
+            xml.contains("<failure message=\"1 problem(s): app.yaml: mapping values are not allowed &lt;here&gt;\" type=\"validation/yaml\">line 4: app.yaml"),
+            "{}",
+            xml
+        );
+        assert!(xml.contains("<system-out>warning: line 4: single replica (validation/yaml)</system-out>"), "{}", xml);
+        assert!(xml.ends_with("  </testsuite>\n</testsuites>"), "{}", xml);
+    }
+}
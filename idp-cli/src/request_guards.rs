@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Default maximum request body size (1 MiB)
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Default number of generations allowed to run at the same time
+pub const DEFAULT_MAX_CONCURRENT_GENERATIONS: usize = 4;
+
+/// Default number of requests per client per minute
+pub const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 60;
+
+/// Limits applied to requests in the Lambda and HTTP server modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuardConfig {
+    /// Largest accepted request body in bytes
+    pub max_body_bytes: usize,
+    /// Generations allowed to run at the same time
+    pub max_concurrent_generations: usize,
+    /// Requests allowed per client per minute (0 disables rate limiting)
+    pub rate_limit_per_minute: u32,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_concurrent_generations: DEFAULT_MAX_CONCURRENT_GENERATIONS,
+            rate_limit_per_minute: DEFAULT_RATE_LIMIT_PER_MINUTE,
+        }
+    }
+}
+
+impl GuardConfig {
+    /// Read limits from `MAX_REQUEST_BYTES`, `MAX_CONCURRENT_GENERATIONS` and
+    /// `RATE_LIMIT_PER_MINUTE`, falling back to the defaults for unset or invalid values
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_body_bytes: env_or("MAX_REQUEST_BYTES", defaults.max_body_bytes),
+            max_concurrent_generations: env_or(
+                "MAX_CONCURRENT_GENERATIONS",
+                defaults.max_concurrent_generations,
+            )
+            .max(1),
+            rate_limit_per_minute: env_or("RATE_LIMIT_PER_MINUTE", defaults.rate_limit_per_minute),
+        }
+    }
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Why a request was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GuardRejection {
+    /// The client exceeded its request rate
+    RateLimited { retry_after_secs: u64 },
+    /// The request body is larger than allowed
+    PayloadTooLarge { limit: usize },
+    /// Too many generations are already running
+    Busy,
+}
+
+impl GuardRejection {
+    /// HTTP status code for the rejection
+    pub fn status(&self) -> u16 {
+        match self {
+            GuardRejection::PayloadTooLarge { .. } => 413,
+            GuardRejection::RateLimited { .. } | GuardRejection::Busy => 429,
+        }
+    }
+
+    /// Seconds the client should wait before retrying, if retrying can help
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            GuardRejection::RateLimited { retry_after_secs } => Some(*retry_after_secs),
+            GuardRejection::Busy => Some(1),
+            GuardRejection::PayloadTooLarge { .. } => None,
+        }
+    }
+
+    /// Human-readable explanation
+    pub fn message(&self) -> String {
+        match self {
+            GuardRejection::RateLimited { .. } => "Rate limit exceeded".to_string(),
+            GuardRejection::PayloadTooLarge { limit } => {
+                format!("Request body exceeds the {} byte limit", limit)
+            }
+            GuardRejection::Busy => "Too many generations in progress".to_string(),
+        }
+    }
+}
+
+/// Token bucket for one client
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Shared rate limiter, size guard and generation concurrency limit
+///
+/// One instance is shared by all requests handled by a process (or a warm
+/// Lambda instance), so limits apply per process.
+pub struct RequestGuards {
+    config: GuardConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    generations: Arc<Semaphore>,
+}
+
+impl RequestGuards {
+    pub fn new(config: GuardConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            generations: Arc::new(Semaphore::new(config.max_concurrent_generations)),
+        }
+    }
+
+    pub fn config(&self) -> &GuardConfig {
+        &self.config
+    }
+
+    /// Reject bodies larger than the configured limit
+    pub fn check_body_size(&self, len: usize) -> Result<(), GuardRejection> {
+        if len > self.config.max_body_bytes {
+            return Err(GuardRejection::PayloadTooLarge {
+                limit: self.config.max_body_bytes,
+            });
+        }
+        Ok(())
+    }
+
+    /// Take one request from the client's allowance
+    ///
+    /// Each client may burst up to the per-minute limit; the allowance refills
+    /// continuously over the minute.
+    pub fn check_rate(&self, client: &str) -> Result<(), GuardRejection> {
+        self.check_rate_at(client, Instant::now())
+    }
+
+    fn check_rate_at(&self, client: &str, now: Instant) -> Result<(), GuardRejection> {
+        let limit = self.config.rate_limit_per_minute;
+        if limit == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(limit);
+        let per_second = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        // Forget clients whose allowance has fully refilled
+        buckets.retain(|_, b| {
+            let idle = now.saturating_duration_since(b.updated).as_secs_f64();
+            b.tokens + idle * per_second < capacity
+        });
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens < 1.0 {
+            let wait = (1.0 - bucket.tokens) / per_second;
+            return Err(GuardRejection::RateLimited {
+                retry_after_secs: (wait.ceil() as u64).max(1),
+            });
+        }
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Reserve a generation slot, held until the returned permit is dropped
+    pub fn try_start_generation(&self) -> Result<OwnedSemaphorePermit, GuardRejection> {
+        self.generations
+            .clone()
+            .try_acquire_owned()
+            .map_err(|_| GuardRejection::Busy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn guards(rate_limit_per_minute: u32, max_concurrent_generations: usize) -> RequestGuards {
+        RequestGuards::new(GuardConfig {
+            max_body_bytes: 10,
+            max_concurrent_generations,
+            rate_limit_per_minute,
+        })
+    }
+
+    #[test]
+    fn test_body_size_limit() {
+        let guards = guards(0, 1);
+        assert!(guards.check_body_size(10).is_ok());
+        let rejection = guards.check_body_size(11).unwrap_err();
+        assert_eq!(rejection.status(), 413);
+        assert_eq!(rejection.retry_after_secs(), None);
+    }
+
+    #[test]
+    fn test_rate_limit_is_per_client_and_refills() {
+        let guards = guards(2, 1);
+        let start = Instant::now();
+        assert!(guards.check_rate_at("a", start).is_ok());
+        assert!(guards.check_rate_at("a", start).is_ok());
+
+        let rejection = guards.check_rate_at("a", start).unwrap_err();
+        assert_eq!(rejection.status(), 429);
+        assert_eq!(rejection.retry_after_secs(), Some(30));
+        assert!(guards.check_rate_at("b", start).is_ok());
+
+        // Two per minute refills one request every 30 seconds
+        assert!(guards.check_rate_at("a", start + Duration::from_secs(30)).is_ok());
+    }
+
+    #[test]
+    fn test_zero_rate_limit_disables_limiting() {
+        let guards = guards(0, 1);
+        for _ in 0..100 {
+            assert!(guards.check_rate("a").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_concurrent_generation_limit() {
+        let guards = guards(0, 1);
+        let permit = guards.try_start_generation().unwrap();
+        assert_eq!(guards.try_start_generation().unwrap_err(), GuardRejection::Busy);
+        drop(permit);
+        assert!(guards.try_start_generation().is_ok());
+    }
+}
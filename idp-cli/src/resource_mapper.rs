@@ -0,0 +1,317 @@
+use serde_json::Value;
+
+use crate::region_check::Cloud;
+
+/// Kind of cloud resource a blueprint or stack resource stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// Managed relational database
+    Database,
+    /// Managed NoSQL database
+    NoSqlDatabase,
+    /// Managed Redis/Memcached
+    Cache,
+    /// Message queue
+    Queue,
+    /// Shared message bus or topic
+    MessageBus,
+    /// Object storage bucket
+    Bucket,
+    /// Virtual network
+    Network,
+    /// Managed Kubernetes cluster
+    Cluster,
+    /// Virtual machine
+    Compute,
+}
+
+/// Words in resource type names for each kind, checked in order
+///
+/// The API's resource type category only says whether a type is SHARED,
+/// NON_SHARED or BOTH, so types are told apart by name, e.g. "Managed
+/// Container Orchestrator", "Service Bus" or "NoSQL Database".
+const KIND_WORDS: &[(ResourceKind, &[&str])] = &[
+    (ResourceKind::NoSqlDatabase, &["nosql", "dynamodb", "cosmos", "cosmosdb", "mongodb", "firestore", "bigtable"]),
+    (ResourceKind::Database, &["database", "relational", "rds", "sql", "postgres", "postgresql", "mysql"]),
+    (ResourceKind::Cache, &["cache", "redis", "memcache", "memcached", "elasticache"]),
+    (ResourceKind::Queue, &["queue", "sqs"]),
+    (ResourceKind::MessageBus, &["bus", "topic", "sns", "pubsub", "messaging"]),
+    (ResourceKind::Bucket, &["storage", "bucket", "s3", "blob"]),
+    (ResourceKind::Network, &["network", "vpc", "vnet"]),
+    (ResourceKind::Cluster, &["orchestrator", "kubernetes", "k8s", "eks", "aks", "gke", "cluster"]),
+    (ResourceKind::Compute, &["vm", "compute", "ec2", "machine", "instance"]),
+];
+
+impl ResourceKind {
+    /// Kind for a resource type name, or None when the name says nothing about it
+    pub fn for_resource_type(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        let words: Vec<&str> = name.split(|c: char| !c.is_ascii_alphanumeric()).filter(|w| !w.is_empty()).collect();
+        KIND_WORDS
+            .iter()
+            .find(|(_, kind_words)| kind_words.iter().any(|word| words.contains(word)))
+            .map(|(kind, _)| *kind)
+    }
+}
+
+/// A blueprint or stack resource with the cloud and kind it maps to
+#[derive(Debug, Clone, PartialEq)]
+pub struct MappedResource<'a> {
+    pub name: &'a str,
+    pub cloud: Cloud,
+    pub kind: ResourceKind,
+    pub configuration: &'a Value,
+}
+
+/// Whether template data comes from a stack or a blueprint, with its name and description
+pub fn source_metadata(data: &Value) -> (&'static str, Option<&str>, Option<&str>) {
+    let source = if data.get("stack").is_some() { "stack" } else { "blueprint" };
+    let text = |field: &str| data.get(source)?.get(field)?.as_str();
+    (source, text("name"), text("description"))
+}
+
+/// Classify the resources in template data (`stack_resources`, or `resources` for blueprints)
+///
+/// Returns the mapped resources and a warning for each resource that can't be
+/// mapped because it has no name, or its cloud provider or resource type is unknown.
+pub fn map_resources(data: &Value) -> (Vec<MappedResource<'_>>, Vec<String>) {
+    let mut mapped = Vec::new();
+    let mut warnings = Vec::new();
+    let resources = data.get("stack_resources").or_else(|| data.get("resources")).and_then(Value::as_array);
+    for (index, resource) in resources.into_iter().flatten().enumerate() {
+        let text = |path: &str| resource.pointer(path).and_then(Value::as_str).unwrap_or_default();
+        let Some(name) = resource.get("name").and_then(Value::as_str) else {
+            warnings.push(format!("Resource {} has no name and was not mapped", index));
+            continue;
+        };
+        let Some(cloud) = Cloud::from_provider_name(text("/cloud_provider/name")) else {
+            warnings.push(format!(
+                "Resource '{}' uses an unknown cloud provider '{}' and was not mapped",
+                name,
+                text("/cloud_provider/name")
+            ));
+            continue;
+        };
+        let Some(kind) = ResourceKind::for_resource_type(text("/resource_type/name")) else {
+            warnings.push(format!(
+                "Resource '{}' has a resource type '{}' with no known mapping and was not mapped",
+                name,
+                text("/resource_type/name")
+            ));
+            continue;
+        };
+        mapped.push(MappedResource {
+            name,
+            cloud,
+            kind,
+            configuration: resource.get("configuration").unwrap_or(&Value::Null),
+        });
+    }
+    (mapped, warnings)
+}
+
+/// Pulumi resource type token by cloud and kind, from the aws, azure-native and gcp providers
+const PULUMI: &[(Cloud, ResourceKind, &str)] = &[
+    (Cloud::Aws, ResourceKind::Database, "aws:rds:Instance"),
+    (Cloud::Aws, ResourceKind::NoSqlDatabase, "aws:dynamodb:Table"),
+    (Cloud::Aws, ResourceKind::Cache, "aws:elasticache:ReplicationGroup"),
+    (Cloud::Aws, ResourceKind::Queue, "aws:sqs:Queue"),
+    (Cloud::Aws, ResourceKind::MessageBus, "aws:sns:Topic"),
+    (Cloud::Aws, ResourceKind::Bucket, "aws:s3:BucketV2"),
+    (Cloud::Aws, ResourceKind::Network, "aws:ec2:Vpc"),
+    (Cloud::Aws, ResourceKind::Cluster, "aws:eks:Cluster"),
+    (Cloud::Aws, ResourceKind::Compute, "aws:ec2:Instance"),
+    (Cloud::Azure, ResourceKind::Database, "azure-native:dbforpostgresql:Server"),
+    (Cloud::Azure, ResourceKind::NoSqlDatabase, "azure-native:documentdb:DatabaseAccount"),
+    (Cloud::Azure, ResourceKind::Cache, "azure-native:cache:Redis"),
+    (Cloud::Azure, ResourceKind::Queue, "azure-native:servicebus:Queue"),
+    (Cloud::Azure, ResourceKind::MessageBus, "azure-native:servicebus:Namespace"),
+    (Cloud::Azure, ResourceKind::Bucket, "azure-native:storage:StorageAccount"),
+    (Cloud::Azure, ResourceKind::Network, "azure-native:network:VirtualNetwork"),
+    (Cloud::Azure, ResourceKind::Cluster, "azure-native:containerservice:ManagedCluster"),
+    (Cloud::Azure, ResourceKind::Compute, "azure-native:compute:VirtualMachine"),
+    (Cloud::Gcp, ResourceKind::Database, "gcp:sql:DatabaseInstance"),
+    (Cloud::Gcp, ResourceKind::NoSqlDatabase, "gcp:bigtable:Instance"),
+    (Cloud::Gcp, ResourceKind::Cache, "gcp:redis:Instance"),
+    (Cloud::Gcp, ResourceKind::Queue, "gcp:pubsub:Subscription"),
+    (Cloud::Gcp, ResourceKind::MessageBus, "gcp:pubsub:Topic"),
+    (Cloud::Gcp, ResourceKind::Bucket, "gcp:storage:Bucket"),
+    (Cloud::Gcp, ResourceKind::Network, "gcp:compute:Network"),
+    (Cloud::Gcp, ResourceKind::Cluster, "gcp:container:Cluster"),
+    (Cloud::Gcp, ResourceKind::Compute, "gcp:compute:Instance"),
+];
+
+/// Pulumi resource type token, e.g. `aws:rds:Instance`, for a cloud and resource kind
+pub fn pulumi_type(cloud: Cloud, kind: ResourceKind) -> Option<&'static str> {
+    PULUMI.iter().find(|(c, k, _)| *c == cloud && *k == kind).map(|(_, _, token)| *token)
+}
+
+/// Terraform resource type by cloud and kind, from the aws, azurerm and google providers
+const TERRAFORM: &[(Cloud, ResourceKind, &str)] = &[
+    (Cloud::Aws, ResourceKind::Database, "aws_db_instance"),
+    (Cloud::Aws, ResourceKind::NoSqlDatabase, "aws_dynamodb_table"),
+    (Cloud::Aws, ResourceKind::Cache, "aws_elasticache_replication_group"),
+    (Cloud::Aws, ResourceKind::Queue, "aws_sqs_queue"),
+    (Cloud::Aws, ResourceKind::MessageBus, "aws_sns_topic"),
+    (Cloud::Aws, ResourceKind::Bucket, "aws_s3_bucket"),
+    (Cloud::Aws, ResourceKind::Network, "aws_vpc"),
+    (Cloud::Aws, ResourceKind::Cluster, "aws_eks_cluster"),
+    (Cloud::Aws, ResourceKind::Compute, "aws_instance"),
+    (Cloud::Azure, ResourceKind::Database, "azurerm_postgresql_flexible_server"),
+    (Cloud::Azure, ResourceKind::NoSqlDatabase, "azurerm_cosmosdb_account"),
+    (Cloud::Azure, ResourceKind::Cache, "azurerm_redis_cache"),
+    (Cloud::Azure, ResourceKind::Queue, "azurerm_servicebus_queue"),
+    (Cloud::Azure, ResourceKind::MessageBus, "azurerm_servicebus_namespace"),
+    (Cloud::Azure, ResourceKind::Bucket, "azurerm_storage_account"),
+    (Cloud::Azure, ResourceKind::Network, "azurerm_virtual_network"),
+    (Cloud::Azure, ResourceKind::Cluster, "azurerm_kubernetes_cluster"),
+    (Cloud::Azure, ResourceKind::Compute, "azurerm_linux_virtual_machine"),
+    (Cloud::Gcp, ResourceKind::Database, "google_sql_database_instance"),
+    (Cloud::Gcp, ResourceKind::NoSqlDatabase, "google_bigtable_instance"),
+    (Cloud::Gcp, ResourceKind::Cache, "google_redis_instance"),
+    (Cloud::Gcp, ResourceKind::Queue, "google_pubsub_subscription"),
+    (Cloud::Gcp, ResourceKind::MessageBus, "google_pubsub_topic"),
+    (Cloud::Gcp, ResourceKind::Bucket, "google_storage_bucket"),
+    (Cloud::Gcp, ResourceKind::Network, "google_compute_network"),
+    (Cloud::Gcp, ResourceKind::Cluster, "google_container_cluster"),
+    (Cloud::Gcp, ResourceKind::Compute, "google_compute_instance"),
+];
+
+/// Terraform resource type, e.g. `aws_db_instance`, for a cloud and resource kind
+pub fn terraform_type(cloud: Cloud, kind: ResourceKind) -> Option<&'static str> {
+    TERRAFORM.iter().find(|(c, k, _)| *c == cloud && *k == kind).map(|(_, _, resource_type)| *resource_type)
+}
+
+/// Crossplane managed resource (`apiVersion`, `kind`) by cloud and kind, from the Upbound providers
+const CROSSPLANE: &[(Cloud, ResourceKind, &str, &str)] = &[
+    (Cloud::Aws, ResourceKind::Database, "rds.aws.upbound.io/v1beta1", "Instance"),
+    (Cloud::Aws, ResourceKind::NoSqlDatabase, "dynamodb.aws.upbound.io/v1beta1", "Table"),
+    (Cloud::Aws, ResourceKind::Cache, "elasticache.aws.upbound.io/v1beta1", "ReplicationGroup"),
+    (Cloud::Aws, ResourceKind::Queue, "sqs.aws.upbound.io/v1beta1", "Queue"),
+    (Cloud::Aws, ResourceKind::MessageBus, "sns.aws.upbound.io/v1beta1", "Topic"),
+    (Cloud::Aws, ResourceKind::Bucket, "s3.aws.upbound.io/v1beta1", "Bucket"),
+    (Cloud::Aws, ResourceKind::Network, "ec2.aws.upbound.io/v1beta1", "VPC"),
+    (Cloud::Aws, ResourceKind::Cluster, "eks.aws.upbound.io/v1beta1", "Cluster"),
+    (Cloud::Aws, ResourceKind::Compute, "ec2.aws.upbound.io/v1beta1", "Instance"),
+    (Cloud::Azure, ResourceKind::Database, "dbforpostgresql.azure.upbound.io/v1beta1", "FlexibleServer"),
+    (Cloud::Azure, ResourceKind::NoSqlDatabase, "cosmosdb.azure.upbound.io/v1beta1", "Account"),
+    (Cloud::Azure, ResourceKind::Cache, "cache.azure.upbound.io/v1beta1", "RedisCache"),
+    (Cloud::Azure, ResourceKind::Queue, "servicebus.azure.upbound.io/v1beta1", "Queue"),
+    (Cloud::Azure, ResourceKind::MessageBus, "servicebus.azure.upbound.io/v1beta1", "ServiceBusNamespace"),
+    (Cloud::Azure, ResourceKind::Bucket, "storage.azure.upbound.io/v1beta1", "Account"),
+    (Cloud::Azure, ResourceKind::Network, "network.azure.upbound.io/v1beta1", "VirtualNetwork"),
+    (Cloud::Azure, ResourceKind::Cluster, "containerservice.azure.upbound.io/v1beta1", "KubernetesCluster"),
+    (Cloud::Azure, ResourceKind::Compute, "compute.azure.upbound.io/v1beta1", "LinuxVirtualMachine"),
+    (Cloud::Gcp, ResourceKind::Database, "sql.gcp.upbound.io/v1beta1", "DatabaseInstance"),
+    (Cloud::Gcp, ResourceKind::NoSqlDatabase, "bigtable.gcp.upbound.io/v1beta1", "Instance"),
+    (Cloud::Gcp, ResourceKind::Cache, "redis.gcp.upbound.io/v1beta1", "Instance"),
+    (Cloud::Gcp, ResourceKind::Queue, "pubsub.gcp.upbound.io/v1beta1", "Subscription"),
+    (Cloud::Gcp, ResourceKind::MessageBus, "pubsub.gcp.upbound.io/v1beta1", "Topic"),
+    (Cloud::Gcp, ResourceKind::Bucket, "storage.gcp.upbound.io/v1beta1", "Bucket"),
+    (Cloud::Gcp, ResourceKind::Network, "compute.gcp.upbound.io/v1beta1", "Network"),
+    (Cloud::Gcp, ResourceKind::Cluster, "container.gcp.upbound.io/v1beta1", "Cluster"),
+    (Cloud::Gcp, ResourceKind::Compute, "compute.gcp.upbound.io/v1beta1", "Instance"),
+];
+
+/// Crossplane `apiVersion` and `kind` for a cloud and resource kind
+pub fn crossplane_type(cloud: Cloud, kind: ResourceKind) -> Option<(&'static str, &'static str)> {
+    CROSSPLANE
+        .iter()
+        .find(|(c, k, _, _)| *c == cloud && *k == kind)
+        .map(|(_, _, api_version, kind)| (*api_version, *kind))
+}
+
+/// Kubernetes object name (lowercase letters, digits and `-`) for a resource or stack name
+pub fn kubernetes_name(name: &str) -> String {
+    let sanitized: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    match sanitized.trim_matches('-') {
+        "" => "unnamed".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_map_resources() {
+        let data = json!({
+            "resources": [
+                {
+                    "name": "orders-db",
+                    "resource_type": {"name": "PostgreSQL", "category": "NON_SHARED"},
+                    "cloud_provider": {"name": "AWS"},
+                    "configuration": {"engine": "postgres"}
+                },
+                {"name": "edge", "resource_type": {"name": "CDN", "category": "BOTH"}, "cloud_provider": {"name": "Akamai"}}
+            ]
+        });
+
+        let (mapped, warnings) = map_resources(&data);
+        assert_eq!(mapped.len(), 1);
+        assert_eq!((mapped[0].name, mapped[0].cloud, mapped[0].kind), ("orders-db", Cloud::Aws, ResourceKind::Database));
+        assert_eq!(mapped[0].configuration, &json!({"engine": "postgres"}));
+        assert_eq!(warnings, vec!["Resource 'edge' uses an unknown cloud provider 'Akamai' and was not mapped"]);
+    }
+
+    #[test]
+    fn test_kinds_of_the_api_resource_types() {
+        // Resource types seeded by the API, whose category is SHARED, NON_SHARED or BOTH
+        let seeded = [
+            ("Managed Container Orchestrator", ResourceKind::Cluster),
+            ("Relational Database Server", ResourceKind::Database),
+            ("Service Bus", ResourceKind::MessageBus),
+            ("Storage", ResourceKind::Bucket),
+            ("NoSQL Database", ResourceKind::NoSqlDatabase),
+            ("Relational Database", ResourceKind::Database),
+            ("Queue", ResourceKind::Queue),
+            ("Cache", ResourceKind::Cache),
+        ];
+        for (name, kind) in seeded {
+            assert_eq!(ResourceKind::for_resource_type(name), Some(kind), "{}", name);
+        }
+        assert_eq!(ResourceKind::for_resource_type("Virtual Machine"), Some(ResourceKind::Compute));
+        assert_eq!(ResourceKind::for_resource_type("CDN"), None);
+
+        let data = json!({
+            "stack_resources": [
+                {"name": "jobs", "resource_type": {"name": "Queue", "category": "NON_SHARED"}, "cloud_provider": {"name": "AWS"}},
+                {"name": "events", "resource_type": {"name": "Service Bus", "category": "SHARED"}, "cloud_provider": {"name": "Azure"}},
+                {"name": "edge", "resource_type": {"name": "CDN", "category": "BOTH"}, "cloud_provider": {"name": "AWS"}}
+            ]
+        });
+        let (mapped, warnings) = map_resources(&data);
+        let types: Vec<_> = mapped.iter().map(|r| terraform_type(r.cloud, r.kind)).collect();
+        assert_eq!(types, vec![Some("aws_sqs_queue"), Some("azurerm_servicebus_namespace")]);
+        assert_eq!(warnings, vec!["Resource 'edge' has a resource type 'CDN' with no known mapping and was not mapped"]);
+    }
+
+    #[test]
+    fn test_every_cloud_and_kind_has_a_type() {
+        let kinds = [
+            ResourceKind::Database,
+            ResourceKind::NoSqlDatabase,
+            ResourceKind::Cache,
+            ResourceKind::Queue,
+            ResourceKind::MessageBus,
+            ResourceKind::Bucket,
+            ResourceKind::Network,
+            ResourceKind::Cluster,
+            ResourceKind::Compute,
+        ];
+        for cloud in [Cloud::Aws, Cloud::Azure, Cloud::Gcp] {
+            for kind in kinds {
+                assert!(crossplane_type(cloud, kind).is_some(), "{:?} {:?}", cloud, kind);
+                assert!(pulumi_type(cloud, kind).is_some(), "{:?} {:?}", cloud, kind);
+                assert!(terraform_type(cloud, kind).is_some(), "{:?} {:?}", cloud, kind);
+            }
+        }
+        assert_eq!(kubernetes_name("Orders DB_1"), "orders-db-1");
+    }
+}
@@ -0,0 +1,458 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_s3::primitives::ByteStream;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use crate::error::CliError;
+use crate::template_discovery::sha256_hex;
+
+/// Default time a cached response stays valid (24 hours)
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Fingerprint of everything a generation's output depends on
+///
+/// Combines a hash of the fetched blueprint or stack (its etag), the template
+/// digest, a hash of the custom variables and where outputs are uploaded.
+pub fn fingerprint<S: Serialize>(
+    source: &S,
+    template_digest: &str,
+    variables: Option<&serde_json::Value>,
+    outputs_bucket: Option<&str>,
+) -> Result<String, CliError> {
+    let source_etag = sha256_hex(&serde_json::to_vec(source)?);
+    let variables_hash = sha256_hex(&serde_json::to_vec(&variables)?);
+    Ok(sha256_hex(
+        format!(
+            "{}\n{}\n{}\n{}",
+            source_etag,
+            template_digest,
+            variables_hash,
+            outputs_bucket.unwrap_or_default()
+        )
+        .as_bytes(),
+    ))
+}
+
+/// Stored response and the inputs it was generated from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    fingerprint: String,
+    /// Seconds since the Unix epoch
+    created_at: u64,
+    response: serde_json::Value,
+}
+
+/// Length of the AES-GCM nonce stored in front of each ciphertext
+const NONCE_LEN: usize = 12;
+
+/// Encrypts entries before the S3 and DynamoDB backends store them
+///
+/// AES-256-GCM with a random nonce per entry. The entry's location is
+/// authenticated with it, so an entry copied to another location doesn't
+/// decrypt.
+#[derive(Clone)]
+pub struct EntryCipher(Aes256Gcm);
+
+/// Encrypted entry as stored
+#[derive(Serialize, Deserialize)]
+struct SealedEntry {
+    /// Base64 of the nonce followed by the ciphertext
+    sealed: String,
+}
+
+impl EntryCipher {
+    /// Key given as base64 of 32 random bytes, e.g. from `openssl rand -base64 32`
+    pub fn from_base64(key: &str) -> Result<Self, String> {
+        let bytes = BASE64.decode(key.trim()).map_err(|_| "the key is not valid base64".to_string())?;
+        Aes256Gcm::new_from_slice(&bytes)
+            .map(Self)
+            .map_err(|_| format!("the key must be 32 bytes, got {}", bytes.len()))
+    }
+
+    fn seal(&self, location: &str, plaintext: &[u8]) -> Result<SealedEntry, CliError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .0
+            .encrypt(&nonce, Payload { msg: plaintext, aad: location.as_bytes() })
+            .map_err(|_| CliError::ProcessingError(format!("Failed to encrypt the cache entry for {}", location)))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(SealedEntry { sealed: BASE64.encode(sealed) })
+    }
+
+    fn open(&self, location: &str, entry: &SealedEntry) -> Result<Vec<u8>, CliError> {
+        let undecryptable = || {
+            CliError::ConfigError(format!(
+                "The cache entry for {} can't be decrypted; it was written with another key or changed",
+                location
+            ))
+        };
+        let bytes = BASE64.decode(&entry.sealed).map_err(|_| undecryptable())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(undecryptable());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        self.0
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: location.as_bytes() })
+            .map_err(|_| undecryptable())
+    }
+}
+
+/// Where cached responses are stored
+pub enum CacheBackend {
+    Disabled,
+    /// In-process map, for a single long-running server
+    Memory(Mutex<HashMap<String, CacheEntry>>),
+    /// One JSON object per output location under `cache/` in a bucket
+    S3 {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+    },
+    /// One item per output location, with an `expires_at` TTL attribute
+    DynamoDb {
+        client: aws_sdk_dynamodb::Client,
+        table: String,
+    },
+}
+
+/// Cache of generation responses
+///
+/// Entries are stored per output location (data source and identifier) and
+/// hold the fingerprint of the inputs that produced them. Every generation
+/// overwrites the files at its location, so only the latest result there can
+/// be returned: a request hits when its fingerprint matches the stored one.
+pub struct ResponseCache {
+    backend: CacheBackend,
+    ttl: Duration,
+    /// Encrypts entries stored in S3 or DynamoDB
+    cipher: Option<EntryCipher>,
+}
+
+impl ResponseCache {
+    pub fn new(backend: CacheBackend, ttl: Duration) -> Self {
+        Self { backend, ttl, cipher: None }
+    }
+
+    /// Encrypt entries stored in S3 or DynamoDB
+    ///
+    /// Entries in the memory backend never leave the process and are kept as is.
+    pub fn with_encryption(mut self, cipher: EntryCipher) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    /// Cache that stores nothing
+    pub fn disabled() -> Self {
+        Self::new(CacheBackend::Disabled, DEFAULT_CACHE_TTL)
+    }
+
+    /// In-process cache
+    pub fn memory(ttl: Duration) -> Self {
+        Self::new(CacheBackend::Memory(Mutex::new(HashMap::new())), ttl)
+    }
+
+    /// Configure the cache from environment variables
+    ///
+    /// - `RESPONSE_CACHE`: `none` (default), `memory`, `s3` or `dynamodb`
+    /// - `RESPONSE_CACHE_BUCKET`: bucket for the S3 backend (default: `OUTPUTS_BUCKET`)
+    /// - `RESPONSE_CACHE_TABLE`: table for the DynamoDB backend (required)
+    /// - `RESPONSE_CACHE_TTL_SECS`: how long entries stay valid (default: 86400)
+    /// - `RESPONSE_CACHE_ENCRYPTION_KEY`: base64 AES-256 key encrypting stored entries (optional)
+    pub async fn from_env() -> Result<Self, CliError> {
+        Self::from_env_named("RESPONSE_CACHE", "none").await
+    }
+
+    /// Configure a store from `<name>`, `<name>_BUCKET`, `<name>_TABLE`,
+    /// `<name>_TTL_SECS` and `<name>_ENCRYPTION_KEY`, using `default_backend`
+    /// when `<name>` is unset
+    pub async fn from_env_named(name: &str, default_backend: &str) -> Result<Self, CliError> {
+        let ttl_var = format!("{}_TTL_SECS", name);
+        let ttl = match std::env::var(&ttl_var) {
+            Ok(value) => Duration::from_secs(value.trim().parse().map_err(|_| {
+                CliError::ConfigError(format!("{} must be a number of seconds, got: {}", ttl_var, value))
+            })?),
+            Err(_) => DEFAULT_CACHE_TTL,
+        };
+
+        let backend_name = std::env::var(name).unwrap_or_else(|_| default_backend.to_string());
+        let backend = match backend_name.to_lowercase().as_str() {
+            "none" => CacheBackend::Disabled,
+            "memory" => CacheBackend::Memory(Mutex::new(HashMap::new())),
+            "s3" => {
+                let bucket = std::env::var(format!("{}_BUCKET", name))
+                    .or_else(|_| std::env::var("OUTPUTS_BUCKET"))
+                    .map_err(|_| {
+                        CliError::ConfigError(format!(
+                            "{}_BUCKET or OUTPUTS_BUCKET is required when {}=s3",
+                            name, name
+                        ))
+                    })?;
+                let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                CacheBackend::S3 {
+                    client: aws_sdk_s3::Client::new(&config),
+                    bucket,
+                }
+            }
+            "dynamodb" => {
+                let table = std::env::var(format!("{}_TABLE", name)).map_err(|_| {
+                    CliError::ConfigError(format!("{}_TABLE is required when {}=dynamodb", name, name))
+                })?;
+                let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+                CacheBackend::DynamoDb {
+                    client: aws_sdk_dynamodb::Client::new(&config),
+                    table,
+                }
+            }
+            other => {
+                return Err(CliError::ConfigError(format!(
+                    "Unknown {} '{}'. Use none, memory, s3 or dynamodb",
+                    name, other
+                )))
+            }
+        };
+
+        let key_var = format!("{}_ENCRYPTION_KEY", name);
+        let cache = Self::new(backend, ttl);
+        match std::env::var(&key_var) {
+            Ok(key) => {
+                let cipher = EntryCipher::from_base64(&key)
+                    .map_err(|e| CliError::ConfigError(format!("Invalid {}: {}", key_var, e)))?;
+                Ok(cache.with_encryption(cipher))
+            }
+            Err(_) => Ok(cache),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self.backend, CacheBackend::Disabled)
+    }
+
+    /// Cached response for a location, if it was generated from the same inputs
+    ///
+    /// Backend failures are logged and treated as a miss.
+    pub async fn get<T: DeserializeOwned>(&self, location: &str, fingerprint: &str) -> Option<T> {
+        match self.lookup(location).await {
+            Some((stored, response)) if stored == fingerprint => {
+                info!("Response cache hit for {}", location);
+                Some(response)
+            }
+            _ => None,
+        }
+    }
+
+    /// Fingerprint and response stored for a location, if not expired
+    ///
+    /// Backend failures are logged and treated as a miss.
+    pub async fn lookup<T: DeserializeOwned>(&self, location: &str) -> Option<(String, T)> {
+        let entry = match self.load(location).await {
+            Ok(entry) => entry?,
+            Err(e) => {
+                warn!("Response cache lookup failed for {}: {}", location, e);
+                return None;
+            }
+        };
+
+        let age = now_secs().saturating_sub(entry.created_at);
+        if age >= self.ttl.as_secs() {
+            return None;
+        }
+        match serde_json::from_value(entry.response) {
+            Ok(response) => Some((entry.fingerprint, response)),
+            Err(e) => {
+                warn!("Ignoring unreadable cached response for {}: {}", location, e);
+                None
+            }
+        }
+    }
+
+    /// Store the response for a location, replacing any earlier entry
+    ///
+    /// Backend failures are logged; caching never fails a request.
+    pub async fn put<T: Serialize>(&self, location: &str, fingerprint: &str, response: &T) {
+        let entry = match serde_json::to_value(response) {
+            Ok(response) => CacheEntry {
+                fingerprint: fingerprint.to_string(),
+                created_at: now_secs(),
+                response,
+            },
+            Err(e) => {
+                warn!("Failed to serialize response for the cache: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.store(location, entry).await {
+            warn!("Failed to cache response for {}: {}", location, e);
+        }
+    }
+
+    async fn load(&self, location: &str) -> Result<Option<CacheEntry>, CliError> {
+        match &self.backend {
+            CacheBackend::Disabled => Ok(None),
+            CacheBackend::Memory(entries) => Ok(entries.lock().unwrap().get(location).cloned()),
+            CacheBackend::S3 { client, bucket } => {
+                let result = client.get_object().bucket(bucket).key(cache_object_key(location)).send().await;
+                let object = match result {
+                    Ok(object) => object,
+                    Err(e) if e.as_service_error().is_some_and(|e| e.is_no_such_key()) => return Ok(None),
+                    Err(e) => return Err(CliError::S3Error(e.to_string())),
+                };
+                let bytes = object
+                    .body
+                    .collect()
+                    .await
+                    .map_err(|e| CliError::S3Error(e.to_string()))?
+                    .into_bytes();
+                self.decode(location, &bytes)
+            }
+            CacheBackend::DynamoDb { client, table } => {
+                let output = client
+                    .get_item()
+                    .table_name(table)
+                    .key("location", AttributeValue::S(location.to_string()))
+                    .send()
+                    .await
+                    .map_err(|e| CliError::ApiError(e.to_string()))?;
+                match output.item().and_then(|item| item.get("entry")).and_then(|value| value.as_s().ok()) {
+                    Some(json) => self.decode(location, json.as_bytes()),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+
+    async fn store(&self, location: &str, entry: CacheEntry) -> Result<(), CliError> {
+        match &self.backend {
+            CacheBackend::Disabled => Ok(()),
+            CacheBackend::Memory(entries) => {
+                entries.lock().unwrap().insert(location.to_string(), entry);
+                Ok(())
+            }
+            CacheBackend::S3 { client, bucket } => {
+                client
+                    .put_object()
+                    .bucket(bucket)
+                    .key(cache_object_key(location))
+                    .content_type("application/json")
+                    .body(ByteStream::from(self.encode(location, &entry)?.into_bytes()))
+                    .send()
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| CliError::S3Error(e.to_string()))
+            }
+            CacheBackend::DynamoDb { client, table } => {
+                let expires_at = entry.created_at + self.ttl.as_secs();
+                client
+                    .put_item()
+                    .table_name(table)
+                    .item("location", AttributeValue::S(location.to_string()))
+                    .item("entry", AttributeValue::S(self.encode(location, &entry)?))
+                    .item("expires_at", AttributeValue::N(expires_at.to_string()))
+                    .send()
+                    .await
+                    .map(|_| ())
+                    .map_err(|e| CliError::ApiError(e.to_string()))
+            }
+        }
+    }
+
+    /// Entry as stored in S3 or DynamoDB: JSON, encrypted if a key is set
+    fn encode(&self, location: &str, entry: &CacheEntry) -> Result<String, CliError> {
+        let json = serde_json::to_string(entry)?;
+        match &self.cipher {
+            Some(cipher) => Ok(serde_json::to_string(&cipher.seal(location, json.as_bytes())?)?),
+            None => Ok(json),
+        }
+    }
+
+    fn decode(&self, location: &str, stored: &[u8]) -> Result<Option<CacheEntry>, CliError> {
+        let Some(cipher) = &self.cipher else {
+            return Ok(Some(serde_json::from_slice(stored)?));
+        };
+        match serde_json::from_slice::<SealedEntry>(stored) {
+            Ok(sealed) => Ok(Some(serde_json::from_slice(&cipher.open(location, &sealed)?)?)),
+            // Written before encryption was turned on; replaced by the next generation
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+fn cache_object_key(location: &str) -> String {
+    format!("cache/{}.json", location.trim_matches('/'))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_fingerprint_tracks_every_input() {
+        let source = json!({"name": "payments", "resources": []});
+        let vars = json!({"env": "prod"});
+        let base = fingerprint(&source, "digest", Some(&vars), Some("outputs")).unwrap();
+
+        assert_eq!(base, fingerprint(&source, "digest", Some(&vars), Some("outputs")).unwrap());
+        assert_ne!(base, fingerprint(&json!({"name": "payments", "resources": [1]}), "digest", Some(&vars), Some("outputs")).unwrap());
+        assert_ne!(base, fingerprint(&source, "other", Some(&vars), Some("outputs")).unwrap());
+        assert_ne!(base, fingerprint(&source, "digest", None, Some("outputs")).unwrap());
+        assert_ne!(base, fingerprint(&source, "digest", Some(&vars), None).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_memory_cache_hits_only_matching_fresh_entries() {
+        let cache = ResponseCache::memory(DEFAULT_CACHE_TTL);
+        assert_eq!(cache.get::<serde_json::Value>("outputs/stack/a", "f1").await, None);
+
+        cache.put("outputs/stack/a", "f1", &json!({"files": 2})).await;
+        assert_eq!(cache.get("outputs/stack/a", "f1").await, Some(json!({"files": 2})));
+        assert_eq!(cache.get::<serde_json::Value>("outputs/stack/a", "f2").await, None);
+        assert_eq!(cache.get::<serde_json::Value>("outputs/stack/b", "f1").await, None);
+
+        let expired = ResponseCache::memory(Duration::ZERO);
+        expired.put("outputs/stack/a", "f1", &json!({"files": 2})).await;
+        assert_eq!(expired.get::<serde_json::Value>("outputs/stack/a", "f1").await, None);
+    }
+
+    #[test]
+    fn test_encrypted_entries_only_open_with_the_key_at_their_location() {
+        let key = BASE64.encode([7u8; 32]);
+        let cache = ResponseCache::disabled().with_encryption(EntryCipher::from_base64(&key).unwrap());
+        let entry = CacheEntry {
+            fingerprint: "f1".to_string(),
+            created_at: 1,
+            response: json!({"files": [{"path": "main.tf", "content": "password = \"hunter2\""}]}),
+        };
+
+        let stored = cache.encode("outputs/stack/a", &entry).unwrap();
+        assert!(!stored.contains("hunter2") && !stored.contains("main.tf"), "{}", stored);
+        assert_ne!(stored, cache.encode("outputs/stack/a", &entry).unwrap(), "each entry gets a new nonce");
+        let decoded = cache.decode("outputs/stack/a", stored.as_bytes()).unwrap().unwrap();
+        assert_eq!(decoded.response, entry.response);
+
+        assert!(cache.decode("outputs/stack/b", stored.as_bytes()).is_err());
+        let other_key = ResponseCache::disabled().with_encryption(EntryCipher::from_base64(&BASE64.encode([8u8; 32])).unwrap());
+        assert!(other_key.decode("outputs/stack/a", stored.as_bytes()).is_err());
+
+        // Plaintext entries from before encryption was turned on are misses
+        let plain = ResponseCache::disabled().encode("outputs/stack/a", &entry).unwrap();
+        assert!(cache.decode("outputs/stack/a", plain.as_bytes()).unwrap().is_none());
+
+        assert_eq!(EntryCipher::from_base64("not base64!").err().unwrap(), "the key is not valid base64");
+        assert_eq!(EntryCipher::from_base64(&BASE64.encode([7u8; 16])).err().unwrap(), "the key must be 32 bytes, got 16");
+    }
+}
@@ -0,0 +1,279 @@
+use serde::Serialize;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::changelog::ChangelogEntry;
+use crate::error::CliError;
+use crate::file_writer::{FileChange, FilePreview};
+use crate::output_manifest::OutputManifest;
+use crate::post_hooks::HookResult;
+use crate::tool_versions::ToolCheck;
+use crate::variable_usage::VariableUsage;
+
+/// Default file name for the run summary written to the output directory
+pub const RUN_SUMMARY_FILE: &str = "run-summary.json";
+
+/// Metrics collected during a single CLI invocation
+///
+/// Serialized to `run-summary.json` so generation performance can be tracked
+/// over time, and printed as a table at the end of a run.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub command: String,
+    pub data_source: String,
+    pub identifier: String,
+    /// Start time in seconds since the Unix epoch
+    pub started_at: u64,
+    pub total_duration_ms: u64,
+    pub stages: Vec<StageTiming>,
+    /// Latency of each API request in milliseconds
+    pub api_latency_ms: Vec<u64>,
+    pub templates_discovered: usize,
+    pub files_written: usize,
+    pub bytes_written: u64,
+    pub warnings: Vec<String>,
+    /// Files written, or rendered by a dry run
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileRecord>,
+    /// Installed tool versions compared with what the generated files need
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tool_checks: Vec<ToolCheck>,
+    /// Post-generation hooks that ran, with their output
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<HookResult>,
+    /// Generated files added, modified and removed since the previous generation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<FileChanges>,
+    /// Output manifest written by the run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub manifest_path: Option<String>,
+    /// Context variables no template references, and references the context doesn't define
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variables: Option<VariableUsage>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+/// A generated file and, for dry runs, how it would change the output directory
+#[derive(Debug, Clone, Serialize)]
+pub struct FileRecord {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change: Option<FileChange>,
+}
+
+/// Generated files that changed compared with the previous generation in the output directory
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileChanges {
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl FileChanges {
+    /// Compare the files of a new manifest with those of the previous one, if any
+    pub fn between(previous: Option<&OutputManifest>, current: &OutputManifest) -> Self {
+        match previous {
+            Some(previous) => {
+                let entry = ChangelogEntry::between(previous, current);
+                Self { added: entry.files_added, modified: entry.files_modified, removed: entry.files_removed }
+            }
+            None => Self { added: current.files.keys().cloned().collect(), ..Self::default() },
+        }
+    }
+
+    /// Files a dry run or `--diff` would add or modify
+    pub fn from_previews(previews: &[FilePreview]) -> Self {
+        let paths = |change| {
+            previews.iter().filter(|p| p.change == change).map(|p| p.path.display().to_string()).collect()
+        };
+        Self { added: paths(FileChange::Created), modified: paths(FileChange::Modified), removed: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Duration of one pipeline stage
+#[derive(Debug, Clone, Serialize)]
+pub struct StageTiming {
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+impl RunSummary {
+    /// Start collecting metrics for a command
+    pub fn start(command: &str, data_source: &str, identifier: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            data_source: data_source.to_string(),
+            identifier: identifier.to_string(),
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            total_duration_ms: 0,
+            stages: Vec::new(),
+            api_latency_ms: Vec::new(),
+            templates_discovered: 0,
+            files_written: 0,
+            bytes_written: 0,
+            warnings: Vec::new(),
+            files: Vec::new(),
+            tool_checks: Vec::new(),
+            hooks: Vec::new(),
+            changes: None,
+            manifest_path: None,
+            variables: None,
+            success: false,
+            error: None,
+            started: Some(Instant::now()),
+        }
+    }
+
+    /// Record how long a pipeline stage took
+    pub fn record_stage(&mut self, name: &str, duration: Duration) {
+        self.stages.push(StageTiming {
+            name: name.to_string(),
+            duration_ms: duration.as_millis() as u64,
+        });
+    }
+
+    /// Record the latency of an API request
+    pub fn record_api_call(&mut self, duration: Duration) {
+        self.api_latency_ms.push(duration.as_millis() as u64);
+    }
+
+    /// Record a warning shown to the user
+    pub fn warn(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+
+    /// Mark the run as finished and compute the total duration
+    pub fn finish(&mut self, result: &Result<(), CliError>) {
+        self.total_duration_ms = self
+            .started
+            .map(|s| s.elapsed().as_millis() as u64)
+            .unwrap_or(0);
+        self.success = result.is_ok();
+        self.error = result.as_ref().err().map(|e| e.to_string());
+    }
+
+    /// Write the summary as pretty-printed JSON
+    pub fn write_to(&self, path: &Path) -> Result<(), CliError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                CliError::IoError(format!("Failed to create directory {}: {}", parent.display(), e))
+            })?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).map_err(|e| {
+            CliError::IoError(format!("Failed to write run summary {}: {}", path.display(), e))
+        })
+    }
+
+    /// Format the summary as a table for terminal output
+    pub fn render_table(&self) -> String {
+        let mut rows: Vec<(String, String)> = self
+            .stages
+            .iter()
+            .map(|s| (format!("stage: {}", s.name), format!("{} ms", s.duration_ms)))
+            .collect();
+
+        if !self.api_latency_ms.is_empty() {
+            let total: u64 = self.api_latency_ms.iter().sum();
+            rows.push((
+                "api latency".to_string(),
+                format!("{} ms ({} request(s))", total, self.api_latency_ms.len()),
+            ));
+        }
+        rows.push(("templates".to_string(), self.templates_discovered.to_string()));
+        rows.push(("files written".to_string(), self.files_written.to_string()));
+        rows.push(("bytes written".to_string(), self.bytes_written.to_string()));
+        rows.push(("warnings".to_string(), self.warnings.len().to_string()));
+        rows.push(("total".to_string(), format!("{} ms", self.total_duration_ms)));
+
+        let width = rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        rows.iter()
+            .map(|(k, v)| format!("  {:<width$}  {}", k, v))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_records_metrics_and_finishes() {
+        let mut summary = RunSummary::start("generate", "blueprint", "web-app");
+        summary.record_stage("fetch", Duration::from_millis(12));
+        summary.record_api_call(Duration::from_millis(10));
+        summary.warn("Overwrote main.tf");
+        summary.finish(&Err(CliError::ApiError("boom".to_string())));
+
+        assert_eq!(summary.stages[0].duration_ms, 12);
+        assert_eq!(summary.api_latency_ms, vec![10]);
+        assert!(!summary.success);
+        assert!(summary.error.as_ref().unwrap().contains("boom"));
+    }
+
+    #[test]
+    fn test_write_to_creates_json_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("nested").join(RUN_SUMMARY_FILE);
+
+        let mut summary = RunSummary::start("generate", "stack", "prod");
+        summary.files_written = 2;
+        summary.finish(&Ok(()));
+        summary.write_to(&path).unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["files_written"], 2);
+        assert_eq!(json["success"], true);
+        assert!(json.get("error").is_none());
+        assert!(json.get("started").is_none());
+    }
+
+    #[test]
+    fn test_render_table() {
+        let mut summary = RunSummary::start("generate", "stack", "prod");
+        summary.record_stage("render", Duration::from_millis(3));
+        let table = summary.render_table();
+        assert!(table.contains("stage: render"));
+        assert!(table.contains("3 ms"));
+        assert!(table.contains("files written"));
+    }
+
+    #[test]
+    fn test_file_changes_between_generations() {
+        let manifest = |files: &[(&str, &str)]| {
+            let mut manifest = OutputManifest::from_blueprint(&crate::models::Blueprint {
+                id: uuid::Uuid::nil(),
+                name: "web-app".to_string(),
+                description: None,
+                resources: vec![],
+                revision: None,
+                updated_at: None,
+                supported_cloud_providers: vec![],
+            });
+            manifest.files = files.iter().map(|(path, digest)| (path.to_string(), digest.to_string())).collect();
+            manifest
+        };
+        let previous = manifest(&[("main.tf", "a"), ("old.tf", "b"), ("same.tf", "c")]);
+        let current = manifest(&[("main.tf", "z"), ("new.tf", "d"), ("same.tf", "c")]);
+
+        let changes = FileChanges::between(Some(&previous), &current);
+        assert_eq!(changes.added, vec!["new.tf"]);
+        assert_eq!(changes.modified, vec!["main.tf"]);
+        assert_eq!(changes.removed, vec!["old.tf"]);
+        assert_eq!(FileChanges::between(None, &current).added, vec!["main.tf", "new.tf", "same.tf"]);
+    }
+}
@@ -1,9 +1,8 @@
+use aws_config::BehaviorVersion;
 use aws_sdk_s3::Client;
 use aws_sdk_s3::config::Region;
-use aws_sdk_s3::error::SdkError;
-use aws_sdk_s3::operation::get_object::GetObjectError;
-use aws_sdk_s3::operation::put_object::PutObjectError;
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::ServerSideEncryption;
 use log::{error, info, warn};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
@@ -12,6 +11,62 @@ use tokio::io::AsyncWriteExt;
 
 use crate::error::CliError;
 
+/// Bucket and key prefix given as `s3://bucket/prefix`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3Location {
+    pub bucket: String,
+    /// Key prefix without leading or trailing slashes (may be empty)
+    pub prefix: String,
+}
+
+impl S3Location {
+    /// Parse an `s3://bucket/prefix` URL
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let rest = value
+            .strip_prefix("s3://")
+            .ok_or_else(|| format!("expected an s3://bucket/prefix URL, got '{}'", value))?;
+        let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+        if bucket.is_empty() {
+            return Err(format!("missing bucket name in '{}'", value));
+        }
+        Ok(Self {
+            bucket: bucket.to_string(),
+            prefix: prefix.trim_matches('/').to_string(),
+        })
+    }
+
+    /// Prefix to list keys under, ending in `/` unless it is empty
+    pub fn key_prefix(&self) -> String {
+        if self.prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", self.prefix)
+        }
+    }
+}
+
+impl std::fmt::Display for S3Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "s3://{}/{}", self.bucket, self.prefix)
+    }
+}
+
+/// Content type of a generated file, from its extension
+///
+/// Generated files are text; formats without a registered type, such as
+/// Terraform, are stored as plain text so they open in the S3 console.
+pub fn content_type(path: &str) -> &'static str {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension.to_lowercase()).unwrap_or_default();
+    match extension.as_str() {
+        "json" => "application/json",
+        "yaml" | "yml" => "application/yaml",
+        "md" => "text/markdown; charset=utf-8",
+        "py" => "text/x-python; charset=utf-8",
+        "sh" => "text/x-shellscript; charset=utf-8",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
 /// S3 client for fetching templates and storing outputs
 pub struct S3Client {
     client: Client,
@@ -27,12 +82,12 @@ impl S3Client {
         region: Option<String>,
     ) -> Result<Self, CliError> {
         let config = if let Some(region_str) = region {
-            aws_config::from_env()
+            aws_config::defaults(BehaviorVersion::latest())
                 .region(Region::new(region_str))
                 .load()
                 .await
         } else {
-            aws_config::load_from_env().await
+            aws_config::load_defaults(BehaviorVersion::latest()).await
         };
 
         let client = Client::new(&config);
@@ -159,6 +214,51 @@ impl S3Client {
         Ok(uploaded_keys)
     }
 
+    /// Upload one generated file to the outputs bucket with retry logic
+    pub async fn upload_output(&self, file: &Path, output_prefix: &str) -> Result<String, CliError> {
+        let outputs_bucket = self.outputs_bucket.as_ref().ok_or_else(|| {
+            CliError::ConfigError("Outputs bucket not configured".to_string())
+        })?;
+        self.upload_file(file, output_prefix, outputs_bucket).await
+    }
+
+    /// Store content under a key of the outputs bucket with retry logic
+    ///
+    /// With a KMS key (ID, alias or ARN) the object is encrypted with
+    /// SSE-KMS, otherwise the bucket's default encryption applies.
+    pub async fn put_output(
+        &self,
+        key: &str,
+        content: Vec<u8>,
+        content_type: &str,
+        kms_key: Option<&str>,
+    ) -> Result<(), CliError> {
+        let outputs_bucket = self.outputs_bucket.as_ref().ok_or_else(|| {
+            CliError::ConfigError("Outputs bucket not configured".to_string())
+        })?;
+
+        info!("Uploading s3://{}/{}", outputs_bucket, key);
+        self.retry_operation(|| async {
+            let request = self
+                .client
+                .put_object()
+                .bucket(outputs_bucket)
+                .key(key)
+                .content_type(content_type)
+                .body(ByteStream::from(content.clone()));
+            let request = match kms_key {
+                Some(kms_key) => request
+                    .server_side_encryption(ServerSideEncryption::AwsKms)
+                    .ssekms_key_id(kms_key),
+                None => request,
+            };
+            request.send().await
+        }, 3).await.map_err(|e| {
+            CliError::S3Error(format!("Failed to upload {}: {}", key, e))
+        })?;
+        Ok(())
+    }
+
     /// Upload a single file to S3 with retry logic
     async fn upload_file(&self, file_path: &Path, prefix: &str, bucket: &str) -> Result<String, CliError> {
         // Read file content
@@ -231,6 +331,25 @@ impl S3Client {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_s3_location() {
+        let location = S3Location::parse("s3://templates/teams/payments/").unwrap();
+        assert_eq!(location.bucket, "templates");
+        assert_eq!(location.key_prefix(), "teams/payments/");
+        assert_eq!(location.to_string(), "s3://templates/teams/payments");
+        assert_eq!(S3Location::parse("s3://templates").unwrap().key_prefix(), "");
+        assert!(S3Location::parse("./templates").is_err());
+        assert!(S3Location::parse("s3:///prefix").is_err());
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type("k8s/deployment.YAML"), "application/yaml");
+        assert_eq!(content_type("cdktf.json"), "application/json");
+        assert_eq!(content_type("main.tf"), "text/plain; charset=utf-8");
+        assert_eq!(content_type("Makefile"), "text/plain; charset=utf-8");
+    }
+
     #[tokio::test]
     #[ignore] // Requires AWS credentials and S3 bucket
     async fn test_s3_client_creation() {
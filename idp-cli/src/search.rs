@@ -0,0 +1,248 @@
+use serde::Serialize;
+
+use crate::cli::DataSource;
+use crate::models::{Blueprint, CloudProvider, ResourceType, Stack};
+
+/// Filters for the search command
+///
+/// All matching is case-insensitive. Provider and resource type must be
+/// satisfied by the same resource when both are given.
+#[derive(Debug, Clone, Default)]
+pub struct SearchCriteria {
+    /// Cloud provider name or display name (e.g. "AWS")
+    pub provider: Option<String>,
+    /// Resource type name (e.g. "RelationalDatabaseServer")
+    pub resource_type: Option<String>,
+    /// Substring of the blueprint/stack name
+    pub name_contains: Option<String>,
+}
+
+/// A blueprint or stack that matched the search criteria
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResult {
+    pub kind: String,
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Number of resources satisfying the provider/resource type criteria
+    pub matching_resources: usize,
+    pub total_resources: usize,
+}
+
+impl SearchCriteria {
+    /// Check a blueprint against the criteria
+    pub fn match_blueprint(&self, blueprint: &Blueprint) -> Option<SearchResult> {
+        if !self.name_matches(&blueprint.name) {
+            return None;
+        }
+
+        let matching = blueprint
+            .resources
+            .iter()
+            .filter(|r| self.resource_matches(&r.cloud_provider, &r.resource_type))
+            .count();
+
+        // A blueprint declaring support for the provider matches even before
+        // it has resources on that provider
+        let supports_provider = self.resource_type.is_none()
+            && self.provider.is_some()
+            && blueprint
+                .supported_cloud_providers
+                .iter()
+                .any(|p| self.provider_matches(p));
+
+        if self.has_resource_criteria() && matching == 0 && !supports_provider {
+            return None;
+        }
+
+        Some(SearchResult {
+            kind: DataSource::Blueprint.to_string(),
+            id: blueprint.id.to_string(),
+            name: blueprint.name.clone(),
+            description: blueprint.description.clone(),
+            matching_resources: matching,
+            total_resources: blueprint.resources.len(),
+        })
+    }
+
+    /// Check a stack against the criteria
+    pub fn match_stack(&self, stack: &Stack) -> Option<SearchResult> {
+        if !self.name_matches(&stack.name) {
+            return None;
+        }
+
+        let matching = stack
+            .stack_resources
+            .iter()
+            .filter(|r| self.resource_matches(&r.cloud_provider, &r.resource_type))
+            .count();
+
+        if self.has_resource_criteria() && matching == 0 {
+            return None;
+        }
+
+        Some(SearchResult {
+            kind: DataSource::Stack.to_string(),
+            id: stack.id.to_string(),
+            name: stack.name.clone(),
+            description: stack.description.clone(),
+            matching_resources: matching,
+            total_resources: stack.stack_resources.len(),
+        })
+    }
+
+    fn has_resource_criteria(&self) -> bool {
+        self.provider.is_some() || self.resource_type.is_some()
+    }
+
+    fn name_matches(&self, name: &str) -> bool {
+        self.name_contains
+            .as_ref()
+            .map(|needle| name.to_lowercase().contains(&needle.to_lowercase()))
+            .unwrap_or(true)
+    }
+
+    fn provider_matches(&self, provider: &CloudProvider) -> bool {
+        self.provider
+            .as_ref()
+            .map(|p| p.eq_ignore_ascii_case(&provider.name) || p.eq_ignore_ascii_case(&provider.display_name))
+            .unwrap_or(true)
+    }
+
+    fn resource_matches(&self, provider: &CloudProvider, resource_type: &ResourceType) -> bool {
+        self.provider_matches(provider)
+            && self
+                .resource_type
+                .as_ref()
+                .map(|t| t.eq_ignore_ascii_case(&resource_type.name))
+                .unwrap_or(true)
+    }
+}
+
+/// Format search results as a table for terminal output
+pub fn render_results(results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        return "No matching blueprints or stacks found.".to_string();
+    }
+
+    let name_width = results
+        .iter()
+        .map(|r| r.name.len())
+        .max()
+        .unwrap_or(0)
+        .max("NAME".len());
+
+    let mut lines = vec![format!(
+        "{:<9}  {:<name_width$}  {:<9}  ID",
+        "KIND", "NAME", "RESOURCES"
+    )];
+
+    for result in results {
+        lines.push(format!(
+            "{:<9}  {:<name_width$}  {:<9}  {}",
+            result.kind,
+            result.name,
+            format!("{}/{}", result.matching_resources, result.total_resources),
+            result.id
+        ));
+    }
+
+    lines.push(format!("\n{} result(s)", results.len()));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::BlueprintResource;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn provider(name: &str) -> CloudProvider {
+        CloudProvider {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            display_name: format!("{} Cloud", name),
+        }
+    }
+
+    fn blueprint(name: &str, resources: &[(&str, &str)], supported: &[&str]) -> Blueprint {
+        Blueprint {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            description: None,
+            resources: resources
+                .iter()
+                .map(|(provider_name, type_name)| BlueprintResource {
+                    id: Uuid::new_v4(),
+                    name: format!("{}-resource", type_name),
+                    description: None,
+                    resource_type: ResourceType {
+                        id: Uuid::new_v4(),
+                        name: type_name.to_string(),
+                        category: "test".to_string(),
+                    },
+                    cloud_provider: provider(provider_name),
+                    configuration: serde_json::json!({}),
+                    cloud_specific_properties: HashMap::new(),
+                })
+                .collect(),
+            revision: None,
+            updated_at: None,
+            supported_cloud_providers: supported.iter().map(|p| provider(p)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_empty_criteria_matches_everything() {
+        let criteria = SearchCriteria::default();
+        let result = criteria.match_blueprint(&blueprint("anything", &[], &[])).unwrap();
+        assert_eq!(result.kind, "blueprint");
+        assert_eq!(result.matching_resources, 0);
+    }
+
+    #[test]
+    fn test_provider_and_resource_type_must_match_same_resource() {
+        let bp = blueprint(
+            "payments",
+            &[("AWS", "Cache"), ("Azure", "RelationalDatabaseServer")],
+            &["AWS", "Azure"],
+        );
+
+        let criteria = SearchCriteria {
+            provider: Some("aws".to_string()),
+            resource_type: Some("RelationalDatabaseServer".to_string()),
+            ..Default::default()
+        };
+        assert!(criteria.match_blueprint(&bp).is_none());
+
+        let criteria = SearchCriteria {
+            provider: Some("Azure Cloud".to_string()),
+            resource_type: Some("relationaldatabaseserver".to_string()),
+            name_contains: Some("PAY".to_string()),
+        };
+        assert_eq!(criteria.match_blueprint(&bp).unwrap().matching_resources, 1);
+    }
+
+    #[test]
+    fn test_supported_provider_matches_blueprint_without_resources() {
+        let criteria = SearchCriteria {
+            provider: Some("GCP".to_string()),
+            ..Default::default()
+        };
+        assert!(criteria.match_blueprint(&blueprint("empty", &[], &["GCP"])).is_some());
+        assert!(criteria.match_blueprint(&blueprint("empty", &[], &["AWS"])).is_none());
+    }
+
+    #[test]
+    fn test_render_results() {
+        assert!(render_results(&[]).contains("No matching"));
+
+        let criteria = SearchCriteria::default();
+        let result = criteria.match_blueprint(&blueprint("payments", &[("AWS", "Cache")], &[])).unwrap();
+        let output = render_results(&[result]);
+        assert!(output.contains("blueprint  payments  1/1"));
+        assert!(output.ends_with("1 result(s)"));
+    }
+}
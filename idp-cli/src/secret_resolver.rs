@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use aws_sdk_ssm::error::DisplayErrorContext;
+use log::info;
+use serde_json::Value;
+use tokio::runtime::RuntimeFlavor;
+
+use crate::error::CliError;
+
+/// A secret referenced by a variables file value
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SecretRef {
+    /// `ssm://prod/db/password`: a Parameter Store parameter, decrypted
+    Parameter(String),
+    /// `secretsmanager://<name or ARN>`, or `...#<key>` for one field of a JSON secret
+    Secret { id: String, key: Option<String> },
+}
+
+impl SecretRef {
+    /// Parse a value as a secret reference; other values are not references
+    pub fn parse(value: &str) -> Option<Self> {
+        if let Some(name) = value.strip_prefix("ssm://") {
+            if name.is_empty() {
+                return None;
+            }
+            // Hierarchical parameter names start with a slash
+            let name = if name.contains('/') && !name.starts_with('/') {
+                format!("/{}", name)
+            } else {
+                name.to_string()
+            };
+            return Some(SecretRef::Parameter(name));
+        }
+        let reference = value.strip_prefix("secretsmanager://").filter(|r| !r.is_empty())?;
+        Some(match reference.split_once('#') {
+            Some((id, key)) => SecretRef::Secret { id: id.to_string(), key: Some(key.to_string()) },
+            None => SecretRef::Secret { id: reference.to_string(), key: None },
+        })
+    }
+}
+
+impl fmt::Display for SecretRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretRef::Parameter(name) => write!(f, "ssm://{}", name.trim_start_matches('/')),
+            SecretRef::Secret { id, key: None } => write!(f, "secretsmanager://{}", id),
+            SecretRef::Secret { id, key: Some(key) } => write!(f, "secretsmanager://{}#{}", id, key),
+        }
+    }
+}
+
+/// Replace secret references in a variables document with the secret values
+///
+/// Secrets are fetched with the standard AWS credentials and region. Returns
+/// the resolved values so callers can mask them; the values themselves are
+/// never logged.
+pub fn resolve(document: &mut Value) -> Result<Vec<String>, CliError> {
+    let mut references = BTreeMap::new();
+    collect(document, "", &mut references);
+    if references.is_empty() {
+        return Ok(Vec::new());
+    }
+    for (path, reference) in &references {
+        info!("Resolving variable '{}' from {}", path, reference);
+    }
+    let mut unique: Vec<SecretRef> = references.into_values().collect();
+    unique.sort();
+    unique.dedup();
+    let values = fetch_all(&unique)?;
+    resolve_with(document, |reference| {
+        let index = unique.iter().position(|r| r == reference).expect("every reference was fetched");
+        Ok(values[index].clone())
+    })
+}
+
+/// Replace secret references using `fetch` to look up each secret
+pub fn resolve_with(
+    document: &mut Value,
+    mut fetch: impl FnMut(&SecretRef) -> Result<String, CliError>,
+) -> Result<Vec<String>, CliError> {
+    let mut resolved = Vec::new();
+    substitute(document, &mut fetch, &mut resolved)?;
+    Ok(resolved)
+}
+
+fn collect(value: &Value, path: &str, references: &mut BTreeMap<String, SecretRef>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                collect(child, &path, references);
+            }
+        }
+        Value::Array(items) => {
+            for (index, item) in items.iter().enumerate() {
+                collect(item, &format!("{}[{}]", path, index), references);
+            }
+        }
+        Value::String(text) => {
+            if let Some(reference) = SecretRef::parse(text) {
+                references.insert(path.to_string(), reference);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn substitute(
+    value: &mut Value,
+    fetch: &mut impl FnMut(&SecretRef) -> Result<String, CliError>,
+    resolved: &mut Vec<String>,
+) -> Result<(), CliError> {
+    match value {
+        Value::Object(map) => map.values_mut().try_for_each(|child| substitute(child, fetch, resolved)),
+        Value::Array(items) => items.iter_mut().try_for_each(|item| substitute(item, fetch, resolved)),
+        Value::String(text) => {
+            if let Some(reference) = SecretRef::parse(text) {
+                let secret = fetch(&reference)?;
+                *text = secret.clone();
+                resolved.push(secret);
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fetch secrets from AWS, in order
+///
+/// Variables files are merged from synchronous code, so this blocks on the
+/// running Tokio runtime (which must be multi-threaded, like the CLI's), or
+/// on a temporary one when there is none.
+fn fetch_all(references: &[SecretRef]) -> Result<Vec<String>, CliError> {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(|| handle.block_on(fetch_all_async(references)))
+        }
+        Ok(_) => Err(CliError::SecretError(
+            "secret references can't be resolved on a single-threaded runtime".to_string(),
+        )),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| CliError::SecretError(format!("Failed to start the secret resolver: {}", e)))?
+            .block_on(fetch_all_async(references)),
+    }
+}
+
+/// Fetch secrets with clients sharing one AWS configuration
+async fn fetch_all_async(references: &[SecretRef]) -> Result<Vec<String>, CliError> {
+    let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+    let ssm = aws_sdk_ssm::Client::new(&config);
+    let secrets = aws_sdk_secretsmanager::Client::new(&config);
+    let mut values = Vec::with_capacity(references.len());
+    for reference in references {
+        let value = match reference {
+            SecretRef::Parameter(name) => get_parameter(&ssm, name).await,
+            SecretRef::Secret { id, key } => get_secret(&secrets, id, key.as_deref()).await,
+        };
+        values.push(value.map_err(|e| CliError::SecretError(format!("{}: {}", reference, e)))?);
+    }
+    Ok(values)
+}
+
+async fn get_secret(client: &aws_sdk_secretsmanager::Client, id: &str, key: Option<&str>) -> Result<String, String> {
+    let secret = client
+        .get_secret_value()
+        .secret_id(id)
+        .send()
+        .await
+        .map_err(|e| DisplayErrorContext(e).to_string())?;
+    let content = secret.secret_string().ok_or("secret has no string value")?;
+    let Some(key) = key else {
+        return Ok(content.to_string());
+    };
+    let document: Value = serde_json::from_str(content).map_err(|_| format!("secret is not JSON, so it has no key '{}'", key))?;
+    match document.get(key) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(value) => Ok(value.to_string()),
+        None => Err(format!("secret has no key '{}'", key)),
+    }
+}
+
+/// Read a parameter, decrypting `SecureString` values
+async fn get_parameter(client: &aws_sdk_ssm::Client, name: &str) -> Result<String, String> {
+    let output = client
+        .get_parameter()
+        .name(name)
+        .with_decryption(true)
+        .send()
+        .await
+        .map_err(|e| DisplayErrorContext(e).to_string())?;
+    output
+        .parameter()
+        .and_then(|parameter| parameter.value())
+        .map(str::to_string)
+        .ok_or_else(|| "parameter has no value".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_references() {
+        assert_eq!(SecretRef::parse("ssm://prod/db/password"), Some(SecretRef::Parameter("/prod/db/password".into())));
+        assert_eq!(SecretRef::parse("ssm:///prod/db/password"), Some(SecretRef::Parameter("/prod/db/password".into())));
+        assert_eq!(SecretRef::parse("ssm://db-password"), Some(SecretRef::Parameter("db-password".into())));
+        assert_eq!(
+            SecretRef::parse("secretsmanager://arn:aws:secretsmanager:us-east-1:123456789012:secret:db-AbCdEf#password"),
+            Some(SecretRef::Secret {
+                id: "arn:aws:secretsmanager:us-east-1:123456789012:secret:db-AbCdEf".into(),
+                key: Some("password".into())
+            })
+        );
+        assert_eq!(SecretRef::parse("ssm://"), None);
+        assert_eq!(SecretRef::parse("https://example.com"), None);
+        assert_eq!(SecretRef::parse("ssm://prod/db/password").unwrap().to_string(), "ssm://prod/db/password");
+    }
+
+    #[test]
+    fn test_resolve_with_replaces_nested_references() {
+        let mut document = json!({
+            "environment": "prod",
+            "db": {"password": "ssm://prod/db/password", "user": "app"},
+            "tokens": ["secretsmanager://ci-token", "plain"]
+        });
+        let resolved = resolve_with(&mut document, |reference| match reference {
+            SecretRef::Parameter(name) => Ok(format!("param:{}", name)),
+            SecretRef::Secret { id, .. } => Ok(format!("secret:{}", id)),
+        })
+        .unwrap();
+
+        assert_eq!(document["db"]["password"], "param:/prod/db/password");
+        assert_eq!(document["tokens"], json!(["secret:ci-token", "plain"]));
+        assert_eq!(resolved, vec!["param:/prod/db/password", "secret:ci-token"]);
+
+        let mut document = json!({"password": "ssm://missing"});
+        let error = resolve_with(&mut document, |reference| Err(CliError::SecretError(format!("{}: not found", reference))));
+        assert!(error.unwrap_err().to_string().contains("ssm://missing: not found"));
+    }
+
+    #[tokio::test]
+    async fn test_single_threaded_runtime_is_refused() {
+        // Blocking on this runtime's only thread would deadlock the lookup
+        let mut document = json!({"password": "ssm://prod/db/password"});
+        let error = resolve(&mut document).unwrap_err();
+        assert!(error.to_string().contains("single-threaded runtime"), "{}", error);
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use handlebars::template::{DecoratorTemplate, HelperTemplate, Parameter, Template, TemplateElement};
+use handlebars::{Context, Handlebars, Output, Path, RenderContext, RenderError, Renderable};
+
+use crate::render_sandbox;
+
+/// Region of rendered output produced by one top-level template element
+#[derive(Debug, Clone)]
+struct Span {
+    /// First and last output lines the element wrote to (1-based)
+    first_output_line: usize,
+    last_output_line: usize,
+    /// Template lines the element was written on (1-based)
+    template_line: usize,
+    template_end_line: usize,
+    kind: SpanKind,
+    /// Variables referenced by the element
+    variables: Vec<String>,
+    /// Opening tag of an expression or block, e.g. `{{#each resources}}`
+    tag: String,
+    /// Closing tag of a block, e.g. `{{/each}}`
+    closing_tag: Option<String>,
+    /// What an expression or block rendered to
+    value: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpanKind {
+    /// Raw text maps output lines to template lines one to one
+    Raw,
+    Expression,
+    Block,
+}
+
+/// Mapping from rendered output lines back to the template that produced them
+///
+/// Built while rendering by recording which output lines each top-level
+/// template element wrote. Lines written inside a block helper
+/// (`{{#each}}`, `{{#if}}`, ...) map to the whole block.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    spans: Vec<Span>,
+    template_lines: Vec<String>,
+}
+
+/// Template location that produced a line of rendered output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateLocation {
+    /// First template line (1-based)
+    pub line: usize,
+    /// Last template line; differs from `line` for block helpers
+    pub end_line: usize,
+    /// Variables substituted on the output line
+    pub variables: Vec<String>,
+    /// Text of the first template line
+    pub source: String,
+}
+
+impl fmt::Display for TemplateLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.end_line > self.line {
+            write!(f, "template lines {}-{}", self.line, self.end_line)?;
+        } else {
+            write!(f, "template line {}", self.line)?;
+        }
+        if !self.variables.is_empty() {
+            write!(f, " (variables: {})", self.variables.join(", "))?;
+        }
+        write!(f, "\n  {} | {}", self.line, self.source)
+    }
+}
+
+impl SourceMap {
+    /// Template location for a line of rendered output (1-based)
+    pub fn locate(&self, output_line: usize) -> Option<TemplateLocation> {
+        let touching: Vec<&Span> = self
+            .spans
+            .iter()
+            .filter(|span| span.first_output_line <= output_line && output_line <= span.last_output_line)
+            .collect();
+        let first = touching.first()?;
+
+        let (line, end_line) = if first.kind == SpanKind::Raw {
+            let line = first.template_line + (output_line - first.first_output_line);
+            (line, line)
+        } else {
+            (first.template_line, first.template_end_line)
+        };
+        let variables: BTreeSet<&String> = touching.iter().flat_map(|span| &span.variables).collect();
+
+        Some(TemplateLocation {
+            line,
+            end_line,
+            variables: variables.into_iter().cloned().collect(),
+            source: self
+                .template_lines
+                .get(line.saturating_sub(1))
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl SourceMap {
+    /// Copy of the output with comments marking what each expression and block produced
+    ///
+    /// Expressions are noted at the end of the line their value starts on,
+    /// with the value they resolved to. Blocks are wrapped in opening and
+    /// closing marker lines. `comment` is the line comment syntax of the
+    /// output format, e.g. `#`.
+    pub fn annotate(&self, output: &str, comment: &str) -> String {
+        let mut annotated = String::with_capacity(output.len() * 2);
+        let lines: Vec<&str> = output.lines().collect();
+        // Blocks that rendered nothing at the end still get a marker
+        let line_count = self
+            .spans
+            .iter()
+            .map(|span| span.first_output_line)
+            .chain([lines.len(), 1])
+            .max()
+            .unwrap_or(1);
+
+        for number in 1..=line_count {
+            for span in self.spans_starting(number, SpanKind::Block) {
+                let range = format!("template lines {}-{}", span.template_line, span.template_end_line);
+                if span.value.is_empty() {
+                    annotated.push_str(&format!("{} --- {} rendered nothing ({})\n", comment, span.tag, range));
+                } else {
+                    annotated.push_str(&format!("{} >>> {} ({})\n", comment, span.tag, range));
+                }
+            }
+
+            annotated.push_str(lines.get(number - 1).copied().unwrap_or_default());
+            let notes: Vec<String> = self
+                .spans_starting(number, SpanKind::Expression)
+                .map(|span| format!("{} = {}", span.tag, display_value(&span.value)))
+                .collect();
+            if !notes.is_empty() {
+                annotated.push_str(&format!(" {} <- {}", comment, notes.join("; ")));
+            }
+            annotated.push('\n');
+
+            for span in self.spans.iter().rev() {
+                if span.kind == SpanKind::Block && !span.value.is_empty() && span.last_output_line == number {
+                    let closing = span.closing_tag.as_deref().unwrap_or_default();
+                    annotated.push_str(&format!("{} <<< {}\n", comment, closing));
+                }
+            }
+        }
+        annotated
+    }
+
+    fn spans_starting(&self, output_line: usize, kind: SpanKind) -> impl Iterator<Item = &Span> {
+        self.spans
+            .iter()
+            .filter(move |span| span.kind == kind && span.first_output_line == output_line)
+    }
+}
+
+/// Short, escaped form of a rendered value
+fn display_value(value: &str) -> String {
+    const MAX_CHARS: usize = 60;
+    if value.chars().count() > MAX_CHARS {
+        let shortened: String = value.chars().take(MAX_CHARS).collect();
+        format!("{:?}... ({} bytes)", shortened, value.len())
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+/// Rendered output, checking the render deadline as it is written
+#[derive(Default)]
+struct Buffer(String);
+
+impl Output for Buffer {
+    fn write(&mut self, seg: &str) -> Result<(), std::io::Error> {
+        render_sandbox::check_write()?;
+        self.0.push_str(seg);
+        Ok(())
+    }
+}
+
+/// Render a template like `Handlebars::render_template`, also returning its source map
+pub fn render_mapped(
+    handlebars: &Handlebars,
+    template_content: &str,
+    ctx: &Context,
+) -> Result<(String, SourceMap), RenderError> {
+    render_mapped_with(handlebars, template_content, ctx, |_| {})
+}
+
+/// [`render_mapped`] with a render context prepared by `prepare`, e.g. to
+/// render inside a block
+pub fn render_mapped_with(
+    handlebars: &Handlebars,
+    template_content: &str,
+    ctx: &Context,
+    prepare: impl FnOnce(&mut RenderContext),
+) -> Result<(String, SourceMap), RenderError> {
+    let template = Template::compile(template_content)?;
+    let mut rc = RenderContext::new(None);
+    prepare(&mut rc);
+    let mut out = Buffer::default();
+
+    let template_lines: Vec<String> = template_content.lines().map(str::to_string).collect();
+    let last_template_line = template_lines.len().max(1);
+    let mut spans = Vec::with_capacity(template.elements.len());
+    // Output line the next write starts on
+    let mut current_line = 1;
+
+    for (idx, element) in template.elements.iter().enumerate() {
+        let start = out.0.len();
+        element.render(handlebars, ctx, &mut rc, &mut out).map_err(|mut e| {
+            // Same line information `Template::render` adds
+            if e.line_no.is_none() {
+                if let Some(mapping) = template.mapping.get(idx) {
+                    e.line_no = Some(mapping.0);
+                    e.column_no = Some(mapping.1);
+                }
+            }
+            e
+        })?;
+
+        let written = &out.0[start..];
+        let first_output_line = current_line;
+        current_line += written.matches('\n').count();
+        let last_output_line = if written.ends_with('\n') {
+            current_line - 1
+        } else {
+            current_line
+        };
+
+        let Some(mapping) = template.mapping.get(idx) else {
+            continue;
+        };
+        // A block ends on the line before the next element when that starts a line
+        let block_end_line = template
+            .mapping
+            .get(idx + 1)
+            .map(|next| if next.1 == 1 { next.0 - 1 } else { next.0 })
+            .unwrap_or(last_template_line);
+        let span = match element {
+            TemplateElement::RawString(text) => {
+                // Raw text mappings can point past leading whitespace, so find the
+                // text after the previous element instead
+                let search_from = idx
+                    .checked_sub(1)
+                    .and_then(|prev| template.mapping.get(prev))
+                    .map(|prev| byte_offset(template_content, prev.0, prev.1))
+                    .unwrap_or(0);
+                let template_line = template_content[search_from..]
+                    .find(text.as_str())
+                    .map(|found| line_at(template_content, search_from + found))
+                    .unwrap_or(mapping.0);
+                Span {
+                    first_output_line,
+                    last_output_line,
+                    template_line,
+                    template_end_line: template_line,
+                    kind: SpanKind::Raw,
+                    variables: Vec::new(),
+                    tag: String::new(),
+                    closing_tag: None,
+                    value: String::new(),
+                }
+            }
+            TemplateElement::Comment(_) => continue,
+            _ => {
+                let mut variables = BTreeSet::new();
+                collect_element(element, &mut variables);
+                let closing_tag = block_name(element).map(|name| format!("{{{{/{}}}}}", name));
+                Span {
+                    first_output_line,
+                    last_output_line: last_output_line.max(first_output_line),
+                    template_line: mapping.0,
+                    template_end_line: if closing_tag.is_some() { block_end_line.max(mapping.0) } else { mapping.0 },
+                    kind: if closing_tag.is_some() { SpanKind::Block } else { SpanKind::Expression },
+                    variables: variables.into_iter().collect(),
+                    tag: tag_at(template_content, byte_offset(template_content, mapping.0, mapping.1)),
+                    closing_tag,
+                    value: written.to_string(),
+                }
+            }
+        };
+        spans.push(span);
+    }
+
+    Ok((out.0, SourceMap { spans, template_lines }))
+}
+
+/// Byte offset of a 1-based line and column
+pub(crate) fn byte_offset(source: &str, line: usize, column: usize) -> usize {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    source[line_start..]
+        .char_indices()
+        .nth(column.saturating_sub(1))
+        .map(|(i, _)| line_start + i)
+        .unwrap_or(source.len())
+}
+
+/// 1-based line of a byte offset
+fn line_at(source: &str, offset: usize) -> usize {
+    source[..offset].matches('\n').count() + 1
+}
+
+/// Template tag starting at a byte offset, e.g. `{{stack.name}}`
+pub(crate) fn tag_at(source: &str, offset: usize) -> String {
+    let rest = &source[offset..];
+    match rest.find("}}") {
+        Some(end) if rest[end + 2..].starts_with('}') => rest[..end + 3].to_string(),
+        Some(end) => rest[..end + 2].to_string(),
+        None => rest.lines().next().unwrap_or_default().to_string(),
+    }
+}
+
+/// Name used in the closing tag of a block element
+fn block_name(element: &TemplateElement) -> Option<String> {
+    let name = match element {
+        TemplateElement::HelperBlock(helper) => &helper.name,
+        TemplateElement::DecoratorBlock(decorator) | TemplateElement::PartialBlock(decorator) => &decorator.name,
+        _ => return None,
+    };
+    Some(match name {
+        Parameter::Name(name) => name.clone(),
+        Parameter::Path(Path::Relative((_, raw))) | Parameter::Path(Path::Local((_, _, raw))) => raw.clone(),
+        _ => String::new(),
+    })
+}
+
+fn collect_element(element: &TemplateElement, variables: &mut BTreeSet<String>) {
+    match element {
+        TemplateElement::Expression(helper)
+        | TemplateElement::HtmlExpression(helper)
+        | TemplateElement::HelperBlock(helper) => collect_helper(helper, variables),
+        TemplateElement::DecoratorExpression(decorator)
+        | TemplateElement::DecoratorBlock(decorator)
+        | TemplateElement::PartialExpression(decorator)
+        | TemplateElement::PartialBlock(decorator) => collect_decorator(decorator, variables),
+        TemplateElement::RawString(_) | TemplateElement::Comment(_) => {}
+    }
+}
+
+fn collect_helper(helper: &HelperTemplate, variables: &mut BTreeSet<String>) {
+    collect_parameter(&helper.name, variables);
+    for param in helper.params.iter().chain(helper.hash.values()) {
+        collect_parameter(param, variables);
+    }
+    for template in helper.template.iter().chain(helper.inverse.iter()) {
+        collect_template(template, variables);
+    }
+}
+
+fn collect_decorator(decorator: &DecoratorTemplate, variables: &mut BTreeSet<String>) {
+    for param in decorator.params.iter().chain(decorator.hash.values()) {
+        collect_parameter(param, variables);
+    }
+    if let Some(template) = &decorator.template {
+        collect_template(template, variables);
+    }
+}
+
+fn collect_template(template: &Template, variables: &mut BTreeSet<String>) {
+    for element in &template.elements {
+        collect_element(element, variables);
+    }
+}
+
+fn collect_parameter(param: &Parameter, variables: &mut BTreeSet<String>) {
+    match param {
+        Parameter::Path(Path::Relative((_, raw))) | Parameter::Path(Path::Local((_, _, raw))) => {
+            if raw != "this" && raw != "." {
+                variables.insert(raw.clone());
+            }
+        }
+        Parameter::Subexpression(subexpression) => collect_element(&subexpression.element, variables),
+        Parameter::Name(_) | Parameter::Literal(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, data: serde_json::Value) -> (String, SourceMap) {
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        render_mapped(&handlebars, template, &Context::from(data)).unwrap()
+    }
+
+    #[test]
+    fn test_output_matches_render_template() {
+        let template = "a: {{name}}\n{{#each items}}\n- {{this}}\n{{/each}}\nb: {{missing}}\n";
+        let data = json!({"name": "x", "items": [1, 2]});
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(handlebars::no_escape);
+        let (output, _) = render_mapped(&handlebars, template, &Context::from(data.clone())).unwrap();
+        assert_eq!(output, handlebars.render_template(template, &data).unwrap());
+    }
+
+    #[test]
+    fn test_locate_maps_multi_line_values_to_expression() {
+        let (output, map) = render("kind: Service\nname: {{stack.name}}\nport: 80\n", json!({"stack": {"name": "a\nb"}}));
+        assert_eq!(output.lines().nth(2), Some("b"));
+
+        let location = map.locate(3).unwrap();
+        assert_eq!(location.line, 2);
+        assert_eq!(location.variables, vec!["stack.name"]);
+        assert_eq!(location.source, "name: {{stack.name}}");
+        // Raw text after the value keeps its own template line
+        assert_eq!(map.locate(4).unwrap().line, 3);
+        assert!(map.locate(4).unwrap().variables.is_empty());
+    }
+
+    #[test]
+    fn test_annotate_marks_expressions_and_blocks() {
+        let template = "name: {{stack.name}}\n{{#each items}}\n- {{this}}\n{{/each}}\n{{#if missing}}\nx: 1\n{{/if}}\nend: {{end}}\n";
+        let (output, map) = render(template, json!({"stack": {"name": "app"}, "items": [1, 2]}));
+        assert_eq!(output, "name: app\n- 1\n- 2\nend: \n");
+
+        assert_eq!(
+            map.annotate(&output, "#"),
+            "name: app # <- {{stack.name}} = \"app\"\n\
+             # >>> {{#each items}} (template lines 2-4)\n\
+             - 1\n\
+             - 2\n\
+             # <<< {{/each}}\n\
+             # --- {{#if missing}} rendered nothing (template lines 5-7)\n\
+             end:  # <- {{end}} = \"\"\n"
+        );
+    }
+
+    #[test]
+    fn test_locate_maps_block_output_to_block_range() {
+        let template = "items:\n{{#each items}}\n  - {{name}}\n{{/each}}\ndone: true\n";
+        let (output, map) = render(template, json!({"items": [{"name": "a"}, {"name": "b"}]}));
+        assert_eq!(output, "items:\n  - a\n  - b\ndone: true\n");
+
+        let location = map.locate(3).unwrap();
+        assert_eq!((location.line, location.end_line), (2, 4));
+        assert_eq!(location.variables, vec!["items", "name"]);
+        assert_eq!(map.locate(4).unwrap().line, 5);
+    }
+}
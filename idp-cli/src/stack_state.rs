@@ -0,0 +1,151 @@
+use crate::models::{StackEvent, StackResourceState, StackStatus};
+use crate::output_style::OutputStyle;
+
+/// Human-readable deployment state of a stack
+pub fn render_status(stack: &str, status: &StackStatus) -> String {
+    let mut lines = vec![format!("Stack {}: {}", stack, status.state)];
+    if let Some(message) = &status.message {
+        lines.push(format!("  {}", message));
+    }
+    if let Some(updated_at) = &status.updated_at {
+        lines.push(format!("  Since {}", updated_at));
+    }
+    lines.join("\n")
+}
+
+/// Why a stack's state is not one of `required`, or None if it is
+///
+/// States are compared case-insensitively.
+pub fn state_mismatch(stack: &str, status: &StackStatus, required: &[String]) -> Option<String> {
+    if required.iter().any(|state| state.eq_ignore_ascii_case(&status.state)) {
+        return None;
+    }
+    let mut message = format!("Stack '{}' is {}, not {}", stack, status.state, required.join(" or "));
+    if let Some(detail) = &status.message {
+        message.push_str(&format!(" ({})", detail));
+    }
+    Some(message)
+}
+
+/// Table of the deployment state of each stack resource
+///
+/// Plain output writes one line per resource instead of aligned columns.
+pub fn render_resources(resources: &[StackResourceState], style: OutputStyle) -> String {
+    if resources.is_empty() {
+        return "No resources found.".to_string();
+    }
+
+    let detail = |resource: &StackResourceState| -> String {
+        [resource.external_id.as_deref(), resource.message.as_deref()]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut lines = Vec::new();
+    if style.is_plain() {
+        for resource in resources {
+            let mut line = format!(
+                "{}: {}, type {}",
+                resource.name,
+                resource.state,
+                resource.resource_type.as_deref().unwrap_or("-")
+            );
+            let detail = detail(resource);
+            if !detail.is_empty() {
+                line.push_str(&format!(", {}", detail));
+            }
+            lines.push(line);
+        }
+    } else {
+        let name_width = resources.iter().map(|r| r.name.len()).max().unwrap_or(0).max("NAME".len());
+        let type_width = resources
+            .iter()
+            .map(|r| r.resource_type.as_deref().unwrap_or("-").len())
+            .max()
+            .unwrap_or(0)
+            .max("TYPE".len());
+        let state_width = resources.iter().map(|r| r.state.len()).max().unwrap_or(0).max("STATE".len());
+
+        lines.push(format!("{:<name_width$}  {:<type_width$}  {:<state_width$}  DETAILS", "NAME", "TYPE", "STATE"));
+        for resource in resources {
+            let line = format!(
+                "{:<name_width$}  {:<type_width$}  {:<state_width$}  {}",
+                resource.name,
+                resource.resource_type.as_deref().unwrap_or("-"),
+                resource.state,
+                detail(resource)
+            );
+            lines.push(line.trim_end().to_string());
+        }
+    }
+
+    lines.push(format!("\n{} resource(s)", resources.len()));
+    lines.join("\n")
+}
+
+/// Event log of a stack, one event per line in the order the API returned them
+pub fn render_events(events: &[StackEvent]) -> String {
+    if events.is_empty() {
+        return "No events found.".to_string();
+    }
+    events
+        .iter()
+        .map(|event| match &event.resource {
+            Some(resource) => format!("{}  {}  [{}] {}", event.timestamp, event.event_type, resource, event.message),
+            None => format!("{}  {}  {}", event.timestamp, event.event_type, event.message),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_mismatch() {
+        let status = StackStatus {
+            state: "PROVISIONING".to_string(),
+            message: Some("2 of 4 resources created".to_string()),
+            updated_at: None,
+        };
+        assert_eq!(state_mismatch("payments", &status, &["provisioning".to_string()]), None);
+        assert_eq!(
+            state_mismatch("payments", &status, &["ACTIVE".to_string(), "UPDATED".to_string()]).as_deref(),
+            Some("Stack 'payments' is PROVISIONING, not ACTIVE or UPDATED (2 of 4 resources created)")
+        );
+    }
+
+    #[test]
+    fn test_render_resources() {
+        let resources = vec![
+            StackResourceState {
+                name: "orders-db".to_string(),
+                resource_type: Some("PostgreSQL".to_string()),
+                state: "ACTIVE".to_string(),
+                external_id: Some("db-123".to_string()),
+                message: None,
+            },
+            StackResourceState {
+                name: "cache".to_string(),
+                resource_type: None,
+                state: "FAILED".to_string(),
+                external_id: None,
+                message: Some("quota exceeded".to_string()),
+            },
+        ];
+
+        assert_eq!(
+            render_resources(&resources, OutputStyle::from_flag(false)),
+            "NAME       TYPE        STATE   DETAILS\n\
+             orders-db  PostgreSQL  ACTIVE  db-123\n\
+             cache      -           FAILED  quota exceeded\n\
+             \n2 resource(s)"
+        );
+        assert_eq!(
+            render_resources(&resources, OutputStyle::from_flag(true)),
+            "orders-db: ACTIVE, type PostgreSQL, db-123\ncache: FAILED, type -, quota exceeded\n\n2 resource(s)"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+/// Build and runtime information about the compiled binary
+///
+/// Used by `--target-info` to confirm which platform a binary was
+/// built for (e.g. `x86_64-unknown-linux-musl` for Lambda) and whether it is
+/// statically linked.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct TargetInfo {
+    /// Full target triple the binary was compiled for
+    pub target: String,
+    /// Cargo profile used for the build (debug, release, ...)
+    pub profile: String,
+    /// CPU architecture (x86_64, aarch64, ...)
+    pub arch: String,
+    /// Operating system (linux, macos, windows, ...)
+    pub os: String,
+    /// Target environment / C library (gnu, musl, msvc, or empty)
+    pub env: String,
+    /// Whether the C runtime is statically linked
+    pub static_crt: bool,
+    /// Whether the lambda feature was enabled at compile time
+    pub lambda_enabled: bool,
+}
+
+/// Targets supported for AWS Lambda deployment packages
+pub const LAMBDA_TARGETS: &[&str] = &["x86_64-unknown-linux-musl", "aarch64-unknown-linux-musl"];
+
+impl TargetInfo {
+    /// Collect information about the currently running binary
+    pub fn current() -> Self {
+        Self {
+            target: env!("IDP_CLI_BUILD_TARGET").to_string(),
+            profile: env!("IDP_CLI_BUILD_PROFILE").to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            os: std::env::consts::OS.to_string(),
+            env: Self::target_env().to_string(),
+            static_crt: cfg!(target_feature = "crt-static"),
+            lambda_enabled: cfg!(feature = "lambda"),
+        }
+    }
+
+    /// Check whether this build is one of the supported Lambda targets
+    pub fn is_lambda_target(&self) -> bool {
+        LAMBDA_TARGETS.contains(&self.target.as_str())
+    }
+
+    /// Format the target information for display
+    ///
+    /// # Returns
+    /// A multi-line string with one `key: value` pair per line
+    pub fn display(&self) -> String {
+        let mut lines = vec![
+            format!("target:         {}", self.target),
+            format!("profile:        {}", self.profile),
+            format!("arch:           {}", self.arch),
+            format!("os:             {}", self.os),
+            format!(
+                "env:            {}",
+                if self.env.is_empty() { "none" } else { &self.env }
+            ),
+            format!("static crt:     {}", self.static_crt),
+            format!("lambda feature: {}", self.lambda_enabled),
+        ];
+
+        if !self.is_lambda_target() {
+            lines.push(format!(
+                "\nNote: Lambda deployment packages should be built for one of: {}",
+                LAMBDA_TARGETS.join(", ")
+            ));
+        }
+
+        lines.join("\n")
+    }
+
+    fn target_env() -> &'static str {
+        if cfg!(target_env = "musl") {
+            "musl"
+        } else if cfg!(target_env = "gnu") {
+            "gnu"
+        } else if cfg!(target_env = "msvc") {
+            "msvc"
+        } else {
+            ""
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_current_matches_compile_time_consts() {
+        let info = TargetInfo::current();
+        assert_eq!(info.arch, std::env::consts::ARCH);
+        assert_eq!(info.os, std::env::consts::OS);
+        assert!(info.target.contains(&info.arch));
+    }
+
+    #[test]
+    fn test_is_lambda_target() {
+        let mut info = TargetInfo::current();
+        info.target = "aarch64-unknown-linux-musl".to_string();
+        assert!(info.is_lambda_target());
+
+        info.target = "x86_64-apple-darwin".to_string();
+        assert!(!info.is_lambda_target());
+    }
+
+    #[test]
+    fn test_display_includes_target_and_hint() {
+        let mut info = TargetInfo::current();
+        info.target = "x86_64-unknown-linux-gnu".to_string();
+        let output = info.display();
+        assert!(output.contains("target:         x86_64-unknown-linux-gnu"));
+        assert!(output.contains("x86_64-unknown-linux-musl"));
+    }
+}
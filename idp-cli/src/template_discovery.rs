@@ -1,10 +1,20 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+use crate::paths::{long_path, to_slash};
+
+/// Top-level directory of per-environment overlays: `overlays/<env>/<path>`
+/// replaces the template at `<path>` when generating for `<env>`
+pub const OVERLAYS_DIR: &str = "overlays";
+
 /// Handles discovery of template files within a template directory
 pub struct TemplateDiscovery {
     /// The root directory containing template files
     pub template_dir: PathBuf,
+    /// Further extensions and file names to treat as templates (lowercase)
+    pub extra_types: Vec<String>,
+    /// Environment whose overlays are applied
+    pub environment: Option<String>,
 }
 
 impl TemplateDiscovery {
@@ -14,14 +24,35 @@ impl TemplateDiscovery {
     ///
     /// * `template_dir` - The root directory to search for template files
     pub fn new(template_dir: PathBuf) -> Self {
-        Self { template_dir }
+        Self { template_dir, extra_types: Vec::new(), environment: None }
+    }
+
+    /// Apply the overlays in `overlays/<environment>/` to the base templates
+    ///
+    /// Without an environment, overlays are left out entirely.
+    pub fn with_environment(mut self, environment: Option<&str>) -> Self {
+        self.environment = environment.map(str::to_string);
+        self
+    }
+
+    /// Also discover files with these extensions or names, e.g. ones with a
+    /// configured validator such as `sh` or `Dockerfile`
+    pub fn with_extra_types<I, S>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.extra_types.extend(types.into_iter().map(|t| t.as_ref().to_lowercase()));
+        self
     }
 
     /// Discovers all template files within the template directory
     ///
     /// This method recursively walks the template directory and identifies
-    /// template files by their extensions (.tf, .yaml, .yml, .json).
-    /// Hidden files and directories (starting with .) are skipped.
+    /// template files by their extensions (.tf, .yaml, .yml, .json), plus
+    /// any extra types.
+    /// Hidden files and directories (starting with .) are skipped, and
+    /// environment overlays are applied (see [`TemplateDiscovery::with_environment`]).
     ///
     /// # Returns
     ///
@@ -46,14 +77,16 @@ impl TemplateDiscovery {
         }
 
         let mut template_files = Vec::new();
+        // Deep template trees can exceed MAX_PATH on Windows
+        let root = long_path(&self.template_dir);
 
         // Walk the directory tree
-        for entry in WalkDir::new(&self.template_dir)
+        for entry in WalkDir::new(&root)
             .follow_links(false)
             .into_iter()
             .filter_entry(|e| {
                 // Don't filter the root directory itself
-                if e.path() == self.template_dir {
+                if e.path() == root {
                     return true;
                 }
                 // Filter out hidden files and directories
@@ -82,17 +115,18 @@ impl TemplateDiscovery {
 
             let path = entry.path();
 
-            // Get the file extension
-            let extension = match path.extension() {
-                Some(ext) => ext.to_string_lossy().to_string(),
-                None => continue, // Skip files without extensions
-            };
+            let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+            let name = path.file_name().map(|name| name.to_string_lossy().to_lowercase());
+            let is_extra = |part: &Option<String>| part.as_ref().is_some_and(|part| self.extra_types.contains(part));
 
             // Check if this is a recognized template file type
-            if let Some(file_type) = TemplateFileType::from_extension(&extension) {
+            let file_type = extension.as_deref().and_then(TemplateFileType::from_extension).or_else(|| {
+                (is_extra(&extension) || is_extra(&name)).then_some(TemplateFileType::Other)
+            });
+            if let Some(file_type) = file_type {
                 // Calculate the relative path from the template directory
                 let relative_path = path
-                    .strip_prefix(&self.template_dir)
+                    .strip_prefix(&root)
                     .map_err(|e| DiscoveryError::PathError(e.to_string()))?
                     .to_path_buf();
 
@@ -104,7 +138,30 @@ impl TemplateDiscovery {
             }
         }
 
-        Ok(template_files)
+        Ok(self.apply_overlays(template_files))
+    }
+
+    /// Replace base templates with the environment's overlays, adding overlay
+    /// files that have no base template, and drop every other overlay
+    fn apply_overlays(&self, templates: Vec<TemplateFile>) -> Vec<TemplateFile> {
+        let (overlays, mut templates): (Vec<_>, Vec<_>) =
+            templates.into_iter().partition(|t| t.relative_path.starts_with(OVERLAYS_DIR));
+        let Some(environment) = &self.environment else {
+            return templates;
+        };
+
+        let overlay_dir = Path::new(OVERLAYS_DIR).join(environment);
+        for overlay in overlays {
+            let Ok(relative_path) = overlay.relative_path.strip_prefix(&overlay_dir) else {
+                continue;
+            };
+            let overlay = TemplateFile { relative_path: relative_path.to_path_buf(), ..overlay };
+            match templates.iter_mut().find(|t| t.relative_path == overlay.relative_path) {
+                Some(base) => *base = overlay,
+                None => templates.push(overlay),
+            }
+        }
+        templates
     }
 
     /// Checks if a directory entry is hidden (starts with .)
@@ -125,6 +182,42 @@ impl TemplateDiscovery {
     }
 }
 
+/// SHA-256 digest of a set of templates, as lowercase hex
+///
+/// Covers each template's relative path and content, sorted by path, so the
+/// same template source always has the same digest regardless of discovery
+/// order or platform.
+pub fn templates_digest(templates: &[TemplateFile]) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let mut sorted: Vec<&TemplateFile> = templates.iter().collect();
+    sorted.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+    let mut hasher = Sha256::new();
+    for template in sorted {
+        let path = to_slash(&template.relative_path);
+        let content = std::fs::read(&template.path)?;
+        // Length prefixes keep path/content boundaries unambiguous
+        hasher.update((path.len() as u64).to_le_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// SHA-256 digest of `data`, as lowercase hex
+pub fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+
+    to_hex(&Sha256::digest(data))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
 /// Represents a discovered template file with its metadata
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TemplateFile {
@@ -149,6 +242,9 @@ pub enum TemplateFileType {
     
     /// JSON files (.json)
     Json,
+
+    /// Other files discovered through [`TemplateDiscovery::with_extra_types`]
+    Other,
 }
 
 impl TemplateFileType {
@@ -198,6 +294,7 @@ pub enum DiscoveryError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
 
     #[test]
     fn test_template_file_type_from_extension() {
@@ -337,11 +434,79 @@ mod tests {
         // Verify relative paths are preserved
         let deployment_file = templates
             .iter()
-            .find(|t| t.relative_path == PathBuf::from("k8s/deployment.yaml"))
+            .find(|t| t.relative_path == Path::new("k8s/deployment.yaml"))
             .expect("Should find k8s/deployment.yaml");
         assert_eq!(deployment_file.file_type, TemplateFileType::Yaml);
     }
 
+    #[test]
+    fn test_discover_templates_with_extra_types() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path();
+        fs::write(template_dir.join("main.tf"), "resource {}").unwrap();
+        fs::write(template_dir.join("run.sh"), "#!/bin/sh").unwrap();
+        fs::write(template_dir.join("Dockerfile"), "FROM scratch").unwrap();
+        fs::write(template_dir.join("notes.txt"), "ignored").unwrap();
+
+        assert_eq!(TemplateDiscovery::new(template_dir.to_path_buf()).discover_templates().unwrap().len(), 1);
+        let mut templates = TemplateDiscovery::new(template_dir.to_path_buf())
+            .with_extra_types(["sh", "Dockerfile"])
+            .discover_templates()
+            .unwrap();
+        templates.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        let found: Vec<_> = templates.iter().map(|t| (t.relative_path.to_str().unwrap(), t.file_type)).collect();
+        assert_eq!(
+            found,
+            vec![
+                ("Dockerfile", TemplateFileType::Other),
+                ("main.tf", TemplateFileType::Terraform),
+                ("run.sh", TemplateFileType::Other),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discover_templates_applies_environment_overlays() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        let template_dir = temp_dir.path();
+        fs::create_dir_all(template_dir.join("infrastructure")).unwrap();
+        fs::create_dir_all(template_dir.join("overlays/prod/infrastructure")).unwrap();
+        fs::create_dir_all(template_dir.join("overlays/dev")).unwrap();
+        fs::write(template_dir.join("main.tf"), "base").unwrap();
+        fs::write(template_dir.join("infrastructure/rds.tf"), "base").unwrap();
+        fs::write(template_dir.join("overlays/prod/infrastructure/rds.tf"), "prod").unwrap();
+        fs::write(template_dir.join("overlays/prod/alarms.tf"), "prod").unwrap();
+        fs::write(template_dir.join("overlays/dev/main.tf"), "dev").unwrap();
+
+        let discover = |environment: Option<&str>| {
+            let mut templates = TemplateDiscovery::new(template_dir.to_path_buf())
+                .with_environment(environment)
+                .discover_templates()
+                .unwrap();
+            templates.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+            templates
+                .iter()
+                .map(|t| (t.relative_path.to_str().unwrap().to_string(), fs::read_to_string(&t.path).unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let found = |pairs: &[(&str, &str)]| {
+            pairs.iter().map(|(path, content)| (path.to_string(), content.to_string())).collect::<Vec<_>>()
+        };
+
+        assert_eq!(discover(None), found(&[("infrastructure/rds.tf", "base"), ("main.tf", "base")]));
+        assert_eq!(
+            discover(Some("prod")),
+            found(&[("alarms.tf", "prod"), ("infrastructure/rds.tf", "prod"), ("main.tf", "base")])
+        );
+        assert_eq!(discover(Some("staging")), discover(None));
+    }
+
     #[test]
     fn test_discover_templates_skips_hidden_files() {
         use std::fs;
@@ -461,12 +626,19 @@ mod tests {
     #[cfg(unix)] // Permission tests only work on Unix-like systems
     fn test_discover_templates_permission_denied() {
         use std::fs;
-        use std::os::unix::fs::PermissionsExt;
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
         use tempfile::TempDir;
 
         let temp_dir = TempDir::new().unwrap();
         let template_dir = temp_dir.path();
 
+        // Root (e.g. in CI containers) ignores directory permissions, so the walk
+        // can't fail. The temporary directory is owned by our effective uid.
+        if fs::metadata(template_dir).unwrap().uid() == 0 {
+            eprintln!("skipping test_discover_templates_permission_denied: root bypasses directory permissions");
+            return;
+        }
+
         // Create a subdirectory with restricted permissions
         let restricted_dir = template_dir.join("restricted");
         fs::create_dir(&restricted_dir).unwrap();
@@ -542,4 +714,24 @@ mod tests {
         let path_error = DiscoveryError::PathError("Invalid path".to_string());
         assert_eq!(path_error.to_string(), "Path error: Invalid path");
     }
+
+    #[test]
+    fn test_templates_digest_ignores_order_and_tracks_content() {
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("a.tf"), "a").unwrap();
+        std::fs::write(temp_dir.path().join("b.tf"), "b").unwrap();
+
+        let discovery = TemplateDiscovery::new(temp_dir.path().to_path_buf());
+        let mut templates = discovery.discover_templates().unwrap();
+        let digest = templates_digest(&templates).unwrap();
+        assert_eq!(digest.len(), 64);
+
+        templates.reverse();
+        assert_eq!(templates_digest(&templates).unwrap(), digest);
+
+        std::fs::write(temp_dir.path().join("b.tf"), "changed").unwrap();
+        assert_ne!(templates_digest(&templates).unwrap(), digest);
+    }
 }
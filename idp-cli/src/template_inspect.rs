@@ -0,0 +1,188 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use handlebars::template::Template;
+use serde::Serialize;
+
+use crate::error::CliError;
+use crate::template_lint::collect_helpers;
+use crate::template_processor::for_each_list;
+use crate::var_trace::{segments, TagVisitor, TemplateWalker, VisitedTag};
+
+/// A block opened in a template, e.g. `{{#each resources}}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TemplateBlock {
+    /// Block helper, e.g. `each`, `if` or a custom helper
+    pub helper: String,
+    pub line: usize,
+    /// Opening tag of the block
+    pub expression: String,
+    /// Variable path the block's content is relative to, for `{{#each}}` and `{{#with}}`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// What a single template references, for editor completion
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateInspection {
+    pub file: PathBuf,
+    /// Absolute variable paths, sorted; `[*]` stands for any item of a list
+    pub variables: Vec<String>,
+    /// Helpers called, sorted
+    pub helpers: Vec<String>,
+    /// Blocks in the order they open
+    pub blocks: Vec<TemplateBlock>,
+}
+
+impl TemplateInspection {
+    /// Parse a template and collect its variable paths, helpers and blocks
+    ///
+    /// References inside `{{#each}}` and `{{#with}}` are resolved to absolute
+    /// paths. A `list.each.ext` template is inspected inside the list's items,
+    /// the way it is rendered.
+    pub fn inspect(file: &Path, content: &str) -> Result<Self, CliError> {
+        let template = Template::compile(content).map_err(|e| CliError::TemplateSyntaxError {
+            line: e.pos().map(|(line, _)| line).unwrap_or(0),
+            message: e.reason().to_string(),
+        })?;
+        let mut walker = TemplateWalker::new(content);
+        if let Some(list) = file.file_name().and_then(|name| for_each_list(Path::new(name))) {
+            let mut prefix = segments(&list);
+            prefix.push("*".to_string());
+            walker = walker.with_scope(prefix);
+        }
+
+        let mut collector = Collector { variables: BTreeSet::new(), helpers: BTreeSet::new(), blocks: Vec::new() };
+        walker.walk(&template, &mut collector);
+        Ok(Self {
+            file: file.to_path_buf(),
+            variables: collector.variables.into_iter().collect(),
+            helpers: collector.helpers.into_iter().collect(),
+            blocks: collector.blocks,
+        })
+    }
+
+    pub fn render_human(&self) -> String {
+        let section = |title: &str, items: Vec<String>| {
+            let mut lines = vec![format!("{} ({}):", title, items.len())];
+            lines.extend(items.into_iter().map(|item| format!("  {}", item)));
+            lines.join("\n")
+        };
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| match &block.scope {
+                Some(scope) => format!("{}: {} -> {}", block.line, block.expression, scope),
+                None => format!("{}: {}", block.line, block.expression),
+            })
+            .collect();
+        [
+            section("Variables", self.variables.clone()),
+            section("Helpers", self.helpers.clone()),
+            section("Blocks", blocks),
+        ]
+        .join("\n\n")
+    }
+}
+
+struct Collector {
+    variables: BTreeSet<String>,
+    helpers: BTreeSet<String>,
+    blocks: Vec<TemplateBlock>,
+}
+
+impl TagVisitor for Collector {
+    fn visit(&mut self, tag: &VisitedTag) {
+        let mut helpers = Vec::new();
+        collect_helpers(tag.helper, &mut helpers);
+        self.helpers.extend(helpers.into_iter().map(str::to_string));
+        for reference in &tag.references {
+            if let Some(path) = reference.path.as_deref().filter(|path| !path.is_empty()) {
+                self.variables.insert(display_path(path));
+            }
+        }
+
+        if tag.helper.template.is_some() {
+            self.blocks.push(TemplateBlock {
+                helper: tag.name().unwrap_or_default().to_string(),
+                line: tag.line,
+                expression: tag.tag.clone(),
+                scope: tag.scope.as_deref().map(display_path),
+            });
+        }
+    }
+}
+
+/// Path segments as written by `list-variables`, e.g. `resources[*].name`
+fn display_path(path: &[String]) -> String {
+    let mut display = String::new();
+    for segment in path {
+        if segment == "*" || segment.parse::<usize>().is_ok() {
+            display.push_str(&format!("[{}]", segment));
+        } else {
+            if !display.is_empty() {
+                display.push('.');
+            }
+            display.push_str(segment);
+        }
+    }
+    display
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inspect_collects_variables_helpers_and_blocks() {
+        let inspection = TemplateInspection::inspect(
+            Path::new("main.tf"),
+            "name = \"{{snake_case blueprint.name}}\"\n\
+             {{#each resources as |r|}}\n\
+             {{name}} {{configuration.engine}} {{r.type}} {{../blueprint.owner}} {{@index}}\n\
+             {{#if configuration.size}}{{default configuration.size \"small\"}}{{/if}}\n\
+             {{/each}}\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            inspection.variables,
+            vec![
+                "blueprint.name",
+                "blueprint.owner",
+                "resources",
+                "resources[*].configuration.engine",
+                "resources[*].configuration.size",
+                "resources[*].name",
+                "resources[*].type",
+            ]
+        );
+        assert_eq!(inspection.helpers, vec!["default", "each", "if", "snake_case"]);
+        assert_eq!(
+            inspection.blocks,
+            vec![
+                TemplateBlock {
+                    helper: "each".to_string(),
+                    line: 2,
+                    expression: "{{#each resources as |r|}}".to_string(),
+                    scope: Some("resources[*]".to_string()),
+                },
+                TemplateBlock {
+                    helper: "if".to_string(),
+                    line: 4,
+                    expression: "{{#if configuration.size}}".to_string(),
+                    scope: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_each_templates_are_inspected_inside_their_items() {
+        let inspection = TemplateInspection::inspect(Path::new("k8s/resources.each.yaml"), "name: {{name}}\n").unwrap();
+        assert_eq!(inspection.variables, vec!["resources[*].name"]);
+
+        let error = TemplateInspection::inspect(Path::new("main.tf"), "{{#if x}}\n").unwrap_err();
+        assert!(matches!(error, CliError::TemplateSyntaxError { .. }));
+    }
+}
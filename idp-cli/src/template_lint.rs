@@ -0,0 +1,382 @@
+use std::path::{Path, PathBuf};
+
+use handlebars::template::{HelperTemplate, Parameter, Template, TemplateElement};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::error::CliError;
+use crate::template_discovery::TemplateFile;
+use crate::template_processor::{for_each_list, lookup_path, split_each, TemplateProcessor};
+use crate::var_trace::{TagVisitor, TemplateWalker, VisitedTag};
+
+/// Kind of problem found in a template
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    /// The template does not parse, e.g. a `{{#if}}` without `{{/if}}`
+    Syntax,
+    /// A reference the data source does not provide
+    UndefinedVariable,
+    /// A helper that is neither built in nor defined in the template directory
+    UnknownHelper,
+}
+
+impl std::fmt::Display for LintKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LintKind::Syntax => "syntax error",
+            LintKind::UndefinedVariable => "undefined variable",
+            LintKind::UnknownHelper => "unknown helper",
+        })
+    }
+}
+
+/// A problem at a template line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    pub file: PathBuf,
+    pub line: usize,
+    pub kind: LintKind,
+    /// The tag containing the problem; empty for syntax errors
+    pub expression: String,
+    pub message: String,
+}
+
+/// Result of linting a template directory
+#[derive(Debug, Clone, Serialize)]
+pub struct LintReport {
+    pub templates: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn render_human(&self) -> String {
+        let mut lines: Vec<String> = self
+            .issues
+            .iter()
+            .map(|issue| {
+                let mut line = format!("{}:{}: {}: {}", issue.file.display(), issue.line, issue.kind, issue.message);
+                if !issue.expression.is_empty() {
+                    line.push_str(&format!("\n    {}", issue.expression));
+                }
+                line
+            })
+            .collect();
+        lines.push(match self.issues.len() {
+            0 => format!("No problems found in {} templates", self.templates),
+            count => format!("{} problem(s) found in {} templates", count, self.templates),
+        });
+        lines.join("\n")
+    }
+}
+
+/// Checks templates against the data handed to the template engine
+pub struct TemplateLinter {
+    data: Value,
+    helpers: Vec<String>,
+}
+
+impl TemplateLinter {
+    /// `data` is the context's template data; `custom_helpers` are the helpers
+    /// loaded for the template directory
+    pub fn new(data: Value, custom_helpers: &[&str]) -> Self {
+        let helpers = TemplateProcessor::BUILTIN_HELPERS
+            .iter()
+            .chain(custom_helpers)
+            .map(|name| name.to_string())
+            .collect();
+        Self { data, helpers }
+    }
+
+    /// Lint discovered templates, sorted by file and line
+    pub fn lint(&self, templates: &[TemplateFile]) -> Result<LintReport, CliError> {
+        let mut issues = Vec::new();
+        for template in templates {
+            let content = std::fs::read_to_string(&template.path).map_err(|e| {
+                CliError::IoError(format!("Failed to read template {}: {}", template.path.display(), e))
+            })?;
+            if let Some(list) = for_each_list(&template.relative_path) {
+                // Lint the content the way it is rendered, inside the list's items
+                let content = format!("{{{{#each {}}}}}{}{{{{/each}}}}", list, content);
+                issues.extend(self.lint_source(&template.relative_path, &content));
+                continue;
+            }
+            match self.with_path_item(&template.relative_path) {
+                Some(linter) => issues.extend(linter.lint_source(&template.relative_path, &content)),
+                None => issues.extend(self.lint_source(&template.relative_path, &content)),
+            }
+        }
+        issues.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        Ok(LintReport { templates: templates.len(), issues })
+    }
+
+    /// For a template whose path loops over a list, a linter that also knows
+    /// `item` and `item_index`, checked against the first item
+    fn with_path_item(&self, relative_path: &Path) -> Option<Self> {
+        let template = relative_path.to_string_lossy().replace('\\', "/");
+        let (_, list, _) = split_each(&template)?;
+        let item = match lookup_path(&self.data, list) {
+            Some(Value::Array(items)) => items.first().cloned().unwrap_or_else(|| json!({})),
+            _ => json!({}),
+        };
+        let mut data = self.data.clone();
+        if let Value::Object(map) = &mut data {
+            map.insert("item".to_string(), item);
+            map.insert("item_index".to_string(), json!(0));
+        }
+        Some(Self { data, helpers: self.helpers.clone() })
+    }
+
+    /// Lint the content of one template
+    pub fn lint_source(&self, file: &Path, content: &str) -> Vec<LintIssue> {
+        let template = match Template::compile(content) {
+            Ok(template) => template,
+            Err(e) => {
+                // The parser reports unbalanced blocks at the end of the file, so find the opening tag
+                let (line, expression, message) = unbalanced_block(content).unwrap_or_else(|| {
+                    (e.pos().map(|(line, _)| line).unwrap_or(0), String::new(), e.reason().to_string())
+                });
+                return vec![LintIssue { file: file.to_path_buf(), line, kind: LintKind::Syntax, expression, message }];
+            }
+        };
+        let mut checker = Checker { linter: self, file, guards: Vec::new(), issues: Vec::new() };
+        TemplateWalker::new(content).walk(&template, &mut checker);
+        checker.issues
+    }
+}
+
+struct Checker<'a> {
+    linter: &'a TemplateLinter,
+    file: &'a Path,
+    /// Paths checked by each enclosing block, for `{{#if}}`/`{{#unless}}`
+    guards: Vec<Vec<Vec<String>>>,
+    issues: Vec<LintIssue>,
+}
+
+impl TagVisitor for Checker<'_> {
+    fn visit(&mut self, tag: &VisitedTag) {
+        let mut helpers = Vec::new();
+        collect_helpers(tag.helper, &mut helpers);
+        for name in helpers {
+            if !self.linter.helpers.iter().any(|known| known == name) {
+                let message = format!("'{}' is not a built-in or custom helper", name);
+                self.report(tag, LintKind::UnknownHelper, message);
+            }
+        }
+
+        // {{#if x}}, {{#unless x}} and {{default x "..."}} expect x to be missing sometimes
+        let guarding = matches!(tag.name(), Some("if" | "unless" | "default"));
+        if !guarding {
+            for reference in &tag.references {
+                let Some(path) = &reference.path else { continue };
+                if !self.is_guarded(path) && !defined(&self.linter.data, path) {
+                    self.report(tag, LintKind::UndefinedVariable, format!("'{}' is not defined", reference.raw));
+                }
+            }
+        }
+
+        if tag.helper.block {
+            let guards = match tag.name() {
+                Some("if" | "unless") => tag.references.iter().filter_map(|reference| reference.path.clone()).collect(),
+                _ => Vec::new(),
+            };
+            self.guards.push(guards);
+        }
+    }
+
+    fn leave(&mut self, _tag: &VisitedTag) {
+        self.guards.pop();
+    }
+}
+
+impl Checker<'_> {
+    fn is_guarded(&self, path: &[String]) -> bool {
+        self.guards.iter().flatten().any(|guard| path.starts_with(guard))
+    }
+
+    fn report(&mut self, tag: &VisitedTag, kind: LintKind, message: String) {
+        self.issues.push(LintIssue {
+            file: self.file.to_path_buf(),
+            line: tag.line,
+            kind,
+            expression: tag.tag.clone(),
+            message,
+        });
+    }
+}
+
+/// First block that is never closed or is closed by the wrong tag
+fn unbalanced_block(content: &str) -> Option<(usize, String, String)> {
+    let mut open: Vec<(usize, &str, &str)> = Vec::new();
+    let mut position = 0;
+    while let Some(start) = content[position..].find("{{").map(|i| position + i) {
+        let inner = content[start..].trim_start_matches('{').trim_start_matches('~').trim_start();
+        // Comments may contain tags
+        let terminator = if inner.starts_with("!--") { "--}}" } else { "}}" };
+        let end = content[start..].find(terminator)? + start + terminator.len();
+        position = end;
+        let tag = &content[start..end];
+        let line = content[..start].matches('\n').count() + 1;
+        let name = |marker: char| {
+            let rest = inner.strip_prefix(marker)?;
+            rest.split(|c: char| c.is_whitespace() || c == '}' || c == '~').next()
+        };
+        if let Some(block) = name('#') {
+            open.push((line, block, tag));
+        } else if let Some(block) = name('/') {
+            match open.pop() {
+                Some((_, opened, _)) if opened == block => {}
+                Some((opened_line, _, opened_tag)) => {
+                    let message = format!("'{}' closes '{}' opened at line {}", tag, opened_tag, opened_line);
+                    return Some((line, tag.to_string(), message));
+                }
+                None => return Some((line, tag.to_string(), format!("'{}' has no opening block", tag))),
+            }
+        }
+    }
+    open.pop().map(|(line, block, tag)| {
+        (line, tag.to_string(), format!("'{}' is never closed with '{{{{/{}}}}}'", tag, block))
+    })
+}
+
+/// Helper names called by an expression, including in subexpressions
+pub(crate) fn collect_helpers<'a>(helper: &'a HelperTemplate, names: &mut Vec<&'a str>) {
+    if let Parameter::Name(name) = &helper.name {
+        names.push(name);
+    }
+    for param in helper.params.iter().chain(helper.hash.values()) {
+        if let Parameter::Subexpression(subexpression) = param {
+            if let TemplateElement::Expression(inner) = subexpression.element.as_ref() {
+                collect_helpers(inner, names);
+            }
+        }
+    }
+}
+
+/// Whether a path exists in the template data
+///
+/// `*` matches any element; paths into empty arrays or objects can't be
+/// checked and count as defined.
+fn defined(data: &Value, path: &[String]) -> bool {
+    let Some((segment, rest)) = path.split_first() else {
+        return true;
+    };
+    match data {
+        Value::Array(items) if segment == "*" => items.is_empty() || items.iter().any(|item| defined(item, rest)),
+        Value::Object(map) if segment == "*" => map.is_empty() || map.values().any(|value| defined(value, rest)),
+        Value::Array(items) if segment == "length" => rest.is_empty() || items.is_empty(),
+        Value::Array(items) => segment
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| items.get(index))
+            .is_some_and(|item| defined(item, rest)),
+        Value::Object(map) => map.get(segment).is_some_and(|value| defined(value, rest)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lint(content: &str) -> Vec<(usize, LintKind, String)> {
+        let data = json!({
+            "blueprint": {"name": "web-app"},
+            "resources": [
+                {"name": "db", "configuration": {"engine": "postgres"}},
+                {"name": "cache"}
+            ]
+        });
+        TemplateLinter::new(data, &["tag_list"])
+            .lint_source(Path::new("main.tf.hbs"), content)
+            .into_iter()
+            .map(|issue| (issue.line, issue.kind, issue.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_reports_undefined_variables_and_unknown_helpers() {
+        let issues = lint(
+            "name = \"{{blueprint.name}}\"\n\
+             owner = \"{{blueprint.owner}}\"\n\
+             {{#each resources}}\n\
+             {{name}} {{configuration.engine}} {{configuration.size}} {{../blueprint.name}} {{@index}}\n\
+             {{/each}}\n\
+             {{#each resources as |r|}}{{r.name}}{{/each}}\n\
+             tags = {{tag_list resources}} {{snake_case blueprint.name}}\n",
+        );
+
+        assert_eq!(
+            issues,
+            vec![
+                (2, LintKind::UndefinedVariable, "'blueprint.owner' is not defined".to_string()),
+                (4, LintKind::UndefinedVariable, "'configuration.size' is not defined".to_string()),
+                (7, LintKind::UnknownHelper, "'snake_case' is not a built-in or custom helper".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_block_params_stand_for_their_list_items() {
+        let issues = lint(
+            "{{#each resources as |r i|}}{{r.name}} {{r.nme}} {{i}}{{/each}}
+             {{#with blueprint as |b|}}{{b.name}} {{b.owner}}{{/with}}
+             {{#each resources as |r|}}{{#if r.configuration.size}}{{r.configuration.size}}{{/if}}{{/each}}
+",
+        );
+        assert_eq!(
+            issues,
+            vec![
+                (1, LintKind::UndefinedVariable, "'r.nme' is not defined".to_string()),
+                (2, LintKind::UndefinedVariable, "'b.owner' is not defined".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_guarded_references_are_not_reported() {
+        let issues = lint(
+            "{{#if blueprint.description}}{{blueprint.description}}{{/if}}\n\
+             {{default blueprint.owner \"platform\"}}\n\
+             {{#unless blueprint.owner}}none{{else}}{{blueprint.owner}}{{/unless}}\n",
+        );
+        assert!(issues.is_empty(), "{:?}", issues);
+    }
+
+    #[test]
+    fn test_reports_unclosed_blocks() {
+        let issues = lint("line one\n{{#each resources}}\n{{name}}\n");
+        assert_eq!(
+            issues,
+            vec![(2, LintKind::Syntax, "'{{#each resources}}' is never closed with '{{/each}}'".to_string())]
+        );
+
+        let issues = lint("{{! {{#if}} in a comment }}\n{{#each resources}}{{#if name}}\n{{/each}}\n");
+        assert_eq!(
+            issues,
+            vec![(3, LintKind::Syntax, "'{{/each}}' closes '{{#if name}}' opened at line 2".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_each_templates_are_linted_inside_their_items() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("resources.each.tf");
+        std::fs::write(&path, "name = \"{{name}}-{{@root.blueprint.name}}\"\nsize = \"{{configuration.size}}\"\n").unwrap();
+        let template = TemplateFile {
+            path,
+            relative_path: PathBuf::from("resources.each.tf"),
+            file_type: crate::template_discovery::TemplateFileType::Terraform,
+        };
+
+        let data = json!({"blueprint": {"name": "web-app"}, "resources": [{"name": "db", "configuration": {}}]});
+        let report = TemplateLinter::new(data, &[]).lint(&[template]).unwrap();
+        let issues: Vec<_> = report.issues.iter().map(|issue| (issue.line, issue.message.as_str())).collect();
+        assert_eq!(issues, vec![(2, "'configuration.size' is not defined")]);
+    }
+}
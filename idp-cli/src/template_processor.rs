@@ -1,10 +1,23 @@
+use crate::dependency_graph::topo_sort_resources;
 use crate::error::CliError;
+use crate::helper_plugins::HelperSet;
+use crate::instance_sizes::InstanceSizeHelper;
+use crate::render_sandbox::{self, DeadlineOutput, EnvHelper, ReadFileHelper, SandboxPolicy};
+use crate::reports::{Finding, FindingLevel, Findings};
+use crate::file_writer::sanitize_path_segment;
+use crate::source_map::{render_mapped, render_mapped_with, SourceMap};
 use crate::template_discovery::TemplateFile;
+use crate::validators::{Validator, ValidatorRegistry};
 use crate::variable_context::VariableContext;
 use handlebars::{
-    Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderError,
+    handlebars_helper, BlockContext, BlockParams, Context, Handlebars, Helper, HelperResult, Output, RenderContext,
+    RenderError, RenderErrorReason, Renderable, Template,
 };
-use std::path::PathBuf;
+use rhai::Dynamic;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
 // Helper function implementations
 
@@ -22,24 +35,63 @@ fn default_helper(
     
     // Get the second parameter (the default/fallback value)
     let default = h.param(1)
-        .ok_or_else(|| RenderError::new("default helper requires a fallback value"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("default helper requires a fallback value".to_string())))?;
     
-    // Use the value if it exists and is not empty, otherwise use default
-    let result = if let Some(val) = value {
-        let val_str = val.value().as_str().unwrap_or("");
-        if val_str.is_empty() {
-            default.value().as_str().unwrap_or("")
-        } else {
-            val_str
+    // Use the value if it exists and is not empty, otherwise use default.
+    // Non-string values (numbers, booleans) are rendered as-is.
+    let result = match value.map(|val| val.value()) {
+        Some(serde_json::Value::String(s)) if !s.is_empty() => s.clone(),
+        Some(serde_json::Value::Null) | Some(serde_json::Value::String(_)) | None => {
+            default.value().as_str().unwrap_or("").to_string()
         }
-    } else {
-        default.value().as_str().unwrap_or("")
+        Some(other) => other.to_string(),
     };
     
-    out.write(result)?;
+    out.write(&result)?;
+    Ok(())
+}
+
+// Topological sort helper: {{#each (topo_sorted resources)}}...{{/each}}
+// Returns the resources array ordered so that dependencies come first
+handlebars_helper!(topo_sorted_helper: |resources: array| {
+    serde_json::Value::Array(topo_sort_resources(resources))
+});
+
+/// Spread helper: {{spread index ["us-east-1a", "us-east-1b", "us-east-1c"]}}
+/// Deterministically picks an item of the list for a key: integers go
+/// round-robin, any other key (e.g. a resource name) by a stable hash
+fn spread_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let key = h.param(0)
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("spread helper requires a key and a list".to_string())))?;
+    let items = h.param(1)
+        .and_then(|items| items.value().as_array())
+        .filter(|items| !items.is_empty())
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("spread helper requires a non-empty list".to_string())))?;
+
+    let index = match key.value() {
+        serde_json::Value::Number(n) if n.is_i64() => n.as_i64().unwrap_or(0).rem_euclid(items.len() as i64) as usize,
+        serde_json::Value::Number(n) if n.is_u64() => (n.as_u64().unwrap_or(0) % items.len() as u64) as usize,
+        serde_json::Value::String(s) => (stable_hash(s) % items.len() as u64) as usize,
+        other => (stable_hash(&other.to_string()) % items.len() as u64) as usize,
+    };
+    match &items[index] {
+        serde_json::Value::String(s) => out.write(s)?,
+        other => out.write(&other.to_string())?,
+    }
     Ok(())
 }
 
+/// FNV-1a hash, stable across runs and platforms unlike `DefaultHasher`
+fn stable_hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3))
+}
+
 /// Uppercase helper: {{uppercase text}}
 /// Converts the input text to uppercase
 fn uppercase_helper(
@@ -50,7 +102,7 @@ fn uppercase_helper(
     out: &mut dyn Output,
 ) -> HelperResult {
     let param = h.param(0)
-        .ok_or_else(|| RenderError::new("uppercase helper requires a parameter"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("uppercase helper requires a parameter".to_string())))?;
     
     let text = param.value().as_str().unwrap_or("");
     out.write(&text.to_uppercase())?;
@@ -67,7 +119,7 @@ fn lowercase_helper(
     out: &mut dyn Output,
 ) -> HelperResult {
     let param = h.param(0)
-        .ok_or_else(|| RenderError::new("lowercase helper requires a parameter"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("lowercase helper requires a parameter".to_string())))?;
     
     let text = param.value().as_str().unwrap_or("");
     out.write(&text.to_lowercase())?;
@@ -84,7 +136,7 @@ fn capitalize_helper(
     out: &mut dyn Output,
 ) -> HelperResult {
     let param = h.param(0)
-        .ok_or_else(|| RenderError::new("capitalize helper requires a parameter"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("capitalize helper requires a parameter".to_string())))?;
     
     let text = param.value().as_str().unwrap_or("");
     if text.is_empty() {
@@ -111,7 +163,7 @@ fn trim_helper(
     out: &mut dyn Output,
 ) -> HelperResult {
     let param = h.param(0)
-        .ok_or_else(|| RenderError::new("trim helper requires a parameter"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("trim helper requires a parameter".to_string())))?;
     
     let text = param.value().as_str().unwrap_or("");
     out.write(text.trim())?;
@@ -128,11 +180,11 @@ fn replace_helper(
     out: &mut dyn Output,
 ) -> HelperResult {
     let text_param = h.param(0)
-        .ok_or_else(|| RenderError::new("replace helper requires text parameter"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("replace helper requires text parameter".to_string())))?;
     let old_param = h.param(1)
-        .ok_or_else(|| RenderError::new("replace helper requires old string parameter"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("replace helper requires old string parameter".to_string())))?;
     let new_param = h.param(2)
-        .ok_or_else(|| RenderError::new("replace helper requires new string parameter"))?;
+        .ok_or_else(|| RenderError::from(RenderErrorReason::Other("replace helper requires new string parameter".to_string())))?;
     
     let text = text_param.value().as_str().unwrap_or("");
     let old = old_param.value().as_str().unwrap_or("");
@@ -143,15 +195,69 @@ fn replace_helper(
     Ok(())
 }
 
+/// Template data of a processor: its variable context in the form templates
+/// see it, built once and shared by the processor's copies
+pub(crate) struct TemplateData {
+    context: Context,
+    /// The data as a Rhai value for script helpers, converted on first use
+    script_root: OnceLock<Dynamic>,
+}
+
+thread_local! {
+    /// Template data of the render in progress on this thread
+    static RENDERING: RefCell<Option<Arc<TemplateData>>> = const { RefCell::new(None) };
+}
+
+impl TemplateData {
+    fn new(context: &VariableContext) -> Self {
+        TemplateData { context: Context::from(context.to_template_data()), script_root: OnceLock::new() }
+    }
+
+    /// Run a render of this data on this thread
+    fn rendering<T>(self: &Arc<Self>, render: impl FnOnce(&Context) -> T) -> T {
+        let previous = RENDERING.with(|rendering| rendering.replace(Some(Arc::clone(self))));
+        let result = render(&self.context);
+        RENDERING.with(|rendering| rendering.replace(previous));
+        result
+    }
+
+    /// `ctx` as a Rhai value for script helpers
+    ///
+    /// When `ctx` is the template data being rendered on this thread, the
+    /// value is converted once and shared; other contexts, such as a template
+    /// helper's `args`, `hash` and `root`, are converted each time.
+    pub(crate) fn script_root(ctx: &Context) -> Result<Dynamic, Box<rhai::EvalAltResult>> {
+        let rendering = RENDERING.with(|rendering| rendering.borrow().clone());
+        let Some(data) = rendering.filter(|data| std::ptr::eq(&data.context, ctx)) else {
+            return rhai::serde::to_dynamic(ctx.data());
+        };
+        if let Some(root) = data.script_root.get() {
+            return Ok(root.clone());
+        }
+        let root = rhai::serde::to_dynamic(data.context.data())?.into_read_only().into_shared();
+        Ok(data.script_root.get_or_init(|| root).clone())
+    }
+}
+
 /// Template processor that substitutes variables in template files
 /// 
 /// Uses the Handlebars template engine to process templates with variable substitution.
 /// Supports {{variable_name}} syntax, dot notation, array indexing, and custom helpers.
 pub struct TemplateProcessor<'a> {
     /// Variable context containing all available variables
-    context: &'a VariableContext,
+    context: Cow<'a, VariableContext>,
+    /// The variable context as template data, shared with copies of the processor
+    data: Arc<TemplateData>,
     /// Handlebars engine instance with custom configuration
-    handlebars: Handlebars<'a>,
+    handlebars: Handlebars<'static>,
+    /// Also produce an annotated copy of each processed file
+    debug_render: bool,
+    /// Render timeout and what the env_var and read_file helpers may access
+    sandbox: Arc<SandboxPolicy>,
+    /// Checks rendered output before it is written, by file type
+    validators: ValidatorRegistry,
+    /// Where validation failures are recorded for `--report`
+    findings: Option<Findings>,
 }
 
 impl<'a> TemplateProcessor<'a> {
@@ -168,7 +274,7 @@ impl<'a> TemplateProcessor<'a> {
     /// A new TemplateProcessor instance ready to process templates
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let context = VariableContextBuilder::from_blueprint(&blueprint);
     /// let processor = TemplateProcessor::new(&context);
     /// ```
@@ -176,7 +282,8 @@ impl<'a> TemplateProcessor<'a> {
         let mut handlebars = Handlebars::new();
         
         // Configure Handlebars
-        // Disable strict mode to allow missing variables (we'll handle them gracefully)
+        // Disable strict mode to allow missing variables (we'll handle them gracefully);
+        // `with_strict_variables` turns it on
         handlebars.set_strict_mode(false);
         
         // Enable HTML escaping prevention for infrastructure code
@@ -187,17 +294,94 @@ impl<'a> TemplateProcessor<'a> {
         Self::register_helpers(&mut handlebars);
         
         TemplateProcessor {
-            context,
+            context: Cow::Borrowed(context),
+            data: Arc::new(TemplateData::new(context)),
             handlebars,
+            debug_render: false,
+            sandbox: Arc::new(SandboxPolicy::default()),
+            validators: ValidatorRegistry::default(),
+            findings: None,
         }
+        .with_sandbox(SandboxPolicy::default())
     }
 
+    /// Copy of this processor that owns its variable context, so it can be
+    /// moved to other threads
+    pub fn to_owned_context(&self) -> TemplateProcessor<'static> {
+        TemplateProcessor {
+            context: Cow::Owned((*self.context).clone()),
+            data: Arc::clone(&self.data),
+            handlebars: self.handlebars.clone(),
+            debug_render: self.debug_render,
+            sandbox: Arc::clone(&self.sandbox),
+            validators: self.validators.clone(),
+            findings: self.findings.clone(),
+        }
+    }
+
+    /// Also produce an annotated copy of each processed file (see [`ProcessedFile::annotated`])
+    pub fn with_debug_render(mut self, enabled: bool) -> Self {
+        self.debug_render = enabled;
+        self
+    }
+
+    /// Fail on references to undefined variables instead of rendering them empty
+    ///
+    /// Helper arguments such as `{{default region "us-east-1"}}` and
+    /// `{{#if tags}}` may still be undefined.
+    pub fn with_strict_variables(mut self, enabled: bool) -> Self {
+        self.handlebars.set_strict_mode(enabled);
+        self
+    }
+
+    /// Apply a render timeout and allow the env_var and read_file helpers to read the listed names and paths
+    /// 
+    /// With a timeout, a file still rendering at the deadline fails at its
+    /// next write or custom helper call (see [`SandboxPolicy::render_timeout`]).
+    pub fn with_sandbox(mut self, policy: SandboxPolicy) -> Self {
+        self.sandbox = Arc::new(policy);
+        self.handlebars.register_helper("env_var", Box::new(EnvHelper(Arc::clone(&self.sandbox))));
+        self.handlebars.register_helper("read_file", Box::new(ReadFileHelper(Arc::clone(&self.sandbox))));
+        self
+    }
+
+    /// Validate rendered output with these validators instead of the built-in ones
+    pub fn with_validators(mut self, validators: ValidatorRegistry) -> Self {
+        self.validators = validators;
+        self
+    }
+
+    /// Also record validation failures, at the template line that produced them
+    pub fn with_findings(mut self, findings: Findings) -> Self {
+        self.findings = Some(findings);
+        self
+    }
+
+    /// Register custom helpers in addition to the built-in ones
+    /// 
+    /// Fails if a custom helper has the name of a built-in helper.
+    pub fn with_helpers(mut self, helpers: &HelperSet) -> Result<Self, CliError> {
+        helpers.apply(&mut self.handlebars)?;
+        Ok(self)
+    }
+
+    /// Names of the helpers every processor has: Handlebars' own and the ones
+    /// from [`TemplateProcessor::register_helpers`]
+    pub const BUILTIN_HELPERS: &'static [&'static str] = &[
+        "if", "unless", "each", "with", "lookup", "raw", "log", "eq", "ne", "gt", "gte", "lt", "lte", "and", "or",
+        "not", "len", "default", "uppercase", "lowercase", "capitalize", "trim", "replace", "topo_sorted", "env_var",
+        "read_file", "instance_size", "spread",
+    ];
+
     /// Register all custom Handlebars helpers
     /// 
     /// Registers helpers for:
     /// - Default values: {{default variable "fallback"}}
     /// - Case conversion: {{uppercase text}}, {{lowercase text}}, {{capitalize text}}
     /// - String operations: {{trim text}}, {{replace text "old" "new"}}
+    /// - Dependency ordering: {{#each (topo_sorted resources)}}...{{/each}}
+    /// - Abstract sizes: {{instance_size configuration.size}}
+    /// - Distribution: {{spread @index ["us-east-1a", "us-east-1b"]}}
     /// 
     /// Note: Conditional (if/else) and loop (each) helpers are built-in to Handlebars
     fn register_helpers(handlebars: &mut Handlebars) {
@@ -212,6 +396,15 @@ impl<'a> TemplateProcessor<'a> {
         // String operation helpers
         handlebars.register_helper("trim", Box::new(trim_helper));
         handlebars.register_helper("replace", Box::new(replace_helper));
+
+        // Dependency ordering helper
+        handlebars.register_helper("topo_sorted", Box::new(topo_sorted_helper));
+
+        // Abstract size translation helper
+        handlebars.register_helper("instance_size", Box::new(InstanceSizeHelper));
+
+        // Deterministic distribution helper
+        handlebars.register_helper("spread", Box::new(spread_helper));
     }
 
     /// Process a template string and substitute variables
@@ -227,21 +420,39 @@ impl<'a> TemplateProcessor<'a> {
     /// * `Err(CliError)` - If template processing fails
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let template = "resource \"aws_instance\" \"{{name}}\" { ... }";
     /// let result = processor.process_template(template)?;
     /// ```
     pub fn process_template(&self, template_content: &str) -> Result<String, CliError> {
-        // Convert VariableContext to a format Handlebars can use
-        let data = serde_json::to_value(self.context.variables())
-            .map_err(|e| CliError::ProcessingError(format!("Failed to serialize context: {}", e)))?;
-        
-        // Render the template
-        self.handlebars
-            .render_template(template_content, &data)
-            .map_err(|e| {
-                self.enhance_template_error(e, template_content)
-            })
+        self.render(template_content, |handlebars, template_content, data| {
+            render_in_scope(handlebars, template_content, data, None)
+        })
+    }
+
+    /// Process a template string, also returning a map from output lines to template lines
+    ///
+    /// Used for files whose output is validated, so validation errors can point
+    /// at the template line and variables that produced the failing line.
+    pub fn process_template_mapped(&self, template_content: &str) -> Result<(String, SourceMap), CliError> {
+        self.render(template_content, render_mapped)
+    }
+
+    /// Render a template with the template data under the sandbox policy
+    fn render<T>(
+        &self,
+        template_content: &str,
+        render: impl FnOnce(&Handlebars<'static>, &str, &Context) -> Result<T, RenderError>,
+    ) -> Result<T, CliError> {
+        let (result, violation) = render_sandbox::guarded(self.sandbox.render_timeout, || {
+            self.data.rendering(|data| render(&self.handlebars, template_content, data))
+        });
+
+        match (result, violation) {
+            (Ok(output), _) => Ok(output),
+            (Err(_), Some(violation)) => Err(CliError::RenderLimitError(violation)),
+            (Err(e), None) => Err(self.enhance_template_error(e, template_content)),
+        }
     }
 
     /// Enhance template rendering errors with helpful context
@@ -258,6 +469,21 @@ impl<'a> TemplateProcessor<'a> {
     /// # Returns
     /// An enhanced CliError with better context
     fn enhance_template_error(&self, error: RenderError, template_content: &str) -> CliError {
+        // Undefined variables in strict mode
+        if let RenderErrorReason::MissingVariable(path) = error.reason() {
+            let variable = path.clone().unwrap_or_else(|| "<helper result>".to_string());
+            let mut suggestion = self.suggest_similar_variables(&variable);
+            if let Some(line_num) = error.line_no {
+                suggestion = format!(
+                    "Template line {}:\n{}\n\n{}",
+                    line_num,
+                    self.get_template_line(template_content, line_num),
+                    suggestion
+                );
+            }
+            return CliError::VariableNotFoundError { variable, suggestion };
+        }
+
         let error_msg = error.to_string();
         
         // Check if this is a template syntax error
@@ -267,13 +493,7 @@ impl<'a> TemplateProcessor<'a> {
                 return CliError::TemplateSyntaxError {
                     line: line_num,
                     message: format!(
-                        "{}\n\n\
-                        Common template syntax issues:\n\
-                        - Unclosed braces: {{{{ variable (missing closing }}}})\n\
-                        - Invalid helper syntax: {{{{helper param1 param2}}}}\n\
-                        - Mismatched block helpers: {{{{#if}}}} without {{{{/if}}}}\n\
-                        \n\
-                        Template line {}:\n{}",
+                        "{}\n\nTemplate line {}:\n{}",
                         error_msg,
                         line_num,
                         self.get_template_line(template_content, line_num)
@@ -293,16 +513,8 @@ impl<'a> TemplateProcessor<'a> {
             }
         }
         
-        // Generic processing error with enhanced message
-        CliError::ProcessingError(format!(
-            "Template processing failed: {}\n\n\
-            Troubleshooting tips:\n\
-            - Verify all {{{{variable}}}} placeholders have corresponding values\n\
-            - Check that nested access paths are correct (e.g., {{{{resource.name}}}})\n\
-            - Ensure array indices are valid (e.g., {{{{resources.0.name}}}})\n\
-            - Use the 'list-variables' command to see available variables",
-            error_msg
-        ))
+        // Generic processing error; remediation comes from the error catalog
+        CliError::ProcessingError(format!("Template processing failed: {}", error_msg))
     }
 
     /// Extract line number from error message
@@ -424,10 +636,11 @@ impl<'a> TemplateProcessor<'a> {
         }
         
         // Same prefix (first 3 chars)
-        if search_lower.len() >= 3 && candidate_lower.len() >= 3 {
-            if search_lower[..3] == candidate_lower[..3] {
-                return true;
-            }
+        if search_lower.len() >= 3
+            && candidate_lower.len() >= 3
+            && search_lower[..3] == candidate_lower[..3]
+        {
+            return true;
         }
         
         // Simple Levenshtein distance check (max 2 edits)
@@ -451,11 +664,11 @@ impl<'a> TemplateProcessor<'a> {
         
         let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
         
-        for i in 0..=len1 {
-            matrix[i][0] = i;
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[0] = i;
         }
-        for j in 0..=len2 {
-            matrix[0][j] = j;
+        for (j, cell) in matrix[0].iter_mut().enumerate() {
+            *cell = j;
         }
         
         for (i, c1) in s1.chars().enumerate() {
@@ -478,7 +691,8 @@ impl<'a> TemplateProcessor<'a> {
     /// 
     /// Reads the template file, processes it with variable substitution,
     /// and returns a ProcessedFile with the relative path and processed content.
-    /// For YAML files, validates the syntax after processing.
+    /// Files with a validator for their type (YAML, JSON and Terraform by
+    /// default) are validated after processing.
     /// 
     /// # Arguments
     /// * `template_file` - The TemplateFile to process
@@ -488,105 +702,348 @@ impl<'a> TemplateProcessor<'a> {
     /// * `Err(CliError)` - If file reading, processing, or validation fails
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let template_file = TemplateFile { ... };
     /// let processed = processor.process_file(&template_file)?;
     /// ```
     pub fn process_file(&self, template_file: &TemplateFile) -> Result<ProcessedFile, CliError> {
+        let template_content = Self::read_template(template_file)?;
+        match self.output_paths(&template_file.relative_path)?.as_slice() {
+            [(relative_path, None)] => self.render_output(template_file, &template_content, relative_path.clone(), None),
+            _ => Err(CliError::ProcessingError(format!(
+                "Template '{}' renders one file per item of a list, not a single file",
+                template_file.relative_path.display()
+            ))),
+        }
+    }
+
+    /// Process a template file into every file its path renders to
+    ///
+    /// Same as [`TemplateProcessor::process_file`] for most templates; a path
+    /// with `{{#each list}}` or a `list.each.tf` file name gives one file per
+    /// item of `list` (none for an empty list). See
+    /// [`TemplateProcessor::output_paths`].
+    pub fn process_file_outputs(&self, template_file: &TemplateFile) -> Result<Vec<ProcessedFile>, CliError> {
+        let template_content = Self::read_template(template_file)?;
+        self.output_paths(&template_file.relative_path)?
+            .into_iter()
+            .map(|(relative_path, item)| self.render_output(template_file, &template_content, relative_path, item.as_ref()))
+            .collect()
+    }
+
+    fn read_template(template_file: &TemplateFile) -> Result<String, CliError> {
+        std::fs::read_to_string(&template_file.path).map_err(|e| {
+            CliError::ProcessingError(format!(
+                "Failed to read template file '{}': {}",
+                template_file.path.display(),
+                e
+            ))
+        })
+    }
+
+    /// Render a template's content for one of its output paths
+    fn render_output(
+        &self,
+        template_file: &TemplateFile,
+        template_content: &str,
+        relative_path: PathBuf,
+        item: Option<&ListItem>,
+    ) -> Result<ProcessedFile, CliError> {
         use crate::template_discovery::TemplateFileType;
-        
-        // Read the template file content
-        let template_content = std::fs::read_to_string(&template_file.path)
-            .map_err(|e| {
-                CliError::ProcessingError(format!(
-                    "Failed to read template file '{}': {}",
-                    template_file.path.display(),
-                    e
-                ))
-            })?;
-        
-        // Process the template
-        let processed_content = self.process_template(&template_content)?;
-        
-        // Validate YAML syntax for YAML files
-        if template_file.file_type == TemplateFileType::Yaml {
-            self.validate_yaml(&processed_content, &template_file.relative_path)?;
+
+        // Sandbox errors don't point at a template line, so name the file
+        let in_file = |e: CliError| match e {
+            CliError::RenderLimitError(message) => {
+                CliError::RenderLimitError(format!("{}: {}", relative_path.display(), message))
+            }
+            other => other,
+        };
+        // The content sees the item as `item`; `*.each.*` templates render as
+        // if inside `{{#each}}` at their item
+        let item = item.cloned();
+
+        if let Some(findings) = &self.findings {
+            findings.check(&template_file.path);
         }
-        
+        // Process the template, validating the output of file types with a validator
+        let validator = self.validators.for_path(&relative_path);
+        let schema = self.validators.schema_for(&template_file.path, template_content, &relative_path)?;
+        if validator.is_none() && schema.is_none() && !self.debug_render {
+            let content = self
+                .render(template_content, move |handlebars, content, data| {
+                    render_in_scope(handlebars, content, data, item.as_ref())
+                })
+                .map_err(in_file)?;
+            return Ok(ProcessedFile::new(relative_path, content));
+        }
+
+        let (processed_content, source_map) = self
+            .render(template_content, move |handlebars, content, data| {
+                render_mapped_with(handlebars, content, data, |rc| {
+                    if let Some(item) = &item {
+                        item.enter(rc);
+                    }
+                })
+            })
+            .map_err(in_file)?;
+        if let Some(validator) = validator {
+            self.validate_output(validator, &processed_content, &relative_path, Some((template_file, &source_map)))?;
+        }
+        if let Some(schema) = &schema {
+            self.validate_output(schema.as_ref(), &processed_content, &relative_path, Some((template_file, &source_map)))?;
+        }
+        let annotated = self.debug_render.then(|| {
+            let comment = match template_file.file_type {
+                TemplateFileType::Json => "//",
+                TemplateFileType::Terraform | TemplateFileType::Yaml | TemplateFileType::Other => "#",
+            };
+            source_map.annotate(&processed_content, comment)
+        });
+
         // Create and return the ProcessedFile
         Ok(ProcessedFile {
-            relative_path: template_file.relative_path.clone(),
+            relative_path,
             content: processed_content,
+            annotated,
         })
     }
 
-    /// Validate YAML syntax after variable substitution
-    /// 
-    /// Validates that the processed content is valid YAML. Supports multi-document
-    /// YAML files (documents separated by ---).
-    /// 
-    /// # Arguments
-    /// * `content` - The processed YAML content to validate
-    /// * `file_path` - The relative path of the file (for error messages)
-    /// 
-    /// # Returns
-    /// * `Ok(())` - If the YAML is valid
-    /// * `Err(CliError)` - If the YAML syntax is invalid
+    /// Output paths of a template, rendering the expressions in its relative path
+    ///
+    /// `{{stack.name}}/main.tf` renders to a single path. `{{#each list}}`
+    /// renders the rest of the path once per item of `list` with the item as
+    /// context, e.g. `modules/{{#each resources}}{{name}}.tf`; the block is
+    /// closed at the end of the path. Each such path comes with its item, which
+    /// the content sees as `item` (and its position as `item_index`).
+    ///
+    /// A file named `list.each.ext`, e.g. `resources.each.tf`, also renders once
+    /// per item of `list`, to a file named after the item's `name`
+    /// (`postgres-db.tf`). Its content is rendered as if inside
+    /// `{{#each list}}`: `{{name}}` and `{{this}}` refer to the item and
+    /// `{{@index}}` to its position.
+    pub fn output_paths(&self, relative_path: &Path) -> Result<Vec<(PathBuf, Option<ListItem>)>, CliError> {
+        let template = relative_path.to_string_lossy().replace('\\', "/");
+        let render = |path_template: &str| {
+            self.process_template(path_template).map_err(|e| {
+                CliError::ProcessingError(format!("Failed to render template path '{}': {}", template, e))
+            })
+        };
+        let list_items = |list: &str| match lookup_path(self.data.context.data(), list) {
+            Some(serde_json::Value::Array(items)) => Ok(items.clone()),
+            _ => Err(CliError::ProcessingError(format!(
+                "Template path '{}' loops over '{}', which is not a list",
+                template, list
+            ))),
+        };
+
+        if let Some(list) = for_each_list(relative_path) {
+            if template.contains("{{#each ") {
+                return Err(CliError::ProcessingError(format!(
+                    "Template path '{}' can't both loop with '{{{{#each}}}}' and be a '.each.' template",
+                    template
+                )));
+            }
+            let dir = match relative_path.parent().map(|dir| dir.to_string_lossy().replace('\\', "/")) {
+                Some(dir) if dir.contains("{{") => checked_path(&template, &render(&dir)?)?,
+                Some(dir) => PathBuf::from(dir),
+                None => PathBuf::new(),
+            };
+            let extension = relative_path.extension().unwrap_or_default().to_string_lossy();
+            let items = list_items(&list)?;
+            let len = items.len();
+            let outputs = items.into_iter().enumerate().map(|(index, value)| {
+                let name = value
+                    .get("name")
+                    .or(Some(&value))
+                    .and_then(serde_json::Value::as_str)
+                    .map(sanitize_path_segment)
+                    .unwrap_or_else(|| index.to_string());
+                let item = ListItem { list: list.clone(), index, len, value, scoped: true };
+                (dir.join(format!("{}.{}", name, extension)), Some(item))
+            });
+            return unique_paths(&template, outputs.collect());
+        }
+
+        if !template.contains("{{") {
+            return Ok(vec![(relative_path.to_path_buf(), None)]);
+        }
+        let Some((prefix, list, rest)) = split_each(&template) else {
+            return Ok(vec![(checked_path(&template, &render(&template)?)?, None)]);
+        };
+        let items = list_items(list)?;
+        let len = items.len();
+
+        // Paths can't hold '{{/each}}', so close the block here; NUL separates the items
+        let prefix = render(prefix)?;
+        let rendered = render(&format!("{{{{#each {}}}}}{}\0{{{{/each}}}}", list, rest))?;
+        let outputs = rendered
+            .split_terminator('\0')
+            .zip(items.into_iter().enumerate())
+            .map(|(path, (index, value))| {
+                let item = ListItem { list: list.to_string(), index, len, value, scoped: false };
+                Ok((checked_path(&template, &format!("{}{}", prefix, path))?, Some(item)))
+            })
+            .collect::<Result<Vec<_>, CliError>>()?;
+        unique_paths(&template, outputs)
+    }
+
+    /// Validate processed content with a validator
     /// 
-    /// # Examples
-    /// ```
-    /// processor.validate_yaml(&yaml_content, &PathBuf::from("deployment.yaml"))?;
-    /// ```
-    fn validate_yaml(&self, content: &str, file_path: &PathBuf) -> Result<(), CliError> {
-        // Split content by document separator (---)
-        // YAML documents can be separated by "---" on its own line
-        let documents: Vec<&str> = content
-            .split("\n---\n")
-            .filter(|doc| !doc.trim().is_empty())
-            .collect();
-        
-        if documents.is_empty() {
-            // Empty file is technically valid YAML
-            return Ok(());
+    /// When the validator reports an output line and the template's source map
+    /// is given, the error also names the template line and variables that
+    /// produced it.
+    fn validate_output(
+        &self,
+        validator: &dyn Validator,
+        content: &str,
+        file_path: &Path,
+        source: Option<(&TemplateFile, &SourceMap)>,
+    ) -> Result<(), CliError> {
+        let invalid = match validator.validate(content, file_path) {
+            Ok(()) => return Ok(()),
+            Err(invalid) => invalid,
+        };
+        let section = invalid.section.map(|section| format!(" ({})", section)).unwrap_or_default();
+        // Point at the template line that produced the failing output line
+        let location = invalid.line.and_then(|line| Some((line, source?.1.locate(line)?)));
+        if let Some(((template_file, _), findings)) = source.zip(self.findings.as_ref()) {
+            findings.push(Finding {
+                rule_id: format!("validation/{}", validator.name().to_lowercase().replace(' ', "-")),
+                rule_name: format!("{} validation", validator.name()),
+                level: FindingLevel::Error,
+                message: format!("{}{}: {}", file_path.display(), section, invalid.message),
+                file: template_file.path.clone(),
+                line: location.as_ref().map(|(_, location)| location.line),
+            });
         }
-        
-        // Validate each document
-        for (index, document) in documents.iter().enumerate() {
-            // Try to parse the document as YAML
-            match serde_yaml::from_str::<serde_yaml::Value>(document) {
-                Ok(_) => {
-                    // Document is valid YAML
-                    continue;
-                }
-                Err(e) => {
-                    // Provide clear error message with document number and location
-                    let doc_info = if documents.len() > 1 {
-                        format!(" (document {})", index + 1)
-                    } else {
-                        String::new()
-                    };
-                    
-                    return Err(CliError::ProcessingError(format!(
-                        "YAML validation failed for '{}'{}: {}\n\
-                        \n\
-                        The generated YAML has invalid syntax. This usually means:\n\
-                        - A variable substitution resulted in invalid YAML structure\n\
-                        - Missing or incorrect indentation\n\
-                        - Unquoted special characters\n\
-                        \n\
-                        Please check your template and variable values.",
-                        file_path.display(),
-                        doc_info,
-                        e
-                    )));
-                }
-            }
+        let origin = location
+            .map(|(line, location)| format!("\n\nOutput line {} comes from {}", line, location))
+            .unwrap_or_default();
+        Err(CliError::ValidationError(format!(
+            "{} validation failed for '{}'{}: {}{}",
+            validator.name(),
+            file_path.display(),
+            section,
+            invalid.message,
+            origin
+        )))
+    }
+}
+
+/// An item of the list a template is rendered once per item of
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    /// Dotted path of the list, e.g. `stack.resources`
+    pub list: String,
+    pub index: usize,
+    /// Number of items in the list
+    pub len: usize,
+    pub value: serde_json::Value,
+    /// Render the content as if inside `{{#each list}}` at this item, as for
+    /// `list.each.ext` templates
+    pub scoped: bool,
+}
+
+impl ListItem {
+    /// Make the item available as `item` and its position as `item_index`,
+    /// and for scoped items enter the item the way `{{#each}}` does:
+    /// `{{name}}` and `{{this}}` refer to the item, `@index`, `@first` and
+    /// `@last` are set, and `../` and `@root` reach the top-level variables
+    ///
+    /// `item` and `item_index` are block params rather than top-level
+    /// variables, so the template data is shared by all items instead of
+    /// copied for each.
+    fn enter(&self, rc: &mut RenderContext) {
+        let mut params = BlockParams::new();
+        // Adding a value can't fail
+        let _ = params.add_value("item", self.value.clone());
+        let _ = params.add_value("item_index", serde_json::json!(self.index));
+        let mut block = BlockContext::new();
+        block.set_block_params(params);
+        rc.push_block(block);
+        if !self.scoped {
+            return;
         }
-        
-        Ok(())
+
+        let mut block = BlockContext::new();
+        *block.base_path_mut() = self.list.split('.').map(str::to_string).chain([self.index.to_string()]).collect();
+        block.set_local_var("index", serde_json::json!(self.index));
+        block.set_local_var("first", serde_json::json!(self.index == 0));
+        block.set_local_var("last", serde_json::json!(self.index + 1 == self.len));
+        rc.push_block(block);
     }
 }
 
+/// Render a template like `Handlebars::render_template`, inside a list item
+/// if given one, checking the render deadline as it writes
+fn render_in_scope(
+    handlebars: &Handlebars,
+    template_content: &str,
+    ctx: &Context,
+    item: Option<&ListItem>,
+) -> Result<String, RenderError> {
+    let template = Template::compile(template_content)?;
+    let mut rc = RenderContext::new(None);
+    if let Some(item) = item {
+        item.enter(&mut rc);
+    }
+    let mut out = DeadlineOutput::default();
+    template.render(handlebars, ctx, &mut rc, &mut out)?;
+    Ok(out.into_string())
+}
+
+/// List rendered once per item by a `list.each.ext` template, e.g. `resources` for `resources.each.tf`
+pub(crate) fn for_each_list(relative_path: &Path) -> Option<String> {
+    relative_path.extension()?;
+    let list = relative_path.file_stem()?.to_str()?.strip_suffix(".each")?;
+    (!list.is_empty() && !list.contains("{{")).then(|| list.to_string())
+}
+
+/// Fail if several items of a template render to the same path, ignoring case
+fn unique_paths(
+    template: &str,
+    outputs: Vec<(PathBuf, Option<ListItem>)>,
+) -> Result<Vec<(PathBuf, Option<ListItem>)>, CliError> {
+    let mut seen = std::collections::HashSet::new();
+    if let Some((path, _)) = outputs.iter().find(|(path, _)| !seen.insert(crate::paths::case_key(path))) {
+        return Err(CliError::OutputConflictError(format!(
+            "Template path '{}' renders to '{}' for more than one item",
+            template,
+            path.display()
+        )));
+    }
+    Ok(outputs)
+}
+
+/// Split a template path at `{{#each list}}` into the part before it, the
+/// list expression and the part after it
+pub(crate) fn split_each(template: &str) -> Option<(&str, &str, &str)> {
+    let start = template.find("{{#each ")?;
+    let end = start + template[start..].find("}}")?;
+    Some((&template[..start], template[start + "{{#each ".len()..end].trim(), &template[end + 2..]))
+}
+
+/// Look up a dotted path such as `stack.resources` in template data
+pub(crate) fn lookup_path<'v>(data: &'v serde_json::Value, path: &str) -> Option<&'v serde_json::Value> {
+    path.split('.').try_fold(data, |value, key| match value {
+        serde_json::Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => value.get(key),
+    })
+}
+
+/// Turn a rendered template path into a relative path, rejecting paths that
+/// would leave the output directory or have empty directory or file names
+fn checked_path(template: &str, rendered: &str) -> Result<PathBuf, CliError> {
+    let parts: Vec<&str> = rendered.split('/').collect();
+    if parts.iter().any(|part| part.trim().is_empty() || *part == "." || *part == ".." || part.contains('\\')) {
+        return Err(CliError::ProcessingError(format!(
+            "Template path '{}' renders to '{}', which is not a valid relative path",
+            template, rendered
+        )));
+    }
+    Ok(parts.iter().collect())
+}
+
 /// Represents a processed template file ready to be written to disk
 /// 
 /// Contains the relative path (preserving directory structure from the template directory)
@@ -599,6 +1056,10 @@ pub struct ProcessedFile {
     
     /// Processed content with all variables substituted
     pub content: String,
+    
+    /// Content with comments marking what each template expression produced,
+    /// set when the processor runs with debug rendering enabled
+    pub annotated: Option<String>,
 }
 
 impl ProcessedFile {
@@ -614,12 +1075,13 @@ impl ProcessedFile {
         ProcessedFile {
             relative_path,
             content,
+            annotated: None,
         }
     }
     
-    /// Get the relative path as a string
+    /// Get the relative path as a string with `/` separators
     pub fn path_str(&self) -> String {
-        self.relative_path.display().to_string()
+        crate::paths::to_slash(&self.relative_path)
     }
     
     /// Get the content length in bytes
@@ -632,6 +1094,7 @@ impl ProcessedFile {
 mod tests {
     use super::*;
     use crate::variable_context::VariableContext;
+    use crate::validators::YamlValidator;
     use serde_json::json;
     use std::fs;
     use tempfile::TempDir;
@@ -1013,7 +1476,7 @@ spec:
         let temp_dir = TempDir::new().unwrap();
         let template_path = temp_dir.path().join("terraform").join("main.tf");
         fs::create_dir_all(template_path.parent().unwrap()).unwrap();
-        fs::write(&template_path, "{{value}}").unwrap();
+        fs::write(&template_path, "value = \"{{value}}\"").unwrap();
         
         let template_file = TemplateFile {
             path: template_path,
@@ -1148,6 +1611,18 @@ spec:
         assert_eq!(result, "fallback");
     }
 
+    #[test]
+    fn test_default_helper_with_number_value() {
+        let mut context = VariableContext::new();
+        context.insert("port".to_string(), json!(8080));
+        
+        let processor = TemplateProcessor::new(&context);
+        let template = r#"{{default port "80"}}"#;
+        
+        let result = processor.process_template(template).unwrap();
+        assert_eq!(result, "8080");
+    }
+
     #[test]
     fn test_default_helper_with_missing_value() {
         let context = VariableContext::new();
@@ -1175,6 +1650,42 @@ spec:
         assert_eq!(result, "replicas = 3");
     }
 
+    #[test]
+    fn test_topo_sorted_helper_orders_dependencies_first() {
+        let mut context = VariableContext::new();
+        context.insert("resources".to_string(), json!([
+            { "name": "web", "resource_type": { "name": "Managed Container Orchestrator", "category": "SHARED" }, "configuration": {} },
+            { "name": "db", "resource_type": { "name": "Monitoring", "category": "BOTH" }, "configuration": {} },
+            { "name": "vpc", "resource_type": { "name": "VPC", "category": "SHARED" }, "configuration": {} }
+        ]));
+
+        let processor = TemplateProcessor::new(&context);
+        let template = "{{#each (topo_sorted resources)}}{{this.name}} {{/each}}";
+
+        let result = processor.process_template(template).unwrap();
+        assert_eq!(result, "db vpc web ");
+    }
+
+    #[test]
+    fn test_spread_helper_is_deterministic() {
+        let mut context = VariableContext::new();
+        context.insert("zones".to_string(), json!(["us-east-1a", "us-east-1b", "us-east-1c"]));
+        context.insert("resources".to_string(), json!([{"name": "a"}, {"name": "b"}, {"name": "c"}, {"name": "d"}]));
+
+        let processor = TemplateProcessor::new(&context);
+        let result = processor
+            .process_template("{{#each resources}}{{spread @index ../zones}} {{/each}}")
+            .unwrap();
+        assert_eq!(result, "us-east-1a us-east-1b us-east-1c us-east-1a ");
+        let literal = processor.process_template(r#"{{spread 4 ["x", "y"]}}"#).unwrap();
+        assert_eq!(literal, "x");
+
+        // Hashed keys must land in the same place in every release
+        let by_name = processor.process_template("{{spread \"orders-db\" zones}}").unwrap();
+        assert_eq!(by_name, "us-east-1a");
+        assert!(processor.process_template("{{spread 1 missing}}").is_err());
+    }
+
     #[test]
     fn test_uppercase_helper() {
         let mut context = VariableContext::new();
@@ -1519,7 +2030,7 @@ spec:
   - port: 80
     targetPort: 8080"#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("service.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("service.yaml"), None);
         assert!(result.is_ok());
     }
 
@@ -1540,7 +2051,7 @@ metadata:
 spec:
   replicas: 3"#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("manifests.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("manifests.yaml"), None);
         assert!(result.is_ok());
     }
 
@@ -1557,7 +2068,7 @@ metadata:
 spec:
   ports: 80"#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("service.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("service.yaml"), None);
         assert!(result.is_err());
         
         if let Err(e) = result {
@@ -1583,7 +2094,7 @@ kind: Deployment
 metadata:
   name: my-deployment"#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("manifests.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("manifests.yaml"), None);
         assert!(result.is_err());
         
         if let Err(e) = result {
@@ -1609,7 +2120,7 @@ metadata:
   name: my-deployment
   [invalid: yaml"#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("manifests.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("manifests.yaml"), None);
         assert!(result.is_err());
         
         if let Err(e) = result {
@@ -1626,7 +2137,7 @@ metadata:
         
         let yaml_content = "";
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("empty.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("empty.yaml"), None);
         assert!(result.is_ok());
     }
 
@@ -1637,7 +2148,7 @@ metadata:
         
         let yaml_content = "   \n\n   \n";
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("whitespace.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("whitespace.yaml"), None);
         assert!(result.is_ok());
     }
 
@@ -1675,7 +2186,7 @@ spec:
             memory: "128Mi"
             cpu: "500m""#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("deployment.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("deployment.yaml"), None);
         assert!(result.is_ok());
     }
 
@@ -1700,7 +2211,7 @@ data:
     #!/bin/bash
     echo "Hello World""#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("configmap.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("configmap.yaml"), None);
         assert!(result.is_ok());
     }
 
@@ -1768,6 +2279,218 @@ metadata:
         }
     }
 
+    #[test]
+    fn test_process_file_debug_render_annotates_output() {
+        let temp_dir = TempDir::new().unwrap();
+        let template_path = temp_dir.path().join("config.json");
+        fs::write(&template_path, "{\n  \"name\": \"{{app_name}}\"\n}\n").unwrap();
+        
+        let template_file = TemplateFile {
+            path: template_path,
+            relative_path: PathBuf::from("config.json"),
+            file_type: crate::template_discovery::TemplateFileType::Json,
+        };
+        
+        let mut context = VariableContext::new();
+        context.insert("app_name".to_string(), json!("my-app"));
+        
+        let processed = TemplateProcessor::new(&context).process_file(&template_file).unwrap();
+        assert!(processed.annotated.is_none());
+        
+        let processed = TemplateProcessor::new(&context)
+            .with_debug_render(true)
+            .process_file(&template_file)
+            .unwrap();
+        assert_eq!(processed.content, "{\n  \"name\": \"my-app\"\n}\n");
+        assert_eq!(
+            processed.annotated.unwrap(),
+            "{\n  \"name\": \"my-app\" // <- {{app_name}} = \"my-app\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_process_file_yaml_error_points_at_template_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let template_path = temp_dir.path().join("service.yaml");
+        let template_content = "kind: Service\n---\nmetadata:\n  name: app\n  labels: {{labels}}\n  team: platform\n";
+        fs::write(&template_path, template_content).unwrap();
+        
+        let template_file = TemplateFile {
+            path: template_path,
+            relative_path: PathBuf::from("service.yaml"),
+            file_type: crate::template_discovery::TemplateFileType::Yaml,
+        };
+        
+        let mut context = VariableContext::new();
+        context.insert("labels".to_string(), json!("a\nb: c: d"));
+        let processor = TemplateProcessor::new(&context);
+        
+        let error_msg = processor.process_file(&template_file).unwrap_err().to_string();
+        assert!(error_msg.contains("(document 2)"));
+        assert!(error_msg.contains("Output line 6 comes from template line 5 (variables: labels)"), "{}", error_msg);
+        assert!(error_msg.contains("5 |   labels: {{labels}}"));
+    }
+
+    #[test]
+    fn test_process_file_terraform_error_points_at_template_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let template_path = temp_dir.path().join("main.tf");
+        fs::write(&template_path, "resource \"aws_s3_bucket\" \"logs\" {\n  bucket = {{bucket}}\n}\n").unwrap();
+        let template_file = TemplateFile {
+            path: template_path,
+            relative_path: PathBuf::from("main.tf"),
+            file_type: crate::template_discovery::TemplateFileType::Terraform,
+        };
+
+        let mut context = VariableContext::new();
+        context.insert("bucket".to_string(), json!(""));
+        let findings = Findings::default();
+        let processor = TemplateProcessor::new(&context).with_findings(findings.clone());
+        let error_msg = processor.process_file(&template_file).unwrap_err().to_string();
+        assert!(error_msg.contains("HCL validation failed for 'main.tf'"), "{}", error_msg);
+        assert!(error_msg.contains("\n      bucket =\n"), "{}", error_msg);
+        assert!(error_msg.contains("comes from template line 2 (variables: bucket)"), "{}", error_msg);
+
+        // Recorded for --report at the template line
+        let findings = findings.to_vec();
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].rule_id.as_str(), findings[0].line), ("validation/hcl", Some(2)));
+        assert_eq!(findings[0].file, template_file.path);
+        assert!(findings[0].message.starts_with("main.tf: invalid expression"), "{}", findings[0].message);
+
+        // Projects whose Terraform templates render fragments can opt out
+        let config = toml::from_str("[tf]\nbuiltin = \"none\"\n").unwrap();
+        let processor = TemplateProcessor::new(&context)
+            .with_validators(crate::validators::ValidatorRegistry::from_config(&config).unwrap());
+        assert!(processor.process_file(&template_file).is_ok());
+    }
+
+    #[test]
+    fn test_process_file_json_schema_error_points_at_template_line() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(
+            temp_dir.path().join("app.schema.json"),
+            r#"{"properties": {"replicas": {"type": "integer", "minimum": 1}}}"#,
+        )
+        .unwrap();
+        let template_path = temp_dir.path().join("app.json");
+        fs::write(
+            &template_path,
+            "{{!-- schema: app.schema.json --}}\n{\n  \"name\": \"{{name}}\",\n  \"replicas\": {{replicas}}\n}\n",
+        )
+        .unwrap();
+        let template_file = TemplateFile {
+            path: template_path,
+            relative_path: PathBuf::from("app.json"),
+            file_type: crate::template_discovery::TemplateFileType::Json,
+        };
+
+        let mut context = VariableContext::new();
+        context.insert("name".to_string(), json!("api"));
+        context.insert("replicas".to_string(), json!(0));
+        let error_msg = TemplateProcessor::new(&context).process_file(&template_file).unwrap_err().to_string();
+        assert!(error_msg.contains("JSON Schema validation failed for 'app.json' (schema "), "{}", error_msg);
+        assert!(error_msg.contains("/replicas: 0 is less than the minimum of 1"), "{}", error_msg);
+        assert!(error_msg.contains("comes from template line 4 (variables: replicas)"), "{}", error_msg);
+
+        context.insert("replicas".to_string(), json!(2));
+        let processed = TemplateProcessor::new(&context).process_file(&template_file).unwrap();
+        assert!(processed.content.starts_with("{\n  \"name\": \"api\""), "{}", processed.content);
+
+        // Syntax is checked without a schema
+        context.insert("replicas".to_string(), json!(""));
+        let error_msg = TemplateProcessor::new(&context).process_file(&template_file).unwrap_err().to_string();
+        assert!(error_msg.contains("JSON validation failed for 'app.json'"), "{}", error_msg);
+    }
+
+    #[test]
+    fn test_process_file_outputs_renders_templated_paths() {
+        let temp_dir = TempDir::new().unwrap();
+        let template_path = temp_dir.path().join("bucket.tf");
+        fs::write(&template_path, "bucket = \"{{item.name}}-{{stack.name}}\" # {{item_index}}\n").unwrap();
+        let template_file = |relative_path: &str| TemplateFile {
+            path: template_path.clone(),
+            relative_path: PathBuf::from(relative_path),
+            file_type: crate::template_discovery::TemplateFileType::Terraform,
+        };
+
+        let mut context = VariableContext::new();
+        context.insert("stack".to_string(), json!({"name": "prod"}));
+        context.insert("resources".to_string(), json!([{"name": "logs"}, {"name": "assets"}]));
+        let processor = TemplateProcessor::new(&context);
+
+        let processed = processor.process_file(&template_file("{{stack.name}}/main.tf")).unwrap();
+        assert_eq!(processed.relative_path, PathBuf::from("prod/main.tf"));
+
+        let outputs = processor
+            .process_file_outputs(&template_file("{{stack.name}}/{{#each resources}}{{name}}-{{@index}}.tf"))
+            .unwrap();
+        let paths: Vec<_> = outputs.iter().map(|file| file.relative_path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::from("prod/logs-0.tf"), PathBuf::from("prod/assets-1.tf")]);
+        assert_eq!(outputs[1].content, "bucket = \"assets-prod\" # 1\n");
+        assert!(processor.process_file(&template_file("{{#each resources}}{{name}}.tf")).is_err());
+
+        let error = processor.process_file_outputs(&template_file("{{#each resources}}same.tf")).unwrap_err();
+        assert!(matches!(error, CliError::OutputConflictError(_)), "{}", error);
+        let error = processor.process_file_outputs(&template_file("{{missing}}/main.tf")).unwrap_err();
+        assert!(error.to_string().contains("not a valid relative path"), "{}", error);
+        let error = processor.process_file_outputs(&template_file("{{#each stack}}{{name}}.tf")).unwrap_err();
+        assert!(error.to_string().contains("'stack', which is not a list"), "{}", error);
+    }
+
+    #[test]
+    fn test_copies_share_template_data() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("prod"));
+        context.insert("resources".to_string(), json!([{"name": "logs", "tags": ["a", "b"]}]));
+        let processor = TemplateProcessor::new(&context);
+        let copy = processor.to_owned_context();
+        assert!(Arc::ptr_eq(&processor.data, &copy.data));
+
+        // Items don't copy the data; `item` is visible inside blocks
+        let item = ListItem { list: "resources".to_string(), index: 0, len: 1, value: json!({"name": "logs"}), scoped: false };
+        let output = copy
+            .render("{{#each resources.0.tags}}{{item.name}}-{{this}}-{{item_index}} {{/each}}{{stack.name}}", |handlebars, content, data| {
+                render_in_scope(handlebars, content, data, Some(&item))
+            })
+            .unwrap();
+        assert_eq!(output, "logs-a-0 logs-b-0 prod");
+    }
+
+    #[test]
+    fn test_each_templates_render_once_per_item_in_scope() {
+        let temp_dir = TempDir::new().unwrap();
+        let tf_path = temp_dir.path().join("resources.each.tf");
+        fs::write(&tf_path, "# {{@index}} of {{../stack.name}}{{#if @last}} (last){{/if}}\nname = \"{{name}}-{{@root.stack.name}}\"\n").unwrap();
+        let yaml_path = temp_dir.path().join("stack.resources.each.yaml");
+        fs::write(&yaml_path, "name: {{this.name}}\nsize: {{configuration.size}}\n").unwrap();
+        let template_file = |path: &Path, relative_path: &str| TemplateFile {
+            path: path.to_path_buf(),
+            relative_path: PathBuf::from(relative_path),
+            file_type: crate::template_discovery::TemplateFileType::Terraform,
+        };
+
+        let mut context = VariableContext::new();
+        let resources = json!([{"name": "postgres-db", "configuration": {"size": "small"}}, {"name": "redis cache"}]);
+        context.insert("stack".to_string(), json!({"name": "prod", "resources": resources}));
+        context.insert("resources".to_string(), resources);
+        let processor = TemplateProcessor::new(&context);
+
+        let outputs = processor.process_file_outputs(&template_file(&tf_path, "{{stack.name}}/resources.each.tf")).unwrap();
+        let paths: Vec<_> = outputs.iter().map(|file| file.path_str()).collect();
+        assert_eq!(paths, vec!["prod/postgres-db.tf", "prod/redis-cache.tf"]);
+        assert_eq!(outputs[0].content, "# 0 of prod\nname = \"postgres-db-prod\"\n");
+        assert_eq!(outputs[1].content, "# 1 of prod (last)\nname = \"redis cache-prod\"\n");
+
+        // Validated output renders through the source-mapped path
+        let outputs = processor.process_file_outputs(&template_file(&yaml_path, "k8s/stack.resources.each.yaml")).unwrap();
+        assert_eq!(outputs[0].relative_path, PathBuf::from("k8s/postgres-db.yaml"));
+        assert_eq!(outputs[0].content, "name: postgres-db\nsize: small\n");
+
+        let error = processor.process_file_outputs(&template_file(&tf_path, "stack.each.tf")).unwrap_err();
+        assert!(error.to_string().contains("loops over 'stack', which is not a list"), "{}", error);
+    }
+
     #[test]
     fn test_process_file_yaml_multi_document_valid() {
         let temp_dir = TempDir::new().unwrap();
@@ -1815,7 +2538,7 @@ spec:
     fn test_process_file_terraform_no_validation() {
         let temp_dir = TempDir::new().unwrap();
         let template_path = temp_dir.path().join("main.tf");
-        // Terraform file - no YAML validation should occur
+        // Terraform file - parsed as HCL, no YAML validation should occur
         let template_content = r#"resource "aws_instance" "{{name}}" {
   ami = "{{ami}}"
 }"#;
@@ -1850,11 +2573,11 @@ labels:
     app: test
   bad indentation here"#;
         
-        let result = processor.validate_yaml(yaml_content, &PathBuf::from("service.yaml"));
+        let result = processor.validate_output(&YamlValidator, yaml_content, &PathBuf::from("service.yaml"), None);
         assert!(result.is_err());
         
         if let Err(e) = result {
-            let error_msg = e.to_string();
+            let error_msg = e.report(true);
             // Check that error message contains helpful information
             assert!(error_msg.contains("YAML validation failed"));
             assert!(error_msg.contains("service.yaml"));
@@ -1880,6 +2603,29 @@ labels:
         assert!(similar_vars.contains("blueprint.name"));
     }
 
+    #[test]
+    fn test_strict_variables_fail_on_undefined_reference() {
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("my-blueprint"));
+
+        let template = "# {{blueprint.name}}\nbucket = \"{{blueprnt.name}}-logs\"\n";
+        assert_eq!(TemplateProcessor::new(&context).process_template(template).unwrap(), "# my-blueprint\nbucket = \"-logs\"\n");
+
+        let processor = TemplateProcessor::new(&context).with_strict_variables(true);
+        match processor.process_template(template).unwrap_err() {
+            CliError::VariableNotFoundError { variable, suggestion } => {
+                assert_eq!(variable, "blueprnt.name");
+                assert!(suggestion.contains("Template line 2:\nbucket = \"{{blueprnt.name}}-logs\""), "{}", suggestion);
+                assert!(suggestion.contains("  - blueprint.name"), "{}", suggestion);
+            }
+            other => panic!("expected VariableNotFoundError, got {:?}", other),
+        }
+
+        // Helper arguments may still be undefined
+        let guarded = "{{default region \"us-east-1\"}}{{#if tags}}tagged{{/if}}";
+        assert_eq!(processor.process_template(guarded).unwrap(), "us-east-1");
+    }
+
     #[test]
     fn test_error_handling_levenshtein_distance() {
         let context = VariableContext::new();
@@ -2028,19 +2774,18 @@ labels:
         let context = VariableContext::new();
         let processor = TemplateProcessor::new(&context);
         
-        // Test that processing errors include helpful troubleshooting tips
+        // Test that processing errors carry troubleshooting tips from the catalog
         let template = "{{invalid syntax here";
         let result = processor.process_template(template);
         
         assert!(result.is_err());
         if let Err(e) = result {
-            let error_msg = e.to_string();
-            // Should contain troubleshooting tips
+            let report = e.report(true);
             assert!(
-                error_msg.contains("Troubleshooting tips") ||
-                error_msg.contains("template syntax") ||
-                error_msg.contains("Common template syntax issues")
+                report.contains("list-variables") ||
+                report.contains("Common template syntax issues")
             );
+            assert!(!e.report(false).contains("Common template syntax issues"));
         }
     }
 }
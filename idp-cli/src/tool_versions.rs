@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::Serialize;
+
+/// Tool that consumes generated files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    Terraform,
+    Tofu,
+    Kubectl,
+    Helm,
+}
+
+impl Tool {
+    /// Executable name and arguments that print the version
+    fn version_command(&self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            Tool::Terraform => ("terraform", &["version"]),
+            Tool::Tofu => ("tofu", &["version"]),
+            Tool::Kubectl => ("kubectl", &["version", "--client"]),
+            Tool::Helm => ("helm", &["version", "--short"]),
+        }
+    }
+}
+
+impl fmt::Display for Tool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.version_command().0)
+    }
+}
+
+/// `major.minor.patch` version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version(pub u64, pub u64, pub u64);
+
+impl Version {
+    /// Find the first version number in a tool's output, e.g. `Terraform v1.5.7`
+    pub fn find_in(text: &str) -> Option<Self> {
+        text.split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .find_map(|token| {
+                let mut parts = token.split('.').filter(|p| !p.is_empty());
+                let major = parts.next()?.parse().ok()?;
+                let minor = parts.next()?.parse().ok()?;
+                let patch = parts.next().and_then(|p| p.parse().ok()).unwrap_or(0);
+                Some(Version(major, minor, patch))
+            })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+/// Generated syntax that needs a minimum tool version
+struct Rule {
+    feature: &'static str,
+    extensions: &'static [&'static str],
+    matches: fn(&str) -> bool,
+    minimum: &'static [(Tool, Version)],
+}
+
+const TERRAFORM: &[&str] = &["tf"];
+const MANIFESTS: &[&str] = &["yaml", "yml"];
+
+const RULES: &[Rule] = &[
+    Rule {
+        feature: "moved block",
+        extensions: TERRAFORM,
+        matches: |content| has_block(content, "moved"),
+        minimum: &[(Tool::Terraform, Version(1, 1, 0))],
+    },
+    Rule {
+        feature: "optional() object attribute",
+        extensions: TERRAFORM,
+        matches: |content| content.contains("optional("),
+        minimum: &[(Tool::Terraform, Version(1, 3, 0))],
+    },
+    Rule {
+        feature: "terraform_data resource",
+        extensions: TERRAFORM,
+        matches: |content| content.contains("resource \"terraform_data\""),
+        minimum: &[(Tool::Terraform, Version(1, 4, 0))],
+    },
+    Rule {
+        feature: "import block",
+        extensions: TERRAFORM,
+        matches: |content| has_block(content, "import"),
+        minimum: &[(Tool::Terraform, Version(1, 5, 0)), (Tool::Tofu, Version(1, 6, 0))],
+    },
+    Rule {
+        feature: "check block",
+        extensions: TERRAFORM,
+        matches: |content| has_block(content, "check"),
+        minimum: &[(Tool::Terraform, Version(1, 5, 0)), (Tool::Tofu, Version(1, 6, 0))],
+    },
+    Rule {
+        feature: "removed block",
+        extensions: TERRAFORM,
+        matches: |content| has_block(content, "removed"),
+        minimum: &[(Tool::Terraform, Version(1, 7, 0)), (Tool::Tofu, Version(1, 7, 0))],
+    },
+    Rule {
+        feature: "provider-defined function",
+        extensions: TERRAFORM,
+        matches: |content| content.contains("provider::"),
+        minimum: &[(Tool::Terraform, Version(1, 8, 0)), (Tool::Tofu, Version(1, 7, 0))],
+    },
+    Rule {
+        feature: "networking.k8s.io/v1 API",
+        extensions: MANIFESTS,
+        matches: |content| has_api_version(content, "networking.k8s.io/v1"),
+        minimum: &[(Tool::Kubectl, Version(1, 19, 0))],
+    },
+    Rule {
+        feature: "policy/v1 API",
+        extensions: MANIFESTS,
+        matches: |content| has_api_version(content, "policy/v1"),
+        minimum: &[(Tool::Kubectl, Version(1, 21, 0))],
+    },
+    Rule {
+        feature: "autoscaling/v2 API",
+        extensions: MANIFESTS,
+        matches: |content| has_api_version(content, "autoscaling/v2"),
+        minimum: &[(Tool::Kubectl, Version(1, 23, 0))],
+    },
+    Rule {
+        feature: "Chart apiVersion v2",
+        extensions: MANIFESTS,
+        matches: |content| has_api_version(content, "v2") && content.contains("name:"),
+        minimum: &[(Tool::Helm, Version(3, 0, 0))],
+    },
+];
+
+/// Top-level HCL block such as `import {` or `check "health" {`
+fn has_block(content: &str, keyword: &str) -> bool {
+    content.lines().any(|line| {
+        line.strip_prefix(keyword)
+            .is_some_and(|rest| rest.starts_with([' ', '\t', '{']) && rest.trim_end().ends_with('{'))
+    })
+}
+
+fn has_api_version(content: &str, api_version: &str) -> bool {
+    content
+        .lines()
+        .any(|line| line.trim().strip_prefix("apiVersion:").is_some_and(|v| v.trim().trim_matches('"') == api_version))
+}
+
+/// Strictest version of one tool needed by the generated files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    pub minimum: Version,
+    /// What needs the version, e.g. "import block in main.tf"
+    pub reason: String,
+}
+
+/// Collect the tool versions needed by the generated files
+///
+/// `Chart.yaml` is only checked against Helm, and other YAML files only
+/// against kubectl.
+pub fn scan_requirements(files: &[(PathBuf, String)]) -> BTreeMap<Tool, Requirement> {
+    let mut requirements: BTreeMap<Tool, Requirement> = BTreeMap::new();
+    for (path, content) in files {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+        let is_chart = path.file_name().is_some_and(|name| name == "Chart.yaml");
+        for rule in RULES.iter().filter(|rule| rule.extensions.contains(&extension)) {
+            if !(rule.matches)(content) {
+                continue;
+            }
+            for &(tool, minimum) in rule.minimum {
+                if (tool == Tool::Helm) != is_chart {
+                    continue;
+                }
+                let stricter = requirements.get(&tool).is_none_or(|current| minimum > current.minimum);
+                if stricter {
+                    let reason = format!("{} in {}", rule.feature, display_name(path));
+                    requirements.insert(tool, Requirement { minimum, reason });
+                }
+            }
+        }
+    }
+    requirements
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// Result of comparing an installed tool with what the generated files need
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolCheck {
+    pub tool: Tool,
+    /// Installed version, None if the tool was not found
+    pub installed: Option<String>,
+    pub required: String,
+    pub reason: String,
+    pub status: CheckStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Compatible,
+    TooOld,
+    NotInstalled,
+}
+
+impl ToolCheck {
+    /// Warning for a tool that is too old
+    pub fn warning(&self) -> Option<String> {
+        match (self.status, &self.installed) {
+            (CheckStatus::TooOld, Some(installed)) => Some(format!(
+                "{} needs {} >= {}, but {} {} is installed",
+                self.reason, self.tool, self.required, self.tool, installed
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Compare requirements with installed versions
+///
+/// `detect` returns the installed version of a tool, see [`detect_version`].
+pub fn check_requirements(
+    requirements: &BTreeMap<Tool, Requirement>,
+    mut detect: impl FnMut(Tool) -> Option<Version>,
+) -> Vec<ToolCheck> {
+    requirements
+        .iter()
+        .map(|(&tool, requirement)| {
+            let installed = detect(tool);
+            let status = match installed {
+                None => CheckStatus::NotInstalled,
+                Some(version) if version < requirement.minimum => CheckStatus::TooOld,
+                Some(_) => CheckStatus::Compatible,
+            };
+            ToolCheck {
+                tool,
+                installed: installed.map(|v| v.to_string()),
+                required: requirement.minimum.to_string(),
+                reason: requirement.reason.clone(),
+                status,
+            }
+        })
+        .collect()
+}
+
+/// Installed version of a tool, found by running its version command
+pub fn detect_version(tool: Tool) -> Option<Version> {
+    let (program, args) = tool.version_command();
+    let output = Command::new(program)
+        .args(args)
+        // Keep terraform from checking for updates over the network
+        .env("CHECKPOINT_DISABLE", "1")
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Version::find_in(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, content: &str) -> (PathBuf, String) {
+        (PathBuf::from("out").join(name), content.to_string())
+    }
+
+    #[test]
+    fn test_find_version_in_tool_output() {
+        assert_eq!(Version::find_in("Terraform v1.5.7\non linux_amd64"), Some(Version(1, 5, 7)));
+        assert_eq!(Version::find_in("Client Version: v1.29.1\nKustomize Version: v5.0.4"), Some(Version(1, 29, 1)));
+        assert_eq!(Version::find_in("v3.14+g3fc9f4b"), Some(Version(3, 14, 0)));
+        assert_eq!(Version::find_in("command not found"), None);
+    }
+
+    #[test]
+    fn test_scan_keeps_strictest_requirement_per_tool() {
+        let requirements = scan_requirements(&[
+            file("moves.tf", "moved {\n  from = a.b\n  to = a.c\n}\n"),
+            file("import.tf", "import {\n  to = aws_s3_bucket.b\n  id = \"b\"\n}\n"),
+            file("deploy.yaml", "apiVersion: policy/v1\nkind: PodDisruptionBudget\n"),
+            file("main.tf", "  # import { is only a comment when indented\n"),
+        ]);
+        assert_eq!(requirements[&Tool::Terraform].minimum, Version(1, 5, 0));
+        assert_eq!(requirements[&Tool::Terraform].reason, "import block in import.tf");
+        assert_eq!(requirements[&Tool::Tofu].minimum, Version(1, 6, 0));
+        assert_eq!(requirements[&Tool::Kubectl].minimum, Version(1, 21, 0));
+        assert!(!requirements.contains_key(&Tool::Helm));
+    }
+
+    #[test]
+    fn test_check_requirements_warns_only_for_old_tools() {
+        let requirements = scan_requirements(&[file("import.tf", "import {\n}\n")]);
+        let checks = check_requirements(&requirements, |tool| match tool {
+            Tool::Terraform => Some(Version(1, 4, 6)),
+            _ => None,
+        });
+
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].status, CheckStatus::TooOld);
+        assert_eq!(
+            checks[0].warning().unwrap(),
+            "import block in import.tf needs terraform >= 1.5.0, but terraform 1.4.6 is installed"
+        );
+        assert_eq!(checks[1].status, CheckStatus::NotInstalled);
+        assert!(checks[1].warning().is_none());
+    }
+}
@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::CliError;
+
+/// Why rendered output failed validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invalid {
+    /// Output line of the problem (1-based), if the validator knows it
+    pub line: Option<usize>,
+    /// Part of the file the problem is in, e.g. `document 2` of a multi-document YAML file
+    pub section: Option<String>,
+    pub message: String,
+}
+
+impl Invalid {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self { line: None, section: None, message: message.into() }
+    }
+
+    pub fn at_line(line: usize, message: impl Into<String>) -> Self {
+        Self { line: Some(line), section: None, message: message.into() }
+    }
+}
+
+/// Checks the rendered output of one file type before it is written
+pub trait Validator: Send + Sync {
+    /// Name used in error messages, e.g. `YAML`
+    fn name(&self) -> &str;
+
+    /// Check rendered content; `path` is the output path relative to the output directory
+    fn validate(&self, content: &str, path: &Path) -> Result<(), Invalid>;
+}
+
+/// YAML, including multi-document files separated by `---`
+pub struct YamlValidator;
+
+impl Validator for YamlValidator {
+    fn name(&self) -> &str {
+        "YAML"
+    }
+
+    fn validate(&self, content: &str, _path: &Path) -> Result<(), Invalid> {
+        // Each document is kept with the output line it starts on
+        let mut documents: Vec<(usize, &str)> = Vec::new();
+        let mut start_line = 1;
+        for document in content.split("\n---\n") {
+            if !document.trim().is_empty() {
+                documents.push((start_line, document));
+            }
+            start_line += document.matches('\n').count() + 2;
+        }
+
+        let count = documents.len();
+        for (index, (start_line, document)) in documents.into_iter().enumerate() {
+            if let Err(e) = serde_yaml::from_str::<serde_yaml::Value>(document) {
+                return Err(Invalid {
+                    line: e.location().map(|location| start_line + location.line() - 1),
+                    section: (count > 1).then(|| format!("document {}", index + 1)),
+                    message: e.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct JsonValidator;
+
+impl Validator for JsonValidator {
+    fn name(&self) -> &str {
+        "JSON"
+    }
+
+    fn validate(&self, content: &str, _path: &Path) -> Result<(), Invalid> {
+        if content.trim().is_empty() {
+            return Ok(());
+        }
+        serde_json::from_str::<serde_json::Value>(content)
+            .map(|_| ())
+            .map_err(|e| Invalid::at_line(e.line(), e.to_string()))
+    }
+}
+
+/// Terraform configuration (HCL syntax)
+pub struct HclValidator;
+
+impl Validator for HclValidator {
+    fn name(&self) -> &str {
+        "HCL"
+    }
+
+    fn validate(&self, content: &str, _path: &Path) -> Result<(), Invalid> {
+        match hcl::parse(content) {
+            Ok(_) => Ok(()),
+            // Show the offending line, since HCL messages only name the expected token
+            Err(hcl::Error::Parse(e)) => Err(Invalid::at_line(
+                e.location().line(),
+                format!("{} at column {}\n\n    {}", e.message(), e.location().column(), e.line().trim_end()),
+            )),
+            Err(e) => Err(Invalid::new(e.to_string())),
+        }
+    }
+}
+
+/// A JSON Schema that rendered `.json` files must conform to
+pub struct JsonSchemaValidator {
+    schema: jsonschema::Validator,
+    /// Schema file, named in errors
+    path: PathBuf,
+}
+
+impl JsonSchemaValidator {
+    pub fn load(path: &Path) -> Result<Self, CliError> {
+        let schema: serde_json::Value = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+            .map_err(|e| CliError::ConfigurationError(format!("Failed to read schema {}: {}", path.display(), e)))?;
+        let schema = jsonschema::validator_for(&schema)
+            .map_err(|e| CliError::ConfigurationError(format!("Invalid schema {}: {}", path.display(), e)))?;
+        Ok(Self { schema, path: path.to_path_buf() })
+    }
+}
+
+impl Validator for JsonSchemaValidator {
+    fn name(&self) -> &str {
+        "JSON Schema"
+    }
+
+    fn validate(&self, content: &str, _path: &Path) -> Result<(), Invalid> {
+        // Syntax errors are reported by the JSON validator
+        let Ok(instance) = serde_json::from_str::<serde_json::Value>(content) else {
+            return Ok(());
+        };
+        let errors: Vec<_> = self.schema.iter_errors(&instance).collect();
+        let Some(first) = errors.first() else {
+            return Ok(());
+        };
+        let messages: Vec<String> = errors
+            .iter()
+            .map(|error| match error.instance_path().as_str() {
+                "" => error.to_string(),
+                pointer => format!("{}: {}", pointer, error),
+            })
+            .collect();
+        Err(Invalid {
+            line: pointer_line(content, first.instance_path().as_str()),
+            section: Some(format!("schema {}", self.path.display())),
+            message: messages.join("; "),
+        })
+    }
+}
+
+/// Output line of the value at a JSON pointer, found by its key when the key
+/// appears once in the output
+fn pointer_line(content: &str, pointer: &str) -> Option<usize> {
+    let key = pointer.rsplit('/').find(|segment| segment.parse::<usize>().is_err())?;
+    let key = serde_json::Value::from(key.replace("~1", "/").replace("~0", "~")).to_string();
+    let mut lines = content.lines().enumerate().filter(|(_, line)| {
+        line.match_indices(&key).any(|(start, _)| line[start + key.len()..].trim_start().starts_with(':'))
+    });
+    match (lines.next(), lines.next()) {
+        (Some((index, _)), None) => Some(index + 1),
+        _ => None,
+    }
+}
+
+/// JSON Schemas of rendered `.json` files
+///
+/// A template names its schema in a leading comment, relative to the
+/// template; otherwise the schema directory is searched for the output path
+/// with `.schema.json` in place of `.json`, then for the file name alone.
+///
+/// ```handlebars
+/// {{!-- schema: ../schemas/app-config.schema.json --}}
+/// { "name": "{{stack.name}}", "replicas": {{replicas}} }
+/// ```
+#[derive(Clone, Default)]
+pub struct JsonSchemas {
+    dir: Option<PathBuf>,
+    /// Schemas compiled so far, by path
+    loaded: Arc<Mutex<HashMap<PathBuf, Arc<JsonSchemaValidator>>>>,
+}
+
+impl JsonSchemas {
+    /// Schema a rendered file must conform to, if it is JSON and has one
+    pub fn for_output(
+        &self,
+        template_path: &Path,
+        template_content: &str,
+        output: &Path,
+    ) -> Result<Option<Arc<JsonSchemaValidator>>, CliError> {
+        if !output.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+            return Ok(None);
+        }
+        let path = if let Some(reference) = schema_reference(template_content) {
+            let path = template_path.parent().unwrap_or(Path::new("")).join(reference);
+            if !path.is_file() {
+                return Err(CliError::ConfigurationError(format!(
+                    "Schema '{}' named in template '{}' not found",
+                    reference,
+                    template_path.display()
+                )));
+            }
+            path
+        } else if let Some(dir) = &self.dir {
+            let file_stem = output.file_stem().unwrap_or_default().to_string_lossy();
+            let candidates =
+                [dir.join(output.with_extension("schema.json")), dir.join(format!("{}.schema.json", file_stem))];
+            match candidates.into_iter().find(|path| path.is_file()) {
+                Some(path) => path,
+                None => return Ok(None),
+            }
+        } else {
+            return Ok(None);
+        };
+
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(schema) = loaded.get(&path) {
+            return Ok(Some(Arc::clone(schema)));
+        }
+        let schema = Arc::new(JsonSchemaValidator::load(&path)?);
+        loaded.insert(path, Arc::clone(&schema));
+        Ok(Some(schema))
+    }
+}
+
+/// Schema named in a template's leading `{{!-- schema: path --}}` comment
+pub fn schema_reference(template_content: &str) -> Option<&str> {
+    let rest = template_content.trim_start();
+    let comment = match rest.strip_prefix("{{!--") {
+        Some(rest) => &rest[..rest.find("--}}")?],
+        None => {
+            let rest = rest.strip_prefix("{{!")?;
+            &rest[..rest.find("}}")?]
+        }
+    };
+    comment
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("schema:"))
+        .map(str::trim)
+        .filter(|reference| !reference.is_empty())
+}
+
+/// An external program, e.g. `shellcheck` or `hadolint`
+///
+/// The rendered content is piped to the program's standard input and `{file}`
+/// in its arguments is replaced with the output path. A non-zero exit status
+/// fails validation with the program's output.
+pub struct CommandValidator {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandValidator {
+    pub fn new(command: &[String]) -> Result<Self, CliError> {
+        let (program, args) = command
+            .split_first()
+            .filter(|(program, _)| !program.is_empty())
+            .ok_or_else(|| CliError::ConfigurationError("Validator command must not be empty".to_string()))?;
+        Ok(Self { program: program.clone(), args: args.to_vec() })
+    }
+}
+
+impl Validator for CommandValidator {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn validate(&self, content: &str, path: &Path) -> Result<(), Invalid> {
+        let file = path.display().to_string();
+        let mut child = Command::new(&self.program)
+            .args(self.args.iter().map(|arg| arg.replace("{file}", &file)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Invalid::new(format!("failed to run '{}': {}", self.program, e)))?;
+
+        // Write on another thread so a program that prints before reading all input can't block us
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let output = std::thread::scope(|scope| {
+            scope.spawn(move || {
+                // Programs may exit without reading everything; their exit status says what happened
+                let _ = stdin.write_all(content.as_bytes());
+            });
+            child.wait_with_output()
+        })
+        .map_err(|e| Invalid::new(format!("failed to run '{}': {}", self.program, e)))?;
+
+        if output.status.success() {
+            return Ok(());
+        }
+        let report = [output.stdout, output.stderr]
+            .iter()
+            .map(|stream| String::from_utf8_lossy(stream).trim().to_string())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        Err(Invalid::new(if report.is_empty() { format!("exited with {}", output.status) } else { report }))
+    }
+}
+
+/// Validators that need no external program
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuiltinValidator {
+    Yaml,
+    Json,
+    Hcl,
+    /// No validation, e.g. for `.tf` templates that render fragments
+    None,
+}
+
+impl BuiltinValidator {
+    fn validator(self) -> Option<Arc<dyn Validator>> {
+        match self {
+            BuiltinValidator::Yaml => Some(Arc::new(YamlValidator)),
+            BuiltinValidator::Json => Some(Arc::new(JsonValidator)),
+            BuiltinValidator::Hcl => Some(Arc::new(HclValidator)),
+            BuiltinValidator::None => None,
+        }
+    }
+}
+
+/// A validator configured for a project: a built-in one or an external program
+///
+/// ```toml
+/// [validators.json]
+/// builtin = "json"
+///
+/// [validators.sh]
+/// command = ["shellcheck", "-"]
+///
+/// [validators.Dockerfile]
+/// command = ["hadolint", "-"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ValidatorConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builtin: Option<BuiltinValidator>,
+    /// Program and arguments
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub command: Vec<String>,
+}
+
+/// Validators keyed by output file extension, or by file name for files
+/// without one such as `Dockerfile`, and the JSON Schemas of JSON output
+#[derive(Clone)]
+pub struct ValidatorRegistry {
+    validators: BTreeMap<String, Arc<dyn Validator>>,
+    schemas: JsonSchemas,
+}
+
+/// YAML, Terraform and JSON output is validated unless configured otherwise
+impl Default for ValidatorRegistry {
+    fn default() -> Self {
+        let mut registry = Self { validators: BTreeMap::new(), schemas: JsonSchemas::default() };
+        registry
+            .register("yaml", Arc::new(YamlValidator))
+            .register("yml", Arc::new(YamlValidator))
+            .register("tf", Arc::new(HclValidator))
+            .register("json", Arc::new(JsonValidator));
+        registry
+    }
+}
+
+impl fmt::Debug for ValidatorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.validators.iter().map(|(key, validator)| (key, validator.name()))).finish()
+    }
+}
+
+impl ValidatorRegistry {
+    /// The default validators with the configured ones added; configured
+    /// validators replace default ones for the same extension, and
+    /// `builtin = "none"` removes them
+    pub fn from_config(config: &BTreeMap<String, ValidatorConfig>) -> Result<Self, CliError> {
+        let mut registry = Self::default();
+        for (key, validator) in config {
+            let validator: Arc<dyn Validator> = match (validator.builtin, validator.command.is_empty()) {
+                (Some(builtin), true) => match builtin.validator() {
+                    Some(validator) => validator,
+                    None => {
+                        registry.validators.remove(&key.to_lowercase());
+                        continue;
+                    }
+                },
+                (None, false) => Arc::new(CommandValidator::new(&validator.command).map_err(|e| {
+                    CliError::ConfigurationError(format!("Validator '{}': {}", key, e))
+                })?),
+                _ => {
+                    return Err(CliError::ConfigurationError(format!(
+                        "Validator '{}' needs exactly one of 'builtin' or 'command'",
+                        key
+                    )))
+                }
+            };
+            registry.register(key, validator);
+        }
+        Ok(registry)
+    }
+
+    /// Register a validator for an extension (without the dot) or a file name
+    pub fn register(&mut self, key: &str, validator: Arc<dyn Validator>) -> &mut Self {
+        self.validators.insert(key.to_lowercase(), validator);
+        self
+    }
+
+    /// Validator for an output path: by extension, then by file name
+    pub fn for_path(&self, path: &Path) -> Option<&dyn Validator> {
+        let key = |part: Option<&std::ffi::OsStr>| part.map(|part| part.to_string_lossy().to_lowercase());
+        key(path.extension())
+            .and_then(|extension| self.validators.get(&extension))
+            .or_else(|| key(path.file_name()).and_then(|name| self.validators.get(&name)))
+            .map(|validator| validator.as_ref())
+    }
+
+    /// Look up schemas of JSON output without one named in its template in a directory
+    pub fn with_schema_dir(mut self, dir: &Path) -> Result<Self, CliError> {
+        if !dir.is_dir() {
+            return Err(CliError::ConfigurationError(format!("Schema directory {} not found", dir.display())));
+        }
+        self.schemas.dir = Some(dir.to_path_buf());
+        Ok(self)
+    }
+
+    /// JSON Schema of a rendered file (see [`JsonSchemas`])
+    pub fn schema_for(
+        &self,
+        template_path: &Path,
+        template_content: &str,
+        output: &Path,
+    ) -> Result<Option<Arc<JsonSchemaValidator>>, CliError> {
+        self.schemas.for_output(template_path, template_content, output)
+    }
+
+    /// Extensions and file names with a validator
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.validators.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_validators_by_extension() {
+        let registry = ValidatorRegistry::default();
+        let validate = |path: &str, content: &str| {
+            registry.for_path(Path::new(path)).unwrap().validate(content, Path::new(path))
+        };
+
+        assert!(validate("main.tf", "resource \"aws_s3_bucket\" \"logs\" {\n  bucket = \"logs\"\n}\n").is_ok());
+        let error = validate("main.tf", "resource \"aws_s3_bucket\" \"logs\" {\n  bucket = \n}\n").unwrap_err();
+        assert_eq!(error.line, Some(2));
+        assert!(error.message.ends_with("\n\n      bucket ="), "{}", error.message);
+        assert!(validate("config.JSON", "{\"a\": 1}").is_ok());
+        assert_eq!(validate("config.json", "{\"a\": 1,\n}").unwrap_err().line, Some(2));
+        let error = validate("k8s/all.yml", "a: 1\n---\nb: [\n").unwrap_err();
+        assert_eq!(error.section.as_deref(), Some("document 2"));
+        assert!(registry.for_path(Path::new("run.sh")).is_none());
+    }
+
+    #[test]
+    fn test_configured_command_validators() {
+        let config: BTreeMap<String, ValidatorConfig> = toml::from_str(
+            "[sh]\ncommand = [\"sh\", \"-c\", \"grep -q '^#!' || { echo 'missing shebang in {file}' >&2; exit 1; }\"]\n\
+             [Dockerfile]\ncommand = [\"true\"]\n",
+        )
+        .unwrap();
+        let registry = ValidatorRegistry::from_config(&config).unwrap();
+
+        let validator = registry.for_path(Path::new("scripts/run.sh")).unwrap();
+        assert_eq!(validator.name(), "sh");
+        assert!(validator.validate("#!/bin/sh\necho hi\n", Path::new("scripts/run.sh")).is_ok());
+        let error = validator.validate("echo hi\n", Path::new("scripts/run.sh")).unwrap_err();
+        assert_eq!(error.message, "missing shebang in scripts/run.sh");
+        assert!(registry.for_path(Path::new("app/Dockerfile")).is_some());
+        assert!(registry.for_path(Path::new("values.yaml")).is_some());
+
+        let config: BTreeMap<String, ValidatorConfig> = toml::from_str("[tf]\nbuiltin = \"none\"\n").unwrap();
+        assert!(ValidatorRegistry::from_config(&config).unwrap().for_path(Path::new("main.tf")).is_none());
+
+        let config = BTreeMap::from([("xml".to_string(), ValidatorConfig::default())]);
+        assert!(ValidatorRegistry::from_config(&config).unwrap_err().to_string().contains("Validator 'xml'"));
+    }
+
+    #[test]
+    fn test_json_schemas_from_front_matter_and_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let schema = r#"{"type": "object", "required": ["name"], "properties": {"replicas": {"type": "integer"}}}"#;
+        std::fs::create_dir_all(dir.path().join("schemas/config")).unwrap();
+        std::fs::write(dir.path().join("schemas/config/app.schema.json"), schema).unwrap();
+        std::fs::write(dir.path().join("schemas/other.schema.json"), "{\"type\": \"array\"}").unwrap();
+        std::fs::create_dir(dir.path().join("templates")).unwrap();
+        let template = dir.path().join("templates/app.json");
+
+        let registry = ValidatorRegistry::default();
+        assert!(registry.schema_for(&template, "{}", Path::new("config/app.json")).unwrap().is_none());
+        let registry = registry.with_schema_dir(&dir.path().join("schemas")).unwrap();
+        let validator = registry.schema_for(&template, "{}", Path::new("config/app.json")).unwrap().unwrap();
+        let error = validator
+            .validate("{\n  \"name\": \"api\",\n  \"replicas\": \"3\"\n}", Path::new("config/app.json"))
+            .unwrap_err();
+        assert_eq!(error.line, Some(3));
+        assert_eq!(error.message, "/replicas: \"3\" is not of type \"integer\"");
+        assert!(error.section.unwrap().ends_with("app.schema.json"));
+        assert!(validator.validate("{\"name\": \"api\", \"replicas\": 3}", Path::new("config/app.json")).is_ok());
+        // Syntax errors are left to the JSON validator
+        assert!(validator.validate("{", Path::new("config/app.json")).is_ok());
+
+        // By file name when there's no schema for the output path; not for other file types
+        assert!(registry.schema_for(&template, "", Path::new("other.json")).unwrap().is_some());
+        assert!(registry.schema_for(&template, "", Path::new("app.yaml")).unwrap().is_none());
+
+        // A schema named in the template wins, relative to the template
+        let content = "{{!-- schema: ../schemas/other.schema.json --}}\n[]";
+        assert_eq!(schema_reference(content), Some("../schemas/other.schema.json"));
+        let validator = registry.schema_for(&template, content, Path::new("config/app.json")).unwrap().unwrap();
+        assert!(validator.validate("[]", Path::new("config/app.json")).is_ok());
+        let error = registry.schema_for(&template, "{{! schema: missing.json }}", Path::new("app.json")).err().unwrap();
+        assert!(error.to_string().contains("Schema 'missing.json' named in template"), "{}", error);
+        assert_eq!(schema_reference("{{!-- generated --}}\n{}"), None);
+    }
+}
@@ -0,0 +1,603 @@
+use std::path::PathBuf;
+
+use handlebars::template::{BlockParam, HelperTemplate, Parameter, Template, TemplateElement};
+use handlebars::Path as HbsPath;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::cli::DataSource;
+use crate::error::CliError;
+use crate::source_map::{byte_offset, tag_at};
+use crate::template_discovery::TemplateFile;
+use crate::variable_context::{VariableContext, VAR_FLAG_SOURCE};
+
+/// How a template reference relates to the traced variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceKind {
+    /// The reference is the variable itself
+    Direct,
+    /// The reference is an enclosing object or array of the variable
+    Parent,
+    /// The reference is a field inside the variable
+    Child,
+}
+
+/// Place in a template where the traced variable is used
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplateReference {
+    /// Template path relative to the template directory
+    pub file: PathBuf,
+    pub line: usize,
+    /// Tag containing the reference, e.g. `{{resources.0.name}}`
+    pub expression: String,
+    pub kind: ReferenceKind,
+    /// Enclosing `{{#each}}`/`{{#with}}` the reference is relative to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+}
+
+/// Where a variable comes from, what value it ends up with and where it is used
+#[derive(Debug, Clone, Serialize)]
+pub struct VariableTrace {
+    pub variable: String,
+    /// Part of the blueprint/stack (or custom variables) the variable comes from
+    pub origin: String,
+    /// Value built from the blueprint or stack
+    pub api_value: Option<Value>,
+    /// Variables file or `--var` that changed the API value, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub set_by: Option<String>,
+    /// Value after merging the custom variables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged_value: Option<Value>,
+    /// Value templates see when referencing the variable's path
+    pub template_value: Option<Value>,
+    pub references: Vec<TemplateReference>,
+}
+
+impl VariableTrace {
+    /// Trace a variable through context building, variable merging and templates
+    ///
+    /// `merged` is the context after merging variables files and `--var`
+    /// assignments, if any were given.
+    pub fn build(
+        variable: &str,
+        data_source: DataSource,
+        base: &VariableContext,
+        merged: Option<&VariableContext>,
+        templates: &[TemplateFile],
+    ) -> Result<Self, CliError> {
+        let target = segments(variable);
+        let api_value = base.get(variable).cloned();
+        let final_context = merged.unwrap_or(base);
+        // Values resolved from secret references are never shown
+        let mask = |value: Value| {
+            if final_context.is_secret(&value) {
+                Value::String(crate::debug_bundle::REDACTED.to_string())
+            } else {
+                value
+            }
+        };
+        let merged_value = merged.map(|context| mask(context.get(variable).cloned().unwrap_or(Value::Null)));
+
+        let mut references = Vec::new();
+        for template in templates {
+            let content = std::fs::read_to_string(&template.path).map_err(|e| {
+                CliError::IoError(format!("Failed to read template {}: {}", template.path.display(), e))
+            })?;
+            for found in find_references(&target, &content)? {
+                references.push(TemplateReference {
+                    file: template.relative_path.clone(),
+                    line: found.line,
+                    expression: found.expression,
+                    kind: found.kind,
+                    via: found.via,
+                });
+            }
+        }
+        references.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+
+        let set_by = merged
+            .filter(|context| context.get(variable) != api_value.as_ref())
+            .and_then(|context| context.source(variable))
+            .map(str::to_string);
+        let origin = match (&api_value, &set_by) {
+            (None, Some(source)) if source == VAR_FLAG_SOURCE => source.clone(),
+            (None, Some(source)) => format!("variables file {}", source),
+            (None, None) => "not defined by the blueprint, stack or custom variables".to_string(),
+            (Some(_), _) => describe_origin(&target, data_source, base),
+        };
+
+        Ok(Self {
+            variable: variable.to_string(),
+            origin,
+            api_value,
+            set_by,
+            merged_value,
+            template_value: template_value(&final_context.to_template_data(), &target).cloned().map(mask),
+            references,
+        })
+    }
+
+    /// Human-readable report
+    pub fn render_human(&self) -> String {
+        let title = format!("Variable {}", self.variable);
+        let mut lines = vec![title.clone(), "=".repeat(title.len()), String::new()];
+
+        lines.push(format!("  Origin:          {}", self.origin));
+        lines.push(format!("  API value:       {}", display_value(self.api_value.as_ref())));
+        if let Some(merged) = &self.merged_value {
+            let note = match &self.set_by {
+                Some(source) => format!("overridden by {}", source),
+                None => "unchanged".to_string(),
+            };
+            lines.push(format!("  After merging:   {} ({})", display_value(Some(merged)), note));
+        }
+        let resolved = self.merged_value.as_ref().or(self.api_value.as_ref());
+        lines.push(format!("  Templates see:   {}", display_value(self.template_value.as_ref())));
+        if self.template_value.as_ref() != resolved && resolved.is_some_and(|value| !value.is_null()) {
+            lines.push(
+                "  Note: templates resolve this path through the nested data, which differs from the flattened variable"
+                    .to_string(),
+            );
+        }
+
+        lines.push(String::new());
+        lines.push(format!("References ({})", self.references.len()));
+        if self.references.is_empty() {
+            lines.push("  (none)".to_string());
+        }
+        for reference in &self.references {
+            let mut line = format!("  {}:{}  {}", reference.file.display(), reference.line, reference.expression);
+            match reference.kind {
+                ReferenceKind::Direct => {}
+                ReferenceKind::Parent => line.push_str("  [enclosing value]"),
+                ReferenceKind::Child => line.push_str("  [field of the variable]"),
+            }
+            if let Some(via) = &reference.via {
+                line.push_str(&format!("  via {}", via));
+            }
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Reference found in a single template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundReference {
+    pub line: usize,
+    pub expression: String,
+    pub kind: ReferenceKind,
+    pub via: Option<String>,
+}
+
+/// Block that changes what relative paths resolve against
+struct Scope {
+    /// Absolute path of the current context; `*` stands for any array index
+    prefix: Vec<String>,
+    /// Opening tag of the block
+    tag: String,
+}
+
+/// Find every reference to a variable (given as path segments) in a template
+pub fn find_references(target: &[String], template_content: &str) -> Result<Vec<FoundReference>, CliError> {
+    let template = Template::compile(template_content).map_err(|e| CliError::TemplateSyntaxError {
+        line: e.pos().map(|(line, _)| line).unwrap_or(0),
+        message: e.to_string(),
+    })?;
+    let mut finder = ReferenceFinder { target, found: Vec::new() };
+    TemplateWalker::new(template_content).walk(&template, &mut finder);
+    Ok(finder.found)
+}
+
+struct ReferenceFinder<'a> {
+    target: &'a [String],
+    found: Vec<FoundReference>,
+}
+
+impl TagVisitor for ReferenceFinder<'_> {
+    fn visit(&mut self, tag: &VisitedTag) {
+        let mut kinds: Vec<ReferenceKind> = tag
+            .references
+            .iter()
+            .filter_map(|reference| reference.path.as_deref())
+            .filter_map(|path| compare(path, self.target))
+            .collect();
+        kinds.sort_by_key(|kind| *kind as u8);
+        if let Some(kind) = kinds.first() {
+            self.found.push(FoundReference {
+                line: tag.line,
+                expression: tag.tag.clone(),
+                kind: *kind,
+                via: tag.via.clone(),
+            });
+        }
+    }
+}
+
+/// A variable reference made by a tag
+pub(crate) struct TagReference<'a> {
+    /// As written, e.g. `../name` or `r.name`
+    pub(crate) raw: &'a str,
+    /// Absolute path segments, where `*` stands for any array index; `None`
+    /// for references to no variable, e.g. `@index` or an index block parameter
+    pub(crate) path: Option<Vec<String>>,
+}
+
+/// An expression or block tag reached by [`TemplateWalker`]
+pub(crate) struct VisitedTag<'a> {
+    pub(crate) helper: &'a HelperTemplate,
+    pub(crate) line: usize,
+    /// Source text of the tag, e.g. `{{#each resources as |r|}}`
+    pub(crate) tag: String,
+    pub(crate) references: Vec<TagReference<'a>>,
+    /// Path the content of an `{{#each}}` or `{{#with}}` block is relative to
+    pub(crate) scope: Option<Vec<String>>,
+    /// Opening tag of the innermost `{{#each}}`/`{{#with}}` around the tag
+    pub(crate) via: Option<String>,
+}
+
+impl VisitedTag<'_> {
+    /// Helper or block name, e.g. `each` or `uppercase`
+    pub(crate) fn name(&self) -> Option<&str> {
+        match &self.helper.name {
+            Parameter::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// Receives the tags of a template from [`TemplateWalker::walk`]
+pub(crate) trait TagVisitor {
+    /// Called for every expression and block tag, before the block's content
+    fn visit(&mut self, tag: &VisitedTag);
+
+    /// Called for block tags once their content and `{{else}}` branch are walked
+    fn leave(&mut self, _tag: &VisitedTag) {}
+}
+
+/// Walks the tags of a template, resolving references to absolute variable paths
+///
+/// Paths inside `{{#each}}` and `{{#with}}` blocks resolve against the
+/// block's path, and block parameters (`r` in `{{#each resources as |r|}}`)
+/// stand for the block's path.
+pub(crate) struct TemplateWalker<'s> {
+    source: &'s str,
+    scopes: Vec<Scope>,
+    /// Block parameters with the path they stand for; `None` for index parameters
+    locals: Vec<(String, Option<Vec<String>>)>,
+}
+
+impl<'s> TemplateWalker<'s> {
+    pub(crate) fn new(source: &'s str) -> Self {
+        Self { source, scopes: Vec::new(), locals: Vec::new() }
+    }
+
+    /// Walk the template as the content of a block relative to `prefix`
+    pub(crate) fn with_scope(mut self, prefix: Vec<String>) -> Self {
+        self.scopes.push(Scope { prefix, tag: String::new() });
+        self
+    }
+
+    /// Walk the template with a block parameter `name` standing for `path`
+    pub(crate) fn with_local(mut self, name: &str, path: Vec<String>) -> Self {
+        self.locals.push((name.to_string(), Some(path)));
+        self
+    }
+
+    pub(crate) fn walk(&mut self, template: &Template, visitor: &mut impl TagVisitor) {
+        self.walk_from(template, 1, visitor);
+    }
+
+    fn walk_from(&mut self, template: &Template, parent_line: usize, visitor: &mut impl TagVisitor) {
+        for (idx, element) in template.elements.iter().enumerate() {
+            let helper = match element {
+                TemplateElement::Expression(helper)
+                | TemplateElement::HtmlExpression(helper)
+                | TemplateElement::HelperBlock(helper) => helper,
+                _ => continue,
+            };
+            let (line, tag) = match template.mapping.get(idx) {
+                Some(mapping) => (mapping.0, tag_at(self.source, byte_offset(self.source, mapping.0, mapping.1))),
+                None => (parent_line, String::new()),
+            };
+            let mut paths = Vec::new();
+            collect_paths(helper, &mut paths);
+            let visited = VisitedTag {
+                helper,
+                line,
+                tag,
+                references: paths.into_iter().map(|raw| TagReference { raw, path: self.resolve(raw) }).collect(),
+                scope: helper.template.as_ref().and_then(|_| block_scope(helper, &self.scopes)),
+                via: self.scopes.last().map(|scope| scope.tag.clone()),
+            };
+            visitor.visit(&visited);
+            if !helper.block {
+                continue;
+            }
+
+            if let Some(inner) = &helper.template {
+                let local_count = self.locals.len();
+                let scope = visited.scope.clone();
+                match &helper.block_param {
+                    Some(BlockParam::Single(Parameter::Name(item))) => self.locals.push((item.clone(), scope.clone())),
+                    Some(BlockParam::Pair((Parameter::Name(item), Parameter::Name(index)))) => {
+                        self.locals.extend([(item.clone(), scope.clone()), (index.clone(), None)]);
+                    }
+                    _ => {}
+                }
+                let pushed = scope.is_some();
+                if let Some(prefix) = scope {
+                    self.scopes.push(Scope { prefix, tag: visited.tag.clone() });
+                }
+                self.walk_from(inner, line, visitor);
+                if pushed {
+                    self.scopes.pop();
+                }
+                self.locals.truncate(local_count);
+            }
+            if let Some(inverse) = &helper.inverse {
+                self.walk_from(inverse, line, visitor);
+            }
+            visitor.leave(&visited);
+        }
+    }
+
+    /// Absolute path segments of a reference, or `None` if it stands for no variable
+    fn resolve(&self, raw: &str) -> Option<Vec<String>> {
+        // Only the first segment of a path names a block parameter, not `../r` or `this.r`
+        let head = raw.split(['.', '[', ']', '/']).next();
+        if let Some((_, path)) = self.locals.iter().rev().find(|(local, _)| head == Some(local.as_str())) {
+            let path = path.as_ref()?;
+            return Some(path.iter().cloned().chain(segments(raw).into_iter().skip(1)).collect());
+        }
+        resolve(raw, &self.scopes)
+    }
+}
+
+/// Context path inside a `{{#each}}` or `{{#with}}` block
+fn block_scope(helper: &HelperTemplate, scopes: &[Scope]) -> Option<Vec<String>> {
+    let name = match &helper.name {
+        Parameter::Name(name) => name.as_str(),
+        _ => return None,
+    };
+    let raw = match helper.params.first()? {
+        Parameter::Path(path) => path_raw(path)?,
+        // e.g. {{#each (topo_sorted resources)}}
+        Parameter::Subexpression(subexpression) => match subexpression.element.as_ref() {
+            TemplateElement::Expression(inner) => inner.params.iter().find_map(|param| match param {
+                Parameter::Path(path) => path_raw(path),
+                _ => None,
+            })?,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let mut prefix = resolve(raw, scopes)?;
+    match name {
+        "each" => prefix.push("*".to_string()),
+        "with" => {}
+        _ => return None,
+    }
+    Some(prefix)
+}
+
+fn collect_paths<'a>(helper: &'a HelperTemplate, paths: &mut Vec<&'a str>) {
+    for param in std::iter::once(&helper.name).chain(&helper.params).chain(helper.hash.values()) {
+        match param {
+            Parameter::Path(path) => paths.extend(path_raw(path)),
+            Parameter::Subexpression(subexpression) => {
+                if let TemplateElement::Expression(inner) = subexpression.element.as_ref() {
+                    collect_paths(inner, paths);
+                }
+            }
+            Parameter::Name(_) | Parameter::Literal(_) => {}
+        }
+    }
+}
+
+/// Raw text of a context path; `@index`-style local variables are skipped
+fn path_raw(path: &HbsPath) -> Option<&str> {
+    match path {
+        HbsPath::Relative((_, raw)) => Some(raw.as_str()),
+        HbsPath::Local(_) => None,
+    }
+}
+
+/// Absolute path segments of a reference made inside the given scopes
+fn resolve(raw: &str, scopes: &[Scope]) -> Option<Vec<String>> {
+    if let Some(rest) = raw.strip_prefix("@root.") {
+        return Some(segments(rest));
+    }
+    if raw.starts_with('@') {
+        return None;
+    }
+    let mut rest = raw;
+    let mut depth = scopes.len();
+    while let Some(stripped) = rest.strip_prefix("../") {
+        depth = depth.checked_sub(1)?;
+        rest = stripped;
+    }
+    let mut path = depth
+        .checked_sub(1)
+        .map(|index| scopes[index].prefix.clone())
+        .unwrap_or_default();
+    path.extend(segments(rest));
+    Some(path)
+}
+
+/// Compare a reference with the traced variable; `*` matches any array index
+fn compare(reference: &[String], target: &[String]) -> Option<ReferenceKind> {
+    let matches = |a: &String, b: &String| a == b || (a == "*" && b.parse::<usize>().is_ok());
+    let common = reference.len().min(target.len());
+    if common == 0 || !reference.iter().zip(target).take(common).all(|(a, b)| matches(a, b)) {
+        return None;
+    }
+    Some(match reference.len().cmp(&target.len()) {
+        std::cmp::Ordering::Equal => ReferenceKind::Direct,
+        std::cmp::Ordering::Less => ReferenceKind::Parent,
+        std::cmp::Ordering::Greater => ReferenceKind::Child,
+    })
+}
+
+/// Split a variable or template path into segments
+///
+/// `resources[0].name`, `resources.0.name` and `resources.[0].name` all
+/// become `["resources", "0", "name"]`. `this` and `.` segments are dropped.
+pub fn segments(path: &str) -> Vec<String> {
+    path.split(['.', '[', ']', '/'])
+        .filter(|segment| !segment.is_empty() && *segment != "this")
+        .map(str::to_string)
+        .collect()
+}
+
+/// Value at a path in the data handed to the template engine
+fn template_value<'a>(data: &'a Value, target: &[String]) -> Option<&'a Value> {
+    target.iter().try_fold(data, |current, segment| match current {
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        Value::Object(map) => map.get(segment),
+        _ => None,
+    })
+}
+
+/// Which part of the blueprint or stack a variable path is built from
+fn describe_origin(target: &[String], data_source: DataSource, context: &VariableContext) -> String {
+    let source_name = |kind: &str| {
+        context
+            .get_scalar_string(&format!("{}.name", kind))
+            .map(|name| format!("{} '{}'", kind, name))
+            .unwrap_or_else(|| kind.to_string())
+    };
+    let root = target.first().map(String::as_str).unwrap_or_default();
+    match root {
+        "blueprint" if data_source == DataSource::Stack => {
+            format!("metadata of the blueprint linked to {}", source_name("stack"))
+        }
+        "blueprint" | "stack" => format!("metadata of {}", source_name(root)),
+        "resources" | "stack_resources" => {
+            let Some(index) = target.get(1) else {
+                return format!("all resources of {}", source_name(&data_source.to_string()));
+            };
+            let resource = context
+                .get_scalar_string(&format!("{}[{}].name", root, index))
+                .map(|name| format!("resource '{}' ({}[{}])", name, root, index))
+                .unwrap_or_else(|| format!("{}[{}]", root, index));
+            let part = match target.get(2).map(String::as_str) {
+                Some("configuration") => "configuration of ",
+                Some("cloud_specific_properties") => "cloud-specific properties of ",
+                Some("resource_type") => "resource type of ",
+                Some("cloud_provider") => "cloud provider of ",
+                _ => "",
+            };
+            format!("{}{} in {}", part, resource, source_name(&data_source.to_string()))
+        }
+        "dependencies" => format!(
+            "dependencies inferred from the resources of {}",
+            source_name(&data_source.to_string())
+        ),
+        "supported_cloud_providers" => format!("supported cloud providers of {}", source_name("blueprint")),
+        _ => source_name(&data_source.to_string()),
+    }
+}
+
+fn display_value(value: Option<&Value>) -> String {
+    match value {
+        None => "(not set)".to_string(),
+        Some(Value::String(s)) => format!("{:?}", s),
+        Some(other) => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn target(path: &str) -> Vec<String> {
+        segments(path)
+    }
+
+    #[test]
+    fn test_segments_normalise_index_syntax() {
+        assert_eq!(segments("resources[0].name"), vec!["resources", "0", "name"]);
+        assert_eq!(segments("resources.[0].name"), segments("resources.0.name"));
+        assert_eq!(segments("this.name"), vec!["name"]);
+    }
+
+    #[test]
+    fn test_find_references_direct_parent_and_scoped() {
+        let template = "engine = \"{{resources.0.configuration.engine}}\"\n\
+                        {{#each resources}}\n\
+                        {{#if configuration.engine}}\n\
+                        e = {{uppercase configuration.engine}}\n\
+                        {{/if}}\n\
+                        n = {{name}}\n\
+                        {{/each}}\n\
+                        all = {{json resources}}\n";
+        let found = find_references(&target("resources[0].configuration.engine"), template).unwrap();
+        let summary: Vec<(usize, ReferenceKind, bool)> =
+            found.iter().map(|f| (f.line, f.kind, f.via.is_some())).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, ReferenceKind::Direct, false),
+                (2, ReferenceKind::Parent, false),
+                (3, ReferenceKind::Direct, true),
+                (4, ReferenceKind::Direct, true),
+                (8, ReferenceKind::Parent, false),
+            ]
+        );
+        assert_eq!(found[3].expression, "{{uppercase configuration.engine}}");
+        assert_eq!(found[3].via.as_deref(), Some("{{#each resources}}"));
+    }
+
+    #[test]
+    fn test_find_references_through_block_params() {
+        let template = "{{#each resources as |r i|}}\n{{r.configuration.engine}} {{i}}\n{{/each}}\n";
+        let found = find_references(&target("resources[0].configuration.engine"), template).unwrap();
+        let summary: Vec<(usize, ReferenceKind)> = found.iter().map(|f| (f.line, f.kind)).collect();
+        assert_eq!(summary, vec![(1, ReferenceKind::Parent), (2, ReferenceKind::Direct)]);
+        assert_eq!(found[1].via.as_deref(), Some("{{#each resources as |r i|}}"));
+    }
+
+    #[test]
+    fn test_find_references_ignores_other_indices_and_paths() {
+        let template = "{{resources.1.configuration.engine}} {{stack.name}} {{@index}}";
+        assert!(find_references(&target("resources[0].configuration.engine"), template)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_build_reports_override_and_template_value() {
+        let mut base = VariableContext::new();
+        base.insert("stack.name".to_string(), json!("payments"));
+        base.insert("stack_resources".to_string(), json!([{"name": "db", "configuration": {"engine": "postgres"}}]));
+        base.insert("stack_resources[0].name".to_string(), json!("db"));
+        base.insert("stack_resources[0].configuration.engine".to_string(), json!("postgres"));
+
+        let mut merged = base.clone();
+        merged.insert_from("stack_resources[0].configuration.engine".to_string(), json!("aurora"), "vars.yaml");
+
+        let trace = VariableTrace::build(
+            "stack_resources[0].configuration.engine",
+            DataSource::Stack,
+            &base,
+            Some(&merged),
+            &[],
+        )
+        .unwrap();
+        assert_eq!(trace.origin, "configuration of resource 'db' (stack_resources[0]) in stack 'payments'");
+        assert_eq!(trace.api_value, Some(json!("postgres")));
+        assert_eq!(trace.merged_value, Some(json!("aurora")));
+        // Templates walk the nested array, which the flattened override doesn't change
+        assert_eq!(trace.template_value, Some(json!("postgres")));
+
+        let report = trace.render_human();
+        assert!(report.contains("\"aurora\" (overridden by vars.yaml)"));
+        assert!(report.contains("Note: templates resolve this path"));
+    }
+}
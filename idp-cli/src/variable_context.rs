@@ -1,6 +1,23 @@
+use crate::cli::VariableType;
+use crate::dependency_graph::DependencyGraph;
 use crate::models::{Blueprint, Stack};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Source shown for variables of the blueprint or stack
+pub const API_SOURCE: &str = "api";
+
+/// Source recorded for variables set with `--var`
+pub const VAR_FLAG_SOURCE: &str = "--var";
+
+/// Source recorded for default variables from the config files
+pub const DEFAULTS_SOURCE: &str = "config defaults";
+
+/// Blueprint resource fields whose entries get their own accessors, e.g. `resources[0].cloud_provider.name`
+const RESOURCE_FLATTENED_FIELDS: &[&str] = &["resource_type", "cloud_provider", "cloud_specific_properties"];
+
+/// Stack resource fields whose entries get their own accessors, e.g. `stack_resources[0].configuration.region`
+const STACK_RESOURCE_FLATTENED_FIELDS: &[&str] = &["resource_type", "cloud_provider", "configuration"];
 
 /// Builder for creating variable contexts from blueprints and stacks
 pub struct VariableContextBuilder;
@@ -14,7 +31,8 @@ impl VariableContextBuilder {
     /// Create a VariableContext from a Blueprint
     /// 
     /// Extracts blueprint metadata and resources into a flattened variable structure.
-    /// Creates both nested JSON structures and flattened dot-notation paths for easy access.
+    /// Only the nested `resources` array is stored; the per-resource dot-notation
+    /// paths below are resolved from it when looked up or listed.
     /// 
     /// # Arguments
     /// * `blueprint` - The blueprint to extract variables from
@@ -38,9 +56,10 @@ impl VariableContextBuilder {
     /// - `resources[N].cloud_provider.display_name` - Cloud provider display name
     /// - `resources[N].configuration` - Resource configuration object
     /// - `resources[N].cloud_specific_properties.{key}` - Cloud-specific property values
+    /// - `dependencies.{resource name}` - Names of resources this resource depends on
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let blueprint = fetch_blueprint_from_api();
     /// let context = VariableContextBuilder::from_blueprint(&blueprint);
     /// 
@@ -105,62 +124,12 @@ impl VariableContextBuilder {
             })
             .collect();
 
-        // Insert the full resources array
-        context.insert("resources".to_string(), Value::Array(resources_json.clone()));
-
-        // Create flattened accessors for each resource
-        for (index, resource) in blueprint.resources.iter().enumerate() {
-            let prefix = format!("resources[{}]", index);
-
-            context.insert(format!("{}.id", prefix), json!(resource.id.to_string()));
-            context.insert(format!("{}.name", prefix), json!(resource.name));
-            
-            if let Some(ref description) = resource.description {
-                context.insert(format!("{}.description", prefix), json!(description));
-            }
+        // Insert the full resources array, moved rather than cloned since it can be large
+        Self::insert_dependencies(&mut context, &resources_json);
+        context.insert("resources".to_string(), Value::Array(resources_json));
 
-            // Flatten resource_type
-            context.insert(
-                format!("{}.resource_type.id", prefix),
-                json!(resource.resource_type.id.to_string()),
-            );
-            context.insert(
-                format!("{}.resource_type.name", prefix),
-                json!(resource.resource_type.name),
-            );
-            context.insert(
-                format!("{}.resource_type.category", prefix),
-                json!(resource.resource_type.category),
-            );
-
-            // Flatten cloud_provider
-            context.insert(
-                format!("{}.cloud_provider.id", prefix),
-                json!(resource.cloud_provider.id.to_string()),
-            );
-            context.insert(
-                format!("{}.cloud_provider.name", prefix),
-                json!(resource.cloud_provider.name),
-            );
-            context.insert(
-                format!("{}.cloud_provider.display_name", prefix),
-                json!(resource.cloud_provider.display_name),
-            );
-
-            // Insert configuration as a whole object
-            context.insert(
-                format!("{}.configuration", prefix),
-                resource.configuration.clone(),
-            );
-
-            // Flatten cloud_specific_properties
-            for (key, value) in &resource.cloud_specific_properties {
-                context.insert(
-                    format!("{}.cloud_specific_properties.{}", prefix, key),
-                    value.clone(),
-                );
-            }
-        }
+        // Per-resource accessors like `resources[0].name` are derived from the array on demand
+        context.item_accessors.insert("resources".to_string(), RESOURCE_FLATTENED_FIELDS);
 
         // Add supported_cloud_providers array
         let cloud_providers_json: Vec<Value> = blueprint
@@ -182,7 +151,8 @@ impl VariableContextBuilder {
     /// Create a VariableContext from a Stack
     /// 
     /// Extracts stack metadata and stack resources into a flattened variable structure.
-    /// Creates both nested JSON structures and flattened dot-notation paths for easy access.
+    /// Only the nested `stack_resources` array is stored; the per-resource dot-notation
+    /// paths below are resolved from it when looked up or listed.
     /// 
     /// # Arguments
     /// * `stack` - The stack to extract variables from
@@ -207,9 +177,10 @@ impl VariableContextBuilder {
     /// - `stack_resources[N].cloud_provider.name` - Cloud provider name
     /// - `stack_resources[N].cloud_provider.display_name` - Cloud provider display name
     /// - `stack_resources[N].configuration.{key}` - Stack resource configuration values
+    /// - `dependencies.{resource name}` - Names of resources this resource depends on
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let stack = fetch_stack_from_api();
     /// let context = VariableContextBuilder::from_stack(&stack);
     /// 
@@ -275,56 +246,12 @@ impl VariableContextBuilder {
             })
             .collect();
 
-        // Insert the full stack_resources array
-        context.insert("stack_resources".to_string(), Value::Array(stack_resources_json.clone()));
+        // Insert the full stack_resources array, moved rather than cloned since it can be large
+        Self::insert_dependencies(&mut context, &stack_resources_json);
+        context.insert("stack_resources".to_string(), Value::Array(stack_resources_json));
 
-        // Create flattened accessors for each stack resource
-        for (index, resource) in stack.stack_resources.iter().enumerate() {
-            let prefix = format!("stack_resources[{}]", index);
-
-            context.insert(format!("{}.id", prefix), json!(resource.id.to_string()));
-            context.insert(format!("{}.name", prefix), json!(resource.name));
-            
-            if let Some(ref description) = resource.description {
-                context.insert(format!("{}.description", prefix), json!(description));
-            }
-
-            // Flatten resource_type
-            context.insert(
-                format!("{}.resource_type.id", prefix),
-                json!(resource.resource_type.id.to_string()),
-            );
-            context.insert(
-                format!("{}.resource_type.name", prefix),
-                json!(resource.resource_type.name),
-            );
-            context.insert(
-                format!("{}.resource_type.category", prefix),
-                json!(resource.resource_type.category),
-            );
-
-            // Flatten cloud_provider
-            context.insert(
-                format!("{}.cloud_provider.id", prefix),
-                json!(resource.cloud_provider.id.to_string()),
-            );
-            context.insert(
-                format!("{}.cloud_provider.name", prefix),
-                json!(resource.cloud_provider.name),
-            );
-            context.insert(
-                format!("{}.cloud_provider.display_name", prefix),
-                json!(resource.cloud_provider.display_name),
-            );
-
-            // Flatten configuration properties
-            for (key, value) in &resource.configuration {
-                context.insert(
-                    format!("{}.configuration.{}", prefix, key),
-                    value.clone(),
-                );
-            }
-        }
+        // Per-resource accessors like `stack_resources[0].name` are derived from the array on demand
+        context.item_accessors.insert("stack_resources".to_string(), STACK_RESOURCE_FLATTENED_FIELDS);
 
         // If blueprint is present, include it as well
         if let Some(ref blueprint) = stack.blueprint {
@@ -340,23 +267,38 @@ impl VariableContextBuilder {
         context
     }
 
+    /// Add `dependencies.<resource name>` entries listing each resource's dependencies
+    ///
+    /// See `DependencyGraph` for how dependencies are inferred.
+    fn insert_dependencies(context: &mut VariableContext, resources: &[Value]) {
+        let graph = DependencyGraph::from_resources(resources);
+        for resource in resources {
+            if let Some(name) = resource.get("name").and_then(|n| n.as_str()) {
+                context.insert(
+                    format!("dependencies.{}", name),
+                    json!(graph.dependencies_of(name)),
+                );
+            }
+        }
+    }
+
     /// Merge custom variables from a file into an existing VariableContext
     /// 
-    /// Loads variables from a JSON or YAML file and merges them with the existing context.
+    /// Loads variables from a JSON, YAML or tfvars file and merges them with the existing context.
     /// Custom variables override blueprint/stack variables when conflicts occur.
     /// Warnings are displayed for any overrides.
     /// 
     /// # Arguments
     /// * `context` - The existing VariableContext to merge into
-    /// * `variables_file` - Path to the JSON or YAML file containing custom variables
+    /// * `variables_file` - Path to the JSON, YAML or tfvars file containing custom variables
     /// 
     /// # Returns
     /// * `Ok(())` if variables were successfully loaded and merged
     /// * `Err(CliError)` if the file cannot be read or parsed
     /// 
     /// # File Format
-    /// Both JSON and YAML formats are supported. The file should contain a flat or nested
-    /// object structure:
+    /// JSON, YAML and Terraform variable files (`.tfvars`, `.hcl`) are supported.
+    /// The file should contain a flat or nested object structure:
     /// 
     /// JSON example:
     /// ```json
@@ -377,14 +319,29 @@ impl VariableContextBuilder {
     ///   domain: example.com
     /// ```
     /// 
+    /// tfvars example:
+    /// ```hcl
+    /// environment = "production"
+    /// region      = "us-east-1"
+    /// custom = {
+    ///   domain = "example.com"
+    /// }
+    /// ```
+    /// 
     /// # Variable Override Behavior
     /// - Custom variables are merged into the context
     /// - If a custom variable has the same key as an existing variable, the custom value overrides it
     /// - A warning is printed to stderr for each override
     /// - Nested structures are preserved
     /// 
+    /// # Secret References
+    /// String values of the form `ssm://<parameter>` or
+    /// `secretsmanager://<name or ARN>[#<key>]` are replaced with the secret
+    /// from AWS before merging, and marked secret so that debug bundles and
+    /// `trace-var` redact them.
+    /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let mut context = VariableContextBuilder::from_blueprint(&blueprint);
     /// VariableContextBuilder::merge_custom_variables(&mut context, Path::new("vars.yaml"))?;
     /// ```
@@ -438,35 +395,120 @@ impl VariableContextBuilder {
                     ))
                 })?
             }
+            "tfvars" | "hcl" => Self::parse_hcl_variables(&file_contents, variables_file)?,
             _ => {
                 return Err(CliError::VariableFileError(format!(
-                    "Unsupported file extension '{}'. Use .json, .yaml, or .yml",
+                    "Unsupported file extension '{}'. Use .json, .yaml, .yml, .tfvars, or .hcl",
                     extension
                 )));
             }
         };
 
         // Ensure we have an object at the root
-        let custom_vars_obj = match custom_vars {
-            serde_json::Value::Object(obj) => obj,
-            _ => {
-                return Err(CliError::VariableFileError(
-                    "Variables file must contain a JSON/YAML object at the root".to_string(),
-                ));
+        if !custom_vars.is_object() {
+            return Err(CliError::VariableFileError(
+                "Variables file must contain a JSON/YAML object at the root".to_string(),
+            ));
+        }
+
+        Self::merge_value(context, custom_vars, &variables_file.display().to_string())
+    }
+
+    /// Parse the attributes of a `.tfvars` or `.hcl` file into a JSON object
+    /// 
+    /// As in Terraform, values must be constants: expressions such as `1 + 2`
+    /// or `"eu-${"west"}-1"` are evaluated, but references to variables and
+    /// function calls are rejected, as are blocks.
+    fn parse_hcl_variables(contents: &str, variables_file: &std::path::Path) -> Result<Value, crate::error::CliError> {
+        use crate::error::CliError;
+        use hcl::eval::{Context, Evaluate};
+
+        let body = hcl::parse(contents).map_err(|e| {
+            CliError::VariableFileError(format!("Failed to parse HCL from '{}': {}", variables_file.display(), e))
+        })?;
+        let context = Context::new();
+        let mut variables = serde_json::Map::new();
+        for structure in body.into_inner() {
+            match structure {
+                hcl::Structure::Attribute(attribute) => {
+                    let value = attribute.expr.evaluate(&context).map_err(|e| {
+                        CliError::VariableFileError(format!(
+                            "'{}' in '{}' must be a constant value: {}",
+                            attribute.key,
+                            variables_file.display(),
+                            e
+                        ))
+                    })?;
+                    let value = serde_json::to_value(value).map_err(|e| {
+                        CliError::VariableFileError(format!("Failed to convert HCL to JSON: {}", e))
+                    })?;
+                    variables.insert(attribute.key.to_string(), value);
+                }
+                hcl::Structure::Block(block) => {
+                    return Err(CliError::VariableFileError(format!(
+                        "'{}' declares a '{}' block; variable files may only set attributes (name = value)",
+                        variables_file.display(),
+                        block.identifier
+                    )));
+                }
             }
-        };
+        }
+        Ok(Value::Object(variables))
+    }
+
+    /// Merge `--var NAME=VALUE` assignments, in order
+    /// 
+    /// Dots in a name nest the value, so `--var tags.team=payments` merges
+    /// like a variables file containing `tags: {team: payments}`. Values are
+    /// strings; secret references are resolved as in variables files.
+    pub fn merge_assignments(
+        context: &mut VariableContext,
+        assignments: &[(String, String)],
+    ) -> Result<(), crate::error::CliError> {
+        for (name, value) in assignments {
+            let mut nested = Value::String(value.clone());
+            for segment in name.rsplit('.') {
+                nested = json!({ segment: nested });
+            }
+            Self::merge_value(context, nested, VAR_FLAG_SOURCE)?;
+        }
+        Ok(())
+    }
+
+    /// Resolve secret references in an object of custom variables and merge it
+    fn merge_value(context: &mut VariableContext, mut value: Value, source: &str) -> Result<(), crate::error::CliError> {
+        // Replace ssm:// and secretsmanager:// references with their values
+        for secret in crate::secret_resolver::resolve(&mut value)? {
+            context.mark_secret(secret);
+        }
 
         // Flatten the custom variables and merge them
-        Self::flatten_and_merge(context, "", &serde_json::Value::Object(custom_vars_obj));
+        Self::flatten_and_merge(context, "", &value, source);
 
         Ok(())
     }
 
+    /// Merge default variables from the config files
+    /// 
+    /// Unlike custom variables, defaults never override: a default is only
+    /// added when the API data doesn't already provide that variable.
+    pub fn merge_defaults(context: &mut VariableContext, defaults: &serde_json::Map<String, serde_json::Value>) {
+        let mut flattened = VariableContext::new();
+        Self::flatten_and_merge(&mut flattened, "", &serde_json::Value::Object(defaults.clone()), DEFAULTS_SOURCE);
+        for (key, value) in flattened.variables {
+            if !context.contains(&key) {
+                context.sources.insert(key.clone(), DEFAULTS_SOURCE.to_string());
+                context.variables.insert(key, value);
+            }
+        }
+    }
+
     /// Helper function to flatten nested structures and merge into context
     /// 
     /// Recursively flattens nested objects and arrays into dot-notation keys
-    /// and merges them into the variable context. Displays warnings for overrides.
-    fn flatten_and_merge(context: &mut VariableContext, prefix: &str, value: &serde_json::Value) {
+    /// and merges them into the variable context, recording `source` as
+    /// where each one came from. Displays warnings for overrides.
+    fn flatten_and_merge(context: &mut VariableContext, prefix: &str, value: &serde_json::Value, source: &str) {
         match value {
             serde_json::Value::Object(obj) => {
                 for (key, val) in obj {
@@ -477,7 +519,7 @@ impl VariableContextBuilder {
                     };
 
                     // Check if this key already exists (for warning)
-                    if context.variables.contains_key(&new_prefix) {
+                    if context.contains(&new_prefix) {
                         eprintln!(
                             "Warning: Custom variable '{}' overrides existing value",
                             new_prefix
@@ -488,13 +530,13 @@ impl VariableContextBuilder {
                     match val {
                         serde_json::Value::Object(_) | serde_json::Value::Array(_) => {
                             // Insert the whole structure
-                            context.insert(new_prefix.clone(), val.clone());
+                            context.insert_from(new_prefix.clone(), val.clone(), source);
                             // Also flatten it
-                            Self::flatten_and_merge(context, &new_prefix, val);
+                            Self::flatten_and_merge(context, &new_prefix, val, source);
                         }
                         _ => {
                             // For primitive values, just insert
-                            context.insert(new_prefix, val.clone());
+                            context.insert_from(new_prefix, val.clone(), source);
                         }
                     }
                 }
@@ -502,20 +544,20 @@ impl VariableContextBuilder {
             serde_json::Value::Array(arr) => {
                 // Insert the whole array
                 if !prefix.is_empty() {
-                    if context.variables.contains_key(prefix) {
+                    if context.contains(prefix) {
                         eprintln!(
                             "Warning: Custom variable '{}' overrides existing value",
                             prefix
                         );
                     }
-                    context.insert(prefix.to_string(), value.clone());
+                    context.insert_from(prefix.to_string(), value.clone(), source);
                 }
 
                 // Also create indexed accessors
                 for (index, item) in arr.iter().enumerate() {
                     let indexed_key = format!("{}[{}]", prefix, index);
                     
-                    if context.variables.contains_key(&indexed_key) {
+                    if context.contains(&indexed_key) {
                         eprintln!(
                             "Warning: Custom variable '{}' overrides existing value",
                             indexed_key
@@ -525,13 +567,13 @@ impl VariableContextBuilder {
                     match item {
                         serde_json::Value::Object(_) | serde_json::Value::Array(_) => {
                             // Insert the whole structure
-                            context.insert(indexed_key.clone(), item.clone());
+                            context.insert_from(indexed_key.clone(), item.clone(), source);
                             // Also flatten it
-                            Self::flatten_and_merge(context, &indexed_key, item);
+                            Self::flatten_and_merge(context, &indexed_key, item, source);
                         }
                         _ => {
                             // For primitive values, just insert
-                            context.insert(indexed_key, item.clone());
+                            context.insert_from(indexed_key, item.clone(), source);
                         }
                     }
                 }
@@ -539,13 +581,13 @@ impl VariableContextBuilder {
             _ => {
                 // Primitive value at root (shouldn't happen due to earlier check, but handle it)
                 if !prefix.is_empty() {
-                    if context.variables.contains_key(prefix) {
+                    if context.contains(prefix) {
                         eprintln!(
                             "Warning: Custom variable '{}' overrides existing value",
                             prefix
                         );
                     }
-                    context.insert(prefix.to_string(), value.clone());
+                    context.insert_from(prefix.to_string(), value.clone(), source);
                 }
             }
         }
@@ -563,6 +605,13 @@ impl Default for VariableContextBuilder {
 #[derive(Debug, Clone)]
 pub struct VariableContext {
     variables: HashMap<String, Value>,
+    /// Values resolved from secret references, redacted wherever variables are shown
+    secrets: BTreeSet<String>,
+    /// Where merged variables came from; the others come from the blueprint or stack
+    sources: HashMap<String, String>,
+    /// Arrays whose per-item accessors (`resources[0].name`) are derived on demand,
+    /// with the item fields whose entries are flattened one level further
+    item_accessors: BTreeMap<String, &'static [&'static str]>,
 }
 
 impl VariableContext {
@@ -570,6 +619,9 @@ impl VariableContext {
     pub fn new() -> Self {
         VariableContext {
             variables: HashMap::new(),
+            secrets: BTreeSet::new(),
+            sources: HashMap::new(),
+            item_accessors: BTreeMap::new(),
         }
     }
 
@@ -588,7 +640,7 @@ impl VariableContext {
     /// * `None` if the variable is not found
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let context = VariableContext::new();
     /// let value = context.get("blueprint.name");
     /// let array_value = context.get("resources[0].name");
@@ -606,15 +658,74 @@ impl VariableContext {
             return self.get_with_array_index(parts[0]);
         }
 
-        // Start with the first part (might have array indexing)
-        let mut current = self.get_with_array_index(parts[0])?;
+        // Start with the first part (might have array indexing), then navigate
+        // through the remaining path
+        let navigated = self.get_with_array_index(parts[0]).and_then(|mut current| {
+            for part in &parts[1..] {
+                current = self.navigate_part(current, part)?;
+            }
+            Some(current)
+        });
+
+        // Item accessors whose keys contain dots, e.g. `stack_resources[0].configuration.app.port`
+        navigated.or_else(|| self.item_accessor(path))
+    }
+
+    /// Check whether a variable is stored or is an item accessor
+    fn contains(&self, key: &str) -> bool {
+        self.variables.contains_key(key) || self.item_accessor(key).is_some()
+    }
 
-        // Navigate through the remaining path
-        for part in &parts[1..] {
-            current = self.navigate_part(current, part)?;
+    /// Resolve a per-item accessor such as `resources[0].cloud_provider.name`
+    fn item_accessor(&self, path: &str) -> Option<&Value> {
+        self.item_accessors.iter().find_map(|(array, flattened)| {
+            let rest = path.strip_prefix(array.as_str())?.strip_prefix('[')?;
+            let (index, field_path) = rest.split_once("].")?;
+            let item = self.variables.get(array)?.get(index.parse::<usize>().ok()?)?;
+            match field_path.split_once('.') {
+                Some((field, key)) if flattened.contains(&field) => item.get(field)?.as_object()?.get(key),
+                _ if flattened.contains(&field_path) => None,
+                _ => item.get(field_path),
+            }
+        })
+    }
+
+    /// List the per-item accessors that aren't shadowed by a stored variable
+    fn item_accessor_entries(&self) -> Vec<(String, &Value)> {
+        let mut entries = Vec::new();
+        for (array, flattened) in &self.item_accessors {
+            let Some(Value::Array(items)) = self.variables.get(array) else {
+                continue;
+            };
+            for (index, item) in items.iter().enumerate() {
+                let Value::Object(fields) = item else {
+                    continue;
+                };
+                for (field, value) in fields {
+                    let prefix = format!("{}[{}].{}", array, index, field);
+                    if !flattened.contains(&field.as_str()) {
+                        entries.push((prefix, value));
+                    } else if let Value::Object(children) = value {
+                        entries.extend(children.iter().map(|(key, child)| (format!("{}.{}", prefix, key), child)));
+                    }
+                }
+            }
         }
+        entries.retain(|(key, _)| !self.variables.contains_key(key));
+        entries
+    }
 
-        Some(current)
+    /// Get a scalar variable as a string
+    /// 
+    /// Strings are returned as-is; numbers and booleans are formatted.
+    /// Returns None for missing, null, array and object values.
+    pub fn get_scalar_string(&self, path: &str) -> Option<String> {
+        match self.get(path)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            _ => None,
+        }
     }
 
     /// Helper to get a value with array indexing from the root variables
@@ -662,7 +773,7 @@ impl VariableContext {
     /// A vector of (String, &Value) tuples representing all variables
     /// 
     /// # Examples
-    /// ```
+    /// ```ignore
     /// let context = VariableContext::new();
     /// for (name, value) in context.list_all() {
     ///     println!("{}: {:?}", name, value);
@@ -673,6 +784,7 @@ impl VariableContext {
             .variables
             .iter()
             .map(|(k, v)| (k.clone(), v))
+            .chain(self.item_accessor_entries())
             .collect();
         
         // Sort by key for consistent output
@@ -690,9 +802,21 @@ impl VariableContext {
         self.variables.insert(key, value);
     }
 
-    /// Get the number of variables in the context
+    /// Insert a variable merged from `source`, e.g. a variables file
+    pub fn insert_from(&mut self, key: String, value: Value, source: &str) {
+        self.sources.insert(key.clone(), source.to_string());
+        self.variables.insert(key, value);
+    }
+
+    /// Where a variable's value came from: a variables file, `--var` or the
+    /// config file defaults; None for variables of the blueprint or stack
+    pub fn source(&self, key: &str) -> Option<&str> {
+        self.sources.get(key).map(String::as_str)
+    }
+
+    /// Get the number of variables in the context, including item accessors
     pub fn len(&self) -> usize {
-        self.variables.len()
+        self.variables.len() + self.item_accessor_entries().len()
     }
 
     /// Check if the context is empty
@@ -701,6 +825,9 @@ impl VariableContext {
     }
 
     /// Get a reference to the underlying variables HashMap
+    /// 
+    /// Item accessors such as `resources[0].name` are not stored; use
+    /// `list_all` to include them.
     pub fn variables(&self) -> &HashMap<String, Value> {
         &self.variables
     }
@@ -709,6 +836,60 @@ impl VariableContext {
     pub fn variables_mut(&mut self) -> &mut HashMap<String, Value> {
         &mut self.variables
     }
+
+    /// Build the data object handed to the template engine
+    /// 
+    /// Handlebars resolves `{{blueprint.name}}` by walking nested objects, so the
+    /// flattened dot-notation keys (e.g. `blueprint.name`) are expanded into nested
+    /// objects alongside the original flat keys. Keys with array indexing are skipped
+    /// because the full arrays are already present in the context. Values that are
+    /// already present in a nested structure are never overwritten.
+    /// 
+    /// # Returns
+    /// A JSON object suitable for rendering templates
+    pub fn to_template_data(&self) -> Value {
+        let mut data: serde_json::Map<String, Value> = self
+            .variables
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        // Sort so that shorter paths are expanded before longer ones
+        let mut dotted: Vec<&String> = self
+            .variables
+            .keys()
+            .filter(|k| k.contains('.') && !k.contains('['))
+            .collect();
+        dotted.sort();
+
+        for key in dotted {
+            let parts: Vec<&str> = key.split('.').collect();
+            Self::insert_nested(&mut data, &parts, &self.variables[key]);
+        }
+
+        Value::Object(data)
+    }
+
+    /// Insert a value at a nested path without overwriting existing values
+    fn insert_nested(map: &mut serde_json::Map<String, Value>, parts: &[&str], value: &Value) {
+        let (first, rest) = match parts.split_first() {
+            Some(split) => split,
+            None => return,
+        };
+
+        if rest.is_empty() {
+            map.entry(first.to_string()).or_insert_with(|| value.clone());
+            return;
+        }
+
+        let entry = map
+            .entry(first.to_string())
+            .or_insert_with(|| Value::Object(serde_json::Map::new()));
+
+        if let Value::Object(child) = entry {
+            Self::insert_nested(child, rest, value);
+        }
+    }
 }
 
 impl Default for VariableContext {
@@ -717,6 +898,115 @@ impl Default for VariableContext {
     }
 }
 
+/// Criteria for narrowing down the variables shown by list-variables
+///
+/// All criteria are optional and combined with AND.
+#[derive(Debug, Clone, Default)]
+pub struct VariableFilter {
+    /// Path prefix pattern such as `resources.*.cloud_provider`; `*` matches one segment
+    pub pattern: Option<String>,
+    /// Case-insensitive text matched against variable names and string values
+    pub search: Option<String>,
+    /// Maximum number of path segments (`resources[0].name` has 3)
+    pub max_depth: Option<usize>,
+    /// Only keep variables of this type
+    pub value_type: Option<VariableType>,
+}
+
+impl VariableFilter {
+    /// Check whether no criteria are set
+    pub fn is_empty(&self) -> bool {
+        self.pattern.is_none()
+            && self.search.is_none()
+            && self.max_depth.is_none()
+            && self.value_type.is_none()
+    }
+
+    /// Check whether a single variable satisfies every criterion
+    pub fn matches(&self, key: &str, value: &Value) -> bool {
+        let segments = path_segments(key);
+
+        if let Some(max_depth) = self.max_depth {
+            if segments.len() > max_depth {
+                return false;
+            }
+        }
+
+        if let Some(value_type) = self.value_type {
+            if !value_type.matches(value) {
+                return false;
+            }
+        }
+
+        if let Some(pattern) = &self.pattern {
+            let pattern_segments = path_segments(pattern);
+            if pattern_segments.len() > segments.len()
+                || !pattern_segments
+                    .iter()
+                    .zip(&segments)
+                    .all(|(p, s)| *p == "*" || p == s)
+            {
+                return false;
+            }
+        }
+
+        if let Some(search) = &self.search {
+            let needle = search.to_lowercase();
+            let in_key = key.to_lowercase().contains(&needle);
+            let in_value = value
+                .as_str()
+                .map(|v| v.to_lowercase().contains(&needle))
+                .unwrap_or(false);
+            if !in_key && !in_value {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl VariableContext {
+    /// Record a value that came from a secret store
+    pub fn mark_secret(&mut self, value: String) {
+        self.secrets.insert(value);
+    }
+
+    /// Whether a variable value is, or contains, a secret
+    pub fn is_secret(&self, value: &Value) -> bool {
+        match value {
+            Value::String(text) => self.secrets.contains(text),
+            Value::Array(items) => items.iter().any(|item| self.is_secret(item)),
+            Value::Object(map) => map.values().any(|item| self.is_secret(item)),
+            _ => false,
+        }
+    }
+
+    /// Create a new context containing only the variables that match a filter
+    pub fn filter(&self, filter: &VariableFilter) -> VariableContext {
+        VariableContext {
+            variables: self
+                .list_all()
+                .into_iter()
+                .filter(|(k, v)| filter.matches(k, v))
+                .map(|(k, v)| (k, v.clone()))
+                .collect(),
+            secrets: self.secrets.clone(),
+            sources: self.sources.clone(),
+            item_accessors: BTreeMap::new(),
+        }
+    }
+}
+
+/// Split a variable path into segments, treating array indices as segments
+///
+/// `resources[0].cloud_provider.name` -> `["resources", "0", "cloud_provider", "name"]`
+fn path_segments(path: &str) -> Vec<&str> {
+    path.split(['.', '[', ']'])
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -741,6 +1031,19 @@ mod tests {
         assert_eq!(context.get("name"), Some(&json!("test-blueprint")));
     }
 
+    #[test]
+    fn test_get_scalar_string() {
+        let mut context = VariableContext::new();
+        context.insert("stack.owner".to_string(), json!("payments"));
+        context.insert("replicas".to_string(), json!(3));
+        context.insert("tags".to_string(), json!({"a": "b"}));
+
+        assert_eq!(context.get_scalar_string("stack.owner"), Some("payments".to_string()));
+        assert_eq!(context.get_scalar_string("replicas"), Some("3".to_string()));
+        assert_eq!(context.get_scalar_string("tags"), None);
+        assert_eq!(context.get_scalar_string("missing"), None);
+    }
+
     #[test]
     fn test_get_nonexistent_variable() {
         let context = VariableContext::new();
@@ -878,6 +1181,111 @@ mod tests {
         assert_eq!(context.get("key2"), Some(&json!("value2")));
     }
 
+    #[test]
+    fn test_to_template_data_expands_dotted_keys() {
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("web-app"));
+        context.insert("blueprint.id".to_string(), json!("1234"));
+        context.insert("resources".to_string(), json!([{"name": "db"}]));
+        context.insert("resources[0].name".to_string(), json!("db"));
+
+        let data = context.to_template_data();
+
+        assert_eq!(data["blueprint"]["name"], json!("web-app"));
+        assert_eq!(data["blueprint"]["id"], json!("1234"));
+        // Flat keys are still available
+        assert_eq!(data["blueprint.name"], json!("web-app"));
+        // Array-indexed keys are not expanded
+        assert!(data.get("resources[0]").is_none());
+        assert_eq!(data["resources"][0]["name"], json!("db"));
+    }
+
+    #[test]
+    fn test_to_template_data_does_not_overwrite_nested_values() {
+        let mut context = VariableContext::new();
+        context.insert("custom".to_string(), json!({"domain": "example.com"}));
+        context.insert("custom.domain".to_string(), json!("other.com"));
+
+        let data = context.to_template_data();
+
+        assert_eq!(data["custom"]["domain"], json!("example.com"));
+    }
+
+    fn create_filter_test_context() -> VariableContext {
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("payments"));
+        context.insert("resources".to_string(), json!([{"name": "db"}]));
+        context.insert("resources[0].name".to_string(), json!("postgres-main"));
+        context.insert("resources[0].cloud_provider.name".to_string(), json!("AWS"));
+        context.insert("resources[1].cloud_provider.name".to_string(), json!("Azure"));
+        context.insert("resources[1].configuration.port".to_string(), json!(5432));
+        context
+    }
+
+    fn filtered_keys(context: &VariableContext, filter: &VariableFilter) -> Vec<String> {
+        context.filter(filter).list_all().into_iter().map(|(k, _)| k).collect()
+    }
+
+    #[test]
+    fn test_filter_by_pattern_with_wildcard() {
+        let context = create_filter_test_context();
+        let filter = VariableFilter {
+            pattern: Some("resources.*.cloud_provider".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            filtered_keys(&context, &filter),
+            vec!["resources[0].cloud_provider.name", "resources[1].cloud_provider.name"]
+        );
+
+        let filter = VariableFilter {
+            pattern: Some("resources[1]".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filtered_keys(&context, &filter).len(), 2);
+    }
+
+    #[test]
+    fn test_filter_by_search_matches_keys_and_values() {
+        let context = create_filter_test_context();
+        let filter = VariableFilter {
+            search: Some("POSTGRES".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filtered_keys(&context, &filter), vec!["resources[0].name"]);
+
+        let filter = VariableFilter {
+            search: Some("port".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filtered_keys(&context, &filter), vec!["resources[1].configuration.port"]);
+    }
+
+    #[test]
+    fn test_filter_by_depth_and_type() {
+        let context = create_filter_test_context();
+        let filter = VariableFilter {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(filtered_keys(&context, &filter), vec!["blueprint.name", "resources"]);
+
+        let filter = VariableFilter {
+            value_type: Some(VariableType::Number),
+            ..Default::default()
+        };
+        assert_eq!(filtered_keys(&context, &filter), vec!["resources[1].configuration.port"]);
+
+        let filter = VariableFilter {
+            max_depth: Some(1),
+            value_type: Some(VariableType::String),
+            ..Default::default()
+        };
+        assert!(context.filter(&filter).is_empty());
+        assert!(!filter.is_empty());
+        assert!(VariableFilter::default().is_empty());
+    }
+
     #[test]
     fn test_default_trait() {
         let context = VariableContext::default();
@@ -885,6 +1293,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::default_constructed_unit_structs)]
     fn test_builder_default_trait() {
         let _builder = VariableContextBuilder::default();
         // Just verify it compiles and constructs
@@ -928,6 +1337,8 @@ mod tests {
             name: "web-app-blueprint".to_string(),
             description: Some("Web application infrastructure".to_string()),
             resources: vec![resource],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![cloud_provider],
         }
     }
@@ -1069,6 +1480,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_blueprint_derives_resource_accessors_on_demand() {
+        let blueprint = create_test_blueprint();
+        let mut context = VariableContextBuilder::from_blueprint(&blueprint);
+
+        // Only the array is stored; the accessors are listed and counted
+        assert!(!context.variables().contains_key("resources[0].name"));
+        let listed: HashMap<String, &Value> = context.list_all().into_iter().collect();
+        assert_eq!(listed.get("resources[0].name"), Some(&&json!("postgres-db")));
+        assert_eq!(listed.get("resources[0].cloud_provider.name"), Some(&&json!("AWS")));
+        assert!(listed.contains_key("resources[0].configuration"));
+        assert!(!listed.contains_key("resources[0].cloud_provider"));
+        assert_eq!(context.len(), listed.len());
+
+        // Merged variables shadow the accessors instead of being listed twice
+        VariableContextBuilder::merge_assignments(
+            &mut context,
+            &[("resources[0].name".to_string(), "renamed".to_string())],
+        )
+        .unwrap();
+        assert_eq!(context.get("resources[0].name"), Some(&json!("renamed")));
+        let names = context.list_all().into_iter().filter(|(k, _)| k == "resources[0].name").count();
+        assert_eq!(names, 1);
+    }
+
     #[test]
     fn test_from_blueprint_includes_supported_cloud_providers() {
         let blueprint = create_test_blueprint();
@@ -1130,6 +1566,8 @@ mod tests {
             name: "multi-resource-blueprint".to_string(),
             description: None,
             resources: vec![db_resource, cache_resource],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![cloud_provider],
         };
 
@@ -1154,6 +1592,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_blueprint_includes_dependencies() {
+        let mut blueprint = create_test_blueprint();
+        blueprint.resources[0].configuration = json!({ "depends_on": ["cache"] });
+        blueprint.resources.push(BlueprintResource {
+            name: "cache".to_string(),
+            configuration: json!({}),
+            ..create_test_blueprint().resources.remove(0)
+        });
+
+        let context = VariableContextBuilder::from_blueprint(&blueprint);
+        let first = blueprint.resources[0].name.clone();
+        assert_eq!(context.get(&format!("dependencies.{}", first)), Some(&json!(["cache"])));
+        assert_eq!(context.get("dependencies.cache"), Some(&json!([])));
+    }
+
     #[test]
     fn test_from_blueprint_with_no_description() {
         let cloud_provider = CloudProvider {
@@ -1183,6 +1637,8 @@ mod tests {
             name: "minimal-blueprint".to_string(),
             description: None,
             resources: vec![resource],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![cloud_provider],
         };
 
@@ -1218,6 +1674,8 @@ mod tests {
             name: "empty-blueprint".to_string(),
             description: Some("Blueprint with no resources".to_string()),
             resources: vec![],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![cloud_provider],
         };
 
@@ -1275,6 +1733,8 @@ mod tests {
             description: Some("Production environment stack".to_string()),
             cloud_name: "aws-us-east-1".to_string(),
             stack_type: "INFRASTRUCTURE_ONLY".to_string(),
+            revision: None,
+            updated_at: None,
             stack_resources: vec![stack_resource],
             blueprint: None,
         }
@@ -1405,6 +1865,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_from_stack_configuration_keys_with_dots() {
+        let mut stack = create_test_stack();
+        stack.stack_resources[0]
+            .configuration
+            .insert("app.port".to_string(), json!(8080));
+        let context = VariableContextBuilder::from_stack(&stack);
+
+        assert_eq!(
+            context.get("stack_resources[0].configuration.app.port"),
+            Some(&json!(8080))
+        );
+        assert!(context
+            .list_all()
+            .iter()
+            .any(|(k, _)| k == "stack_resources[0].configuration.app.port"));
+    }
+
     #[test]
     fn test_from_stack_with_multiple_stack_resources() {
         let cloud_provider = CloudProvider {
@@ -1449,6 +1927,8 @@ mod tests {
             description: None,
             cloud_name: "aws-us-east-1".to_string(),
             stack_type: "INFRASTRUCTURE_ONLY".to_string(),
+            revision: None,
+            updated_at: None,
             stack_resources: vec![db_resource, cache_resource],
             blueprint: None,
         };
@@ -1503,6 +1983,8 @@ mod tests {
             description: None,
             cloud_name: "aws-us-west-2".to_string(),
             stack_type: "INFRASTRUCTURE_ONLY".to_string(),
+            revision: None,
+            updated_at: None,
             stack_resources: vec![stack_resource],
             blueprint: None,
         };
@@ -1534,6 +2016,8 @@ mod tests {
             description: Some("Stack with no resources".to_string()),
             cloud_name: "aws-us-east-1".to_string(),
             stack_type: "INFRASTRUCTURE_ONLY".to_string(),
+            revision: None,
+            updated_at: None,
             stack_resources: vec![],
             blueprint: None,
         };
@@ -1595,6 +2079,8 @@ mod tests {
             name: "web-app-blueprint".to_string(),
             description: Some("Web application blueprint".to_string()),
             resources: vec![blueprint_resource],
+            revision: None,
+            updated_at: None,
             supported_cloud_providers: vec![cloud_provider],
         };
 
@@ -1604,6 +2090,8 @@ mod tests {
             description: Some("Production stack".to_string()),
             cloud_name: "aws-us-east-1".to_string(),
             stack_type: "INFRASTRUCTURE_ONLY".to_string(),
+            revision: None,
+            updated_at: None,
             stack_resources: vec![stack_resource],
             blueprint: Some(blueprint),
         };
@@ -1665,6 +2153,8 @@ mod tests {
             description: None,
             cloud_name: "aws-us-east-1".to_string(),
             stack_type: "WEB_APPLICATION".to_string(),
+            revision: None,
+            updated_at: None,
             stack_resources: vec![stack_resource],
             blueprint: None,
         };
@@ -1723,6 +2213,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(unused_imports, unused_mut)]
     fn test_merge_custom_variables_from_yaml() {
         use std::io::Write;
         use tempfile::NamedTempFile;
@@ -1917,6 +2408,7 @@ replicas: 5
     }
 
     #[test]
+    #[allow(unused_imports, unused_mut)]
     fn test_merge_custom_variables_invalid_yaml() {
         use std::io::Write;
         use tempfile::NamedTempFile;
@@ -1947,6 +2439,7 @@ invalid: yaml: content:
     }
 
     #[test]
+    #[allow(unused_imports, unused_mut)]
     fn test_merge_custom_variables_unsupported_extension() {
         use std::io::Write;
         use tempfile::NamedTempFile;
@@ -1970,6 +2463,56 @@ invalid: yaml: content:
         std::fs::remove_file(&temp_path).ok();
     }
 
+    #[test]
+    fn test_merge_custom_variables_from_tfvars() {
+        let dir = tempfile::tempdir().unwrap();
+        let tfvars = dir.path().join("prod.tfvars");
+        std::fs::write(
+            &tfvars,
+            r#"
+# Shared with the Terraform root module
+region        = "eu-west-1"
+instance_count = 2 + 1
+enabled       = true
+zones         = ["a", "b"]
+tags = {
+  Team          = "payments"
+  "cost-center" = 42
+}
+"#,
+        )
+        .unwrap();
+
+        let mut context = VariableContext::new();
+        context.insert("region".to_string(), json!("us-east-1"));
+        VariableContextBuilder::merge_custom_variables(&mut context, &tfvars).unwrap();
+
+        assert_eq!(context.get("region"), Some(&json!("eu-west-1")));
+        assert_eq!(context.get("instance_count"), Some(&json!(3)));
+        assert_eq!(context.get("enabled"), Some(&json!(true)));
+        assert_eq!(context.get("zones[1]"), Some(&json!("b")));
+        assert_eq!(context.get("tags.cost-center"), Some(&json!(42)));
+        assert_eq!(context.source("tags.Team"), Some(tfvars.display().to_string().as_str()));
+    }
+
+    #[test]
+    fn test_merge_custom_variables_rejects_non_constant_hcl() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("vars.hcl");
+        let merge = |content: &str| {
+            std::fs::write(&file, content).unwrap();
+            VariableContextBuilder::merge_custom_variables(&mut VariableContext::new(), &file)
+                .unwrap_err()
+                .to_string()
+        };
+
+        let error = merge("name = \"app-${var.environment}\"\n");
+        assert!(error.contains("'name'") && error.contains("must be a constant value"), "{}", error);
+        assert!(merge("region = upper(\"eu\")\n").contains("undefined function"));
+        assert!(merge("locals {\n  a = 1\n}\n").contains("declares a 'locals' block"));
+        assert!(merge("region = \n").contains("Failed to parse HCL"));
+    }
+
     #[test]
     fn test_merge_custom_variables_non_object_root() {
         use tempfile::NamedTempFile;
@@ -1995,6 +2538,7 @@ invalid: yaml: content:
     }
 
     #[test]
+    #[allow(unused_imports, unused_mut)]
     fn test_merge_custom_variables_yml_extension() {
         use std::io::Write;
         use tempfile::NamedTempFile;
@@ -2059,6 +2603,7 @@ number: 123
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_merge_custom_variables_preserves_types() {
         use tempfile::NamedTempFile;
 
@@ -2090,4 +2635,47 @@ number: 123
         // Cleanup
         std::fs::remove_file(&temp_path).ok();
     }
+
+    #[test]
+    fn test_merge_defaults_does_not_override() {
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("payments"));
+        let defaults = json!({"environment": "staging", "stack": {"name": "default"}, "tags": {"team": "platform"}});
+
+        VariableContextBuilder::merge_defaults(&mut context, defaults.as_object().unwrap());
+
+        assert_eq!(context.get("environment"), Some(&json!("staging")));
+        assert_eq!(context.get("stack.name"), Some(&json!("payments")));
+        assert_eq!(context.get("tags.team"), Some(&json!("platform")));
+        assert_eq!(context.source("environment"), Some(DEFAULTS_SOURCE));
+        assert_eq!(context.source("stack.name"), None);
+    }
+
+    #[test]
+    fn test_later_files_and_assignments_win_and_record_their_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let common = dir.path().join("common.yaml");
+        let prod = dir.path().join("prod.json");
+        std::fs::write(&common, "environment: staging\nreplicas: 2\ntags:\n  team: platform\n").unwrap();
+        std::fs::write(&prod, r#"{"environment": "production"}"#).unwrap();
+
+        let mut context = VariableContext::new();
+        context.insert("stack.name".to_string(), json!("payments"));
+        VariableContextBuilder::merge_custom_variables(&mut context, &common).unwrap();
+        VariableContextBuilder::merge_custom_variables(&mut context, &prod).unwrap();
+        VariableContextBuilder::merge_assignments(
+            &mut context,
+            &[("replicas".to_string(), "3".to_string()), ("tags.team".to_string(), "payments".to_string())],
+        )
+        .unwrap();
+
+        assert_eq!(context.get("environment"), Some(&json!("production")));
+        assert_eq!(context.source("environment"), Some(prod.display().to_string().as_str()));
+        assert_eq!(context.get("replicas"), Some(&json!("3")));
+        assert_eq!(context.source("replicas"), Some(VAR_FLAG_SOURCE));
+        assert_eq!(context.get("tags.team"), Some(&json!("payments")));
+        assert_eq!(context.get("tags"), Some(&json!({"team": "payments"})));
+        assert_eq!(context.source("tags.team"), Some(VAR_FLAG_SOURCE));
+        assert_eq!(context.source("stack.name"), None);
+    }
 }
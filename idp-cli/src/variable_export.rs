@@ -0,0 +1,208 @@
+use serde_json::{json, Map, Value};
+
+use crate::variable_context::{VariableContext, API_SOURCE};
+
+/// Export a variable catalog as a Markdown table
+///
+/// Produces one row per variable with its name, type and example value,
+/// suitable for pasting into documentation.
+pub fn to_markdown(context: &VariableContext) -> String {
+    let mut output = String::from("| Variable | Type | Example |\n|----------|------|---------|\n");
+
+    for (key, value) in context.list_all() {
+        output.push_str(&format!(
+            "| `{}` | {} | {} |\n",
+            key,
+            type_name(value),
+            escape_markdown(&example_value(value))
+        ));
+    }
+
+    output
+}
+
+/// Export a variable catalog as CSV with a `variable,type,example` header
+pub fn to_csv(context: &VariableContext) -> String {
+    let mut output = String::from("variable,type,example\n");
+
+    for (key, value) in context.list_all() {
+        output.push_str(&format!(
+            "{},{},{}\n",
+            escape_csv(&key),
+            type_name(value),
+            escape_csv(&example_value(value))
+        ));
+    }
+
+    output
+}
+
+/// Export a variable catalog as a JSON array of `variable`, `type`, `value` and `source` entries
+///
+/// Used by `list-variables --output json`; unlike the Markdown and CSV
+/// exports, values are kept whole instead of shortened to an example.
+/// `source` is the variables file, `--var` or config defaults that set the
+/// value, or `api` for values of the blueprint or stack.
+pub fn to_json(context: &VariableContext) -> Value {
+    Value::Array(
+        context
+            .list_all()
+            .into_iter()
+            .map(|(key, value)| {
+                let source = context.source(&key).unwrap_or(API_SOURCE);
+                json!({ "variable": key, "type": type_name(value), "value": value, "source": source })
+            })
+            .collect(),
+    )
+}
+
+/// Export the variable context as a JSON Schema document
+///
+/// The schema describes the nested structure templates see (e.g.
+/// `blueprint.name` becomes the `name` property of the `blueprint` object),
+/// so it can seed a `variables.schema.json` for validating custom variables.
+pub fn to_json_schema(context: &VariableContext, title: &str) -> Value {
+    // Only root keys: dotted and indexed keys are already represented in the
+    // nested objects produced by to_template_data
+    let data = context.to_template_data();
+    let roots: Map<String, Value> = data
+        .as_object()
+        .map(|obj| {
+            obj.iter()
+                .filter(|(k, _)| !k.contains('.') && !k.contains('['))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut schema = schema_for(&Value::Object(roots));
+    if let Value::Object(obj) = &mut schema {
+        obj.insert(
+            "$schema".to_string(),
+            json!("https://json-schema.org/draft/2020-12/schema"),
+        );
+        obj.insert("title".to_string(), json!(title));
+    }
+
+    schema
+}
+
+/// Build a schema fragment describing a single value
+fn schema_for(value: &Value) -> Value {
+    match value {
+        Value::Object(obj) => {
+            let properties: Map<String, Value> = obj
+                .iter()
+                .map(|(k, v)| (k.clone(), schema_for(v)))
+                .collect();
+            let mut required: Vec<&String> = obj.keys().collect();
+            required.sort();
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            })
+        }
+        Value::Array(items) => match items.first() {
+            Some(first) => json!({ "type": "array", "items": schema_for(first) }),
+            None => json!({ "type": "array" }),
+        },
+        Value::String(_) => json!({ "type": "string" }),
+        Value::Number(n) if n.is_i64() || n.is_u64() => json!({ "type": "integer" }),
+        Value::Number(_) => json!({ "type": "number" }),
+        Value::Bool(_) => json!({ "type": "boolean" }),
+        Value::Null => json!({ "type": "null" }),
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Short example value: primitives verbatim, containers summarized
+fn example_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(arr) => format!("[{} element(s)]", arr.len()),
+        Value::Object(obj) => format!("{{{} properties}}", obj.len()),
+        other => other.to_string(),
+    }
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn escape_csv(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_test_context() -> VariableContext {
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("web|app"));
+        context.insert("blueprint.description".to_string(), json!("A, \"quoted\" app"));
+        context.insert("resources".to_string(), json!([{"name": "db", "port": 5432}]));
+        context.insert("resources[0].name".to_string(), json!("db"));
+        context
+    }
+
+    #[test]
+    fn test_to_markdown_escapes_pipes() {
+        let output = to_markdown(&create_test_context());
+        assert!(output.starts_with("| Variable | Type | Example |"));
+        assert!(output.contains("| `blueprint.name` | string | web\\|app |"));
+        assert!(output.contains("| `resources` | array | [1 element(s)] |"));
+    }
+
+    #[test]
+    fn test_to_csv_quotes_special_characters() {
+        let output = to_csv(&create_test_context());
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "variable,type,example");
+        assert_eq!(lines[1], "blueprint.description,string,\"A, \"\"quoted\"\" app\"");
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn test_to_json_keeps_types_and_values() {
+        let output = to_json(&create_test_context());
+        let entries = output.as_array().unwrap();
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[0],
+            json!({"variable": "blueprint.description", "type": "string", "value": "A, \"quoted\" app", "source": "api"})
+        );
+        let resources = entries.iter().find(|e| e["variable"] == "resources").unwrap();
+        assert_eq!(resources["type"], "array");
+        assert_eq!(resources["value"][0]["port"], 5432);
+    }
+
+    #[test]
+    fn test_to_json_schema_describes_nested_structure() {
+        let schema = to_json_schema(&create_test_context(), "web-app variables");
+        assert_eq!(schema["title"], "web-app variables");
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"]["blueprint"]["properties"]["name"]["type"], "string");
+        assert_eq!(schema["properties"]["resources"]["type"], "array");
+        assert_eq!(
+            schema["properties"]["resources"]["items"]["properties"]["port"]["type"],
+            "integer"
+        );
+        assert!(schema["properties"].get("blueprint.name").is_none());
+    }
+}
@@ -0,0 +1,236 @@
+use std::path::Path;
+
+use handlebars::template::Template;
+use serde::Serialize;
+
+use crate::error::CliError;
+use crate::template_discovery::TemplateFile;
+use crate::template_lint::{LintIssue, LintKind, TemplateLinter};
+use crate::template_processor::{for_each_list, split_each};
+use crate::var_trace::{segments, TagVisitor, TemplateWalker, VisitedTag};
+use crate::variable_context::VariableContext;
+
+/// Unused variables listed by name in the human-readable report
+const UNUSED_SHOWN: usize = 10;
+
+/// Context variables no template references, and references the context doesn't define
+///
+/// Built from the template sources rather than the rendered output, so a
+/// variable referenced only in a branch that didn't render still counts as
+/// used.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VariableUsage {
+    /// Number of variables in the context
+    pub variables: usize,
+    /// Variables no template references, sorted by name
+    pub unused: Vec<String>,
+    /// References to variables the context does not define, which render as empty strings
+    pub undefined: Vec<LintIssue>,
+}
+
+impl VariableUsage {
+    /// Compare the variables of a context with the references in templates
+    ///
+    /// `custom_helpers` are the helpers loaded for the template directory.
+    pub fn build(
+        context: &VariableContext,
+        templates: &[TemplateFile],
+        custom_helpers: &[&str],
+    ) -> Result<Self, CliError> {
+        // References guarded by {{#if}} or {{default}} are not reported, as with lint-templates
+        let undefined = TemplateLinter::new(context.to_template_data(), custom_helpers)
+            .lint(templates)?
+            .issues
+            .into_iter()
+            .filter(|issue| issue.kind == LintKind::UndefinedVariable)
+            .collect();
+
+        let mut references = Vec::new();
+        for template in templates {
+            let content = std::fs::read_to_string(&template.path).map_err(|e| {
+                CliError::IoError(format!("Failed to read template {}: {}", template.path.display(), e))
+            })?;
+            references.extend(template_references(&template.relative_path, &content));
+        }
+        let unused = context
+            .list_all()
+            .into_iter()
+            .map(|(name, _)| name)
+            .filter(|name| {
+                let variable = segments(name);
+                !references.iter().any(|reference| reference.uses(&variable))
+            })
+            .collect();
+
+        Ok(Self { variables: context.len(), unused, undefined })
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.unused.is_empty() && self.undefined.is_empty()
+    }
+
+    /// Human-readable report
+    pub fn render_human(&self) -> String {
+        let mut lines = vec![format!(
+            "Variables: {} of {} referenced by templates",
+            self.variables - self.unused.len(),
+            self.variables
+        )];
+        if !self.unused.is_empty() {
+            let shown: Vec<&str> = self.unused.iter().take(UNUSED_SHOWN).map(String::as_str).collect();
+            let mut line = format!("Unused ({}): {}", self.unused.len(), shown.join(", "));
+            if self.unused.len() > shown.len() {
+                line.push_str(&format!(" and {} more", self.unused.len() - shown.len()));
+            }
+            lines.push(line);
+        }
+        if !self.undefined.is_empty() {
+            lines.push(format!("Undefined, rendered as empty strings ({}):", self.undefined.len()));
+            for issue in &self.undefined {
+                lines.push(format!("  {}:{}: {}", issue.file.display(), issue.line, issue.message));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// A variable path referenced by a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Reference {
+    /// Absolute path segments; `*` stands for any array index
+    path: Vec<String>,
+    /// Whether the whole value is used, e.g. `{{json resources}}`, rather than
+    /// only checked or iterated over, as by `{{#each resources}}`
+    whole: bool,
+}
+
+impl Reference {
+    /// Whether the reference uses a variable given as path segments
+    ///
+    /// A reference uses the variable itself and the objects containing it,
+    /// and the fields inside it if it uses the whole value.
+    fn uses(&self, variable: &[String]) -> bool {
+        if self.path.len() < variable.len() && !self.whole {
+            return false;
+        }
+        self.path
+            .iter()
+            .zip(variable)
+            .all(|(reference, segment)| reference == segment || (reference == "*" && segment.parse::<usize>().is_ok()))
+    }
+}
+
+/// References in a template's path and content, resolved the way the template is rendered
+fn template_references(relative_path: &Path, content: &str) -> Vec<Reference> {
+    let mut path = relative_path.to_string_lossy().replace('\\', "/");
+    let mut item = None;
+    let content = match (for_each_list(relative_path), split_each(&path)) {
+        // Rendered inside the list's items
+        (Some(list), _) => format!("{{{{#each {}}}}}{}{{{{/each}}}}", list, content),
+        // Rendered once per item of the list in the path, as `item`
+        (None, Some((before, list, after))) => {
+            let mut prefix = segments(list);
+            prefix.push("*".to_string());
+            item = Some(prefix);
+            // The block in a path ends with the path
+            path = format!("{}{{{{#each {}}}}}{}{{{{/each}}}}", before, list, after);
+            content.to_string()
+        }
+        (None, None) => content.to_string(),
+    };
+
+    let mut collector = ReferenceCollector { references: Vec::new() };
+    for source in [path.as_str(), content.as_str()] {
+        // Templates that don't parse fail to render, so there is nothing to report
+        if let Ok(template) = Template::compile(source) {
+            let mut walker = TemplateWalker::new(source);
+            if let Some(item) = &item {
+                walker = walker.with_local("item", item.clone());
+            }
+            walker.walk(&template, &mut collector);
+        }
+    }
+    collector.references
+}
+
+struct ReferenceCollector {
+    references: Vec<Reference>,
+}
+
+impl TagVisitor for ReferenceCollector {
+    fn visit(&mut self, tag: &VisitedTag) {
+        let whole = !(tag.helper.block && matches!(tag.name(), Some("each" | "with" | "if" | "unless")));
+        for reference in &tag.references {
+            if let Some(path) = &reference.path {
+                self.references.push(Reference { path: path.clone(), whole });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::template_discovery::TemplateFileType;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn build(templates: &[(&str, &str)]) -> VariableUsage {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<TemplateFile> = templates
+            .iter()
+            .map(|(name, content)| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, content).unwrap();
+                TemplateFile { path, relative_path: PathBuf::from(name), file_type: TemplateFileType::Terraform }
+            })
+            .collect();
+
+        let mut context = VariableContext::new();
+        context.insert("blueprint.name".to_string(), json!("web-app"));
+        context.insert("blueprint.description".to_string(), json!("Web application"));
+        context.insert("region".to_string(), json!("eu-west-1"));
+        context.insert(
+            "resources".to_string(),
+            json!([{"name": "db", "configuration": {"engine": "postgres"}}, {"name": "cache"}]),
+        );
+        context.insert("resources[0].name".to_string(), json!("db"));
+        context.insert("resources[0].configuration".to_string(), json!({"engine": "postgres"}));
+        context.insert("resources[1].name".to_string(), json!("cache"));
+        VariableUsage::build(&context, &files, &[]).unwrap()
+    }
+
+    #[test]
+    fn test_reports_unused_and_undefined_variables() {
+        let usage = build(&[(
+            "main.tf",
+            "# {{blueprint.name}}\n{{#each resources}}\n# {{name}} {{configuration.engine}}\n{{/each}}\n# {{blueprnt.owner}}\n{{#if tags}}{{tags.team}}{{/if}}\n",
+        )]);
+        assert_eq!(usage.variables, 7);
+        assert_eq!(usage.unused, vec!["blueprint.description", "region"]);
+        let undefined: Vec<_> = usage.undefined.iter().map(|issue| (issue.line, issue.message.as_str())).collect();
+        assert_eq!(undefined, vec![(5, "'blueprnt.owner' is not defined")]);
+        assert!(usage.render_human().contains("Variables: 5 of 7 referenced by templates"), "{}", usage.render_human());
+    }
+
+    #[test]
+    fn test_whole_values_use_their_fields() {
+        // Iterating over a list only uses the fields referenced inside the block
+        let usage = build(&[("main.tf", "{{#each resources as |resource|}}{{resource.name}}{{/each}}")]);
+        assert!(usage.unused.contains(&"resources[0].configuration".to_string()), "{:?}", usage.unused);
+        assert!(!usage.unused.contains(&"resources[1].name".to_string()), "{:?}", usage.unused);
+
+        // Passing a whole value to a helper uses all of it
+        let usage = build(&[("main.tf", "{{trim region}}{{json blueprint}}{{#with resources}}{{length}}{{/with}}")]);
+        assert_eq!(usage.unused, vec!["resources[0].configuration", "resources[0].name", "resources[1].name"]);
+    }
+
+    #[test]
+    fn test_template_paths_reference_variables() {
+        let usage = build(&[
+            ("{{#each resources}}{{name}}.tf", "# {{item.configuration.engine}} {{region}}"),
+            ("resources.each.tf", "# {{name}}"),
+        ]);
+        assert_eq!(usage.unused, vec!["blueprint.description", "blueprint.name"]);
+    }
+}
@@ -0,0 +1,41 @@
+# PostgreSQL in the private subnets, reachable only from the service. The
+# master password is generated and kept in Secrets Manager by RDS.
+
+resource "aws_db_subnet_group" "main" {
+  name       = var.name
+  subnet_ids = aws_subnet.private[*].id
+}
+
+resource "aws_security_group" "db" {
+  name   = "${var.name}-db"
+  vpc_id = aws_vpc.main.id
+
+  ingress {
+    from_port       = 5432
+    to_port         = 5432
+    protocol        = "tcp"
+{{#if (eq (default compute "ecs") "eks")}}
+    security_groups = [aws_eks_cluster.main.vpc_config[0].cluster_security_group_id]
+{{else}}
+    security_groups = [aws_security_group.service.id]
+{{/if}}
+  }
+}
+
+resource "aws_db_instance" "main" {
+  identifier                  = var.name
+  engine                      = "postgres"
+  engine_version              = var.db_engine_version
+  instance_class              = var.db_instance_class
+  allocated_storage           = 20
+  max_allocated_storage       = 100
+  storage_encrypted           = true
+  db_name                     = replace(var.name, "-", "_")
+  username                    = "app"
+  manage_master_user_password = true
+  db_subnet_group_name        = aws_db_subnet_group.main.name
+  vpc_security_group_ids      = [aws_security_group.db.id]
+  backup_retention_period     = 7
+  deletion_protection         = true
+  final_snapshot_identifier   = "${var.name}-final"
+}
@@ -0,0 +1,34 @@
+output "vpc_id" {
+  value = aws_vpc.main.id
+}
+{{#if (eq (default compute "ecs") "eks")}}
+
+output "cluster_name" {
+  value = aws_eks_cluster.main.name
+}
+
+output "cluster_endpoint" {
+  value = aws_eks_cluster.main.endpoint
+}
+{{else}}
+
+output "cluster_name" {
+  value = aws_ecs_cluster.main.name
+}
+
+output "service_url" {
+  value = "http://${aws_lb.main.dns_name}"
+}
+{{/if}}
+
+output "database_endpoint" {
+  value = aws_db_instance.main.endpoint
+}
+
+output "database_secret_arn" {
+  value = aws_db_instance.main.master_user_secret[0].secret_arn
+}
+
+output "assets_bucket" {
+  value = aws_s3_bucket.assets.bucket
+}
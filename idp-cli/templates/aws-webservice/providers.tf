@@ -0,0 +1,24 @@
+# {{service_name}}: AWS web service
+# Generated by idp-cli from the built-in aws-webservice template pack
+
+terraform {
+  required_version = ">= 1.5"
+
+  required_providers {
+    aws = {
+      source  = "hashicorp/aws"
+      version = "~> 5.0"
+    }
+  }
+}
+
+provider "aws" {
+  region = var.region
+
+  default_tags {
+    tags = {
+      Service   = var.name
+      ManagedBy = "idp-cli"
+    }
+  }
+}
@@ -0,0 +1,55 @@
+variable "name" {
+  description = "Name of the service, used to name and tag its resources"
+  type        = string
+  default     = "{{service_name}}"
+}
+
+variable "region" {
+  description = "AWS region to deploy to"
+  type        = string
+  default     = "{{default region "us-east-1"}}"
+}
+
+variable "vpc_cidr" {
+  description = "CIDR block of the VPC"
+  type        = string
+  default     = "{{default vpc_cidr "10.0.0.0/16"}}"
+}
+
+variable "container_image" {
+  description = "Container image of the service"
+  type        = string
+  default     = "{{default container_image "public.ecr.aws/nginx/nginx:stable"}}"
+}
+
+variable "container_port" {
+  description = "Port the container listens on"
+  type        = number
+  default     = {{default container_port "80"}}
+}
+
+variable "desired_count" {
+  description = "Number of running tasks or nodes"
+  type        = number
+  default     = {{default desired_count "2"}}
+}
+
+variable "db_instance_class" {
+  description = "Instance class of the database"
+  type        = string
+  default     = "{{instance_size (default size "small") cloud="aws" kind="database"}}"
+}
+
+variable "db_engine_version" {
+  description = "PostgreSQL major version"
+  type        = string
+  default     = "{{default db_engine_version "16"}}"
+}
+{{#if (eq (default compute "ecs") "eks")}}
+
+variable "node_instance_type" {
+  description = "Instance type of the EKS worker nodes"
+  type        = string
+  default     = "{{instance_size (default size "small") cloud="aws" kind="compute"}}"
+}
+{{/if}}
@@ -46,6 +46,7 @@ fn run_cli_generate(
 }
 
 /// Helper to run list-variables command
+#[allow(clippy::needless_borrows_for_generic_args)]
 fn run_cli_list_variables(
     data_source: &str,
     identifier: &str,
@@ -95,6 +96,8 @@ fn create_mock_blueprint() -> Blueprint {
                 cloud_specific_properties,
             }
         ],
+        revision: None,
+        updated_at: None,
         supported_cloud_providers: vec![],
     }
 }
@@ -112,6 +115,8 @@ fn create_mock_stack() -> Stack {
         description: Some("Production web application".to_string()),
         cloud_name: "aws-us-east-1".to_string(),
         stack_type: "RestfulApi".to_string(),
+        revision: None,
+        updated_at: None,
         stack_resources: vec![
             StackResource {
                 id: Uuid::new_v4(),
@@ -655,6 +660,8 @@ resource "aws_resource" "{{this.name}}" {
                 cloud_specific_properties: cloud_props2,
             }
         ],
+        revision: None,
+        updated_at: None,
         supported_cloud_providers: vec![],
     };
     
@@ -1,9 +1,10 @@
 #[cfg(feature = "lambda")]
 mod lambda_tests {
-    use lambda_http::{Body, Request};
+    use lambda_http::{http, Body};
     use serde_json::json;
 
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn test_lambda_handler_compiles() {
         // This test verifies that the lambda handler module compiles correctly
         // and has the expected function signature
@@ -18,7 +19,7 @@ mod lambda_tests {
             "identifier": "test-blueprint"
         });
         
-        let request = Request::builder()
+        let request = http::Request::builder()
             .method("POST")
             .uri("/")
             .header("content-type", "application/json")
@@ -82,6 +83,7 @@ mod lambda_tests {
 #[cfg(not(feature = "lambda"))]
 mod no_lambda_tests {
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn test_lambda_feature_not_enabled() {
         // When lambda feature is not enabled, this test passes
         // to indicate that lambda functionality is optional